@@ -1,11 +1,20 @@
-use gpui::{Menu, MenuItem, actions};
+use daw_core::RecentProject;
+use gpui::{Action, Menu, MenuItem, actions};
+use std::path::PathBuf;
 
 actions!(
     daw,
     [OpenProject, SaveProject, SaveProjectAs, RenderProject]
 );
 
-pub fn app_menus() -> Vec<Menu> {
+/// Open a project from the File > Open Recent menu
+#[derive(Clone, PartialEq, Debug, Action)]
+#[action(namespace = daw, no_json)]
+pub struct OpenRecentProject {
+    pub path: PathBuf,
+}
+
+pub fn app_menus(recent: &[RecentProject]) -> Vec<Menu> {
     vec![
         Menu {
             name: "Cedar".into(),
@@ -15,6 +24,20 @@ pub fn app_menus() -> Vec<Menu> {
             name: "File".into(),
             items: vec![
                 MenuItem::action("Open Project...", OpenProject),
+                MenuItem::submenu(Menu {
+                    name: "Open Recent".into(),
+                    items: recent
+                        .iter()
+                        .map(|project| {
+                            MenuItem::action(
+                                project.name.clone(),
+                                OpenRecentProject {
+                                    path: project.path.clone(),
+                                },
+                            )
+                        })
+                        .collect(),
+                }),
                 MenuItem::separator(),
                 MenuItem::action("Save", SaveProject),
                 MenuItem::action("Save As...", SaveProjectAs),
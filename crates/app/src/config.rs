@@ -1,3 +1,4 @@
+use daw_core::{Dither, RenderFormat, RenderTail, SaveOptions};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
@@ -7,6 +8,24 @@ use std::path::PathBuf;
 pub struct Config {
     #[serde(default)]
     pub picker_directories: HashMap<String, PathBuf>,
+    /// Open finished renders in the default player instead of asking
+    #[serde(default)]
+    pub open_after_render: bool,
+    /// Sample format renders are written in
+    #[serde(default)]
+    pub render_format: RenderFormat,
+    /// How renders in integer formats are rounded
+    #[serde(default)]
+    pub render_dither: Dither,
+    /// Time rendered after the last clip, so decays ring out
+    #[serde(default)]
+    pub render_tail: Option<RenderTail>,
+    /// Mix the metronome's click into renders
+    #[serde(default)]
+    pub render_metronome: bool,
+    /// Format and compression projects are saved with
+    #[serde(default)]
+    pub save_options: SaveOptions,
 }
 
 impl Config {
@@ -1,11 +1,12 @@
 use gpui::KeyBinding;
 
 use crate::app_menus::{OpenProject, RenderProject, SaveProject, SaveProjectAs};
-use crate::{PlayPause, Quit};
+use crate::{Panic, PlayPause, Quit};
 
 pub fn keybindings() -> Vec<KeyBinding> {
     vec![
         KeyBinding::new("space", PlayPause, None),
+        KeyBinding::new("escape escape", Panic, None),
         KeyBinding::new("cmd-q", Quit, None),
         KeyBinding::new("cmd-o", OpenProject, None),
         KeyBinding::new("cmd-s", SaveProject, None),
@@ -1,15 +1,21 @@
 mod app_menus;
 mod config;
 mod keybindings;
+#[cfg(feature = "vst3")]
+mod plugins;
 mod theme;
 mod ui;
 
-use app_menus::{OpenProject, RenderProject, SaveProject, SaveProjectAs, app_menus};
+use app_menus::{
+    OpenProject, OpenRecentProject, RenderProject, SaveProject, SaveProjectAs, app_menus,
+};
 use config::Config;
-use daw_core::{PPQN, Session};
+#[cfg(feature = "vst3")]
+use daw_core::Vst3Info;
+use daw_core::{MasterSettings, PPQN, RecentProjects, RenderOptions, SaveAsSamples, Session};
 use gpui::{
-    App, Application, Context, Entity, FocusHandle, Timer, Window, WindowOptions, actions, div,
-    prelude::*, px,
+    App, Application, Context, Entity, FocusHandle, Menu, Timer, Window, WindowOptions, actions,
+    div, prelude::*, px,
 };
 use keybindings::keybindings;
 use std::path::{Path, PathBuf};
@@ -62,7 +68,11 @@ struct Daw {
     selected_clips: Vec<ClipId>,
     last_tick: Option<u64>,
     config: Config,
+    recent_projects: RecentProjects,
     scroll_handle: gpui::ScrollHandle,
+    /// Installed plugins, as listed in the Plugins menu
+    #[cfg(feature = "vst3")]
+    plugins: Vec<Vst3Info>,
 }
 
 impl Daw {
@@ -71,18 +81,34 @@ impl Daw {
     }
 
     fn from_path(path: &Path, cx: &mut Context<Self>) -> Self {
-        let session = Session::from_project(path).expect("Failed to load project");
+        let config = Config::load();
+        let mut session = Session::from_project(path).expect("Failed to load project");
+        session.set_save_options(config.save_options);
+
+        // Projects that were moved or deleted since drop off the menu
+        let mut recent_projects = RecentProjects::load();
+        recent_projects.prune();
+        recent_projects.add(path, session.name());
+        if let Err(e) = recent_projects.save() {
+            eprintln!("Failed to save recent projects: {}", e);
+        }
+        cx.set_menus(app_menus(recent_projects.list()));
 
         let time_signature = session.time_signature();
         let tempo = session.tempo();
 
+        let metronome_enabled = session.metronome_enabled();
         let header = cx.new(|cx| {
-            Header::new(
+            let mut header = Header::new(
                 tempo,
                 time_signature.numerator,
                 time_signature.denominator,
                 cx,
-            )
+            );
+            header.metronome_enabled = metronome_enabled;
+            header.set_project_info(session.info(), cx);
+            header.set_master(session.master_bus(), cx);
+            header
         });
         cx.subscribe(
             &header,
@@ -91,6 +117,7 @@ impl Daw {
                 HeaderEvent::Pause => this.pause(&header, cx),
                 HeaderEvent::Stop => this.stop(&header, cx),
                 HeaderEvent::ToggleMetronome => this.toggle_metronome(&header, cx),
+                HeaderEvent::SetMaster(settings) => this.set_master(*settings, &header, cx),
             },
         )
         .detach();
@@ -98,7 +125,9 @@ impl Daw {
         // UI-owned zoom state (not from core)
         let pixels_per_beat = DEFAULT_PIXELS_PER_BEAT;
         let playhead = cx.new(|_| Playhead::new(0, pixels_per_beat));
-        let cursor = cx.new(|_| Cursor::new(Some(0), pixels_per_beat)); // Initialize at tick 0
+        // Cursor starts where the project was saved with it
+        let cursor_tick = session.cursor_tick();
+        let cursor = cx.new(|_| Cursor::new(cursor_tick, pixels_per_beat));
 
         let tracks = session.tracks().to_vec();
         let track_labels = cx.new(|_| TrackLabels::new(tracks.clone()));
@@ -110,8 +139,11 @@ impl Daw {
                     this.update_track_labels(cx);
                     cx.notify();
                 }
-                TrackLabelsEvent::SoloExclusive(track_id) => {
-                    this.session.solo_track_exclusive(*track_id);
+                TrackLabelsEvent::Solo {
+                    track_id,
+                    alternate,
+                } => {
+                    this.session.solo_track(*track_id, *alternate);
                     this.update_track_labels(cx);
                     cx.notify();
                 }
@@ -172,6 +204,8 @@ impl Daw {
         }
 
         let focus_handle = cx.focus_handle();
+        #[cfg(feature = "vst3")]
+        Self::scan_plugins(cx);
 
         Self {
             session,
@@ -184,11 +218,33 @@ impl Daw {
             project_path: path.to_path_buf(),
             selected_clips: Vec::new(),
             last_tick: None,
-            config: Config::load(),
+            config,
+            recent_projects,
             scroll_handle: gpui::ScrollHandle::new(),
+            #[cfg(feature = "vst3")]
+            plugins: Vec::new(),
         }
     }
 
+    /// Put the open project at the top of File > Open Recent
+    fn remember_project(&mut self, cx: &mut Context<Self>) {
+        self.recent_projects
+            .add(&self.project_path, self.session.name());
+        if let Err(e) = self.recent_projects.save() {
+            eprintln!("Failed to save recent projects: {}", e);
+        }
+        cx.set_menus(self.menus());
+    }
+
+    /// The menu bar, with the recent projects and any plugins found
+    fn menus(&self) -> Vec<Menu> {
+        #[cfg_attr(not(feature = "vst3"), allow(unused_mut))]
+        let mut menus = app_menus(self.recent_projects.list());
+        #[cfg(feature = "vst3")]
+        menus.push(plugins::plugin_menu(&self.plugins));
+        menus
+    }
+
     fn load_project(&mut self, path: PathBuf, cx: &mut Context<Self>) {
         // If same project, do nothing
         if path == self.project_path {
@@ -197,20 +253,28 @@ impl Daw {
 
         // Load new session
         match Session::from_project(&path) {
-            Ok(session) => {
+            Ok(mut session) => {
+                session.set_save_options(self.config.save_options);
+
                 // Get new project settings
                 let time_signature = session.time_signature();
                 let tempo = session.tempo();
+                let cursor_tick = session.cursor_tick();
+                let metronome_enabled = session.metronome_enabled();
 
                 // Update session and project state
                 self.session = session;
                 self.project_path = path;
                 self.selected_clips.clear();
+                self.remember_project(cx);
 
                 // Update header with new values
                 self.header_handle.update(cx, |header, cx| {
                     header.set_tick(0, cx);
                     header.set_playing(false, cx);
+                    header.set_metronome_enabled(metronome_enabled, cx);
+                    header.set_project_info(self.session.info(), cx);
+                    header.set_master(self.session.master_bus(), cx);
                     header.update_values(
                         tempo,
                         time_signature.numerator,
@@ -224,9 +288,9 @@ impl Daw {
                     cx.notify();
                 });
 
-                // Reset cursor to beginning
+                // Put the cursor where the project was saved with it
                 self.cursor_handle.update(cx, |cursor, cx| {
-                    cursor.set_tick(Some(0));
+                    cursor.set_tick(cursor_tick);
                     cx.notify();
                 });
 
@@ -240,6 +304,10 @@ impl Daw {
             }
             Err(e) => {
                 eprintln!("Failed to load project: {}", e);
+                // Don't offer it again from the recent menu
+                self.recent_projects.remove(&path);
+                let _ = self.recent_projects.save();
+                cx.set_menus(self.menus());
             }
         }
     }
@@ -480,8 +548,10 @@ impl Daw {
                 self.last_tick = Some(tick);
 
                 // Batch updates: update entities without individual notifications
+                let peak = self.session.master_peak();
                 self.header_handle.update(cx, |header, cx| {
                     header.set_tick_silent(tick, cx);
+                    header.set_master_peak_silent(peak, cx);
                 });
                 self.playhead_handle.update(cx, |playhead, _cx| {
                     playhead.set_tick(tick);
@@ -555,6 +625,18 @@ impl Daw {
         let enabled = self.session.metronome_enabled();
         header.update(cx, |header, cx| header.set_metronome_enabled(enabled, cx));
     }
+
+    fn set_master(
+        &mut self,
+        settings: MasterSettings,
+        header: &Entity<Header>,
+        cx: &mut Context<Self>,
+    ) {
+        self.session.set_master_bus(settings);
+        // The session keeps the balance in range
+        let settings = self.session.master_bus();
+        header.update(cx, |header, cx| header.set_master(settings, cx));
+    }
 }
 
 impl Render for Daw {
@@ -572,7 +654,7 @@ impl Render for Daw {
         let ruler =
             cx.new(|_| TimelineRuler::new(pixels_per_beat, time_signature.into(), timeline_width));
 
-        div()
+        let root = div()
             .id("root")
             .size_full()
             .bg(theme.background)
@@ -591,6 +673,10 @@ impl Render for Daw {
                     }
                 });
             }))
+            .on_action(cx.listener(|this, _: &Panic, _, cx| {
+                this.session.panic();
+                cx.notify();
+            }))
             .on_action(cx.listener(|this, _: &OpenProject, _, cx| {
                 let start_dir = this.config.picker_directories.get("open_project").cloned();
                 cx.spawn(
@@ -622,6 +708,9 @@ impl Render for Daw {
                 )
                 .detach();
             }))
+            .on_action(cx.listener(|this, action: &OpenRecentProject, _, cx| {
+                this.load_project(action.path.clone(), cx);
+            }))
             .on_action(cx.listener(|this, _: &SaveProject, _, _cx| {
                 if let Err(e) = this.session.save_in_place() {
                     eprintln!("Failed to save project: {}", e);
@@ -650,7 +739,7 @@ impl Render for Daw {
                         if let Some(file) = file {
                             let path = file.path().to_path_buf();
                             let _ = cx.update(|cx| {
-                                this.update(cx, |daw, _cx| {
+                                this.update(cx, |daw, cx| {
                                     if let Some(parent) = path.parent() {
                                         daw.config.picker_directories.insert(
                                             "save_project".to_string(),
@@ -658,10 +747,15 @@ impl Render for Daw {
                                         );
                                         daw.config.save();
                                     }
-                                    if let Err(e) = daw.session.save(&path) {
-                                        eprintln!("Failed to save project: {}", e);
-                                    } else {
-                                        daw.project_path = path;
+                                    match daw.session.save_as(&path, SaveAsSamples::Reference) {
+                                        Ok(report) => {
+                                            for name in report.missing {
+                                                eprintln!("Sample for clip '{}' not found", name);
+                                            }
+                                            daw.project_path = path;
+                                            daw.remember_project(cx);
+                                        }
+                                        Err(e) => eprintln!("Failed to save project: {}", e),
                                     }
                                 })
                             });
@@ -683,29 +777,81 @@ impl Render for Daw {
                     async move |this: gpui::WeakEntity<Self>, cx: &mut gpui::AsyncApp| {
                         let mut dialog = rfd::AsyncFileDialog::new()
                             .add_filter("WAV Audio", &["wav"])
-                            .set_title("Render to WAV")
+                            .add_filter("FLAC Audio", &["flac"])
+                            .set_title("Render")
                             .set_file_name(&default_name);
                         if let Some(dir) = start_dir {
                             dialog = dialog.set_directory(&dir);
                         }
                         let file = dialog.save_file().await;
 
-                        if let Some(file) = file {
-                            let path = file.path().to_path_buf();
-                            let _ = cx.update(|cx| {
-                                this.update(cx, |daw, _cx| {
-                                    if let Some(parent) = path.parent() {
-                                        daw.config
-                                            .picker_directories
-                                            .insert("render".to_string(), parent.to_path_buf());
-                                        daw.config.save();
-                                    }
-                                    if let Err(e) = daw.session.render_to_file(&path) {
+                        let Some(file) = file else {
+                            return;
+                        };
+                        let path = file.path().to_path_buf();
+                        let rendered = cx.update(|cx| {
+                            this.update(cx, |daw, _cx| {
+                                if let Some(parent) = path.parent() {
+                                    daw.config
+                                        .picker_directories
+                                        .insert("render".to_string(), parent.to_path_buf());
+                                    daw.config.save();
+                                }
+                                let options = RenderOptions {
+                                    format: daw.config.render_format,
+                                    dither: daw.config.render_dither,
+                                    tail: daw.config.render_tail,
+                                    metronome: daw.config.render_metronome,
+                                    ..Default::default()
+                                };
+                                match daw.session.render_to_file_with(&path, &options) {
+                                    Ok(summary) => Some((summary, daw.config.open_after_render)),
+                                    Err(e) => {
                                         eprintln!("Failed to render: {}", e);
+                                        None
                                     }
-                                })
-                            });
+                                }
+                            })
+                        });
+                        let Ok(Ok(Some((summary, open_after_render)))) = rendered else {
+                            return;
+                        };
+
+                        if open_after_render {
+                            let _ = cx.update(|cx| cx.open_with_system(&summary.path));
+                            return;
                         }
+                        let choice = rfd::AsyncMessageDialog::new()
+                            .set_title("Render complete")
+                            .set_description(format!(
+                                "{}\n\n{:.1}s, peak {:.1} dBFS ({:.1} dBTP), {:.1} LUFS{}",
+                                summary.path.display(),
+                                summary.duration.as_secs_f64(),
+                                summary.peak_db,
+                                summary.true_peak_db,
+                                summary.loudness_lufs,
+                                if summary.clipped_samples > 0 {
+                                    format!("\n\n{} samples clipped", summary.clipped_samples)
+                                } else {
+                                    String::new()
+                                }
+                            ))
+                            .set_buttons(rfd::MessageButtons::YesNoCancelCustom(
+                                "Reveal".to_string(),
+                                "Open".to_string(),
+                                "Close".to_string(),
+                            ))
+                            .show()
+                            .await;
+                        let _ = cx.update(|cx| match choice {
+                            rfd::MessageDialogResult::Custom(label) if label == "Reveal" => {
+                                cx.reveal_path(&summary.path)
+                            }
+                            rfd::MessageDialogResult::Custom(label) if label == "Open" => {
+                                cx.open_with_system(&summary.path)
+                            }
+                            _ => {}
+                        });
                     },
                 )
                 .detach();
@@ -766,18 +912,23 @@ impl Render for Daw {
                             )
                             .child(self.track_labels_handle.clone()),
                     ),
-            )
+            );
+        #[cfg(feature = "vst3")]
+        let root = root.on_action(cx.listener(|this, action: &plugins::AddPlugin, _, cx| {
+            this.add_plugin(action.index, cx);
+        }));
+        root
     }
 }
 
-actions!(daw, [PlayPause, Quit]);
+actions!(daw, [PlayPause, Panic, Quit]);
 
 fn main() {
     Application::new().run(|cx: &mut App| {
         theme::init(cx);
 
         // Set up menus
-        cx.set_menus(app_menus());
+        cx.set_menus(app_menus(RecentProjects::load().list()));
 
         // Set up actions
         cx.on_action(|_: &Quit, cx: &mut App| {
@@ -0,0 +1,146 @@
+//! VST3 plugins on tracks, with the `vst3` feature: a Plugins menu of the
+//! installed plugins, and their editors embedded in windows of their own.
+
+use crate::Daw;
+use daw_core::{EditorParent, Session, Vst3Info};
+use gpui::{
+    Action, Context, Menu, MenuItem, TitlebarOptions, Window, WindowOptions, div, prelude::*, px,
+    size,
+};
+
+/// Add the plugin at `index` in the Plugins menu to the selected clip's track
+#[derive(Clone, PartialEq, Debug, Action)]
+#[action(namespace = daw, no_json)]
+pub struct AddPlugin {
+    pub index: usize,
+}
+
+pub fn plugin_menu(plugins: &[Vst3Info]) -> Menu {
+    Menu {
+        name: "Plugins".into(),
+        items: plugins
+            .iter()
+            .enumerate()
+            .map(|(index, info)| {
+                MenuItem::action(
+                    format!("{} ({})", info.name, info.vendor),
+                    AddPlugin { index },
+                )
+            })
+            .collect(),
+    }
+}
+
+/// Root view of an editor window, which the plugin draws over
+struct PluginEditorView;
+
+impl Render for PluginEditorView {
+    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+        div().size_full()
+    }
+}
+
+impl Daw {
+    /// Find the installed plugins in the background and list them in the
+    /// Plugins menu
+    pub(crate) fn scan_plugins(cx: &mut Context<Self>) {
+        cx.spawn(
+            async |this: gpui::WeakEntity<Self>, cx: &mut gpui::AsyncApp| {
+                let (plugins, errors) =
+                    cx.background_spawn(async { Session::scan_plugins() }).await;
+                for err in errors {
+                    eprintln!("Skipping plugin: {}", err);
+                }
+                let _ = cx.update(|cx| {
+                    this.update(cx, |daw, cx| {
+                        daw.plugins = plugins;
+                        cx.set_menus(daw.menus());
+                    })
+                });
+            },
+        )
+        .detach();
+    }
+
+    /// Load the plugin at `index` in the Plugins menu onto the selected clip's
+    /// track, or the first track if no clip is selected, and open its editor
+    pub(crate) fn add_plugin(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(info) = self.plugins.get(index).cloned() else {
+            return;
+        };
+        let Some(track_id) = self.selected_track() else {
+            eprintln!("No track to add '{}' to", info.name);
+            return;
+        };
+        match self.session.add_track_plugin(track_id, &info) {
+            Ok(slot) => self.open_plugin_editor(track_id, slot, cx),
+            Err(e) => eprintln!("Failed to load plugin '{}': {}", info.name, e),
+        }
+    }
+
+    /// Track of the selected clip, or the first track that isn't a folder
+    fn selected_track(&self) -> Option<u64> {
+        let tracks = self.session.tracks();
+        self.selected_clips
+            .first()
+            .and_then(|selected| {
+                tracks
+                    .iter()
+                    .find(|track| track.clips().iter().any(|clip| clip.name == selected.0))
+            })
+            .or_else(|| tracks.iter().find(|track| !track.is_folder))
+            .map(|track| track.id.0)
+    }
+
+    /// Open the editor of the plugin at `index` on a track in a window of its
+    /// own. Closing the window closes the editor.
+    fn open_plugin_editor(&mut self, track_id: u64, index: usize, cx: &mut Context<Self>) {
+        let Some(name) = self
+            .session
+            .track_plugins(track_id)
+            .get(index)
+            .map(|info| info.name.clone())
+        else {
+            return;
+        };
+        let options = WindowOptions {
+            titlebar: Some(TitlebarOptions {
+                title: Some(name.into()),
+                ..Default::default()
+            }),
+            is_resizable: false,
+            ..Default::default()
+        };
+
+        let daw = cx.entity().downgrade();
+        let session = &mut self.session;
+        let opened = cx.open_window(options, |window, cx| {
+            let editor_size = EditorParent::from_window(window)
+                .map_err(anyhow::Error::from)
+                .and_then(|parent| session.open_plugin_editor(track_id, index, parent));
+            match editor_size {
+                Ok(editor_size) => {
+                    window.resize(size(
+                        px(editor_size.width as f32),
+                        px(editor_size.height as f32),
+                    ));
+                    // The editor lets go of the window before it closes
+                    window.on_window_should_close(cx, move |_, cx| {
+                        let _ = daw.update(cx, |daw, _| {
+                            daw.session.close_plugin_editor(track_id, index)
+                        });
+                        true
+                    });
+                }
+                Err(e) => {
+                    eprintln!("Failed to open plugin editor: {}", e);
+                    window.remove_window();
+                }
+            }
+            cx.new(|_| PluginEditorView)
+        });
+        if let Err(e) = opened {
+            eprintln!("Failed to open plugin window: {}", e);
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use gpui::{App, Global, Hsla, hsla};
+use gpui::{App, Global, Hsla, hsla, rgb};
 
 /// Simple theme with semantic colors for the DAW
 #[derive(Clone)]
@@ -68,6 +68,14 @@ impl Theme {
             ],
         }
     }
+
+    /// Color for a track: its own color if set, otherwise a palette color by index
+    pub fn track_color(&self, color: Option<[u8; 3]>, index: usize) -> Hsla {
+        match color {
+            Some([r, g, b]) => rgb(u32::from_be_bytes([0, r, g, b])).into(),
+            None => self.track_colors[index % self.track_colors.len()],
+        }
+    }
 }
 
 impl Global for Theme {}
@@ -3,10 +3,14 @@ use crate::ui::primitives::{
     Input,
     button::{button, button_active},
 };
-use daw_core::PPQN;
+use daw_core::{MasterSettings, PPQN, ProjectInfo};
 use gpui::{Context, Entity, EventEmitter, FocusHandle, Focusable, Window, div, prelude::*, px};
 
 const HEADER_HEIGHT: f32 = 50.0;
+/// Width of a full-scale bar on the master meter
+const METER_WIDTH: f32 = 60.0;
+/// Quietest level the master meter shows, in dBFS
+const METER_FLOOR_DB: f32 = -60.0;
 
 pub struct Header {
     current_tick: u64,
@@ -18,6 +22,12 @@ pub struct Header {
     bpm_input: Entity<Input>,
     time_sig_numerator_input: Entity<Input>,
     time_sig_denominator_input: Entity<Input>,
+    /// The project's key and author, shown on the right
+    project_info: String,
+    /// Master gain and balance, nudged from the header
+    master: MasterSettings,
+    /// Output peak on the left and right, as linear gain
+    master_peak: [f32; 2],
     focus_handle: FocusHandle,
 }
 
@@ -26,6 +36,7 @@ pub enum HeaderEvent {
     Pause,
     Stop,
     ToggleMetronome,
+    SetMaster(MasterSettings),
 }
 
 impl EventEmitter<HeaderEvent> for Header {}
@@ -74,6 +85,9 @@ impl Header {
             bpm_input,
             time_sig_numerator_input,
             time_sig_denominator_input,
+            project_info: String::new(),
+            master: MasterSettings::default(),
+            master_peak: [0.0; 2],
             focus_handle: cx.focus_handle(),
         }
     }
@@ -133,13 +147,131 @@ impl Header {
 
     pub fn set_playing(&mut self, playing: bool, cx: &mut Context<Self>) {
         self.playing = playing;
+        if !playing {
+            // Nothing polls the meter while stopped, so let it drop
+            self.master_peak = [0.0; 2];
+        }
         cx.notify();
     }
 
+    /// Show the master bus's gain and balance
+    pub fn set_master(&mut self, master: MasterSettings, cx: &mut Context<Self>) {
+        self.master = master;
+        cx.notify();
+    }
+
+    /// Set the master meter without triggering a notification (for batched updates)
+    pub fn set_master_peak_silent(&mut self, peak: [f32; 2], _cx: &mut Context<Self>) {
+        self.master_peak = peak;
+    }
+
     pub fn set_metronome_enabled(&mut self, enabled: bool, cx: &mut Context<Self>) {
         self.metronome_enabled = enabled;
         cx.notify();
     }
+
+    /// Show the project's key and author
+    pub fn set_project_info(&mut self, info: &ProjectInfo, cx: &mut Context<Self>) {
+        let key = info.key.map(|key| key.to_string());
+        let author = (!info.author.is_empty()).then(|| info.author.clone());
+        self.project_info = key
+            .into_iter()
+            .chain(author)
+            .collect::<Vec<_>>()
+            .join(" • ");
+        cx.notify();
+    }
+}
+
+impl Header {
+    /// A button that sets the master to `settings`
+    fn master_button(
+        id: &'static str,
+        label: &'static str,
+        settings: MasterSettings,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        button(id, cx)
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(move |_, _, _, cx| {
+                    cx.emit(HeaderEvent::SetMaster(settings));
+                }),
+            )
+            .child(label)
+    }
+
+    /// Master gain and balance nudges, and a bar per side for the output level
+    fn render_master(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let theme = cx.theme().clone();
+        let master = self.master;
+        let meter = self.master_peak.map(|peak| {
+            let db = 20.0 * peak.max(1e-6).log10();
+            let fill = ((db - METER_FLOOR_DB) / -METER_FLOOR_DB).clamp(0.0, 1.0);
+            div()
+                .w(px(METER_WIDTH))
+                .h(px(4.))
+                .bg(theme.background)
+                .child(div().w(px(METER_WIDTH * fill)).h_full().bg(theme.accent))
+        });
+        let pan = if master.pan.abs() < 0.005 {
+            "C".to_string()
+        } else if master.pan < 0.0 {
+            format!("L{:.0}", -master.pan * 100.0)
+        } else {
+            format!("R{:.0}", master.pan * 100.0)
+        };
+        div()
+            .flex()
+            .gap_1()
+            .items_center()
+            .text_color(theme.text)
+            .child(div().flex().flex_col().gap_1().children(meter))
+            .child(Self::master_button(
+                "master-gain-down",
+                "−",
+                MasterSettings {
+                    gain_db: master.gain_db - 1.0,
+                    ..master
+                },
+                cx,
+            ))
+            .child(
+                div()
+                    .w(px(56.))
+                    .flex()
+                    .justify_center()
+                    .child(format!("{:+.1} dB", master.gain_db)),
+            )
+            .child(Self::master_button(
+                "master-gain-up",
+                "+",
+                MasterSettings {
+                    gain_db: master.gain_db + 1.0,
+                    ..master
+                },
+                cx,
+            ))
+            .child(Self::master_button(
+                "master-pan-left",
+                "◀",
+                MasterSettings {
+                    pan: master.pan - 0.1,
+                    ..master
+                },
+                cx,
+            ))
+            .child(div().w(px(32.)).flex().justify_center().child(pan))
+            .child(Self::master_button(
+                "master-pan-right",
+                "▶",
+                MasterSettings {
+                    pan: master.pan + 0.1,
+                    ..master
+                },
+                cx,
+            ))
+    }
 }
 
 impl Focusable for Header {
@@ -296,6 +428,19 @@ impl Render for Header {
                             ),
                     ),
             )
-            .child(div().flex_1())
+            .child(
+                div()
+                    .flex_1()
+                    .flex()
+                    .gap_4()
+                    .items_center()
+                    .justify_end()
+                    .child(self.render_master(cx))
+                    .child(
+                        div()
+                            .text_color(theme.text_muted)
+                            .child(self.project_info.clone()),
+                    ),
+            )
     }
 }
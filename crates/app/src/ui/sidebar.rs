@@ -1,4 +1,5 @@
 use crate::theme::ActiveTheme;
+use daw_core::SampleLibrary;
 use gpui::{Context, Window, div, prelude::*, px};
 use std::collections::BTreeMap;
 
@@ -10,31 +11,20 @@ pub struct Sidebar {
 
 impl Sidebar {
     pub fn new() -> Self {
-        let mut directories = BTreeMap::new();
+        let mut directories: BTreeMap<String, Vec<String>> = BTreeMap::new();
 
-        if let Ok(entries) = std::fs::read_dir("samples") {
-            for entry in entries.flatten() {
-                if let Ok(file_type) = entry.file_type() {
-                    if file_type.is_dir() {
-                        if let Some(dir_name) = entry.file_name().to_str() {
-                            let mut samples = Vec::new();
-                            let dir_path = format!("samples/{}", dir_name);
-
-                            if let Ok(sample_entries) = std::fs::read_dir(&dir_path) {
-                                for sample_entry in sample_entries.flatten() {
-                                    if let Some(name) = sample_entry.file_name().to_str() {
-                                        if name.ends_with(".wav") {
-                                            samples.push(name.to_string());
-                                        }
-                                    }
-                                }
-                            }
-
-                            samples.sort();
-                            directories.insert(dir_name.to_string(), samples);
-                        }
-                    }
-                }
+        // Samples one folder deep, grouped by folder
+        let mut library = SampleLibrary::new(["samples"]);
+        library.scan();
+        for sample in library.samples() {
+            let mut components = sample.relative_path().components();
+            if let (Some(dir), Some(file), None) =
+                (components.next(), components.next(), components.next())
+            {
+                directories
+                    .entry(dir.as_os_str().to_string_lossy().into_owned())
+                    .or_default()
+                    .push(file.as_os_str().to_string_lossy().into_owned());
             }
         }
 
@@ -50,7 +50,7 @@ impl Render for Track {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let theme = cx.theme();
         let track_index = self.track.id.0 as usize;
-        let track_color = theme.track_colors[track_index % theme.track_colors.len()];
+        let track_color = theme.track_color(self.track.color, track_index);
 
         let selected_clips = self.selected_clips.clone();
 
@@ -13,7 +13,11 @@ pub struct TrackLabels {
 
 pub enum TrackLabelsEvent {
     ToggleEnabled(u64),
-    SoloExclusive(u64),
+    /// Solo button pressed; `alternate` when a modifier was held
+    Solo {
+        track_id: u64,
+        alternate: bool,
+    },
 }
 
 impl EventEmitter<TrackLabelsEvent> for TrackLabels {}
@@ -48,7 +52,7 @@ impl Render for TrackLabels {
                     .border_color(theme.border),
             )
             .children(self.tracks.iter().enumerate().map(|(i, track)| {
-                let track_color = theme.track_colors[i % theme.track_colors.len()];
+                let track_color = theme.track_color(track.color, i);
                 let text_color = to_dark_variant(track_color);
                 let track_id = track.id.0;
                 let enabled = track.enabled;
@@ -122,9 +126,17 @@ impl Render for TrackLabels {
                                     .child("S")
                                     .on_mouse_down(
                                         gpui::MouseButton::Left,
-                                        cx.listener(move |_this, _event, _window, cx| {
-                                            cx.emit(TrackLabelsEvent::SoloExclusive(track_id));
-                                        }),
+                                        cx.listener(
+                                            move |_this,
+                                                  event: &gpui::MouseDownEvent,
+                                                  _window,
+                                                  cx| {
+                                                cx.emit(TrackLabelsEvent::Solo {
+                                                    track_id,
+                                                    alternate: event.modifiers.secondary(),
+                                                });
+                                            },
+                                        ),
                                     ),
                             )
                             .child(
@@ -0,0 +1,54 @@
+//! Drive the transport without a UI, using only the public `daw_core` API.
+//!
+//! Plays a project (or an empty session with the metronome on) for a few bars and
+//! prints the musical position as it advances.
+//!
+//! ```text
+//! cargo run -p daw_core --example headless_transport -- [project.dawproj] [bars]
+//! ```
+
+use daw_core::{PlaybackState, Session};
+use std::path::Path;
+use std::time::Duration;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let project = args.next();
+    let bars: u64 = args.next().map_or(Ok(2), |bars| bars.parse())?;
+
+    let mut session = match &project {
+        Some(path) => Session::from_project(Path::new(path))?,
+        None => {
+            let mut session = Session::new(vec![], 120.0, (4, 4))?;
+            session.set_metronome_enabled(true);
+            session
+        }
+    };
+
+    let end_tick = session.time_context().bars_to_ticks(bars as f64);
+    println!(
+        "Playing {} bars of {} at {} BPM",
+        bars,
+        session.name(),
+        session.tempo()
+    );
+
+    session.play();
+    let mut last_beat = None;
+    while session.playback_state() == PlaybackState::Playing {
+        // Poll regularly so the session keeps its position in sync with the engine
+        if let Some(tick) = session.poll() {
+            let position = session.time_context().format_position(tick);
+            if last_beat != Some((position.bar, position.beat)) {
+                last_beat = Some((position.bar, position.beat));
+                println!("{}", position);
+            }
+            if tick >= end_tick {
+                session.stop();
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Render a project to a WAV file without opening an audio device.
+//!
+//! ```text
+//! cargo run -p daw_core --example render_project -- projects/demo.dawproj out.wav
+//! ```
+
+use daw_core::Session;
+use std::path::PathBuf;
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (Some(project), Some(output)) = (args.next(), args.next()) else {
+        anyhow::bail!("usage: render_project <project.dawproj> <output.wav>");
+    };
+    let project = PathBuf::from(project);
+
+    // Samples referenced from the dev root live in {dev_root}/samples, next to projects/
+    let dev_root = project.parent().and_then(|dir| dir.parent());
+    let session = Session::from_project_offline(&project, dev_root, 44100)?;
+
+    let clips: usize = session.tracks().iter().map(|t| t.clips().len()).sum();
+    println!(
+        "{}: {} tracks, {} clips at {} BPM",
+        session.name(),
+        session.tracks().len(),
+        clips,
+        session.tempo()
+    );
+
+    let summary = session.render_to_file(output.as_ref())?;
+    println!(
+        "Rendered {:.1}s to {} (peak {:.1} dBFS, {:.1} LUFS)",
+        summary.duration.as_secs_f64(),
+        output,
+        summary.peak_db,
+        summary.loudness_lufs
+    );
+    Ok(())
+}
@@ -0,0 +1,247 @@
+//! Render projects from the command line, without a window or an audio device.
+//!
+//! For batch bouncing and for checking project files in CI: each project is loaded
+//! into an offline session and rendered next to it, or just checked with `--check`.
+//! The exit code is non-zero if any project failed.
+//!
+//! ```text
+//! cargo run -p daw_core --bin daw-render -- [options] <project.dawproj>...
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use daw_core::{
+    DEFAULT_RENDER_SAMPLE_RATE, Dither, Normalization, RenderFileType, RenderOptions, RenderTail,
+    Session,
+};
+use serde::de::DeserializeOwned;
+
+const USAGE: &str = "\
+usage: daw-render [options] <project.dawproj>...
+
+options:
+  -o, --out-dir <dir>     write renders here instead of next to each project
+      --type <type>       wav, flac, or vorbis if built with it (default wav)
+      --format <format>   int16, int24 or float32 (default float32)
+      --dither <dither>   none, triangular or noise_shaped (default none)
+      --normalize <lufs>  bring each mix to this loudness, with peaks under -1 dBTP
+      --tail <seconds>    keep rendering after the last clip ends
+      --metronome         mix in the metronome's click
+      --rate <hz>         sample rate to render at, instead of the project's
+      --threads <n>       threads to mix on, instead of one per core
+      --stems             also render each track to '<name> stems/'
+      --dev-root <dir>    where dev-root samples live, instead of the project's
+                          grandparent directory
+      --check             only load each project and report missing samples
+  -h, --help              show this message";
+
+/// What to do with every project named on the command line
+#[derive(Default)]
+struct Args {
+    projects: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    file_type: RenderFileType,
+    options: RenderOptions,
+    sample_rate: Option<u32>,
+    stems: bool,
+    dev_root: Option<PathBuf>,
+    check: bool,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("daw-render: {err:#}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let mut failed = 0;
+    for project in &args.projects {
+        if let Err(err) = process(project, &args) {
+            eprintln!("{}: {err:#}", project.display());
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        eprintln!("{failed} of {} projects failed", args.projects.len());
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// The parsed arguments, or `None` if help was asked for
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<Option<Args>> {
+    let mut parsed = Args::default();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| anyhow::anyhow!("{name} needs a value"))
+        };
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-o" | "--out-dir" => parsed.out_dir = Some(value(&arg)?.into()),
+            "--type" => parsed.file_type = parse_name(&value(&arg)?)?,
+            "--format" => parsed.options.format = parse_name(&value(&arg)?)?,
+            "--dither" => parsed.options.dither = parse_name::<Dither>(&value(&arg)?)?,
+            "--normalize" => {
+                parsed.options.normalize = Some(Normalization {
+                    loudness_lufs: Some(value(&arg)?.parse()?),
+                    ..Normalization::default()
+                })
+            }
+            "--tail" => parsed.options.tail = Some(RenderTail::Seconds(value(&arg)?.parse()?)),
+            "--metronome" => parsed.options.metronome = true,
+            "--rate" => parsed.sample_rate = Some(value(&arg)?.parse()?),
+            "--threads" => parsed.options.threads = Some(value(&arg)?.parse()?),
+            "--stems" => parsed.stems = true,
+            "--dev-root" => parsed.dev_root = Some(value(&arg)?.into()),
+            "--check" => parsed.check = true,
+            option if option.starts_with('-') => anyhow::bail!("unknown option {option}"),
+            project => parsed.projects.push(project.into()),
+        }
+    }
+    if parsed.projects.is_empty() {
+        anyhow::bail!("no projects given");
+    }
+    parsed.options.file_type = Some(parsed.file_type);
+    Ok(Some(parsed))
+}
+
+/// A setting by the name it's saved under, such as `int24` or `noise_shaped`
+fn parse_name<T: DeserializeOwned>(name: &str) -> anyhow::Result<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_string()))
+        .map_err(|_| anyhow::anyhow!("unknown value {name:?}"))
+}
+
+fn process(project: &Path, args: &Args) -> anyhow::Result<()> {
+    // Samples referenced from the dev root live in {dev_root}/samples, like
+    // Session::from_project looks for them
+    let dev_root = args
+        .dev_root
+        .as_deref()
+        .or_else(|| project.parent().and_then(|dir| dir.parent()));
+
+    let report = Session::validate_project(project, dev_root)?;
+    for issue in &report.missing_samples {
+        eprintln!(
+            "{}: missing sample {} (used by {})",
+            project.display(),
+            issue.sample_ref.path().display(),
+            issue.clips.join(", ")
+        );
+    }
+    for issue in &report.unsupported_samples {
+        eprintln!(
+            "{}: can't decode sample {} (used by {})",
+            project.display(),
+            issue.sample_ref.path().display(),
+            issue.clips.join(", ")
+        );
+    }
+    if args.check {
+        anyhow::ensure!(report.is_ok(), "samples are missing or can't be decoded");
+    }
+
+    // Load at the rate the render is written at, so audio is only resampled once
+    let sample_rate = args
+        .sample_rate
+        .or_else(|| {
+            daw_project::load_project_metadata(project)
+                .ok()
+                .and_then(|metadata| metadata.sample_rate)
+        })
+        .unwrap_or(DEFAULT_RENDER_SAMPLE_RATE);
+    let mut session = Session::from_project_offline(project, dev_root, sample_rate)?;
+    if args.check {
+        let clips: usize = session.tracks().iter().map(|t| t.clips().len()).sum();
+        println!(
+            "{}: ok, {} tracks, {} clips",
+            project.display(),
+            session.tracks().len(),
+            clips
+        );
+        return Ok(());
+    }
+    session.set_project_sample_rate(Some(sample_rate));
+
+    let out_dir = match &args.out_dir {
+        Some(dir) => dir.clone(),
+        None => project
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."))
+            .to_path_buf(),
+    };
+    std::fs::create_dir_all(&out_dir)?;
+    let stem = project.file_stem().map_or_else(
+        || session.name().to_string(),
+        |stem| stem.to_string_lossy().into(),
+    );
+
+    let output = out_dir.join(format!("{stem}.{}", args.file_type.extension()));
+    let summary = session.render_to_file_with(&output, &args.options)?;
+    println!(
+        "{}: {:.1}s, peak {:.1} dBFS, {:.1} LUFS{}",
+        output.display(),
+        summary.duration.as_secs_f64(),
+        summary.peak_db,
+        summary.loudness_lufs,
+        if summary.clipped_samples > 0 {
+            format!(", {} samples clipped", summary.clipped_samples)
+        } else {
+            String::new()
+        }
+    );
+
+    if args.stems {
+        let stems = session.render_stems(&out_dir.join(format!("{stem} stems")))?;
+        println!("{} stems written", stems.len());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use daw_core::RenderFormat;
+
+    fn parse(args: &[&str]) -> anyhow::Result<Option<Args>> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_options_are_parsed_by_their_saved_names() {
+        let args = parse(&[
+            "--format",
+            "int24",
+            "--dither",
+            "noise_shaped",
+            "--type",
+            "flac",
+            "--tail",
+            "2.5",
+            "a.dawproj",
+            "b.dawproj",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(args.projects.len(), 2);
+        assert_eq!(args.options.format, RenderFormat::Int24);
+        assert_eq!(args.options.dither, Dither::NoiseShaped);
+        assert_eq!(args.options.file_type, Some(RenderFileType::Flac));
+        assert_eq!(args.options.tail, Some(RenderTail::Seconds(2.5)));
+
+        assert!(parse(&["--help"]).unwrap().is_none());
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--format", "mp3", "a.dawproj"]).is_err());
+        assert!(parse(&["--bogus", "a.dawproj"]).is_err());
+        assert!(parse(&["a.dawproj", "--threads"]).is_err());
+    }
+}
@@ -1,13 +1,72 @@
-pub mod session;
-pub mod time;
+//! Public API of the DAW for frontends and tools.
+//!
+//! Everything a frontend needs is re-exported here: [`Session`] to drive the
+//! engine and edit the project, and the data types its methods take and return.
+//! Depend on this crate alone rather than the internal `daw_*` crates; their APIs
+//! change freely, while the items below follow semver.
+//!
+//! Engine internals (the real-time thread, its queues and `basedrop` handles)
+//! stay behind [`Session`] and are not reachable from here.
+//!
+//! # Features
+//!
+//! - `internals` - re-exports the internal crates under `daw_core::internals` for tools
+//!   that need lower-level access. Not covered by semver.
 
-pub use session::{Metronome, PlaybackState, Session, SnapMode};
+mod recent;
+mod session;
+mod time;
+mod watchdog;
+
+pub use recent::{MAX_RECENT_PROJECTS, RecentProject, RecentProjects};
+pub use session::{
+    DEFAULT_AUTOSAVE_INTERVAL, DEFAULT_RENDER_SAMPLE_RATE, InsertLength, LOCATORS,
+    MAX_COUNT_IN_BARS, PAD_BASE_NOTE, PlaybackState, SaveAsSamples, Session, SessionEvent,
+    SoloMode,
+};
 pub use time::{MusicalPosition, TimeContext, TimeSignature};
 
 // Re-export utilities and data types needed by frontends
-pub use daw_decode::strip_samples_root;
-pub use daw_project::{ClipData, Project, ProjectError, SampleRef, TrackData};
-pub use daw_transport::{AudioBuffer, Clip, PPQN, Track, TrackId, WaveformData, samples_to_ticks};
+pub use daw_decode::{
+    CacheEntry, CacheStats, CacheTier, LibrarySample, SampleLibrary, decode_audio_arc,
+    strip_samples_root,
+};
+pub use daw_dsp::{
+    CompressorSettings, DelaySettings, Effect, EffectParameter, EqSettings, LimiterSettings,
+    MasterSettings, ReverbSettings,
+};
+pub use daw_engine::AudioInput;
+#[cfg(feature = "vst3")]
+pub use daw_plugins::{EditorParent, EditorSize, PluginError, Vst3Info};
+pub use daw_project::{
+    ClipData, ClipKey, CollectReport, EndBehavior, LoopRegion, MasterData, OfflineClip, Project,
+    ProjectChange, ProjectError, ProjectFormat, ProjectInfo, PunchRegion, SampleIssue, SampleRef,
+    SampleRoot, SaveOptions, SettingChange, SnapMode, TempoChange, TempoMap, TimeSignatureChange,
+    TrackData, ValidationReport, backup_path,
+};
+pub use daw_render::{
+    Dither, Normalization, RenderFileType, RenderFormat, RenderOptions, RenderSummary, RenderTail,
+    Watermark, WatermarkSound,
+};
+pub use daw_transport::{
+    Adsr, AudioArc, AudioBuffer, AutomationCurve, AutomationLane, AutomationPoint, Clip,
+    ClipContent, ClipId, ClipSource, EffectiveMix, IdAllocator, MAX_POLYPHONY, MusicalKey, Note,
+    NoteList, PAN_PARAMETER, PPQN, Sampler, SamplerMap, TempoCurve, Track, TrackId, TrackSend,
+    VOLUME_PARAMETER, WaveformData, effective_mix, samples_to_ticks,
+};
 
 // Note: render_timeline, write_wav, save_project, and decode_file are intentionally NOT re-exported.
 // These operations should go through Session methods to maintain proper encapsulation.
+
+/// The crates `daw_core` is built on, for tools that need more than [`Session`]
+/// offers. These are not covered by semver and may change in any release.
+///
+/// The engine crate is left out on purpose: frontends talk to the audio thread
+/// only through [`Session`].
+#[cfg(feature = "internals")]
+pub mod internals {
+    pub use daw_decode as decode;
+    pub use daw_project as project;
+    pub use daw_render as render;
+    pub use daw_transport as transport;
+}
@@ -0,0 +1,166 @@
+//! The list of recently opened projects, shared by the frontends.
+//!
+//! It's kept as JSON in the user's config directory (`~/.config/daw/recent.json`
+//! on Linux), most recent first, so every frontend offers the same projects to
+//! reopen. Nothing here is written unless a frontend asks: sessions don't add
+//! themselves, so tests and tools leave the list alone.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How many projects the list keeps; older ones drop off the end
+pub const MAX_RECENT_PROJECTS: usize = 10;
+
+/// A project in the recent list
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecentProject {
+    /// The project file, as an absolute path
+    pub path: PathBuf,
+    /// The project's name when it was last opened or saved
+    pub name: String,
+}
+
+/// Recently opened projects, most recent first, and the file they're kept in.
+#[derive(Debug, Clone, Default)]
+pub struct RecentProjects {
+    file: Option<PathBuf>,
+    projects: Vec<RecentProject>,
+}
+
+impl RecentProjects {
+    /// The list in the user's config directory, or an empty one if there's none
+    /// yet or it can't be read.
+    pub fn load() -> Self {
+        match user_config_dir() {
+            Some(dir) => Self::load_from(&dir.join("recent.json")),
+            None => Self::default(),
+        }
+    }
+
+    /// The list kept in `file`, or an empty one if it doesn't exist or can't be
+    /// read. Saving writes back to `file`.
+    pub fn load_from(file: &Path) -> Self {
+        let projects = std::fs::read(file)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Vec<RecentProject>>(&bytes).ok())
+            .unwrap_or_default();
+        Self {
+            file: Some(file.to_path_buf()),
+            projects,
+        }
+    }
+
+    /// The projects, most recent first
+    pub fn list(&self) -> &[RecentProject] {
+        &self.projects
+    }
+
+    /// Put the project at `path` at the top of the list, moving it up if it's
+    /// already there and dropping the oldest past [`MAX_RECENT_PROJECTS`].
+    pub fn add(&mut self, path: &Path, name: &str) {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.projects.retain(|project| project.path != path);
+        self.projects.insert(
+            0,
+            RecentProject {
+                path,
+                name: name.to_string(),
+            },
+        );
+        self.projects.truncate(MAX_RECENT_PROJECTS);
+    }
+
+    /// Take the project at `path` off the list
+    pub fn remove(&mut self, path: &Path) {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.projects.retain(|project| project.path != path);
+    }
+
+    /// Take projects whose files no longer exist off the list, returning them
+    pub fn prune(&mut self) -> Vec<RecentProject> {
+        let (kept, pruned) = std::mem::take(&mut self.projects)
+            .into_iter()
+            .partition(|project| project.path.is_file());
+        self.projects = kept;
+        pruned
+    }
+
+    /// Write the list back to its file, creating the folder if needed.
+    ///
+    /// Does nothing if there's no file for it, such as when the platform's
+    /// config directory can't be found.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(file, serde_json::to_vec_pretty(&self.projects)?)?;
+        Ok(())
+    }
+}
+
+/// This application's directory in the platform's user config directory, such as
+/// `~/.config/daw` on Linux.
+fn user_config_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var_os("HOME")?).join("Library/Application Support")
+    } else if cfg!(windows) {
+        PathBuf::from(std::env::var_os("APPDATA")?)
+    } else {
+        match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        }
+    };
+    Some(base.join("daw"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_recent_projects_are_kept_most_recent_first() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("config/recent.json");
+        let project = |n: usize| dir.path().join(format!("song{}.dawproj", n));
+        for n in 0..=MAX_RECENT_PROJECTS {
+            std::fs::write(project(n), b"{}").unwrap();
+        }
+
+        let mut recent = RecentProjects::load_from(&file);
+        assert!(recent.list().is_empty());
+        for n in 0..=MAX_RECENT_PROJECTS {
+            recent.add(&project(n), &format!("Song {}", n));
+        }
+        // Reopening moves a project back to the top; the oldest fell off
+        recent.add(&project(3), "Song 3 renamed");
+        assert_eq!(recent.list().len(), MAX_RECENT_PROJECTS);
+        assert_eq!(recent.list()[0].path, project(3));
+        assert_eq!(recent.list()[0].name, "Song 3 renamed");
+        assert_eq!(recent.list()[1].path, project(MAX_RECENT_PROJECTS));
+        assert!(recent.list().iter().all(|p| p.path != project(0)));
+        recent.save().unwrap();
+
+        std::fs::remove_file(project(5)).unwrap();
+        let mut reloaded = RecentProjects::load_from(&file);
+        assert_eq!(reloaded.list(), recent.list());
+        let pruned = reloaded.prune();
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(pruned[0].path, project(5));
+        reloaded.remove(&project(3));
+        assert_eq!(reloaded.list().len(), MAX_RECENT_PROJECTS - 2);
+        assert_eq!(reloaded.list()[0].path, project(MAX_RECENT_PROJECTS));
+    }
+
+    #[test]
+    fn test_unreadable_recent_list_starts_empty() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("recent.json");
+        std::fs::write(&file, b"not json").unwrap();
+        assert!(RecentProjects::load_from(&file).list().is_empty());
+    }
+}
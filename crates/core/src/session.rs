@@ -123,7 +123,7 @@
 //! ### Project Management
 //!
 //! ```rust,no_run
-//! # use daw_core::Session;
+//! # use daw_core::{RenderFormat, RenderOptions, Session, Watermark};
 //! # use std::path::Path;
 //! # let mut session = Session::new(vec![], 120.0, (4, 4))?;
 //! // Save project
@@ -134,6 +134,21 @@
 //!
 //! // Render to WAV
 //! session.render_to_file(Path::new("output.wav"))?;
+//!
+//! // Render a demo copy with a beep every 15 seconds, as 16-bit
+//! let options = RenderOptions {
+//!     watermark: Some(Watermark::default()),
+//!     format: RenderFormat::Int16,
+//!     ..Default::default()
+//! };
+//! session.render_to_file_with(Path::new("demo.wav"), &options)?;
+//!
+//! // Bounce the first four bars only
+//! let options = RenderOptions {
+//!     end_tick: Some(16 * daw_core::PPQN),
+//!     ..Default::default()
+//! };
+//! session.render_to_file_with(Path::new("intro.wav"), &options)?;
 //! # Ok::<(), anyhow::Error>(())
 //! ```
 //!
@@ -172,17 +187,99 @@
 //! [`TimeContext`]: crate::time::TimeContext
 //! [`Track`]: daw_transport::Track
 
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use basedrop::Shared;
+use basedrop::{Owned, Shared};
 
 use crate::time::{TimeContext, TimeSignature};
-use daw_decode::{AudioCache, decode_audio_arc_direct};
-use daw_engine::{AudioEngineHandle, EngineClip, EngineCommand, EngineStatus, EngineTrack};
-use daw_project::{PathContext, SampleRef, save_project};
-use daw_render::{render_timeline, write_wav};
-use daw_transport::{AudioArc, Clip, PPQN, Track, TrackId};
+use crate::watchdog::Watchdog;
+#[cfg(feature = "watch")]
+use daw_decode::SampleWatcher;
+use daw_decode::{
+    AudioCache, CacheEntry, CacheStats, ChannelLayout, DEFAULT_MEMORY_BUDGET_BYTES, DiskCache,
+    Prefetcher, decode_audio_arc_direct,
+};
+use daw_dsp::{Effect, InsertChain, MasterBus, MasterSettings, Processor};
+use daw_engine::{
+    AudioEngineHandle, AudioInput, CountIn, EndAction, EngineClip, EngineCommand, EngineSend,
+    EngineStatus, EngineTrack, Inserts, LoopRange, MasterEffects, PlaybackEnd, VoiceStart,
+};
+#[cfg(feature = "vst3")]
+use daw_engine::{MAX_BLOCK_FRAMES, PluginInsert};
+use daw_midi::MidiInput;
+#[cfg(feature = "vst3")]
+use daw_plugins::{EditorParent, EditorSize, PluginError, Vst3Editor, Vst3Info, Vst3Plugin};
+use daw_project::{
+    CollectReport, EndBehavior, LoopRegion, MasterData, OfflineClip, PathContext, PeakCache,
+    ProjectChange, ProjectInfo, PunchRegion, SampleRef, SampleRoot, SaveOptions, SessionState,
+    SnapMode, TempoMap, ValidationReport, collect_samples, embed_samples, export_midi,
+    find_missing_samples, rebase_samples, save_project,
+};
+use daw_render::{
+    RenderFileType, RenderOptions, RenderSummary, process_master, render_clip, render_end_tick,
+    render_range, render_range_to_wav, render_stems, render_track, write_audio_file,
+};
+use daw_transport::{
+    Adsr, AudioArc, AutomationCurve, AutomationLane, AutomationPoint, Clip, ClipContent, ClipId,
+    ClipSource, EffectiveMix, IdAllocator, MusicalKey, PAN_PARAMETER, PPQN, Sampler, TempoCurve,
+    Track, TrackAutomation, TrackId, TrackSend, VOLUME_PARAMETER, WaveformData, any_soloed,
+    clip_source_frame, effective_mix, is_within, routed_sends, sampler_notes, track_with_ancestors,
+};
+
+/// Numbers of the locators a session can store, matching the number keys
+pub const LOCATORS: std::ops::RangeInclusive<u8> = 1..=9;
+
+/// MIDI note that plays the first track's pad; each note above it plays the next
+/// track. Note 36 is the kick in General MIDI, where most pad controllers start.
+pub const PAD_BASE_NOTE: u8 = 36;
+
+/// How often sessions with a project file autosave it, unless changed with
+/// [`Session::set_autosave_interval`]
+pub const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sample rate renders are written at when the project doesn't name one
+pub const DEFAULT_RENDER_SAMPLE_RATE: u32 = 44100;
+
+/// Voice that sample previews play on; pads use track ids, which never reach it
+const PREVIEW_VOICE: u64 = u64::MAX;
+
+/// Most bars [`Session::set_count_in_bars`] counts in
+pub const MAX_COUNT_IN_BARS: u32 = 2;
+
+/// Clip edits [`Session::undo`] can step back through, oldest dropped first
+const UNDO_LIMIT: usize = 100;
+
+/// Every track's clips at one point, by track id, to return to with undo or redo
+type ClipSnapshot = Vec<(u64, Vec<Clip>)>;
+
+/// A VST3 plugin on a track, with its editor while it's open
+#[cfg(feature = "vst3")]
+struct TrackPlugin {
+    /// Id the engine runs it under
+    id: u64,
+    track_id: u64,
+    plugin: Vst3Plugin,
+    editor: Option<Vst3Editor>,
+}
+
+/// A take being captured by [`Session::start_recording`]
+struct Recording {
+    input: AudioInput,
+    /// Interleaved input read so far, at the input's rate and channel count
+    samples: Vec<f32>,
+    /// Where the take goes on the timeline
+    start_tick: u64,
+    /// Samples at the start of `samples` taken during the count-in and pre-roll,
+    /// which are left out of the take
+    lead_in: usize,
+    /// Tracks that were armed when it started, which get a clip of the take
+    tracks: Vec<u64>,
+}
 
 /// Metronome samples and state
 pub struct Metronome {
@@ -222,6 +319,17 @@ impl Metronome {
             volume: 0.8,
         })
     }
+
+    /// A metronome without click samples, for sessions that never play back
+    pub fn silent() -> Self {
+        let silence = AudioArc::new(Vec::new(), 48000, 1);
+        Self {
+            hi: silence.clone(),
+            lo: silence,
+            enabled: false,
+            volume: 0.8,
+        }
+    }
 }
 
 /// Resolve an asset path (like assets/metronome_hi.wav) searching relative to base directories.
@@ -255,21 +363,70 @@ fn resolve_asset_path(path: &Path, base_dir: Option<&Path>) -> Option<PathBuf> {
     None
 }
 
-/// Snap mode for cursor placement and editing operations
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum SnapMode {
-    /// No snapping - cursor placed exactly where clicked
-    None,
-    /// Snap to quarter note beats (PPQN)
+/// Length of clips created when inserting a one-shot sample
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InsertLength {
+    /// Clip spans the sample's duration at the tempo where it starts
+    #[default]
+    AudioLength,
+    /// Clip is one beat long (PPQN)
     Beat,
-    /// Snap to half beats (PPQN / 2)
-    HalfBeat,
-    /// Snap to quarter beats (PPQN / 4)
-    QuarterBeat,
-    /// Snap to bars (PPQN * beats_per_bar)
+    /// Clip is one bar long (PPQN * beats_per_bar)
     Bar,
 }
 
+impl InsertLength {
+    /// Clip length in ticks for `audio` starting at `start_tick`, under the given
+    /// tempo curve and time signature
+    pub fn length_ticks(
+        &self,
+        audio: &AudioArc,
+        start_tick: u64,
+        tempo: &TempoCurve,
+        time_signature: TimeSignature,
+    ) -> u64 {
+        match self {
+            InsertLength::AudioLength => {
+                (tempo.samples_to_span(start_tick, audio.frames() as f64, audio.sample_rate())
+                    as u64)
+                    .max(1)
+            }
+            InsertLength::Beat => PPQN,
+            InsertLength::Bar => time_signature.ticks_per_bar(),
+        }
+    }
+}
+
+/// What pressing a track's solo button does to the other tracks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SoloMode {
+    /// Soloing a track unsolos every other track ("listen to the latest solo")
+    #[default]
+    Exclusive,
+    /// Soloing a track adds it to the tracks already soloed
+    Additive,
+}
+
+impl SoloMode {
+    /// The other mode, used while a modifier key is held
+    pub fn alternate(self) -> Self {
+        match self {
+            SoloMode::Exclusive => SoloMode::Additive,
+            SoloMode::Additive => SoloMode::Exclusive,
+        }
+    }
+}
+
+/// What [`Session::save_as`] does with samples referenced relative to the project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SaveAsSamples {
+    /// Leave the files where they are and rewrite the references to find them
+    #[default]
+    Reference,
+    /// Copy the files into the new project's `audio` folder
+    Copy,
+}
+
 /// Current playback state of the session
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PlaybackState {
@@ -323,18 +480,151 @@ pub struct Session {
     playback_state: PlaybackState,
     /// Cache for decoded and resampled audio
     cache: AudioCache,
+    /// Samples to warm the cache with in idle time, by track and clip ticks
+    prefetcher: Prefetcher<(TrackId, u64, u64)>,
     /// Mapping from clip name to sample reference
     sample_refs: HashMap<String, SampleRef>,
+    /// Clips whose samples couldn't be loaded, saved as they were until relinked
+    offline_clips: Vec<OfflineClip>,
+    /// Clips frozen tracks had before, by track id, to put back when they're unfrozen
+    frozen_tracks: HashMap<u64, Vec<Clip>>,
     /// Path to the project file (if loaded from or saved to a file)
     project_path: Option<PathBuf>,
+    /// Root that dev-root sample references resolve under
+    dev_root: Option<PathBuf>,
+    /// The user's sample libraries, where `SampleRef::Library` samples are found
+    sample_roots: Vec<SampleRoot>,
+    /// Folder the project's embedded samples were written out to when it was loaded
+    embedded_dir: Option<PathBuf>,
+    /// Whether saving stores the sample files inside the project file
+    embed_samples: bool,
+    /// Format and compression project files and autosaves are written with
+    save_options: SaveOptions,
     /// Project name
     name: String,
+    /// Author, notes, timestamps and key saved with the project
+    info: ProjectInfo,
+    /// Sample rate the project prefers for playback and rendering, if any
+    project_sample_rate: Option<u32>,
     /// Metronome state and samples
     metronome: Metronome,
     /// Edit cursor position in ticks (None if not set)
     cursor_tick: Option<u64>,
     /// Snap mode for cursor and editing operations
     snap_mode: SnapMode,
+    /// Arrangement zoom the frontend last reported, saved with the project
+    zoom: Option<f32>,
+    /// Length of clips inserted from one-shot samples
+    insert_length: InsertLength,
+    /// What pressing a solo button does to the other tracks
+    solo_mode: SoloMode,
+    /// What playback does after the last clip
+    end_behavior: EndBehavior,
+    /// Whether stopping moves the playhead back to where playback started
+    return_to_start: bool,
+    /// Tick the current (or last) playback started from
+    play_start_tick: u64,
+    /// Numbered locator positions in ticks, keyed by number (1-9)
+    locators: BTreeMap<u8, u64>,
+    /// Section playback cycles through while it's enabled
+    loop_region: Option<LoopRegion>,
+    /// Section recordings are kept to while punching in
+    punch_region: Option<PunchRegion>,
+    /// Bars of metronome played before playback starts from stopped
+    count_in_bars: u32,
+    /// Bars before the cursor that playback starts from
+    pre_roll_bars: u32,
+    /// Tempo and time signature changes after the start, kept with the project
+    tempo_map: TempoMap,
+    /// Whether the master output is muted, by `panic()` or `set_master_muted()`
+    master_muted: bool,
+    /// Gain, balance and limiter on the master bus, for playback and renders alike
+    master_bus: MasterSettings,
+    /// Insert effects on the master bus, before its gain
+    master_effects: Vec<Effect>,
+    /// Effects each track's insert chain in the engine was built with, by track
+    /// id, so a new chain is only built when which effects run changes
+    engine_inserts: HashMap<u64, Vec<Effect>>,
+    /// Effects the master bus's insert chain in the engine was built with
+    engine_master_inserts: Vec<Effect>,
+    /// Output peaks on the left and right over the last poll
+    master_peak: [f32; 2],
+    /// Whether triggering a pad during playback also records it as a clip
+    pad_recording: bool,
+    /// Tracks that take input when recording starts
+    armed_tracks: HashSet<u64>,
+    /// The take being recorded, if any
+    recording: Option<Recording>,
+    /// Linear gain of sample previews
+    preview_volume: f32,
+    /// Hands out ids for new tracks and clips
+    ids: IdAllocator,
+    /// Clips before each edit that can be undone, most recent last
+    undo_stack: Vec<ClipSnapshot>,
+    /// Clips before each undo, to redo it
+    redo_stack: Vec<ClipSnapshot>,
+    /// Notices playback freezing because the audio stream stopped
+    watchdog: Watchdog,
+    /// Events not yet collected with `take_events`
+    events: Vec<SessionEvent>,
+    /// How often the project is autosaved while polling (None = never)
+    autosave_interval: Option<Duration>,
+    /// When the project was last autosaved, or the session created
+    last_autosave: Instant,
+    /// Autosave left behind by an earlier session, found when the project was loaded
+    recoverable_autosave: Option<PathBuf>,
+    /// Device whose MIDI is played on the armed tracks, if one was chosen. Not
+    /// saved with the project
+    midi_input: Option<MidiInput>,
+    /// Notices sample files changing on disk, while watching is on
+    #[cfg(feature = "watch")]
+    sample_watcher: Option<SampleWatcher>,
+    /// When the watched folders were last brought up to date with the samples in use
+    #[cfg(feature = "watch")]
+    sample_watch_synced: Instant,
+    /// VST3 plugins on tracks, run after their effects in the order they were
+    /// added. They're not saved with the project.
+    #[cfg(feature = "vst3")]
+    plugins: Vec<TrackPlugin>,
+    /// Plugins taken off their tracks, kept loaded until the engine hands back
+    /// their processors, so the last reference isn't dropped on the audio thread
+    #[cfg(feature = "vst3")]
+    removed_plugins: Vec<TrackPlugin>,
+    /// Ids of removed plugins the engine couldn't be told about yet, retried in
+    /// [`poll`](Session::poll)
+    #[cfg(feature = "vst3")]
+    unsent_plugin_removals: Vec<u64>,
+    /// Id the next plugin is handed to the engine under
+    #[cfg(feature = "vst3")]
+    next_plugin_id: u64,
+}
+
+/// Something that happened outside a direct call, for the frontend to report.
+///
+/// Collected with [`Session::take_events`] after polling.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionEvent {
+    /// The audio stream stopped advancing during playback
+    AudioStalled,
+    /// The audio stream was restarted after a stall and playback resumed
+    AudioRestarted,
+    /// The audio stream couldn't be restarted, so playback was paused
+    AudioRestartFailed(String),
+    /// Samples changed on disk and were reloaded, by name (see
+    /// [`Session::reload_changed_samples`]); only sent while watching samples
+    SamplesReloaded(Vec<String>),
+    /// Samples changed on disk but couldn't be reloaded; they are tried again on the
+    /// next change
+    SampleReloadFailed(String),
+    /// The periodic autosave couldn't be written; it's tried again after the next
+    /// interval
+    AutosaveFailed(String),
+    /// The MIDI input device couldn't be opened again after the audio stream was
+    /// restarted, so MIDI input was turned off
+    MidiInputLost(String),
+    /// Input came in faster than [`Session::poll`] read it while recording, and
+    /// this many frames were lost from the take
+    RecordingDropout(u64),
 }
 
 impl Session {
@@ -374,14 +664,60 @@ impl Session {
         time_signature: impl Into<TimeSignature>,
         sample_refs: HashMap<String, SampleRef>,
     ) -> anyhow::Result<Self> {
-        let time_context = TimeContext::new(tempo, time_signature.into());
-
         // Load metronome samples
         let metronome = Metronome::load()?;
 
         // Start the audio engine
         let engine = daw_engine::start(vec![])?;
+
+        Ok(Self::with_engine(
+            engine,
+            tracks,
+            TimeContext::new(tempo, time_signature.into()),
+            sample_refs,
+            metronome,
+        ))
+    }
+
+    /// Create a session that isn't connected to an audio device.
+    ///
+    /// Offline sessions support everything except playback: editing, saving and
+    /// rendering work as usual, while transport commands are ignored. Useful for
+    /// tests, batch processing and other headless tools.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use daw_core::{Session, Track, TrackId};
+    ///
+    /// let tracks = vec![Track::new(TrackId(0), "Drums".to_string())];
+    /// let session = Session::new_offline(tracks, 120.0, (4, 4), 48000);
+    /// assert_eq!(session.sample_rate(), 48000);
+    /// ```
+    pub fn new_offline(
+        tracks: Vec<Track>,
+        tempo: f64,
+        time_signature: impl Into<TimeSignature>,
+        sample_rate: u32,
+    ) -> Self {
+        Self::with_engine(
+            daw_engine::start_offline(sample_rate),
+            tracks,
+            TimeContext::new(tempo, time_signature.into()),
+            HashMap::new(),
+            Metronome::silent(),
+        )
+    }
+
+    fn with_engine(
+        engine: AudioEngineHandle,
+        tracks: Vec<Track>,
+        time_context: TimeContext,
+        sample_refs: HashMap<String, SampleRef>,
+        metronome: Metronome,
+    ) -> Self {
         let sample_rate = engine.sample_rate;
+        let ids = IdAllocator::for_tracks(&tracks);
 
         let mut session = Self {
             engine,
@@ -389,19 +725,72 @@ impl Session {
             time_context,
             current_tick: 0,
             playback_state: PlaybackState::Stopped,
-            cache: AudioCache::new(),
+            cache: AudioCache::new().with_channel_layout(ChannelLayout::Stereo),
+            prefetcher: Prefetcher::new(Some(sample_rate)),
             sample_refs,
+            offline_clips: Vec::new(),
+            frozen_tracks: HashMap::new(),
             project_path: None,
+            dev_root: None,
+            sample_roots: Vec::new(),
+            embedded_dir: None,
+            embed_samples: false,
+            save_options: SaveOptions::default(),
             name: "Untitled".to_string(),
+            info: ProjectInfo::default(),
+            project_sample_rate: None,
             metronome,
             cursor_tick: Some(0), // Initialize cursor at beginning
             snap_mode: SnapMode::Bar,
+            zoom: None,
+            insert_length: InsertLength::default(),
+            solo_mode: SoloMode::default(),
+            end_behavior: EndBehavior::default(),
+            return_to_start: false,
+            master_muted: false,
+            master_bus: MasterSettings::default(),
+            master_effects: Vec::new(),
+            engine_inserts: HashMap::new(),
+            engine_master_inserts: Vec::new(),
+            master_peak: [0.0; 2],
+            pad_recording: false,
+            armed_tracks: HashSet::new(),
+            midi_input: None,
+            recording: None,
+            preview_volume: 1.0,
+            play_start_tick: 0,
+            locators: BTreeMap::new(),
+            loop_region: None,
+            punch_region: None,
+            count_in_bars: 0,
+            pre_roll_bars: 0,
+            tempo_map: TempoMap::default(),
+            ids,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            watchdog: Watchdog::new(Instant::now(), 0),
+            events: Vec::new(),
+            autosave_interval: Some(DEFAULT_AUTOSAVE_INTERVAL),
+            last_autosave: Instant::now(),
+            recoverable_autosave: None,
+            #[cfg(feature = "watch")]
+            sample_watcher: None,
+            #[cfg(feature = "watch")]
+            sample_watch_synced: Instant::now(),
+            #[cfg(feature = "vst3")]
+            plugins: Vec::new(),
+            #[cfg(feature = "vst3")]
+            removed_plugins: Vec::new(),
+            #[cfg(feature = "vst3")]
+            unsent_plugin_removals: Vec::new(),
+            #[cfg(feature = "vst3")]
+            next_plugin_id: 0,
         };
 
         // Now send the real tracks with correct sample rate conversion
         session.send_tracks_to_engine(sample_rate);
 
-        Ok(session)
+        session
     }
 
     /// Load a session from a project file.
@@ -419,10 +808,23 @@ impl Session {
     /// # Ok::<(), anyhow::Error>(())
     /// ```
     pub fn from_project(path: &Path) -> anyhow::Result<Self> {
+        Self::from_project_with_progress(path, |_| {})
+    }
+
+    /// Load a session from a project file, reporting how much audio is loaded.
+    ///
+    /// `progress` is called with the fraction of the project's audio decoded so
+    /// far, from 0.0 to 1.0, several times a second. Audio files are decoded in
+    /// parallel in the background, but this call still blocks until the project
+    /// is loaded, so frontends should make it off their UI thread.
+    pub fn from_project_with_progress(
+        path: &Path,
+        progress: impl FnMut(f32),
+    ) -> anyhow::Result<Self> {
         // Use default dev root (grandparent of project file, e.g., /Users/korbin/dev/daw)
         // PathContext::resolve will then look for samples in {dev_root}/samples/
         let dev_root = path.parent().and_then(|p| p.parent());
-        Self::from_project_with_context(path, dev_root)
+        Self::load(path, dev_root, progress)
     }
 
     /// Load a session from a project file with explicit dev root.
@@ -433,8 +835,88 @@ impl Session {
     /// * `dev_root` - Optional path to the dev workspace root (e.g., /Users/korbin/dev/daw).
     ///   DevRoot sample refs will resolve to `{dev_root}/samples/{path}`.
     pub fn from_project_with_context(path: &Path, dev_root: Option<&Path>) -> anyhow::Result<Self> {
-        // Start engine first to get sample rate
-        let engine = daw_engine::start(vec![])?;
+        Self::load(path, dev_root, |_| {})
+    }
+
+    /// Check a project file before loading it, without decoding any audio (see
+    /// [`daw_project::validate`]).
+    ///
+    /// Samples are looked for where [`from_project_with_context`](Self::from_project_with_context)
+    /// would look, so frontends can warn about missing ones before a long load.
+    pub fn validate_project(
+        path: &Path,
+        dev_root: Option<&Path>,
+    ) -> anyhow::Result<ValidationReport> {
+        let ctx = PathContext {
+            dev_root: dev_root.map(|p| p.to_path_buf()),
+            ..PathContext::from_project_path(path)
+        };
+        Ok(daw_project::validate(path, &ctx)?)
+    }
+
+    fn load(
+        path: &Path,
+        dev_root: Option<&Path>,
+        progress: impl FnMut(f32),
+    ) -> anyhow::Result<Self> {
+        // Start engine first to get sample rate, at the project's own if the device
+        // supports it
+        let preferred = daw_project::load_project_metadata(path)
+            .ok()
+            .and_then(|metadata| metadata.sample_rate);
+        let engine = daw_engine::start_with_sample_rate(vec![], preferred)?;
+
+        // Reuse audio decoded and peaks computed by earlier opens when the user cache
+        // directory is available
+        let cache = AudioCache::new().with_memory_budget(DEFAULT_MEMORY_BUDGET_BYTES);
+        let cache = match DiskCache::in_default_location() {
+            Some(disk) => cache.with_disk_cache(disk),
+            None => cache,
+        };
+
+        // Get the project directory to use as base for asset resolution
+        let metronome = Metronome::load_with_base(path.parent())?;
+
+        Self::from_project_with_engine(
+            path,
+            dev_root,
+            engine,
+            cache,
+            PeakCache::in_default_location(),
+            metronome,
+            progress,
+        )
+    }
+
+    /// Load a project into an offline session (see [`Session::new_offline`]).
+    ///
+    /// Audio is resampled to `sample_rate`. Nothing is read from or written to the
+    /// user's caches of decoded audio and waveform peaks.
+    pub fn from_project_offline(
+        path: &Path,
+        dev_root: Option<&Path>,
+        sample_rate: u32,
+    ) -> anyhow::Result<Self> {
+        Self::from_project_with_engine(
+            path,
+            dev_root,
+            daw_engine::start_offline(sample_rate),
+            AudioCache::new(),
+            None,
+            Metronome::silent(),
+            |_| {},
+        )
+    }
+
+    fn from_project_with_engine(
+        path: &Path,
+        dev_root: Option<&Path>,
+        engine: AudioEngineHandle,
+        cache: AudioCache,
+        peak_cache: Option<PeakCache>,
+        metronome: Metronome,
+        progress: impl FnMut(f32),
+    ) -> anyhow::Result<Self> {
         let sample_rate = engine.sample_rate;
 
         // Build path context
@@ -445,17 +927,27 @@ impl Session {
         let ctx = PathContext {
             project_root,
             dev_root: dev_root.map(|p| p.to_path_buf()),
+            embedded_dir: None,
+            sample_roots: Vec::new(),
         };
 
-        // Load project with audio resampled to engine sample rate
-        let project = daw_project::load_project_with_sample_rate(path, Some(sample_rate), &ctx)?;
-
-        // Get the project directory to use as base for asset resolution
-        let project_dir = path.parent();
+        // Load project with audio resampled to engine sample rate and folded to stereo,
+        // so mono and surround files play the same on any output. Damaged files are
+        // decoded as far as they go, so a bad packet silences a moment of a clip
+        // rather than taking the whole clip offline
+        let project = daw_project::load_project_with_progress(
+            path,
+            Some(sample_rate),
+            &ctx,
+            cache
+                .with_channel_layout(ChannelLayout::Stereo)
+                .with_lossy_decoding(),
+            peak_cache.as_ref(),
+            progress,
+        )?;
+        let session_state = project.session_state;
 
-        // Create time context and load metronome
         let time_context = TimeContext::new(project.tempo, project.time_signature);
-        let metronome = Metronome::load_with_base(project_dir)?;
 
         // Log offline clips if any
         if !project.offline_clips.is_empty() {
@@ -464,10 +956,39 @@ impl Session {
                 project.offline_clips.len()
             );
             for offline in &project.offline_clips {
-                eprintln!("  - {} ({}): {}", offline.name, offline.sample_ref, offline.error);
+                eprintln!(
+                    "  - {} ({}): {}",
+                    offline.name, offline.sample_ref, offline.error
+                );
+            }
+        }
+        for damaged in &project.damaged_samples {
+            eprintln!(
+                "Warning: {} is damaged and was partly silenced:",
+                damaged.sample_ref
+            );
+            for issue in &damaged.issues {
+                eprintln!("  - {}", issue);
             }
         }
 
+        if let Some(preferred) = project.sample_rate
+            && preferred != sample_rate
+        {
+            eprintln!(
+                "Warning: project is set to {} Hz but plays at {} Hz",
+                preferred, sample_rate
+            );
+        }
+
+        let recoverable_autosave = daw_project::stale_autosave(path);
+        if let Some(autosave) = &recoverable_autosave {
+            eprintln!(
+                "Warning: {} holds changes that were never saved",
+                autosave.display()
+            );
+        }
+
         // Create session with loaded cache
         let mut session = Self {
             engine,
@@ -476,41 +997,449 @@ impl Session {
             current_tick: 0,
             playback_state: PlaybackState::Stopped,
             cache: project.cache,
+            prefetcher: Prefetcher::new(Some(sample_rate)),
             sample_refs: project.sample_refs,
+            offline_clips: project.offline_clips,
+            frozen_tracks: HashMap::new(),
             project_path: Some(path.to_path_buf()),
+            dev_root: ctx.dev_root.clone(),
+            sample_roots: Vec::new(),
+            // A project shared with its samples inside keeps them there when saved again
+            embed_samples: project.embedded_dir.is_some(),
+            embedded_dir: project.embedded_dir,
+            save_options: project.save_options,
             name: project.name,
+            info: project.info,
+            project_sample_rate: project.sample_rate,
             metronome,
             cursor_tick: Some(0), // Initialize cursor at beginning
             snap_mode: SnapMode::QuarterBeat,
+            zoom: None,
+            insert_length: InsertLength::default(),
+            solo_mode: SoloMode::default(),
+            end_behavior: project.end_behavior,
+            return_to_start: false,
+            master_muted: false,
+            master_bus: MasterSettings::default(),
+            master_effects: Vec::new(),
+            engine_inserts: HashMap::new(),
+            engine_master_inserts: Vec::new(),
+            master_peak: [0.0; 2],
+            pad_recording: false,
+            armed_tracks: HashSet::new(),
+            midi_input: None,
+            recording: None,
+            preview_volume: 1.0,
+            play_start_tick: 0,
+            locators: project.locators,
+            loop_region: None,
+            punch_region: None,
+            count_in_bars: 0,
+            pre_roll_bars: 0,
+            tempo_map: project.tempo_map,
+            ids: project.ids,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            watchdog: Watchdog::new(Instant::now(), 0),
+            events: Vec::new(),
+            autosave_interval: Some(DEFAULT_AUTOSAVE_INTERVAL),
+            last_autosave: Instant::now(),
+            recoverable_autosave,
+            #[cfg(feature = "watch")]
+            sample_watcher: None,
+            #[cfg(feature = "watch")]
+            sample_watch_synced: Instant::now(),
+            #[cfg(feature = "vst3")]
+            plugins: Vec::new(),
+            #[cfg(feature = "vst3")]
+            removed_plugins: Vec::new(),
+            #[cfg(feature = "vst3")]
+            unsent_plugin_removals: Vec::new(),
+            #[cfg(feature = "vst3")]
+            next_plugin_id: 0,
         };
 
+        session.plan_prefetch(&ctx);
+
+        if let Some(state) = session_state {
+            session.restore_session_state(state);
+        }
+        session.restore_master(project.master);
+
         // Send tracks to engine (already at correct sample rate)
         session.send_tracks_to_engine(sample_rate);
 
         Ok(session)
     }
 
-    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+    /// Write the project to `path`, leaving the session's project file as it is.
+    ///
+    /// Samples referenced relative to the project are referenced so they're still
+    /// found from `path`'s folder. Until a session has a project file, its references
+    /// are taken to be relative to wherever it's saved.
+    ///
+    /// The file is stamped with the time as when it was last modified, and first
+    /// created if it's never been saved. A copy saved somewhere other than the
+    /// session's project file leaves the session's own timestamps alone.
+    pub fn save(&mut self, path: &Path) -> anyhow::Result<()> {
+        let info = self.info.clone();
+        let now = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        self.info.created.get_or_insert(now);
+        self.info.modified = Some(now);
+        let written = self.write_project(path, &self.save_options);
+        if written.is_err() || self.project_path.as_deref().is_some_and(|own| own != path) {
+            self.info = info;
+        }
+        written?;
+        Ok(())
+    }
+
+    /// Write the project to `path` with `options`, embedding samples as
+    /// [`embeds_samples`] says.
+    ///
+    /// Samples that were already embedded stay embedded either way, since there may
+    /// be no other copy of them.
+    ///
+    /// [`embeds_samples`]: Self::embeds_samples
+    fn write_project(
+        &self,
+        path: &Path,
+        options: &SaveOptions,
+    ) -> Result<(), daw_project::ProjectError> {
+        let mut sample_refs = self.sample_refs.clone();
+        if self.project_path.is_some() {
+            rebase_samples(
+                &mut sample_refs,
+                &self.path_context(),
+                &self.context_for(path),
+            );
+        }
+        let mut to_embed: HashMap<String, SampleRef> = sample_refs
+            .iter()
+            .filter(|(_, sample_ref)| {
+                self.embed_samples || matches!(sample_ref, SampleRef::Embedded(_))
+            })
+            .map(|(name, sample_ref)| (name.clone(), sample_ref.clone()))
+            .collect();
+        let embedded = embed_samples(&mut to_embed, &self.path_context())?;
+        sample_refs.extend(to_embed);
+
         save_project(
             path,
             self.name.clone(),
+            &self.info,
             self.tempo(),
             (
                 self.time_signature().numerator,
                 self.time_signature().denominator,
             ),
+            &self.tempo_map,
+            self.project_sample_rate,
+            self.end_behavior,
+            &self.locators,
+            &self.master_data(),
             &self.tracks,
-            &self.sample_refs,
+            &self.offline_clips,
+            &self.ids,
+            &sample_refs,
+            embedded,
+            Some(self.session_state()),
+            options,
+        )
+    }
+
+    /// Cursor, snapping, metronome, zoom, loop, punch, count-in and pre-roll as
+    /// they're saved with the project
+    fn session_state(&self) -> SessionState {
+        SessionState {
+            cursor_tick: self.cursor_tick,
+            snap_mode: self.snap_mode,
+            metronome_enabled: self.metronome.enabled,
+            metronome_volume: self.metronome.volume,
+            zoom: self.zoom,
+            loop_region: self.loop_region,
+            punch_region: self.punch_region,
+            count_in_bars: self.count_in_bars,
+            pre_roll_bars: self.pre_roll_bars,
+        }
+    }
+
+    /// Put back the state a project was saved with, so it opens where it was left
+    fn restore_session_state(&mut self, state: SessionState) {
+        self.cursor_tick = state.cursor_tick;
+        self.snap_mode = state.snap_mode;
+        self.metronome.enabled = state.metronome_enabled;
+        self.metronome.volume = state.metronome_volume.clamp(0.0, 1.0);
+        self.zoom = state.zoom;
+        self.loop_region = state
+            .loop_region
+            .filter(|region| region.start_tick < region.end_tick);
+        self.punch_region = state
+            .punch_region
+            .filter(|region| region.start_tick < region.end_tick);
+        self.count_in_bars = state.count_in_bars.min(MAX_COUNT_IN_BARS);
+        self.pre_roll_bars = state.pre_roll_bars;
+    }
+
+    /// Format, compression and backups the project is saved with
+    pub fn save_options(&self) -> SaveOptions {
+        self.save_options
+    }
+
+    /// Choose how the project is saved from the next save on, e.g. compressed
+    /// MessagePack for big projects where JSON gets large.
+    ///
+    /// Loaded projects keep the format they were in until this is called, and
+    /// keep no backups. Autosaves use the same format and compression, so they
+    /// compare equal to an unchanged project file. Projects in any format load
+    /// regardless.
+    pub fn set_save_options(&mut self, options: SaveOptions) {
+        self.save_options = options;
+    }
+
+    /// Whether saving stores the sample files inside the project file, so it can
+    /// be shared as a single file.
+    ///
+    /// On for projects that were loaded with samples embedded.
+    pub fn embeds_samples(&self) -> bool {
+        self.embed_samples
+    }
+
+    /// Store the sample files inside the project file from the next save on.
+    ///
+    /// Meant for small sketches: every sample the project uses goes in, in its
+    /// original format. Turning it off leaves samples that are already embedded in
+    /// the file, since they may not exist anywhere else.
+    pub fn set_embed_samples(&mut self, embed: bool) {
+        self.embed_samples = embed;
+    }
+
+    /// Save to the project file the session was loaded from or last given.
+    ///
+    /// The project's autosave is removed, since the file now holds everything in it.
+    pub fn save_in_place(&mut self) -> anyhow::Result<()> {
+        let path = self
+            .project_path
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("No project path set"))?;
+        self.save(&path)?;
+        daw_project::discard_autosave(&path)?;
+        Ok(())
+    }
+
+    /// Save to `path` with copies of every sample the project uses in an `audio`
+    /// folder next to it, so the project folder can be moved to another machine as is.
+    ///
+    /// Samples from the dev root or elsewhere outside the project folder are copied in
+    /// and referenced relative to the project from then on; ones already inside it stay
+    /// where they are. The session takes `path` as its project file. Samples that can't
+    /// be found keep their references and are listed in the report. If saving fails,
+    /// the session keeps referring to the samples where they were.
+    ///
+    /// Embedded samples are written into the folder too, and
+    /// [`embeds_samples`](Self::embeds_samples) is turned off.
+    pub fn save_with_samples(&mut self, path: &Path) -> anyhow::Result<CollectReport> {
+        let mut sample_refs = self.sample_refs.clone();
+        let report = collect_samples(
+            &mut sample_refs,
+            &self.path_context(),
+            &self.context_for(path),
+        )?;
+
+        let embedded = std::mem::replace(&mut self.embed_samples, false);
+        if let Err(err) = self.save_to_new_path(path, sample_refs) {
+            self.embed_samples = embedded;
+            return Err(err);
+        }
+        Ok(report)
+    }
+
+    /// Save to `path` and take it as the session's project file.
+    ///
+    /// Samples referenced relative to the old project file would be lost from the
+    /// new one, so `samples` says what to do with them: rewrite the references to
+    /// find the files where they are, or copy the files into an `audio` folder next
+    /// to `path`. Samples from the dev root and embedded ones are found either way.
+    /// Copied samples, and ones that couldn't be found, are listed in the report;
+    /// missing ones are still referenced where they were. If saving fails, the
+    /// session keeps its project file and references.
+    pub fn save_as(
+        &mut self,
+        path: &Path,
+        samples: SaveAsSamples,
+    ) -> anyhow::Result<CollectReport> {
+        let to = self.context_for(path);
+        let mut sample_refs = self.sample_refs.clone();
+        if self.project_path.is_some() {
+            rebase_samples(&mut sample_refs, &self.path_context(), &to);
+        }
+
+        let mut project_relative: HashMap<String, SampleRef> = sample_refs
+            .iter()
+            .filter(|(_, sample_ref)| matches!(sample_ref, SampleRef::ProjectRelative(_)))
+            .map(|(name, sample_ref)| (name.clone(), sample_ref.clone()))
+            .collect();
+        let report = match samples {
+            // Already rebased onto the new project, so collected from there
+            SaveAsSamples::Copy => collect_samples(&mut project_relative, &to, &to)?,
+            SaveAsSamples::Reference => {
+                let mut missing: Vec<String> = project_relative
+                    .iter()
+                    .filter(|(_, sample_ref)| to.resolve(sample_ref).is_none())
+                    .map(|(name, _)| name.clone())
+                    .collect();
+                missing.sort();
+                CollectReport {
+                    missing,
+                    ..Default::default()
+                }
+            }
+        };
+        sample_refs.extend(project_relative);
+
+        self.save_to_new_path(path, sample_refs)?;
+        Ok(report)
+    }
+
+    /// Save to `path` as the session's project file, with `sample_refs` made for it
+    fn save_to_new_path(
+        &mut self,
+        path: &Path,
+        sample_refs: HashMap<String, SampleRef>,
+    ) -> anyhow::Result<()> {
+        let previous_refs = std::mem::replace(&mut self.sample_refs, sample_refs);
+        let previous_path = self.project_path.replace(path.to_path_buf());
+        if let Err(err) = self.save(path) {
+            self.sample_refs = previous_refs;
+            self.project_path = previous_path;
+            return Err(err);
+        }
+        if previous_path.as_deref() != Some(path) {
+            // An autosave found beside the old project file doesn't belong to this one
+            self.recoverable_autosave = None;
+        }
+        Ok(())
+    }
+
+    /// How often polling autosaves the project, or `None` if it doesn't.
+    /// [`DEFAULT_AUTOSAVE_INTERVAL`] unless changed.
+    pub fn autosave_interval(&self) -> Option<Duration> {
+        self.autosave_interval
+    }
+
+    /// Set how often polling autosaves the project, or turn autosaving off with `None`.
+    pub fn set_autosave_interval(&mut self, interval: Option<Duration>) {
+        self.autosave_interval = interval;
+    }
+
+    /// Write the session to the project's autosave file (`song.autosave` next to
+    /// `song.dawproj`) without touching the project file.
+    ///
+    /// Returns whether the autosave holds unsaved changes; when the session matches
+    /// the project file no autosave is kept. [`poll`](Self::poll) calls this every
+    /// [`autosave_interval`](Self::autosave_interval) once the project has a file,
+    /// except while an autosave from an earlier session waits to be recovered.
+    pub fn autosave(&mut self) -> anyhow::Result<bool> {
+        self.last_autosave = Instant::now();
+        let path = self
+            .project_path
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No project path set"))?;
+        // Autosaves are replaced as a whole, so there's nothing to keep backups of
+        let options = SaveOptions {
+            backups: 0,
+            ..self.save_options
+        };
+        let unsaved =
+            daw_project::write_autosave(path, |autosave| self.write_project(autosave, &options))?;
+        Ok(unsaved)
+    }
+
+    /// Autosave left behind with unsaved changes by a session that didn't close
+    /// normally, found when this project was loaded.
+    ///
+    /// Stays available until it's recovered, discarded, or replaced by
+    /// [`save_in_place`](Self::save_in_place).
+    pub fn recoverable_autosave(&self) -> Option<&Path> {
+        self.recoverable_autosave
+            .as_deref()
+            .filter(|path| path.exists())
+    }
+
+    /// Replace the session's project with the contents of its
+    /// [`recoverable_autosave`](Self::recoverable_autosave).
+    ///
+    /// The autosave is kept until the project is saved, so crashing again before
+    /// then loses nothing. If the autosave can't be read the session is left as it
+    /// was.
+    pub fn recover_autosave(&mut self) -> anyhow::Result<()> {
+        let autosave = self
+            .recoverable_autosave()
+            .ok_or_else(|| anyhow::anyhow!("No autosave to recover"))?
+            .to_path_buf();
+        // Make sure it parses before handing the cache over to loading it
+        daw_project::load_project_metadata(&autosave)?;
+
+        let cache = std::mem::replace(&mut self.cache, AudioCache::new());
+        let project = daw_project::load_project_with_progress(
+            &autosave,
+            Some(self.engine.sample_rate),
+            &self.path_context(),
+            cache,
+            None,
+            |_| {},
         )?;
+        self.tracks = project.tracks;
+        self.time_context = TimeContext::new(project.tempo, project.time_signature);
+        self.cache = project.cache;
+        self.sample_refs = project.sample_refs;
+        self.offline_clips = project.offline_clips;
+        self.frozen_tracks.clear();
+        self.embedded_dir = project.embedded_dir.or(self.embedded_dir.take());
+        self.name = project.name;
+        self.info = project.info;
+        self.end_behavior = project.end_behavior;
+        self.locators = project.locators;
+        self.tempo_map = project.tempo_map;
+        self.ids = project.ids;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        if let Some(state) = project.session_state {
+            self.restore_session_state(state);
+        }
+        self.restore_master(project.master);
+        self.recoverable_autosave = None;
+        self.sync_tracks_to_engine();
         Ok(())
     }
 
-    pub fn save_in_place(&self) -> anyhow::Result<()> {
+    /// What the [`recoverable_autosave`](Self::recoverable_autosave) changes from
+    /// the saved project, for deciding whether to recover it.
+    pub fn recoverable_autosave_changes(&self) -> anyhow::Result<Vec<ProjectChange>> {
+        let autosave = self
+            .recoverable_autosave()
+            .ok_or_else(|| anyhow::anyhow!("No autosave to recover"))?;
         let path = self
             .project_path
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("No project path set"))?;
-        self.save(path)
+        let saved = daw_project::read_project(path)?;
+        Ok(daw_project::diff(
+            &saved,
+            &daw_project::read_project(autosave)?,
+        ))
+    }
+
+    /// Delete the [`recoverable_autosave`](Self::recoverable_autosave), keeping the
+    /// project as it was saved.
+    pub fn discard_autosave(&mut self) -> anyhow::Result<()> {
+        if let Some(path) = &self.project_path {
+            daw_project::discard_autosave(path)?;
+        }
+        self.recoverable_autosave = None;
+        Ok(())
     }
 
     /// Start playback.
@@ -519,17 +1448,48 @@ impl Session {
     /// If paused: resumes from current position.
     /// Sends a play command to the audio engine via a lock-free queue.
     /// The audio will start playing asynchronously.
+    ///
+    /// The tick playback starts from is remembered for [`stop()`](Self::stop)
+    /// when [`return_to_start`](Self::return_to_start) is on.
+    ///
+    /// Starting from stopped, playback begins the [pre-roll](Self::set_pre_roll_bars)
+    /// before the cursor, after the [count-in](Self::set_count_in_bars) if there is
+    /// one; resuming from a pause does neither.
     pub fn play(&mut self) {
+        if self.playback_state == PlaybackState::Playing {
+            return;
+        }
+
+        let stopped = self.playback_state == PlaybackState::Stopped;
         // Only seek to cursor if we're stopped (not paused)
-        if self.playback_state == PlaybackState::Stopped {
-            if let Some(cursor_tick) = self.cursor_tick {
-                self.seek(cursor_tick);
-            }
+        if stopped && let Some(cursor_tick) = self.cursor_tick {
+            self.seek(cursor_tick);
         }
         // If paused, just resume from current position
+        self.play_start_tick = self.current_tick;
+        if stopped && self.pre_roll_bars > 0 {
+            self.seek(self.current_tick.saturating_sub(self.pre_roll_ticks()));
+        }
 
-        let _ = self.engine.commands.push(EngineCommand::Play);
+        // The stream is running during a count-in, but the position holds still
+        let mut watch_from = Instant::now();
+        match stopped.then(|| self.count_in()).flatten() {
+            Some(count_in) => {
+                watch_from += Duration::from_secs_f64(
+                    count_in.length as f64 / self.engine.sample_rate as f64,
+                );
+                let _ = self
+                    .engine
+                    .commands
+                    .push(EngineCommand::PlayAfterCountIn(count_in));
+            }
+            None => {
+                let _ = self.engine.commands.push(EngineCommand::Play);
+            }
+        }
         self.playback_state = PlaybackState::Playing;
+        self.watchdog
+            .reset(watch_from, self.ticks_to_samples(self.current_tick));
     }
 
     /// Pause playback, maintaining the current position.
@@ -542,13 +1502,15 @@ impl Session {
 
     /// Stop/reset playback.
     ///
-    /// If playing: stops playback (state = Stopped, next play from cursor).
+    /// If playing: stops playback (state = Stopped, next play from cursor). With
+    /// [`return_to_start`](Self::return_to_start) on, the playhead also jumps back
+    /// to where playback started; otherwise it stays where it stopped.
     /// If already stopped: resets to beginning (tick 0, cursor 0).
     pub fn stop(&mut self) {
         if self.playback_state == PlaybackState::Playing {
             // Stop playback - next play will be from cursor
             let _ = self.engine.commands.push(EngineCommand::Pause);
-            self.playback_state = PlaybackState::Stopped;
+            self.finish_playback();
         } else {
             // Already stopped - reset to beginning
             let _ = self.engine.commands.push(EngineCommand::Seek { sample: 0 });
@@ -558,60 +1520,443 @@ impl Session {
         }
     }
 
-    /// Seek to a specific tick position.
+    /// Silence everything right away, for feedback or a runaway loop.
     ///
-    /// The tick is converted to samples based on the current tempo and sent
-    /// to the audio engine.
-    ///
-    /// # Arguments
-    ///
-    /// * `tick` - The tick position to seek to (480 ticks = 1 quarter note at PPQN=480)
-    pub fn seek(&mut self, tick: u64) {
-        let sample = self.ticks_to_samples(tick);
-        let _ = self.engine.commands.push(EngineCommand::Seek { sample });
-        self.current_tick = tick;
+    /// Mutes the master output with a ramp of about a millisecond, short enough to
+    /// be instant but without a click, and stops playback once it is silent. The
+    /// playhead stays where it was. The master stays muted until
+    /// [`set_master_muted(false)`](Self::set_master_muted).
+    pub fn panic(&mut self) {
+        let _ = self.engine.commands.push(EngineCommand::Panic);
+        self.master_muted = true;
+        self.playback_state = PlaybackState::Stopped;
     }
 
-    /// Poll the session for position updates and perform garbage collection.
-    ///
-    /// **This must be called regularly (recommended: 60 Hz / every ~16ms)** to:
-    /// 1. Retrieve playback position updates from the audio engine
-    /// 2. Free memory from old track data via the basedrop collector
-    ///
-    /// Returns `Some(tick)` if the playback position changed since the last poll,
-    /// `None` otherwise.
-    ///
-    /// # Example
-    ///
-    /// ```no_run
-    /// # use daw_core::Session;
-    /// # let mut session = Session::new(vec![], 120.0, (4, 4))?;
-    /// // In your main loop at 60 Hz:
-    /// if let Some(tick) = session.poll() {
-    ///     println!("Playback position: {}", tick);
-    /// }
-    /// # Ok::<(), anyhow::Error>(())
-    /// ```
-    pub fn poll(&mut self) -> Option<u64> {
-        // Free any old track data that the audio thread has dropped
-        self.engine.collector.collect();
+    /// Whether the master output is muted
+    pub fn master_muted(&self) -> bool {
+        self.master_muted
+    }
 
-        let mut position_changed = None;
-        while let Ok(status) = self.engine.status.pop() {
-            match status {
-                EngineStatus::Position(sample) => {
-                    let tick = self.samples_to_ticks(sample);
-                    self.current_tick = tick;
-                    position_changed = Some(tick);
-                }
-            }
-        }
-        position_changed
+    /// Mute or unmute the master output, with a short ramp
+    pub fn set_master_muted(&mut self, muted: bool) {
+        let _ = self
+            .engine
+            .commands
+            .push(EngineCommand::SetMasterMute(muted));
+        self.master_muted = muted;
     }
 
-    // =========================================================================
-    // Internal engine synchronization methods (not part of public API)
-    // =========================================================================
+    /// Gain, balance and limiter on the master bus
+    pub fn master_bus(&self) -> MasterSettings {
+        self.master_bus
+    }
+
+    /// Set the master bus's gain, balance and limiter, with the balance kept
+    /// within -1.0 to 1.0. Playback follows right away, with a short glide on gain
+    /// and balance changes, and renders go through the same processing.
+    pub fn set_master_bus(&mut self, mut settings: MasterSettings) {
+        settings.pan = settings.pan.clamp(-1.0, 1.0);
+        let _ = self
+            .engine
+            .commands
+            .push(EngineCommand::SetMasterBus(settings));
+        self.master_bus = settings;
+    }
+
+    /// Highest sample level on the left and right of the output, as linear gain,
+    /// over the audio played between the last two calls to `poll()`; both are 0.0
+    /// while nothing plays. Mono output reports the same on both sides.
+    pub fn master_peak(&self) -> [f32; 2] {
+        self.master_peak
+    }
+
+    /// The master channel as it's saved with the project
+    fn master_data(&self) -> MasterData {
+        MasterData {
+            gain_db: self.master_bus.gain_db,
+            pan: self.master_bus.pan,
+            limiter: self.master_bus.limiter,
+            effects: self.master_effects.clone(),
+        }
+    }
+
+    /// Take on the master channel saved with a project
+    fn restore_master(&mut self, master: MasterData) {
+        self.set_master_bus(MasterSettings {
+            gain_db: master.gain_db,
+            pan: master.pan,
+            limiter: master.limiter,
+        });
+        self.master_effects = master.effects;
+        self.sync_master_effects();
+    }
+
+    /// Play `audio` once from its start, right away and whether or not the
+    /// arrangement is playing.
+    ///
+    /// `voice` is any id the caller picks: playing another sample under the same id
+    /// cuts off the one before with a short fade, so a retriggered sample doesn't
+    /// pile up on itself. Audio at another sample rate is resampled first, which
+    /// fails only if the resampler does. Goes through the master output, so nothing
+    /// is heard while it is muted.
+    pub fn play_sample(&mut self, voice: u64, audio: &AudioArc, gain: f32) -> anyhow::Result<()> {
+        let audio = audio.resample(self.engine.sample_rate)?;
+        let audio = Shared::new(&self.engine.handle, audio);
+        let _ = self
+            .engine
+            .commands
+            .push(EngineCommand::StartVoice(VoiceStart {
+                id: voice,
+                audio,
+                gain,
+            }));
+        Ok(())
+    }
+
+    /// Fade out the sample playing under `voice`, if any
+    pub fn stop_sample(&mut self, voice: u64) {
+        let _ = self
+            .engine
+            .commands
+            .push(EngineCommand::StopVoice { id: voice });
+    }
+
+    /// Fade out every sample started with [`play_sample`](Self::play_sample),
+    /// including pads
+    pub fn stop_all_samples(&mut self) {
+        let _ = self.engine.commands.push(EngineCommand::StopAllVoices);
+    }
+
+    /// Audition a sample file before putting it in the arrangement.
+    ///
+    /// The file is decoded at the engine's sample rate through the session's cache,
+    /// so previewing a sample that is then inserted doesn't decode it twice. It plays
+    /// once at the [preview volume](Self::set_preview_volume), cutting off the
+    /// previous preview, and leaves the tracks alone. Fails if the file can't be
+    /// decoded.
+    pub fn preview_sample(&mut self, path: &Path) -> anyhow::Result<()> {
+        let audio = self
+            .cache
+            .get_or_load_direct(path, Some(self.engine.sample_rate))?;
+        self.play_sample(PREVIEW_VOICE, &audio, self.preview_volume)
+    }
+
+    /// Fade out the sample being previewed, if any
+    pub fn stop_preview(&mut self) {
+        self.stop_sample(PREVIEW_VOICE);
+    }
+
+    /// Volume of sample previews (0.0 to 1.0)
+    pub fn preview_volume(&self) -> f32 {
+        self.preview_volume
+    }
+
+    /// Set the volume of sample previews, including the one playing now
+    pub fn set_preview_volume(&mut self, volume: f32) {
+        self.preview_volume = volume.clamp(0.0, 1.0);
+        let _ = self.engine.commands.push(EngineCommand::SetVoiceGain {
+            id: PREVIEW_VOICE,
+            gain: self.preview_volume,
+        });
+    }
+
+    /// The clip a track's pad plays: its first audio clip
+    pub fn pad_clip(&self, track_id: u64) -> Option<&Clip> {
+        self.tracks
+            .iter()
+            .find(|track| track.id.0 == track_id)?
+            .clips()
+            .iter()
+            .find(|clip| clip.audio().is_some())
+    }
+
+    /// Tracks that have a pad to play, in track order
+    pub fn pad_tracks(&self) -> Vec<u64> {
+        self.tracks
+            .iter()
+            .filter(|track| self.pad_clip(track.id.0).is_some())
+            .map(|track| track.id.0)
+            .collect()
+    }
+
+    /// Play a track's sample live, like hitting a drum pad.
+    ///
+    /// The pad plays the track's first audio clip as it sounds in the arrangement,
+    /// from where its trim starts, at `velocity` (0.0 to 1.0) times the clip's gain
+    /// and the track's volume. Hitting it again cuts off the
+    /// last hit. Muted tracks, and tracks silenced by another track's solo, stay
+    /// silent. While [pad recording](Self::set_pad_recording) is on and the
+    /// arrangement is playing, the hit is also recorded: a copy of the clip is placed
+    /// at the playhead, snapped to the grid.
+    ///
+    /// Returns whether the pad was played.
+    pub fn trigger_pad(&mut self, track_id: u64, velocity: f32) -> bool {
+        let Some(index) = self.tracks.iter().position(|track| track.id.0 == track_id) else {
+            return false;
+        };
+        let any_soloed = any_soloed(&self.tracks);
+        let mix = effective_mix(&self.tracks)[index];
+        let Some(clip) = self.pad_clip(track_id).cloned() else {
+            return false;
+        };
+        if !mix.audible(any_soloed) {
+            return false;
+        }
+        let Some(audio) = clip.audio() else {
+            return false;
+        };
+
+        let sample_rate = audio.sample_rate();
+        let channels = audio.channels() as usize;
+        let length =
+            self.tempo_curve()
+                .span_to_samples(clip.start_tick, clip.duration_ticks(), sample_rate)
+                as u64;
+        let samples = (0..length)
+            .map_while(|position| {
+                clip_source_frame(
+                    position,
+                    clip.audio_offset,
+                    audio.frames() as u64,
+                    clip.looped,
+                    clip.reversed,
+                )
+            })
+            .flat_map(|frame| {
+                let start = frame as usize * channels;
+                audio.samples()[start..start + channels].iter().copied()
+            })
+            .collect();
+        let hit = AudioArc::new(samples, sample_rate, audio.channels());
+        if self
+            .play_sample(
+                track_id,
+                &hit,
+                velocity.clamp(0.0, 1.0) * clip.gain * mix.volume,
+            )
+            .is_err()
+        {
+            return false;
+        }
+
+        if self.pad_recording && self.is_playing() {
+            let start_tick = self.snap_to_grid(self.current_tick);
+            let length = clip.duration_ticks();
+            self.add_clip(
+                TrackId(track_id),
+                Clip {
+                    start_tick,
+                    end_tick: start_tick + length,
+                    ..clip
+                },
+            );
+        }
+        true
+    }
+
+    /// Play the pad for a MIDI note: [`PAD_BASE_NOTE`] plays the first track with a
+    /// pad, the note above it the second, and so on. `velocity` is the MIDI velocity
+    /// (0 to 127).
+    ///
+    /// Returns whether a pad was played.
+    pub fn trigger_note(&mut self, note: u8, velocity: u8) -> bool {
+        let Some(index) = note.checked_sub(PAD_BASE_NOTE) else {
+            return false;
+        };
+        match self.pad_tracks().get(index as usize) {
+            Some(&track_id) => self.trigger_pad(track_id, velocity as f32 / 127.0),
+            None => false,
+        }
+    }
+
+    /// Whether pads hit during playback are recorded as clips
+    pub fn pad_recording(&self) -> bool {
+        self.pad_recording
+    }
+
+    /// Arm or disarm recording of pad hits (see [`trigger_pad`](Self::trigger_pad))
+    pub fn set_pad_recording(&mut self, armed: bool) {
+        self.pad_recording = armed;
+    }
+
+    /// Seek to a specific tick position.
+    ///
+    /// The tick is converted to samples based on the current tempo and sent
+    /// to the audio engine.
+    ///
+    /// # Arguments
+    ///
+    /// * `tick` - The tick position to seek to (480 ticks = 1 quarter note at PPQN=480)
+    pub fn seek(&mut self, tick: u64) {
+        let sample = self.ticks_to_samples(tick);
+        let _ = self.engine.commands.push(EngineCommand::Seek { sample });
+        self.current_tick = tick;
+        self.watchdog.reset(Instant::now(), sample);
+    }
+
+    /// Poll the session for position updates and perform garbage collection.
+    ///
+    /// **This must be called regularly (recommended: 60 Hz / every ~16ms)** to:
+    /// 1. Retrieve playback position updates from the audio engine
+    /// 2. Free memory from old track data via the basedrop collector
+    /// 3. Restart the audio stream if playback has stalled (see [`SessionEvent`])
+    /// 4. Reload samples changed on disk, while [watching](Self::set_watching_samples)
+    ///
+    /// Returns `Some(tick)` if the playback position changed since the last poll,
+    /// `None` otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// # use daw_core::Session;
+    /// # let mut session = Session::new(vec![], 120.0, (4, 4))?;
+    /// // In your main loop at 60 Hz:
+    /// if let Some(tick) = session.poll() {
+    ///     println!("Playback position: {}", tick);
+    /// }
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    pub fn poll(&mut self) -> Option<u64> {
+        // Free any old track data that the audio thread has dropped
+        self.engine.collector.collect();
+
+        let mut position_changed = None;
+        let mut master_peak: Option<[f32; 2]> = None;
+        while let Ok(status) = self.engine.status.pop() {
+            match status {
+                EngineStatus::Position {
+                    sample,
+                    peak: [left, right],
+                } => {
+                    let tick = self.samples_to_ticks(sample);
+                    self.current_tick = tick;
+                    self.watchdog.report(sample);
+                    position_changed = Some(tick);
+                    let peak = master_peak.get_or_insert([0.0; 2]);
+                    peak[0] = peak[0].max(left);
+                    peak[1] = peak[1].max(right);
+                }
+                EngineStatus::ReachedEnd => {
+                    // Like `stop()` while playing: the next play starts from the cursor
+                    self.finish_playback();
+                    position_changed = Some(self.current_tick);
+                }
+                // Dropped here rather than on the audio thread, the processor before
+                // the plugin it came from
+                #[cfg(feature = "vst3")]
+                EngineStatus::PluginRemoved(insert) => {
+                    let id = insert.id;
+                    drop(insert);
+                    self.removed_plugins.retain(|plugin| plugin.id != id);
+                }
+                #[cfg(not(feature = "vst3"))]
+                EngineStatus::PluginRemoved(_) => {}
+                EngineStatus::MidiInputClosed(_) => {}
+            }
+        }
+        #[cfg(feature = "vst3")]
+        self.unsent_plugin_removals.retain(|&id| {
+            self.engine
+                .commands
+                .push(EngineCommand::RemovePlugin { id })
+                .is_err()
+        });
+        if let Some(peak) = master_peak {
+            self.master_peak = peak;
+        }
+        if let Some(recording) = &mut self.recording {
+            recording.input.read(&mut recording.samples);
+            let dropped = recording.input.take_dropped_frames();
+            if dropped > 0 {
+                self.events.push(SessionEvent::RecordingDropout(dropped));
+            }
+        }
+        self.check_watchdog();
+        self.check_autosave();
+        #[cfg(feature = "watch")]
+        self.check_sample_watcher();
+        position_changed
+    }
+
+    /// Enter the stopped state after the engine was paused, returning to the
+    /// start of playback if that preference is on
+    fn finish_playback(&mut self) {
+        self.playback_state = PlaybackState::Stopped;
+        if self.return_to_start {
+            self.seek(self.play_start_tick);
+        }
+    }
+
+    /// Take the events that happened since the last call
+    pub fn take_events(&mut self) -> Vec<SessionEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Restart the audio stream if it stopped advancing during playback.
+    ///
+    /// On success playback resumes where it froze; otherwise it's paused there,
+    /// and pressing play tries again.
+    fn check_watchdog(&mut self) {
+        if self.playback_state != PlaybackState::Playing || self.engine.is_offline() {
+            return;
+        }
+        let now = Instant::now();
+        if !self.engine.stream_failed() && !self.watchdog.is_stalled(now, self.engine.sample_rate) {
+            return;
+        }
+
+        self.events.push(SessionEvent::AudioStalled);
+        let restarted = self.engine.restart();
+        // The old stream dropped the plugin processors it held, so none come back
+        #[cfg(feature = "vst3")]
+        {
+            self.removed_plugins.clear();
+            self.unsent_plugin_removals.clear();
+        }
+        match restarted {
+            Ok(()) => {
+                // The new stream may run at a different rate and starts empty and paused
+                self.engine_inserts.clear();
+                self.engine_master_inserts.clear();
+                self.sync_tracks_to_engine();
+                self.set_master_muted(self.master_muted);
+                self.set_master_bus(self.master_bus);
+                self.sync_master_effects();
+                #[cfg(feature = "vst3")]
+                self.send_plugins_to_engine();
+                self.reconnect_midi_input();
+                self.seek(self.current_tick);
+                let _ = self.engine.commands.push(EngineCommand::Play);
+                self.events.push(SessionEvent::AudioRestarted);
+            }
+            Err(err) => {
+                self.playback_state = PlaybackState::Paused;
+                self.events
+                    .push(SessionEvent::AudioRestartFailed(err.to_string()));
+            }
+        }
+    }
+
+    /// Autosave once the interval has passed since the last one
+    fn check_autosave(&mut self) {
+        let Some(interval) = self.autosave_interval else {
+            return;
+        };
+        if self.project_path.is_none()
+            || self.recoverable_autosave().is_some()
+            || self.last_autosave.elapsed() < interval
+        {
+            return;
+        }
+        if let Err(err) = self.autosave() {
+            self.events
+                .push(SessionEvent::AutosaveFailed(err.to_string()));
+        }
+    }
+
+    // =========================================================================
+    // Internal engine synchronization methods (not part of public API)
+    // =========================================================================
 
     /// Send updated tracks to the audio engine (lock-free).
     /// Converts tick positions to sample positions.
@@ -628,18 +1973,94 @@ impl Session {
         let mut engine_tracks = self.convert_tracks_for_engine(sample_rate);
 
         // Add metronome track if enabled
-        if self.metronome.enabled {
-            if let Some(metronome_track) = self.generate_metronome_track(sample_rate) {
-                engine_tracks.push(metronome_track);
-            }
+        if self.metronome.enabled
+            && let Some(metronome_track) = self.generate_metronome_track(sample_rate)
+        {
+            engine_tracks.push(metronome_track);
         }
 
+        self.send_inserts_to_engine(&engine_tracks, sample_rate);
         let shared_tracks = Shared::new(&self.engine.handle, engine_tracks);
         let _ = self.engine.tracks.push(shared_tracks);
+
+        let end = self.playback_end(sample_rate);
+        let _ = self.engine.commands.push(EngineCommand::SetEnd(end));
+        self.send_loop_to_engine(sample_rate);
     }
 
-    /// Generate a metronome track with clicks on each beat
-    fn generate_metronome_track(&mut self, sample_rate: u32) -> Option<EngineTrack> {
+    /// Build a new insert chain for each track whose effects changed kind or
+    /// order since its last one, and take away the chains of tracks no longer
+    /// sent. Changed settings only go with the tracks, so tails ring on through
+    /// them. A chain the command queue has no room for is tried again next time.
+    fn send_inserts_to_engine(&mut self, tracks: &[EngineTrack], sample_rate: u32) {
+        if self.engine.is_offline() {
+            return;
+        }
+        let send = |engine: &mut AudioEngineHandle, track: u64, chain: InsertChain| {
+            let inserts = Inserts(Owned::new(&engine.handle, chain));
+            engine
+                .commands
+                .push(EngineCommand::SetTrackInserts { track, inserts })
+                .is_ok()
+        };
+
+        for track in tracks {
+            let built = self
+                .engine_inserts
+                .get(&track.id)
+                .map_or(&[][..], Vec::as_slice);
+            if same_effect_kinds(built, &track.effects) {
+                continue;
+            }
+            let chain = InsertChain::new(&track.effects, sample_rate);
+            if !send(&mut self.engine, track.id, chain) {
+                continue;
+            }
+            if track.effects.is_empty() {
+                self.engine_inserts.remove(&track.id);
+            } else {
+                self.engine_inserts.insert(track.id, track.effects.clone());
+            }
+        }
+        self.engine_inserts.retain(|id, _| {
+            tracks.iter().any(|track| track.id == *id)
+                || !send(&mut self.engine, *id, InsertChain::default())
+        });
+    }
+
+    /// Tell the engine which samples the loop region covers, if looping is on
+    fn send_loop_to_engine(&mut self, sample_rate: u32) {
+        let loop_range = self
+            .loop_region
+            .filter(|region| region.enabled)
+            .map(|region| LoopRange {
+                start: self.ticks_to_samples_with_rate(region.start_tick, sample_rate),
+                end: self.ticks_to_samples_with_rate(region.end_tick, sample_rate),
+            });
+        let _ = self
+            .engine
+            .commands
+            .push(EngineCommand::SetLoop(loop_range));
+    }
+
+    /// Where the engine should end playback for the current end behavior.
+    ///
+    /// An empty arrangement has no end, so the metronome can still run on its own.
+    fn playback_end(&self, sample_rate: u32) -> Option<PlaybackEnd> {
+        let action = match self.end_behavior {
+            EndBehavior::Continue => return None,
+            EndBehavior::Stop => EndAction::Stop,
+            EndBehavior::Loop => EndAction::Loop,
+        };
+        let end_tick = self.end_tick();
+        (end_tick > 0).then(|| PlaybackEnd {
+            sample: self.ticks_to_samples_with_rate(end_tick, sample_rate),
+            action,
+        })
+    }
+
+    /// The metronome's downbeat and other-beat clicks at `sample_rate`
+    fn metronome_sounds(&self, sample_rate: u32) -> Option<(AudioArc, AudioArc)> {
         // Resample metronome samples if needed (cheap clone if already at target rate)
         let hi_audio = if self.metronome.hi.sample_rate() == sample_rate {
             self.metronome.hi.clone()
@@ -652,6 +2073,12 @@ impl Session {
         } else {
             self.metronome.lo.resample(sample_rate).ok()?
         };
+        Some((hi_audio, lo_audio))
+    }
+
+    /// Generate a metronome track with clicks on each beat
+    fn generate_metronome_track(&mut self, sample_rate: u32) -> Option<EngineTrack> {
+        let (hi_audio, lo_audio) = self.metronome_sounds(sample_rate)?;
 
         // Calculate timeline length based on content
         let max_tick = self.max_tick();
@@ -659,36 +2086,87 @@ impl Session {
         let ticks_per_bar = self.time_context.time_signature.ticks_per_bar();
         let end_tick = max_tick + ticks_per_bar * 4;
 
-        // Generate clicks for each beat
-        let beats_per_bar = self.time_context.time_signature.beats_per_bar();
-        let mut clips = Vec::new();
-        let mut current_tick = 0u64;
-        let mut beat_in_bar = 0u32;
-
-        while current_tick < end_tick {
-            let audio = if beat_in_bar == 0 {
-                hi_audio.clone()
-            } else {
-                lo_audio.clone()
-            };
-
-            clips.push(EngineClip {
-                start: self.ticks_to_samples_with_rate(current_tick, sample_rate),
-                audio,
+        let clips = self
+            .metronome_clicks(end_tick)
+            .map(|(tick, downbeat)| EngineClip {
+                start: self.ticks_to_samples_with_rate(tick, sample_rate),
+                audio: if downbeat {
+                    hi_audio.clone()
+                } else {
+                    lo_audio.clone()
+                },
                 offset: 0,
                 length: None,
-            });
-
-            current_tick += PPQN;
-            beat_in_bar = (beat_in_bar + 1) % beats_per_bar;
-        }
+                fade_in: 0,
+                fade_out: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+            })
+            .collect();
 
         Some(EngineTrack {
+            id: u64::MAX,
             clips,
             volume: self.metronome.volume,
+            pan: 0.0,
+            automation: TrackAutomation::default(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+            plugins: Vec::new(),
+            midi_input: false,
+            sampler: None,
+            notes: Vec::new(),
         })
     }
 
+    /// The metronome's clicks as a track ending at `end_tick`, for mixing into
+    /// renders. It plays at the metronome's volume, but whether or not the metronome
+    /// is on.
+    fn metronome_render_track(&self, end_tick: u64) -> Track {
+        let sound = |audio: &AudioArc| {
+            let waveform = Arc::new(WaveformData::from_audio_arc(audio, 512));
+            (audio.clone(), waveform)
+        };
+        let (hi, lo) = (sound(&self.metronome.hi), sound(&self.metronome.lo));
+
+        let mut track = Track::new(TrackId(u64::MAX), "Metronome".to_string());
+        track.volume = self.metronome.volume;
+        // Like in playback, a solo doesn't silence the click
+        track.solo = self.any_track_soloed();
+        let mut ids = IdAllocator::default();
+        for (tick, downbeat) in self.metronome_clicks(end_tick) {
+            let (audio, waveform) = if downbeat { &hi } else { &lo };
+            let clip = Clip {
+                id: ClipId(0),
+                start_tick: tick,
+                end_tick: tick + PPQN,
+                content: ClipContent::Audio(audio.clone()),
+                waveform: waveform.clone(),
+                audio_offset: 0,
+                name: "Click".to_string(),
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source: ClipSource::default(),
+            };
+            track.insert_clip(clip, &mut ids);
+        }
+        track
+    }
+
+    /// Tick of every beat before `end_tick`, and whether it starts a bar
+    fn metronome_clicks(&self, end_tick: u64) -> impl Iterator<Item = (u64, bool)> {
+        let beats_per_bar = self.time_context.time_signature.beats_per_bar() as u64;
+        (0..end_tick)
+            .step_by(PPQN as usize)
+            .enumerate()
+            .map(move |(beat, tick)| (tick, (beat as u64).is_multiple_of(beats_per_bar)))
+    }
+
     /// Get the maximum tick position across all clips.
     ///
     /// This is useful for frontends to determine timeline length for layout purposes.
@@ -703,66 +2181,124 @@ impl Session {
         max_tick
     }
 
+    /// Get the tick where the arrangement ends: the end of the last clip,
+    /// including its fade-out tail.
+    ///
+    /// Unlike an export, muted tracks count, so muting doesn't move the end.
+    /// Returns 0 if there are no clips.
+    pub fn end_tick(&self) -> u64 {
+        self.tracks
+            .iter()
+            .flat_map(|track| track.clips())
+            .map(|clip| clip.end_tick + clip.fade_out_ticks)
+            .max()
+            .unwrap_or(0)
+    }
+
     fn convert_tracks_for_engine(&mut self, sample_rate: u32) -> Vec<EngineTrack> {
         // Build engine tracks from clips, resampling audio if needed
         // Note: Clips already have AudioArc, which makes cloning cheap
-        let any_soloed = self.tracks.iter().any(|t| t.solo);
+        let any_soloed = any_soloed(&self.tracks);
+        let tempo = self.tempo_curve();
+        let ticks_to_samples =
+            |ticks: u64| tempo.ticks_to_samples(ticks as f64, sample_rate) as u64;
+        let span_to_samples =
+            |start: u64, ticks: u64| tempo.span_to_samples(start, ticks, sample_rate) as u64;
 
-        self.tracks
+        // Folder mute, solo and volume apply to every track inside them
+        let mixed: Vec<(&Track, EffectiveMix)> = self
+            .tracks
             .iter()
-            .filter(|track| {
-                // Track must be enabled
-                if !track.enabled {
-                    return false;
-                }
-                // If any track is soloed, only play soloed tracks
-                if any_soloed && !track.solo {
-                    return false;
-                }
-                true
-            })
-            .map(|track| EngineTrack {
+            .zip(effective_mix(&self.tracks))
+            .filter(|(_, mix)| mix.audible(any_soloed))
+            .collect();
+        // Sends find their buses among the tracks that are heard
+        let mixed_tracks: Vec<&Track> = mixed.iter().map(|(track, _)| *track).collect();
+        mixed
+            .iter()
+            .map(|&(track, mix)| EngineTrack {
+                id: track.id.0,
                 clips: track
                     .clips()
                     .iter()
                     .filter_map(|clip| {
+                        // MIDI clips have no audio for the engine to play
+                        let audio = clip.audio()?;
+
                         // Resample audio if not at engine sample rate
                         // If already at target rate, this is just a cheap Arc clone
-                        let audio = if clip.audio.sample_rate() == sample_rate {
-                            clip.audio.clone()
+                        let audio = if audio.sample_rate() == sample_rate {
+                            audio.clone()
                         } else {
-                            clip.audio.resample(sample_rate).ok()?
+                            audio.resample(sample_rate).ok()?
                         };
 
                         // Convert duration from ticks to samples
                         let length_samples =
-                            self.ticks_to_samples_with_rate(clip.duration_ticks(), sample_rate);
+                            span_to_samples(clip.start_tick, clip.duration_ticks());
 
                         Some(EngineClip {
-                            start: self.ticks_to_samples_with_rate(clip.start_tick, sample_rate),
+                            start: ticks_to_samples(clip.start_tick),
                             audio,
                             offset: clip.audio_offset,
                             length: Some(length_samples),
+                            fade_in: span_to_samples(clip.start_tick, clip.fade_in_ticks),
+                            fade_out: span_to_samples(clip.end_tick, clip.fade_out_ticks),
+                            gain: clip.gain,
+                            looped: clip.looped,
+                            reversed: clip.reversed,
                         })
                     })
                     .collect(),
-                volume: track.volume,
+                volume: mix.volume,
+                pan: track.pan,
+                automation: TrackAutomation::new(&self.tracks, track, &tempo, sample_rate),
+                // Folders hold no audio of their own to run through effects
+                effects: if track.is_folder {
+                    Vec::new()
+                } else {
+                    track.effects.clone()
+                },
+                is_bus: track.is_bus,
+                sends: routed_sends(track, &mixed_tracks)
+                    .map(|(bus, send)| EngineSend {
+                        bus,
+                        level: send.level,
+                        pre_fader: send.pre_fader,
+                    })
+                    .collect(),
+                #[cfg(feature = "vst3")]
+                plugins: self
+                    .plugins
+                    .iter()
+                    .filter(|plugin| plugin.track_id == track.id.0 && !track.is_folder)
+                    .map(|plugin| plugin.id)
+                    .collect(),
+                #[cfg(not(feature = "vst3"))]
+                plugins: Vec::new(),
+                midi_input: self.armed_tracks.contains(&track.id.0),
+                sampler: track
+                    .sampler
+                    .as_ref()
+                    .filter(|_| !track.is_folder && !track.is_bus)
+                    .and_then(|sampler| sampler.resample(sample_rate).ok()),
+                notes: sampler_notes(track, &tempo, sample_rate),
             })
             .collect()
     }
 
     fn ticks_to_samples(&self, ticks: u64) -> u64 {
-        self.time_context
-            .ticks_to_samples(ticks, self.engine.sample_rate)
+        self.ticks_to_samples_with_rate(ticks, self.engine.sample_rate)
     }
 
     fn ticks_to_samples_with_rate(&self, ticks: u64, sample_rate: u32) -> u64 {
-        self.time_context.ticks_to_samples(ticks, sample_rate)
+        self.tempo_curve()
+            .ticks_to_samples(ticks as f64, sample_rate) as u64
     }
 
     fn samples_to_ticks(&self, samples: u64) -> u64 {
-        self.time_context
-            .samples_to_ticks(samples, self.engine.sample_rate)
+        self.tempo_curve()
+            .samples_to_ticks(samples as f64, self.engine.sample_rate) as u64
     }
 
     pub fn current_tick(&self) -> u64 {
@@ -805,184 +2341,2709 @@ impl Session {
         self.sync_tempo_to_engine();
     }
 
+    /// Tempo and time signature changes after the start, as saved with the project.
+    ///
+    /// Playback and rendering follow the tempo changes, through
+    /// [`tempo_curve`](Self::tempo_curve). Time signature changes are kept so they
+    /// survive opening and saving the project, but bars and the metronome still
+    /// count in the starting [`time_signature`](Self::time_signature).
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+
+    /// Replace the tempo and time signature changes, putting them in tick order,
+    /// and update the engine with new sample positions
+    pub fn set_tempo_map(&mut self, mut tempo_map: TempoMap) {
+        tempo_map.normalize();
+        self.tempo_map = tempo_map;
+        self.sync_tempo_to_engine();
+    }
+
+    /// The starting tempo with the changes after it, which playback and renders
+    /// both convert ticks to samples through
+    pub fn tempo_curve(&self) -> TempoCurve {
+        TempoCurve::new(
+            self.tempo(),
+            self.tempo_map
+                .tempo_changes
+                .iter()
+                .map(|change| (change.tick, change.bpm)),
+        )
+    }
+
     // Track management methods
 
     /// Replace all tracks. Track's insert_clip handles overlap resolution internally.
+    ///
+    /// Ids used by the new tracks and their clips are never handed out again.
     pub fn set_tracks(&mut self, tracks: Vec<Track>) {
+        self.ids.reserve_tracks(&tracks);
         self.tracks = tracks;
-        self.send_tracks_to_engine(self.engine.sample_rate);
+        let tracks = &self.tracks;
+        self.frozen_tracks
+            .retain(|id, _| tracks.iter().any(|track| track.id.0 == *id));
+        #[cfg(feature = "vst3")]
+        {
+            let ids: HashSet<u64> = self.tracks.iter().map(|track| track.id.0).collect();
+            self.remove_plugins(|plugin| !ids.contains(&plugin.track_id));
+        }
+        self.sync_tracks_to_engine();
+    }
+
+    /// Append an empty track and return its id.
+    pub fn add_track(&mut self, name: String) -> TrackId {
+        let id = self.ids.track_id();
+        self.tracks.push(Track::new(id.clone(), name));
+        self.sync_tracks_to_engine();
+        id
     }
 
-    /// Add a clip to a track. Overlaps are resolved automatically by Track.
-    pub fn add_clip(&mut self, track_id: TrackId, clip: Clip) {
-        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id.0) {
-            track.insert_clip(clip);
-            self.send_tracks_to_engine(self.engine.sample_rate);
+    /// Append an empty aux bus and return its id. Tracks feed it through
+    /// [`set_track_send`](Self::set_track_send).
+    pub fn add_bus_track(&mut self, name: String) -> TrackId {
+        let id = self.ids.track_id();
+        self.tracks.push(Track::new_bus(id.clone(), name));
+        self.sync_tracks_to_engine();
+        id
+    }
+
+    /// Remove a track and its clips.
+    ///
+    /// Removing a folder moves the tracks inside it up one level, and removing a
+    /// bus drops the sends to it. The id is not reused by tracks added later.
+    pub fn remove_track(&mut self, track_id: u64) {
+        let Some(index) = self.tracks.iter().position(|t| t.id.0 == track_id) else {
+            return;
+        };
+
+        let removed = self.tracks.remove(index);
+        self.frozen_tracks.remove(&track_id);
+        self.armed_tracks.remove(&track_id);
+        #[cfg(feature = "vst3")]
+        self.remove_plugins(|plugin| plugin.track_id == track_id);
+        for track in &mut self.tracks {
+            if track.parent.as_ref() == Some(&removed.id) {
+                track.parent = removed.parent.clone();
+            }
+            track.sends.retain(|send| send.bus != removed.id);
         }
+        self.sync_tracks_to_engine();
     }
 
-    /// Set the volume for a specific track
-    pub fn set_track_volume(&mut self, track_id: u64, volume: f32) {
+    /// Rename a track or folder
+    pub fn rename_track(&mut self, track_id: u64, name: String) {
         if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
-            track.volume = volume.clamp(0.0, 1.0);
-            self.send_tracks_to_engine(self.engine.sample_rate);
+            track.name = name;
+            self.sync_tracks_to_engine();
         }
     }
 
-    pub fn sample_rate(&self) -> u32 {
-        self.engine.sample_rate
-    }
+    /// Move a track to `new_index` in the track list, counted among the tracks
+    /// that aren't moving.
+    ///
+    /// A folder moves with everything inside it, and an index past the end moves
+    /// the track to the end. The track stays in the folder it was in; use
+    /// [`set_track_parent`](Self::set_track_parent) to change that.
+    pub fn move_track(&mut self, track_id: u64, new_index: usize) {
+        let track_id = TrackId(track_id);
+        if !self.tracks.iter().any(|t| t.id == track_id) {
+            return;
+        }
+        let moving: Vec<bool> = self
+            .tracks
+            .iter()
+            .map(|track| is_within(&self.tracks, &track.id, &track_id))
+            .collect();
 
-    pub fn render_to_file(&self, path: &Path) -> anyhow::Result<()> {
-        let sample_rate = 44100;
-        let channels = 2;
-        let buffer = render_timeline(&self.tracks, self.tempo(), sample_rate, channels);
-        write_wav(&buffer, path)
+        let mut moved = Vec::new();
+        let mut rest = Vec::new();
+        for (track, moving) in self.tracks.drain(..).zip(moving) {
+            if moving {
+                moved.push(track);
+            } else {
+                rest.push(track);
+            }
+        }
+        let at = new_index.min(rest.len());
+        rest.splice(at..at, moved);
+        self.tracks = rest;
+        self.sync_tracks_to_engine();
     }
 
-    pub fn name(&self) -> &str {
-        &self.name
+    /// Add a clip to a track and return the id it was given. Overlaps are resolved
+    /// automatically by Track.
+    pub fn add_clip(&mut self, track_id: TrackId, clip: Clip) -> Option<ClipId> {
+        self.add_clip_with_crossfade(track_id, clip, 0)
     }
 
-    pub fn set_name(&mut self, name: String) {
-        self.name = name;
-    }
+    /// Add a clip to a track, crossfading into any neighbors it cuts, and return
+    /// the id it was given.
+    ///
+    /// The clip always gets a fresh id; whatever `clip.id` held is replaced. Folder
+    /// tracks can't hold clips, so adding to one does nothing and returns `None`.
+    pub fn add_clip_with_crossfade(
+        &mut self,
+        track_id: TrackId,
+        mut clip: Clip,
+        crossfade_ticks: u64,
+    ) -> Option<ClipId> {
+        let track = self
+            .tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id.0 && !t.is_folder)?;
 
-    pub fn project_path(&self) -> Option<&Path> {
-        self.project_path.as_deref()
+        clip.id = self.ids.clip_id();
+        let id = clip.id;
+        track.insert_clip_with_crossfade(clip, crossfade_ticks, &mut self.ids);
+        self.sync_tracks_to_engine();
+        Some(id)
     }
 
-    pub fn set_project_path(&mut self, path: PathBuf) {
-        self.project_path = Some(path);
+    // Clip editing
+
+    /// Move a clip to `start_tick` on `track_id`, which can be the track it's on or
+    /// another one. Clips it lands on are cut to make room, like an inserted clip.
+    ///
+    /// Returns whether it moved: there has to be such a clip and the track has to
+    /// exist and not be a folder. Can be undone.
+    pub fn move_clip(&mut self, clip_id: ClipId, track_id: u64, start_tick: u64) -> bool {
+        if self.clip_track_index(clip_id).is_none()
+            || !self
+                .tracks
+                .iter()
+                .any(|track| track.id.0 == track_id && !track.is_folder)
+        {
+            return false;
+        }
+        self.record_undo();
+
+        let index = self
+            .clip_track_index(clip_id)
+            .expect("clip was found above");
+        let mut clip = self.tracks[index]
+            .remove_clip(clip_id)
+            .expect("clip was found above");
+        clip.end_tick = start_tick + clip.duration_ticks();
+        clip.start_tick = start_tick;
+        let track = self
+            .tracks
+            .iter_mut()
+            .find(|track| track.id.0 == track_id)
+            .expect("track was found above");
+        track.insert_clip(clip, &mut self.ids);
+        self.sync_tracks_to_engine();
+        true
     }
 
-    pub fn sample_refs(&self) -> &HashMap<String, SampleRef> {
-        &self.sample_refs
+    /// Remove a clip from its track. Returns whether there was such a clip. Can be
+    /// undone.
+    pub fn delete_clip(&mut self, clip_id: ClipId) -> bool {
+        let Some(index) = self.clip_track_index(clip_id) else {
+            return false;
+        };
+        self.record_undo();
+        self.tracks[index].remove_clip(clip_id);
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Put a copy of a clip right after it on the same track and return the copy's
+    /// id. Clips it lands on are cut to make room. Can be undone.
+    pub fn duplicate_clip(&mut self, clip_id: ClipId) -> Option<ClipId> {
+        let index = self.clip_track_index(clip_id)?;
+        self.record_undo();
+
+        let mut copy = self.tracks[index].clip(clip_id)?.clone();
+        copy.id = self.ids.clip_id();
+        let duration = copy.duration_ticks();
+        copy.start_tick = copy.end_tick;
+        copy.end_tick = copy.start_tick + duration;
+        let id = copy.id;
+        self.tracks[index].insert_clip(copy, &mut self.ids);
+        self.sync_tracks_to_engine();
+        Some(id)
+    }
+
+    /// Cut a clip in two at `tick` and return the id of the right part. The two
+    /// play on the way the clip did. Returns `None` if there's no such clip or
+    /// `tick` isn't inside it. Can be undone.
+    pub fn split_clip_at(&mut self, clip_id: ClipId, tick: u64) -> Option<ClipId> {
+        let index = self.clip_track_index(clip_id)?;
+        let clip = self.tracks[index].clip(clip_id)?;
+        if tick <= clip.start_tick || tick >= clip.end_tick {
+            return None;
+        }
+        self.record_undo();
+
+        let tempo = self.tempo_curve();
+        let id = self.tracks[index].split_clip(clip_id, tick, &tempo, &mut self.ids);
+        self.sync_tracks_to_engine();
+        id
+    }
+
+    /// Move a clip's edges to `start_tick` and `end_tick`, keeping its audio where
+    /// it is on the timeline.
+    ///
+    /// Edges stop at the neighboring clips, and the start stops where the audio
+    /// begins. Returns whether the clip was trimmed, which it isn't if there's no
+    /// such clip or the edges would leave it empty. Can be undone.
+    pub fn trim_clip(&mut self, clip_id: ClipId, start_tick: u64, end_tick: u64) -> bool {
+        let Some(index) = self.clip_track_index(clip_id) else {
+            return false;
+        };
+        let before = self.clip_snapshot();
+        let tempo = self.tempo_curve();
+        if !self.tracks[index].trim_clip(clip_id, start_tick, end_tick, &tempo) {
+            return false;
+        }
+        self.push_undo(before);
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Step back to the clips as they were before the last clip edit. Returns
+    /// whether there was an edit to undo.
+    ///
+    /// Only clips are restored; track settings and tracks added or removed since
+    /// are left as they are.
+    pub fn undo(&mut self) -> bool {
+        let Some(snapshot) = self.undo_stack.pop() else {
+            return false;
+        };
+        self.redo_stack.push(self.clip_snapshot());
+        self.restore_clips(snapshot);
+        true
+    }
+
+    /// Redo the last clip edit undone. Returns whether there was one to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(snapshot) = self.redo_stack.pop() else {
+            return false;
+        };
+        self.undo_stack.push(self.clip_snapshot());
+        self.restore_clips(snapshot);
+        true
+    }
+
+    /// Whether there's a clip edit to undo
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    /// Whether there's an undone clip edit to redo
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    /// Index of the track holding the clip with id `clip_id`
+    fn clip_track_index(&self, clip_id: ClipId) -> Option<usize> {
+        self.tracks
+            .iter()
+            .position(|track| track.clip(clip_id).is_some())
+    }
+
+    fn clip_snapshot(&self) -> ClipSnapshot {
+        self.tracks
+            .iter()
+            .map(|track| (track.id.0, track.clips().to_vec()))
+            .collect()
+    }
+
+    /// Remember the clips as they are, before an edit that can be undone
+    fn record_undo(&mut self) {
+        let snapshot = self.clip_snapshot();
+        self.push_undo(snapshot);
+    }
+
+    fn push_undo(&mut self, snapshot: ClipSnapshot) {
+        if self.undo_stack.len() == UNDO_LIMIT {
+            self.undo_stack.remove(0);
+        }
+        self.undo_stack.push(snapshot);
+        self.redo_stack.clear();
+    }
+
+    /// Put back the clips in `snapshot` on the tracks that are still there
+    fn restore_clips(&mut self, snapshot: ClipSnapshot) {
+        for (track_id, clips) in snapshot {
+            let Some(track) = self.tracks.iter_mut().find(|track| track.id.0 == track_id) else {
+                continue;
+            };
+            track.clear_clips();
+            for clip in clips {
+                track.insert_clip(clip, &mut self.ids);
+            }
+        }
+        self.sync_tracks_to_engine();
+    }
+
+    /// Set the volume for a specific track
+    pub fn set_track_volume(&mut self, track_id: u64, volume: f32) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.volume = volume.clamp(0.0, 1.0);
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    /// Sample rate the session plays at
+    pub fn sample_rate(&self) -> u32 {
+        self.engine.sample_rate
+    }
+
+    /// Sample rate saved with the project, which the device is opened at when the
+    /// project is loaded and renders are written at
+    pub fn project_sample_rate(&self) -> Option<u32> {
+        self.project_sample_rate
+    }
+
+    /// Set the sample rate the project prefers, or `None` to use the device's
+    /// default rate and [`DEFAULT_RENDER_SAMPLE_RATE`] for renders. Takes effect
+    /// for playback the next time the project is loaded.
+    pub fn set_project_sample_rate(&mut self, sample_rate: Option<u32>) {
+        self.project_sample_rate = sample_rate.filter(|&rate| rate > 0);
+    }
+
+    /// Whether the project prefers a sample rate the session isn't playing at,
+    /// because the device doesn't support it or the session is offline
+    pub fn sample_rate_mismatch(&self) -> bool {
+        self.project_sample_rate
+            .is_some_and(|rate| rate != self.engine.sample_rate)
+    }
+
+    /// Render the arrangement to a WAV file, or FLAC for a `.flac` path, returning
+    /// its length and levels so frontends can report on the finished render.
+    ///
+    /// The file is written at the project's sample rate, or
+    /// [`DEFAULT_RENDER_SAMPLE_RATE`] if it doesn't name one. Track pan follows the
+    /// equal-power law of [`pan_gains`](daw_transport::pan_gains), and mute and
+    /// solo silence tracks like they do in playback.
+    pub fn render_to_file(&self, path: &Path) -> anyhow::Result<RenderSummary> {
+        self.render_to_file_with(path, &RenderOptions::default())
+    }
+
+    /// Render the arrangement to a WAV file like [`render_to_file`](Self::render_to_file),
+    /// applying `options` to the finished mix and writing it in `options.format`. The
+    /// summary measures the mix as rendered, watermark included.
+    ///
+    /// `options.normalize` turns the mix up or down to a loudness or true peak
+    /// target before the watermark goes on; the summary reports the gain it took.
+    /// Files holding integers are dithered last, as `options.dither` says.
+    ///
+    /// The file is a WAV, FLAC or, with the `vorbis` feature, Ogg Vorbis file as
+    /// `options.file_type` says, or else as the extension of `path` does; other
+    /// extensions fail before anything is rendered.
+    ///
+    /// `options.start_tick` and `options.end_tick` bounce just that part of the
+    /// arrangement, such as a loop; either one left out falls back to the start or
+    /// end of the arrangement, the end followed by `options.tail`. Fails if the
+    /// range is empty.
+    ///
+    /// `options.metronome` mixes in the metronome's click on every beat, at its
+    /// volume. The mix then goes through the [master bus](Self::set_master_bus)
+    /// before it's normalized.
+    ///
+    /// The mix is spread over every core, or `options.threads` of them; the result
+    /// is the same either way. WAV files that aren't normalized are written a few
+    /// blocks at a time as they're mixed, so memory use doesn't grow with the length
+    /// of the render.
+    pub fn render_to_file_with(
+        &self,
+        path: &Path,
+        options: &RenderOptions,
+    ) -> anyhow::Result<RenderSummary> {
+        let sample_rate = self
+            .project_sample_rate
+            .unwrap_or(DEFAULT_RENDER_SAMPLE_RATE);
+        let file_type = match options.file_type {
+            Some(file_type) => file_type,
+            None => RenderFileType::for_path(path)?,
+        };
+        let channels = 2;
+        let start_tick = options.start_tick.unwrap_or(0);
+        let tempo = self.tempo_curve();
+        let end_tick = options.end_tick.unwrap_or_else(|| {
+            let time_signature = self.time_signature();
+            let last_tick = render_end_tick(&self.tracks);
+            let tail = options.tail.map_or(0, |tail| {
+                tail.ticks(
+                    tempo.bpm_at(last_tick),
+                    (time_signature.numerator, time_signature.denominator),
+                )
+            });
+            last_tick + tail
+        });
+        if (options.start_tick.is_some() || options.end_tick.is_some()) && start_tick >= end_tick {
+            anyhow::bail!("render range {}..{} is empty", start_tick, end_tick);
+        }
+        let tracks = if options.metronome {
+            let mut tracks = self.tracks.clone();
+            tracks.push(self.metronome_render_track(end_tick));
+            Cow::Owned(tracks)
+        } else {
+            Cow::Borrowed(&self.tracks[..])
+        };
+        let (tracks, tempo) = (&*tracks, &tempo);
+        let pool = options
+            .threads
+            .map(|threads| rayon::ThreadPoolBuilder::new().num_threads(threads).build())
+            .transpose()?;
+        let mut master =
+            MasterBus::with_effects(self.master_bus, &self.master_effects, sample_rate);
+
+        // Without normalizing, which needs the whole mix measured first, a WAV file
+        // is written as it's mixed so long renders don't have to fit in memory
+        if file_type == RenderFileType::Wav && options.normalize.is_none() {
+            let mut watermark = options
+                .watermark
+                .as_ref()
+                .map(|watermark| watermark.marker(sample_rate))
+                .transpose()?;
+            let mut dither = file_type
+                .integer_bits(options.format)
+                .map(|bits| options.dither.ditherer(bits, channels));
+            let mut processors: Vec<&mut dyn Processor> = vec![&mut master];
+            processors.extend(watermark.as_mut().map(|w| w as &mut dyn Processor));
+            processors.extend(dither.as_mut().map(|d| d as &mut dyn Processor));
+            let mut render = || {
+                render_range_to_wav(
+                    tracks,
+                    tempo,
+                    sample_rate,
+                    channels,
+                    start_tick..end_tick,
+                    &mut processors,
+                    path,
+                    options.format,
+                )
+            };
+            return match pool {
+                Some(pool) => pool.install(render),
+                None => render(),
+            };
+        }
+
+        let render = || render_range(tracks, tempo, sample_rate, channels, start_tick..end_tick);
+        let mut buffer = match pool {
+            Some(pool) => pool.install(render),
+            None => render(),
+        };
+        buffer = process_master(&buffer, &mut master);
+        let mut gain_db = 0.0;
+        if let Some(normalization) = &options.normalize {
+            (buffer, gain_db) = normalization.apply(&buffer);
+        }
+        if let Some(watermark) = &options.watermark {
+            buffer = watermark.apply(&buffer)?;
+        }
+        if let Some(bits) = file_type.integer_bits(options.format) {
+            buffer = options.dither.apply(&buffer, bits);
+        }
+        write_audio_file(&buffer, path, file_type, options.format)?;
+        Ok(RenderSummary {
+            gain_db,
+            ..RenderSummary::of(&buffer, path)
+        })
+    }
+
+    /// Render each track to its own WAV file in `out_dir`, for handing the mix to
+    /// another DAW or a collaborator, returning the files written in track order.
+    ///
+    /// Stems are written at the same rate as [`render_to_file`](Self::render_to_file)
+    /// and all run the length of the full mix, so they line up when imported
+    /// together. Muted tracks, tracks silenced by a solo, folders and tracks without
+    /// audio clips are skipped.
+    pub fn render_stems(&self, out_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+        let sample_rate = self
+            .project_sample_rate
+            .unwrap_or(DEFAULT_RENDER_SAMPLE_RATE);
+        render_stems(&self.tracks, &self.tempo_curve(), sample_rate, out_dir)
+    }
+
+    /// Write the tempo, time signatures, locators and MIDI clips to a Standard MIDI
+    /// File, so the arrangement can be carried on in another DAW.
+    ///
+    /// Each track with MIDI clips becomes a track in the file; audio is left out.
+    pub fn export_midi(&self, path: &Path) -> anyhow::Result<()> {
+        let time_signature = self.time_signature();
+        export_midi(
+            path,
+            &self.name,
+            self.tempo(),
+            (time_signature.numerator, time_signature.denominator),
+            &self.tempo_map,
+            &self.locators,
+            &self.tracks,
+        )?;
+        Ok(())
+    }
+
+    /// Write the arrangement to a DAWproject file, with the audio its clips play,
+    /// so it can be opened in Bitwig Studio, Studio One and other DAWs.
+    ///
+    /// Tracks keep their mix settings and folders; clips whose samples can't be
+    /// found are left out.
+    #[cfg(feature = "dawproject")]
+    pub fn export_dawproject(&self, path: &Path) -> anyhow::Result<()> {
+        let time_signature = self.time_signature();
+        daw_project::export_dawproject(
+            path,
+            &self.name,
+            &self.info,
+            self.tempo(),
+            (time_signature.numerator, time_signature.denominator),
+            &self.tempo_map,
+            &self.locators,
+            &self.tracks,
+            &self.sample_refs,
+            &self.path_context(),
+        )?;
+        Ok(())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// Author, notes, key, and when the project was first and last saved
+    pub fn info(&self) -> &ProjectInfo {
+        &self.info
+    }
+
+    pub fn set_author(&mut self, author: String) {
+        self.info.author = author;
+    }
+
+    /// Set the free-form description and notes saved with the project
+    pub fn set_notes(&mut self, notes: String) {
+        self.info.notes = notes;
+    }
+
+    /// Set the key and scale the song is in, or `None` if it has none
+    pub fn set_key(&mut self, key: Option<MusicalKey>) {
+        self.info.key = key;
+    }
+
+    pub fn project_path(&self) -> Option<&Path> {
+        self.project_path.as_deref()
+    }
+
+    pub fn set_project_path(&mut self, path: PathBuf) {
+        self.project_path = Some(path);
+    }
+
+    pub fn sample_refs(&self) -> &HashMap<String, SampleRef> {
+        &self.sample_refs
+    }
+
+    /// Warm the audio cache with samples that may be needed soon, for frontends to
+    /// call when idle. Returns how many files were decoded.
+    ///
+    /// Clips on muted tracks are kept warm so unmuting one mid-playback doesn't
+    /// stutter, and clips still ahead of the playhead come next; both go nearest to
+    /// the playhead first. Decoding stops once `budget` has passed, finishing the
+    /// file it's on.
+    pub fn prefetch_idle(&mut self, budget: Duration) -> usize {
+        let playhead = self.current_tick;
+        let tracks = &self.tracks;
+        self.prefetcher
+            .run(&mut self.cache, budget, |_, (track_id, start, end)| {
+                let track = tracks.iter().find(|t| t.id == *track_id)?;
+                if !track.enabled {
+                    Some(start.abs_diff(playhead))
+                } else if *end > playhead {
+                    Some(start.saturating_sub(playhead))
+                } else {
+                    // Already played
+                    None
+                }
+            })
+    }
+
+    /// Queue the sample of every clip that can be found for [`Self::prefetch_idle`]
+    fn plan_prefetch(&mut self, ctx: &PathContext) {
+        self.prefetcher.clear();
+        for track in &self.tracks {
+            for clip in track.clips() {
+                if let Some(path) = self
+                    .sample_refs
+                    .get(&clip.name)
+                    .and_then(|sample_ref| ctx.resolve(sample_ref))
+                {
+                    self.prefetcher
+                        .push(path, (track.id.clone(), clip.start_tick, clip.end_tick));
+                }
+            }
+        }
+    }
+
+    // End of arrangement
+
+    pub fn end_behavior(&self) -> EndBehavior {
+        self.end_behavior
+    }
+
+    /// Set whether playback stops, loops or continues after the last clip
+    pub fn set_end_behavior(&mut self, end_behavior: EndBehavior) {
+        self.end_behavior = end_behavior;
+        self.sync_tracks_to_engine();
     }
 
     // Metronome controls
 
-    pub fn metronome_enabled(&self) -> bool {
-        self.metronome.enabled
+    pub fn metronome_enabled(&self) -> bool {
+        self.metronome.enabled
+    }
+
+    pub fn set_metronome_enabled(&mut self, enabled: bool) {
+        self.metronome.enabled = enabled;
+        self.sync_tracks_to_engine();
+    }
+
+    pub fn toggle_metronome(&mut self) {
+        self.set_metronome_enabled(!self.metronome.enabled);
+    }
+
+    pub fn metronome_volume(&self) -> f32 {
+        self.metronome.volume
+    }
+
+    pub fn set_metronome_volume(&mut self, volume: f32) {
+        self.metronome.volume = volume.clamp(0.0, 1.0);
+        if self.metronome.enabled {
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    // Track enabled/disabled controls
+
+    pub fn set_track_enabled(&mut self, track_id: u64, enabled: bool) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.enabled = enabled;
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    pub fn toggle_track_enabled(&mut self, track_id: u64) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.enabled = !track.enabled;
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    // Track pan controls
+
+    pub fn set_track_pan(&mut self, track_id: u64, pan: f32) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.pan = pan.clamp(-1.0, 1.0);
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    // Track automation
+
+    /// A track's lane for `parameter`, such as [`VOLUME_PARAMETER`]
+    pub fn automation_lane(&self, track_id: u64, parameter: &str) -> Option<&AutomationLane> {
+        self.tracks
+            .iter()
+            .find(|t| t.id.0 == track_id)?
+            .automation
+            .iter()
+            .find(|lane| lane.parameter == parameter)
+    }
+
+    /// Add a point to a track's lane for `parameter`, starting the lane if the
+    /// track doesn't have one. Volume and pan are kept in the range their controls
+    /// allow. Returns the point's index in the lane, or `None` if there's no such
+    /// track, it's a folder, or the value isn't a number.
+    pub fn add_automation_point(
+        &mut self,
+        track_id: u64,
+        parameter: &str,
+        tick: u64,
+        value: f32,
+        curve: AutomationCurve,
+    ) -> Option<usize> {
+        if !value.is_finite() {
+            return None;
+        }
+        let track = self
+            .tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id && !t.is_folder)?;
+        let lane = match track
+            .automation
+            .iter()
+            .position(|lane| lane.parameter == parameter)
+        {
+            Some(index) => &mut track.automation[index],
+            None => {
+                track.automation.push(AutomationLane::new(parameter));
+                track.automation.last_mut()?
+            }
+        };
+        let index = lane.add_point(AutomationPoint {
+            tick,
+            value: automation_value(parameter, value),
+            curve,
+        });
+        self.sync_tracks_to_engine();
+        Some(index)
+    }
+
+    /// Move the point at `index` in a track's lane to `tick` and `value`. Returns
+    /// its new index, which changes if it moved past another point.
+    pub fn move_automation_point(
+        &mut self,
+        track_id: u64,
+        parameter: &str,
+        index: usize,
+        tick: u64,
+        value: f32,
+    ) -> Option<usize> {
+        if !value.is_finite() {
+            return None;
+        }
+        let lane = self.automation_lane_mut(track_id, parameter)?;
+        let index = lane.move_point(index, tick, automation_value(parameter, value))?;
+        self.sync_tracks_to_engine();
+        Some(index)
+    }
+
+    /// Change how the point at `index` in a track's lane moves on to the next one
+    pub fn set_automation_curve(
+        &mut self,
+        track_id: u64,
+        parameter: &str,
+        index: usize,
+        curve: AutomationCurve,
+    ) {
+        let Some(point) = self
+            .automation_lane_mut(track_id, parameter)
+            .and_then(|lane| lane.points.get_mut(index))
+        else {
+            return;
+        };
+        point.curve = curve;
+        self.sync_tracks_to_engine();
+    }
+
+    /// Delete the point at `index` in a track's lane. A lane left without points
+    /// is removed, so the track's own control takes over again. Returns whether
+    /// there was such a point.
+    pub fn delete_automation_point(
+        &mut self,
+        track_id: u64,
+        parameter: &str,
+        index: usize,
+    ) -> bool {
+        let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) else {
+            return false;
+        };
+        let Some(lane) = track
+            .automation
+            .iter_mut()
+            .find(|lane| lane.parameter == parameter)
+        else {
+            return false;
+        };
+        if lane.remove_point(index).is_none() {
+            return false;
+        }
+        track.automation.retain(|lane| !lane.points.is_empty());
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    fn automation_lane_mut(
+        &mut self,
+        track_id: u64,
+        parameter: &str,
+    ) -> Option<&mut AutomationLane> {
+        self.tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id)?
+            .automation
+            .iter_mut()
+            .find(|lane| lane.parameter == parameter)
+    }
+
+    // Track effects
+
+    /// A track's insert effects, in the order its audio goes through them
+    pub fn track_effects(&self, track_id: u64) -> &[Effect] {
+        self.tracks
+            .iter()
+            .find(|t| t.id.0 == track_id)
+            .map_or(&[], |track| &track.effects)
+    }
+
+    /// Add `effect` to the end of a track's inserts, such as one made with
+    /// [`Effect::from_kind`]. Returns its index, or `None` if there's no such
+    /// track or it's a folder, which has no audio of its own.
+    pub fn add_track_effect(&mut self, track_id: u64, effect: Effect) -> Option<usize> {
+        let track = self
+            .tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id && !t.is_folder)?;
+        track.effects.push(effect);
+        let index = track.effects.len() - 1;
+        self.sync_tracks_to_engine();
+        Some(index)
+    }
+
+    /// Remove the insert at `index` from a track. Returns whether there was one.
+    pub fn remove_track_effect(&mut self, track_id: u64, index: usize) -> bool {
+        let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) else {
+            return false;
+        };
+        if index >= track.effects.len() {
+            return false;
+        }
+        track.effects.remove(index);
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Move the insert at `from` to `to` in a track's order, shifting the ones in
+    /// between. Returns whether both are within the track's inserts.
+    pub fn move_track_effect(&mut self, track_id: u64, from: usize, to: usize) -> bool {
+        let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) else {
+            return false;
+        };
+        if from >= track.effects.len() || to >= track.effects.len() {
+            return false;
+        }
+        let effect = track.effects.remove(from);
+        track.effects.insert(to, effect);
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Set the parameter called `name` on the insert at `index`, kept within its
+    /// range (see [`Effect::parameters`]). Returns whether the track has such an
+    /// insert with such a parameter.
+    pub fn set_track_effect_parameter(
+        &mut self,
+        track_id: u64,
+        index: usize,
+        name: &str,
+        value: f32,
+    ) -> bool {
+        let Some(effect) = self
+            .tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id)
+            .and_then(|track| track.effects.get_mut(index))
+        else {
+            return false;
+        };
+        if !effect.set_parameter(name, value) {
+            return false;
+        }
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    // Master effects
+
+    /// The master bus's insert effects, in the order the mix goes through them
+    pub fn master_effects(&self) -> &[Effect] {
+        &self.master_effects
+    }
+
+    /// Add `effect` to the end of the master bus's inserts and return its index
+    pub fn add_master_effect(&mut self, effect: Effect) -> usize {
+        self.master_effects.push(effect);
+        self.sync_master_effects();
+        self.master_effects.len() - 1
+    }
+
+    /// Remove the master insert at `index`. Returns whether there was one.
+    pub fn remove_master_effect(&mut self, index: usize) -> bool {
+        if index >= self.master_effects.len() {
+            return false;
+        }
+        self.master_effects.remove(index);
+        self.sync_master_effects();
+        true
+    }
+
+    /// Move the master insert at `from` to `to`, shifting the ones in between.
+    /// Returns whether both are within the master's inserts.
+    pub fn move_master_effect(&mut self, from: usize, to: usize) -> bool {
+        if from >= self.master_effects.len() || to >= self.master_effects.len() {
+            return false;
+        }
+        let effect = self.master_effects.remove(from);
+        self.master_effects.insert(to, effect);
+        self.sync_master_effects();
+        true
+    }
+
+    /// Set the parameter called `name` on the master insert at `index`, kept
+    /// within its range. Returns whether there's such an insert with such a
+    /// parameter.
+    pub fn set_master_effect_parameter(&mut self, index: usize, name: &str, value: f32) -> bool {
+        let Some(effect) = self.master_effects.get_mut(index) else {
+            return false;
+        };
+        if !effect.set_parameter(name, value) {
+            return false;
+        }
+        self.sync_master_effects();
+        true
+    }
+
+    /// Send the master effects to the engine
+    /// Send the master effects to the engine: a new insert chain if which
+    /// effects run changed, built here so the audio thread doesn't allocate, or
+    /// else only their settings
+    fn sync_master_effects(&mut self) {
+        if !self.engine.is_offline()
+            && !same_effect_kinds(&self.engine_master_inserts, &self.master_effects)
+        {
+            let chain = InsertChain::new(&self.master_effects, self.engine.sample_rate);
+            let inserts = Inserts(Owned::new(&self.engine.handle, chain));
+            if self
+                .engine
+                .commands
+                .push(EngineCommand::SetMasterInserts(inserts))
+                .is_ok()
+            {
+                self.engine_master_inserts = self.master_effects.clone();
+            }
+            return;
+        }
+        let effects = Shared::new(&self.engine.handle, self.master_effects.clone());
+        let _ = self
+            .engine
+            .commands
+            .push(EngineCommand::SetMasterEffects(MasterEffects(effects)));
+    }
+
+    // Plugins
+
+    /// The VST3 plugins installed in the platform's standard plugin folders, and
+    /// why each bundle that couldn't be loaded failed
+    #[cfg(feature = "vst3")]
+    pub fn scan_plugins() -> (Vec<Vst3Info>, Vec<PluginError>) {
+        daw_plugins::scan(&daw_plugins::default_search_paths())
+    }
+
+    /// A track's plugins, in the order its audio goes through them after its
+    /// effects
+    #[cfg(feature = "vst3")]
+    pub fn track_plugins(&self, track_id: u64) -> Vec<&Vst3Info> {
+        self.plugins
+            .iter()
+            .filter(|plugin| plugin.track_id == track_id)
+            .map(|plugin| plugin.plugin.info())
+            .collect()
+    }
+
+    /// Load the plugin `info` describes, found with [`scan_plugins`](Self::scan_plugins),
+    /// onto the end of a track's plugins, and return its index among them.
+    ///
+    /// Fails if there's no such track, it's a folder, or the plugin doesn't load.
+    #[cfg(feature = "vst3")]
+    pub fn add_track_plugin(&mut self, track_id: u64, info: &Vst3Info) -> anyhow::Result<usize> {
+        if !self
+            .tracks
+            .iter()
+            .any(|t| t.id.0 == track_id && !t.is_folder)
+        {
+            anyhow::bail!("no track {track_id} to add a plugin to");
+        }
+        let plugin = Vst3Plugin::load(info, self.engine.sample_rate, MAX_BLOCK_FRAMES)?;
+        let id = self.next_plugin_id;
+        // An offline engine has no audio thread to run it
+        if !self.engine.is_offline() {
+            let added = self
+                .engine
+                .commands
+                .push(EngineCommand::AddPlugin(PluginInsert {
+                    id,
+                    processor: Box::new(plugin.processor()),
+                }));
+            if added.is_err() {
+                anyhow::bail!("the audio engine is too busy to add a plugin, try again");
+            }
+        }
+        self.next_plugin_id += 1;
+        self.plugins.push(TrackPlugin {
+            id,
+            track_id,
+            plugin,
+            editor: None,
+        });
+        self.sync_tracks_to_engine();
+        Ok(self.track_plugins(track_id).len() - 1)
+    }
+
+    /// Remove the plugin at `index` from a track, closing its editor. Returns
+    /// whether there was one.
+    #[cfg(feature = "vst3")]
+    pub fn remove_track_plugin(&mut self, track_id: u64, index: usize) -> bool {
+        let Some(id) = self.track_plugin(track_id, index).map(|plugin| plugin.id) else {
+            return false;
+        };
+        self.remove_plugins(|plugin| plugin.id == id);
+        true
+    }
+
+    /// Set a parameter of the plugin at `index` on a track to a normalized value
+    /// between 0.0 and 1.0. Returns whether the track has such a plugin.
+    #[cfg(feature = "vst3")]
+    pub fn set_track_plugin_parameter(
+        &mut self,
+        track_id: u64,
+        index: usize,
+        parameter: u32,
+        value: f64,
+    ) -> bool {
+        let Some(plugin) = self.track_plugin(track_id, index) else {
+            return false;
+        };
+        plugin.plugin.set_parameter(parameter, value);
+        true
+    }
+
+    /// Open the editor of the plugin at `index` on a track inside `parent`, a
+    /// window the frontend opened for it, replacing any editor it had open. Returns
+    /// the size to make the window.
+    ///
+    /// Call from the frontend's main thread, like the other editor methods.
+    #[cfg(feature = "vst3")]
+    pub fn open_plugin_editor(
+        &mut self,
+        track_id: u64,
+        index: usize,
+        parent: EditorParent,
+    ) -> anyhow::Result<EditorSize> {
+        let plugin = self
+            .track_plugin(track_id, index)
+            .ok_or_else(|| anyhow::anyhow!("track {track_id} has no plugin {index}"))?;
+        // The old editor lets go of its window before the new one takes one
+        plugin.editor = None;
+        let editor = plugin.plugin.open_editor(parent)?;
+        let size = editor.size();
+        plugin.editor = Some(editor);
+        Ok(size)
+    }
+
+    /// Close the editor of the plugin at `index` on a track, before its window
+    /// is closed. Returns whether it was open.
+    #[cfg(feature = "vst3")]
+    pub fn close_plugin_editor(&mut self, track_id: u64, index: usize) -> bool {
+        self.track_plugin(track_id, index)
+            .and_then(|plugin| plugin.editor.take())
+            .is_some()
+    }
+
+    /// The size an open editor asked its window to change to since the last call,
+    /// if it did. The editor is told once it's asked, so resize the window to match.
+    #[cfg(feature = "vst3")]
+    pub fn plugin_editor_resize(&mut self, track_id: u64, index: usize) -> Option<EditorSize> {
+        let editor = self.track_plugin(track_id, index)?.editor.as_ref()?;
+        let size = editor.requested_size()?;
+        editor.set_size(size);
+        Some(size)
+    }
+
+    #[cfg(feature = "vst3")]
+    fn track_plugin(&mut self, track_id: u64, index: usize) -> Option<&mut TrackPlugin> {
+        self.plugins
+            .iter_mut()
+            .filter(|plugin| plugin.track_id == track_id)
+            .nth(index)
+    }
+
+    /// Take the plugins that match out of the tracks and the engine, closing
+    /// their editors. They stay loaded until the engine hands their processors
+    /// back in [`poll`](Self::poll).
+    #[cfg(feature = "vst3")]
+    fn remove_plugins(&mut self, mut matches: impl FnMut(&TrackPlugin) -> bool) {
+        let (removed, kept): (Vec<_>, Vec<_>) = std::mem::take(&mut self.plugins)
+            .into_iter()
+            .partition(|plugin| matches(plugin));
+        self.plugins = kept;
+        // Tracks stop listing them before the engine lets them go
+        self.sync_tracks_to_engine();
+        for mut plugin in removed {
+            plugin.editor = None;
+            if self.engine.is_offline() {
+                continue;
+            }
+            let sent = self
+                .engine
+                .commands
+                .push(EngineCommand::RemovePlugin { id: plugin.id });
+            if sent.is_err() {
+                self.unsent_plugin_removals.push(plugin.id);
+            }
+            self.removed_plugins.push(plugin);
+        }
+    }
+
+    /// Hand every plugin to a new engine stream, which starts without any
+    #[cfg(feature = "vst3")]
+    fn send_plugins_to_engine(&mut self) {
+        for plugin in &self.plugins {
+            let _ = self
+                .engine
+                .commands
+                .push(EngineCommand::AddPlugin(PluginInsert {
+                    id: plugin.id,
+                    processor: Box::new(plugin.plugin.processor()),
+                }));
+        }
+    }
+
+    // Sampler
+
+    /// The sampler playing a track's MIDI, if it has one
+    pub fn track_sampler(&self, track_id: u64) -> Option<&Sampler> {
+        self.tracks
+            .iter()
+            .find(|t| t.id.0 == track_id)?
+            .sampler
+            .as_ref()
+    }
+
+    /// Give a track a sampler to play its MIDI clips, and live MIDI input while
+    /// it's armed, or take it away with `None`. Returns whether there's such a
+    /// track; folders and buses can't have one.
+    ///
+    /// The sampler isn't saved with the project yet, like MIDI clips.
+    pub fn set_track_sampler(&mut self, track_id: u64, sampler: Option<Sampler>) -> bool {
+        let Some(track) = self
+            .tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id && !t.is_folder && !t.is_bus)
+        else {
+            return false;
+        };
+        track.sampler = sampler;
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Change the attack, decay, sustain and release of a track's sampler.
+    /// Returns whether the track has a sampler.
+    pub fn set_track_sampler_envelope(&mut self, track_id: u64, envelope: Adsr) -> bool {
+        let Some(sampler) = self
+            .tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id)
+            .and_then(|track| track.sampler.as_mut())
+        else {
+            return false;
+        };
+        sampler.envelope = envelope;
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Decode a sample file for a [`Sampler`], at the engine's sample rate and
+    /// through the session's cache. Fails if the file can't be decoded.
+    pub fn load_sampler_sample(&mut self, path: &Path) -> anyhow::Result<AudioArc> {
+        self.cache
+            .get_or_load_direct(path, Some(self.engine.sample_rate))
+    }
+
+    // Track sends
+
+    /// Send a track to a bus at `level` (0.0 to 1.0), taken before or after the
+    /// track's volume, replacing any send it had to that bus. Returns whether it
+    /// was set: folders and buses can't send, and `bus_id` has to be a bus.
+    pub fn set_track_send(
+        &mut self,
+        track_id: u64,
+        bus_id: u64,
+        level: f32,
+        pre_fader: bool,
+    ) -> bool {
+        let is_bus = self.tracks.iter().any(|t| t.id.0 == bus_id && t.is_bus);
+        let Some(track) = self
+            .tracks
+            .iter_mut()
+            .find(|t| t.id.0 == track_id && !t.is_folder && !t.is_bus)
+        else {
+            return false;
+        };
+        if !is_bus || !level.is_finite() {
+            return false;
+        }
+        let send = TrackSend {
+            bus: TrackId(bus_id),
+            level: level.clamp(0.0, 1.0),
+            pre_fader,
+        };
+        match track.sends.iter_mut().find(|s| s.bus == send.bus) {
+            Some(existing) => *existing = send,
+            None => track.sends.push(send),
+        }
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Stop sending a track to a bus. Returns whether it had a send there.
+    pub fn remove_track_send(&mut self, track_id: u64, bus_id: u64) -> bool {
+        let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) else {
+            return false;
+        };
+        let before = track.sends.len();
+        track.sends.retain(|send| send.bus.0 != bus_id);
+        if track.sends.len() == before {
+            return false;
+        }
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    // Track color and metadata
+
+    /// Set a track's display color (None lets the frontend choose)
+    pub fn set_track_color(&mut self, track_id: u64, color: Option<[u8; 3]>) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.color = color;
+        }
+    }
+
+    /// Set a freeform metadata entry on a track, or remove it when `value` is None
+    pub fn set_track_metadata(&mut self, track_id: u64, key: String, value: Option<String>) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            match value {
+                Some(value) => {
+                    track.metadata.insert(key, value);
+                }
+                None => {
+                    track.metadata.remove(&key);
+                }
+            }
+        }
+    }
+
+    // Track solo controls
+
+    pub fn set_track_solo(&mut self, track_id: u64, solo: bool) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.solo = solo;
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    pub fn toggle_track_solo(&mut self, track_id: u64) {
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.solo = !track.solo;
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    /// Returns true if any track is soloed
+    pub fn any_track_soloed(&self) -> bool {
+        any_soloed(&self.tracks)
+    }
+
+    /// Press a track's solo button, following the solo mode.
+    ///
+    /// With `alternate` (a modifier key held), the other mode is used for this press:
+    /// additive soloing in exclusive mode, and exclusive soloing in additive mode.
+    pub fn solo_track(&mut self, track_id: u64, alternate: bool) {
+        let mode = if alternate {
+            self.solo_mode.alternate()
+        } else {
+            self.solo_mode
+        };
+        match mode {
+            SoloMode::Exclusive => self.solo_track_exclusive(track_id),
+            SoloMode::Additive => self.toggle_track_solo(track_id),
+        }
+    }
+
+    /// Exclusively solo a track - unsolos all other tracks and solos the specified one.
+    /// If the track is already the only soloed track, unsolos it.
+    pub fn solo_track_exclusive(&mut self, track_id: u64) {
+        let is_only_soloed = self.tracks.iter().all(|t| t.id.0 == track_id || !t.solo);
+        let is_this_soloed = self
+            .tracks
+            .iter()
+            .find(|t| t.id.0 == track_id)
+            .map(|t| t.solo)
+            .unwrap_or(false);
+
+        for track in &mut self.tracks {
+            if track.id.0 == track_id {
+                // Toggle off if it's the only soloed track, otherwise solo it
+                track.solo = !(is_only_soloed && is_this_soloed);
+            } else {
+                track.solo = false;
+            }
+        }
+        self.sync_tracks_to_engine();
+    }
+
+    // Track folders
+
+    /// Group tracks into a new folder track and return the folder's id.
+    ///
+    /// The folder takes the place of the first grouped track, inside the folder that
+    /// track was in, and the grouped tracks (with anything nested in them) move
+    /// directly below it. Mute, solo and volume set on the folder apply to the whole
+    /// group. Returns `None` if none of the ids name a track.
+    pub fn group_tracks(&mut self, track_ids: &[u64], name: String) -> Option<u64> {
+        let grouped = |track: &Track| track_ids.contains(&track.id.0);
+        let first = self.tracks.iter().position(grouped)?;
+
+        // Everything that moves: the grouped tracks and their contents
+        let moving: Vec<bool> = self
+            .tracks
+            .iter()
+            .map(|track| {
+                track_ids
+                    .iter()
+                    .any(|&id| is_within(&self.tracks, &track.id, &TrackId(id)))
+            })
+            .collect();
+
+        // Nest the folder in the closest enclosing folder that isn't itself moving
+        let parent = self.tracks[first].parent.as_ref().and_then(|parent| {
+            let parent = self.tracks.iter().find(|t| &t.id == parent)?;
+            track_with_ancestors(&self.tracks, parent)
+                .find(|t| !track_ids.contains(&t.id.0))
+                .map(|t| t.id.clone())
+        });
+
+        let folder_id = self.ids.track_id().0;
+        let mut folder = Track::new_folder(TrackId(folder_id), name);
+        folder.parent = parent;
+
+        // The folder goes where the first grouped track was, among the tracks that stay
+        let insert_at = moving[..first].iter().filter(|&&moving| !moving).count();
+
+        let mut members = Vec::new();
+        let mut rest = Vec::new();
+        for (mut track, moving) in self.tracks.drain(..).zip(moving) {
+            if !moving {
+                rest.push(track);
+                continue;
+            }
+            if grouped(&track) {
+                track.parent = Some(TrackId(folder_id));
+            }
+            members.push(track);
+        }
+
+        rest.splice(insert_at..insert_at, std::iter::once(folder).chain(members));
+        self.tracks = rest;
+        self.sync_tracks_to_engine();
+        Some(folder_id)
+    }
+
+    /// Remove a folder track, moving the tracks inside it up one level.
+    ///
+    /// Does nothing if `folder_id` isn't a folder track.
+    pub fn ungroup_tracks(&mut self, folder_id: u64) {
+        let Some(index) = self
+            .tracks
+            .iter()
+            .position(|t| t.id.0 == folder_id && t.is_folder)
+        else {
+            return;
+        };
+
+        let folder = self.tracks.remove(index);
+        for track in &mut self.tracks {
+            if track.parent.as_ref() == Some(&folder.id) {
+                track.parent = folder.parent.clone();
+            }
+        }
+        self.sync_tracks_to_engine();
+    }
+
+    /// Move a track into a folder track, or to the top level with `None`.
+    ///
+    /// Does nothing if `parent` isn't a folder track or is the track itself or
+    /// nested inside it.
+    pub fn set_track_parent(&mut self, track_id: u64, parent: Option<u64>) {
+        if let Some(parent_id) = parent {
+            let is_folder = self
+                .tracks
+                .iter()
+                .any(|t| t.id.0 == parent_id && t.is_folder);
+            if !is_folder || is_within(&self.tracks, &TrackId(parent_id), &TrackId(track_id)) {
+                return;
+            }
+        }
+
+        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
+            track.parent = parent.map(TrackId);
+            self.sync_tracks_to_engine();
+        }
+    }
+
+    // Cursor and snapping methods
+
+    /// Get the current cursor position in ticks
+    pub fn cursor_tick(&self) -> Option<u64> {
+        self.cursor_tick
+    }
+
+    /// Set the cursor position to a specific tick (applies snapping if enabled)
+    pub fn set_cursor(&mut self, tick: u64) {
+        let snapped_tick = self.snap_to_grid(tick);
+        self.cursor_tick = Some(snapped_tick);
+    }
+
+    /// Clear the cursor position
+    pub fn clear_cursor(&mut self) {
+        self.cursor_tick = None;
+    }
+
+    // Locators
+
+    /// Get the tick stored in locator `n`, if it's set
+    pub fn locator(&self, n: u8) -> Option<u64> {
+        self.locators.get(&n).copied()
+    }
+
+    /// Get all set locators as number -> tick
+    pub fn locators(&self) -> &BTreeMap<u8, u64> {
+        &self.locators
+    }
+
+    /// Store a tick in locator `n` (1-9); other numbers are ignored.
+    ///
+    /// Locators are saved with the project. Unlike the cursor they aren't snapped.
+    pub fn set_locator(&mut self, n: u8, tick: u64) {
+        if LOCATORS.contains(&n) {
+            self.locators.insert(n, tick);
+        }
+    }
+
+    /// Store the playhead in locator `n` while playing, otherwise the cursor
+    pub fn set_locator_here(&mut self, n: u8) {
+        let tick = match (self.is_playing(), self.cursor_tick) {
+            (false, Some(cursor)) => cursor,
+            _ => self.current_tick,
+        };
+        self.set_locator(n, tick);
+    }
+
+    /// Clear locator `n`
+    pub fn clear_locator(&mut self, n: u8) {
+        self.locators.remove(&n);
+    }
+
+    /// Move the cursor and playhead to locator `n`.
+    ///
+    /// Returns false if the locator isn't set. During playback, playback jumps
+    /// there and continues.
+    pub fn goto_locator(&mut self, n: u8) -> bool {
+        let Some(tick) = self.locator(n) else {
+            return false;
+        };
+        self.cursor_tick = Some(tick);
+        self.seek(tick);
+        true
+    }
+
+    // Loop region
+
+    /// Section playback cycles through, with whether looping is on
+    pub fn loop_region(&self) -> Option<LoopRegion> {
+        self.loop_region
+    }
+
+    /// Whether playback is cycling through the loop region
+    pub fn is_looping(&self) -> bool {
+        self.loop_region.is_some_and(|region| region.enabled)
+    }
+
+    /// Loop playback between two ticks, given in either order, and turn looping on.
+    ///
+    /// Playback that reaches the end of the region jumps back to its start; started
+    /// past the region, it plays on as usual. An empty region clears the loop.
+    pub fn set_loop(&mut self, start_tick: u64, end_tick: u64) {
+        let (start_tick, end_tick) = (start_tick.min(end_tick), start_tick.max(end_tick));
+        self.loop_region = (start_tick < end_tick).then_some(LoopRegion {
+            start_tick,
+            end_tick,
+            enabled: true,
+        });
+        self.send_loop_to_engine(self.engine.sample_rate);
+    }
+
+    /// Turn looping on or off, keeping the region. Does nothing without a region.
+    pub fn toggle_loop(&mut self) {
+        if let Some(region) = &mut self.loop_region {
+            region.enabled = !region.enabled;
+            self.send_loop_to_engine(self.engine.sample_rate);
+        }
+    }
+
+    /// Remove the loop region, so playback no longer cycles
+    pub fn clear_loop(&mut self) {
+        self.loop_region = None;
+        self.send_loop_to_engine(self.engine.sample_rate);
+    }
+
+    // Punch region
+
+    /// Section recordings are kept to, with whether punching in is on
+    pub fn punch_region(&self) -> Option<PunchRegion> {
+        self.punch_region
+    }
+
+    /// Whether recordings are kept to the punch region
+    pub fn is_punching(&self) -> bool {
+        self.punch_region.is_some_and(|region| region.enabled)
+    }
+
+    /// Punch in between two ticks, given in either order, and turn punching on.
+    ///
+    /// Recording and playback run on as usual, but
+    /// [`stop_recording`](Self::stop_recording) only keeps the part of the take
+    /// inside the region, so only that stretch of the armed tracks is replaced. An
+    /// empty region clears it.
+    pub fn set_punch(&mut self, start_tick: u64, end_tick: u64) {
+        let (start_tick, end_tick) = (start_tick.min(end_tick), start_tick.max(end_tick));
+        self.punch_region = (start_tick < end_tick).then_some(PunchRegion {
+            start_tick,
+            end_tick,
+            enabled: true,
+        });
+    }
+
+    /// Turn punching in on or off, keeping the region. Does nothing without a
+    /// region.
+    pub fn toggle_punch(&mut self) {
+        if let Some(region) = &mut self.punch_region {
+            region.enabled = !region.enabled;
+        }
+    }
+
+    /// Remove the punch region, so whole takes are kept again
+    pub fn clear_punch(&mut self) {
+        self.punch_region = None;
+    }
+
+    // Count-in and pre-roll
+
+    /// Bars of metronome played before playback starts, 0 for none
+    pub fn count_in_bars(&self) -> u32 {
+        self.count_in_bars
+    }
+
+    /// Count in this many bars of metronome before playback or recording starts
+    /// from stopped, up to [`MAX_COUNT_IN_BARS`]. The count-in clicks whether or not
+    /// the metronome is on, at its volume and the tempo where playback starts.
+    pub fn set_count_in_bars(&mut self, bars: u32) {
+        self.count_in_bars = bars.min(MAX_COUNT_IN_BARS);
+    }
+
+    /// Bars before the cursor that playback starts from, 0 for none
+    pub fn pre_roll_bars(&self) -> u32 {
+        self.pre_roll_bars
+    }
+
+    /// Start playback from stopped this many bars before the cursor, or the start
+    /// of the arrangement if that comes first. Recordings still begin at the
+    /// cursor, and stopping returns to it.
+    pub fn set_pre_roll_bars(&mut self, bars: u32) {
+        self.pre_roll_bars = bars;
+    }
+
+    fn pre_roll_ticks(&self) -> u64 {
+        self.time_context.time_signature.ticks_per_bar() * self.pre_roll_bars as u64
+    }
+
+    /// Frames the count-in lasts at `sample_rate`, at the tempo at the playhead
+    fn count_in_frames(&self, sample_rate: u32) -> u64 {
+        let ticks = self.time_context.time_signature.ticks_per_bar() * self.count_in_bars as u64;
+        TempoCurve::constant(self.tempo_curve().bpm_at(self.current_tick))
+            .span_to_samples(0, ticks, sample_rate)
+            .round() as u64
+    }
+
+    /// The count-in to play before playback starts from the playhead, or `None`
+    /// if it's off
+    fn count_in(&self) -> Option<CountIn> {
+        if self.count_in_bars == 0 {
+            return None;
+        }
+        let sample_rate = self.engine.sample_rate;
+        let length = self.count_in_frames(sample_rate);
+        let beats_per_bar = self.time_context.time_signature.beats_per_bar() as u64;
+        let beats = self.count_in_bars as u64 * beats_per_bar;
+
+        // Without click sounds it still counts in, silently
+        let clips = self
+            .metronome_sounds(sample_rate)
+            .map(|(hi, lo)| {
+                (0..beats)
+                    .map(|beat| EngineClip {
+                        start: beat * length / beats,
+                        audio: if beat.is_multiple_of(beats_per_bar) {
+                            hi.clone()
+                        } else {
+                            lo.clone()
+                        },
+                        offset: 0,
+                        length: None,
+                        fade_in: 0,
+                        fade_out: 0,
+                        gain: 1.0,
+                        looped: false,
+                        reversed: false,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let clicks = vec![EngineTrack {
+            id: u64::MAX,
+            clips,
+            volume: self.metronome.volume,
+            pan: 0.0,
+            automation: TrackAutomation::default(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+            plugins: Vec::new(),
+            midi_input: false,
+            sampler: None,
+            notes: Vec::new(),
+        }];
+        Some(CountIn {
+            clicks: Shared::new(&self.engine.handle, clicks),
+            length,
+            position: 0,
+        })
+    }
+
+    /// Get the current snap mode
+    pub fn snap_mode(&self) -> SnapMode {
+        self.snap_mode
+    }
+
+    /// Set the snap mode
+    pub fn set_snap_mode(&mut self, mode: SnapMode) {
+        self.snap_mode = mode;
+    }
+
+    /// Arrangement zoom saved with the project, as last given to
+    /// [`set_zoom`](Self::set_zoom)
+    pub fn zoom(&self) -> Option<f32> {
+        self.zoom
+    }
+
+    /// Record the frontend's arrangement zoom (e.g. pixels per beat) so it's saved
+    /// with the project and restored when it's opened.
+    ///
+    /// The session itself has no use for it; frontends keep their own zoom and
+    /// report it here when it changes.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = Some(zoom);
+    }
+
+    /// Get what pressing a solo button does to the other tracks
+    pub fn solo_mode(&self) -> SoloMode {
+        self.solo_mode
+    }
+
+    /// Set what pressing a solo button does to the other tracks
+    pub fn set_solo_mode(&mut self, mode: SoloMode) {
+        self.solo_mode = mode;
+    }
+
+    /// Get the length used for clips inserted from one-shot samples
+    pub fn insert_length(&self) -> InsertLength {
+        self.insert_length
+    }
+
+    /// Set the length used for clips inserted from one-shot samples
+    pub fn set_insert_length(&mut self, insert_length: InsertLength) {
+        self.insert_length = insert_length;
+    }
+
+    /// Whether stopping playback returns the playhead to where playback started
+    pub fn return_to_start(&self) -> bool {
+        self.return_to_start
+    }
+
+    /// Set whether stopping playback returns the playhead to where it started
+    pub fn set_return_to_start(&mut self, enabled: bool) {
+        self.return_to_start = enabled;
+    }
+
+    /// Get the tick the current or most recent playback started from
+    pub fn play_start_tick(&self) -> u64 {
+        self.play_start_tick
+    }
+
+    /// Insert a one-shot sample on a track at the cursor position.
+    ///
+    /// The clip length follows the current insert length setting, computed through
+    /// the tempo map from the cursor. The clip's source info records the sample's path and rate,
+    /// plus any tempo and key named in its file name. Does nothing if the cursor
+    /// is not set.
+    pub fn insert_sample_at_cursor(
+        &mut self,
+        track_id: TrackId,
+        audio: AudioArc,
+        sample_ref: SampleRef,
+        name: String,
+    ) {
+        let Some(start_tick) = self.cursor_tick else {
+            return;
+        };
+
+        let length = self.insert_length.length_ticks(
+            &audio,
+            start_tick,
+            &self.tempo_curve(),
+            self.time_signature(),
+        );
+        let waveform = Arc::new(WaveformData::from_audio_arc(&audio, 512));
+        let source = ClipSource::from_file(sample_ref.path(), audio.sample_rate());
+        self.sample_refs.insert(name.clone(), sample_ref);
+        self.add_clip(
+            track_id,
+            Clip {
+                // Assigned by add_clip
+                id: ClipId(0),
+                start_tick,
+                end_tick: start_tick + length,
+                content: ClipContent::Audio(audio),
+                waveform,
+                audio_offset: 0,
+                name,
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source,
+            },
+        );
+    }
+
+    /// Import an audio file onto a track at `tick`, as for a file dropped on the
+    /// arrangement, and return the new clip's id.
+    ///
+    /// The file is decoded at the engine's sample rate through the session's cache
+    /// and the clip spans all of it at the tempos from `tick` on. It's named after
+    /// the file, with a number added if another sample already has that name, and
+    /// the project refers to the file relative to the project, dev root or a sample
+    /// library where it can. Fails if there's no such track, it's a folder, or the
+    /// file can't be decoded.
+    pub fn import_audio_file(
+        &mut self,
+        path: &Path,
+        track_id: TrackId,
+        tick: u64,
+    ) -> anyhow::Result<ClipId> {
+        if !self
+            .tracks
+            .iter()
+            .any(|track| track.id == track_id && !track.is_folder)
+        {
+            anyhow::bail!("No track {} to import into", track_id.0);
+        }
+        let track = track_id.0;
+        let path = std::path::absolute(path)?;
+        let original = self.cache.get_or_load_direct(&path, None)?;
+        let audio = self
+            .cache
+            .get_or_load_direct(&path, Some(self.engine.sample_rate))?;
+        let sample_ref = self.path_context().sample_ref_for(&path);
+
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Audio".to_string());
+        let mut name = stem.clone();
+        let mut number = 2;
+        while self
+            .sample_refs
+            .get(&name)
+            .is_some_and(|existing| *existing != sample_ref)
+        {
+            name = format!("{} {}", stem, number);
+            number += 1;
+        }
+
+        let length = InsertLength::AudioLength.length_ticks(
+            &audio,
+            tick,
+            &self.tempo_curve(),
+            self.time_signature(),
+        );
+        let waveform = Arc::new(WaveformData::from_audio_arc(&audio, 512));
+        let source = ClipSource::from_file(sample_ref.path(), original.sample_rate());
+        self.sample_refs.insert(name.clone(), sample_ref);
+        let id = self.add_clip(
+            track_id,
+            Clip {
+                // Assigned by add_clip
+                id: ClipId(0),
+                start_tick: tick,
+                end_tick: tick + length,
+                content: ClipContent::Audio(audio),
+                waveform,
+                audio_offset: 0,
+                name,
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source,
+            },
+        );
+        id.ok_or_else(|| anyhow::anyhow!("No track {} to import into", track))
+    }
+
+    /// Write recorded audio into the project's `audio` folder as a new WAV.
+    ///
+    /// Returns the audio at the engine's sample rate, ready to put in a clip, with
+    /// the reference to store for it (`audio/recording-001.wav`, say). The project
+    /// has to be saved first so the folder has a place; a file that's already taken
+    /// is never overwritten.
+    pub fn ingest_recording(&mut self, audio: &AudioArc) -> anyhow::Result<(AudioArc, SampleRef)> {
+        if self.project_path.is_none() {
+            anyhow::bail!("save the project before recording into it");
+        }
+        let ctx = self.path_context();
+        let sample_ref = daw_project::ingest_recording(&mut self.cache, audio, &ctx)?;
+        let path = ctx
+            .resolve(&sample_ref)
+            .ok_or_else(|| anyhow::anyhow!("recording not found after writing it"))?;
+        let audio = self
+            .cache
+            .get_or_load_direct(&path, Some(self.engine.sample_rate))?;
+        Ok((audio, sample_ref))
+    }
+
+    // MIDI input
+
+    /// Names of the MIDI input devices connected now, to pick from with
+    /// [`set_midi_input_device`](Self::set_midi_input_device)
+    pub fn midi_input_devices() -> anyhow::Result<Vec<String>> {
+        Ok(daw_midi::input_devices()?)
     }
 
-    pub fn set_metronome_enabled(&mut self, enabled: bool) {
-        self.metronome.enabled = enabled;
-        self.send_tracks_to_engine(self.engine.sample_rate);
+    /// The MIDI input device played on the armed tracks, if any
+    pub fn midi_input_device(&self) -> Option<&str> {
+        self.midi_input.as_ref().map(MidiInput::device)
     }
 
-    pub fn toggle_metronome(&mut self) {
-        self.set_metronome_enabled(!self.metronome.enabled);
+    /// Play a MIDI input device on the armed tracks, whether or not the
+    /// arrangement is playing, or stop listening to MIDI input with `None`.
+    ///
+    /// Fails in an offline session, if the device can't be opened or if the
+    /// engine's command queue is full, leaving the device chosen before in place.
+    /// The choice isn't saved with the project.
+    pub fn set_midi_input_device(&mut self, device: Option<&str>) -> anyhow::Result<()> {
+        let Some(device) = device else {
+            self.midi_input = None;
+            let _ = self.engine.commands.push(EngineCommand::SetMidiInput(None));
+            return Ok(());
+        };
+        if self.engine.is_offline() {
+            anyhow::bail!("offline sessions have no audio to play MIDI input on");
+        }
+        let (input, receiver) = MidiInput::open(device)?;
+        let sent = self
+            .engine
+            .commands
+            .push(EngineCommand::SetMidiInput(Some(receiver)));
+        if sent.is_err() {
+            anyhow::bail!("the audio engine is too busy to take MIDI input, try again");
+        }
+        self.midi_input = Some(input);
+        Ok(())
     }
 
-    pub fn metronome_volume(&self) -> f32 {
-        self.metronome.volume
+    /// Open the MIDI input device again for a restarted engine, which starts
+    /// without one. Dropped with an event if it's gone.
+    fn reconnect_midi_input(&mut self) {
+        let Some(device) = self.midi_input_device().map(str::to_string) else {
+            return;
+        };
+        if let Err(err) = self.set_midi_input_device(Some(&device)) {
+            self.midi_input = None;
+            self.events
+                .push(SessionEvent::MidiInputLost(err.to_string()));
+        }
     }
 
-    pub fn set_metronome_volume(&mut self, volume: f32) {
-        self.metronome.volume = volume.clamp(0.0, 1.0);
-        if self.metronome.enabled {
-            self.send_tracks_to_engine(self.engine.sample_rate);
+    // Recording
+
+    /// Arm or disarm a track for recording. Armed tracks each get a clip of the
+    /// take when [`stop_recording`](Self::stop_recording) finishes it; folders
+    /// can't be armed.
+    pub fn set_track_armed(&mut self, track_id: u64, armed: bool) {
+        if !self
+            .tracks
+            .iter()
+            .any(|track| track.id.0 == track_id && !track.is_folder)
+        {
+            return;
+        }
+        if armed {
+            self.armed_tracks.insert(track_id);
+        } else {
+            self.armed_tracks.remove(&track_id);
         }
+        // Armed tracks play live MIDI input
+        self.sync_tracks_to_engine();
     }
 
-    // Track enabled/disabled controls
+    /// Whether a track is armed for recording
+    pub fn is_track_armed(&self, track_id: u64) -> bool {
+        self.armed_tracks.contains(&track_id)
+    }
 
-    pub fn set_track_enabled(&mut self, track_id: u64, enabled: bool) {
-        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
-            track.enabled = enabled;
-            self.send_tracks_to_engine(self.engine.sample_rate);
+    /// Whether a take is being recorded
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Start recording the default input device onto the armed tracks.
+    ///
+    /// Playback starts if it isn't running, from the cursor like
+    /// [`play()`](Self::play), and the take is placed where it started. Input is
+    /// gathered on [`poll()`](Self::poll). Fails in an offline session, without an
+    /// input device, or for the reasons [`start_recording_with`](Self::start_recording_with)
+    /// does.
+    pub fn start_recording(&mut self) -> anyhow::Result<()> {
+        if self.engine.is_offline() {
+            anyhow::bail!("offline sessions have no audio input to record");
         }
+        self.check_can_record()?;
+        let input = AudioInput::open()?;
+        self.start_recording_with(input)
     }
 
-    pub fn toggle_track_enabled(&mut self, track_id: u64) {
-        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
-            track.enabled = !track.enabled;
-            self.send_tracks_to_engine(self.engine.sample_rate);
+    /// Start recording like [`start_recording`](Self::start_recording), taking the
+    /// audio from `input`. Fails if a take is already being recorded, no track is
+    /// armed, or the project hasn't been saved, since the take is written into its
+    /// `audio` folder.
+    pub fn start_recording_with(&mut self, input: AudioInput) -> anyhow::Result<()> {
+        self.check_can_record()?;
+        let was_playing = self.is_playing();
+        let counts_in = self.playback_state == PlaybackState::Stopped && self.count_in_bars > 0;
+        self.play();
+        let start_tick = if was_playing {
+            self.current_tick
+        } else {
+            self.play_start_tick
+        };
+
+        // Input during the count-in and pre-roll is heard but not kept
+        let tempo = self.tempo_curve();
+        let mut lead_in = tempo.ticks_to_seconds(start_tick as f64)
+            - tempo.ticks_to_seconds(self.current_tick as f64);
+        if counts_in {
+            let sample_rate = self.engine.sample_rate;
+            lead_in += self.count_in_frames(sample_rate) as f64 / sample_rate as f64;
         }
+        let lead_in =
+            (lead_in * input.sample_rate() as f64).round() as usize * input.channels() as usize;
+        let tracks = self
+            .tracks
+            .iter()
+            .map(|track| track.id.0)
+            .filter(|id| self.armed_tracks.contains(id))
+            .collect();
+        self.recording = Some(Recording {
+            input,
+            samples: Vec::new(),
+            start_tick,
+            lead_in,
+            tracks,
+        });
+        Ok(())
     }
 
-    // Track pan controls
+    fn check_can_record(&self) -> anyhow::Result<()> {
+        if self.recording.is_some() {
+            anyhow::bail!("already recording");
+        }
+        if self.project_path.is_none() {
+            anyhow::bail!("save the project before recording into it");
+        }
+        if !self
+            .tracks
+            .iter()
+            .any(|track| self.armed_tracks.contains(&track.id.0))
+        {
+            anyhow::bail!("no track is armed for recording");
+        }
+        Ok(())
+    }
 
-    pub fn set_track_pan(&mut self, track_id: u64, pan: f32) {
-        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
-            track.pan = pan.clamp(-1.0, 1.0);
-            self.send_tracks_to_engine(self.engine.sample_rate);
+    /// Stop recording and playback, and put the take on the tracks that were armed
+    /// when it started. Returns the new clips' ids, none if nothing was recorded.
+    ///
+    /// The take is written into the project's `audio` folder as a new WAV
+    /// (`audio/recording-001.wav`, say) and every clip plays that one file. Clips
+    /// already where the take lands are cut to make room. While
+    /// [punching in](Self::set_punch), the clips only cover the part of the take
+    /// inside the punch region, and none are made if the take missed it. Can be
+    /// undone as one edit.
+    pub fn stop_recording(&mut self) -> anyhow::Result<Vec<ClipId>> {
+        let Some(mut recording) = self.recording.take() else {
+            return Ok(Vec::new());
+        };
+        if self.playback_state == PlaybackState::Playing {
+            self.stop();
+        }
+        recording.input.read(&mut recording.samples);
+        let dropped = recording.input.take_dropped_frames();
+        if dropped > 0 {
+            self.events.push(SessionEvent::RecordingDropout(dropped));
+        }
+        let lead_in = recording.lead_in.min(recording.samples.len());
+        recording.samples.drain(..lead_in);
+        let channels = recording.input.channels();
+        if recording.samples.len() < channels as usize {
+            return Ok(Vec::new());
+        }
+
+        let take = AudioArc::new(recording.samples, recording.input.sample_rate(), channels);
+        let tempo = self.tempo_curve();
+        let start_tick = recording.start_tick;
+        let end_seconds = tempo.ticks_to_seconds(start_tick as f64) + take.duration_secs();
+        let end_tick = (tempo.seconds_to_ticks(end_seconds).round() as u64).max(start_tick + 1);
+        let mut range = start_tick..end_tick;
+        if let Some(punch) = self.punch_region.filter(|region| region.enabled) {
+            range = range.start.max(punch.start_tick)..range.end.min(punch.end_tick);
+        }
+        if range.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (audio, sample_ref) = self.ingest_recording(&take)?;
+        let sample_rate = self.engine.sample_rate;
+        let mut clip = self.ingested_clip(audio, sample_ref, range.clone());
+        // The whole take is kept in the file, so a punched clip can be trimmed out
+        clip.audio_offset = (tempo.ticks_to_samples(range.start as f64, sample_rate)
+            - tempo.ticks_to_samples(start_tick as f64, sample_rate))
+        .round() as u64;
+
+        self.record_undo();
+        let mut ids = Vec::new();
+        for track in &mut self.tracks {
+            if !recording.tracks.contains(&track.id.0) {
+                continue;
+            }
+            let mut clip = clip.clone();
+            clip.id = self.ids.clip_id();
+            ids.push(clip.id);
+            track.insert_clip(clip, &mut self.ids);
         }
+        self.sync_tracks_to_engine();
+        Ok(ids)
     }
 
-    // Track solo controls
+    /// Render a clip on its own, with its trim and fades baked in.
+    ///
+    /// The audio starts at the clip's start and runs through its crossfade tail, at
+    /// the engine's sample rate and without the track's volume or pan, so it can take
+    /// the clip's place. Returns `None` if there's no such clip or it holds MIDI.
+    pub fn bounce_clip(&self, track_id: u64, clip_id: ClipId) -> Option<AudioArc> {
+        let clip = self
+            .tracks
+            .iter()
+            .find(|track| track.id.0 == track_id)?
+            .clips()
+            .iter()
+            .find(|clip| clip.id == clip_id)?;
+        render_clip(clip, &self.tempo_curve(), self.engine.sample_rate)
+    }
 
-    pub fn set_track_solo(&mut self, track_id: u64, solo: bool) {
-        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
-            track.solo = solo;
-            self.send_tracks_to_engine(self.engine.sample_rate);
+    /// Bounce a clip like [`bounce_clip`](Self::bounce_clip) and write it into the
+    /// project's `audio` folder as a new WAV.
+    ///
+    /// Returns the cached audio with the reference to store for it
+    /// (`audio/bounce-001.wav`, say). Like recordings, the project has to be saved
+    /// first and existing files are never overwritten. The clip itself is left as it
+    /// is; putting the bounce in its place is up to the caller.
+    pub fn bounce_clip_to_project(
+        &mut self,
+        track_id: u64,
+        clip_id: ClipId,
+    ) -> anyhow::Result<(AudioArc, SampleRef)> {
+        if self.project_path.is_none() {
+            anyhow::bail!("save the project before bouncing into it");
         }
+        let bounced = self
+            .bounce_clip(track_id, clip_id)
+            .ok_or_else(|| anyhow::anyhow!("no audio clip {} on track {}", clip_id.0, track_id))?;
+        let ctx = self.path_context();
+        let sample_ref = daw_project::ingest_bounce(&mut self.cache, &bounced, &ctx)?;
+        let path = ctx
+            .resolve(&sample_ref)
+            .ok_or_else(|| anyhow::anyhow!("bounce not found after writing it"))?;
+        let audio = self
+            .cache
+            .get_or_load_direct(&path, Some(self.engine.sample_rate))?;
+        Ok((audio, sample_ref))
     }
 
-    pub fn toggle_track_solo(&mut self, track_id: u64) {
-        if let Some(track) = self.tracks.iter_mut().find(|t| t.id.0 == track_id) {
-            track.solo = !track.solo;
-            self.send_tracks_to_engine(self.engine.sample_rate);
+    /// Render the ticks in `range` and put the result on `track_id` as a new clip
+    /// spanning them, for bouncing a section in place or freezing tracks. Returns
+    /// the new clip's id.
+    ///
+    /// The mix is taken as it's heard, with mute, solo, volume and pan, at the
+    /// engine's sample rate but without the master bus, so the clip can play through
+    /// it again; mute what was bounced so it isn't heard twice. Clips already on the
+    /// track within the range are cut to make room. Like
+    /// [`bounce_clip_to_project`](Self::bounce_clip_to_project), the audio is
+    /// written into the project's `audio` folder (`audio/bounce-001.wav`, say), so
+    /// the project has to be saved first. Fails if the range is empty or there's no
+    /// such track or it's a folder.
+    pub fn bounce_selection_to_new_clip(
+        &mut self,
+        range: Range<u64>,
+        track_id: TrackId,
+    ) -> anyhow::Result<ClipId> {
+        if self.project_path.is_none() {
+            anyhow::bail!("save the project before bouncing into it");
+        }
+        if range.is_empty() {
+            anyhow::bail!("bounce range {}..{} is empty", range.start, range.end);
+        }
+        if !self
+            .tracks
+            .iter()
+            .any(|track| track.id == track_id && !track.is_folder)
+        {
+            anyhow::bail!("No track {} to bounce into", track_id.0);
         }
+        let track = track_id.0;
+
+        let bounced = render_range(
+            &self.tracks,
+            &self.tempo_curve(),
+            self.engine.sample_rate,
+            2,
+            range.clone(),
+        );
+        let clip = self.ingest_bounce_clip(&bounced, range)?;
+        self.add_clip(track_id, clip)
+            .ok_or_else(|| anyhow::anyhow!("No track {} to bounce into", track))
     }
 
-    /// Returns true if any track is soloed
-    pub fn any_track_soloed(&self) -> bool {
-        self.tracks.iter().any(|t| t.solo)
+    /// Write `audio` into the project's `audio` folder and make a clip of it
+    /// spanning `range`, for adding to a track
+    fn ingest_bounce_clip(&mut self, audio: &AudioArc, range: Range<u64>) -> anyhow::Result<Clip> {
+        let ctx = self.path_context();
+        let sample_ref = daw_project::ingest_bounce(&mut self.cache, audio, &ctx)?;
+        let path = ctx
+            .resolve(&sample_ref)
+            .ok_or_else(|| anyhow::anyhow!("bounce not found after writing it"))?;
+        let audio = self
+            .cache
+            .get_or_load_direct(&path, Some(self.engine.sample_rate))?;
+        Ok(self.ingested_clip(audio, sample_ref, range))
     }
 
-    /// Exclusively solo a track - unsolos all other tracks and solos the specified one.
-    /// If the track is already the only soloed track, unsolos it.
-    pub fn solo_track_exclusive(&mut self, track_id: u64) {
-        let is_only_soloed = self
+    /// A clip of audio written into the project, spanning `range`, named after its
+    /// file
+    fn ingested_clip(&mut self, audio: AudioArc, sample_ref: SampleRef, range: Range<u64>) -> Clip {
+        let name = sample_ref
+            .path()
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "Audio".to_string());
+        let waveform = Arc::new(WaveformData::from_audio_arc(&audio, 512));
+        let source = ClipSource::from_file(sample_ref.path(), audio.sample_rate());
+        self.sample_refs.insert(name.clone(), sample_ref);
+        Clip {
+            // Assigned when it's added to a track
+            id: ClipId(0),
+            start_tick: range.start,
+            end_tick: range.end,
+            content: ClipContent::Audio(audio),
+            waveform,
+            audio_offset: 0,
+            name,
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source,
+        }
+    }
+
+    /// Render a track's clips to a single clip and play that instead, to save the
+    /// work of mixing them live. Returns the frozen clip's id.
+    ///
+    /// The render spans the track's audio clips with their fade-out tails, and its
+    /// MIDI clips played by its sampler if it has one, at the engine's sample rate
+    /// and without the track's volume, pan or mute, which still apply to the frozen
+    /// clip. The track's clips are kept and put back by
+    /// [`unfreeze_track`](Self::unfreeze_track) until the project is closed; a saved
+    /// project keeps only the frozen clip. Like
+    /// [`bounce_selection_to_new_clip`](Self::bounce_selection_to_new_clip), the
+    /// audio is written into the project's `audio` folder, so the project has to be
+    /// saved first. Fails if the track is already frozen, is a folder or has nothing
+    /// to render.
+    pub fn freeze_track(&mut self, track_id: u64) -> anyhow::Result<ClipId> {
+        if self.project_path.is_none() {
+            anyhow::bail!("save the project before freezing a track");
+        }
+        if self.frozen_tracks.contains_key(&track_id) {
+            anyhow::bail!("Track {} is already frozen", track_id);
+        }
+        let track = self
             .tracks
             .iter()
-            .all(|t| (t.id.0 == track_id && t.solo) || !t.solo);
-        let is_this_soloed = self
+            .find(|track| track.id.0 == track_id && !track.is_folder)
+            .ok_or_else(|| anyhow::anyhow!("No track {} to freeze", track_id))?;
+        let range = track
+            .clips()
+            .iter()
+            .filter(|clip| {
+                clip.audio().is_some() || (clip.notes().is_some() && track.sampler.is_some())
+            })
+            .map(|clip| clip.start_tick..clip.end_tick + clip.fade_out_ticks)
+            .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+            .ok_or_else(|| anyhow::anyhow!("Track {} has no audio to freeze", track_id))?;
+
+        let frozen = render_track(
+            track,
+            &self.tempo_curve(),
+            self.engine.sample_rate,
+            range.clone(),
+        );
+        let mut clip = self.ingest_bounce_clip(&frozen, range)?;
+        clip.id = self.ids.clip_id();
+        let id = clip.id;
+
+        let track = self
             .tracks
+            .iter_mut()
+            .find(|track| track.id.0 == track_id)
+            .expect("track was found above");
+        let originals = track.clips().to_vec();
+        track.clear_clips();
+        track.insert_clip(clip, &mut self.ids);
+        self.frozen_tracks.insert(track_id, originals);
+        self.sync_tracks_to_engine();
+        Ok(id)
+    }
+
+    /// Put back the clips a track had before it was frozen, in place of the frozen
+    /// clip and any edits made to it. Returns whether the track was frozen.
+    pub fn unfreeze_track(&mut self, track_id: u64) -> bool {
+        let Some(originals) = self.frozen_tracks.remove(&track_id) else {
+            return false;
+        };
+        let Some(track) = self.tracks.iter_mut().find(|track| track.id.0 == track_id) else {
+            return false;
+        };
+        track.clear_clips();
+        for clip in originals {
+            track.insert_clip(clip, &mut self.ids);
+        }
+        self.sync_tracks_to_engine();
+        true
+    }
+
+    /// Whether a track is frozen, with its clips kept to unfreeze it
+    pub fn is_track_frozen(&self, track_id: u64) -> bool {
+        self.frozen_tracks.contains_key(&track_id)
+    }
+
+    /// Swap the audio of every clip using a sample for the file at `new_path`.
+    ///
+    /// `old` names the sample as its reference is displayed (`project:audio/kick.wav`),
+    /// as the path in its reference (`audio/kick.wav`), or as the file it resolves to.
+    /// Matching clips keep their place on the timeline and their fades, while their
+    /// trims stay at the same fraction of the sample: a clip that played the second
+    /// half of the old sample plays the second half of the new one. Their waveforms
+    /// are regenerated and the project refers to the new file from then on.
+    ///
+    /// Returns the number of clips changed. The new file is decoded first, so if that
+    /// fails the session is left untouched.
+    pub fn replace_sample(&mut self, old: &str, new_path: &Path) -> anyhow::Result<usize> {
+        let ctx = self.path_context();
+        let old_path = Path::new(old);
+        let old_file = std::fs::canonicalize(old_path).ok();
+        let names: HashSet<String> = self
+            .sample_refs
             .iter()
-            .find(|t| t.id.0 == track_id)
-            .map(|t| t.solo)
-            .unwrap_or(false);
+            .filter(|(_, sample_ref)| {
+                sample_ref.to_string() == old
+                    || sample_ref.path() == old_path
+                    || old_file.is_some()
+                        && ctx
+                            .resolve(sample_ref)
+                            .and_then(|path| std::fs::canonicalize(path).ok())
+                            == old_file
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if names.is_empty() {
+            return Ok(0);
+        }
+
+        let new_path = std::path::absolute(new_path)?;
+        let original = self.cache.get_or_load_direct(&new_path, None)?;
+        let audio = self
+            .cache
+            .get_or_load_direct(&new_path, Some(self.engine.sample_rate))?;
+        let waveform = Arc::new(WaveformData::from_audio_arc(&audio, 512));
+        let sample_ref = ctx.sample_ref_for(&new_path);
+        let source = ClipSource::from_file(sample_ref.path(), original.sample_rate());
 
+        let tempo = self.tempo_curve();
+        let sample_rate = self.engine.sample_rate;
+        let mut replaced = 0;
         for track in &mut self.tracks {
-            if track.id.0 == track_id {
-                // Toggle off if it's the only soloed track, otherwise solo it
-                track.solo = !(is_only_soloed && is_this_soloed);
-            } else {
-                track.solo = false;
+            track.edit_clips(|clip| {
+                let Some(old_audio) = clip.audio().filter(|_| names.contains(&clip.name)) else {
+                    return;
+                };
+                let ratio = audio.frames() as f64 / old_audio.frames().max(1) as f64;
+                let length =
+                    tempo.span_to_samples(clip.start_tick, clip.duration_ticks(), sample_rate);
+                let length = (length * ratio).round();
+                let duration =
+                    (tempo.samples_to_span(clip.start_tick, length, sample_rate) as u64).max(1);
+
+                // A clip that grows is cut off at the next clip on its track
+                clip.audio_offset = (clip.audio_offset as f64 * ratio).round() as u64;
+                clip.end_tick = clip.start_tick + duration;
+                clip.fade_in_ticks = clip.fade_in_ticks.min(duration);
+                clip.content = ClipContent::Audio(audio.clone());
+                clip.waveform = waveform.clone();
+                clip.source = source.clone();
+                replaced += 1;
+            });
+        }
+
+        for name in names {
+            self.sample_refs.insert(name, sample_ref.clone());
+        }
+        self.sync_tracks_to_engine();
+        Ok(replaced)
+    }
+
+    /// Clips whose samples couldn't be found or decoded when the project was
+    /// loaded, in the order they were saved.
+    ///
+    /// They're saved with the project as they were until they're relinked.
+    pub fn offline_clips(&self) -> &[OfflineClip] {
+        &self.offline_clips
+    }
+
+    /// Relink the offline clip at `index` to the file at `path`, along with the
+    /// other offline clips that used the same sample.
+    ///
+    /// The clips go back on their tracks with the ids, positions, trims, fades and
+    /// gain they were saved with, and the project refers to `path` from then on.
+    /// Returns the ids of the clips relinked. The file is decoded first, so if that
+    /// fails the clips stay offline.
+    pub fn relink_offline_clip(
+        &mut self,
+        index: usize,
+        path: &Path,
+    ) -> anyhow::Result<Vec<ClipId>> {
+        let sample_ref = self
+            .offline_clips
+            .get(index)
+            .ok_or_else(|| anyhow::anyhow!("No offline clip {}", index))?
+            .sample_ref
+            .clone();
+        let ctx = self.path_context();
+        let sample_rate = self.engine.sample_rate;
+        self.cache
+            .get_or_load_direct(&std::path::absolute(path)?, Some(sample_rate))?;
+
+        let mut relinked = Vec::new();
+        let mut result = Ok(());
+        let mut index = 0;
+        while index < self.offline_clips.len() {
+            if self.offline_clips[index].sample_ref != sample_ref {
+                index += 1;
+                continue;
+            }
+            let (clip, new_ref) = match self.offline_clips[index].relink(
+                path,
+                &mut self.cache,
+                Some(sample_rate),
+                &ctx,
+                &mut self.ids,
+            ) {
+                Ok(relink) => relink,
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            };
+            let offline = self.offline_clips.remove(index);
+            self.sample_refs.insert(clip.name.clone(), new_ref);
+            relinked.push(clip.id);
+            if let Some(track) = self.tracks.iter_mut().find(|t| t.id == offline.track_id) {
+                track.insert_clip(clip, &mut self.ids);
             }
         }
-        self.send_tracks_to_engine(self.engine.sample_rate);
+        self.sync_tracks_to_engine();
+        result?;
+        Ok(relinked)
     }
 
-    // Cursor and snapping methods
+    /// Look through `folder` and the folders inside it for files named like the
+    /// missing samples of the offline clips, and relink the clips to them (see
+    /// [`relink_offline_clip`](Self::relink_offline_clip)).
+    ///
+    /// Returns the ids of the clips relinked. Clips whose file was found but
+    /// couldn't be decoded stay offline, with the reason as their error.
+    pub fn relink_offline_clips_in(&mut self, folder: &Path) -> Vec<ClipId> {
+        let mut found: Vec<(SampleRef, PathBuf)> = Vec::new();
+        for (index, path) in find_missing_samples(&self.offline_clips, folder) {
+            let sample_ref = &self.offline_clips[index].sample_ref;
+            if !found.iter().any(|(done, _)| done == sample_ref) {
+                found.push((sample_ref.clone(), path));
+            }
+        }
+        self.relink_offline_samples(found)
+    }
 
-    /// Get the current cursor position in ticks
-    pub fn cursor_tick(&self) -> Option<u64> {
-        self.cursor_tick
+    /// Relink the offline clips playing each sample to the file found for it.
+    /// Clips whose file couldn't be decoded stay offline, with the reason as their
+    /// error.
+    fn relink_offline_samples(&mut self, found: Vec<(SampleRef, PathBuf)>) -> Vec<ClipId> {
+        let mut relinked = Vec::new();
+        for (sample_ref, path) in found {
+            let Some(index) = self
+                .offline_clips
+                .iter()
+                .position(|offline| offline.sample_ref == sample_ref)
+            else {
+                continue;
+            };
+            match self.relink_offline_clip(index, &path) {
+                Ok(ids) => relinked.extend(ids),
+                Err(err) => {
+                    for offline in &mut self.offline_clips {
+                        if offline.sample_ref == sample_ref {
+                            offline.error = format!("Failed to decode {}: {}", path.display(), err);
+                        }
+                    }
+                }
+            }
+        }
+        relinked
     }
 
-    /// Set the cursor position to a specific tick (applies snapping if enabled)
-    pub fn set_cursor(&mut self, tick: u64) {
-        let snapped_tick = self.snap_to_grid(tick);
-        self.cursor_tick = Some(snapped_tick);
+    /// The user's sample libraries, in the order they're searched
+    pub fn sample_roots(&self) -> &[SampleRoot] {
+        &self.sample_roots
     }
 
-    /// Clear the cursor position
-    pub fn clear_cursor(&mut self) {
-        self.cursor_tick = None;
+    /// Set the user's sample libraries, which `SampleRef::Library` samples are
+    /// found in and new samples from inside them are referenced by.
+    ///
+    /// Offline clips whose samples are found in the new libraries are relinked
+    /// (see [`relink_offline_clip`](Self::relink_offline_clip)); returns their ids.
+    pub fn set_sample_roots(&mut self, sample_roots: Vec<SampleRoot>) -> Vec<ClipId> {
+        self.sample_roots = sample_roots;
+        let ctx = self.path_context();
+        let mut found: Vec<(SampleRef, PathBuf)> = Vec::new();
+        for offline in &self.offline_clips {
+            if found.iter().any(|(done, _)| *done == offline.sample_ref) {
+                continue;
+            }
+            if let Some(path) = ctx.resolve(&offline.sample_ref) {
+                found.push((offline.sample_ref.clone(), path));
+            }
+        }
+        self.relink_offline_samples(found)
     }
 
-    /// Get the current snap mode
-    pub fn snap_mode(&self) -> SnapMode {
-        self.snap_mode
+    /// Reload samples whose files changed on disk since they were loaded, such as
+    /// after editing them in another program.
+    ///
+    /// Clips using a reloaded sample keep their place, length, trim and fades; only
+    /// their audio and waveform change, and a trim past the end of a sample that got
+    /// shorter is pulled back to its end. Returns the names of the reloaded samples.
+    ///
+    /// Samples that decode are reloaded even if another fails, for instance because
+    /// it's still being written. The error names the ones that failed; they count as
+    /// changed until they load, so the next call tries them again.
+    pub fn reload_changed_samples(&mut self) -> anyhow::Result<Vec<String>> {
+        let ctx = self.path_context();
+        let mut changed: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+        for (name, sample_ref) in &self.sample_refs {
+            if let Some(path) = ctx.resolve(sample_ref)
+                && self.cache.has_changed(&path)
+            {
+                changed.entry(path).or_default().push(name.clone());
+            }
+        }
+
+        let mut reloaded = HashMap::new();
+        let mut failed = Vec::new();
+        for (path, names) in changed {
+            let loaded = self
+                .cache
+                .get_or_load_direct(&path, None)
+                .and_then(|original| {
+                    let audio = self
+                        .cache
+                        .get_or_load_direct(&path, Some(self.engine.sample_rate))?;
+                    Ok((audio, original.sample_rate()))
+                });
+            match loaded {
+                Ok((audio, original_rate)) => {
+                    let waveform = Arc::new(WaveformData::from_audio_arc(&audio, 512));
+                    for name in names {
+                        reloaded.insert(name, (audio.clone(), waveform.clone(), original_rate));
+                    }
+                }
+                Err(err) => failed.push(format!("{}: {}", path.display(), err)),
+            }
+        }
+
+        if !reloaded.is_empty() {
+            for track in &mut self.tracks {
+                track.edit_clips(|clip| {
+                    if clip.audio().is_none() {
+                        return;
+                    }
+                    let Some((audio, waveform, original_rate)) = reloaded.get(&clip.name) else {
+                        return;
+                    };
+                    clip.audio_offset = clip.audio_offset.min(audio.frames() as u64);
+                    clip.content = ClipContent::Audio(audio.clone());
+                    clip.waveform = waveform.clone();
+                    clip.source.sample_rate = Some(*original_rate);
+                });
+            }
+            self.sync_tracks_to_engine();
+        }
+
+        if !failed.is_empty() {
+            anyhow::bail!("couldn't reload {}", failed.join("; "));
+        }
+        let mut names: Vec<String> = reloaded.into_keys().collect();
+        names.sort();
+        Ok(names)
     }
 
-    /// Set the snap mode
-    pub fn set_snap_mode(&mut self, mode: SnapMode) {
-        self.snap_mode = mode;
+    /// Reload samples as soon as their files change on disk, so edits saved from
+    /// another program are heard right away.
+    ///
+    /// While on, [`poll`](Self::poll) reloads changed samples like
+    /// [`reload_changed_samples`](Self::reload_changed_samples) and reports it with
+    /// [`SessionEvent::SamplesReloaded`] or [`SessionEvent::SampleReloadFailed`].
+    /// Fails if the operating system's file watching can't be set up.
+    #[cfg(feature = "watch")]
+    pub fn set_watching_samples(&mut self, enabled: bool) -> anyhow::Result<()> {
+        if !enabled {
+            self.sample_watcher = None;
+        } else if self.sample_watcher.is_none() {
+            let mut watcher = SampleWatcher::new()?;
+            watcher.watch_files(self.sample_paths().iter().map(PathBuf::as_path));
+            self.sample_watcher = Some(watcher);
+            self.sample_watch_synced = Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Whether samples are reloaded as soon as their files change
+    #[cfg(feature = "watch")]
+    pub fn is_watching_samples(&self) -> bool {
+        self.sample_watcher.is_some()
+    }
+
+    /// Reload samples the watcher saw change, and watch the folders of samples
+    /// added since the last look
+    #[cfg(feature = "watch")]
+    fn check_sample_watcher(&mut self) {
+        /// How often to look for samples added or moved since the folders were set
+        const RESYNC_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+        let Some(watcher) = &mut self.sample_watcher else {
+            return;
+        };
+        if !watcher.take_changed().is_empty() {
+            match self.reload_changed_samples() {
+                Ok(names) if names.is_empty() => {}
+                Ok(names) => self.events.push(SessionEvent::SamplesReloaded(names)),
+                Err(err) => self
+                    .events
+                    .push(SessionEvent::SampleReloadFailed(err.to_string())),
+            }
+        }
+
+        if self.sample_watch_synced.elapsed() >= RESYNC_INTERVAL {
+            let paths = self.sample_paths();
+            if let Some(watcher) = &mut self.sample_watcher {
+                watcher.watch_files(paths.iter().map(PathBuf::as_path));
+            }
+            self.sample_watch_synced = Instant::now();
+        }
+    }
+
+    /// Files of the samples in use that can be found
+    #[cfg(feature = "watch")]
+    fn sample_paths(&self) -> Vec<PathBuf> {
+        let ctx = self.path_context();
+        self.sample_refs
+            .values()
+            .filter_map(|sample_ref| ctx.resolve(sample_ref))
+            .collect()
+    }
+
+    /// Where sample references resolve for a project file at `path`
+    fn context_for(&self, path: &Path) -> PathContext {
+        PathContext {
+            dev_root: self.dev_root.clone(),
+            embedded_dir: self.embedded_dir.clone(),
+            sample_roots: self.sample_roots.clone(),
+            ..PathContext::from_project_path(path)
+        }
+    }
+
+    /// Where the session's sample references resolve
+    fn path_context(&self) -> PathContext {
+        let project_root = self
+            .project_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(|path| path.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from("."));
+        PathContext {
+            project_root,
+            dev_root: self.dev_root.clone(),
+            embedded_dir: self.embedded_dir.clone(),
+            sample_roots: self.sample_roots.clone(),
+        }
+    }
+
+    /// Counts and bytes of the decoded audio the session keeps in memory
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    /// The `n` pieces of decoded audio taking the most memory, largest first.
+    ///
+    /// Entries marked `in_use` are played by clips; the rest are only kept to
+    /// speed up later loads and renders.
+    pub fn largest_cached_audio(&self, n: usize) -> Vec<CacheEntry> {
+        self.cache.largest_entries(n)
     }
 
     /// Snap a tick value to the current grid based on snap mode
@@ -1021,3 +5082,246 @@ impl Session {
         }
     }
 }
+
+/// Whether two lists run the same kinds of effect in the same order, so one's
+/// insert chain can take the other's settings
+fn same_effect_kinds(a: &[Effect], b: &[Effect]) -> bool {
+    a.iter().map(Effect::kind).eq(b.iter().map(Effect::kind))
+}
+
+/// `value` kept within what the control `parameter` automates allows
+fn automation_value(parameter: &str, value: f32) -> f32 {
+    match parameter {
+        VOLUME_PARAMETER => value.clamp(0.0, 1.0),
+        PAN_PARAMETER => value.clamp(-1.0, 1.0),
+        _ => value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use daw_transport::{Note, NoteList};
+
+    fn one_shot(frames: usize, sample_rate: u32) -> AudioArc {
+        AudioArc::new(vec![0.0; frames * 2], sample_rate, 2)
+    }
+
+    #[test]
+    fn test_insert_length_audio_uses_tempo() {
+        // 0.25 seconds of audio
+        let audio = one_shot(11025, 44100);
+
+        // At 120 BPM a beat is 0.5s, so the clip is half a beat
+        let four_four = TimeSignature::new(4, 4);
+        let tempo = TempoCurve::constant(120.0);
+        assert_eq!(
+            InsertLength::AudioLength.length_ticks(&audio, 0, &tempo, four_four),
+            PPQN / 2
+        );
+
+        // At 60 BPM a beat is 1s, so the clip is a quarter beat
+        let tempo = TempoCurve::constant(60.0);
+        assert_eq!(
+            InsertLength::AudioLength.length_ticks(&audio, 0, &tempo, four_four),
+            PPQN / 4
+        );
+
+        // After a change to 60 BPM the clip takes that tempo
+        let tempo = TempoCurve::new(120.0, [(PPQN * 4, 60.0)]);
+        assert_eq!(
+            InsertLength::AudioLength.length_ticks(&audio, PPQN * 4, &tempo, four_four),
+            PPQN / 4
+        );
+    }
+
+    #[test]
+    fn test_insert_length_grid() {
+        let audio = one_shot(11025, 44100);
+        let tempo = TempoCurve::constant(93.0);
+        let three_four = TimeSignature::new(3, 4);
+
+        assert_eq!(
+            InsertLength::Beat.length_ticks(&audio, 0, &tempo, three_four),
+            PPQN
+        );
+        assert_eq!(
+            InsertLength::Bar.length_ticks(&audio, 0, &tempo, three_four),
+            PPQN * 3
+        );
+    }
+
+    #[test]
+    fn test_armed_tracks_take_midi_input() {
+        let tracks = (0..3)
+            .map(|id| Track::new(TrackId(id), format!("Track {}", id)))
+            .collect();
+        let mut session = Session::new_offline(tracks, 120.0, (4, 4), 44100);
+        session.set_track_armed(1, true);
+        let midi_input = |session: &mut Session| -> Vec<bool> {
+            session
+                .convert_tracks_for_engine(44100)
+                .iter()
+                .map(|track| track.midi_input)
+                .collect()
+        };
+        assert_eq!(midi_input(&mut session), [false, true, false]);
+
+        // Offline sessions have nothing to play it on
+        assert!(session.set_midi_input_device(Some("Keys")).is_err());
+        assert!(session.set_midi_input_device(None).is_ok());
+        assert_eq!(session.midi_input_device(), None);
+    }
+
+    #[test]
+    fn test_midi_clips_play_on_the_track_sampler() {
+        let notes = vec![
+            Note {
+                start_tick: 0,
+                duration_ticks: PPQN,
+                pitch: 60,
+                velocity: 100,
+            },
+            // Runs past the clip's end, so it's cut off there
+            Note {
+                start_tick: PPQN,
+                duration_ticks: 4 * PPQN,
+                pitch: 64,
+                velocity: 80,
+            },
+            // Starts after the clip ends
+            Note {
+                start_tick: 3 * PPQN,
+                duration_ticks: PPQN,
+                pitch: 67,
+                velocity: 80,
+            },
+        ];
+        let mut track = Track::new(TrackId(0), "Keys".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(
+            Clip {
+                id: ClipId(0),
+                start_tick: PPQN,
+                end_tick: 3 * PPQN,
+                content: ClipContent::Midi(NoteList::new(notes)),
+                waveform: Arc::new(WaveformData::from_audio_arc(&one_shot(0, 44100), 512)),
+                audio_offset: 0,
+                name: "notes".to_string(),
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source: ClipSource::default(),
+            },
+            &mut ids,
+        );
+        let folder = Track::new_folder(TrackId(1), "Folder".to_string());
+        let mut session = Session::new_offline(vec![track, folder], 120.0, (4, 4), 44100);
+
+        let sampler = Sampler::pitched(one_shot(100, 44100), 60);
+        assert!(session.set_track_sampler(0, Some(sampler.clone())));
+        assert!(!session.set_track_sampler(1, Some(sampler)));
+        assert!(session.set_track_sampler_envelope(0, Adsr::default()));
+        assert!(session.track_sampler(0).is_some());
+
+        // A beat is 22050 samples at 120 BPM
+        let engine_tracks = session.convert_tracks_for_engine(44100);
+        assert!(engine_tracks[0].sampler.is_some());
+        let notes: Vec<_> = engine_tracks[0]
+            .notes
+            .iter()
+            .map(|note| (note.start, note.end, note.pitch))
+            .collect();
+        assert_eq!(notes, [(22050, 44100, 60), (44100, 66150, 64)]);
+
+        assert!(session.set_track_sampler(0, None));
+        let engine_tracks = session.convert_tracks_for_engine(44100);
+        assert!(engine_tracks[0].sampler.is_none());
+    }
+
+    #[test]
+    fn test_solo_mode() {
+        let tracks = (0..3)
+            .map(|id| Track::new(TrackId(id), format!("Track {}", id)))
+            .collect();
+        let mut session = Session::new_offline(tracks, 120.0, (4, 4), 44100);
+        let soloed =
+            |session: &Session| -> Vec<bool> { session.tracks().iter().map(|t| t.solo).collect() };
+
+        // Exclusive by default; a modifier adds to the solo instead
+        session.solo_track(0, false);
+        session.solo_track(1, false);
+        assert_eq!(soloed(&session), [false, true, false]);
+        session.solo_track(2, true);
+        assert_eq!(soloed(&session), [false, true, true]);
+
+        // In additive mode it's the other way around
+        session.set_solo_mode(SoloMode::Additive);
+        session.solo_track(0, false);
+        assert_eq!(soloed(&session), [true, true, true]);
+        session.solo_track(1, false);
+        assert_eq!(soloed(&session), [true, false, true]);
+        session.solo_track(2, true);
+        assert_eq!(soloed(&session), [false, false, true]);
+    }
+
+    #[test]
+    fn test_render_with_metronome() {
+        // Two bars of silence set the length
+        let silence = one_shot(88200 * 2, 44100);
+        let mut track = Track::new(TrackId(0), "Track".to_string());
+        track.insert_clip(
+            Clip {
+                id: ClipId(0),
+                start_tick: 0,
+                end_tick: PPQN * 8,
+                waveform: Arc::new(WaveformData::from_audio_arc(&silence, 512)),
+                content: ClipContent::Audio(silence),
+                audio_offset: 0,
+                name: "Silence".to_string(),
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source: ClipSource::default(),
+            },
+            &mut IdAllocator::default(),
+        );
+        let mut session = Session::new_offline(vec![track], 120.0, (4, 4), 44100);
+        session.metronome = Metronome {
+            hi: AudioArc::new(vec![0.5; 100], 44100, 1),
+            lo: AudioArc::new(vec![0.25; 100], 44100, 1),
+            enabled: false,
+            volume: 0.5,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("click.wav");
+        let render = |session: &Session, metronome| {
+            let options = RenderOptions {
+                metronome,
+                ..Default::default()
+            };
+            session.render_to_file_with(&path, &options).unwrap();
+            decode_audio_arc_direct(&path, None).unwrap()
+        };
+
+        let clicks = render(&session, true);
+        assert_eq!(clicks.frames(), 88200 * 2);
+        let left = |frame: usize| clicks.samples()[frame * 2];
+        // Downbeats on the high click, other beats on the low one
+        for beat in 0..8 {
+            let level = if beat % 4 == 0 { 0.25 } else { 0.125 };
+            assert_eq!(left(beat * 22050), level, "beat {beat}");
+            assert_eq!(left(beat * 22050 + 100), 0.0);
+        }
+        assert!(render(&session, false).samples().iter().all(|&s| s == 0.0));
+
+        // A solo doesn't silence the click
+        session.set_track_solo(0, true);
+        assert_eq!(render(&session, true).samples(), clicks.samples());
+    }
+}
@@ -1,4 +1,4 @@
-use daw_transport::PPQN;
+use daw_transport::{PPQN, TempoCurve};
 
 #[derive(Debug, Clone, Copy)]
 pub struct TimeSignature {
@@ -87,24 +87,27 @@ impl TimeContext {
         self.beats_to_ticks(beats)
     }
 
+    /// The tempo as a [`TempoCurve`] without changes, the conversion renders use
+    pub fn tempo_curve(&self) -> TempoCurve {
+        TempoCurve::constant(self.tempo)
+    }
+
     pub fn ticks_to_seconds(&self, ticks: u64) -> f64 {
-        let beats = self.ticks_to_beats(ticks);
-        beats * 60.0 / self.tempo
+        self.tempo_curve().ticks_to_seconds(ticks as f64)
     }
 
     pub fn seconds_to_ticks(&self, seconds: f64) -> u64 {
-        let beats = seconds * self.tempo / 60.0;
-        self.beats_to_ticks(beats)
+        self.tempo_curve().seconds_to_ticks(seconds) as u64
     }
 
     pub fn ticks_to_samples(&self, ticks: u64, sample_rate: u32) -> u64 {
-        let seconds = self.ticks_to_seconds(ticks);
-        (seconds * sample_rate as f64) as u64
+        self.tempo_curve()
+            .ticks_to_samples(ticks as f64, sample_rate) as u64
     }
 
     pub fn samples_to_ticks(&self, samples: u64, sample_rate: u32) -> u64 {
-        let seconds = samples as f64 / sample_rate as f64;
-        self.seconds_to_ticks(seconds)
+        self.tempo_curve()
+            .samples_to_ticks(samples as f64, sample_rate) as u64
     }
 
     pub fn format_position(&self, ticks: u64) -> MusicalPosition {
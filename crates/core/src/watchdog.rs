@@ -0,0 +1,101 @@
+//! Detection of an audio stream that has stopped advancing.
+//!
+//! A driver hiccup or an unplugged device can stop the audio callback without any
+//! error being reported, leaving playback frozen while the session still thinks
+//! it's playing. The watchdog compares how far the engine's reported position has
+//! moved with how far it should have moved in the time that passed.
+
+use std::time::{Duration, Instant};
+
+/// How long playback may go without advancing before the stream counts as stalled
+pub(crate) const STALL_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Tracks playback progress against the wall clock.
+#[derive(Debug)]
+pub(crate) struct Watchdog {
+    /// Time and position progress is measured from
+    anchor: (Instant, u64),
+    /// Latest position reported by the engine
+    reported: u64,
+}
+
+impl Watchdog {
+    pub(crate) fn new(now: Instant, sample: u64) -> Self {
+        Self {
+            anchor: (now, sample),
+            reported: sample,
+        }
+    }
+
+    /// Measure progress from `sample` at `now`, after playback starts or jumps
+    pub(crate) fn reset(&mut self, now: Instant, sample: u64) {
+        *self = Self::new(now, sample);
+    }
+
+    /// Record a position reported by the engine
+    pub(crate) fn report(&mut self, sample: u64) {
+        self.reported = sample;
+    }
+
+    /// Whether playback has fallen behind the clock for at least `STALL_TIMEOUT`.
+    ///
+    /// Call while playing. Progress is measured over windows of `STALL_TIMEOUT`;
+    /// a window in which the position advanced by less than half the expected
+    /// amount counts as a stall.
+    pub(crate) fn is_stalled(&mut self, now: Instant, sample_rate: u32) -> bool {
+        let (since, start) = self.anchor;
+        let elapsed = now.saturating_duration_since(since);
+        if elapsed < STALL_TIMEOUT {
+            return false;
+        }
+
+        let expected = elapsed.as_secs_f64() * sample_rate as f64;
+        // A position that moved backwards was a jump, which still means the stream runs
+        let advanced = self.reported < start || (self.reported - start) as f64 >= expected / 2.0;
+        if advanced {
+            self.anchor = (now, self.reported);
+        }
+        !advanced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48000;
+
+    #[test]
+    fn test_advancing_playback_is_not_stalled() {
+        let start = Instant::now();
+        let mut watchdog = Watchdog::new(start, 0);
+
+        for step in 1..=4u32 {
+            let now = start + STALL_TIMEOUT * step;
+            watchdog.report(((STALL_TIMEOUT * step).as_secs_f64() * RATE as f64) as u64);
+            assert!(!watchdog.is_stalled(now, RATE));
+        }
+    }
+
+    #[test]
+    fn test_frozen_position_is_a_stall() {
+        let start = Instant::now();
+        let mut watchdog = Watchdog::new(start, 1000);
+        watchdog.report(1200);
+
+        assert!(!watchdog.is_stalled(start + STALL_TIMEOUT / 2, RATE));
+        assert!(watchdog.is_stalled(start + STALL_TIMEOUT, RATE));
+
+        // Resetting (e.g. after restarting the stream) starts a new window
+        watchdog.reset(start + STALL_TIMEOUT, 1200);
+        assert!(!watchdog.is_stalled(start + STALL_TIMEOUT * 3 / 2, RATE));
+    }
+
+    #[test]
+    fn test_jump_backwards_counts_as_progress() {
+        let start = Instant::now();
+        let mut watchdog = Watchdog::new(start, 96000);
+        watchdog.report(10);
+        assert!(!watchdog.is_stalled(start + STALL_TIMEOUT, RATE));
+    }
+}
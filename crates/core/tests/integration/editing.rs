@@ -0,0 +1,790 @@
+//! Editing operations and how they show up in the rendered output.
+
+use crate::{SAMPLE_RATE, render_bytes, write_sample};
+use daw_core::{
+    AudioArc, AudioInput, AutomationCurve, ClipId, Effect, InsertLength, PPQN, SampleRef, Session,
+    SnapMode, TempoChange, TempoMap, Track, TrackId, VOLUME_PARAMETER,
+};
+use std::path::{Path, PathBuf};
+use tempfile::tempdir;
+
+fn drum_session() -> Session {
+    Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    )
+}
+
+#[test]
+fn test_inserting_over_a_clip_replaces_the_overlap() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 220.0);
+
+    let mut session = drum_session();
+    session.set_insert_length(InsertLength::Bar);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+
+    // A one-beat snare in the middle of the bar splits the kick clip around it
+    session.set_snap_mode(SnapMode::Beat);
+    session.set_insert_length(InsertLength::Beat);
+    session.set_cursor(PPQN);
+    session.insert_sample_at_cursor(TrackId(0), snare, snare_ref, "snare".to_string());
+
+    let ranges: Vec<(u64, u64)> = session.tracks()[0]
+        .clips()
+        .iter()
+        .map(|clip| (clip.start_tick, clip.end_tick))
+        .collect();
+    assert_eq!(
+        ranges,
+        vec![(0, PPQN), (PPQN, 2 * PPQN), (2 * PPQN, 4 * PPQN)]
+    );
+}
+
+#[test]
+fn test_tempo_and_mute_change_the_render() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = drum_session();
+    session.set_snap_mode(SnapMode::Beat);
+    session.set_cursor(4 * PPQN);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let at_120 = render_bytes(&session, dir.path(), "at_120");
+
+    // At half the tempo the clip starts twice as late, so the render is longer
+    session.set_tempo(60.0);
+    let at_60 = render_bytes(&session, dir.path(), "at_60");
+    assert!(at_60.len() > at_120.len());
+
+    // Muted tracks don't count towards the render length, so nothing is left
+    session.toggle_track_enabled(0);
+    let muted = render_bytes(&session, dir.path(), "muted");
+    let empty = render_bytes(&drum_session(), dir.path(), "empty");
+    assert_eq!(muted, empty);
+}
+
+#[test]
+fn test_tempo_changes_move_clips_in_the_render() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = drum_session();
+    session.set_snap_mode(SnapMode::Beat);
+    session.set_cursor(4 * PPQN);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let at_120 = render_bytes(&session, dir.path(), "at_120");
+
+    // Slowing down before the clip pushes it later
+    session.set_tempo_map(TempoMap {
+        tempo_changes: vec![TempoChange {
+            tick: 2 * PPQN,
+            bpm: 60.0,
+        }],
+        ..TempoMap::default()
+    });
+    let slowed = render_bytes(&session, dir.path(), "slowed");
+    assert!(slowed.len() > at_120.len());
+
+    // A change right at the start is the same as that tempo throughout
+    session.set_tempo_map(TempoMap {
+        tempo_changes: vec![TempoChange { tick: 0, bpm: 60.0 }],
+        ..TempoMap::default()
+    });
+    let from_start = render_bytes(&session, dir.path(), "from_start");
+    session.set_tempo_map(TempoMap::default());
+    session.set_tempo(60.0);
+    assert_eq!(from_start, render_bytes(&session, dir.path(), "at_60"));
+}
+
+#[test]
+fn test_audio_imported_after_a_tempo_change_plays_to_its_end() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, _) = write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = drum_session();
+    session.set_tempo_map(TempoMap {
+        tempo_changes: vec![TempoChange {
+            tick: 4 * PPQN,
+            bpm: 240.0,
+        }],
+        ..TempoMap::default()
+    });
+    let id = session
+        .import_audio_file(&dir.path().join("kick.wav"), TrackId(0), 4 * PPQN)
+        .expect("import");
+
+    // A quarter second is a whole beat at 240 BPM, not the half beat it is at 120
+    let clip = &session.tracks()[0].clips()[0];
+    assert_eq!(clip.duration_ticks(), PPQN);
+    let bounced = session.bounce_clip(0, id).expect("bounce");
+    assert!(bounced.frames() >= kick.frames());
+    let last = (kick.frames() - 1) * 2;
+    assert!((bounced.samples()[last] - kick.samples()[last]).abs() < 1e-6);
+}
+
+#[test]
+fn test_replacing_a_sample_swaps_every_clip_using_it() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 220.0);
+
+    let mut session = drum_session();
+    session.set_project_path(dir.path().join("song.dawproj"));
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in [0, 2] {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(0),
+            kick.clone(),
+            kick_ref.clone(),
+            "kick".to_string(),
+        );
+    }
+    session.set_cursor(PPQN);
+    session.insert_sample_at_cursor(TrackId(0), snare, snare_ref.clone(), "snare".to_string());
+
+    // The final kick is half as long as the placeholder
+    let half: Vec<f32> = kick.samples()[..kick.frames() / 2 * 2].to_vec();
+    let final_kick = AudioArc::new(half, SAMPLE_RATE, 2);
+    daw_render::write_wav(
+        &final_kick,
+        &dir.path().join("kick-final.wav"),
+        daw_core::RenderFormat::Float32,
+    )
+    .expect("write");
+
+    let replaced = session
+        .replace_sample("project:kick.wav", &dir.path().join("kick-final.wav"))
+        .expect("replace");
+    assert_eq!(replaced, 2);
+
+    let clips = session.tracks()[0].clips();
+    for clip in clips.iter().filter(|clip| clip.name == "kick") {
+        assert_eq!(clip.audio().unwrap().frames(), final_kick.frames());
+        assert!(clip.duration_ticks().abs_diff(PPQN / 4) <= 1);
+    }
+    let snare_clip = clips.iter().find(|clip| clip.name == "snare").unwrap();
+    assert_eq!(snare_clip.duration_ticks(), PPQN / 2);
+    assert_eq!(
+        session.sample_refs()["kick"],
+        SampleRef::ProjectRelative(PathBuf::from("kick-final.wav"))
+    );
+    assert_eq!(session.sample_refs()["snare"], snare_ref);
+
+    // Naming the sample by its file works too, and a sample nobody uses changes nothing
+    let file = dir.path().join("kick-final.wav");
+    let replaced = session
+        .replace_sample(file.to_str().unwrap(), &dir.path().join("kick.wav"))
+        .expect("replace");
+    assert_eq!(replaced, 2);
+    assert_eq!(
+        session
+            .replace_sample("hat.wav", &dir.path().join("kick.wav"))
+            .expect("replace"),
+        0
+    );
+
+    // A missing replacement is an error and leaves the clips alone
+    assert!(
+        session
+            .replace_sample("kick.wav", &dir.path().join("missing.wav"))
+            .is_err()
+    );
+    assert_eq!(session.sample_refs()["kick"], kick_ref);
+}
+
+#[test]
+fn test_recorded_pad_hits_become_clips_at_the_playhead() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 220.0);
+
+    let mut session = drum_session();
+    let snares = session.add_track("Snare".to_string());
+    session.set_insert_length(InsertLength::Beat);
+    session.set_cursor(0);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.insert_sample_at_cursor(snares.clone(), snare, snare_ref, "snare".to_string());
+    let empty = session.add_track("Empty".to_string());
+    assert_eq!(session.pad_tracks(), vec![0, snares.0]);
+
+    // Pads play without recording anything until recording is armed and playing
+    assert!(session.trigger_pad(0, 1.0));
+    assert!(!session.trigger_pad(empty.0, 1.0));
+    session.set_pad_recording(true);
+    assert!(session.trigger_pad(0, 1.0));
+    assert_eq!(session.tracks()[0].clips().len(), 1);
+
+    // A hit lands on the grid line nearest the playhead, as a copy of the pad's clip
+    session.set_snap_mode(SnapMode::Beat);
+    session.clear_cursor();
+    session.seek(2 * PPQN + PPQN / 10);
+    session.play();
+    assert!(session.trigger_note(daw_core::PAD_BASE_NOTE + 1, 100));
+    let snare_clips = session.tracks()[1].clips();
+    assert_eq!(snare_clips.len(), 2);
+    assert_eq!(
+        (snare_clips[1].start_tick, snare_clips[1].end_tick),
+        (2 * PPQN, 3 * PPQN)
+    );
+    assert_eq!(snare_clips[1].name, "snare");
+    assert_ne!(snare_clips[1].id, snare_clips[0].id);
+
+    // Notes past the last pad and muted tracks play nothing
+    assert!(!session.trigger_note(daw_core::PAD_BASE_NOTE + 2, 100));
+    session.toggle_track_enabled(0);
+    assert!(!session.trigger_pad(0, 1.0));
+    assert_eq!(session.tracks()[0].clips().len(), 1);
+}
+
+#[test]
+fn test_previewing_a_sample_leaves_the_arrangement_alone() {
+    let dir = tempdir().expect("tempdir");
+    write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = drum_session();
+    session.set_preview_volume(1.5);
+    assert_eq!(session.preview_volume(), 1.0);
+    session.set_preview_volume(0.5);
+
+    session
+        .preview_sample(&dir.path().join("kick.wav"))
+        .expect("preview");
+    session.stop_preview();
+    assert!(session.tracks()[0].clips().is_empty());
+    assert!(session.sample_refs().is_empty());
+    assert_eq!(session.max_tick(), 0);
+
+    assert!(
+        session
+            .preview_sample(&dir.path().join("missing.wav"))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_bouncing_a_clip_bakes_in_its_fades() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let project_path = dir.path().join("bounce.dawproj");
+    drum_session().save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.set_insert_length(InsertLength::Beat);
+    session.insert_sample_at_cursor(TrackId(0), kick.clone(), kick_ref, "kick".to_string());
+
+    let mut faded = session.tracks()[0].clips()[0].clone();
+    faded.fade_in_ticks = PPQN / 8;
+    let id = session.add_clip(TrackId(0), faded).expect("add");
+
+    // One beat at 120 BPM, starting from silence and then playing the kick as is
+    let bounced = session.bounce_clip(0, id).expect("bounce");
+    assert_eq!(bounced.frames(), SAMPLE_RATE as usize / 2);
+    assert_eq!(bounced.channels(), 2);
+    assert_eq!(bounced.samples()[0], 0.0);
+    let later = SAMPLE_RATE as usize / 8 * 2;
+    assert_eq!(
+        bounced.samples()[later..later + 2],
+        kick.samples()[later..later + 2]
+    );
+    assert!(session.bounce_clip(0, ClipId(id.0 + 1)).is_none());
+
+    let (audio, sample_ref) = session.bounce_clip_to_project(0, id).expect("write");
+    assert_eq!(sample_ref.path(), Path::new("audio/bounce-001.wav"));
+    assert!(dir.path().join("audio/bounce-001.wav").exists());
+    assert_eq!(audio.samples(), bounced.samples());
+    // The clip is still the faded original
+    assert_eq!(session.tracks()[0].clips()[0].fade_in_ticks, PPQN / 8);
+}
+
+#[test]
+fn test_bouncing_a_selection_in_place() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let project_path = dir.path().join("bounce.dawproj");
+    let mut session = drum_session();
+    session.add_track("Print".to_string());
+    session.save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.set_insert_length(InsertLength::Beat);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_track_volume(0, 0.5);
+    let print = session.tracks()[1].id.clone();
+
+    assert!(
+        session
+            .bounce_selection_to_new_clip(PPQN..PPQN, print.clone())
+            .is_err()
+    );
+    let before = session
+        .render_to_file(&dir.path().join("before.wav"))
+        .expect("render");
+    let id = session
+        .bounce_selection_to_new_clip(0..PPQN * 2, print.clone())
+        .expect("bounce");
+    assert!(dir.path().join("audio/bounce-001.wav").exists());
+    let clip = &session.tracks()[1].clips()[0];
+    assert_eq!(clip.id, id);
+    assert_eq!((clip.start_tick, clip.end_tick), (0, PPQN * 2));
+    assert_eq!(clip.name, "bounce-001");
+
+    // With the drums muted, the bounce plays what they did, volume included
+    session.set_track_enabled(0, false);
+    let after = session
+        .render_to_file(&dir.path().join("after.wav"))
+        .expect("render");
+    assert_eq!(after.duration, before.duration * 2);
+    assert!((after.peak_db - before.peak_db).abs() < 0.01);
+    let before = daw_core::decode_audio_arc(&before.path, None).expect("decode");
+    let after = daw_core::decode_audio_arc(&after.path, None).expect("decode");
+    for (a, b) in before.samples().iter().zip(after.samples()) {
+        assert!((a - b).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn test_clip_edits_and_undoing_them() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = drum_session();
+    session.add_track("Copy".to_string());
+    session.set_insert_length(InsertLength::Bar);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let id = session.tracks()[0].clips()[0].id;
+    let ranges = |session: &Session, track: usize| -> Vec<(u64, u64)> {
+        session.tracks()[track]
+            .clips()
+            .iter()
+            .map(|clip| (clip.start_tick, clip.end_tick))
+            .collect()
+    };
+    let bar = PPQN * 4;
+    let original = render_bytes(&session, dir.path(), "original");
+
+    // Splitting doesn't change the sound
+    assert!(!session.can_undo());
+    assert_eq!(session.split_clip_at(id, 0), None);
+    let right = session.split_clip_at(id, PPQN).expect("split");
+    assert_eq!(ranges(&session, 0), vec![(0, PPQN), (PPQN, bar)]);
+    assert_eq!(
+        render_bytes(&session, dir.path(), "split"),
+        original,
+        "the halves play on as the clip did"
+    );
+
+    assert!(session.trim_clip(right, PPQN * 2, bar));
+    let copy = session.duplicate_clip(right).expect("duplicate");
+    assert_eq!(
+        ranges(&session, 0),
+        vec![(0, PPQN), (PPQN * 2, bar), (bar, bar + PPQN * 2)]
+    );
+    assert!(session.move_clip(copy, 1, PPQN * 8));
+    assert!(!session.move_clip(copy, 99, 0));
+    assert_eq!(ranges(&session, 1), vec![(PPQN * 8, PPQN * 10)]);
+    assert!(session.delete_clip(id));
+    assert!(!session.delete_clip(id));
+    assert_eq!(ranges(&session, 0), vec![(PPQN * 2, bar)]);
+
+    // Every edit steps back, then forward again
+    while session.undo() {}
+    assert_eq!(ranges(&session, 0), vec![(0, bar)]);
+    assert!(ranges(&session, 1).is_empty());
+    assert_eq!(render_bytes(&session, dir.path(), "undone"), original);
+    assert!(session.redo());
+    assert!(session.redo());
+    assert_eq!(ranges(&session, 0), vec![(0, PPQN), (PPQN * 2, bar)]);
+
+    // A new edit drops what could be redone
+    assert!(session.delete_clip(right));
+    assert!(!session.can_redo());
+}
+
+#[test]
+fn test_freezing_a_track_and_unfreezing_it() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let project_path = dir.path().join("freeze.dawproj");
+    let mut session = drum_session();
+    session.add_track("Empty".to_string());
+    session.save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.set_snap_mode(SnapMode::Beat);
+    session.set_insert_length(InsertLength::Beat);
+    session.insert_sample_at_cursor(TrackId(0), kick.clone(), kick_ref.clone(), "kick".into());
+    session.set_cursor(PPQN * 2);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_track_volume(0, 0.5);
+    let empty = session.tracks()[1].id.0;
+    let originals: Vec<ClipId> = session.tracks()[0].clips().iter().map(|c| c.id).collect();
+
+    assert!(session.freeze_track(empty).is_err());
+    let before = session
+        .render_to_file(&dir.path().join("before.wav"))
+        .expect("render");
+    let id = session.freeze_track(0).expect("freeze");
+    assert!(session.is_track_frozen(0));
+    assert!(session.freeze_track(0).is_err());
+    assert!(dir.path().join("audio/bounce-001.wav").exists());
+    let clips = session.tracks()[0].clips();
+    assert_eq!(clips.len(), 1);
+    assert_eq!(clips[0].id, id);
+    assert_eq!((clips[0].start_tick, clips[0].end_tick), (0, PPQN * 3));
+
+    // The frozen clip still goes through the track's volume, so it sounds the same
+    let after = session
+        .render_to_file(&dir.path().join("after.wav"))
+        .expect("render");
+    assert_eq!(after.duration, before.duration);
+    let before = daw_core::decode_audio_arc(&before.path, None).expect("decode");
+    let after = daw_core::decode_audio_arc(&after.path, None).expect("decode");
+    for (a, b) in before.samples().iter().zip(after.samples()) {
+        assert!((a - b).abs() < 1e-4);
+    }
+
+    assert!(session.unfreeze_track(0));
+    assert!(!session.is_track_frozen(0));
+    assert!(!session.unfreeze_track(0));
+    let restored: Vec<ClipId> = session.tracks()[0].clips().iter().map(|c| c.id).collect();
+    assert_eq!(restored, originals);
+}
+
+#[test]
+fn test_recording_a_take_onto_the_armed_tracks() {
+    let dir = tempdir().expect("tempdir");
+    let mut session = drum_session();
+    session.add_track("Vocals".to_string());
+    let vocals = session.tracks()[1].id.0;
+    session.set_track_armed(vocals, true);
+    assert!(session.is_track_armed(vocals));
+
+    // Takes are written into the project, so it has to be saved first
+    let (_, input) = AudioInput::from_queue(1, SAMPLE_RATE, 16);
+    assert!(session.start_recording_with(input).is_err());
+    let project_path = dir.path().join("take.dawproj");
+    session.save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.set_track_armed(vocals, true);
+    session.set_cursor(PPQN * 4);
+
+    // Half a second is a beat at 120 BPM
+    let frames = SAMPLE_RATE as usize / 2;
+    let (mut feed, input) = AudioInput::from_queue(1, SAMPLE_RATE, frames);
+    session.start_recording_with(input).expect("record");
+    assert!(session.is_recording());
+    for i in 0..frames {
+        feed.push((i as f32 * 0.05).sin() * 0.5).expect("push");
+    }
+    session.poll();
+    let ids = session.stop_recording().expect("stop");
+    assert!(!session.is_recording());
+
+    assert!(dir.path().join("audio/recording-001.wav").exists());
+    assert!(session.tracks()[0].clips().is_empty());
+    let clips = session.tracks()[1].clips();
+    assert_eq!(ids, vec![clips[0].id]);
+    assert_eq!(
+        (clips[0].start_tick, clips[0].end_tick),
+        (PPQN * 4, PPQN * 5)
+    );
+
+    // The take comes off again in one undo
+    assert!(session.undo());
+    assert!(session.tracks()[1].clips().is_empty());
+
+    session.set_track_armed(vocals, false);
+    let (_, input) = AudioInput::from_queue(1, SAMPLE_RATE, 16);
+    assert!(session.start_recording_with(input).is_err());
+}
+
+#[test]
+fn test_punching_in_replaces_only_the_punch_region() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let project_path = dir.path().join("punch.dawproj");
+    drum_session().save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.set_insert_length(InsertLength::Bar);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_track_armed(0, true);
+    session.set_punch(2 * PPQN, PPQN);
+    assert!(session.is_punching());
+
+    // A bar-long take from the start, of which only the second beat is kept
+    let frames = SAMPLE_RATE as usize * 2;
+    let (mut feed, input) = AudioInput::from_queue(1, SAMPLE_RATE, frames);
+    session.start_recording_with(input).expect("record");
+    for _ in 0..frames {
+        feed.push(0.5).expect("push");
+    }
+    session.poll();
+    let ids = session.stop_recording().expect("stop");
+
+    let clips = session.tracks()[0].clips();
+    let spans: Vec<(u64, u64)> = clips.iter().map(|c| (c.start_tick, c.end_tick)).collect();
+    assert_eq!(
+        spans,
+        vec![(0, PPQN), (PPQN, 2 * PPQN), (2 * PPQN, 4 * PPQN)]
+    );
+    assert_eq!(clips[1].id, ids[0]);
+    assert_eq!(clips[1].audio_offset, SAMPLE_RATE as u64 / 2);
+
+    // A take that misses the punch region leaves the track alone
+    session.set_cursor(8 * PPQN);
+    let (mut feed, input) = AudioInput::from_queue(1, SAMPLE_RATE, frames);
+    session.start_recording_with(input).expect("record");
+    feed.push(0.5).expect("push");
+    session.poll();
+    assert!(session.stop_recording().expect("stop").is_empty());
+    assert_eq!(session.tracks()[0].clips().len(), 3);
+
+    session.toggle_punch();
+    assert!(!session.is_punching());
+    assert!(session.punch_region().is_some());
+}
+
+#[test]
+fn test_count_in_and_pre_roll_are_left_out_of_the_take() {
+    let dir = tempdir().expect("tempdir");
+    let project_path = dir.path().join("take.dawproj");
+    drum_session().save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.set_track_armed(0, true);
+    session.set_count_in_bars(1);
+    session.set_pre_roll_bars(1);
+    session.set_cursor(PPQN * 8);
+
+    // A bar of count-in and a bar of pre-roll are four seconds at 120 BPM; a beat
+    // after that is kept
+    let lead_in = SAMPLE_RATE as usize * 4;
+    let take = SAMPLE_RATE as usize / 2;
+    let (mut feed, input) = AudioInput::from_queue(1, SAMPLE_RATE, lead_in + take);
+    session.start_recording_with(input).expect("record");
+    assert_eq!(session.current_tick(), PPQN * 4);
+    for i in 0..lead_in + take {
+        let sample = if i < lead_in { 0.0 } else { 0.5 };
+        feed.push(sample).expect("push");
+    }
+    session.poll();
+    session.stop_recording().expect("stop");
+
+    let clips = session.tracks()[0].clips();
+    assert_eq!(
+        (clips[0].start_tick, clips[0].end_tick),
+        (PPQN * 8, PPQN * 9)
+    );
+    let audio = clips[0].audio().expect("audio");
+    assert!(audio.samples().iter().all(|&sample| sample == 0.5));
+}
+
+#[test]
+fn test_automation_points_are_edited_and_followed_by_renders() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let mut session = drum_session();
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let untouched = render_bytes(&session, dir.path(), "untouched");
+
+    // Points are kept in tick order as they're added and moved
+    let volume = |session: &Session| {
+        session.automation_lane(0, VOLUME_PARAMETER).map(|lane| {
+            lane.points
+                .iter()
+                .map(|p| (p.tick, p.value))
+                .collect::<Vec<_>>()
+        })
+    };
+    let curve = AutomationCurve::Linear;
+    assert_eq!(
+        session.add_automation_point(0, VOLUME_PARAMETER, PPQN, 2.0, curve),
+        Some(0)
+    );
+    assert_eq!(
+        session.add_automation_point(0, VOLUME_PARAMETER, 0, 0.5, curve),
+        Some(0)
+    );
+    assert_eq!(volume(&session), Some(vec![(0, 0.5), (PPQN, 1.0)]));
+    assert_eq!(
+        session.move_automation_point(0, VOLUME_PARAMETER, 0, 2 * PPQN, 0.0),
+        Some(1)
+    );
+    assert_eq!(volume(&session), Some(vec![(PPQN, 1.0), (2 * PPQN, 0.0)]));
+    assert_eq!(
+        session.add_automation_point(9, VOLUME_PARAMETER, 0, 0.5, curve),
+        None
+    );
+
+    // Held at the first point's value before it, so the kick plays as before
+    assert_eq!(render_bytes(&session, dir.path(), "held"), untouched);
+    session.move_automation_point(0, VOLUME_PARAMETER, 0, 0, 0.25);
+    assert_ne!(render_bytes(&session, dir.path(), "quieter"), untouched);
+
+    let project_path = dir.path().join("automation.dawproj");
+    session.save(&project_path).expect("save");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(volume(&reloaded), volume(&session));
+
+    // Deleting the last point removes the lane, handing back to the fader
+    assert!(reloaded.delete_automation_point(0, VOLUME_PARAMETER, 1));
+    assert!(reloaded.delete_automation_point(0, VOLUME_PARAMETER, 0));
+    assert!(!reloaded.delete_automation_point(0, VOLUME_PARAMETER, 0));
+    assert_eq!(volume(&reloaded), None);
+    assert_eq!(render_bytes(&reloaded, dir.path(), "restored"), untouched);
+}
+
+#[test]
+fn test_track_effects_are_edited_saved_and_rendered() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let mut session = drum_session();
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let untouched = render_bytes(&session, dir.path(), "untouched");
+
+    let kinds = |session: &Session| {
+        session
+            .track_effects(0)
+            .iter()
+            .map(Effect::kind)
+            .collect::<Vec<_>>()
+    };
+    let eq = Effect::from_kind("eq").unwrap();
+    assert_eq!(session.add_track_effect(0, eq), Some(0));
+    assert_eq!(session.add_track_effect(9, eq), None);
+    // A flat EQ leaves the audio alone
+    assert_eq!(render_bytes(&session, dir.path(), "flat"), untouched);
+
+    assert_eq!(
+        session.add_track_effect(0, Effect::from_kind("reverb").unwrap()),
+        Some(1)
+    );
+    assert!(session.set_track_effect_parameter(0, 1, "mix", 0.5));
+    assert!(!session.set_track_effect_parameter(0, 1, "ratio", 2.0));
+    assert!(!session.set_track_effect_parameter(0, 2, "mix", 0.5));
+    assert!(session.move_track_effect(0, 1, 0));
+    assert!(!session.move_track_effect(0, 0, 2));
+    assert_eq!(kinds(&session), vec!["reverb", "eq"]);
+    let reverbed = render_bytes(&session, dir.path(), "reverbed");
+    assert_ne!(reverbed, untouched);
+
+    let project_path = dir.path().join("effects.dawproj");
+    session.save(&project_path).expect("save");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.track_effects(0), session.track_effects(0));
+    assert_eq!(reloaded.track_effects(0)[0].parameters()[2].value, 0.5);
+    assert_eq!(render_bytes(&reloaded, dir.path(), "reloaded"), reverbed);
+
+    assert!(reloaded.remove_track_effect(0, 0));
+    assert!(reloaded.remove_track_effect(0, 0));
+    assert!(!reloaded.remove_track_effect(0, 0));
+    assert_eq!(render_bytes(&reloaded, dir.path(), "restored"), untouched);
+}
+
+#[test]
+fn test_looped_and_reversed_clip_survives_reload() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let project_path = dir.path().join("loop.dawproj");
+    drum_session().save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.set_insert_length(InsertLength::Beat);
+    session.insert_sample_at_cursor(TrackId(0), kick.clone(), kick_ref, "kick".to_string());
+
+    let mut clip = session.tracks()[0].clips()[0].clone();
+    clip.gain = 0.5;
+    clip.looped = true;
+    clip.reversed = true;
+    session.add_clip(TrackId(0), clip).expect("add");
+    session.save_in_place().expect("save");
+
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    let clip = &reloaded.tracks()[0].clips()[0];
+    assert_eq!(clip.gain, 0.5);
+    assert!(clip.looped);
+    assert!(clip.reversed);
+
+    // The quarter-second kick plays backwards twice over the half-second beat
+    let bounced = reloaded.bounce_clip(0, clip.id).expect("bounce");
+    assert_eq!(bounced.frames(), SAMPLE_RATE as usize / 2);
+    let last = kick.frames() - 1;
+    for frame in [100, kick.frames() + 100] {
+        let expected = kick.samples()[(last - 100) * 2] * 0.5;
+        assert!((bounced.samples()[frame * 2] - expected).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_imported_audio_file_is_saved_with_the_project() {
+    let dir = tempdir().expect("tempdir");
+    write_sample(dir.path(), "kick", 60.0);
+    std::fs::create_dir(dir.path().join("other")).expect("mkdir");
+    write_sample(&dir.path().join("other"), "kick", 90.0);
+
+    let project_path = dir.path().join("import.dawproj");
+    drum_session().save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    let id = session
+        .import_audio_file(&dir.path().join("kick.wav"), TrackId(0), PPQN)
+        .expect("import");
+    // The same file again shares its sample; another file named kick doesn't
+    session
+        .import_audio_file(&dir.path().join("kick.wav"), TrackId(0), 2 * PPQN)
+        .expect("import again");
+    session
+        .import_audio_file(&dir.path().join("other/kick.wav"), TrackId(0), 4 * PPQN)
+        .expect("import other");
+
+    let clips = session.tracks()[0].clips();
+    assert_eq!(clips.len(), 3);
+    assert_eq!(clips[0].id, id);
+    // A quarter second is half a beat at 120 BPM
+    assert_eq!(
+        (clips[0].start_tick, clips[0].end_tick),
+        (PPQN, PPQN * 3 / 2)
+    );
+    assert_eq!(clips[0].name, "kick");
+    assert_eq!(clips[1].name, "kick");
+    assert_eq!(clips[2].name, "kick 2");
+    assert_eq!(
+        session.sample_refs()["kick 2"],
+        SampleRef::ProjectRelative(PathBuf::from("other/kick.wav"))
+    );
+
+    assert!(
+        session
+            .import_audio_file(&dir.path().join("missing.wav"), TrackId(0), 0)
+            .is_err()
+    );
+    assert!(
+        session
+            .import_audio_file(&dir.path().join("kick.wav"), TrackId(7), 0)
+            .is_err()
+    );
+    assert_eq!(session.tracks()[0].clips().len(), 3);
+
+    session.save_in_place().expect("save");
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    let names: Vec<&str> = reloaded.tracks()[0]
+        .clips()
+        .iter()
+        .map(|clip| clip.name.as_str())
+        .collect();
+    assert_eq!(names, ["kick", "kick", "kick 2"]);
+    assert!(reloaded.offline_clips().is_empty());
+}
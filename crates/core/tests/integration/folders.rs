@@ -0,0 +1,217 @@
+//! Grouping tracks into folders and mixing them as a group.
+
+use crate::{SAMPLE_RATE, render_bytes, write_sample};
+use daw_core::{Effect, Session, Track, TrackId};
+use tempfile::tempdir;
+
+fn band() -> Session {
+    let tracks = ["Kick", "Snare", "Bass", "Hats"]
+        .iter()
+        .enumerate()
+        .map(|(id, name)| Track::new(TrackId(id as u64), name.to_string()))
+        .collect();
+    Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE)
+}
+
+fn layout(session: &Session) -> Vec<(&str, Option<u64>)> {
+    session
+        .tracks()
+        .iter()
+        .map(|t| (t.name.as_str(), t.parent.as_ref().map(|p| p.0)))
+        .collect()
+}
+
+#[test]
+fn test_group_and_ungroup() {
+    let mut session = band();
+
+    // Grouping gathers the tracks under a new folder at the first one's position
+    let drums = session
+        .group_tracks(&[0, 1, 3], "Drums".to_string())
+        .expect("group");
+    assert!(session.tracks()[0].is_folder);
+    assert_eq!(
+        layout(&session),
+        vec![
+            ("Drums", None),
+            ("Kick", Some(drums)),
+            ("Snare", Some(drums)),
+            ("Hats", Some(drums)),
+            ("Bass", None),
+        ]
+    );
+
+    // Folders nest, and ungrouping moves the contents up one level
+    let cymbals = session
+        .group_tracks(&[3], "Cymbals".to_string())
+        .expect("group");
+    assert_eq!(
+        session.tracks()[3].parent.as_ref().map(|p| p.0),
+        Some(drums)
+    );
+    session.ungroup_tracks(drums);
+    assert_eq!(
+        layout(&session),
+        vec![
+            ("Kick", None),
+            ("Snare", None),
+            ("Cymbals", None),
+            ("Hats", Some(cymbals)),
+            ("Bass", None),
+        ]
+    );
+}
+
+#[test]
+fn test_parent_cycles_are_rejected() {
+    let mut session = band();
+    let outer = session.group_tracks(&[0], "Outer".to_string()).unwrap();
+    let inner = session.group_tracks(&[0], "Inner".to_string()).unwrap();
+
+    // A folder can't move inside itself, and plain tracks can't be parents
+    session.set_track_parent(outer, Some(inner));
+    assert_eq!(session.tracks()[0].parent, None);
+    session.set_track_parent(2, Some(1));
+    assert_eq!(
+        session
+            .tracks()
+            .iter()
+            .find(|t| t.id.0 == 2)
+            .unwrap()
+            .parent,
+        None
+    );
+
+    session.set_track_parent(2, Some(inner));
+    assert_eq!(
+        session
+            .tracks()
+            .iter()
+            .find(|t| t.id.0 == 2)
+            .unwrap()
+            .parent,
+        Some(TrackId(inner))
+    );
+}
+
+#[test]
+fn test_moving_a_folder_takes_its_tracks_along() {
+    let mut session = band();
+    let drums = session
+        .group_tracks(&[0, 1], "Drums".to_string())
+        .expect("group");
+
+    // The index counts the tracks that stay: Bass, Hats
+    session.move_track(drums, 1);
+    assert_eq!(
+        layout(&session),
+        vec![
+            ("Bass", None),
+            ("Drums", None),
+            ("Kick", Some(drums)),
+            ("Snare", Some(drums)),
+            ("Hats", None),
+        ]
+    );
+
+    // A track keeps its folder wherever it goes, and past the end is the end
+    session.move_track(0, 99);
+    assert_eq!(layout(&session).last(), Some(&("Kick", Some(drums))));
+    session.move_track(3, 0);
+    assert_eq!(layout(&session)[0], ("Hats", None));
+}
+
+#[test]
+fn test_folder_mute_and_volume_apply_to_the_group() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (bass, bass_ref) = write_sample(dir.path(), "bass", 40.0);
+
+    let mut session = band();
+    session.insert_sample_at_cursor(TrackId(0), kick.clone(), kick_ref, "kick".to_string());
+    session.insert_sample_at_cursor(TrackId(2), bass, bass_ref.clone(), "bass".to_string());
+    let drums = session.group_tracks(&[0, 1], "Drums".to_string()).unwrap();
+
+    // Folders can't hold clips themselves
+    session.insert_sample_at_cursor(TrackId(drums), kick, bass_ref, "kick".to_string());
+    assert!(session.tracks()[0].clips().is_empty());
+
+    let full = render_bytes(&session, dir.path(), "full");
+
+    // Muting the folder silences the kick but leaves the bass
+    session.toggle_track_enabled(drums);
+    let folder_muted = render_bytes(&session, dir.path(), "folder_muted");
+    session.toggle_track_enabled(drums);
+    session.toggle_track_enabled(0);
+    let kick_muted = render_bytes(&session, dir.path(), "kick_muted");
+    session.toggle_track_enabled(0);
+    assert_eq!(folder_muted, kick_muted);
+    assert_ne!(folder_muted, full);
+
+    // Folder volume scales the tracks inside it
+    session.set_track_volume(drums, 0.5);
+    let folder_half = render_bytes(&session, dir.path(), "folder_half");
+    session.set_track_volume(drums, 1.0);
+    session.set_track_volume(0, 0.5);
+    let kick_half = render_bytes(&session, dir.path(), "kick_half");
+    assert_eq!(folder_half, kick_half);
+}
+
+#[test]
+fn test_folders_survive_save_and_reload() {
+    let dir = tempdir().expect("tempdir");
+    let mut session = band();
+    let drums = session.group_tracks(&[0, 1], "Drums".to_string()).unwrap();
+    session.set_track_volume(drums, 0.25);
+
+    let project_path = dir.path().join("folders.dawproj");
+    session.save(&project_path).expect("save");
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+
+    assert_eq!(layout(&reloaded), layout(&session));
+    assert!(reloaded.tracks()[0].is_folder);
+    assert_eq!(reloaded.tracks()[0].volume, 0.25);
+}
+
+#[test]
+fn test_tracks_send_to_a_bus() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let mut session = band();
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let dry = render_bytes(&session, dir.path(), "dry");
+
+    let reverb = session.add_bus_track("Reverb".to_string()).0;
+    session.add_track_effect(reverb, Effect::from_kind("reverb").unwrap());
+    // Only regular tracks send, and only to buses
+    assert!(!session.set_track_send(0, 1, 0.5, false));
+    assert!(!session.set_track_send(reverb, reverb, 0.5, false));
+    assert!(session.set_track_send(0, reverb, 2.0, false));
+    assert!(session.set_track_send(0, reverb, 0.5, true));
+    assert_eq!(session.tracks()[0].sends.len(), 1);
+    assert_eq!(session.tracks()[0].sends[0].level, 0.5);
+    let sent = render_bytes(&session, dir.path(), "sent");
+    assert_ne!(sent, dry);
+
+    // Soloing the kick keeps the bus it sends to, muting the bus doesn't
+    session.toggle_track_solo(0);
+    assert_eq!(render_bytes(&session, dir.path(), "soloed"), sent);
+    session.toggle_track_solo(0);
+    session.toggle_track_enabled(reverb);
+    assert_eq!(render_bytes(&session, dir.path(), "bus_muted"), dry);
+    session.toggle_track_enabled(reverb);
+
+    let project_path = dir.path().join("buses.dawproj");
+    session.save(&project_path).expect("save");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert!(reloaded.tracks()[4].is_bus);
+    assert_eq!(reloaded.tracks()[0].sends, session.tracks()[0].sends);
+    assert_eq!(render_bytes(&reloaded, dir.path(), "reloaded"), sent);
+
+    // Removing the bus drops the sends to it
+    reloaded.remove_track(reverb);
+    assert!(reloaded.tracks()[0].sends.is_empty());
+    assert!(!reloaded.remove_track_send(0, reverb));
+    assert_eq!(render_bytes(&reloaded, dir.path(), "removed"), dry);
+}
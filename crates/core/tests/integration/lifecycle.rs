@@ -0,0 +1,1459 @@
+//! Create → edit → save → reload → render.
+
+use crate::{SAMPLE_RATE, render_bytes, write_sample};
+use daw_core::{
+    AudioArc, CacheTier, ClipId, DEFAULT_RENDER_SAMPLE_RATE, Dither, Effect, EndBehavior,
+    LimiterSettings, MasterSettings, MusicalKey, Normalization, PPQN, ProjectChange, ProjectFormat,
+    RenderFormat, RenderOptions, RenderTail, SampleRef, SampleRoot, SaveAsSamples, SaveOptions,
+    Session, SettingChange, SnapMode, TempoChange, TempoMap, TimeSignatureChange, Track, TrackId,
+    Watermark, WatermarkSound, WaveformData, backup_path, decode_audio_arc,
+};
+use daw_transport::gain_to_db;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::tempdir;
+
+#[test]
+fn test_saved_project_reloads_and_renders_identically() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 220.0);
+
+    // Build a session without an audio device
+    let tracks = vec![
+        Track::new(TrackId(0), "Kick".to_string()),
+        Track::new(TrackId(1), "Snare".to_string()),
+    ];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.set_name("Lifecycle".to_string());
+
+    // Place a kick on every beat of the first bar and a snare on beats 2 and 4
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in 0..4 {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(0),
+            kick.clone(),
+            kick_ref.clone(),
+            "kick".to_string(),
+        );
+    }
+    for beat in [1, 3] {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(1),
+            snare.clone(),
+            snare_ref.clone(),
+            "snare".to_string(),
+        );
+    }
+
+    // Mix edits
+    session.set_track_volume(1, 0.5);
+    session.set_track_pan(1, -0.25);
+    session.set_track_color(0, Some([200, 40, 40]));
+    assert_eq!(session.tracks()[0].clips().len(), 4);
+    assert_eq!(session.tracks()[1].clips().len(), 2);
+
+    let project_path = dir.path().join("lifecycle.dawproj");
+    session.save(&project_path).expect("save");
+    let original = render_bytes(&session, dir.path(), "original");
+
+    // Reload and check that everything survived the round trip
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.name(), "Lifecycle");
+    assert_eq!(reloaded.tempo(), 120.0);
+    assert_eq!(reloaded.project_path(), Some(project_path.as_path()));
+
+    let snare_track = &reloaded.tracks()[1];
+    assert_eq!(snare_track.volume, 0.5);
+    assert_eq!(snare_track.pan, -0.25);
+    assert_eq!(reloaded.tracks()[0].color, Some([200, 40, 40]));
+
+    let starts: Vec<u64> = snare_track.clips().iter().map(|c| c.start_tick).collect();
+    assert_eq!(starts, vec![PPQN, 3 * PPQN]);
+
+    let rendered = render_bytes(&reloaded, dir.path(), "reloaded");
+    assert_eq!(rendered, original, "reloaded project renders differently");
+}
+
+#[test]
+fn test_missing_sample_does_not_stop_reload() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Kick".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+
+    let project_path = dir.path().join("missing.dawproj");
+    session.save(&project_path).expect("save");
+    std::fs::remove_file(dir.path().join("kick.wav")).expect("remove sample");
+
+    // The clip goes offline but the rest of the project still loads
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks().len(), 1);
+    assert!(reloaded.tracks()[0].clips().is_empty());
+}
+
+#[test]
+fn test_validating_finds_missing_samples_before_loading() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 220.0);
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.set_snap_mode(SnapMode::Beat);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_cursor(PPQN);
+    session.insert_sample_at_cursor(TrackId(0), snare, snare_ref.clone(), "snare".to_string());
+    let project_path = dir.path().join("song.dawproj");
+    session.save(&project_path).expect("save");
+
+    let report = Session::validate_project(&project_path, None).expect("validate");
+    assert!(report.is_ok());
+    assert_eq!((report.clip_count, report.sample_count), (2, 2));
+
+    std::fs::remove_file(dir.path().join("snare.wav")).expect("remove sample");
+    let report = Session::validate_project(&project_path, None).expect("validate");
+    assert!(!report.is_ok());
+    assert_eq!(report.missing_samples.len(), 1);
+    assert_eq!(report.missing_samples[0].sample_ref, snare_ref);
+    assert_eq!(report.missing_samples[0].clips, ["snare"]);
+}
+
+#[test]
+fn test_missing_samples_are_relinked_from_a_folder() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 220.0);
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in 0..2 {
+        session.set_cursor(beat * 2 * PPQN);
+        session.insert_sample_at_cursor(TrackId(0), kick.clone(), kick_ref.clone(), "kick".into());
+    }
+    session.set_cursor(PPQN);
+    session.insert_sample_at_cursor(TrackId(0), snare, snare_ref, "snare".to_string());
+    let project_path = dir.path().join("song.dawproj");
+    session.save(&project_path).expect("save");
+    let ids: Vec<ClipId> = session.tracks()[0].clips().iter().map(|c| c.id).collect();
+
+    let library = dir.path().join("library/drums");
+    std::fs::create_dir_all(&library).expect("mkdir");
+    std::fs::rename(dir.path().join("kick.wav"), library.join("kick.wav")).expect("move");
+    std::fs::rename(dir.path().join("snare.wav"), library.join("snare.wav")).expect("move");
+
+    // Offline clips survive saving until they're relinked
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    assert_eq!(session.offline_clips().len(), 3);
+    session.save_in_place().expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reopen");
+    assert_eq!(session.offline_clips().len(), 3);
+    assert!(session.tracks()[0].clips().is_empty());
+
+    // Relinking one kick brings back the other too
+    let kick_index = session
+        .offline_clips()
+        .iter()
+        .position(|offline| offline.name == "kick")
+        .expect("offline kick");
+    let relinked = session
+        .relink_offline_clip(kick_index, &library.join("kick.wav"))
+        .expect("relink");
+    assert_eq!(relinked, vec![ids[0], ids[2]]);
+    assert_eq!(session.offline_clips().len(), 1);
+
+    assert_eq!(session.relink_offline_clips_in(dir.path()), vec![ids[1]]);
+    assert!(session.offline_clips().is_empty());
+    let starts: Vec<u64> = session.tracks()[0]
+        .clips()
+        .iter()
+        .map(|c| c.start_tick)
+        .collect();
+    assert_eq!(starts, vec![0, PPQN, 2 * PPQN]);
+    assert_eq!(
+        session.sample_refs()["snare"],
+        SampleRef::ProjectRelative("library/drums/snare.wav".into())
+    );
+
+    session.save_in_place().expect("save");
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert!(reloaded.offline_clips().is_empty());
+    assert_eq!(reloaded.tracks()[0].clips().len(), 3);
+}
+
+#[test]
+fn test_cache_stats_show_memory_per_sample() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 220.0);
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.set_snap_mode(SnapMode::Beat);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_cursor(PPQN);
+    session.insert_sample_at_cursor(TrackId(0), snare, snare_ref, "snare".to_string());
+    let project_path = dir.path().join("stats.dawproj");
+    session.save(&project_path).expect("save");
+
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    let stats = reloaded.cache_stats();
+    let sample_bytes = SAMPLE_RATE as usize / 4 * 2 * size_of::<f32>();
+    assert_eq!(stats.originals, 2);
+    assert_eq!(stats.original_bytes, 2 * sample_bytes);
+    assert_eq!(stats.bytes, stats.original_bytes);
+
+    let largest = reloaded.largest_cached_audio(1);
+    assert_eq!(largest.len(), 1);
+    assert_eq!(largest[0].tier, CacheTier::Original);
+    assert_eq!(largest[0].bytes, sample_bytes);
+    assert!(largest[0].in_use, "clips play the cached audio");
+}
+
+#[test]
+fn test_recording_is_written_into_the_project() {
+    let dir = tempdir().expect("tempdir");
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Vocals".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    let take = AudioArc::new(vec![0.1; SAMPLE_RATE as usize], SAMPLE_RATE, 1);
+    assert!(
+        session.ingest_recording(&take).is_err(),
+        "nowhere to write yet"
+    );
+
+    let project_path = dir.path().join("recorded.dawproj");
+    session.save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    let (audio, sample_ref) = session.ingest_recording(&take).expect("ingest");
+    assert_eq!(sample_ref.path(), Path::new("audio/recording-001.wav"));
+    assert!(dir.path().join("audio/recording-001.wav").exists());
+    session.insert_sample_at_cursor(TrackId(0), audio, sample_ref, "take 1".to_string());
+    session.save_in_place().expect("save");
+
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    let clips = reloaded.tracks()[0].clips();
+    assert_eq!(clips.len(), 1);
+    assert_eq!(clips[0].audio().unwrap().frames(), SAMPLE_RATE as usize);
+}
+
+#[test]
+fn test_autosave_is_recovered_after_a_crash() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let project_path = dir.path().join("song.dawproj");
+    let autosave_path = dir.path().join("song.autosave");
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.save(&project_path).expect("save");
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    assert_eq!(session.recoverable_autosave(), None);
+    // Nothing unsaved yet, so nothing is kept
+    assert!(!session.autosave().expect("autosave"));
+    assert!(!autosave_path.exists());
+
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_tempo(96.0);
+    assert!(session.autosave().expect("autosave"));
+    // The session goes away without saving, as in a crash
+    drop(session);
+
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reopen");
+    assert_eq!(
+        session.recoverable_autosave(),
+        Some(autosave_path.as_path())
+    );
+    assert!(session.tracks()[0].clips().is_empty());
+    let changes = session.recoverable_autosave_changes().expect("changes");
+    assert_eq!(changes.len(), 2);
+    assert!(matches!(
+        &changes[0],
+        ProjectChange::Setting(SettingChange { name: "tempo", .. })
+    ));
+    assert!(matches!(
+        &changes[1],
+        ProjectChange::ClipAdded { track_id: 0, clip } if clip.name == "kick"
+    ));
+    session.recover_autosave().expect("recover");
+    assert_eq!(session.recoverable_autosave(), None);
+    assert_eq!(session.tempo(), 96.0);
+    assert_eq!(session.tracks()[0].clips().len(), 1);
+    // Kept until the recovered work is saved
+    assert!(autosave_path.exists());
+    session.save_in_place().expect("save");
+    assert!(!autosave_path.exists());
+
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.recoverable_autosave(), None);
+    assert_eq!(reloaded.tracks()[0].clips().len(), 1);
+}
+
+#[test]
+fn test_discarded_autosave_is_not_offered_again() {
+    let dir = tempdir().expect("tempdir");
+    let project_path = dir.path().join("song.dawproj");
+    Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE)
+        .save(&project_path)
+        .expect("save");
+
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("open");
+    session.add_track("Bass".to_string());
+    session.autosave().expect("autosave");
+    drop(session);
+
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reopen");
+    assert!(session.recoverable_autosave().is_some());
+    session.discard_autosave().expect("discard");
+    assert!(session.tracks().is_empty());
+
+    let session = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(session.recoverable_autosave(), None);
+}
+
+#[test]
+fn test_collected_project_opens_without_its_original_samples() {
+    let dir = tempdir().expect("tempdir");
+    let dev_root = dir.path().join("dev");
+    let old = dir.path().join("old");
+    std::fs::create_dir_all(dev_root.join("samples")).expect("mkdir");
+    std::fs::create_dir_all(&old).expect("mkdir");
+    let (kick, _) = write_sample(&dev_root.join("samples"), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(&old, "snare", 220.0);
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.set_snap_mode(SnapMode::Beat);
+    let kick_ref = SampleRef::DevRoot("kick.wav".into());
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_cursor(PPQN);
+    session.insert_sample_at_cursor(TrackId(0), snare, snare_ref, "snare".to_string());
+    session.save(&old.join("song.dawproj")).expect("save");
+
+    let mut session =
+        Session::from_project_offline(&old.join("song.dawproj"), Some(&dev_root), SAMPLE_RATE)
+            .expect("open");
+    let moved = dir.path().join("moved/song.dawproj");
+    let report = session.save_with_samples(&moved).expect("collect");
+    assert_eq!(report.copied.len(), 2);
+    assert!(report.missing.is_empty());
+    assert_eq!(session.project_path(), Some(moved.as_path()));
+    assert_eq!(
+        session.sample_refs()["kick"],
+        SampleRef::ProjectRelative("audio/kick.wav".into())
+    );
+
+    std::fs::remove_dir_all(&dev_root).expect("remove dev root");
+    std::fs::remove_dir_all(&old).expect("remove old project");
+    let reloaded = Session::from_project_offline(&moved, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips().len(), 2);
+}
+
+#[test]
+fn test_library_samples_are_found_wherever_the_library_is() {
+    let dir = tempdir().expect("tempdir");
+    let library = dir.path().join("home/samples");
+    std::fs::create_dir_all(library.join("kicks")).expect("mkdir");
+    write_sample(&library.join("kicks"), "909", 60.0);
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    session.set_sample_roots(vec![SampleRoot::new("Drums", &library)]);
+    let track = session.add_track("Kick".to_string());
+    let clip = session
+        .import_audio_file(&library.join("kicks/909.wav"), track, 0)
+        .expect("import");
+    let library_ref = SampleRef::Library {
+        root_name: "Drums".to_string(),
+        path: "kicks/909.wav".into(),
+    };
+    assert_eq!(session.sample_refs()["909"], library_ref);
+    let project_path = dir.path().join("projects/song.dawproj");
+    std::fs::create_dir_all(project_path.parent().unwrap()).expect("mkdir");
+    session.save(&project_path).expect("save");
+
+    // On another machine the library is somewhere else, and nothing's found until
+    // it's pointed at
+    let elsewhere = dir.path().join("studio/drums");
+    std::fs::create_dir_all(elsewhere.parent().unwrap()).expect("mkdir");
+    std::fs::rename(&library, &elsewhere).expect("move library");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.offline_clips().len(), 1);
+    assert!(reloaded.set_sample_roots(vec![]).is_empty());
+
+    let relinked = reloaded.set_sample_roots(vec![
+        SampleRoot::new("Loops", dir.path().join("studio/loops")),
+        SampleRoot::new("Drums", &elsewhere),
+    ]);
+    assert_eq!(relinked, vec![clip]);
+    assert!(reloaded.offline_clips().is_empty());
+    assert_eq!(reloaded.sample_refs()["909"], library_ref);
+}
+
+#[test]
+fn test_saved_as_project_still_finds_its_samples() {
+    let dir = tempdir().expect("tempdir");
+    let old = dir.path().join("old");
+    std::fs::create_dir_all(&old).expect("mkdir");
+    let (kick, kick_ref) = write_sample(&old, "kick", 60.0);
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.save(&old.join("song.dawproj")).expect("save");
+
+    // Referenced where it is, so the copy opens while the old folder is around
+    let mut session =
+        Session::from_project_offline(&old.join("song.dawproj"), None, SAMPLE_RATE).expect("open");
+    let referenced = dir.path().join("referenced/song.dawproj");
+    std::fs::create_dir_all(referenced.parent().unwrap()).expect("mkdir");
+    let report = session
+        .save_as(&referenced, SaveAsSamples::Reference)
+        .expect("save as");
+    assert!(report.copied.is_empty());
+    assert!(report.missing.is_empty());
+    assert_eq!(session.project_path(), Some(referenced.as_path()));
+    let reloaded = Session::from_project_offline(&referenced, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips().len(), 1);
+
+    // Copied along, so it opens on its own
+    let copied = dir.path().join("copied/song.dawproj");
+    std::fs::create_dir_all(copied.parent().unwrap()).expect("mkdir");
+    let report = session
+        .save_as(&copied, SaveAsSamples::Copy)
+        .expect("save as");
+    assert_eq!(
+        report.copied,
+        vec![dir.path().join("copied/audio/kick.wav")]
+    );
+    assert_eq!(
+        session.sample_refs()["kick"],
+        SampleRef::ProjectRelative("audio/kick.wav".into())
+    );
+    std::fs::remove_dir_all(&old).expect("remove old project");
+    let reloaded = Session::from_project_offline(&copied, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips().len(), 1);
+}
+
+#[test]
+fn test_project_with_embedded_samples_opens_on_its_own() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let path = dir.path().join("sketch.dawproj");
+    session.save(&path).expect("save");
+
+    let mut session = Session::from_project_offline(&path, None, SAMPLE_RATE).expect("open");
+    assert!(!session.embeds_samples());
+    session.set_embed_samples(true);
+    session.save_in_place().expect("save embedded");
+
+    std::fs::remove_file(dir.path().join("kick.wav")).expect("remove sample");
+    let mut reloaded = Session::from_project_offline(&path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips().len(), 1);
+    assert!(reloaded.tracks()[0].clips()[0].audio().is_some());
+    assert!(reloaded.embeds_samples());
+    assert_eq!(
+        reloaded.sample_refs()["kick"],
+        SampleRef::Embedded("kick.wav".into())
+    );
+
+    // With embedding off, samples that only exist in the file stay in it
+    reloaded.set_embed_samples(false);
+    reloaded.save(&path).expect("save again");
+    let reloaded = Session::from_project_offline(&path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips().len(), 1);
+}
+
+#[test]
+fn test_zipped_project_travels_as_one_file() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let path = dir.path().join("sketch.dawproj");
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session
+        .save_as(&path, SaveAsSamples::Reference)
+        .expect("save");
+    session.set_embed_samples(true);
+    session.set_save_options(SaveOptions {
+        zip: true,
+        ..Default::default()
+    });
+    session.save_in_place().expect("save zipped");
+    assert!(std::fs::read(&path).unwrap().starts_with(b"PK"));
+
+    // Moved on its own to a folder without the sample
+    let elsewhere = tempdir().expect("tempdir");
+    let moved = elsewhere.path().join("sketch.dawproj");
+    std::fs::copy(&path, &moved).expect("copy");
+    let mut reloaded = Session::from_project_offline(&moved, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips().len(), 1);
+    assert!(reloaded.tracks()[0].clips()[0].audio().is_some());
+    assert!(reloaded.save_options().zip);
+    // Saved the same way, so an unchanged project leaves nothing to recover
+    assert!(!reloaded.autosave().expect("autosave"));
+}
+
+#[test]
+fn test_compressed_binary_project_keeps_its_format() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let path = dir.path().join("big.dawproj");
+
+    let mut session = Session::new_offline(
+        vec![Track::new(TrackId(0), "Drums".to_string())],
+        120.0,
+        (4, 4),
+        SAMPLE_RATE,
+    );
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let options = SaveOptions {
+        format: ProjectFormat::MessagePack,
+        compress: true,
+        ..Default::default()
+    };
+    session.set_save_options(options);
+    session.save(&path).expect("save");
+    // Written as a zstd frame
+    assert!(
+        std::fs::read(&path)
+            .unwrap()
+            .starts_with(&[0x28, 0xb5, 0x2f, 0xfd])
+    );
+
+    let mut reloaded = Session::from_project_offline(&path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips().len(), 1);
+    assert_eq!(reloaded.save_options(), options);
+    // Autosaved the same way, so an unchanged project leaves nothing to recover
+    assert!(!reloaded.autosave().expect("autosave"));
+}
+
+#[test]
+fn test_saving_keeps_backups_but_autosaving_does_not() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("song.dawproj");
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    session.set_save_options(SaveOptions {
+        backups: 3,
+        ..Default::default()
+    });
+    session.set_name("First".to_string());
+    session
+        .save_as(&path, SaveAsSamples::Reference)
+        .expect("save");
+    session.set_name("Second".to_string());
+    session.save_in_place().expect("save");
+
+    let backup = Session::from_project_offline(&backup_path(&path, 1), None, SAMPLE_RATE)
+        .expect("load backup");
+    assert_eq!(backup.name(), "First");
+
+    session.set_name("Third".to_string());
+    assert!(session.autosave().expect("autosave"));
+    assert!(!backup_path(&path, 2).exists());
+    assert!(!backup_path(&path.with_extension("autosave"), 1).exists());
+}
+
+#[test]
+fn test_reopened_project_is_where_it_was_left() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("song.dawproj");
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    session.set_snap_mode(SnapMode::HalfBeat);
+    session.set_cursor(PPQN * 5 / 2);
+    session.set_metronome_enabled(true);
+    session.set_metronome_volume(0.3);
+    session.set_zoom(64.0);
+    session.save(&path).expect("save");
+
+    let reloaded = Session::from_project_offline(&path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.snap_mode(), SnapMode::HalfBeat);
+    assert_eq!(reloaded.cursor_tick(), Some(PPQN * 5 / 2));
+    assert!(reloaded.metronome_enabled());
+    assert_eq!(reloaded.metronome_volume(), 0.3);
+    assert_eq!(reloaded.zoom(), Some(64.0));
+}
+
+#[test]
+fn test_tempo_changes_are_saved() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("song.dawproj");
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    session.set_tempo_map(TempoMap {
+        tempo_changes: vec![
+            TempoChange {
+                tick: PPQN * 16,
+                bpm: 90.0,
+            },
+            TempoChange {
+                tick: PPQN * 8,
+                bpm: 140.0,
+            },
+        ],
+        time_signature_changes: vec![TimeSignatureChange {
+            tick: PPQN * 8,
+            time_signature: (7, 8),
+        }],
+    });
+    session.save(&path).expect("save");
+
+    let reloaded = Session::from_project_offline(&path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tempo(), 120.0);
+    let tempo_map = reloaded.tempo_map();
+    assert_eq!(tempo_map, session.tempo_map());
+    assert_eq!(tempo_map.tempo_changes[0].bpm, 140.0);
+    assert_eq!(tempo_map.tempo_at(reloaded.tempo(), PPQN * 20), 90.0);
+    assert_eq!(tempo_map.time_signature_at((4, 4), PPQN * 8), (7, 8));
+}
+
+#[test]
+fn test_project_info_is_saved_and_stamped() {
+    let dir = tempdir().expect("tempdir");
+    let path = dir.path().join("song.dawproj");
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    assert!(session.info().is_empty());
+    session.set_author("Korbin".to_string());
+    session.set_notes("Needs a bridge".to_string());
+    session.set_key(MusicalKey::parse("F#m"));
+    session.save(&path).expect("save");
+    let created = session.info().created.expect("created");
+    assert_eq!(session.info().modified, Some(created));
+
+    let mut reloaded = Session::from_project_offline(&path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.info(), session.info());
+    assert_eq!(
+        reloaded.info().key.map(|key| key.to_string()).as_deref(),
+        Some("F#m")
+    );
+    // The stamps match the file, so an autosave has nothing new to keep
+    assert!(!reloaded.autosave().expect("autosave"));
+
+    // A copy saved elsewhere is stamped without touching the session's own
+    let before = reloaded.info().clone();
+    reloaded
+        .save(&dir.path().join("copy.dawproj"))
+        .expect("save copy");
+    assert_eq!(reloaded.info(), &before);
+    reloaded.save_in_place().expect("save");
+    assert_eq!(reloaded.info().created, Some(created));
+    assert!(reloaded.info().modified >= Some(created));
+}
+
+#[test]
+fn test_ids_are_never_reused() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    let drums = session.add_track("Drums".to_string());
+    let bass = session.add_track("Bass".to_string());
+    assert_ne!(drums, bass);
+
+    // Removing the last track and adding another doesn't hand out its id again
+    session.remove_track(bass.0);
+    let keys = session.add_track("Keys".to_string());
+    assert_ne!(keys, bass);
+
+    session.set_cursor(0);
+    session.insert_sample_at_cursor(drums.clone(), kick, kick_ref, "kick".to_string());
+    let clip_id = session.tracks()[0].clips()[0].id;
+
+    // The allocator is saved with the project, so ids stay unique after a reload
+    let project_path = dir.path().join("ids.dawproj");
+    session.save(&project_path).expect("save");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.tracks()[0].clips()[0].id, clip_id);
+
+    reloaded.remove_track(keys.0);
+    let added = reloaded.add_track("Pads".to_string());
+    assert!(![&drums, &bass, &keys].contains(&&added));
+}
+
+#[test]
+fn test_clip_source_is_detected_and_saved() {
+    let dir = tempdir().expect("tempdir");
+    let (audio, sample_ref) = write_sample(dir.path(), "loop_128bpm_Am", 110.0);
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    let track = session.add_track("Loops".to_string());
+    session.insert_sample_at_cursor(track, audio, sample_ref, "loop".to_string());
+
+    let project_path = dir.path().join("source.dawproj");
+    session.save(&project_path).expect("save");
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+
+    let source = &reloaded.tracks()[0].clips()[0].source;
+    assert_eq!(source.bpm, Some(128.0));
+    assert_eq!(
+        source.key.map(|key| key.to_string()),
+        Some("Am".to_string())
+    );
+    assert_eq!(source.sample_rate, Some(SAMPLE_RATE));
+    assert_eq!(
+        source.path.as_deref(),
+        Some(Path::new("loop_128bpm_Am.wav"))
+    );
+}
+
+#[test]
+fn test_end_behavior_is_saved() {
+    let dir = tempdir().expect("tempdir");
+    let (audio, sample_ref) = write_sample(dir.path(), "pad", 110.0);
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    assert_eq!(session.end_behavior(), EndBehavior::Continue);
+    assert_eq!(session.end_tick(), 0);
+
+    let track = session.add_track("Pads".to_string());
+    session.insert_sample_at_cursor(track, audio, sample_ref, "pad".to_string());
+    assert_eq!(session.end_tick(), session.max_tick());
+    session.set_end_behavior(EndBehavior::Loop);
+
+    let project_path = dir.path().join("end.dawproj");
+    session.save(&project_path).expect("save");
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.end_behavior(), EndBehavior::Loop);
+}
+
+#[test]
+fn test_project_sample_rate_is_saved_and_rendered_at() {
+    let dir = tempdir().expect("tempdir");
+    let (audio, sample_ref) = write_sample(dir.path(), "pad", 110.0);
+
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    assert_eq!(session.project_sample_rate(), None);
+    assert!(!session.sample_rate_mismatch());
+    let track = session.add_track("Pads".to_string());
+    session.insert_sample_at_cursor(track, audio, sample_ref, "pad".to_string());
+
+    // Without a project rate, renders are written at the default
+    let default_path = dir.path().join("default.wav");
+    session.render_to_file(&default_path).expect("render");
+    let rendered = decode_audio_arc(&default_path, None).expect("decode");
+    assert_eq!(rendered.sample_rate(), DEFAULT_RENDER_SAMPLE_RATE);
+
+    session.set_project_sample_rate(Some(48000));
+    assert!(session.sample_rate_mismatch());
+    let project_path = dir.path().join("rate.dawproj");
+    session.save(&project_path).expect("save");
+
+    // An offline session can't follow the project's rate, so it records the mismatch
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.project_sample_rate(), Some(48000));
+    assert_eq!(reloaded.sample_rate(), SAMPLE_RATE);
+    assert!(reloaded.sample_rate_mismatch());
+
+    let path = dir.path().join("rate.wav");
+    let summary = reloaded.render_to_file(&path).expect("render");
+    let rendered = decode_audio_arc(&path, None).expect("decode");
+    assert_eq!(rendered.sample_rate(), 48000);
+    assert!((summary.duration.as_secs_f64() - 0.25).abs() < 0.001);
+}
+
+#[test]
+fn test_render_reports_length_and_levels() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in [0, 2] {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(0),
+            kick.clone(),
+            kick_ref.clone(),
+            "kick".to_string(),
+        );
+    }
+
+    // The second quarter-second kick starts a second in
+    let path = dir.path().join("summary.wav");
+    let summary = session.render_to_file(&path).expect("render");
+    assert_eq!(summary.path, path);
+    assert!((summary.duration.as_secs_f64() - 1.25).abs() < 0.001);
+    assert!(
+        summary.peak_db < 0.0 && summary.peak_db > -1.0,
+        "{}",
+        summary.peak_db
+    );
+    assert!(summary.loudness_lufs.is_finite() && summary.loudness_lufs < summary.peak_db);
+}
+
+#[test]
+fn test_demo_render_carries_watermark_in_gaps() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in [0, 4] {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(0),
+            kick.clone(),
+            kick_ref.clone(),
+            "kick".to_string(),
+        );
+    }
+
+    let clean_path = dir.path().join("clean.wav");
+    let demo_path = dir.path().join("demo.wav");
+    let clean = session.render_to_file(&clean_path).expect("render");
+    let options = RenderOptions {
+        watermark: Some(Watermark {
+            interval: Duration::from_secs(1),
+            level_db: -40.0,
+            sound: WatermarkSound::Tone {
+                frequency: 1000.0,
+                length: Duration::from_millis(100),
+            },
+        }),
+        ..Default::default()
+    };
+    let demo = session
+        .render_to_file_with(&demo_path, &options)
+        .expect("render");
+    assert_eq!(demo.duration, clean.duration);
+
+    // A second in, between the kicks, the clean render is silent and the demo beeps
+    let clean = decode_audio_arc(&clean_path, None).expect("decode");
+    let demo = decode_audio_arc(&demo_path, None).expect("decode");
+    let gap = 44100 * 2..(44100 + 4410) * 2;
+    assert!(clean.samples()[gap.clone()].iter().all(|&s| s == 0.0));
+    let beep = demo.samples()[gap]
+        .iter()
+        .fold(0.0f32, |peak, &s| peak.max(s.abs()));
+    assert!((gain_to_db(beep) - -40.0).abs() < 0.5, "{beep}");
+}
+
+#[test]
+fn test_render_writes_integer_formats() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+
+    let float_path = dir.path().join("float.wav");
+    session.render_to_file(&float_path).expect("render");
+    let float = decode_audio_arc(&float_path, None).expect("decode");
+
+    for (format, bits) in [(RenderFormat::Int16, 16), (RenderFormat::Int24, 24)] {
+        let path = dir.path().join(format!("{}.wav", bits));
+        let options = RenderOptions {
+            format,
+            ..Default::default()
+        };
+        let summary = session
+            .render_to_file_with(&path, &options)
+            .expect("render");
+        assert_eq!(
+            summary.duration,
+            Duration::from_secs_f64(float.duration_secs())
+        );
+
+        // Smaller than the float file, and the same audio to within a step
+        let size = std::fs::metadata(&path).expect("metadata").len();
+        let float_size = std::fs::metadata(&float_path).expect("metadata").len();
+        assert!(
+            size * 32 < float_size * (bits + 1),
+            "{size} vs {float_size}"
+        );
+        let rendered = decode_audio_arc(&path, None).expect("decode");
+        let step = 1.0 / (1u32 << (bits - 1)) as f32;
+        assert_eq!(rendered.samples().len(), float.samples().len());
+        for (a, b) in rendered.samples().iter().zip(float.samples()) {
+            assert!((a - b).abs() <= step, "{a} vs {b}");
+        }
+    }
+}
+
+#[test]
+fn test_render_dithers_integer_formats() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+
+    let render = |name: &str, format, dither| {
+        let path = dir.path().join(name);
+        let options = RenderOptions {
+            format,
+            dither,
+            ..Default::default()
+        };
+        session
+            .render_to_file_with(&path, &options)
+            .expect("render");
+        decode_audio_arc(&path, None).expect("decode")
+    };
+    let rounded = render("rounded.wav", RenderFormat::Int16, Dither::None);
+    let step = 1.0 / 32768.0;
+    for dither in [Dither::Triangular, Dither::NoiseShaped] {
+        let dithered = render("dithered.wav", RenderFormat::Int16, dither);
+        assert_ne!(dithered.samples(), rounded.samples());
+        for (a, b) in dithered.samples().iter().zip(rounded.samples()) {
+            assert!((a - b).abs() <= 3.0 * step, "{a} vs {b}");
+        }
+        // Renders of the same mix come out the same
+        let again = render("again.wav", RenderFormat::Int16, dither);
+        assert_eq!(again.samples(), dithered.samples());
+    }
+
+    // Floats aren't rounded, so they aren't dithered
+    let plain = render("plain.wav", RenderFormat::Float32, Dither::None);
+    let float = render("float.wav", RenderFormat::Float32, Dither::Triangular);
+    assert_eq!(float.samples(), plain.samples());
+}
+
+#[test]
+fn test_render_to_flac_is_lossless() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 200.0);
+
+    let tracks = vec![
+        Track::new(TrackId(0), "Kick".to_string()),
+        Track::new(TrackId(1), "Snare".to_string()),
+    ];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.insert_sample_at_cursor(TrackId(1), snare, snare_ref, "snare".to_string());
+
+    for (format, bits) in [(RenderFormat::Int16, 16), (RenderFormat::Int24, 24)] {
+        let options = RenderOptions {
+            format,
+            ..Default::default()
+        };
+        let wav_path = dir.path().join(format!("{}.wav", bits));
+        session
+            .render_to_file_with(&wav_path, &options)
+            .expect("render");
+        // Picked by the extension
+        let flac_path = dir.path().join(format!("{}.flac", bits));
+        session
+            .render_to_file_with(&flac_path, &options)
+            .expect("render");
+
+        let wav = decode_audio_arc(&wav_path, None).expect("decode");
+        let flac = decode_audio_arc(&flac_path, None).expect("decode");
+        assert_eq!((flac.channels(), flac.sample_rate()), (2, SAMPLE_RATE));
+        assert_eq!(flac.samples(), wav.samples());
+        let size = |path: &Path| std::fs::metadata(path).expect("metadata").len();
+        assert!(size(&flac_path) < size(&wav_path));
+    }
+
+    let err = session
+        .render_to_file(&dir.path().join("song.mp3"))
+        .unwrap_err();
+    assert!(err.to_string().contains(".mp3"), "{err}");
+}
+
+#[test]
+fn test_render_goes_through_the_master_bus() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let plain = session
+        .render_to_file(&dir.path().join("plain.wav"))
+        .expect("render");
+
+    session.set_master_bus(MasterSettings {
+        gain_db: -6.0,
+        pan: 0.0,
+        limiter: None,
+    });
+    let quieter = session
+        .render_to_file(&dir.path().join("quieter.wav"))
+        .expect("render");
+    assert!((quieter.peak_db - (plain.peak_db - 6.0)).abs() < 0.01);
+
+    // Turned up into the limiter, which holds it to the ceiling
+    session.set_master_bus(MasterSettings {
+        gain_db: 12.0,
+        pan: 0.0,
+        limiter: Some(LimiterSettings {
+            ceiling_db: plain.peak_db - 3.0,
+            release_ms: 50.0,
+        }),
+    });
+    let limited = session
+        .render_to_file(&dir.path().join("limited.wav"))
+        .expect("render");
+    assert!((limited.peak_db - (plain.peak_db - 3.0)).abs() < 0.01);
+    assert_eq!(session.master_bus().gain_db, 12.0);
+    assert_eq!(limited.clipped_samples, 0);
+
+    // Without the limiter, the report counts what goes over full scale
+    session.set_master_bus(MasterSettings {
+        gain_db: -plain.peak_db + 6.0,
+        pan: 0.0,
+        limiter: None,
+    });
+    let clipped = session
+        .render_to_file(&dir.path().join("clipped.wav"))
+        .expect("render");
+    assert!(clipped.clipped_samples > 0);
+    assert_eq!(plain.clipped_samples, 0);
+    assert!((clipped.rms_db - plain.rms_db - (-plain.peak_db + 6.0)).abs() < 0.01);
+}
+
+#[test]
+fn test_master_channel_is_saved_and_rendered() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_master_bus(MasterSettings {
+        gain_db: -3.0,
+        pan: 2.0,
+        limiter: None,
+    });
+    assert_eq!(session.master_bus().pan, 1.0);
+    assert_eq!(
+        session.add_master_effect(Effect::from_kind("eq").unwrap()),
+        0
+    );
+    assert_eq!(
+        session.add_master_effect(Effect::from_kind("reverb").unwrap()),
+        1
+    );
+    assert!(session.move_master_effect(1, 0));
+    assert!(session.set_master_effect_parameter(0, "mix", 0.5));
+    assert!(!session.set_master_effect_parameter(2, "mix", 0.5));
+    assert!(session.remove_master_effect(1));
+    assert_eq!(session.master_peak(), [0.0; 2]);
+
+    // Balanced all the way right, the left side is silent
+    let path = dir.path().join("right.wav");
+    session.render_to_file(&path).expect("render");
+    let rendered = decode_audio_arc(&path, None).expect("decode");
+    let samples = rendered.samples();
+    assert!(samples.iter().step_by(2).all(|sample| *sample == 0.0));
+    assert!(
+        samples
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .any(|sample| *sample != 0.0)
+    );
+
+    let project_path = dir.path().join("master.dawproj");
+    session.save(&project_path).expect("save");
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.master_bus(), session.master_bus());
+    assert_eq!(reloaded.master_effects(), session.master_effects());
+    assert_eq!(
+        render_bytes(&reloaded, dir.path(), "reloaded"),
+        std::fs::read(&path).expect("read render")
+    );
+}
+
+#[test]
+fn test_render_normalized_to_a_loudness_target() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in 0..4 {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(0),
+            kick.clone(),
+            kick_ref.clone(),
+            "kick".to_string(),
+        );
+    }
+    let plain = session
+        .render_to_file(&dir.path().join("plain.wav"))
+        .expect("render");
+    assert_eq!(plain.gain_db, 0.0);
+    assert!(plain.true_peak_db >= plain.peak_db);
+
+    // Quiet enough that the loudness target decides
+    let options = RenderOptions {
+        normalize: Some(Normalization {
+            loudness_lufs: Some(plain.loudness_lufs - 6.0),
+            true_peak_db: Some(0.0),
+        }),
+        ..Default::default()
+    };
+    let quieter = session
+        .render_to_file_with(&dir.path().join("quieter.wav"), &options)
+        .expect("render");
+    assert!((quieter.gain_db + 6.0).abs() < 0.01, "{}", quieter.gain_db);
+    assert!((quieter.loudness_lufs - (plain.loudness_lufs - 6.0)).abs() < 0.05);
+
+    // Louder, but held under the ceiling
+    let options = RenderOptions {
+        normalize: Some(Normalization {
+            loudness_lufs: Some(0.0),
+            true_peak_db: Some(-1.0),
+        }),
+        ..Default::default()
+    };
+    let loud = session
+        .render_to_file_with(&dir.path().join("loud.wav"), &options)
+        .expect("render");
+    assert!(
+        (loud.true_peak_db + 1.0).abs() < 0.05,
+        "{}",
+        loud.true_peak_db
+    );
+    assert!((loud.gain_db - (-1.0 - plain.true_peak_db)).abs() < 0.05);
+}
+
+#[test]
+fn test_render_tail_keeps_going_after_the_last_clip() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    let path = dir.path().join("tail.wav");
+    let plain = session.render_to_file(&path).expect("render");
+
+    let render = |tail, end_tick| {
+        let options = RenderOptions {
+            tail: Some(tail),
+            end_tick,
+            ..Default::default()
+        };
+        let summary = session
+            .render_to_file_with(&path, &options)
+            .expect("render");
+        (summary.duration - plain.duration).as_secs_f64()
+    };
+    assert!((render(RenderTail::Seconds(1.5), None) - 1.5).abs() < 0.001);
+    // A bar of 4/4 at 120 BPM
+    assert!((render(RenderTail::Bars(1), None) - 2.0).abs() < 0.001);
+    // An explicit end stays where it is
+    let end = render(RenderTail::Bars(1), Some(PPQN * 4));
+    assert!((end - (2.0 - plain.duration.as_secs_f64())).abs() < 0.001);
+
+    // The tail is silent
+    let rendered = decode_audio_arc(&path, None).expect("decode");
+    let kick_samples = (plain.duration.as_secs_f64() * SAMPLE_RATE as f64) as usize * 2;
+    assert!(rendered.samples()[kick_samples..].iter().all(|&s| s == 0.0));
+}
+
+#[test]
+fn test_render_a_region_only() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in [0, 2] {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(0),
+            kick.clone(),
+            kick_ref.clone(),
+            "kick".to_string(),
+        );
+    }
+    let full_path = dir.path().join("full.wav");
+    session.render_to_file(&full_path).expect("render");
+    let full = decode_audio_arc(&full_path, None).expect("decode");
+
+    // Half a second from the second kick on, past the end of the arrangement
+    let path = dir.path().join("region.wav");
+    let options = RenderOptions {
+        start_tick: Some(PPQN * 2),
+        end_tick: Some(PPQN * 3),
+        ..Default::default()
+    };
+    let summary = session
+        .render_to_file_with(&path, &options)
+        .expect("render");
+    assert!((summary.duration.as_secs_f64() - 0.5).abs() < 0.001);
+    let region = decode_audio_arc(&path, None).expect("decode");
+    let (kick_part, tail) = region.samples().split_at(full.samples().len() - 44100 * 2);
+    assert_eq!(kick_part, &full.samples()[44100 * 2..]);
+    assert!(tail.iter().all(|&s| s == 0.0));
+
+    // Only a start renders to the end of the arrangement
+    let options = RenderOptions {
+        start_tick: Some(PPQN * 2),
+        ..Default::default()
+    };
+    let summary = session
+        .render_to_file_with(&path, &options)
+        .expect("render");
+    assert!((summary.duration.as_secs_f64() - 0.25).abs() < 0.001);
+
+    let options = RenderOptions {
+        start_tick: Some(PPQN * 3),
+        end_tick: Some(PPQN * 2),
+        ..Default::default()
+    };
+    assert!(session.render_to_file_with(&path, &options).is_err());
+}
+
+#[test]
+fn test_track_edits_survive_a_save() {
+    let dir = tempdir().expect("tempdir");
+    let mut session = Session::new_offline(Vec::new(), 120.0, (4, 4), SAMPLE_RATE);
+    let drums = session.add_track("Drums".to_string());
+    let bass = session.add_track("Bass".to_string());
+    let keys = session.add_track("Keys".to_string());
+    session.rename_track(bass.0, "Sub".to_string());
+    session.move_track(keys.0, 0);
+    session.remove_track(drums.0);
+
+    let names = |session: &Session| -> Vec<(u64, String)> {
+        session
+            .tracks()
+            .iter()
+            .map(|track| (track.id.0, track.name.clone()))
+            .collect()
+    };
+    let expected = vec![(keys.0, "Keys".to_string()), (bass.0, "Sub".to_string())];
+    assert_eq!(names(&session), expected);
+
+    let project_path = dir.path().join("tracks.dawproj");
+    session.save(&project_path).expect("save");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(names(&reloaded), expected);
+
+    // The removed track's id isn't handed out again
+    let added = reloaded.add_track("Drums".to_string());
+    assert!(added.0 > keys.0);
+}
+
+#[test]
+fn test_render_stems_writes_a_file_per_track() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+    let (snare, snare_ref) = write_sample(dir.path(), "snare", 200.0);
+
+    let tracks = vec![
+        Track::new(TrackId(0), "Kick".to_string()),
+        Track::new(TrackId(1), "Snare".to_string()),
+    ];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session.set_cursor(PPQN * 4);
+    session.insert_sample_at_cursor(TrackId(1), snare, snare_ref, "snare".to_string());
+    let full = session
+        .render_to_file(&dir.path().join("full.wav"))
+        .expect("render");
+
+    let paths = session
+        .render_stems(&dir.path().join("stems"))
+        .expect("stems");
+    let names: Vec<_> = paths.iter().map(|path| path.file_name().unwrap()).collect();
+    assert_eq!(names, ["01 Kick.wav", "02 Snare.wav"]);
+    for path in &paths {
+        let stem = decode_audio_arc(path, None).expect("decode");
+        assert!((stem.duration_secs() - full.duration.as_secs_f64()).abs() < 0.001);
+    }
+}
+
+#[test]
+fn test_sample_edited_on_disk_is_reloaded_into_clips() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.set_snap_mode(SnapMode::Beat);
+    for beat in [0, 2] {
+        session.set_cursor(beat * PPQN);
+        session.insert_sample_at_cursor(
+            TrackId(0),
+            kick.clone(),
+            kick_ref.clone(),
+            "kick".to_string(),
+        );
+    }
+    let project_path = dir.path().join("hot.dawproj");
+    session.save(&project_path).expect("save");
+
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("load");
+    let before: Vec<(u64, u64)> = session.tracks()[0]
+        .clips()
+        .iter()
+        .map(|clip| (clip.start_tick, clip.end_tick))
+        .collect();
+    assert!(session.reload_changed_samples().expect("reload").is_empty());
+
+    // Edit the sample "in another program": same name, new sound, half as long
+    let edited = AudioArc::new(
+        kick.samples()[..kick.frames() / 2 * 2]
+            .iter()
+            .map(|s| s * 0.5)
+            .collect(),
+        SAMPLE_RATE,
+        2,
+    );
+    daw_render::write_wav(
+        &edited,
+        &dir.path().join("kick.wav"),
+        daw_core::RenderFormat::Float32,
+    )
+    .expect("write");
+
+    assert_eq!(
+        session.reload_changed_samples().expect("reload"),
+        vec!["kick"]
+    );
+    let waveform = WaveformData::from_audio_arc(&edited, 512);
+    for clip in session.tracks()[0].clips() {
+        assert_eq!(clip.audio().unwrap().samples(), edited.samples());
+        assert_eq!(clip.waveform.peaks, waveform.peaks);
+    }
+    let after: Vec<(u64, u64)> = session.tracks()[0]
+        .clips()
+        .iter()
+        .map(|clip| (clip.start_tick, clip.end_tick))
+        .collect();
+    assert_eq!(after, before);
+
+    // Nothing left to reload until it changes again
+    assert!(session.reload_changed_samples().expect("reload").is_empty());
+}
+
+#[cfg(feature = "dawproject")]
+#[test]
+fn test_dawproject_export_carries_the_audio() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick, kick_ref, "kick".to_string());
+    session
+        .save_as(&dir.path().join("song.dawproj"), SaveAsSamples::Reference)
+        .expect("save");
+    let path = dir.path().join("song.dawproject");
+    session.export_dawproject(&path).expect("export");
+
+    // A zip archive with the sample stored as it is
+    let bytes = std::fs::read(&path).expect("read export");
+    let sample = std::fs::read(dir.path().join("kick.wav")).expect("read sample");
+    assert!(bytes.starts_with(b"PK"));
+    assert!(bytes.windows(sample.len()).any(|window| window == sample));
+}
+
+#[cfg(feature = "watch")]
+#[test]
+fn test_watched_sample_is_reloaded_when_polled() {
+    let dir = tempdir().expect("tempdir");
+    let (kick, kick_ref) = write_sample(dir.path(), "kick", 60.0);
+
+    let tracks = vec![Track::new(TrackId(0), "Kick".to_string())];
+    let mut session = Session::new_offline(tracks, 120.0, (4, 4), SAMPLE_RATE);
+    session.insert_sample_at_cursor(TrackId(0), kick.clone(), kick_ref, "kick".to_string());
+    let project_path = dir.path().join("watched.dawproj");
+    session.save(&project_path).expect("save");
+
+    let mut session =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("load");
+    session.set_watching_samples(true).expect("watch");
+    assert!(session.is_watching_samples());
+
+    let edited = kick.with_gain(0.5);
+    daw_render::write_wav(
+        &edited,
+        &dir.path().join("kick.wav"),
+        daw_core::RenderFormat::Float32,
+    )
+    .expect("write");
+
+    let deadline = std::time::Instant::now() + Duration::from_secs(5);
+    let mut events = Vec::new();
+    while events.is_empty() && std::time::Instant::now() < deadline {
+        session.poll();
+        events = session.take_events();
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(
+        events,
+        vec![daw_core::SessionEvent::SamplesReloaded(vec![
+            "kick".to_string()
+        ])]
+    );
+    let clip = &session.tracks()[0].clips()[0];
+    assert_eq!(clip.audio().unwrap().samples(), edited.samples());
+}
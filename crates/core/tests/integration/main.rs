@@ -0,0 +1,45 @@
+//! End-to-end tests of the `Session` API, run headlessly through offline sessions.
+//!
+//! Each test walks through a flow the way a frontend would, so they also serve as
+//! examples of how the pieces fit together.
+
+mod editing;
+mod folders;
+mod lifecycle;
+mod transport;
+
+use daw_core::{AudioArc, SampleRef};
+use std::path::{Path, PathBuf};
+
+/// Sample rate used throughout, matching the render rate so no resampling happens
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// Write a short decaying stereo burst into `dir` and return its decoded audio and
+/// a project-relative reference to it.
+pub fn write_sample(dir: &Path, name: &str, frequency: f32) -> (AudioArc, SampleRef) {
+    let frames = SAMPLE_RATE as usize / 4;
+    let samples = (0..frames)
+        .flat_map(|frame| {
+            let t = frame as f32 / SAMPLE_RATE as f32;
+            let value = (t * frequency * std::f32::consts::TAU).sin() * (-t * 12.0).exp();
+            [value, value * 0.5]
+        })
+        .collect();
+    let audio = AudioArc::new(samples, SAMPLE_RATE, 2);
+
+    let file_name = format!("{}.wav", name);
+    daw_render::write_wav(
+        &audio,
+        &dir.join(&file_name),
+        daw_core::RenderFormat::Float32,
+    )
+    .expect("write sample");
+    (audio, SampleRef::ProjectRelative(PathBuf::from(file_name)))
+}
+
+/// Render a session to a WAV file in `dir` and return the file's bytes
+pub fn render_bytes(session: &daw_core::Session, dir: &Path, name: &str) -> Vec<u8> {
+    let path = dir.join(format!("{}.wav", name));
+    session.render_to_file(&path).expect("render");
+    std::fs::read(path).expect("read render")
+}
@@ -0,0 +1,146 @@
+//! Play, stop and jump around, checking where the playhead ends up.
+
+use crate::SAMPLE_RATE;
+use daw_core::{MAX_COUNT_IN_BARS, PPQN, Session};
+use tempfile::tempdir;
+
+#[test]
+fn test_stop_can_return_to_start_of_playback() {
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    let bar = 4 * PPQN;
+
+    // By default the playhead stays where playback stopped
+    session.set_cursor(bar);
+    session.play();
+    session.seek(3 * bar);
+    session.stop();
+    assert_eq!(session.current_tick(), 3 * bar);
+
+    // With the preference on it jumps back to where playback started
+    session.set_return_to_start(true);
+    session.play();
+    assert_eq!(session.play_start_tick(), bar);
+    session.seek(3 * bar);
+    session.stop();
+    assert_eq!(session.current_tick(), bar);
+
+    // Resuming after a pause starts a new playback from the paused position
+    session.set_cursor(2 * bar);
+    session.play();
+    session.seek(3 * bar);
+    session.pause();
+    session.play();
+    session.seek(4 * bar);
+    session.stop();
+    assert_eq!(session.current_tick(), 3 * bar);
+}
+
+#[test]
+fn test_locators_jump_and_are_saved() {
+    let dir = tempdir().expect("tempdir");
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    let bar = 4 * PPQN;
+
+    // Locators keep exact ticks, even off the snap grid
+    session.set_cursor(2 * bar);
+    session.set_locator_here(1);
+    session.set_locator(2, 5 * bar + 7);
+    session.set_locator(10, bar);
+    assert_eq!(session.locators().len(), 2);
+
+    assert!(session.goto_locator(2));
+    assert_eq!(session.cursor_tick(), Some(5 * bar + 7));
+    assert_eq!(session.current_tick(), 5 * bar + 7);
+    assert!(!session.goto_locator(3));
+
+    let project_path = dir.path().join("locators.dawproj");
+    session.save(&project_path).expect("save");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert!(reloaded.goto_locator(1));
+    assert_eq!(reloaded.cursor_tick(), Some(2 * bar));
+}
+
+#[test]
+fn test_panic_stops_and_mutes_until_unmuted() {
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    let bar = 4 * PPQN;
+
+    session.play();
+    session.seek(2 * bar);
+    session.panic();
+    assert!(!session.is_playing());
+    assert!(session.master_muted());
+    assert_eq!(session.current_tick(), 2 * bar);
+
+    // Playing again doesn't lift the mute; that takes an explicit unmute
+    session.play();
+    assert!(session.master_muted());
+    session.set_master_muted(false);
+    assert!(!session.master_muted());
+}
+
+#[test]
+fn test_loop_region_toggles_and_is_saved() {
+    let dir = tempdir().expect("tempdir");
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    let bar = 4 * PPQN;
+
+    // Toggling without a region does nothing
+    session.toggle_loop();
+    assert!(!session.is_looping());
+
+    // Ticks can come in either order; the region survives looping being turned off
+    session.set_loop(4 * bar, 2 * bar);
+    assert!(session.is_looping());
+    session.toggle_loop();
+    assert!(!session.is_looping());
+    let region = session.loop_region().expect("region");
+    assert_eq!((region.start_tick, region.end_tick), (2 * bar, 4 * bar));
+
+    let project_path = dir.path().join("loop.dawproj");
+    session.save(&project_path).expect("save");
+    let mut reloaded =
+        Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.loop_region(), Some(region));
+    reloaded.toggle_loop();
+    assert!(reloaded.is_looping());
+
+    // An empty region is no region
+    reloaded.set_loop(bar, bar);
+    assert_eq!(reloaded.loop_region(), None);
+}
+
+#[test]
+fn test_pre_roll_and_count_in_are_saved() {
+    let dir = tempdir().expect("tempdir");
+    let mut session = Session::new_offline(vec![], 120.0, (4, 4), SAMPLE_RATE);
+    let bar = 4 * PPQN;
+
+    // Playback starts two bars early, and stopping goes back to the cursor
+    session.set_pre_roll_bars(2);
+    session.set_return_to_start(true);
+    session.set_cursor(3 * bar);
+    session.play();
+    assert_eq!(session.current_tick(), bar);
+    session.stop();
+    assert_eq!(session.current_tick(), 3 * bar);
+
+    // Not before the start of the arrangement, though
+    session.set_cursor(bar);
+    session.play();
+    assert_eq!(session.current_tick(), 0);
+    session.pause();
+    session.play();
+    assert_eq!(session.current_tick(), 0, "resuming doesn't pre-roll");
+    session.stop();
+
+    session.set_count_in_bars(5);
+    assert_eq!(session.count_in_bars(), MAX_COUNT_IN_BARS);
+
+    let project_path = dir.path().join("preroll.dawproj");
+    session.save(&project_path).expect("save");
+    let reloaded = Session::from_project_offline(&project_path, None, SAMPLE_RATE).expect("reload");
+    assert_eq!(reloaded.pre_roll_bars(), 2);
+    assert_eq!(reloaded.count_in_bars(), MAX_COUNT_IN_BARS);
+}
@@ -0,0 +1,299 @@
+//! Loading audio on a background thread pool.
+//!
+//! Decoding and resampling a large project can take seconds. [`AudioCache::get_or_load_async`]
+//! moves that work to rayon's thread pool so a frontend can keep drawing and show progress,
+//! and so several files decode in parallel. The cache itself is only touched on the calling
+//! thread: a finished load is handed back with [`AudioCache::finish_load`], which stores the
+//! audio in the cache like a synchronous load would.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use daw_transport::AudioArc;
+
+use crate::{
+    AudioCache, Cached, DecodeIssue, Fingerprint, fingerprint_and_hash, load_original,
+    load_resampled,
+};
+
+/// Audio being loaded in the background by [`AudioCache::get_or_load_async`].
+///
+/// Check [`progress`](Self::progress) from a UI while it runs, then pass it to
+/// [`AudioCache::finish_load`] to get the audio.
+#[derive(Debug)]
+pub struct PendingLoad {
+    path: PathBuf,
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    /// Fraction done, as the bits of an `f32`
+    progress: AtomicU32,
+    result: Mutex<Option<anyhow::Result<Loaded>>>,
+    finished: Condvar,
+}
+
+/// What a load produced, for the cache to store
+#[derive(Debug)]
+struct Loaded {
+    fingerprint: Fingerprint,
+    hash: u64,
+    original: AudioArc,
+    /// Present when a target rate other than the original's was requested
+    resampled: Option<(u32, AudioArc)>,
+    /// Damage patched over when the original was decoded just now
+    issues: Vec<DecodeIssue>,
+}
+
+impl Shared {
+    fn report(&self, fraction: f32) {
+        self.progress.store(fraction.to_bits(), Ordering::Relaxed);
+    }
+
+    fn finish(&self, result: anyhow::Result<Loaded>) {
+        self.report(1.0);
+        *self.result.lock().unwrap_or_else(|err| err.into_inner()) = Some(result);
+        self.finished.notify_all();
+    }
+}
+
+impl PendingLoad {
+    /// The file being loaded
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Fraction of the load done so far, from 0.0 to 1.0
+    pub fn progress(&self) -> f32 {
+        f32::from_bits(self.shared.progress.load(Ordering::Relaxed))
+    }
+
+    /// Whether the load has finished, successfully or not
+    pub fn is_finished(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// Block until the load finishes or `timeout` passes, returning whether it finished
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let result = self.lock();
+        let (result, _) = self
+            .shared
+            .finished
+            .wait_timeout_while(result, timeout, |result| result.is_none())
+            .unwrap_or_else(|err| err.into_inner());
+        result.is_some()
+    }
+
+    fn wait(self) -> anyhow::Result<Loaded> {
+        let result = self.lock();
+        let mut result = self
+            .shared
+            .finished
+            .wait_while(result, |result| result.is_none())
+            .unwrap_or_else(|err| err.into_inner());
+        result.take().expect("waited until finished")
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<anyhow::Result<Loaded>>> {
+        self.shared
+            .result
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+impl AudioCache {
+    /// Start loading audio on a background thread, like
+    /// [`get_or_load_direct`](Self::get_or_load_direct).
+    ///
+    /// Returns right away. Audio that is already cached comes back as a finished
+    /// load. `progress` is called from the background thread with the fraction done,
+    /// from 0.0 to 1.0; when the file has to be both decoded and resampled, decoding
+    /// counts as the first half. Pass the returned [`PendingLoad`] to
+    /// [`finish_load`](Self::finish_load) to get the audio and cache it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use daw_decode::AudioCache;
+    /// use std::path::Path;
+    /// use std::time::Duration;
+    ///
+    /// let mut cache = AudioCache::new();
+    /// let pending = cache.get_or_load_async(Path::new("/samples/pad.flac"), Some(48000), |_| {});
+    /// while !pending.wait_timeout(Duration::from_millis(16)) {
+    ///     println!("{:.0}%", pending.progress() * 100.0);
+    /// }
+    /// let audio = cache.finish_load(pending).unwrap();
+    /// ```
+    pub fn get_or_load_async(
+        &mut self,
+        path: &Path,
+        target_sample_rate: Option<u32>,
+        mut progress: impl FnMut(f32) + Send + 'static,
+    ) -> PendingLoad {
+        let shared = Arc::new(Shared::default());
+        let pending = PendingLoad {
+            path: path.to_path_buf(),
+            shared: shared.clone(),
+        };
+
+        // Reuse whatever is cached for a file known to be unchanged
+        let known = self.known_content(path);
+        let original = known.and_then(|(_, hash)| Some(self.originals.get(&hash)?.audio.clone()));
+        if let (Some((fingerprint, hash)), Some(original)) = (known, &original) {
+            let resampled = match target_sample_rate {
+                Some(rate) if rate != original.sample_rate() => self
+                    .resampled
+                    .get(&(hash, rate))
+                    .map(|cached| Some((rate, cached.audio.clone()))),
+                _ => Some(None),
+            };
+            if let Some(resampled) = resampled {
+                shared.finish(Ok(Loaded {
+                    fingerprint,
+                    hash,
+                    original: original.clone(),
+                    resampled,
+                    issues: Vec::new(),
+                }));
+                return pending;
+            }
+        }
+
+        let path = path.to_path_buf();
+        let disk = self.disk.clone();
+        let lossy = self.lossy;
+        let layout = self.layout;
+        rayon::spawn(move || {
+            let mut report = |fraction: f32| {
+                shared.report(fraction);
+                progress(fraction);
+            };
+            let result = (|| {
+                let (fingerprint, hash) = match known {
+                    Some(known) => known,
+                    None => fingerprint_and_hash(&path)?,
+                };
+                let decoded = original.is_none();
+                let (original, issues) = match original {
+                    Some(original) => (original, Vec::new()),
+                    None => load_original(&path, hash, disk.as_ref(), lossy, layout)?,
+                };
+
+                let resampled = match target_sample_rate {
+                    Some(rate) if rate != original.sample_rate() => {
+                        let (start, scale) = if decoded { (0.5, 0.5) } else { (0.0, 1.0) };
+                        report(start);
+                        let audio = load_resampled(
+                            hash,
+                            &original,
+                            rate,
+                            disk.as_ref(),
+                            layout,
+                            |fraction| report(start + fraction * scale),
+                        )?;
+                        Some((rate, audio))
+                    }
+                    _ => None,
+                };
+
+                Ok(Loaded {
+                    fingerprint,
+                    hash,
+                    original,
+                    resampled,
+                    issues,
+                })
+            })();
+            report(1.0);
+            shared.finish(result);
+        });
+
+        pending
+    }
+
+    /// Wait for a load started by [`get_or_load_async`](Self::get_or_load_async) and
+    /// store its audio in the cache.
+    ///
+    /// If the same contents were cached in the meantime (by another path, say), the
+    /// cached audio is returned and the newly loaded copy dropped.
+    pub fn finish_load(&mut self, pending: PendingLoad) -> anyhow::Result<AudioArc> {
+        let path = pending.path.clone();
+        let loaded = pending.wait()?;
+        let now = self.tick();
+
+        self.content_hashes
+            .insert(path.clone(), (loaded.fingerprint, loaded.hash));
+        self.paths.entry(loaded.hash).or_insert(path);
+        if !loaded.issues.is_empty() {
+            self.decode_issues.insert(loaded.hash, loaded.issues);
+        }
+
+        let original = self.originals.entry(loaded.hash).or_insert(Cached {
+            audio: loaded.original,
+            last_used: now,
+        });
+        original.last_used = now;
+        let mut audio = original.audio.clone();
+
+        if let Some((rate, resampled)) = loaded.resampled {
+            let resampled = self.resampled.entry((loaded.hash, rate)).or_insert(Cached {
+                audio: resampled,
+                last_used: now,
+            });
+            resampled.last_used = now;
+            audio = resampled.audio.clone();
+        }
+
+        self.enforce_budget();
+        Ok(audio)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::write_sawtooth_wav;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_async_load_matches_sync_load() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("loop.wav");
+        write_sawtooth_wav(&path, 44100, 4410);
+
+        let reported = Arc::new(AtomicBool::new(false));
+        let mut cache = AudioCache::new();
+        let pending = cache.get_or_load_async(&path, Some(48000), {
+            let reported = reported.clone();
+            move |_| reported.store(true, Ordering::Relaxed)
+        });
+        let audio = cache.finish_load(pending).unwrap();
+
+        assert_eq!(audio.sample_rate(), 48000);
+        assert!(reported.load(Ordering::Relaxed));
+        assert_eq!((cache.originals_count(), cache.resampled_count()), (1, 1));
+
+        // Now cached: a synchronous load shares the audio, and an async one is already done
+        let sync = cache.get_or_load_direct(&path, Some(48000)).unwrap();
+        assert!(Arc::ptr_eq(sync.samples_arc(), audio.samples_arc()));
+        let pending = cache.get_or_load_async(&path, Some(48000), |_| {});
+        assert!(pending.is_finished());
+        assert_eq!(pending.progress(), 1.0);
+        let again = cache.finish_load(pending).unwrap();
+        assert!(Arc::ptr_eq(again.samples_arc(), audio.samples_arc()));
+    }
+
+    #[test]
+    fn test_async_load_reports_errors() {
+        let mut cache = AudioCache::new();
+        let pending = cache.get_or_load_async(Path::new("/nonexistent/kick.wav"), None, |_| {});
+        assert!(pending.wait_timeout(Duration::from_secs(10)));
+        assert!(cache.finish_load(pending).is_err());
+        assert_eq!(cache.total_count(), 0);
+    }
+}
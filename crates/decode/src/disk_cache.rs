@@ -0,0 +1,336 @@
+//! Persistent on-disk cache of decoded audio.
+//!
+//! Decoding compressed formats (MP3, FLAC, ...) and resampling them is slow, and
+//! opening a project does it for every sample. [`DiskCache`] stores the decoded PCM
+//! in a raw format that loads with a single read, keyed by a hash of the source
+//! file's contents so edited files are never served stale.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use daw_transport::AudioArc;
+
+use crate::ChannelLayout;
+
+/// Identifies cache files and their layout version
+const MAGIC: &[u8; 8] = b"DAWPCM01";
+const HEADER_LEN: usize = 8 + 4 + 2 + 8;
+
+/// Default size limit for the cache directory (2 GiB)
+pub const DEFAULT_DISK_CACHE_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Directory of decoded audio files with a total size limit.
+///
+/// Entries are named by content hash, sample rate and channel layout. When the cache
+/// grows past its limit the least recently used entries are deleted. Every failure is
+/// treated as a cache miss, so a broken cache never stops audio from loading.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+}
+
+impl DiskCache {
+    /// Create a cache in `dir`, which is created on first write.
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes,
+        }
+    }
+
+    /// Create a cache in the platform's user cache directory with the default size limit.
+    ///
+    /// Returns `None` if the cache directory can't be determined.
+    pub fn in_default_location() -> Option<Self> {
+        user_cache_dir().map(|dir| Self::new(dir.join("decoded"), DEFAULT_DISK_CACHE_BYTES))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Look up decoded audio for `source`. `sample_rate` of `None` means the
+    /// original decoded audio, otherwise the version resampled to that rate.
+    pub fn get(&self, source: &Path, sample_rate: Option<u32>) -> Option<AudioArc> {
+        let hash = hash_file_contents(source).ok()?;
+        self.get_by_hash(hash, sample_rate, ChannelLayout::Native)
+    }
+
+    /// [`get`](Self::get) for a source whose content hash is already known, with
+    /// its audio decoded into `layout`, which is stored apart
+    pub(crate) fn get_by_hash(
+        &self,
+        hash: u64,
+        sample_rate: Option<u32>,
+        layout: ChannelLayout,
+    ) -> Option<AudioArc> {
+        let entry = self.entry_path(hash, sample_rate, layout);
+        let audio = read_entry(&entry).ok()?;
+        // Bump the modification time so eviction sees this entry as recently used
+        if let Ok(file) = File::options().write(true).open(&entry) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(audio)
+    }
+
+    /// Store decoded audio for `source`, then evict old entries if over the size limit.
+    pub fn put(
+        &self,
+        source: &Path,
+        sample_rate: Option<u32>,
+        audio: &AudioArc,
+    ) -> anyhow::Result<()> {
+        let hash = hash_file_contents(source)?;
+        self.put_by_hash(hash, sample_rate, ChannelLayout::Native, audio)
+    }
+
+    /// [`put`](Self::put) for a source whose content hash is already known, with
+    /// its audio decoded into `layout`
+    pub(crate) fn put_by_hash(
+        &self,
+        hash: u64,
+        sample_rate: Option<u32>,
+        layout: ChannelLayout,
+        audio: &AudioArc,
+    ) -> anyhow::Result<()> {
+        let entry = self.entry_path(hash, sample_rate, layout);
+        fs::create_dir_all(&self.dir)?;
+
+        // Write to a temporary file first so readers never see a partial entry
+        let temp = entry.with_extension("tmp");
+        write_entry(&temp, audio)?;
+        fs::rename(&temp, &entry)?;
+
+        self.evict()
+    }
+
+    /// Delete every cache entry.
+    pub fn clear(&self) -> anyhow::Result<()> {
+        for (path, _, _) in self.entries() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Total size of all cache entries in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        self.entries().iter().map(|(_, size, _)| size).sum()
+    }
+
+    /// Delete least recently used entries until the cache fits its size limit.
+    fn evict(&self) -> anyhow::Result<()> {
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, size, _) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            fs::remove_file(path)?;
+            total -= size;
+        }
+        Ok(())
+    }
+
+    /// Cache entries as (path, size, last used)
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        dir.filter_map(Result::ok)
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "pcm"))
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok()?;
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((entry.path(), metadata.len(), modified))
+            })
+            .collect()
+    }
+
+    fn entry_path(&self, hash: u64, sample_rate: Option<u32>, layout: ChannelLayout) -> PathBuf {
+        let tag = layout.tag();
+        let name = match sample_rate {
+            Some(rate) => format!("{:016x}{}-{}.pcm", hash, tag, rate),
+            None => format!("{:016x}{}.pcm", hash, tag),
+        };
+        self.dir.join(name)
+    }
+}
+
+/// This application's directory in the platform's user cache directory, such as
+/// `~/.cache/daw` on Linux.
+///
+/// Returns `None` if the platform cache directory can't be determined.
+pub fn user_cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "macos") {
+        PathBuf::from(std::env::var_os("HOME")?).join("Library/Caches")
+    } else if cfg!(windows) {
+        PathBuf::from(std::env::var_os("LOCALAPPDATA")?)
+    } else {
+        match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        }
+    };
+    Some(base.join("daw"))
+}
+
+/// FNV-1a hash of a file's contents and length.
+///
+/// A fixed algorithm rather than `DefaultHasher`, so keys stay valid across builds.
+pub(crate) fn hash_file_contents(path: &Path) -> std::io::Result<u64> {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut file = File::open(path)?;
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut hash = OFFSET_BASIS;
+    let mut len = 0u64;
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        for &byte in &buf[..read] {
+            hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+        }
+        len += read as u64;
+    }
+    for byte in len.to_le_bytes() {
+        hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+    }
+    Ok(hash)
+}
+
+fn write_entry(path: &Path, audio: &AudioArc) -> anyhow::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writer.write_all(MAGIC)?;
+    writer.write_all(&audio.sample_rate().to_le_bytes())?;
+    writer.write_all(&audio.channels().to_le_bytes())?;
+    writer.write_all(&(audio.frames() as u64).to_le_bytes())?;
+    for sample in audio.samples() {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_entry(path: &Path) -> anyhow::Result<AudioArc> {
+    let bytes = fs::read(path)?;
+    anyhow::ensure!(
+        bytes.len() >= HEADER_LEN && &bytes[..8] == MAGIC,
+        "not a cache entry"
+    );
+
+    let sample_rate = u32::from_le_bytes(bytes[8..12].try_into()?);
+    let channels = u16::from_le_bytes(bytes[12..14].try_into()?);
+    let frames = u64::from_le_bytes(bytes[14..22].try_into()?);
+    let data = &bytes[HEADER_LEN..];
+    anyhow::ensure!(channels > 0, "cache entry has no channels");
+    anyhow::ensure!(
+        data.len() as u64 == frames * channels as u64 * 4,
+        "cache entry is truncated"
+    );
+
+    let samples = data
+        .chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect();
+    Ok(AudioArc::new(samples, sample_rate, channels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn source_file(dir: &Path, name: &str, contents: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = DiskCache::new(temp_dir.path().join("cache"), u64::MAX);
+        let source = source_file(temp_dir.path(), "a.mp3", b"encoded audio");
+        let audio = AudioArc::new(vec![0.0, 0.5, -0.5, 1.0], 44100, 2);
+
+        assert!(cache.get(&source, None).is_none());
+        cache.put(&source, None, &audio).unwrap();
+
+        let cached = cache.get(&source, None).unwrap();
+        assert_eq!(cached.samples(), audio.samples());
+        assert_eq!(cached.sample_rate(), 44100);
+        assert_eq!(cached.channels(), 2);
+        // Each sample rate is its own entry
+        assert!(cache.get(&source, Some(48000)).is_none());
+    }
+
+    #[test]
+    fn test_changed_source_misses() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = DiskCache::new(temp_dir.path().join("cache"), u64::MAX);
+        let source = source_file(temp_dir.path(), "a.flac", b"version one");
+        cache
+            .put(&source, None, &AudioArc::new(vec![0.25], 44100, 1))
+            .unwrap();
+
+        fs::write(&source, b"version two").unwrap();
+        assert!(cache.get(&source, None).is_none());
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let temp_dir = TempDir::new().unwrap();
+        let audio = AudioArc::new(vec![0.0; 1000], 44100, 1);
+        let entry_size = (HEADER_LEN + 4000) as u64;
+        let cache = DiskCache::new(temp_dir.path().join("cache"), entry_size * 2);
+
+        let sources: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| source_file(temp_dir.path(), name, name.as_bytes()))
+            .collect();
+
+        cache.put(&sources[0], None, &audio).unwrap();
+        cache.put(&sources[1], None, &audio).unwrap();
+        // Make "a" clearly the oldest entry
+        let hash = hash_file_contents(&sources[0]).unwrap();
+        let oldest = cache.entry_path(hash, None, ChannelLayout::Native);
+        File::options()
+            .write(true)
+            .open(&oldest)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+
+        cache.put(&sources[2], None, &audio).unwrap();
+
+        assert!(cache.get(&sources[0], None).is_none());
+        assert!(cache.get(&sources[1], None).is_some());
+        assert!(cache.get(&sources[2], None).is_some());
+        assert_eq!(cache.size_bytes(), entry_size * 2);
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_a_miss() {
+        let temp_dir = TempDir::new().unwrap();
+        let cache = DiskCache::new(temp_dir.path().join("cache"), u64::MAX);
+        let source = source_file(temp_dir.path(), "a.wav", b"audio");
+        cache
+            .put(&source, None, &AudioArc::new(vec![0.0; 8], 44100, 2))
+            .unwrap();
+
+        let hash = hash_file_contents(&source).unwrap();
+        let entry = cache.entry_path(hash, None, ChannelLayout::Native);
+        let bytes = fs::read(&entry).unwrap();
+        fs::write(&entry, &bytes[..bytes.len() - 3]).unwrap();
+
+        assert!(cache.get(&source, None).is_none());
+    }
+}
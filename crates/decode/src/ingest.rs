@@ -0,0 +1,193 @@
+//! Writing newly recorded or bounced audio to disk.
+//!
+//! A recording or bounce exists only in memory until it is written out, and the
+//! project can't refer to it before then. [`AudioCache::ingest_recording`] and
+//! [`AudioCache::ingest_bounce`] are the paths from produced samples to a file:
+//! they write a WAV that can't clobber an existing one and cache the audio under
+//! that file, so clips can use it right away without decoding it back.
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use daw_transport::AudioArc;
+
+use crate::layout::apply_layout;
+use crate::{AudioCache, Cached, fingerprint_and_hash};
+
+/// Start of the names of recorded files, followed by a number
+const RECORDING_PREFIX: &str = "recording-";
+/// Start of the names of bounced clips, followed by a number
+const BOUNCE_PREFIX: &str = "bounce-";
+
+impl AudioCache {
+    /// Write `audio` to a new WAV file in `dest_dir` and cache it under that file.
+    ///
+    /// The file is named `recording-001.wav`, `recording-002.wav` and so on, taking
+    /// the first free number, and holds the samples as 32-bit floats so nothing is
+    /// lost. `dest_dir` is created if needed. Returns the path of the file; loading
+    /// it through this cache then returns the audio without decoding, brought into
+    /// the cache's [`ChannelLayout`](crate::ChannelLayout).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use daw_decode::AudioCache;
+    /// use daw_transport::AudioArc;
+    /// use std::path::Path;
+    ///
+    /// let mut cache = AudioCache::new();
+    /// let take = AudioArc::new(vec![0.0; 48000], 48000, 1);
+    /// let path = cache.ingest_recording(&take, Path::new("song/audio")).unwrap();
+    /// let audio = cache.get_or_load_direct(&path, Some(48000)).unwrap();
+    /// ```
+    pub fn ingest_recording(
+        &mut self,
+        audio: &AudioArc,
+        dest_dir: &Path,
+    ) -> anyhow::Result<PathBuf> {
+        self.ingest(audio, dest_dir, RECORDING_PREFIX)
+    }
+
+    /// Write a bounced clip to a new WAV file in `dest_dir` and cache it under that
+    /// file, like [`ingest_recording`](Self::ingest_recording) but named
+    /// `bounce-001.wav`, `bounce-002.wav` and so on.
+    pub fn ingest_bounce(&mut self, audio: &AudioArc, dest_dir: &Path) -> anyhow::Result<PathBuf> {
+        self.ingest(audio, dest_dir, BOUNCE_PREFIX)
+    }
+
+    fn ingest(
+        &mut self,
+        audio: &AudioArc,
+        dest_dir: &Path,
+        prefix: &str,
+    ) -> anyhow::Result<PathBuf> {
+        std::fs::create_dir_all(dest_dir)?;
+        let (path, file) = create_numbered_file(dest_dir, prefix)?;
+        let written = write_float_wav(file, audio);
+        if let Err(err) = written {
+            // Leave no half-written file behind to be mistaken for a take
+            let _ = std::fs::remove_file(&path);
+            return Err(err.context(format!("failed to write {}", path.display())));
+        }
+
+        let (fingerprint, hash) = fingerprint_and_hash(&path)?;
+        let now = self.tick();
+        self.content_hashes
+            .insert(path.clone(), (fingerprint, hash));
+        self.paths.insert(hash, path.clone());
+        self.originals.insert(
+            hash,
+            Cached {
+                audio: apply_layout(audio.clone(), None, self.layout),
+                last_used: now,
+            },
+        );
+        self.enforce_budget();
+        Ok(path)
+    }
+}
+
+/// Create the first `{prefix}NNN.wav` in `dir` that doesn't exist yet
+fn create_numbered_file(dir: &Path, prefix: &str) -> anyhow::Result<(PathBuf, File)> {
+    for number in 1.. {
+        let path = dir.join(format!("{}{:03}.wav", prefix, number));
+        match File::create_new(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    unreachable!("ran out of file numbers")
+}
+
+fn write_float_wav(file: File, audio: &AudioArc) -> anyhow::Result<()> {
+    let spec = hound::WavSpec {
+        channels: audio.channels(),
+        sample_rate: audio.sample_rate(),
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::new(BufWriter::new(file), spec)?;
+    for &sample in audio.samples() {
+        writer.write_sample(sample)?;
+    }
+    writer.finalize()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChannelLayout, decode_audio_arc_direct};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn take(value: f32) -> AudioArc {
+        let samples = (0..4800).map(|i| value * (i as f32 / 4800.0)).collect();
+        AudioArc::new(samples, 48000, 1)
+    }
+
+    #[test]
+    fn test_ingested_recording_is_written_and_cached() {
+        let temp_dir = TempDir::new().unwrap();
+        let dest = temp_dir.path().join("audio");
+        let mut cache = AudioCache::new();
+
+        let audio = take(0.5);
+        let path = cache.ingest_recording(&audio, &dest).unwrap();
+        assert_eq!(path, dest.join("recording-001.wav"));
+
+        // The file holds the exact samples
+        let decoded = decode_audio_arc_direct(&path, None).unwrap();
+        assert_eq!(decoded.samples(), audio.samples());
+        assert_eq!(decoded.sample_rate(), 48000);
+
+        // And loading it hands back the recorded buffer without decoding
+        assert_eq!(cache.originals_count(), 1);
+        let cached = cache.get_or_load_direct(&path, None).unwrap();
+        assert!(Arc::ptr_eq(cached.samples_arc(), audio.samples_arc()));
+        assert!(!cache.has_changed(&path));
+    }
+
+    #[test]
+    fn test_recordings_never_overwrite_files() {
+        let temp_dir = TempDir::new().unwrap();
+        std::fs::write(temp_dir.path().join("recording-001.wav"), b"keep").unwrap();
+        let mut cache = AudioCache::new();
+
+        let second = cache.ingest_recording(&take(0.5), temp_dir.path()).unwrap();
+        let third = cache
+            .ingest_recording(&take(0.25), temp_dir.path())
+            .unwrap();
+        assert_eq!(second.file_name().unwrap(), "recording-002.wav");
+        assert_eq!(third.file_name().unwrap(), "recording-003.wav");
+        assert_eq!(
+            std::fs::read(temp_dir.path().join("recording-001.wav")).unwrap(),
+            b"keep"
+        );
+    }
+
+    #[test]
+    fn test_bounces_are_numbered_apart_from_recordings() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = AudioCache::new();
+
+        cache.ingest_recording(&take(0.5), temp_dir.path()).unwrap();
+        let bounce = cache.ingest_bounce(&take(0.25), temp_dir.path()).unwrap();
+        assert_eq!(bounce.file_name().unwrap(), "bounce-001.wav");
+        assert_eq!(cache.originals_count(), 2);
+        assert!(!cache.has_changed(&bounce));
+    }
+
+    #[test]
+    fn test_ingested_recording_follows_channel_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut cache = AudioCache::new().with_channel_layout(ChannelLayout::Stereo);
+
+        let path = cache.ingest_recording(&take(0.5), temp_dir.path()).unwrap();
+        assert_eq!(cache.get_or_load_direct(&path, None).unwrap().channels(), 2);
+        // The file keeps the recording as it was captured
+        assert_eq!(decode_audio_arc_direct(&path, None).unwrap().channels(), 1);
+    }
+}
@@ -0,0 +1,201 @@
+//! Folding decoded audio into a fixed channel layout.
+
+use daw_transport::AudioArc;
+use symphonia::core::audio::Channels;
+
+/// Gain of a channel folded into one side, about -3 dB as in ITU-R BS.775
+const FOLD_GAIN: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Front channels that go straight to the left
+const LEFT: Channels = Channels::FRONT_LEFT
+    .union(Channels::FRONT_LEFT_CENTRE)
+    .union(Channels::FRONT_LEFT_WIDE);
+/// Front channels that go straight to the right
+const RIGHT: Channels = Channels::FRONT_RIGHT
+    .union(Channels::FRONT_RIGHT_CENTRE)
+    .union(Channels::FRONT_RIGHT_WIDE);
+/// Surround and height channels folded into the left
+const SURROUND_LEFT: Channels = Channels::REAR_LEFT
+    .union(Channels::SIDE_LEFT)
+    .union(Channels::REAR_LEFT_CENTRE)
+    .union(Channels::TOP_FRONT_LEFT)
+    .union(Channels::TOP_REAR_LEFT)
+    .union(Channels::FRONT_LEFT_HIGH);
+/// Surround and height channels folded into the right
+const SURROUND_RIGHT: Channels = Channels::REAR_RIGHT
+    .union(Channels::SIDE_RIGHT)
+    .union(Channels::REAR_RIGHT_CENTRE)
+    .union(Channels::TOP_FRONT_RIGHT)
+    .union(Channels::TOP_REAR_RIGHT)
+    .union(Channels::FRONT_RIGHT_HIGH);
+/// Low frequency effects, left out of a downmix like most players do
+const LFE: Channels = Channels::LFE1.union(Channels::LFE2);
+
+/// Channels decoded audio comes out with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelLayout {
+    /// As many channels as the file has
+    #[default]
+    Native,
+    /// Always two channels. Mono is copied to both sides; files with more channels
+    /// are folded down with the ITU-R BS.775 gains: centre and surround channels at
+    /// -3 dB into their side (both sides for centre), and LFE left out.
+    Stereo,
+}
+
+impl ChannelLayout {
+    /// Part of the disk cache entry names for audio in this layout
+    pub(crate) fn tag(self) -> &'static str {
+        match self {
+            Self::Native => "",
+            Self::Stereo => "-stereo",
+        }
+    }
+}
+
+/// Bring `audio`, decoded with the channel positions in `positions` if the file
+/// declared them, into `layout`
+pub(crate) fn apply_layout(
+    audio: AudioArc,
+    positions: Option<Channels>,
+    layout: ChannelLayout,
+) -> AudioArc {
+    let channels = audio.channels() as usize;
+    if layout == ChannelLayout::Native || channels == 2 {
+        return audio;
+    }
+
+    let samples = audio.samples();
+    let stereo: Vec<f32> = if channels == 1 {
+        samples
+            .iter()
+            .flat_map(|&sample| [sample, sample])
+            .collect()
+    } else {
+        let gains = stereo_gains(positions, channels);
+        samples
+            .chunks_exact(channels)
+            .flat_map(|frame| {
+                frame.iter().zip(&gains).fold(
+                    [0.0, 0.0],
+                    |[left, right], (&sample, &(to_left, to_right))| {
+                        [left + sample * to_left, right + sample * to_right]
+                    },
+                )
+            })
+            .collect()
+    };
+    AudioArc::new(stereo, audio.sample_rate(), 2)
+}
+
+/// Gain of each of `channels` channels into the left and right of a downmix
+fn stereo_gains(positions: Option<Channels>, channels: usize) -> Vec<(f32, f32)> {
+    let positions = positions
+        .filter(|positions| positions.count() == channels)
+        .or_else(|| default_positions(channels));
+    let Some(positions) = positions else {
+        // Nothing to go by, so every channel goes to both sides evenly
+        let gain = 1.0 / channels as f32;
+        return vec![(gain, gain); channels];
+    };
+
+    // Channels are interleaved in the order of their position bits
+    positions
+        .iter()
+        .map(|position| {
+            if LEFT.contains(position) {
+                (1.0, 0.0)
+            } else if RIGHT.contains(position) {
+                (0.0, 1.0)
+            } else if SURROUND_LEFT.contains(position) {
+                (FOLD_GAIN, 0.0)
+            } else if SURROUND_RIGHT.contains(position) {
+                (0.0, FOLD_GAIN)
+            } else if LFE.contains(position) {
+                (0.0, 0.0)
+            } else {
+                // Centre channels, front, rear or above
+                (FOLD_GAIN, FOLD_GAIN)
+            }
+        })
+        .collect()
+}
+
+/// The usual positions for files that give a channel count but no layout, in the
+/// order WAV uses
+fn default_positions(channels: usize) -> Option<Channels> {
+    let front = Channels::FRONT_LEFT | Channels::FRONT_RIGHT;
+    let centre = front | Channels::FRONT_CENTRE;
+    let surround_5_1 = centre | Channels::LFE1 | Channels::REAR_LEFT | Channels::REAR_RIGHT;
+    Some(match channels {
+        3 => centre,
+        4 => front | Channels::REAR_LEFT | Channels::REAR_RIGHT,
+        5 => centre | Channels::REAR_LEFT | Channels::REAR_RIGHT,
+        6 => surround_5_1,
+        7 => {
+            centre
+                | Channels::LFE1
+                | Channels::REAR_CENTRE
+                | Channels::SIDE_LEFT
+                | Channels::SIDE_RIGHT
+        }
+        8 => surround_5_1 | Channels::SIDE_LEFT | Channels::SIDE_RIGHT,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_native_and_stereo_pass_through() {
+        let audio = AudioArc::new(vec![0.1, 0.2, 0.3], 48000, 1);
+        let native = apply_layout(audio.clone(), None, ChannelLayout::Native);
+        assert_eq!(native.channels(), 1);
+
+        let stereo = AudioArc::new(vec![0.1, 0.2], 48000, 2);
+        let out = apply_layout(stereo.clone(), None, ChannelLayout::Stereo);
+        assert_eq!(out.samples().as_ptr(), stereo.samples().as_ptr());
+    }
+
+    #[test]
+    fn test_mono_is_copied_to_both_sides() {
+        let audio = AudioArc::new(vec![0.1, -0.2], 44100, 1);
+        let out = apply_layout(audio, None, ChannelLayout::Stereo);
+        assert_eq!(out.channels(), 2);
+        assert_eq!(out.sample_rate(), 44100);
+        assert_eq!(out.samples(), &[0.1, 0.1, -0.2, -0.2]);
+    }
+
+    #[test]
+    fn test_5_1_folds_down_without_lfe() {
+        // FL FR FC LFE RL RR
+        let audio = AudioArc::new(vec![1.0, 0.5, 1.0, 1.0, 1.0, 0.0], 48000, 6);
+        let out = apply_layout(audio, None, ChannelLayout::Stereo);
+        let [left, right] = [out.samples()[0], out.samples()[1]];
+        assert!((left - (1.0 + 2.0 * FOLD_GAIN)).abs() < 1e-6);
+        assert!((right - (0.5 + FOLD_GAIN)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_declared_positions_win_over_count() {
+        // A quad file laid out as FL FR SL SR rather than the default rears
+        let positions = Channels::FRONT_LEFT
+            | Channels::FRONT_RIGHT
+            | Channels::SIDE_LEFT
+            | Channels::SIDE_RIGHT;
+        let audio = AudioArc::new(vec![0.0, 0.0, 1.0, 0.0], 48000, 4);
+        let out = apply_layout(audio, Some(positions), ChannelLayout::Stereo);
+        assert!((out.samples()[0] - FOLD_GAIN).abs() < 1e-6);
+        assert_eq!(out.samples()[1], 0.0);
+    }
+
+    #[test]
+    fn test_unknown_layout_spreads_evenly() {
+        let audio = AudioArc::new(vec![1.0; 10], 48000, 10);
+        let out = apply_layout(audio, None, ChannelLayout::Stereo);
+        assert!((out.samples()[0] - 1.0).abs() < 1e-6);
+        assert!((out.samples()[1] - 1.0).abs() < 1e-6);
+    }
+}
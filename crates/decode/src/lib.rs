@@ -1,19 +1,51 @@
 use std::collections::HashMap;
-use std::collections::hash_map::DefaultHasher;
+use std::collections::hash_map::{DefaultHasher, Entry};
 use std::fs::File;
 use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
 
-use daw_transport::{AudioArc, AudioBuffer};
+use daw_transport::{AudioArc, AudioBuffer, resample_audio_arc_with_progress};
 use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs::DecoderOptions;
-use symphonia::core::formats::FormatOptions;
+use symphonia::core::errors::{Error as SymphoniaError, SeekErrorKind};
+use symphonia::core::formats::{FormatOptions, SeekMode, SeekTo};
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 
+mod async_load;
+mod disk_cache;
+mod ingest;
+mod layout;
+mod library;
+mod metadata;
+mod prefetch;
+#[cfg(test)]
+pub(crate) mod test_wav;
+#[cfg(feature = "watch")]
+mod watch;
+
+pub use async_load::PendingLoad;
+pub use disk_cache::{DEFAULT_DISK_CACHE_BYTES, DiskCache, user_cache_dir};
+pub use layout::ChannelLayout;
+pub use library::{
+    LibraryChanges, LibrarySample, SampleLibrary, is_supported_audio, supported_extensions,
+};
+pub use metadata::{AudioMarker, AudioMetadata, probe_metadata, probe_metadata_direct};
+pub use prefetch::Prefetcher;
+#[cfg(feature = "watch")]
+pub use watch::SampleWatcher;
+
+use disk_cache::hash_file_contents;
+use layout::apply_layout;
+
 const SAMPLES_ROOT: &str = "samples";
 
+/// Default memory budget for an [`AudioCache`] owned by a session: 1 GiB
+pub const DEFAULT_MEMORY_BUDGET_BYTES: usize = 1024 * 1024 * 1024;
+
 /// Resolve a sample path to an absolute path.
 /// Accepts paths relative to the samples root (e.g., `cr78/hihat.wav`)
 /// or paths that already include the samples root (e.g., `samples/cr78/hihat.wav`).
@@ -180,6 +212,9 @@ pub fn decode_audio_arc(path: &Path, target_sample_rate: Option<u32>) -> anyhow:
 
 /// Decode an audio file directly (without path resolution) and return an `AudioArc`.
 ///
+/// Fails on the first packet that can't be decoded; see [`decode_audio_arc_lossy`] for
+/// a mode that keeps going.
+///
 /// # Arguments
 ///
 /// * `path` - Absolute path to the audio file
@@ -188,6 +223,68 @@ pub fn decode_audio_arc_direct(
     path: &Path,
     target_sample_rate: Option<u32>,
 ) -> anyhow::Result<AudioArc> {
+    decode_audio_arc_direct_with_layout(path, target_sample_rate, ChannelLayout::Native)
+}
+
+/// Like [`decode_audio_arc_direct`], bringing the audio into a [`ChannelLayout`] first.
+pub fn decode_audio_arc_direct_with_layout(
+    path: &Path,
+    target_sample_rate: Option<u32>,
+    layout: ChannelLayout,
+) -> anyhow::Result<AudioArc> {
+    let (audio, _) = decode_original(path, false, layout)?;
+
+    // Resample if requested
+    match target_sample_rate {
+        Some(target_rate) if target_rate != audio.sample_rate() => audio.resample(target_rate),
+        _ => Ok(audio),
+    }
+}
+
+/// A stretch of a file that couldn't be decoded by [`decode_audio_arc_lossy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeIssue {
+    /// Frame of the decoded audio where the damage starts
+    pub frame: u64,
+    /// Frames of silence put in its place; 0 when decoding stopped there, leaving
+    /// the audio shorter than the file claims
+    pub frames: u64,
+    /// What went wrong
+    pub error: String,
+}
+
+impl std::fmt::Display for DecodeIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.frames == 0 {
+            write!(f, "stopped at frame {}: {}", self.frame, self.error)
+        } else {
+            write!(
+                f,
+                "{} frames silenced at frame {}: {}",
+                self.frames, self.frame, self.error
+            )
+        }
+    }
+}
+
+/// Decode an audio file directly, getting past damage instead of failing on it.
+///
+/// A packet that fails to decode is replaced by silence of the same length, so
+/// everything after it stays in time, and a stream that can't be read any further
+/// ends the audio there. Each of these is returned as a [`DecodeIssue`] alongside the
+/// audio. Opening the file still fails as usual if it isn't audio at all, or if not
+/// a single packet could be decoded.
+pub fn decode_audio_arc_lossy(path: &Path) -> anyhow::Result<(AudioArc, Vec<DecodeIssue>)> {
+    decode_original(path, true, ChannelLayout::Native)
+}
+
+/// Decode all of `path` at its own sample rate into `layout`; when `lossy`, damage
+/// is patched over and reported rather than failing the decode
+fn decode_original(
+    path: &Path,
+    lossy: bool,
+    layout: ChannelLayout,
+) -> anyhow::Result<(AudioArc, Vec<DecodeIssue>)> {
     let file = File::open(path)?;
     let mss = MediaSourceStream::new(Box::new(file), Default::default());
 
@@ -210,13 +307,26 @@ pub fn decode_audio_arc_direct(
         .ok_or_else(|| anyhow::anyhow!("no default track"))?;
 
     let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
-    let channels = track.codec_params.channels.map(|c| c.count()).unwrap_or(2) as u16;
+    let positions = track.codec_params.channels;
+    let channels = positions.map(|c| c.count()).unwrap_or(2) as u16;
     let track_id = track.id;
 
+    // Packet durations are in the track's time base, which is usually one tick per frame
+    let (ts_numer, ts_denom) = match track.codec_params.time_base {
+        Some(time_base) => (
+            time_base.numer as u64 * sample_rate as u64,
+            time_base.denom as u64,
+        ),
+        None => (1, 1),
+    };
+
     let mut decoder =
         symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
 
+    let channel_count = channels as usize;
     let mut samples = Vec::new();
+    let mut issues = Vec::new();
+    let mut decoded_any = false;
 
     loop {
         let packet = match format.next_packet() {
@@ -226,6 +336,14 @@ pub fn decode_audio_arc_direct(
             {
                 break;
             }
+            Err(e) if lossy => {
+                issues.push(DecodeIssue {
+                    frame: (samples.len() / channel_count) as u64,
+                    frames: 0,
+                    error: e.to_string(),
+                });
+                break;
+            }
             Err(e) => return Err(e.into()),
         };
 
@@ -233,24 +351,266 @@ pub fn decode_audio_arc_direct(
             continue;
         }
 
-        let decoded = decoder.decode(&packet)?;
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(e @ (SymphoniaError::DecodeError(_) | SymphoniaError::IoError(_))) if lossy => {
+                let frames = packet.dur() * ts_numer / ts_denom;
+                issues.push(DecodeIssue {
+                    frame: (samples.len() / channel_count) as u64,
+                    frames,
+                    error: e.to_string(),
+                });
+                samples.resize(samples.len() + frames as usize * channel_count, 0.0);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
         let spec = *decoded.spec();
         let duration = decoded.capacity() as u64;
 
         let mut sample_buf = SampleBuffer::<f32>::new(duration, spec);
         sample_buf.copy_interleaved_ref(decoded);
         samples.extend_from_slice(sample_buf.samples());
+        decoded_any = true;
+    }
+
+    if !decoded_any && let Some(issue) = issues.first() {
+        anyhow::bail!("no audio could be decoded: {}", issue.error);
     }
 
     let audio = AudioArc::new(samples, sample_rate, channels);
+    Ok((apply_layout(audio, positions, layout), issues))
+}
+
+/// Decode only a range of frames from an audio file, without path resolution.
+///
+/// Seeks to `start_frame` and decodes until `frames` frames have been produced (or the
+/// file ends), so a short region deep into a long recording doesn't require decoding the
+/// whole file. A stream that can't seek is decoded from its start, keeping only the
+/// region and stopping at its end. Frames are counted at the file's own sample rate; the result is resampled
+/// afterwards if `target_sample_rate` is given.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be decoded, or if `start_frame` is past the end
+/// of a seekable file.
+pub fn decode_region_direct(
+    path: &Path,
+    start_frame: u64,
+    frames: u64,
+    target_sample_rate: Option<u32>,
+) -> anyhow::Result<AudioArc> {
+    decode_region(
+        path,
+        start_frame,
+        frames,
+        target_sample_rate,
+        ChannelLayout::Native,
+    )
+}
+
+/// [`decode_region_direct`] into `layout`
+fn decode_region(
+    path: &Path,
+    start_frame: u64,
+    frames: u64,
+    target_sample_rate: Option<u32>,
+    layout: ChannelLayout,
+) -> anyhow::Result<AudioArc> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+
+    let mut format = probed.format;
+
+    let track = format
+        .default_track()
+        .ok_or_else(|| anyhow::anyhow!("no default track"))?;
+
+    let sample_rate = track.codec_params.sample_rate.unwrap_or(44100);
+    let positions = track.codec_params.channels;
+    let channels = positions.map(|c| c.count()).unwrap_or(2) as u16;
+    let track_id = track.id;
+
+    // Packet timestamps are in the track's time base, which is usually one tick per frame
+    let (ts_numer, ts_denom) = match track.codec_params.time_base {
+        Some(time_base) => (
+            time_base.numer as u64 * sample_rate as u64,
+            time_base.denom as u64,
+        ),
+        None => (1, 1),
+    };
+    let ts_to_frame = |ts: u64| ts * ts_numer / ts_denom;
+
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let seek = format.seek(
+        SeekMode::Accurate,
+        SeekTo::TimeStamp {
+            ts: start_frame * ts_denom / ts_numer,
+            track_id,
+        },
+    );
+    match seek {
+        Ok(_) => decoder.reset(),
+        // Streams that can't seek are decoded from the start instead; the frames
+        // before the region are skipped below, and decoding still stops at its end
+        Err(SymphoniaError::SeekError(SeekErrorKind::Unseekable | SeekErrorKind::ForwardOnly)) => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let channel_count = channels as usize;
+    let end_frame = start_frame + frames;
+    let mut samples = Vec::with_capacity(frames as usize * channel_count);
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(symphonia::core::errors::Error::IoError(e))
+                if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let packet_start = ts_to_frame(packet.ts());
+        if packet_start >= end_frame {
+            break;
+        }
+
+        let decoded = decoder.decode(&packet)?;
+        let spec = *decoded.spec();
+        let duration = decoded.capacity() as u64;
+
+        let mut sample_buf = SampleBuffer::<f32>::new(duration, spec);
+        sample_buf.copy_interleaved_ref(decoded);
+
+        // Keep only the frames of this packet that fall inside the region
+        let packet_frames = (sample_buf.samples().len() / channel_count) as u64;
+        let skip = start_frame.saturating_sub(packet_start).min(packet_frames);
+        let take = (end_frame - packet_start).min(packet_frames);
+        if skip < take {
+            samples.extend_from_slice(
+                &sample_buf.samples()[skip as usize * channel_count..take as usize * channel_count],
+            );
+        }
+    }
+
+    let audio = apply_layout(
+        AudioArc::new(samples, sample_rate, channels),
+        positions,
+        layout,
+    );
 
-    // Resample if requested
     match target_sample_rate {
         Some(target_rate) if target_rate != sample_rate => audio.resample(target_rate),
         _ => Ok(audio),
     }
 }
 
+/// Decode the original audio of `path`, whose contents hash to `hash`, into
+/// `layout`, through the disk cache if there is one; when `lossy`, damage is
+/// patched over as in [`decode_audio_arc_lossy`]
+fn load_original(
+    path: &Path,
+    hash: u64,
+    disk: Option<&DiskCache>,
+    lossy: bool,
+    layout: ChannelLayout,
+) -> anyhow::Result<(AudioArc, Vec<DecodeIssue>)> {
+    if let Some(audio) = disk.and_then(|disk| disk.get_by_hash(hash, None, layout)) {
+        return Ok((audio, Vec::new()));
+    }
+    let (audio, issues) = decode_original(path, lossy, layout)?;
+    // Patched audio isn't kept on disk, so its issues are reported on every load
+    if let Some(disk) = disk.filter(|_| issues.is_empty()) {
+        // A failed write only means the next load decodes again
+        let _ = disk.put_by_hash(hash, None, layout, &audio);
+    }
+    Ok((audio, issues))
+}
+
+/// Resample the `original` audio of the file whose contents hash to `hash`, in
+/// `layout`, to `rate`, through the disk cache if there is one
+fn load_resampled(
+    hash: u64,
+    original: &AudioArc,
+    rate: u32,
+    disk: Option<&DiskCache>,
+    layout: ChannelLayout,
+    progress: impl FnMut(f32),
+) -> anyhow::Result<AudioArc> {
+    if let Some(audio) = disk.and_then(|disk| disk.get_by_hash(hash, Some(rate), layout)) {
+        return Ok(audio);
+    }
+    let audio = resample_audio_arc_with_progress(original, rate, progress)?;
+    if let Some(disk) = disk {
+        let _ = disk.put_by_hash(hash, Some(rate), layout, &audio);
+    }
+    Ok(audio)
+}
+
+/// Key for a cached region: (file_hash, start_frame, frames, target_rate)
+type RegionKey = (u64, u64, u64, Option<u32>);
+
+/// Size and modification time of a file, to notice when it changes
+type Fingerprint = (u64, Option<SystemTime>);
+
+fn fingerprint(path: &Path) -> std::io::Result<Fingerprint> {
+    let metadata = std::fs::metadata(path)?;
+    Ok((metadata.len(), metadata.modified().ok()))
+}
+
+/// Fingerprint and content hash of `path`, reading the whole file
+fn fingerprint_and_hash(path: &Path) -> anyhow::Result<(Fingerprint, u64)> {
+    let read_error = |err| anyhow::anyhow!("failed to read {}: {}", path.display(), err);
+    let fingerprint = fingerprint(path).map_err(read_error)?;
+    let hash = hash_file_contents(path).map_err(read_error)?;
+    Ok((fingerprint, hash))
+}
+
+/// A cache entry with the time it was last handed out
+#[derive(Debug)]
+struct Cached {
+    audio: AudioArc,
+    last_used: u64,
+}
+
+/// An entry that may be evicted to get under the memory budget
+enum Evictable {
+    Original(u64),
+    Resampled((u64, u32)),
+    Region(RegionKey),
+}
+
+/// Bytes of sample data kept alive by `audio`, which for a slice is the whole
+/// shared buffer
+fn buffer_bytes(audio: &AudioArc) -> usize {
+    audio.samples_arc().len() * std::mem::size_of::<f32>()
+}
+
+/// Identity of the sample buffer behind `audio`, shared by its clones and slices
+fn buffer_id(audio: &AudioArc) -> *const f32 {
+    audio.samples_arc().as_ptr()
+}
+
 /// Two-tier audio cache for efficient loading and resampling.
 ///
 /// `AudioCache` stores decoded audio files and their resampled versions to avoid
@@ -259,6 +619,12 @@ pub fn decode_audio_arc_direct(
 /// 1. **Original tier**: Stores decoded audio at its original sample rate
 /// 2. **Resampled tier**: Stores resampled versions keyed by (file, target_rate)
 ///
+/// Both tiers are keyed by a hash of the file's contents rather than its path, so
+/// the same sample reached through two paths (a copy in the project folder and
+/// the one in the samples directory, say) is decoded once and shared. The hash
+/// is remembered per path and only recomputed when the file's size or
+/// modification time changes, which also means an edited file is decoded again.
+///
 /// All cached audio is stored as `AudioArc`, making clones very cheap (just a
 /// reference count increment).
 ///
@@ -270,6 +636,16 @@ pub fn decode_audio_arc_direct(
 /// - Rendering at different sample rates (e.g., 44.1kHz for CD)
 /// - Both using the same cache without duplicate work
 ///
+/// # Memory Budget
+///
+/// By default the cache grows without bound. With a budget set (see
+/// [`with_memory_budget`](Self::with_memory_budget)), entries are evicted in
+/// least recently used order once the cached sample data exceeds it: resampled
+/// versions and regions first, since they can be rebuilt from the original, then
+/// originals. Audio that is still referenced outside the cache (by a clip, say)
+/// is never evicted, because dropping it wouldn't free anything. The cache can
+/// therefore stay over budget while everything in it is in use.
+///
 /// # Examples
 ///
 /// ```no_run
@@ -287,12 +663,28 @@ pub fn decode_audio_arc_direct(
 /// ```
 #[derive(Debug)]
 pub struct AudioCache {
-    /// Original decoded audio (no resampling): file_hash -> AudioArc
-    originals: HashMap<u64, AudioArc>,
-    /// Resampled versions: (file_hash, target_rate) -> AudioArc
-    resampled: HashMap<(u64, u32), AudioArc>,
+    /// Original decoded audio (no resampling): content_hash -> AudioArc
+    originals: HashMap<u64, Cached>,
+    /// Resampled versions: (content_hash, target_rate) -> AudioArc
+    resampled: HashMap<(u64, u32), Cached>,
+    /// Partially decoded regions of files that aren't fully loaded
+    regions: HashMap<RegionKey, Cached>,
+    /// Optional persistent tier backing originals and resampled versions
+    disk: Option<DiskCache>,
     /// Map from file hash to resolved path for debugging
     paths: HashMap<u64, PathBuf>,
+    /// Content hash of each file loaded so far, valid while its fingerprint matches
+    content_hashes: HashMap<PathBuf, (Fingerprint, u64)>,
+    /// Whether files are decoded past damage (see [`with_lossy_decoding`](Self::with_lossy_decoding))
+    lossy: bool,
+    /// Damage found while decoding: content_hash -> issues, for files that had any
+    decode_issues: HashMap<u64, Vec<DecodeIssue>>,
+    /// Channels all cached audio is brought into
+    layout: ChannelLayout,
+    /// Most bytes of sample data to keep when entries can be evicted (None = unbounded)
+    budget: Option<usize>,
+    /// Counter standing in for the time of each access, for LRU order
+    clock: u64,
 }
 
 impl AudioCache {
@@ -301,10 +693,98 @@ impl AudioCache {
         Self {
             originals: HashMap::new(),
             resampled: HashMap::new(),
+            regions: HashMap::new(),
+            disk: None,
             paths: HashMap::new(),
+            content_hashes: HashMap::new(),
+            lossy: false,
+            decode_issues: HashMap::new(),
+            layout: ChannelLayout::Native,
+            budget: None,
+            clock: 0,
+        }
+    }
+
+    /// Limit the cached sample data to about `bytes` (see [Memory Budget](Self#memory-budget)).
+    pub fn with_memory_budget(mut self, bytes: usize) -> Self {
+        self.budget = Some(bytes);
+        self
+    }
+
+    /// Change the memory budget, evicting right away if the cache is over it.
+    pub fn set_memory_budget(&mut self, bytes: Option<usize>) {
+        self.budget = bytes;
+        self.enforce_budget();
+    }
+
+    /// The memory budget in bytes, if any
+    pub fn memory_budget(&self) -> Option<usize> {
+        self.budget
+    }
+
+    /// Back this cache with a persistent [`DiskCache`].
+    ///
+    /// Decoded and resampled audio is then written to disk, and later loads of the
+    /// same file contents read it back instead of decoding again.
+    pub fn with_disk_cache(mut self, disk: DiskCache) -> Self {
+        self.disk = Some(disk);
+        self
+    }
+
+    /// Decode files past packets that fail, as [`decode_audio_arc_lossy`] does, instead
+    /// of failing the load.
+    ///
+    /// What was patched over is kept per file; see [`decode_issues`](Self::decode_issues).
+    pub fn with_lossy_decoding(mut self) -> Self {
+        self.lossy = true;
+        self
+    }
+
+    /// Turn lossy decoding on or off for files decoded from now on.
+    pub fn set_lossy_decoding(&mut self, lossy: bool) {
+        self.lossy = lossy;
+    }
+
+    /// Whether files are decoded past damage
+    pub fn lossy_decoding(&self) -> bool {
+        self.lossy
+    }
+
+    /// Damage patched over while decoding `path` with lossy decoding on.
+    ///
+    /// Empty for files that decoded cleanly or haven't been loaded. Issues are
+    /// reported for the file's contents, so another path to the same audio shares them.
+    pub fn decode_issues(&self, path: &Path) -> &[DecodeIssue] {
+        self.content_hashes
+            .get(path)
+            .and_then(|(_, hash)| self.decode_issues.get(hash))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Bring all audio loaded through this cache into `layout`, such as
+    /// [`ChannelLayout::Stereo`] for an engine that plays everything in stereo.
+    pub fn with_channel_layout(mut self, layout: ChannelLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Change the channel layout of audio loaded from now on.
+    ///
+    /// Cached audio in the old layout is dropped, so it's decoded again on its next load.
+    pub fn set_channel_layout(&mut self, layout: ChannelLayout) {
+        if layout != self.layout {
+            self.layout = layout;
+            self.originals.clear();
+            self.resampled.clear();
+            self.regions.clear();
         }
     }
 
+    /// The channel layout audio is loaded in
+    pub fn channel_layout(&self) -> ChannelLayout {
+        self.layout
+    }
+
     /// Get audio from cache or load it from disk.
     ///
     /// This is the main entry point for loading audio. It:
@@ -382,47 +862,287 @@ impl AudioCache {
         path: &Path,
         target_sample_rate: Option<u32>,
     ) -> anyhow::Result<AudioArc> {
-        let hash = hash_path(path);
+        self.get_or_load_direct_with_progress(path, target_sample_rate, |_| {})
+    }
 
-        // Load original if not cached
-        if !self.originals.contains_key(&hash) {
-            let audio = decode_audio_arc_direct(path, None)?;
-            self.originals.insert(hash, audio);
-            self.paths.insert(hash, path.to_path_buf());
-        }
+    /// Like [`get_or_load_direct`](Self::get_or_load_direct), reporting resampling progress.
+    ///
+    /// `progress` receives the fraction resampled so far, from 0.0 to 1.0, so a loading UI
+    /// can show progress for long files. It is only called when a new resampled version has
+    /// to be computed.
+    pub fn get_or_load_direct_with_progress(
+        &mut self,
+        path: &Path,
+        target_sample_rate: Option<u32>,
+        progress: impl FnMut(f32),
+    ) -> anyhow::Result<AudioArc> {
+        let hash = self.content_hash(path)?;
+        let now = self.tick();
 
-        let original = self.originals.get(&hash).unwrap();
+        // Load original if not cached
+        let original = match self.originals.entry(hash) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let (audio, issues) =
+                    load_original(path, hash, self.disk.as_ref(), self.lossy, self.layout)?;
+                self.paths.insert(hash, path.to_path_buf());
+                if !issues.is_empty() {
+                    self.decode_issues.insert(hash, issues);
+                }
+                entry.insert(Cached {
+                    audio,
+                    last_used: now,
+                })
+            }
+        };
+        original.last_used = now;
+        let original = original.audio.clone();
 
-        // If no target rate specified, return original
         let target_rate = match target_sample_rate {
-            Some(rate) => rate,
-            None => return Ok(original.clone()),
+            Some(rate) if rate != original.sample_rate() => rate,
+            // No target rate, or the original is already at it
+            _ => {
+                self.enforce_budget();
+                return Ok(original);
+            }
         };
 
-        // If original is already at target rate, return it
-        if original.sample_rate() == target_rate {
-            return Ok(original.clone());
-        }
-
         // Check if resampled version is cached
         let key = (hash, target_rate);
-        if let Some(resampled) = self.resampled.get(&key) {
-            return Ok(resampled.clone());
+        if let Some(resampled) = self.resampled.get_mut(&key) {
+            resampled.last_used = now;
+            return Ok(resampled.audio.clone());
         }
 
         // Resample from original and cache it
-        let resampled = original.resample(target_rate)?;
-        self.resampled.insert(key, resampled.clone());
+        let resampled = load_resampled(
+            hash,
+            &original,
+            target_rate,
+            self.disk.as_ref(),
+            self.layout,
+            progress,
+        )?;
+        self.resampled.insert(
+            key,
+            Cached {
+                audio: resampled.clone(),
+                last_used: now,
+            },
+        );
+        // The original isn't referenced anymore unless a clip uses it directly
+        drop(original);
+        self.enforce_budget();
         Ok(resampled)
     }
 
+    /// Get a range of frames from a file, decoding only that range if needed.
+    ///
+    /// `start_frame` and `frames` are counted at the file's own sample rate. If the whole
+    /// file is already cached the region is a zero-copy slice of it; otherwise only the
+    /// region is decoded with [`decode_region_direct`] and cached under its own key.
+    pub fn get_or_load_region_direct(
+        &mut self,
+        path: &Path,
+        start_frame: u64,
+        frames: u64,
+        target_sample_rate: Option<u32>,
+    ) -> anyhow::Result<AudioArc> {
+        let hash = hash_path(path);
+        let now = self.tick();
+        let key = (hash, start_frame, frames, target_sample_rate);
+        if let Some(region) = self.regions.get_mut(&key) {
+            region.last_used = now;
+            return Ok(region.audio.clone());
+        }
+
+        // Hashing the contents would read the whole file, so only a file already
+        // loaded in full can serve regions from its original
+        let original = self
+            .known_content_hash(path)
+            .and_then(|content_hash| self.originals.get_mut(&content_hash));
+        let region = match original {
+            Some(Cached {
+                audio: original,
+                last_used,
+            }) => {
+                *last_used = now;
+                let total = original.frames();
+                let start = (start_frame as usize).min(total);
+                let end = start.saturating_add(frames as usize).min(total);
+                let region = original.slice(start..end);
+                match target_sample_rate {
+                    Some(rate) => region.resample(rate)?,
+                    None => region,
+                }
+            }
+            None => decode_region(path, start_frame, frames, target_sample_rate, self.layout)?,
+        };
+
+        self.paths.insert(hash, path.to_path_buf());
+        self.regions.insert(
+            key,
+            Cached {
+                audio: region.clone(),
+                last_used: now,
+            },
+        );
+        self.enforce_budget();
+        Ok(region)
+    }
+
+    /// Hash of the contents of `path`, reusing the last one while the file is unchanged
+    fn content_hash(&mut self, path: &Path) -> anyhow::Result<u64> {
+        if let Some(hash) = self.known_content_hash(path) {
+            return Ok(hash);
+        }
+        let (fingerprint, hash) = fingerprint_and_hash(path)?;
+        self.content_hashes
+            .insert(path.to_path_buf(), (fingerprint, hash));
+        Ok(hash)
+    }
+
+    /// The hash of the contents of `path` as of its last load, if the file hasn't
+    /// changed since.
+    ///
+    /// Identical files share a hash, so it can key anything derived from the audio.
+    /// Checking costs a `stat` of the file; nothing is read.
+    pub fn known_content_hash(&self, path: &Path) -> Option<u64> {
+        self.known_content(path).map(|(_, hash)| hash)
+    }
+
+    /// Whether `path` was loaded before and has changed on disk since.
+    ///
+    /// Files that were never loaded, and files that can no longer be read, are not
+    /// reported. Loading the file again clears the change. Checking costs a `stat`.
+    pub fn has_changed(&self, path: &Path) -> bool {
+        self.content_hashes
+            .get(path)
+            .is_some_and(|&(known, _)| fingerprint(path).is_ok_and(|now| now != known))
+    }
+
+    /// Files loaded before that have changed on disk since, as [`has_changed`]
+    /// reports them
+    ///
+    /// [`has_changed`]: Self::has_changed
+    pub fn changed_files(&self) -> Vec<PathBuf> {
+        self.content_hashes
+            .keys()
+            .filter(|path| self.has_changed(path))
+            .cloned()
+            .collect()
+    }
+
+    /// The fingerprint and content hash remembered for `path`, if the file hasn't
+    /// changed since
+    fn known_content(&self, path: &Path) -> Option<(Fingerprint, u64)> {
+        let &(known, hash) = self.content_hashes.get(path)?;
+        (fingerprint(path).ok()? == known).then_some((known, hash))
+    }
+
+    /// Advance the access clock, returning the new time
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &Cached> {
+        self.originals
+            .values()
+            .chain(self.resampled.values())
+            .chain(self.regions.values())
+    }
+
+    /// Bytes of sample data held by the cache.
+    ///
+    /// Entries sharing a buffer (a region sliced from a cached original) are
+    /// counted once. Audio still referenced elsewhere counts too, since the cache
+    /// keeps it alive as well.
+    pub fn bytes_used(&self) -> usize {
+        let mut seen = std::collections::HashSet::new();
+        self.entries()
+            .filter(|entry| seen.insert(buffer_id(&entry.audio)))
+            .map(|entry| buffer_bytes(&entry.audio))
+            .sum()
+    }
+
+    /// Evict least recently used entries until the cache is within its budget or
+    /// everything left is still referenced elsewhere.
+    fn enforce_budget(&mut self) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        let mut used = self.bytes_used();
+        if used <= budget {
+            return;
+        }
+
+        // How many cache entries hold each buffer, to tell them from outside references
+        let mut holders: HashMap<*const f32, usize> = HashMap::new();
+        for entry in self.entries() {
+            *holders.entry(buffer_id(&entry.audio)).or_default() += 1;
+        }
+
+        // Derived entries go first, then originals, each oldest first
+        let mut candidates: Vec<(bool, u64, Evictable)> = self
+            .resampled
+            .iter()
+            .map(|(key, entry)| (false, entry.last_used, Evictable::Resampled(*key)))
+            .chain(
+                self.regions
+                    .iter()
+                    .map(|(key, entry)| (false, entry.last_used, Evictable::Region(*key))),
+            )
+            .chain(
+                self.originals
+                    .iter()
+                    .map(|(key, entry)| (true, entry.last_used, Evictable::Original(*key))),
+            )
+            .collect();
+        candidates.sort_by_key(|(is_original, last_used, _)| (*is_original, *last_used));
+
+        for (_, _, candidate) in candidates {
+            if used <= budget {
+                break;
+            }
+            let entry = match &candidate {
+                Evictable::Original(key) => self.originals.get(key),
+                Evictable::Resampled(key) => self.resampled.get(key),
+                Evictable::Region(key) => self.regions.get(key),
+            };
+            let Some(entry) = entry else {
+                continue;
+            };
+            let id = buffer_id(&entry.audio);
+            if Arc::strong_count(entry.audio.samples_arc()) > holders[&id] {
+                continue;
+            }
+
+            let removed = match candidate {
+                Evictable::Original(key) => self.originals.remove(&key),
+                Evictable::Resampled(key) => self.resampled.remove(&key),
+                Evictable::Region(key) => self.regions.remove(&key),
+            };
+            if let Some(removed) = removed {
+                let remaining = holders.get_mut(&id).expect("counted above");
+                *remaining -= 1;
+                if *remaining == 0 {
+                    used -= buffer_bytes(&removed.audio);
+                }
+            }
+        }
+    }
+
     /// Clear all cached audio.
     ///
     /// This frees memory but requires re-decoding on next access.
     pub fn clear(&mut self) {
         self.originals.clear();
         self.resampled.clear();
+        self.regions.clear();
         self.paths.clear();
+        self.content_hashes.clear();
+        self.decode_issues.clear();
     }
 
     /// Get the number of cached original audio files.
@@ -435,18 +1155,89 @@ impl AudioCache {
         self.resampled.len()
     }
 
-    /// Get total cache entry count (originals + resampled).
+    /// Get the number of cached partial regions.
+    pub fn regions_count(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Get total cache entry count (originals + resampled + regions).
     pub fn total_count(&self) -> usize {
-        self.originals_count() + self.resampled_count()
+        self.originals_count() + self.resampled_count() + self.regions_count()
     }
 
-    /// Get cache statistics for debugging.
+    /// Get cache statistics, with entry counts and bytes per tier.
     pub fn stats(&self) -> CacheStats {
+        let mut tier_bytes = [0; 3];
+        for entry in self.entry_sizes() {
+            tier_bytes[entry.tier as usize] += entry.bytes;
+        }
+        let [original_bytes, resampled_bytes, region_bytes] = tier_bytes;
         CacheStats {
             originals: self.originals_count(),
             resampled: self.resampled_count(),
+            regions: self.regions_count(),
             total: self.total_count(),
+            bytes: original_bytes + resampled_bytes + region_bytes,
+            original_bytes,
+            resampled_bytes,
+            region_bytes,
+        }
+    }
+
+    /// Every cached entry with the bytes it holds.
+    ///
+    /// A buffer shared by several entries (a region sliced from a cached original)
+    /// is counted once, with the first of them in tier order, so the sizes add up to
+    /// [`bytes_used`](Self::bytes_used).
+    pub fn entry_sizes(&self) -> Vec<CacheEntry> {
+        // How many cache entries hold each buffer, to tell them from outside references
+        let mut holders: HashMap<*const f32, usize> = HashMap::new();
+        for entry in self.entries() {
+            *holders.entry(buffer_id(&entry.audio)).or_default() += 1;
         }
+
+        let originals = self
+            .originals
+            .iter()
+            .map(|(hash, entry)| (CacheTier::Original, *hash, entry));
+        let resampled = self
+            .resampled
+            .iter()
+            .map(|((hash, _), entry)| (CacheTier::Resampled, *hash, entry));
+        let regions = self
+            .regions
+            .iter()
+            .map(|((hash, ..), entry)| (CacheTier::Region, *hash, entry));
+
+        let mut seen = std::collections::HashSet::new();
+        originals
+            .chain(resampled)
+            .chain(regions)
+            .map(|(tier, hash, entry)| {
+                let id = buffer_id(&entry.audio);
+                CacheEntry {
+                    tier,
+                    path: self.paths.get(&hash).cloned(),
+                    sample_rate: entry.audio.sample_rate(),
+                    channels: entry.audio.channels(),
+                    frames: entry.audio.frames(),
+                    bytes: if seen.insert(id) {
+                        buffer_bytes(&entry.audio)
+                    } else {
+                        0
+                    },
+                    in_use: Arc::strong_count(entry.audio.samples_arc()) > holders[&id],
+                }
+            })
+            .collect()
+    }
+
+    /// The `n` entries holding the most bytes, largest first.
+    pub fn largest_entries(&self, n: usize) -> Vec<CacheEntry> {
+        let mut entries = self.entry_sizes();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.bytes));
+        entries.truncate(n);
+        entries
     }
 }
 
@@ -463,8 +1254,45 @@ pub struct CacheStats {
     pub originals: usize,
     /// Number of resampled versions cached
     pub resampled: usize,
+    /// Number of partially decoded regions cached
+    pub regions: usize,
     /// Total number of cache entries
     pub total: usize,
+    /// Bytes of sample data held by the cache (see [`AudioCache::bytes_used`])
+    pub bytes: usize,
+    /// Bytes held by originals
+    pub original_bytes: usize,
+    /// Bytes held by resampled versions
+    pub resampled_bytes: usize,
+    /// Bytes held by regions, besides those sliced from a cached original
+    pub region_bytes: usize,
+}
+
+/// The tiers of an [`AudioCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheTier {
+    /// Decoded audio at its own sample rate
+    Original,
+    /// An original resampled to another rate
+    Resampled,
+    /// Part of a file decoded on its own
+    Region,
+}
+
+/// One entry of an [`AudioCache`] and its size (see [`AudioCache::entry_sizes`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheEntry {
+    pub tier: CacheTier,
+    /// The file the audio was loaded from
+    pub path: Option<PathBuf>,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub frames: usize,
+    /// Bytes of sample data, 0 when the buffer is counted with another entry
+    pub bytes: usize,
+    /// Whether the audio is referenced outside the cache, by a clip say. Such
+    /// entries are never evicted, since dropping them frees nothing.
+    pub in_use: bool,
 }
 
 /// Hash a file path for use as a cache key.
@@ -477,45 +1305,43 @@ fn hash_path(path: &Path) -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::f32::consts::PI;
+    use crate::test_wav::create_test_wav;
     use std::sync::Arc;
     use tempfile::TempDir;
 
-    /// Helper: Create a test WAV file with a sine wave
-    fn create_test_wav(
-        path: &Path,
-        frequency: f32,
-        sample_rate: u32,
-        duration_secs: f32,
-        channels: u16,
-    ) {
-        let num_frames = (sample_rate as f32 * duration_secs) as usize;
-        let mut samples = Vec::with_capacity(num_frames * channels as usize);
-
-        for i in 0..num_frames {
-            let t = i as f32 / sample_rate as f32;
-            let sample = (2.0 * PI * frequency * t).sin() * 0.5; // 0.5 amplitude
-            for _ in 0..channels {
-                samples.push(sample);
-            }
+    /// Frames in each block of the files written by [`write_ima_adpcm_wav`]
+    const ADPCM_BLOCK_FRAMES: u64 = 65;
+    /// Blocks the WAV reader puts in one packet at [`ADPCM_BLOCK_FRAMES`]
+    const ADPCM_BLOCKS_PER_PACKET: usize = 17;
+
+    /// Helper: Write a mono IMA ADPCM WAV of `blocks` quiet blocks, giving
+    /// `damaged` an out of range step index so it fails to decode
+    fn write_ima_adpcm_wav(path: &Path, blocks: usize, damaged: Option<usize>) {
+        let block_align = 36u16;
+        let mut data = Vec::new();
+        for block in 0..blocks {
+            let step_index = if Some(block) == damaged { 200 } else { 0 };
+            data.extend_from_slice(&[0, 0, step_index, 0]);
+            data.extend_from_slice(&[0; 32]);
         }
 
-        // Convert to i16 PCM
-        let pcm_samples: Vec<i16> = samples.iter().map(|&s| (s * 32767.0) as i16).collect();
-
-        // Write WAV file using hound
-        let spec = hound::WavSpec {
-            channels,
-            sample_rate,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        };
-
-        let mut writer = hound::WavWriter::create(path, spec).unwrap();
-        for &sample in &pcm_samples {
-            writer.write_sample(sample).unwrap();
-        }
-        writer.finalize().unwrap();
+        let mut file = Vec::new();
+        file.extend_from_slice(b"RIFF");
+        file.extend_from_slice(&(4 + 8 + 20 + 8 + data.len() as u32).to_le_bytes());
+        file.extend_from_slice(b"WAVEfmt ");
+        file.extend_from_slice(&20u32.to_le_bytes());
+        file.extend_from_slice(&0x11u16.to_le_bytes()); // IMA ADPCM
+        file.extend_from_slice(&1u16.to_le_bytes());
+        file.extend_from_slice(&44100u32.to_le_bytes());
+        file.extend_from_slice(&(44100 * block_align as u32 / 65).to_le_bytes());
+        file.extend_from_slice(&block_align.to_le_bytes());
+        file.extend_from_slice(&4u16.to_le_bytes());
+        file.extend_from_slice(&2u16.to_le_bytes());
+        file.extend_from_slice(&(ADPCM_BLOCK_FRAMES as u16).to_le_bytes());
+        file.extend_from_slice(b"data");
+        file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        file.extend_from_slice(&data);
+        std::fs::write(path, file).unwrap();
     }
 
     #[test]
@@ -532,6 +1358,46 @@ mod tests {
         assert!(audio.frames() > 0);
     }
 
+    #[test]
+    fn test_lossy_decode_silences_damaged_packets() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("damaged.wav");
+        let blocks = ADPCM_BLOCKS_PER_PACKET * 3;
+        write_ima_adpcm_wav(&wav_path, blocks, Some(ADPCM_BLOCKS_PER_PACKET + 3));
+
+        assert!(decode_audio_arc_direct(&wav_path, None).is_err());
+
+        let (audio, issues) = decode_audio_arc_lossy(&wav_path).unwrap();
+        let packet_frames = ADPCM_BLOCKS_PER_PACKET as u64 * ADPCM_BLOCK_FRAMES;
+        // The damaged packet is replaced by silence, keeping the rest in time
+        assert_eq!(audio.frames() as u64, blocks as u64 * ADPCM_BLOCK_FRAMES);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].frame, packet_frames);
+        assert_eq!(issues[0].frames, packet_frames);
+        assert!(issues[0].error.contains("step index"));
+    }
+
+    #[test]
+    fn test_lossy_decode_of_intact_file_has_no_issues() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("intact.wav");
+        write_ima_adpcm_wav(&wav_path, ADPCM_BLOCKS_PER_PACKET * 2, None);
+
+        let strict = decode_audio_arc_direct(&wav_path, None).unwrap();
+        let (audio, issues) = decode_audio_arc_lossy(&wav_path).unwrap();
+        assert!(issues.is_empty());
+        assert_eq!(audio.samples(), strict.samples());
+    }
+
+    #[test]
+    fn test_lossy_decode_fails_when_nothing_decodes() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("ruined.wav");
+        write_ima_adpcm_wav(&wav_path, 1, Some(0));
+
+        assert!(decode_audio_arc_lossy(&wav_path).is_err());
+    }
+
     #[test]
     fn test_decode_audio_arc_with_resample() {
         let temp_dir = TempDir::new().unwrap();
@@ -692,6 +1558,39 @@ mod tests {
         assert_eq!(stats.total, 3);
     }
 
+    #[test]
+    fn test_audio_cache_byte_stats() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("test.wav");
+        create_test_wav(&wav_path, 440.0, 44100, 0.1, 2);
+
+        let mut cache = AudioCache::new();
+        let original = cache.get_or_load_direct(&wav_path, None).unwrap();
+        cache.get_or_load_direct(&wav_path, Some(96000)).unwrap();
+        // Sliced from the cached original, so it holds no bytes of its own
+        cache
+            .get_or_load_region_direct(&wav_path, 0, 100, None)
+            .unwrap();
+
+        let stats = cache.stats();
+        assert_eq!(stats.original_bytes, 4410 * 2 * 4);
+        assert_eq!(stats.resampled_bytes, 9600 * 2 * 4);
+        assert_eq!(stats.region_bytes, 0);
+        assert_eq!(stats.bytes, cache.bytes_used());
+
+        let largest = cache.largest_entries(2);
+        assert_eq!(largest.len(), 2);
+        assert_eq!(largest[0].tier, CacheTier::Resampled);
+        assert_eq!(largest[0].sample_rate, 96000);
+        assert_eq!(largest[0].path.as_deref(), Some(wav_path.as_path()));
+        assert!(!largest[0].in_use);
+        // The original is still referenced here
+        assert_eq!(largest[1].tier, CacheTier::Original);
+        assert!(largest[1].in_use);
+        drop(original);
+        assert_eq!(cache.entry_sizes().len(), 3);
+    }
+
     #[test]
     fn test_audio_cache_repeated_loads_cheap() {
         let temp_dir = TempDir::new().unwrap();
@@ -712,6 +1611,110 @@ mod tests {
         assert_eq!(Arc::strong_count(audio3.samples_arc()), 4);
     }
 
+    #[test]
+    fn test_audio_cache_shares_identical_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let samples = temp_dir.path().join("kick.wav");
+        let project_copy = temp_dir.path().join("project_kick.wav");
+        create_test_wav(&samples, 60.0, 44100, 0.1, 2);
+        std::fs::copy(&samples, &project_copy).unwrap();
+
+        let mut cache = AudioCache::new();
+        let a = cache.get_or_load(&samples, Some(48000)).unwrap();
+        let b = cache.get_or_load(&project_copy, Some(48000)).unwrap();
+
+        assert_eq!(cache.originals_count(), 1);
+        assert_eq!(cache.resampled_count(), 1);
+        assert!(Arc::ptr_eq(a.samples_arc(), b.samples_arc()));
+    }
+
+    #[test]
+    fn test_audio_cache_reloads_changed_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("test.wav");
+        create_test_wav(&wav_path, 440.0, 44100, 0.1, 2);
+
+        let mut cache = AudioCache::new();
+        let before = cache.get_or_load(&wav_path, None).unwrap();
+
+        // A different length changes the fingerprint even within the same mtime tick
+        create_test_wav(&wav_path, 440.0, 44100, 0.2, 2);
+        let after = cache.get_or_load(&wav_path, None).unwrap();
+
+        assert_eq!(after.frames(), before.frames() * 2);
+        assert_eq!(cache.originals_count(), 2);
+    }
+
+    #[test]
+    fn test_audio_cache_reports_changed_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("test.wav");
+        let other = temp_dir.path().join("other.wav");
+        create_test_wav(&wav_path, 440.0, 44100, 0.1, 2);
+        create_test_wav(&other, 440.0, 44100, 0.1, 2);
+
+        let mut cache = AudioCache::new();
+        assert!(!cache.has_changed(&wav_path));
+        cache.get_or_load_direct(&wav_path, None).unwrap();
+        assert!(cache.changed_files().is_empty());
+
+        // Files never loaded aren't reported, however they change
+        create_test_wav(&wav_path, 440.0, 44100, 0.2, 2);
+        create_test_wav(&other, 440.0, 44100, 0.2, 2);
+        assert_eq!(cache.changed_files(), vec![wav_path.clone()]);
+
+        cache.get_or_load_direct(&wav_path, None).unwrap();
+        assert!(!cache.has_changed(&wav_path));
+
+        // A deleted file can't be reloaded, so it isn't reported either
+        std::fs::remove_file(&wav_path).unwrap();
+        assert!(!cache.has_changed(&wav_path));
+    }
+
+    #[test]
+    fn test_memory_budget_evicts_least_recently_used() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.wav");
+        let b = temp_dir.path().join("b.wav");
+        create_test_wav(&a, 440.0, 44100, 0.1, 2);
+        create_test_wav(&b, 220.0, 44100, 0.1, 2);
+
+        let mut cache = AudioCache::new();
+        cache.get_or_load(&a, Some(48000)).unwrap();
+        cache.get_or_load(&b, Some(48000)).unwrap();
+        // Using `a` again makes `b` the least recently used
+        cache.get_or_load(&a, Some(48000)).unwrap();
+
+        let before = cache.stats();
+        assert_eq!(before.bytes, cache.bytes_used());
+        cache.set_memory_budget(Some(before.bytes - 1));
+
+        // Only b's resampled version had to go; the originals are kept
+        assert_eq!(cache.resampled_count(), 1);
+        assert_eq!(cache.originals_count(), 2);
+        cache.get_or_load(&a, Some(48000)).unwrap();
+        assert_eq!(cache.resampled_count(), 1);
+    }
+
+    #[test]
+    fn test_memory_budget_keeps_referenced_audio() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("test.wav");
+        create_test_wav(&wav_path, 440.0, 44100, 0.1, 2);
+
+        let mut cache = AudioCache::new().with_memory_budget(0);
+        let audio = cache.get_or_load(&wav_path, None).unwrap();
+
+        // Still used, so evicting it wouldn't free anything
+        assert_eq!(cache.originals_count(), 1);
+        assert_eq!(cache.bytes_used(), audio.len() * std::mem::size_of::<f32>());
+
+        drop(audio);
+        cache.set_memory_budget(Some(0));
+        assert_eq!(cache.total_count(), 0);
+        assert_eq!(cache.bytes_used(), 0);
+    }
+
     #[test]
     fn test_audio_cache_missing_file() {
         let mut cache = AudioCache::new();
@@ -720,22 +1723,100 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_audio_cache_stereo_layout() {
+        let temp_dir = TempDir::new().unwrap();
+        let mono_path = temp_dir.path().join("mono.wav");
+        let surround_path = temp_dir.path().join("surround.wav");
+        let disk = DiskCache::new(temp_dir.path().join("cache"), u64::MAX);
+        create_test_wav(&mono_path, 440.0, 44100, 0.1, 1);
+        create_test_wav(&surround_path, 220.0, 44100, 0.1, 6);
+
+        let mut cache = AudioCache::new()
+            .with_disk_cache(disk.clone())
+            .with_channel_layout(ChannelLayout::Stereo);
+        let mono = cache.get_or_load_direct(&mono_path, Some(48000)).unwrap();
+        assert_eq!(mono.channels(), 2);
+        assert_eq!(mono.sample_rate(), 48000);
+        let surround = cache.get_or_load_direct(&surround_path, None).unwrap();
+        assert_eq!(surround.channels(), 2);
+        let region = cache
+            .get_or_load_region_direct(&mono_path, 0, 100, None)
+            .unwrap();
+        assert_eq!(region.channels(), 2);
+
+        // Stereo versions are kept on disk apart from the files' own layouts
+        assert!(disk.get(&mono_path, None).is_none());
+        let native = AudioCache::new()
+            .with_disk_cache(disk)
+            .get_or_load_direct(&mono_path, Some(48000))
+            .unwrap();
+        assert_eq!(native.channels(), 1);
+
+        cache.set_channel_layout(ChannelLayout::Native);
+        assert_eq!(cache.originals_count(), 0);
+        let surround = cache.get_or_load_direct(&surround_path, None).unwrap();
+        assert_eq!(surround.channels(), 6);
+    }
+
+    #[test]
+    fn test_audio_cache_lossy_decoding_keeps_issues() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("damaged.wav");
+        let disk = DiskCache::new(temp_dir.path().join("cache"), u64::MAX);
+        write_ima_adpcm_wav(&wav_path, ADPCM_BLOCKS_PER_PACKET * 2, Some(0));
+
+        let mut cache = AudioCache::new().with_disk_cache(disk.clone());
+        assert!(cache.get_or_load_direct(&wav_path, None).is_err());
+
+        cache.set_lossy_decoding(true);
+        let audio = cache.get_or_load_direct(&wav_path, Some(48000)).unwrap();
+        assert_eq!(audio.sample_rate(), 48000);
+        assert_eq!(cache.decode_issues(&wav_path).len(), 1);
+        assert_eq!(cache.decode_issues(&wav_path)[0].frame, 0);
+        // Damaged audio isn't kept on disk, so it's reported again next time
+        assert!(disk.get(&wav_path, None).is_none());
+
+        let mut cache = AudioCache::new().with_lossy_decoding();
+        let pending = cache.get_or_load_async(&wav_path, None, |_| {});
+        cache.finish_load(pending).unwrap();
+        assert_eq!(cache.decode_issues(&wav_path).len(), 1);
+
+        cache.clear();
+        assert!(cache.decode_issues(&wav_path).is_empty());
+    }
+
     #[test]
     fn test_cache_stats_equality() {
         let stats1 = CacheStats {
             originals: 5,
             resampled: 10,
+            regions: 0,
             total: 15,
+            bytes: 0,
+            original_bytes: 0,
+            resampled_bytes: 0,
+            region_bytes: 0,
         };
         let stats2 = CacheStats {
             originals: 5,
             resampled: 10,
+            regions: 0,
             total: 15,
+            bytes: 0,
+            original_bytes: 0,
+            resampled_bytes: 0,
+            region_bytes: 0,
         };
         let stats3 = CacheStats {
             originals: 3,
             resampled: 10,
+            regions: 0,
             total: 13,
+            bytes: 0,
+            original_bytes: 0,
+            resampled_bytes: 0,
+            region_bytes: 0,
         };
 
         assert_eq!(stats1, stats2);
@@ -747,4 +1828,79 @@ mod tests {
         let cache = AudioCache::default();
         assert_eq!(cache.total_count(), 0);
     }
+
+    #[test]
+    fn test_decode_region_matches_full_decode() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("long.wav");
+
+        create_test_wav(&wav_path, 440.0, 44100, 1.0, 2);
+
+        let full = decode_audio_arc_direct(&wav_path, None).unwrap();
+        let region = decode_region_direct(&wav_path, 30000, 1000, None).unwrap();
+
+        assert_eq!(region.frames(), 1000);
+        assert_eq!(region.samples(), full.slice(30000..31000).samples());
+    }
+
+    #[test]
+    fn test_decode_region_stops_at_end_of_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("short.wav");
+
+        create_test_wav(&wav_path, 440.0, 44100, 0.1, 1);
+
+        let region = decode_region_direct(&wav_path, 4000, 10000, None).unwrap();
+        assert_eq!(region.frames(), 4410 - 4000);
+    }
+
+    #[test]
+    fn test_audio_cache_region() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("test.wav");
+
+        create_test_wav(&wav_path, 440.0, 44100, 0.5, 2);
+
+        let mut cache = AudioCache::new();
+
+        // Decoded on its own, without loading the whole file
+        let region = cache
+            .get_or_load_region_direct(&wav_path, 1000, 500, None)
+            .unwrap();
+        assert_eq!(region.frames(), 500);
+        assert_eq!(cache.originals_count(), 0);
+        assert_eq!(cache.regions_count(), 1);
+
+        let again = cache
+            .get_or_load_region_direct(&wav_path, 1000, 500, None)
+            .unwrap();
+        assert!(Arc::ptr_eq(region.samples_arc(), again.samples_arc()));
+
+        // Once the whole file is loaded, new regions share its samples
+        let original = cache.get_or_load_direct(&wav_path, None).unwrap();
+        let sliced = cache
+            .get_or_load_region_direct(&wav_path, 2000, 500, None)
+            .unwrap();
+        assert!(Arc::ptr_eq(original.samples_arc(), sliced.samples_arc()));
+        assert_eq!(sliced.samples(), original.slice(2000..2500).samples());
+    }
+
+    #[test]
+    fn test_audio_cache_disk_tier() {
+        let temp_dir = TempDir::new().unwrap();
+        let wav_path = temp_dir.path().join("test.wav");
+        let disk = DiskCache::new(temp_dir.path().join("cache"), u64::MAX);
+
+        create_test_wav(&wav_path, 440.0, 44100, 0.1, 2);
+
+        let mut cache = AudioCache::new().with_disk_cache(disk.clone());
+        let first = cache.get_or_load_direct(&wav_path, Some(48000)).unwrap();
+        assert!(disk.get(&wav_path, None).is_some());
+        assert!(disk.get(&wav_path, Some(48000)).is_some());
+
+        // A fresh cache, as on the next project open, reads both tiers back from disk
+        let mut cache = AudioCache::new().with_disk_cache(disk);
+        let second = cache.get_or_load_direct(&wav_path, Some(48000)).unwrap();
+        assert_eq!(second.samples(), first.samples());
+    }
 }
@@ -0,0 +1,373 @@
+//! Finding the audio files in a set of sample folders.
+//!
+//! [`SampleLibrary`] walks its root folders for files symphonia can decode and keeps
+//! what it found, so a sample browser can list them without touching the disk. A
+//! rescan only reads folders that changed since the last one: a folder's listing is
+//! reused while its modification time stays the same, since adding, removing or
+//! renaming anything directly inside it changes that time.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+use symphonia::core::probe::QueryDescriptor;
+use symphonia::default::formats::{FlacReader, MkvReader, OggReader, WavReader};
+
+/// File extensions of the formats symphonia can read, in lowercase
+pub fn supported_extensions() -> &'static [&'static str] {
+    static EXTENSIONS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    EXTENSIONS.get_or_init(|| {
+        let mut extensions: Vec<&'static str> = [
+            FlacReader::query(),
+            MkvReader::query(),
+            OggReader::query(),
+            WavReader::query(),
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|descriptor| descriptor.extensions.iter().copied())
+        .collect();
+        extensions.sort_unstable();
+        extensions.dedup();
+        extensions
+    })
+}
+
+/// Whether `path` has the extension of a format symphonia can read
+pub fn is_supported_audio(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            supported_extensions()
+                .iter()
+                .any(|supported| supported.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// An audio file found by a [`SampleLibrary`] scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibrarySample {
+    /// Where the file is
+    pub path: PathBuf,
+    /// The root folder it was found under
+    pub root: PathBuf,
+}
+
+impl LibrarySample {
+    /// The file's path inside its root folder
+    pub fn relative_path(&self) -> &Path {
+        self.path.strip_prefix(&self.root).unwrap_or(&self.path)
+    }
+
+    /// The file name without its extension, for display
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// What a [`SampleLibrary::scan`] found different from the scan before it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LibraryChanges {
+    /// Files that weren't there before
+    pub added: Vec<PathBuf>,
+    /// Files that are gone
+    pub removed: Vec<PathBuf>,
+}
+
+impl LibraryChanges {
+    /// Whether the scan found nothing new or missing
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Listing of one folder as of its last read
+#[derive(Debug, Clone)]
+struct Folder {
+    modified: SystemTime,
+    files: Vec<PathBuf>,
+    folders: Vec<PathBuf>,
+}
+
+/// The audio files under a set of root folders.
+///
+/// Nothing is read until [`scan`](Self::scan) is called; after that the results are
+/// kept until the next scan. Hidden files and folders (starting with `.`) are skipped,
+/// and symlinked folders are not followed.
+///
+/// # Examples
+///
+/// ```no_run
+/// use daw_decode::SampleLibrary;
+///
+/// let mut library = SampleLibrary::new(["samples", "/Volumes/Sounds"]);
+/// library.scan();
+/// for sample in library.samples() {
+///     println!("{}", sample.relative_path().display());
+/// }
+///
+/// // Later, pick up whatever changed on disk
+/// let changes = library.scan();
+/// println!("{} new, {} gone", changes.added.len(), changes.removed.len());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SampleLibrary {
+    roots: Vec<PathBuf>,
+    /// Every folder seen by the last scan, by path
+    folders: BTreeMap<PathBuf, Folder>,
+    /// Every file found by the last scan, sorted by path
+    samples: BTreeMap<PathBuf, LibrarySample>,
+}
+
+impl SampleLibrary {
+    /// A library over the given root folders, not scanned yet
+    pub fn new<P: Into<PathBuf>>(roots: impl IntoIterator<Item = P>) -> Self {
+        Self {
+            roots: roots.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// The root folders, in the order they were added
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Add a root folder, which is searched on the next scan
+    pub fn add_root(&mut self, root: impl Into<PathBuf>) {
+        let root = root.into();
+        if !self.roots.contains(&root) {
+            self.roots.push(root);
+        }
+    }
+
+    /// Remove a root folder; its files drop out on the next scan
+    pub fn remove_root(&mut self, root: &Path) {
+        self.roots.retain(|existing| existing != root);
+    }
+
+    /// Files found by the last scan, sorted by path
+    pub fn samples(&self) -> impl Iterator<Item = &LibrarySample> {
+        self.samples.values()
+    }
+
+    /// Number of files found by the last scan
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether the last scan found no files (or there hasn't been one)
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The file at `path`, if the last scan found it
+    pub fn get(&self, path: &Path) -> Option<&LibrarySample> {
+        self.samples.get(path)
+    }
+
+    /// Walk the root folders again and report what changed.
+    ///
+    /// Folders unchanged since the last scan aren't listed again. Roots that don't
+    /// exist or can't be read contribute nothing. A file under two roots belongs to
+    /// the first one.
+    pub fn scan(&mut self) -> LibraryChanges {
+        let mut folders = BTreeMap::new();
+        let mut samples = BTreeMap::new();
+        for root in &self.roots {
+            scan_folder(root, root, &self.folders, &mut folders, &mut samples);
+        }
+
+        let changes = LibraryChanges {
+            added: samples
+                .keys()
+                .filter(|path| !self.samples.contains_key(*path))
+                .cloned()
+                .collect(),
+            removed: self
+                .samples
+                .keys()
+                .filter(|path| !samples.contains_key(*path))
+                .cloned()
+                .collect(),
+        };
+        self.folders = folders;
+        self.samples = samples;
+        changes
+    }
+}
+
+/// Collect the audio files under `dir` into `samples`, reusing listings from
+/// `previous` for folders that haven't changed
+fn scan_folder(
+    root: &Path,
+    dir: &Path,
+    previous: &BTreeMap<PathBuf, Folder>,
+    folders: &mut BTreeMap<PathBuf, Folder>,
+    samples: &mut BTreeMap<PathBuf, LibrarySample>,
+) {
+    if folders.contains_key(dir) {
+        return;
+    }
+    let Ok(modified) = fs::metadata(dir).and_then(|metadata| metadata.modified()) else {
+        return;
+    };
+    let folder = match previous.get(dir) {
+        Some(folder) if folder.modified == modified => folder.clone(),
+        _ => match read_folder(dir, modified) {
+            Some(folder) => folder,
+            None => return,
+        },
+    };
+
+    for file in &folder.files {
+        samples
+            .entry(file.clone())
+            .or_insert_with(|| LibrarySample {
+                path: file.clone(),
+                root: root.to_path_buf(),
+            });
+    }
+    let subfolders = folder.folders.clone();
+    folders.insert(dir.to_path_buf(), folder);
+    for subfolder in subfolders {
+        scan_folder(root, &subfolder, previous, folders, samples);
+    }
+}
+
+fn read_folder(dir: &Path, modified: SystemTime) -> Option<Folder> {
+    let mut folder = Folder {
+        modified,
+        files: Vec::new(),
+        folders: Vec::new(),
+    };
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            folder.folders.push(path);
+        } else if is_supported_audio(&path) && (file_type.is_file() || path.is_file()) {
+            folder.files.push(path);
+        }
+    }
+    Some(folder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"").unwrap();
+    }
+
+    #[test]
+    fn test_supported_extensions_come_from_symphonia() {
+        for ext in ["wav", "flac", "ogg"] {
+            assert!(supported_extensions().contains(&ext), "{ext}");
+        }
+        assert!(is_supported_audio(Path::new("kick.WAV")));
+        assert!(!is_supported_audio(Path::new("notes.txt")));
+        assert!(!is_supported_audio(Path::new("README")));
+    }
+
+    #[test]
+    fn test_scan_finds_audio_under_roots() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let drums = temp.path().join("drums");
+        let loops = temp.path().join("loops");
+        touch(&drums.join("kick.wav"));
+        touch(&drums.join("cr78/snare.flac"));
+        touch(&drums.join("readme.txt"));
+        touch(&drums.join(".hidden/hat.wav"));
+        touch(&loops.join("pad.ogg"));
+
+        let mut library = SampleLibrary::new([&drums, &loops]);
+        assert!(library.is_empty());
+        let changes = library.scan();
+        assert_eq!(changes.added.len(), 3);
+        assert!(changes.removed.is_empty());
+
+        let relative: Vec<_> = library
+            .samples()
+            .map(|sample| sample.relative_path().to_path_buf())
+            .collect();
+        assert_eq!(
+            relative,
+            vec![
+                PathBuf::from("cr78/snare.flac"),
+                PathBuf::from("kick.wav"),
+                PathBuf::from("pad.ogg"),
+            ]
+        );
+        let pad = library.get(&loops.join("pad.ogg")).unwrap();
+        assert_eq!(
+            (pad.root.as_path(), pad.name().as_str()),
+            (loops.as_path(), "pad")
+        );
+
+        // Removing a root drops its files on the next scan
+        library.remove_root(&loops);
+        let changes = library.scan();
+        assert_eq!(changes.removed, vec![loops.join("pad.ogg")]);
+        assert_eq!(library.len(), 2);
+    }
+
+    #[test]
+    fn test_rescan_picks_up_changes() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().to_path_buf();
+        touch(&root.join("kick.wav"));
+        touch(&root.join("cr78/snare.wav"));
+
+        let mut library = SampleLibrary::new([&root]);
+        library.scan();
+        assert!(library.scan().is_empty());
+
+        touch(&root.join("cr78/clap.wav"));
+        fs::remove_file(root.join("kick.wav")).unwrap();
+        touch(&root.join("new/tom.wav"));
+
+        let changes = library.scan();
+        assert_eq!(
+            changes.added,
+            vec![root.join("cr78/clap.wav"), root.join("new/tom.wav")]
+        );
+        assert_eq!(changes.removed, vec![root.join("kick.wav")]);
+        assert_eq!(library.len(), 3);
+    }
+
+    #[test]
+    fn test_unchanged_folders_are_not_listed_again() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let root = temp.path().to_path_buf();
+        touch(&root.join("kick.wav"));
+
+        let mut library = SampleLibrary::new([&root]);
+        library.scan();
+
+        // Plant a file in the cached listing; a rescan that reused the listing keeps it
+        let folder = library.folders.get_mut(&root).unwrap();
+        folder.files.push(root.join("cached.wav"));
+        library.scan();
+        assert!(library.get(&root.join("cached.wav")).is_some());
+
+        // Once the folder changes it is listed afresh
+        let folder = library.folders.get_mut(&root).unwrap();
+        folder.modified = SystemTime::UNIX_EPOCH;
+        library.scan();
+        assert!(library.get(&root.join("cached.wav")).is_none());
+        assert!(library.get(&root.join("kick.wav")).is_some());
+    }
+}
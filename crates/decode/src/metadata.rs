@@ -0,0 +1,403 @@
+//! Reading facts about an audio file without decoding its samples.
+//!
+//! A sample browser or import dialog wants the length, format and any tempo, key or
+//! markers embedded in a file for many files at once. [`probe_metadata`] gets them
+//! from the container's headers and tags, reading only a few kilobytes of each file.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use daw_transport::MusicalKey;
+use symphonia::core::formats::{Cue, FormatOptions};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey, Tag, Value};
+use symphonia::core::probe::Hint;
+
+use crate::resolve_sample_path;
+
+/// Tag names that hold the musical key in the formats we read (ID3, Vorbis comments,
+/// MP4), compared case-insensitively
+const KEY_TAGS: [&str; 4] = [
+    "TKEY",
+    "INITIALKEY",
+    "KEY",
+    "----:com.apple.iTunes:initialkey",
+];
+
+/// What [`probe_metadata`] found out about an audio file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioMetadata {
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Bits per stored sample, for PCM and lossless formats
+    pub bits_per_sample: Option<u32>,
+    /// Length in frames, if the container states it
+    pub frames: Option<u64>,
+    /// Tempo from the file's tags, or a WAV file's ACID chunk
+    pub bpm: Option<f64>,
+    /// Key from the file's tags
+    pub key: Option<MusicalKey>,
+    /// Cue points, in order of position
+    pub markers: Vec<AudioMarker>,
+}
+
+/// A cue point embedded in an audio file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AudioMarker {
+    /// Position in frames from the start of the file
+    pub frame: u64,
+    pub label: Option<String>,
+}
+
+impl AudioMetadata {
+    /// Length of the audio, if the container states it
+    pub fn duration(&self) -> Option<Duration> {
+        let frames = self.frames?;
+        Some(Duration::from_secs_f64(
+            frames as f64 / self.sample_rate as f64,
+        ))
+    }
+}
+
+/// Read an audio file's format, length and embedded tags without decoding it.
+///
+/// Resolves the path like [`decode_audio_arc`](crate::decode_audio_arc).
+pub fn probe_metadata(path: &Path) -> anyhow::Result<AudioMetadata> {
+    let resolved = resolve_sample_path(path)
+        .ok_or_else(|| anyhow::anyhow!("sample not found: {}", path.display()))?;
+    probe_metadata_direct(&resolved)
+}
+
+/// Read an audio file's format, length and embedded tags without decoding it or
+/// resolving its path.
+///
+/// # Examples
+///
+/// ```no_run
+/// use daw_decode::probe_metadata_direct;
+/// use std::path::Path;
+///
+/// let metadata = probe_metadata_direct(Path::new("/samples/loop_124bpm.wav")).unwrap();
+/// if let (Some(duration), Some(bpm)) = (metadata.duration(), metadata.bpm) {
+///     println!("{:.1}s at {} BPM", duration.as_secs_f64(), bpm);
+/// }
+/// ```
+pub fn probe_metadata_direct(path: &Path) -> anyhow::Result<AudioMetadata> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let mut probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let format = &mut probed.format;
+
+    let track = format
+        .default_track()
+        .ok_or_else(|| anyhow::anyhow!("no default track"))?;
+    let params = &track.codec_params;
+
+    let mut metadata = AudioMetadata {
+        sample_rate: params.sample_rate.unwrap_or(44100),
+        channels: params.channels.map(|c| c.count()).unwrap_or(2) as u16,
+        bits_per_sample: params.bits_per_sample,
+        frames: params.n_frames,
+        bpm: None,
+        key: None,
+        markers: markers_from_cues(format.cues()),
+    };
+
+    // Tags can sit before the container (ID3) or inside it; the container's win
+    let mut revisions: Vec<MetadataRevision> = Vec::new();
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        revisions.push(revision.clone());
+    }
+    if let Some(revision) = format.metadata().current() {
+        revisions.push(revision.clone());
+    }
+    for tag in revisions.iter().flat_map(|revision| revision.tags()) {
+        if let Some(bpm) = bpm_from_tag(tag) {
+            metadata.bpm = Some(bpm);
+        }
+        if let Some(key) = key_from_tag(tag) {
+            metadata.key = Some(key);
+        }
+    }
+
+    // Symphonia doesn't read WAV cue or ACID chunks
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+        && let Ok(riff) = read_riff_extras(path)
+    {
+        metadata.bpm = metadata.bpm.or(riff.bpm);
+        if metadata.markers.is_empty() {
+            metadata.markers = riff.markers;
+        }
+    }
+
+    Ok(metadata)
+}
+
+fn markers_from_cues(cues: &[Cue]) -> Vec<AudioMarker> {
+    let mut markers: Vec<_> = cues
+        .iter()
+        .map(|cue| AudioMarker {
+            frame: cue.start_ts,
+            label: cue
+                .tags
+                .iter()
+                .find(|tag| tag.std_key == Some(StandardTagKey::TrackTitle))
+                .map(|tag| tag.value.to_string()),
+        })
+        .collect();
+    markers.sort_by_key(|marker| marker.frame);
+    markers
+}
+
+fn bpm_from_tag(tag: &Tag) -> Option<f64> {
+    if tag.std_key != Some(StandardTagKey::Bpm) {
+        return None;
+    }
+    let bpm = match &tag.value {
+        Value::Float(bpm) => *bpm,
+        Value::UnsignedInt(bpm) => *bpm as f64,
+        Value::SignedInt(bpm) => *bpm as f64,
+        value => value.to_string().trim().parse().ok()?,
+    };
+    (bpm > 0.0).then_some(bpm)
+}
+
+fn key_from_tag(tag: &Tag) -> Option<MusicalKey> {
+    if !KEY_TAGS
+        .iter()
+        .any(|name| tag.key.eq_ignore_ascii_case(name))
+    {
+        return None;
+    }
+    let name = tag.value.to_string();
+    let name = name.trim();
+    // `MusicalKey::parse` rejects a bare note, but in a key tag it means major
+    MusicalKey::parse(name).or_else(|| MusicalKey::parse(&format!("{}maj", name)))
+}
+
+/// Extras in a WAV file's RIFF chunks
+#[derive(Debug, Default)]
+struct RiffExtras {
+    bpm: Option<f64>,
+    markers: Vec<AudioMarker>,
+}
+
+/// Walk a WAV file's chunks, reading the `cue `, `LIST`/`adtl` and `acid` chunks and
+/// skipping over everything else, including the audio.
+fn read_riff_extras(path: &Path) -> std::io::Result<RiffExtras> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..] != b"WAVE" {
+        return Ok(RiffExtras::default());
+    }
+
+    let mut extras = RiffExtras::default();
+    // Cue point id -> frame, and cue point id -> label
+    let mut cues = Vec::new();
+    let mut labels = Vec::new();
+
+    let mut chunk_header = [0u8; 8];
+    while reader.read_exact(&mut chunk_header).is_ok() {
+        let id = &chunk_header[..4];
+        let len = u32_le(&chunk_header[4..]) as u64;
+        let padded = len + len % 2;
+        match id {
+            b"cue " | b"acid" | b"LIST" => {
+                let mut body = vec![0u8; len as usize];
+                reader.read_exact(&mut body)?;
+                reader.seek_relative((padded - len) as i64)?;
+                match id {
+                    b"cue " => cues = parse_cue_chunk(&body),
+                    b"acid" => extras.bpm = parse_acid_chunk(&body),
+                    _ => labels.extend(parse_adtl_labels(&body)),
+                }
+            }
+            _ => {
+                reader.seek(SeekFrom::Current(padded as i64))?;
+            }
+        }
+    }
+
+    extras.markers = cues
+        .into_iter()
+        .map(|(id, frame)| AudioMarker {
+            frame,
+            label: labels
+                .iter()
+                .find(|(label_id, _)| *label_id == id)
+                .map(|(_, label)| label.clone()),
+        })
+        .collect();
+    extras.markers.sort_by_key(|marker| marker.frame);
+    Ok(extras)
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Cue point ids and their sample offsets
+fn parse_cue_chunk(body: &[u8]) -> Vec<(u32, u64)> {
+    const POINT_LEN: usize = 24;
+    if body.len() < 4 {
+        return Vec::new();
+    }
+    body[4..]
+        .chunks_exact(POINT_LEN)
+        .take(u32_le(body) as usize)
+        .map(|point| (u32_le(&point[..4]), u32_le(&point[20..24]) as u64))
+        .collect()
+}
+
+/// Tempo stored by loop libraries in the ACID chunk
+fn parse_acid_chunk(body: &[u8]) -> Option<f64> {
+    let bytes = body.get(20..24)?;
+    let tempo = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64;
+    (tempo.is_finite() && tempo > 0.0).then_some(tempo)
+}
+
+/// Cue point labels from an `adtl` list
+fn parse_adtl_labels(body: &[u8]) -> Vec<(u32, String)> {
+    let mut labels = Vec::new();
+    let Some(mut rest) = body.strip_prefix(b"adtl") else {
+        return labels;
+    };
+    while rest.len() >= 8 {
+        let len = u32_le(&rest[4..8]) as usize;
+        let Some(sub) = rest.get(8..8 + len) else {
+            break;
+        };
+        if &rest[..4] == b"labl" && sub.len() >= 4 {
+            let text = sub[4..].split(|&byte| byte == 0).next().unwrap_or_default();
+            labels.push((u32_le(sub), String::from_utf8_lossy(text).into_owned()));
+        }
+        rest = rest.get(8 + len + len % 2..).unwrap_or_default();
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::write_silent_wav;
+
+    /// Append a chunk to a WAV file, keeping the RIFF size in step
+    fn append_chunk(path: &Path, id: &[u8; 4], body: &[u8]) {
+        let mut bytes = std::fs::read(path).unwrap();
+        bytes.extend_from_slice(id);
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(body);
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        let riff_len = (bytes.len() - 8) as u32;
+        bytes[4..8].copy_from_slice(&riff_len.to_le_bytes());
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_probe_format_and_length() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("pad.wav");
+        write_silent_wav(&path, 2, 48000, 24, 24000);
+
+        let metadata = probe_metadata_direct(&path).unwrap();
+        assert_eq!(metadata.sample_rate, 48000);
+        assert_eq!(metadata.channels, 2);
+        assert_eq!(metadata.bits_per_sample, Some(24));
+        assert_eq!(metadata.frames, Some(24000));
+        assert_eq!(metadata.duration(), Some(Duration::from_millis(500)));
+        assert_eq!((metadata.bpm, metadata.key), (None, None));
+        assert!(metadata.markers.is_empty());
+    }
+
+    #[test]
+    fn test_probe_wav_tempo_and_markers() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("loop.wav");
+        write_silent_wav(&path, 2, 44100, 24, 44100);
+
+        let mut acid = vec![0u8; 24];
+        acid[20..24].copy_from_slice(&124.0f32.to_le_bytes());
+        append_chunk(&path, b"acid", &acid);
+
+        // Two cue points, listed out of order
+        let mut cue = 2u32.to_le_bytes().to_vec();
+        for (id, frame) in [(2u32, 22050u32), (1, 11025)] {
+            cue.extend_from_slice(&id.to_le_bytes());
+            cue.extend_from_slice(&frame.to_le_bytes());
+            cue.extend_from_slice(b"data");
+            cue.extend_from_slice(&[0; 8]);
+            cue.extend_from_slice(&frame.to_le_bytes());
+        }
+        append_chunk(&path, b"cue ", &cue);
+
+        let mut adtl = b"adtl".to_vec();
+        adtl.extend_from_slice(b"labl");
+        adtl.extend_from_slice(&9u32.to_le_bytes());
+        adtl.extend_from_slice(&1u32.to_le_bytes());
+        adtl.extend_from_slice(b"Drop\0");
+        adtl.push(0);
+        append_chunk(&path, b"LIST", &adtl);
+
+        let metadata = probe_metadata_direct(&path).unwrap();
+        assert_eq!(metadata.bpm, Some(124.0));
+        assert_eq!(
+            metadata.markers,
+            vec![
+                AudioMarker {
+                    frame: 11025,
+                    label: Some("Drop".to_string()),
+                },
+                AudioMarker {
+                    frame: 22050,
+                    label: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_key_tags() {
+        let tag = |key: &str, value: &str| Tag::new(None, key, Value::from(value));
+        assert_eq!(
+            key_from_tag(&tag("TKEY", "F#m")),
+            Some(MusicalKey {
+                tonic: 6,
+                minor: true
+            })
+        );
+        assert_eq!(
+            key_from_tag(&tag("initialkey", "C")),
+            Some(MusicalKey {
+                tonic: 0,
+                minor: false
+            })
+        );
+        assert_eq!(key_from_tag(&tag("TITLE", "C")), None);
+        assert_eq!(
+            bpm_from_tag(&Tag::new(
+                Some(StandardTagKey::Bpm),
+                "TBPM",
+                Value::from("128")
+            )),
+            Some(128.0)
+        );
+    }
+}
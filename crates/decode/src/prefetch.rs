@@ -0,0 +1,144 @@
+//! Warming the cache in idle time.
+//!
+//! A [`Prefetcher`] holds files that are likely to be needed soon, such as the
+//! samples of clips just past the playhead or on muted tracks, and decodes them
+//! into an [`AudioCache`] a little at a time, nearest first. The caller decides
+//! what "nearest" means: each request carries a key, and [`Prefetcher::run`]
+//! asks for its distance every time, so the order follows the playhead.
+
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::AudioCache;
+
+/// A queue of files to decode into a cache ahead of time.
+#[derive(Debug, Clone)]
+pub struct Prefetcher<K> {
+    requests: Vec<(PathBuf, K)>,
+    target_sample_rate: Option<u32>,
+}
+
+impl<K> Prefetcher<K> {
+    /// Create an empty prefetcher that warms audio at `target_sample_rate`, or at
+    /// each file's own rate if `None`.
+    pub fn new(target_sample_rate: Option<u32>) -> Self {
+        Self {
+            requests: Vec::new(),
+            target_sample_rate,
+        }
+    }
+
+    /// Queue `path`, tagged with `key` for working out how soon it's needed.
+    pub fn push(&mut self, path: impl Into<PathBuf>, key: K) {
+        self.requests.push((path.into(), key));
+    }
+
+    /// Drop every queued request.
+    pub fn clear(&mut self) {
+        self.requests.clear();
+    }
+
+    /// Number of requests still queued.
+    pub fn pending(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Decode queued files into `cache`, nearest first, until `budget` has passed.
+    ///
+    /// `distance` gives how far each request is from being needed, or `None` if it
+    /// no longer is, which drops it. At least one file is decoded per call, so small
+    /// budgets still make progress, and a file is never interrupted, so the budget can
+    /// be overrun by the last one. Files that fail to decode are dropped; loading them
+    /// for real reports the error. Returns how many files were warmed.
+    pub fn run(
+        &mut self,
+        cache: &mut AudioCache,
+        budget: Duration,
+        mut distance: impl FnMut(&Path, &K) -> Option<u64>,
+    ) -> usize {
+        let started = Instant::now();
+        let mut ranked: Vec<(u64, (PathBuf, K))> = self
+            .requests
+            .drain(..)
+            .filter_map(|(path, key)| distance(&path, &key).map(|d| (d, (path, key))))
+            .collect();
+        // Farthest first, so the nearest pops off the end
+        ranked.sort_by_key(|&(d, _)| Reverse(d));
+
+        let mut warmed = 0;
+        while let Some((_, (path, _))) = ranked.pop() {
+            if cache
+                .get_or_load_direct(&path, self.target_sample_rate)
+                .is_ok()
+            {
+                warmed += 1;
+            }
+            if started.elapsed() >= budget {
+                break;
+            }
+        }
+
+        // What's left waits for the next idle moment
+        self.requests
+            .extend(ranked.into_iter().rev().map(|(_, request)| request));
+        warmed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_wav::write_silent_wav;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_prefetch_warms_nearest_first() {
+        let dir = TempDir::new().unwrap();
+        let near = dir.path().join("near.wav");
+        let far = dir.path().join("far.wav");
+        // Of different lengths, since the cache keeps identical files once
+        write_silent_wav(&near, 1, 44100, 16, 441);
+        write_silent_wav(&far, 1, 44100, 16, 882);
+
+        let mut prefetcher = Prefetcher::new(None);
+        prefetcher.push(&far, 960u64);
+        prefetcher.push(&near, 480u64);
+
+        // Measured from a playhead at 400, `near` comes first
+        let mut cache = AudioCache::new();
+        let from_playhead = |_: &Path, tick: &u64| Some(tick.abs_diff(400));
+        assert_eq!(prefetcher.run(&mut cache, Duration::ZERO, from_playhead), 1);
+        assert_eq!(cache.originals_count(), 1);
+        assert_eq!(prefetcher.requests, vec![(far.clone(), 960)]);
+
+        assert_eq!(prefetcher.run(&mut cache, Duration::ZERO, from_playhead), 1);
+        assert!(prefetcher.is_empty());
+        assert_eq!(cache.originals_count(), 2);
+    }
+
+    #[test]
+    fn test_prefetch_drops_requests_no_longer_needed() {
+        let dir = TempDir::new().unwrap();
+        let passed = dir.path().join("passed.wav");
+        let missing = dir.path().join("missing.wav");
+        write_silent_wav(&passed, 1, 44100, 16, 441);
+
+        let mut prefetcher = Prefetcher::new(Some(48000));
+        prefetcher.push(&passed, 0u64);
+        prefetcher.push(&missing, 1000u64);
+
+        // The playhead is past the first clip, and the second file doesn't exist
+        let mut cache = AudioCache::new();
+        let warmed = prefetcher.run(&mut cache, Duration::from_secs(10), |_, &tick| {
+            tick.checked_sub(500)
+        });
+        assert_eq!(warmed, 0);
+        assert!(prefetcher.is_empty());
+        assert_eq!(cache.total_count(), 0);
+    }
+}
@@ -0,0 +1,65 @@
+//! WAV files for the tests to decode.
+
+use std::f32::consts::PI;
+use std::path::Path;
+
+/// Write interleaved integer `samples` to a PCM WAV at `path`
+pub(crate) fn write_wav(
+    path: &Path,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+    samples: impl IntoIterator<Item = i32>,
+) {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut writer = hound::WavWriter::create(path, spec).unwrap();
+    for sample in samples {
+        writer.write_sample(sample).unwrap();
+    }
+    writer.finalize().unwrap();
+}
+
+/// Write a 16-bit WAV of a half-scale sine wave at `frequency`, the same in every
+/// channel
+pub(crate) fn create_test_wav(
+    path: &Path,
+    frequency: f32,
+    sample_rate: u32,
+    duration_secs: f32,
+    channels: u16,
+) {
+    let frames = (sample_rate as f32 * duration_secs) as usize;
+    let samples = (0..frames).flat_map(|i| {
+        let t = i as f32 / sample_rate as f32;
+        let sample = ((2.0 * PI * frequency * t).sin() * 0.5 * 32767.0) as i16;
+        std::iter::repeat_n(sample as i32, channels as usize)
+    });
+    write_wav(path, channels, sample_rate, 16, samples);
+}
+
+/// Write a stereo 16-bit WAV of `frames` frames of a sawtooth, for checking
+/// decoded audio against
+pub(crate) fn write_sawtooth_wav(path: &Path, sample_rate: u32, frames: usize) {
+    let samples = (0..frames).flat_map(|frame| {
+        let value = ((frame % 100) as i32 - 50) * 200;
+        [value, value]
+    });
+    write_wav(path, 2, sample_rate, 16, samples);
+}
+
+/// Write a WAV of `frames` frames of silence
+pub(crate) fn write_silent_wav(
+    path: &Path,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+    frames: usize,
+) {
+    let samples = std::iter::repeat_n(0, frames * channels as usize);
+    write_wav(path, channels, sample_rate, bits_per_sample, samples);
+}
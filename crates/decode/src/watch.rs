@@ -0,0 +1,252 @@
+//! Noticing when audio files change on disk.
+//!
+//! [`SampleWatcher`] asks the operating system to report changes to the folders
+//! holding a set of files, so a sample saved from an external editor can be reloaded
+//! without polling every file. Folders are watched rather than the files themselves
+//! because many editors save by writing a new file and renaming it over the old one,
+//! which a watch on the old file would miss.
+
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, channel};
+use std::time::{Duration, Instant};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::is_supported_audio;
+
+/// Watches the folders holding a set of audio files and reports which files in them
+/// were written to, created, renamed or removed.
+///
+/// Changes are held back until the folder has been quiet for a moment, so a file
+/// that is saved in several writes is reported once, after the last one.
+///
+/// # Examples
+///
+/// ```no_run
+/// use daw_decode::{AudioCache, SampleWatcher};
+/// use std::path::Path;
+/// use std::time::Duration;
+///
+/// let kick = Path::new("/samples/kick.wav");
+/// let mut cache = AudioCache::new();
+/// let mut watcher = SampleWatcher::new().unwrap();
+/// watcher.watch_files([kick]);
+///
+/// loop {
+///     for path in watcher.take_changed() {
+///         if cache.has_changed(&path) {
+///             let audio = cache.get_or_load_direct(&path, None).unwrap();
+///         }
+///     }
+///     std::thread::sleep(Duration::from_millis(100));
+/// }
+/// ```
+pub struct SampleWatcher {
+    watcher: RecommendedWatcher,
+    /// Folders currently watched
+    folders: HashSet<PathBuf>,
+    /// Paths reported by the watcher thread
+    events: Receiver<PathBuf>,
+    /// Changed audio files not reported yet
+    pending: BTreeSet<PathBuf>,
+    /// When the last change came in
+    last_change: Option<Instant>,
+    /// How long to wait after the last change before reporting
+    quiet: Duration,
+}
+
+impl std::fmt::Debug for SampleWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SampleWatcher")
+            .field("folders", &self.folders)
+            .field("pending", &self.pending)
+            .field("quiet", &self.quiet)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SampleWatcher {
+    /// Default time to wait after the last change before reporting it
+    pub const DEFAULT_QUIET: Duration = Duration::from_millis(250);
+
+    /// A watcher with nothing to watch yet.
+    ///
+    /// Fails if the operating system's file watching can't be set up.
+    pub fn new() -> anyhow::Result<Self> {
+        let (sender, events) = channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let Ok(event) = event else {
+                return;
+            };
+            if event.kind.is_access() {
+                return;
+            }
+            for path in event.paths {
+                let _ = sender.send(path);
+            }
+        })?;
+        Ok(Self {
+            watcher,
+            folders: HashSet::new(),
+            events,
+            pending: BTreeSet::new(),
+            last_change: None,
+            quiet: Self::DEFAULT_QUIET,
+        })
+    }
+
+    /// Set how long to wait after the last change before reporting it
+    pub fn with_quiet_period(mut self, quiet: Duration) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// The folders currently watched
+    pub fn folders(&self) -> impl Iterator<Item = &Path> {
+        self.folders.iter().map(PathBuf::as_path)
+    }
+
+    /// Watch the folders holding `files`, and stop watching folders that don't
+    /// hold any of them.
+    ///
+    /// Call again whenever the set of files changes. Folders that can't be watched
+    /// (because they don't exist, say) are skipped and tried again on the next call.
+    pub fn watch_files<'a>(&mut self, files: impl IntoIterator<Item = &'a Path>) {
+        let wanted: HashSet<PathBuf> = files
+            .into_iter()
+            .filter_map(|file| file.parent())
+            .map(Path::to_path_buf)
+            .collect();
+
+        let unwanted: Vec<PathBuf> = self.folders.difference(&wanted).cloned().collect();
+        for folder in unwanted {
+            let _ = self.watcher.unwatch(&folder);
+            self.folders.remove(&folder);
+        }
+        for folder in wanted {
+            if !self.folders.contains(&folder)
+                && self
+                    .watcher
+                    .watch(&folder, RecursiveMode::NonRecursive)
+                    .is_ok()
+            {
+                self.folders.insert(folder);
+            }
+        }
+    }
+
+    /// Stop watching everything
+    pub fn clear(&mut self) {
+        self.watch_files([]);
+        self.pending.clear();
+        self.last_change = None;
+    }
+
+    /// Audio files in the watched folders that changed, once no change has come in
+    /// for the quiet period; empty until then.
+    ///
+    /// Returns each file once however many times it changed. Anything that isn't an
+    /// audio file symphonia can read is left out.
+    pub fn take_changed(&mut self) -> Vec<PathBuf> {
+        self.take_changed_at(Instant::now())
+    }
+
+    fn take_changed_at(&mut self, now: Instant) -> Vec<PathBuf> {
+        for path in self.events.try_iter() {
+            if is_supported_audio(&path) {
+                self.pending.insert(path);
+                self.last_change = Some(now);
+            }
+        }
+        match self.last_change {
+            Some(last) if now.duration_since(last) >= self.quiet => {
+                self.last_change = None;
+                std::mem::take(&mut self.pending).into_iter().collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wait for the watcher to report something, giving up after a few seconds
+    fn wait_for_changes(watcher: &mut SampleWatcher) -> Vec<PathBuf> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            let changed = watcher.take_changed();
+            if !changed.is_empty() {
+                return changed;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        Vec::new()
+    }
+
+    #[test]
+    fn test_watcher_reports_changed_audio_files() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let kick = temp.path().join("kick.wav");
+        std::fs::write(&kick, b"one").unwrap();
+
+        let mut watcher = SampleWatcher::new()
+            .unwrap()
+            .with_quiet_period(Duration::from_millis(50));
+        watcher.watch_files([kick.as_path()]);
+        assert_eq!(watcher.folders().count(), 1);
+
+        std::fs::write(temp.path().join("notes.txt"), b"ignored").unwrap();
+        std::fs::write(&kick, b"two").unwrap();
+        std::fs::write(&kick, b"three").unwrap();
+        let changed = wait_for_changes(&mut watcher);
+        assert_eq!(
+            changed
+                .iter()
+                .map(|path| path.file_name().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["kick.wav"]
+        );
+
+        watcher.clear();
+        assert_eq!(watcher.folders().count(), 0);
+    }
+
+    #[test]
+    fn test_changes_wait_for_quiet_period() {
+        let mut watcher = SampleWatcher::new().unwrap();
+        let (sender, events) = channel();
+        watcher.events = events;
+
+        let start = Instant::now();
+        sender.send(PathBuf::from("/samples/kick.wav")).unwrap();
+        sender.send(PathBuf::from("/samples/kick.wav")).unwrap();
+        assert!(watcher.take_changed_at(start).is_empty());
+
+        // Another change restarts the wait
+        sender.send(PathBuf::from("/samples/snare.wav")).unwrap();
+        let later = start + SampleWatcher::DEFAULT_QUIET / 2;
+        assert!(watcher.take_changed_at(later).is_empty());
+        assert!(
+            watcher
+                .take_changed_at(start + SampleWatcher::DEFAULT_QUIET)
+                .is_empty()
+        );
+
+        let changed = watcher.take_changed_at(later + SampleWatcher::DEFAULT_QUIET);
+        assert_eq!(
+            changed,
+            vec![
+                PathBuf::from("/samples/kick.wav"),
+                PathBuf::from("/samples/snare.wav"),
+            ]
+        );
+        assert!(
+            watcher
+                .take_changed_at(later + Duration::from_secs(10))
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Compressor: turns down whatever goes over a threshold.
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Processor, db_to_gain};
+
+/// How hard a [`Compressor`] squeezes and how quickly it reacts.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CompressorSettings {
+    /// Level above which the gain comes down, in dBFS
+    pub threshold_db: f32,
+    /// How many dB over the threshold it takes to come out 1 dB over
+    pub ratio: f32,
+    /// Time to clamp down once the level rises, in milliseconds
+    pub attack_ms: f32,
+    /// Time to let go once it falls, in milliseconds
+    pub release_ms: f32,
+    /// Gain added afterwards to make up for the reduction, in dB
+    pub makeup_db: f32,
+}
+
+impl Default for CompressorSettings {
+    fn default() -> Self {
+        Self {
+            threshold_db: -18.0,
+            ratio: 4.0,
+            attack_ms: 10.0,
+            release_ms: 100.0,
+            makeup_db: 0.0,
+        }
+    }
+}
+
+impl CompressorSettings {
+    pub(crate) fn parameters_mut(&mut self) -> Vec<(&'static str, &mut f32, RangeInclusive<f32>)> {
+        vec![
+            ("threshold_db", &mut self.threshold_db, -60.0..=0.0),
+            ("ratio", &mut self.ratio, 1.0..=20.0),
+            ("attack_ms", &mut self.attack_ms, 0.1..=200.0),
+            ("release_ms", &mut self.release_ms, 5.0..=2000.0),
+            ("makeup_db", &mut self.makeup_db, 0.0..=24.0),
+        ]
+    }
+}
+
+/// Share of the distance to a new level an envelope covers each frame, for a
+/// reaction time of `ms`
+fn follow_coefficient(ms: f32, sample_rate: u32) -> f32 {
+    let frames = ms / 1000.0 * sample_rate as f32;
+    if frames > 0.0 {
+        1.0 - (-1.0 / frames).exp()
+    } else {
+        1.0
+    }
+}
+
+/// The compressor itself. Channels are linked, so the stereo image stays put
+/// while it works.
+#[derive(Debug, Clone)]
+pub struct Compressor {
+    settings: CompressorSettings,
+    sample_rate: u32,
+    attack: f32,
+    release: f32,
+    /// Level the compressor is reacting to, following the peaks
+    envelope: f32,
+}
+
+impl Compressor {
+    pub fn new(settings: CompressorSettings, sample_rate: u32) -> Self {
+        let mut compressor = Self {
+            settings,
+            sample_rate,
+            attack: 1.0,
+            release: 1.0,
+            envelope: 0.0,
+        };
+        compressor.set(settings);
+        compressor
+    }
+
+    pub fn settings(&self) -> CompressorSettings {
+        self.settings
+    }
+
+    /// Change the settings, keeping the gain reduction it's in the middle of
+    pub fn set(&mut self, settings: CompressorSettings) {
+        self.attack = follow_coefficient(settings.attack_ms, self.sample_rate);
+        self.release = follow_coefficient(settings.release_ms, self.sample_rate);
+        self.settings = settings;
+    }
+}
+
+impl Processor for Compressor {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        let slope = 1.0 - 1.0 / self.settings.ratio.max(1.0);
+        let makeup = db_to_gain(self.settings.makeup_db);
+        for frame in block.chunks_mut(channels) {
+            let peak = frame
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            let speed = if peak > self.envelope {
+                self.attack
+            } else {
+                self.release
+            };
+            self.envelope += (peak - self.envelope) * speed;
+
+            let over_db = 20.0 * self.envelope.max(1e-9).log10() - self.settings.threshold_db;
+            let gain = if over_db > 0.0 {
+                db_to_gain(-over_db * slope) * makeup
+            } else {
+                makeup
+            };
+            if gain != 1.0 {
+                for sample in frame {
+                    *sample *= gain;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loud_audio_is_turned_down_by_the_ratio() {
+        let settings = CompressorSettings {
+            threshold_db: -20.0,
+            ratio: 4.0,
+            attack_ms: 1.0,
+            release_ms: 50.0,
+            makeup_db: 0.0,
+        };
+        // A steady level 20 dB over the threshold comes out 5 dB over it
+        let mut block = vec![1.0; 4800];
+        Compressor::new(settings, 48000).process(&mut block, 1);
+        let settled = 20.0 * block.last().unwrap().log10();
+        assert!((settled + 15.0).abs() < 0.1, "{settled}");
+
+        // Under the threshold only the makeup gain applies
+        let mut quiet = vec![0.05; 480];
+        Compressor::new(
+            CompressorSettings {
+                makeup_db: 6.0,
+                ..settings
+            },
+            48000,
+        )
+        .process(&mut quiet, 1);
+        assert!((quiet[479] - 0.05 * db_to_gain(6.0)).abs() < 1e-6);
+    }
+}
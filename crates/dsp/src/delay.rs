@@ -0,0 +1,126 @@
+//! Delay: echoes of the input, each quieter than the last.
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MAX_EFFECT_CHANNELS, Processor};
+
+/// Longest delay time a [`Delay`] can be set to, in milliseconds
+pub const MAX_DELAY_MS: f32 = 2000.0;
+
+/// How far apart the echoes of a [`Delay`] are and how long they ring on.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DelaySettings {
+    /// Time between echoes, in milliseconds
+    pub time_ms: f32,
+    /// Share of each echo fed back into the next
+    pub feedback: f32,
+    /// Balance from the dry input (0.0) to only the echoes (1.0)
+    pub mix: f32,
+}
+
+impl Default for DelaySettings {
+    fn default() -> Self {
+        Self {
+            time_ms: 250.0,
+            feedback: 0.35,
+            mix: 0.3,
+        }
+    }
+}
+
+impl DelaySettings {
+    pub(crate) fn parameters_mut(&mut self) -> Vec<(&'static str, &mut f32, RangeInclusive<f32>)> {
+        vec![
+            ("time_ms", &mut self.time_ms, 1.0..=MAX_DELAY_MS),
+            ("feedback", &mut self.feedback, 0.0..=0.95),
+            ("mix", &mut self.mix, 0.0..=1.0),
+        ]
+    }
+}
+
+/// The delay itself, with room for [`MAX_DELAY_MS`] of audio allocated up front
+/// so the time can change without reallocating.
+#[derive(Debug, Clone)]
+pub struct Delay {
+    settings: DelaySettings,
+    sample_rate: u32,
+    /// Interleaved history of `MAX_EFFECT_CHANNELS` channels, written in a circle
+    buffer: Vec<f32>,
+    /// Frame of `buffer` written next
+    write: usize,
+    /// Frames between writing a sample and reading it back
+    delay_frames: usize,
+}
+
+impl Delay {
+    pub fn new(settings: DelaySettings, sample_rate: u32) -> Self {
+        let frames = (MAX_DELAY_MS / 1000.0 * sample_rate as f32) as usize + 1;
+        let mut delay = Self {
+            settings,
+            sample_rate,
+            buffer: vec![0.0; frames * MAX_EFFECT_CHANNELS],
+            write: 0,
+            delay_frames: 1,
+        };
+        delay.set(settings);
+        delay
+    }
+
+    pub fn settings(&self) -> DelaySettings {
+        self.settings
+    }
+
+    /// Change the settings, keeping the echoes already ringing
+    pub fn set(&mut self, settings: DelaySettings) {
+        let frames = self.buffer.len() / MAX_EFFECT_CHANNELS;
+        let delay_frames = (settings.time_ms / 1000.0 * self.sample_rate as f32).round() as usize;
+        self.delay_frames = delay_frames.clamp(1, frames - 1);
+        self.settings = settings;
+    }
+}
+
+impl Processor for Delay {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        let frames = self.buffer.len() / MAX_EFFECT_CHANNELS;
+        let DelaySettings { feedback, mix, .. } = self.settings;
+        for frame in block.chunks_mut(channels) {
+            let read = (self.write + frames - self.delay_frames) % frames;
+            for (channel, sample) in frame.iter_mut().take(MAX_EFFECT_CHANNELS).enumerate() {
+                let echo = self.buffer[read * MAX_EFFECT_CHANNELS + channel];
+                self.buffer[self.write * MAX_EFFECT_CHANNELS + channel] = *sample + echo * feedback;
+                *sample = *sample * (1.0 - mix) + echo * mix;
+            }
+            self.write = (self.write + 1) % frames;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echoes_repeat_at_the_delay_time_and_fade() {
+        let settings = DelaySettings {
+            time_ms: 10.0,
+            feedback: 0.5,
+            mix: 1.0,
+        };
+        // 10 ms is 10 frames at 1 kHz
+        let mut block = [0.0; 40];
+        block[0] = 1.0;
+        let mut delay = Delay::new(settings, 1000);
+        // Any block size gives the same echoes
+        for chunk in block.chunks_mut(3) {
+            delay.process(chunk, 1);
+        }
+        assert_eq!(block[0], 0.0);
+        assert_eq!(block[10], 1.0);
+        assert_eq!(block[20], 0.5);
+        assert_eq!(block[30], 0.25);
+        assert_eq!(block.iter().filter(|sample| **sample != 0.0).count(), 3);
+    }
+}
@@ -0,0 +1,277 @@
+//! Insert effects: the built-in processors a track can run its audio through,
+//! in order, before its volume and pan.
+//!
+//! An [`Effect`] is only settings, cheap to copy and saved with the project. An
+//! [`InsertChain`] holds the running processors for a list of them. It follows
+//! new settings without allocating, so a reverb's tail isn't cut off by turning a
+//! knob; a changed list takes a new chain, built off the audio thread, that
+//! carries over the state of the effects that stay.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Compressor, CompressorSettings, Delay, DelaySettings, EqSettings, Equalizer, Processor, Reverb,
+    ReverbSettings,
+};
+
+/// A built-in effect and its settings.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Effect {
+    Eq(EqSettings),
+    Compressor(CompressorSettings),
+    Delay(DelaySettings),
+    Reverb(ReverbSettings),
+}
+
+/// One of an effect's settings, by the name frontends set it with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EffectParameter {
+    pub name: &'static str,
+    pub value: f32,
+    /// Lowest value it takes
+    pub min: f32,
+    /// Highest value it takes
+    pub max: f32,
+}
+
+impl Effect {
+    /// Every kind of effect, by the name it's saved under
+    pub const KINDS: [&'static str; 4] = ["eq", "compressor", "delay", "reverb"];
+
+    /// The effect of kind `kind` (see [`Effect::KINDS`]) with its default settings
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "eq" => Some(Self::Eq(EqSettings::default())),
+            "compressor" => Some(Self::Compressor(CompressorSettings::default())),
+            "delay" => Some(Self::Delay(DelaySettings::default())),
+            "reverb" => Some(Self::Reverb(ReverbSettings::default())),
+            _ => None,
+        }
+    }
+
+    /// Name of the kind of effect, as in [`Effect::KINDS`]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Eq(_) => "eq",
+            Self::Compressor(_) => "compressor",
+            Self::Delay(_) => "delay",
+            Self::Reverb(_) => "reverb",
+        }
+    }
+
+    /// The effect's settings as named parameters, in a fixed order
+    pub fn parameters(&self) -> Vec<EffectParameter> {
+        let mut effect = *self;
+        effect
+            .parameters_mut()
+            .into_iter()
+            .map(|(name, value, range)| EffectParameter {
+                name,
+                value: *value,
+                min: *range.start(),
+                max: *range.end(),
+            })
+            .collect()
+    }
+
+    /// Set the parameter called `name`, kept within its range. Returns whether
+    /// the effect has such a parameter.
+    pub fn set_parameter(&mut self, name: &str, value: f32) -> bool {
+        if !value.is_finite() {
+            return false;
+        }
+        let Some((_, parameter, range)) = self
+            .parameters_mut()
+            .into_iter()
+            .find(|(parameter, _, _)| *parameter == name)
+        else {
+            return false;
+        };
+        *parameter = value.clamp(*range.start(), *range.end());
+        true
+    }
+
+    fn parameters_mut(&mut self) -> Vec<(&'static str, &mut f32, std::ops::RangeInclusive<f32>)> {
+        match self {
+            Self::Eq(settings) => settings.parameters_mut(),
+            Self::Compressor(settings) => settings.parameters_mut(),
+            Self::Delay(settings) => settings.parameters_mut(),
+            Self::Reverb(settings) => settings.parameters_mut(),
+        }
+    }
+}
+
+/// A running effect
+#[derive(Debug, Clone)]
+enum Insert {
+    Eq(Equalizer),
+    Compressor(Compressor),
+    Delay(Delay),
+    Reverb(Reverb),
+}
+
+impl Insert {
+    fn new(effect: Effect, sample_rate: u32) -> Self {
+        match effect {
+            Effect::Eq(settings) => Self::Eq(Equalizer::new(settings, sample_rate)),
+            Effect::Compressor(settings) => {
+                Self::Compressor(Compressor::new(settings, sample_rate))
+            }
+            Effect::Delay(settings) => Self::Delay(Delay::new(settings, sample_rate)),
+            Effect::Reverb(settings) => Self::Reverb(Reverb::new(settings, sample_rate)),
+        }
+    }
+
+    /// The effect's current settings
+    fn effect(&self) -> Effect {
+        match self {
+            Self::Eq(eq) => Effect::Eq(eq.settings()),
+            Self::Compressor(compressor) => Effect::Compressor(compressor.settings()),
+            Self::Delay(delay) => Effect::Delay(delay.settings()),
+            Self::Reverb(reverb) => Effect::Reverb(reverb.settings()),
+        }
+    }
+
+    /// Take on `effect`'s settings if it's the same kind of effect; returns
+    /// whether it was
+    fn set(&mut self, effect: Effect) -> bool {
+        match (self, effect) {
+            (Self::Eq(eq), Effect::Eq(settings)) => {
+                if eq.settings() != settings {
+                    eq.set(settings);
+                }
+            }
+            (Self::Compressor(compressor), Effect::Compressor(settings)) => {
+                compressor.set(settings)
+            }
+            (Self::Delay(delay), Effect::Delay(settings)) => delay.set(settings),
+            (Self::Reverb(reverb), Effect::Reverb(settings)) => reverb.set(settings),
+            _ => return false,
+        }
+        true
+    }
+
+    fn processor(&mut self) -> &mut dyn Processor {
+        match self {
+            Self::Eq(eq) => eq,
+            Self::Compressor(compressor) => compressor,
+            Self::Delay(delay) => delay,
+            Self::Reverb(reverb) => reverb,
+        }
+    }
+}
+
+/// A track's insert effects, run one after the other.
+///
+/// Effects keep state for up to [`MAX_EFFECT_CHANNELS`](crate::MAX_EFFECT_CHANNELS)
+/// channels; any past that go through untouched.
+#[derive(Debug, Clone, Default)]
+pub struct InsertChain {
+    inserts: Vec<Insert>,
+}
+
+impl InsertChain {
+    pub fn new(effects: &[Effect], sample_rate: u32) -> Self {
+        Self {
+            inserts: effects
+                .iter()
+                .map(|&effect| Insert::new(effect, sample_rate))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inserts.is_empty()
+    }
+
+    /// Take on new settings for the effects in the chain, in order. A slot
+    /// holding another kind of effect keeps its own, and effects past the end of
+    /// the chain are left out: changing which effects run takes a new chain (see
+    /// [`InsertChain::take_state`]), so this never allocates or frees.
+    pub fn set(&mut self, effects: &[Effect]) {
+        for (insert, &effect) in self.inserts.iter_mut().zip(effects) {
+            insert.set(effect);
+        }
+    }
+
+    /// Carry the running state of `old`'s effects into the slots of this chain
+    /// that hold the same kind of effect, keeping this chain's settings, so tails
+    /// ring on when a chain replaces another. Effects are swapped rather than
+    /// freed: `old` is left with the fresh ones they replace.
+    pub fn take_state(&mut self, old: &mut InsertChain) {
+        for (insert, old) in self.inserts.iter_mut().zip(&mut old.inserts) {
+            let effect = insert.effect();
+            if std::mem::discriminant(insert) == std::mem::discriminant(old) {
+                std::mem::swap(insert, old);
+                insert.set(effect);
+            }
+        }
+    }
+}
+
+impl Processor for InsertChain {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        for insert in &mut self.inserts {
+            insert.processor().process(block, channels);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameters_are_set_by_name_within_their_range() {
+        let mut effect = Effect::from_kind("delay").unwrap();
+        assert_eq!(effect.kind(), "delay");
+        assert!(effect.set_parameter("feedback", 2.0));
+        assert!(!effect.set_parameter("room_size", 0.5));
+        assert!(!effect.set_parameter("mix", f32::NAN));
+        let feedback = effect.parameters()[1];
+        assert_eq!((feedback.name, feedback.value), ("feedback", 0.95));
+
+        for kind in Effect::KINDS {
+            assert_eq!(Effect::from_kind(kind).unwrap().kind(), kind);
+        }
+        assert_eq!(Effect::from_kind("flanger"), None);
+    }
+
+    #[test]
+    fn test_changing_settings_keeps_the_tail() {
+        let delay = |mix| {
+            Effect::Delay(DelaySettings {
+                time_ms: 10.0,
+                feedback: 0.5,
+                mix,
+            })
+        };
+        let mut chain = InsertChain::new(&[delay(1.0)], 1000);
+        let mut block = vec![0.0; 15];
+        block[0] = 1.0;
+        chain.process(&mut block, 1);
+
+        // The echo already on its way still arrives after the mix changes
+        chain.set(&[delay(0.5)]);
+        let mut block = vec![0.0; 10];
+        chain.process(&mut block, 1);
+        assert_eq!(block[5], 0.25);
+
+        // A new chain with a delay first takes over the old one's echoes
+        let reverb = Effect::Reverb(ReverbSettings::default());
+        let mut replacement = InsertChain::new(&[delay(1.0), reverb], 1000);
+        replacement.take_state(&mut chain);
+        chain = replacement;
+        let mut block = vec![0.0; 10];
+        chain.process(&mut block, 1);
+        assert!(block[5] > 0.0);
+
+        // Putting a different effect in the slot starts over
+        let mut replacement = InsertChain::new(&[reverb, delay(1.0)], 1000);
+        replacement.take_state(&mut chain);
+        let mut block = vec![0.0; 30];
+        replacement.process(&mut block, 1);
+        assert!(block.iter().all(|sample| *sample == 0.0));
+    }
+}
@@ -0,0 +1,250 @@
+//! Three-band parametric EQ: a low shelf, a bell in the middle and a high shelf.
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{MAX_EFFECT_CHANNELS, Processor};
+
+/// Where each band of an [`Equalizer`] sits and how far it boosts or cuts.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+    /// Corner of the low shelf in Hz
+    pub low_frequency: f32,
+    pub low_gain_db: f32,
+    /// Center of the middle band in Hz
+    pub mid_frequency: f32,
+    pub mid_gain_db: f32,
+    /// Width of the middle band; higher is narrower
+    pub mid_q: f32,
+    /// Corner of the high shelf in Hz
+    pub high_frequency: f32,
+    pub high_gain_db: f32,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        Self {
+            low_frequency: 100.0,
+            low_gain_db: 0.0,
+            mid_frequency: 1000.0,
+            mid_gain_db: 0.0,
+            mid_q: 0.7,
+            high_frequency: 8000.0,
+            high_gain_db: 0.0,
+        }
+    }
+}
+
+impl EqSettings {
+    pub(crate) fn parameters_mut(&mut self) -> Vec<(&'static str, &mut f32, RangeInclusive<f32>)> {
+        vec![
+            ("low_frequency", &mut self.low_frequency, 20.0..=1000.0),
+            ("low_gain_db", &mut self.low_gain_db, -24.0..=24.0),
+            ("mid_frequency", &mut self.mid_frequency, 100.0..=10000.0),
+            ("mid_gain_db", &mut self.mid_gain_db, -24.0..=24.0),
+            ("mid_q", &mut self.mid_q, 0.1..=10.0),
+            ("high_frequency", &mut self.high_frequency, 1000.0..=20000.0),
+            ("high_gain_db", &mut self.high_gain_db, -24.0..=24.0),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BandShape {
+    LowShelf,
+    Bell,
+    HighShelf,
+}
+
+/// Biquad coefficients from the Audio EQ Cookbook, divided through by `a0`
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    fn new(shape: BandShape, frequency: f32, gain_db: f32, q: f32, sample_rate: u32) -> Self {
+        // Stay clear of Nyquist, where the filter stops being stable
+        let frequency = frequency.clamp(10.0, sample_rate as f32 * 0.45);
+        let w0 = std::f32::consts::TAU * frequency / sample_rate as f32;
+        let (sin, cos) = w0.sin_cos();
+        let a = 10f32.powf(gain_db / 40.0);
+        let [b0, b1, b2, a0, a1, a2] = match shape {
+            BandShape::Bell => {
+                let alpha = sin / (2.0 * q);
+                [
+                    1.0 + alpha * a,
+                    -2.0 * cos,
+                    1.0 - alpha * a,
+                    1.0 + alpha / a,
+                    -2.0 * cos,
+                    1.0 - alpha / a,
+                ]
+            }
+            BandShape::LowShelf | BandShape::HighShelf => {
+                // A shelf slope of 1, as steep as it gets without overshooting
+                let alpha = sin / 2.0 * std::f32::consts::SQRT_2;
+                let root = 2.0 * a.sqrt() * alpha;
+                let sign = if shape == BandShape::LowShelf {
+                    1.0
+                } else {
+                    -1.0
+                };
+                [
+                    a * ((a + 1.0) - sign * (a - 1.0) * cos + root),
+                    sign * 2.0 * a * ((a - 1.0) - sign * (a + 1.0) * cos),
+                    a * ((a + 1.0) - sign * (a - 1.0) * cos - root),
+                    (a + 1.0) + sign * (a - 1.0) * cos + root,
+                    -sign * 2.0 * ((a - 1.0) + sign * (a + 1.0) * cos),
+                    (a + 1.0) + sign * (a - 1.0) * cos - root,
+                ]
+            }
+        };
+        Self {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// The last two inputs and outputs of a biquad on one channel
+#[derive(Debug, Clone, Copy, Default)]
+struct BiquadState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl BiquadState {
+    #[inline]
+    fn next(&mut self, filter: &Biquad, x: f32) -> f32 {
+        let y = filter.b0 * x + filter.b1 * self.x1 + filter.b2 * self.x2
+            - filter.a1 * self.y1
+            - filter.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// The EQ itself. Bands left at 0 dB are skipped, so a flat EQ passes audio
+/// through untouched.
+#[derive(Debug, Clone)]
+pub struct Equalizer {
+    settings: EqSettings,
+    sample_rate: u32,
+    /// Each band's filter, or `None` while it's flat
+    bands: [Option<Biquad>; 3],
+    states: [[BiquadState; MAX_EFFECT_CHANNELS]; 3],
+}
+
+impl Equalizer {
+    pub fn new(settings: EqSettings, sample_rate: u32) -> Self {
+        let mut eq = Self {
+            settings,
+            sample_rate,
+            bands: [None; 3],
+            states: Default::default(),
+        };
+        eq.set(settings);
+        eq
+    }
+
+    pub fn settings(&self) -> EqSettings {
+        self.settings
+    }
+
+    /// Change the settings, keeping the filters' history so the sound doesn't jump
+    pub fn set(&mut self, settings: EqSettings) {
+        let band = |shape, frequency, gain_db, q| {
+            (gain_db != 0.0).then(|| Biquad::new(shape, frequency, gain_db, q, self.sample_rate))
+        };
+        self.bands = [
+            band(
+                BandShape::LowShelf,
+                settings.low_frequency,
+                settings.low_gain_db,
+                1.0,
+            ),
+            band(
+                BandShape::Bell,
+                settings.mid_frequency,
+                settings.mid_gain_db,
+                settings.mid_q.max(0.01),
+            ),
+            band(
+                BandShape::HighShelf,
+                settings.high_frequency,
+                settings.high_gain_db,
+                1.0,
+            ),
+        ];
+        self.settings = settings;
+    }
+}
+
+impl Processor for Equalizer {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        for (filter, states) in self.bands.iter().zip(&mut self.states) {
+            let Some(filter) = filter else {
+                continue;
+            };
+            for frame in block.chunks_mut(channels) {
+                for (sample, state) in frame.iter_mut().zip(states.iter_mut()) {
+                    *sample = state.next(filter, *sample);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gain of a filter at `frequency`, for checking the response in tests
+    fn response_db(filter: &Biquad, frequency: f32, sample_rate: u32) -> f32 {
+        let w = std::f32::consts::TAU * frequency / sample_rate as f32;
+        let (z1, z2) = ((-w).sin_cos(), (-2.0 * w).sin_cos());
+        let ratio = |c0: f32, c1: f32, c2: f32| {
+            let re = c0 + c1 * z1.1 + c2 * z2.1;
+            let im = c1 * z1.0 + c2 * z2.0;
+            (re * re + im * im).sqrt()
+        };
+        let gain = ratio(filter.b0, filter.b1, filter.b2) / ratio(1.0, filter.a1, filter.a2);
+        20.0 * gain.log10()
+    }
+
+    #[test]
+    fn test_bands_boost_and_cut_where_they_sit() {
+        let shelf = Biquad::new(BandShape::LowShelf, 100.0, 6.0, 1.0, 48000);
+        assert!((response_db(&shelf, 20.0, 48000) - 6.0).abs() < 0.3);
+        assert!(response_db(&shelf, 5000.0, 48000).abs() < 0.1);
+
+        let bell = Biquad::new(BandShape::Bell, 1000.0, -12.0, 1.0, 48000);
+        assert!((response_db(&bell, 1000.0, 48000) + 12.0).abs() < 0.1);
+        assert!(response_db(&bell, 50.0, 48000).abs() < 0.1);
+
+        let shelf = Biquad::new(BandShape::HighShelf, 8000.0, 6.0, 1.0, 48000);
+        assert!((response_db(&shelf, 20000.0, 48000) - 6.0).abs() < 0.5);
+        assert!(response_db(&shelf, 100.0, 48000).abs() < 0.1);
+
+        // A flat EQ leaves the audio alone
+        let input: Vec<f32> = (0..256).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut block = input.clone();
+        Equalizer::new(EqSettings::default(), 48000).process(&mut block, 2);
+        assert_eq!(block, input);
+    }
+}
@@ -0,0 +1,24 @@
+//! Small DSP building blocks shared by the engine and offline renderers.
+
+mod compressor;
+mod delay;
+mod effects;
+mod eq;
+mod master;
+mod reverb;
+mod smoother;
+
+pub use compressor::{Compressor, CompressorSettings};
+pub use delay::{Delay, DelaySettings, MAX_DELAY_MS};
+pub use effects::{Effect, EffectParameter, InsertChain};
+pub use eq::{EqSettings, Equalizer};
+pub use master::{Limiter, LimiterSettings, MasterBus, MasterSettings, Processor};
+pub use reverb::{Reverb, ReverbSettings};
+pub use smoother::Smoother;
+
+/// Channels an insert effect keeps state for; any after them pass through
+pub const MAX_EFFECT_CHANNELS: usize = 2;
+
+fn db_to_gain(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
@@ -0,0 +1,335 @@
+//! Processing on the master bus, after every track has been summed.
+//!
+//! The engine runs a [`MasterBus`] on the live output and renders run one over the
+//! finished mix, so a bounce sounds the way playback did. Master effects implement
+//! [`Processor`], so new stages can join the bus without either side changing.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Effect, InsertChain, Smoother, db_to_gain};
+
+/// Something that processes interleaved audio in place, one block at a time.
+///
+/// Blocks can be any length, so the same processor gives the same result whether
+/// it's fed by an audio callback or a whole render at once.
+pub trait Processor: Send {
+    /// Process an interleaved block of `channels` channels in place
+    fn process(&mut self, block: &mut [f32], channels: usize);
+}
+
+/// What the master bus does to the mix, after its insert effects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MasterSettings {
+    /// Gain applied to the whole mix in dB
+    pub gain_db: f32,
+    /// Balance from the left (-1.0) to the right (1.0): the other side is turned
+    /// down and this one stays at unity. Stereo output only
+    pub pan: f32,
+    /// Keep peaks under a ceiling, after the gain and balance
+    pub limiter: Option<LimiterSettings>,
+}
+
+impl Default for MasterSettings {
+    fn default() -> Self {
+        Self {
+            gain_db: 0.0,
+            pan: 0.0,
+            limiter: None,
+        }
+    }
+}
+
+/// A peak limiter's ceiling and how fast it lets go.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LimiterSettings {
+    /// Highest sample level let through, in dBFS
+    pub ceiling_db: f32,
+    /// Time to recover from gain reduction, in milliseconds
+    pub release_ms: f32,
+}
+
+impl Default for LimiterSettings {
+    fn default() -> Self {
+        Self {
+            ceiling_db: -1.0,
+            release_ms: 100.0,
+        }
+    }
+}
+
+/// Frames a master gain change glides over, so moving it doesn't click
+const GAIN_RAMP_FRAMES: u32 = 64;
+
+/// The master bus: insert effects, gain, balance, then the limiter.
+///
+/// With the default settings and no effects the mix passes through untouched.
+#[derive(Debug, Clone)]
+pub struct MasterBus {
+    settings: MasterSettings,
+    inserts: InsertChain,
+    gain: Smoother,
+    pan: Smoother,
+    limiter: Option<Limiter>,
+    sample_rate: u32,
+}
+
+impl MasterBus {
+    pub fn new(settings: MasterSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            inserts: InsertChain::default(),
+            gain: Smoother::linear(db_to_gain(settings.gain_db), GAIN_RAMP_FRAMES),
+            pan: Smoother::linear(settings.pan.clamp(-1.0, 1.0), GAIN_RAMP_FRAMES),
+            limiter: settings
+                .limiter
+                .map(|limiter| Limiter::new(limiter, sample_rate)),
+            sample_rate,
+        }
+    }
+
+    /// A bus like [`MasterBus::new`] that runs the mix through `effects` first
+    pub fn with_effects(settings: MasterSettings, effects: &[Effect], sample_rate: u32) -> Self {
+        let mut bus = Self::new(settings, sample_rate);
+        bus.inserts = InsertChain::new(effects, sample_rate);
+        bus
+    }
+
+    /// Give the insert effects new settings, without changing which effects run
+    /// (see [`InsertChain::set`])
+    pub fn set_effects(&mut self, effects: &[Effect]) {
+        self.inserts.set(effects);
+    }
+
+    /// Run the mix through `inserts` from now on, carrying over the state of the
+    /// effects that stay (see [`InsertChain::take_state`]). `inserts` is left
+    /// holding the old chain, for the caller to free.
+    pub fn replace_inserts(&mut self, inserts: &mut InsertChain) {
+        inserts.take_state(&mut self.inserts);
+        std::mem::swap(&mut self.inserts, inserts);
+    }
+
+    pub fn settings(&self) -> MasterSettings {
+        self.settings
+    }
+
+    /// Change the settings; the gain glides to its new value and the limiter keeps
+    /// any gain reduction it's in the middle of
+    pub fn set(&mut self, settings: MasterSettings) {
+        self.gain.set_target(db_to_gain(settings.gain_db));
+        self.pan.set_target(settings.pan.clamp(-1.0, 1.0));
+        self.limiter = match (self.limiter.take(), settings.limiter) {
+            (Some(limiter), Some(new)) => Some(limiter.with_settings(new, self.sample_rate)),
+            (None, Some(new)) => Some(Limiter::new(new, self.sample_rate)),
+            (_, None) => None,
+        };
+        self.settings = settings;
+    }
+}
+
+impl Processor for MasterBus {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        if !self.inserts.is_empty() {
+            self.inserts.process(block, channels);
+        }
+        if !self.gain.is_settled() {
+            for frame in block.chunks_mut(channels) {
+                let gain = self.gain.next();
+                for sample in frame {
+                    *sample *= gain;
+                }
+            }
+        } else if self.gain.value() != 1.0 {
+            let gain = self.gain.value();
+            for sample in block.iter_mut() {
+                *sample *= gain;
+            }
+        }
+        if channels == 2 && (!self.pan.is_settled() || self.pan.value() != 0.0) {
+            for frame in block.chunks_mut(2) {
+                let pan = self.pan.next();
+                frame[0] *= (1.0 - pan).min(1.0);
+                frame[1] *= (1.0 + pan).min(1.0);
+            }
+        }
+        if let Some(limiter) = &mut self.limiter {
+            limiter.process(block, channels);
+        }
+    }
+}
+
+/// Peak limiter with an instant attack, so nothing gets past the ceiling, and an
+/// exponential release.
+#[derive(Debug, Clone, Copy)]
+pub struct Limiter {
+    ceiling: f32,
+    /// Share of the distance back to the wanted gain left after each frame
+    release: f32,
+    /// Gain applied to the last frame
+    gain: f32,
+}
+
+impl Limiter {
+    pub fn new(settings: LimiterSettings, sample_rate: u32) -> Self {
+        Self {
+            ceiling: 1.0,
+            release: 0.0,
+            gain: 1.0,
+        }
+        .with_settings(settings, sample_rate)
+    }
+
+    fn with_settings(self, settings: LimiterSettings, sample_rate: u32) -> Self {
+        let release_frames = settings.release_ms / 1000.0 * sample_rate as f32;
+        Self {
+            ceiling: db_to_gain(settings.ceiling_db),
+            release: if release_frames > 0.0 {
+                (-1.0 / release_frames).exp()
+            } else {
+                0.0
+            },
+            gain: self.gain,
+        }
+    }
+}
+
+impl Processor for Limiter {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        for frame in block.chunks_mut(channels) {
+            let peak = frame
+                .iter()
+                .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+            let wanted = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+            self.gain = if wanted < self.gain {
+                wanted
+            } else {
+                wanted + (self.gain - wanted) * self.release
+            };
+            if self.gain != 1.0 {
+                for sample in frame {
+                    *sample *= self.gain;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(amplitude: f32) -> Vec<f32> {
+        (0..4800)
+            .flat_map(|i| {
+                let value = amplitude * (i as f32 * 0.05).sin();
+                [value, value]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_default_bus_passes_audio_through() {
+        let input = sine(0.9);
+        let mut block = input.clone();
+        MasterBus::new(MasterSettings::default(), 48000).process(&mut block, 2);
+        assert_eq!(block, input);
+    }
+
+    #[test]
+    fn test_limiter_holds_peaks_to_the_ceiling() {
+        let settings = MasterSettings {
+            gain_db: 6.0,
+            pan: 0.0,
+            limiter: Some(LimiterSettings::default()),
+        };
+        let mut block = sine(0.9);
+        MasterBus::new(settings, 48000).process(&mut block, 2);
+        let ceiling = db_to_gain(-1.0);
+        assert!(block.iter().all(|sample| sample.abs() <= ceiling + 1e-6));
+        // Turned up, then held at the ceiling
+        let peak = block.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        assert!((peak - ceiling).abs() < 1e-3);
+
+        // Quiet audio is only turned up
+        let mut quiet = sine(0.1);
+        MasterBus::new(settings, 48000).process(&mut quiet, 2);
+        for (out, input) in quiet.iter().zip(sine(0.1)) {
+            assert!((out - input * db_to_gain(6.0)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_block_size_doesnt_change_the_result() {
+        let settings = MasterSettings {
+            gain_db: 3.0,
+            pan: 0.0,
+            limiter: Some(LimiterSettings {
+                ceiling_db: -3.0,
+                release_ms: 10.0,
+            }),
+        };
+        let mut whole = sine(0.9);
+        MasterBus::new(settings, 48000).process(&mut whole, 2);
+
+        let mut blocks = sine(0.9);
+        let mut bus = MasterBus::new(settings, 48000);
+        for block in blocks.chunks_mut(2 * 37) {
+            bus.process(block, 2);
+        }
+        assert_eq!(blocks, whole);
+    }
+
+    #[test]
+    fn test_gain_change_glides() {
+        let mut bus = MasterBus::new(MasterSettings::default(), 48000);
+        bus.set(MasterSettings {
+            gain_db: -120.0,
+            pan: 0.0,
+            limiter: None,
+        });
+        let mut block = vec![1.0; 2 * GAIN_RAMP_FRAMES as usize];
+        bus.process(&mut block, 2);
+        assert!(block[0] < 1.0 && block[0] > 0.9);
+        assert!(block.last().unwrap().abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_replaced_inserts_keep_the_tail_of_effects_that_stay() {
+        let delay = Effect::Delay(crate::DelaySettings {
+            time_ms: 10.0,
+            feedback: 0.0,
+            mix: 1.0,
+        });
+        let mut bus = MasterBus::with_effects(MasterSettings::default(), &[delay], 1000);
+        let mut block = vec![0.0; 5];
+        block[0] = 1.0;
+        bus.process(&mut block, 1);
+
+        let mut inserts = InsertChain::new(&[delay, Effect::from_kind("eq").unwrap()], 1000);
+        bus.replace_inserts(&mut inserts);
+        assert!(!inserts.is_empty());
+        let mut block = vec![0.0; 10];
+        bus.process(&mut block, 1);
+        assert!(block[5] > 0.5);
+    }
+
+    #[test]
+    fn test_balance_turns_down_the_other_side_after_the_effects() {
+        let settings = MasterSettings {
+            pan: 0.5,
+            ..MasterSettings::default()
+        };
+        let delay = Effect::Delay(crate::DelaySettings {
+            time_ms: 1.0,
+            feedback: 0.0,
+            mix: 1.0,
+        });
+        let mut block = vec![1.0; 2 * 100];
+        MasterBus::with_effects(settings, &[delay], 1000).process(&mut block, 2);
+        // The delay holds back the first frame, then balance scales the echo
+        assert_eq!(&block[..4], &[0.0, 0.0, 0.5, 1.0]);
+    }
+}
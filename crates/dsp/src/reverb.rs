@@ -0,0 +1,210 @@
+//! Reverb: a small room built from comb and allpass filters, after Freeverb.
+
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Processor;
+
+/// Comb lengths in frames at 44.1 kHz; ones that share no factors keep the
+/// echoes from lining up into a pitch
+const COMB_FRAMES: [usize; 4] = [1116, 1188, 1277, 1356];
+const ALLPASS_FRAMES: [usize; 2] = [556, 441];
+/// Frames the right channel's filters are longer by, so the sides differ
+const STEREO_SPREAD: usize = 23;
+/// Scale on the input, so the combs' sum doesn't run hot
+const INPUT_GAIN: f32 = 0.03;
+
+/// How big the space of a [`Reverb`] is and how much of it is heard.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReverbSettings {
+    /// From a small room (0.0) to a long hall (1.0)
+    pub room_size: f32,
+    /// How quickly the highs die away in the tail
+    pub damping: f32,
+    /// Balance from the dry input (0.0) to only the reverb (1.0)
+    pub mix: f32,
+}
+
+impl Default for ReverbSettings {
+    fn default() -> Self {
+        Self {
+            room_size: 0.5,
+            damping: 0.5,
+            mix: 0.25,
+        }
+    }
+}
+
+impl ReverbSettings {
+    pub(crate) fn parameters_mut(&mut self) -> Vec<(&'static str, &mut f32, RangeInclusive<f32>)> {
+        vec![
+            ("room_size", &mut self.room_size, 0.0..=1.0),
+            ("damping", &mut self.damping, 0.0..=1.0),
+            ("mix", &mut self.mix, 0.0..=1.0),
+        ]
+    }
+}
+
+/// A delay line fed back on itself through a lowpass
+#[derive(Debug, Clone)]
+struct Comb {
+    buffer: Vec<f32>,
+    index: usize,
+    /// Lowpass state, which damps the highs on each pass
+    filtered: f32,
+}
+
+impl Comb {
+    #[inline]
+    fn next(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.buffer[self.index];
+        self.filtered = output * (1.0 - damping) + self.filtered * damping;
+        self.buffer[self.index] = input + self.filtered * feedback;
+        self.index = (self.index + 1) % self.buffer.len();
+        output
+    }
+}
+
+/// A delay line that smears the echoes without coloring them
+#[derive(Debug, Clone)]
+struct Allpass {
+    buffer: Vec<f32>,
+    index: usize,
+}
+
+impl Allpass {
+    #[inline]
+    fn next(&mut self, input: f32) -> f32 {
+        let delayed = self.buffer[self.index];
+        self.buffer[self.index] = input + delayed * 0.5;
+        self.index = (self.index + 1) % self.buffer.len();
+        delayed - input
+    }
+}
+
+/// The filters for one output channel
+#[derive(Debug, Clone)]
+struct Tank {
+    combs: Vec<Comb>,
+    allpasses: Vec<Allpass>,
+}
+
+impl Tank {
+    fn new(sample_rate: u32, spread: usize) -> Self {
+        let frames = |frames: usize| ((frames + spread) * sample_rate as usize / 44100).max(1);
+        Self {
+            combs: COMB_FRAMES
+                .iter()
+                .map(|&length| Comb {
+                    buffer: vec![0.0; frames(length)],
+                    index: 0,
+                    filtered: 0.0,
+                })
+                .collect(),
+            allpasses: ALLPASS_FRAMES
+                .iter()
+                .map(|&length| Allpass {
+                    buffer: vec![0.0; frames(length)],
+                    index: 0,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// The reverb itself. Both channels are fed the same mono sum and come out
+/// different, which widens the space; with more than two channels only the
+/// first two are reverberated.
+#[derive(Debug, Clone)]
+pub struct Reverb {
+    settings: ReverbSettings,
+    tanks: [Tank; 2],
+}
+
+impl Reverb {
+    pub fn new(settings: ReverbSettings, sample_rate: u32) -> Self {
+        Self {
+            settings,
+            tanks: [
+                Tank::new(sample_rate, 0),
+                Tank::new(sample_rate, STEREO_SPREAD),
+            ],
+        }
+    }
+
+    pub fn settings(&self) -> ReverbSettings {
+        self.settings
+    }
+
+    /// Change the settings, keeping the tail that's ringing
+    pub fn set(&mut self, settings: ReverbSettings) {
+        self.settings = settings;
+    }
+}
+
+impl Processor for Reverb {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        let ReverbSettings {
+            room_size,
+            damping,
+            mix,
+        } = self.settings;
+        let feedback = 0.7 + room_size.clamp(0.0, 1.0) * 0.28;
+        let damping = damping.clamp(0.0, 1.0) * 0.4;
+        let reverberated = channels.min(self.tanks.len());
+        for frame in block.chunks_mut(channels) {
+            let input = frame[..reverberated].iter().sum::<f32>() / reverberated as f32;
+            let input = input * INPUT_GAIN;
+            for (sample, tank) in frame.iter_mut().zip(&mut self.tanks) {
+                let mut wet = tank
+                    .combs
+                    .iter_mut()
+                    .map(|comb| comb.next(input, feedback, damping))
+                    .sum::<f32>();
+                for allpass in &mut tank.allpasses {
+                    wet = allpass.next(wet);
+                }
+                *sample = *sample * (1.0 - mix) + wet * mix;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_a_click_rings_on_and_dies_away() {
+        let mut block = vec![0.0; 2 * 48000 * 4];
+        block[0] = 1.0;
+        block[1] = 1.0;
+        let mut reverb = Reverb::new(
+            ReverbSettings {
+                mix: 1.0,
+                ..ReverbSettings::default()
+            },
+            48000,
+        );
+        for chunk in block.chunks_mut(2 * 256) {
+            reverb.process(chunk, 2);
+        }
+
+        let energy = |seconds: std::ops::Range<usize>| {
+            block[seconds.start * 96000..seconds.end * 96000]
+                .iter()
+                .map(|sample| sample * sample)
+                .sum::<f32>()
+        };
+        assert!(energy(0..1) > 0.0);
+        assert!(energy(3..4) < energy(0..1) / 100.0);
+        // The two sides don't come out the same
+        assert!(
+            block[2000..4000]
+                .chunks(2)
+                .any(|frame| frame[0] != frame[1])
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! Per-sample smoothing of parameter changes.
+//!
+//! Jumping a gain from one value to another between two samples is heard as a click.
+//! A [`Smoother`] moves towards a new target over a short time instead, one sample at
+//! a time, so parameters set from a UI or automation can be applied at audio rate.
+
+/// How close a one-pole smoother has to get to its target before snapping onto it
+const SETTLE_THRESHOLD: f32 = 1e-5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Curve {
+    /// Reach the target in a fixed number of frames at a constant rate
+    Linear {
+        frames: u32,
+        step: f32,
+        remaining: u32,
+    },
+    /// Close a fixed fraction of the distance to the target every frame
+    OnePole { coefficient: f32 },
+}
+
+/// A parameter value that glides towards its target one frame at a time.
+///
+/// Two shapes are available: a [linear ramp](Self::linear), which reaches the target
+/// in an exact number of frames and suits mutes and fades that must end on time, and a
+/// [one-pole](Self::one_pole) low-pass, which slows down as it approaches the target
+/// and suits faders that are moved continuously.
+///
+/// # Examples
+///
+/// ```
+/// use daw_dsp::Smoother;
+///
+/// let mut gain = Smoother::linear(1.0, 4);
+/// gain.set_target(0.0);
+/// let ramp: Vec<f32> = (0..5).map(|_| gain.next()).collect();
+/// assert_eq!(ramp, vec![0.75, 0.5, 0.25, 0.0, 0.0]);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Smoother {
+    current: f32,
+    target: f32,
+    curve: Curve,
+}
+
+impl Smoother {
+    /// A smoother starting at `value` that ramps to each new target in `frames` frames.
+    ///
+    /// A ramp of zero frames jumps straight to the target.
+    pub fn linear(value: f32, frames: u32) -> Self {
+        Self {
+            current: value,
+            target: value,
+            curve: Curve::Linear {
+                frames,
+                step: 0.0,
+                remaining: 0,
+            },
+        }
+    }
+
+    /// A smoother starting at `value` that follows new targets with a time constant of
+    /// `time_constant` frames: after that many frames it has covered about 63% of the
+    /// distance, and after five times as many it is within 1%.
+    ///
+    /// A time constant of zero or less jumps straight to the target.
+    pub fn one_pole(value: f32, time_constant: f32) -> Self {
+        let coefficient = if time_constant > 0.0 {
+            1.0 - (-1.0 / time_constant).exp()
+        } else {
+            1.0
+        };
+        Self {
+            current: value,
+            target: value,
+            curve: Curve::OnePole { coefficient },
+        }
+    }
+
+    /// Number of frames covering `seconds` at `sample_rate`, for sizing ramps and time
+    /// constants
+    pub fn frames_for(seconds: f32, sample_rate: u32) -> f32 {
+        seconds * sample_rate as f32
+    }
+
+    /// The value the smoother is heading towards
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
+    /// The value most recently produced
+    pub fn value(&self) -> f32 {
+        self.current
+    }
+
+    /// Whether the smoother has reached its target and will keep producing it
+    pub fn is_settled(&self) -> bool {
+        self.current == self.target
+    }
+
+    /// Start gliding towards `target` from the current value.
+    ///
+    /// Setting the target it already has leaves a ramp in progress untouched.
+    pub fn set_target(&mut self, target: f32) {
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        if let Curve::Linear {
+            frames,
+            step,
+            remaining,
+        } = &mut self.curve
+        {
+            if *frames == 0 {
+                self.current = target;
+                *remaining = 0;
+            } else {
+                *step = (target - self.current) / *frames as f32;
+                *remaining = *frames;
+            }
+        }
+    }
+
+    /// Jump to `value` at once, with no glide
+    pub fn reset(&mut self, value: f32) {
+        self.current = value;
+        self.target = value;
+        if let Curve::Linear { remaining, .. } = &mut self.curve {
+            *remaining = 0;
+        }
+    }
+
+    /// Jump to the current target, skipping whatever glide is left
+    pub fn settle(&mut self) {
+        self.reset(self.target);
+    }
+
+    /// Advance one frame and return the new value
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> f32 {
+        if self.is_settled() {
+            return self.current;
+        }
+        match &mut self.curve {
+            Curve::Linear {
+                step, remaining, ..
+            } => {
+                *remaining = remaining.saturating_sub(1);
+                self.current = if *remaining == 0 {
+                    self.target
+                } else {
+                    self.current + *step
+                };
+            }
+            Curve::OnePole { coefficient } => {
+                self.current += (self.target - self.current) * *coefficient;
+                if (self.target - self.current).abs() < SETTLE_THRESHOLD {
+                    self.current = self.target;
+                }
+            }
+        }
+        self.current
+    }
+
+    /// Advance `frames` frames at once, as if [`next`](Self::next) had been called
+    /// that many times
+    pub fn skip(&mut self, frames: u32) {
+        if self.is_settled() {
+            return;
+        }
+        match &mut self.curve {
+            Curve::Linear {
+                step, remaining, ..
+            } => {
+                if frames >= *remaining {
+                    self.settle();
+                } else {
+                    *remaining -= frames;
+                    self.current += *step * frames as f32;
+                }
+            }
+            Curve::OnePole { .. } => {
+                for _ in 0..frames {
+                    if self.next() == self.target {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fill `out` with the next `out.len()` values
+    pub fn fill(&mut self, out: &mut [f32]) {
+        if self.is_settled() {
+            out.fill(self.current);
+            return;
+        }
+        for value in out {
+            *value = self.next();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frames until the smoother first lands exactly on its target
+    fn frames_to_settle(smoother: &mut Smoother) -> usize {
+        (1..=1_000_000)
+            .find(|_| {
+                smoother.next();
+                smoother.is_settled()
+            })
+            .expect("smoother never settled")
+    }
+
+    #[test]
+    fn test_linear_settles_in_exact_frames() {
+        let mut smoother = Smoother::linear(0.0, 480);
+        smoother.set_target(1.0);
+        assert!(!smoother.is_settled());
+        assert_eq!(frames_to_settle(&mut smoother), 480);
+        assert_eq!(smoother.value(), 1.0);
+
+        // Ramps take the same time whatever the distance
+        smoother.set_target(0.9);
+        assert_eq!(frames_to_settle(&mut smoother), 480);
+        assert_eq!(smoother.value(), 0.9);
+    }
+
+    #[test]
+    fn test_linear_retarget_mid_ramp_starts_from_current_value() {
+        let mut smoother = Smoother::linear(0.0, 10);
+        smoother.set_target(1.0);
+        smoother.skip(5);
+        assert!((smoother.value() - 0.5).abs() < 1e-6);
+
+        smoother.set_target(0.0);
+        let next = smoother.next();
+        assert!((next - 0.45).abs() < 1e-6);
+        assert_eq!(frames_to_settle(&mut smoother), 9);
+    }
+
+    #[test]
+    fn test_one_pole_time_constant() {
+        let sample_rate = 48000;
+        let tau = Smoother::frames_for(0.005, sample_rate);
+        let mut smoother = Smoother::one_pole(0.0, tau);
+        smoother.set_target(1.0);
+
+        // About 63% after one time constant, within 1% after five
+        smoother.skip(tau as u32);
+        assert!(
+            (smoother.value() - 0.632).abs() < 0.01,
+            "{}",
+            smoother.value()
+        );
+        smoother.skip(4 * tau as u32);
+        assert!(smoother.value() > 0.99 && smoother.value() < 1.0);
+
+        // And then lands exactly on the target rather than creeping forever
+        let remaining = frames_to_settle(&mut smoother);
+        assert!(remaining < 10 * tau as usize, "{remaining}");
+        assert_eq!(smoother.value(), 1.0);
+    }
+
+    #[test]
+    fn test_one_pole_is_monotonic() {
+        let mut smoother = Smoother::one_pole(1.0, 100.0);
+        smoother.set_target(0.25);
+        let mut values = vec![0.0; 2000];
+        smoother.fill(&mut values);
+        assert!(values.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(values.iter().all(|&value| value >= 0.25));
+        assert_eq!(*values.last().unwrap(), 0.25);
+    }
+
+    #[test]
+    fn test_zero_time_jumps_and_reset_skips_glide() {
+        let mut linear = Smoother::linear(0.0, 0);
+        linear.set_target(0.5);
+        assert_eq!(linear.value(), 0.5);
+        let mut one_pole = Smoother::one_pole(0.0, 0.0);
+        one_pole.set_target(0.5);
+        assert_eq!(one_pole.next(), 0.5);
+
+        let mut smoother = Smoother::linear(0.0, 100);
+        smoother.set_target(1.0);
+        smoother.next();
+        smoother.reset(0.25);
+        assert!(smoother.is_settled());
+        assert_eq!((smoother.next(), smoother.target()), (0.25, 0.25));
+    }
+
+    #[test]
+    fn test_skip_matches_stepping() {
+        for mut smoother in [Smoother::linear(0.0, 64), Smoother::one_pole(0.0, 16.0)] {
+            smoother.set_target(1.0);
+            let mut stepped = smoother;
+            smoother.skip(20);
+            for _ in 0..20 {
+                stepped.next();
+            }
+            assert!((smoother.value() - stepped.value()).abs() < 1e-5);
+        }
+    }
+}
@@ -1,9 +1,8 @@
 use daw_core::{
-    Clip, PPQN, Project, SampleRef, Session, TimeSignature, Track, TrackId, WaveformData,
-    samples_to_ticks, strip_samples_root,
+    AudioArc, Clip, ClipContent, ClipSource, EndBehavior, IdAllocator, InsertLength, LOCATORS,
+    MasterData, PPQN, Project, SampleLibrary, SampleRef, Session, TimeContext, TimeSignature,
+    Track, TrackId, WaveformData, decode_audio_arc, strip_samples_root,
 };
-use daw_decode::decode_audio_arc;
-use daw_transport::AudioArc;
 use eframe::egui;
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -12,6 +11,19 @@ use std::sync::Arc;
 const SAMPLES_DIR: &str = "samples";
 const NUM_STEPS: usize = 16;
 const DEFAULT_TRACKS: usize = 4;
+/// Longest gap between two Esc presses that still counts as the panic shortcut
+const PANIC_DOUBLE_PRESS_SECS: f64 = 0.5;
+/// Keys that play each track's sample live, from the top track down
+const PAD_KEYS: [egui::Key; 8] = [
+    egui::Key::A,
+    egui::Key::S,
+    egui::Key::D,
+    egui::Key::F,
+    egui::Key::G,
+    egui::Key::H,
+    egui::Key::J,
+    egui::Key::K,
+];
 
 fn main() -> eframe::Result<()> {
     let options = eframe::NativeOptions {
@@ -65,6 +77,10 @@ struct SequencerApp {
     error_message: Option<String>,
     show_inspector: bool,
     current_project: Option<Project>,
+    /// When Esc was last pressed, in egui input time
+    last_escape: Option<f64>,
+    /// Whether pad keys pressed during playback also set the step under the playhead
+    record_pads: bool,
 }
 
 impl SequencerApp {
@@ -93,29 +109,18 @@ impl SequencerApp {
             error_message: None,
             show_inspector: false,
             current_project: None,
+            last_escape: None,
+            record_pads: false,
         }
     }
 
     fn scan_samples() -> Vec<PathBuf> {
-        fn scan_dir(dir: &str, samples: &mut Vec<PathBuf>) {
-            if let Ok(entries) = std::fs::read_dir(dir) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    if path.is_dir() {
-                        if let Some(path_str) = path.to_str() {
-                            scan_dir(path_str, samples);
-                        }
-                    } else if path.extension().is_some_and(|e| e == "wav") {
-                        samples.push(path);
-                    }
-                }
-            }
-        }
-
-        let mut samples = Vec::new();
-        scan_dir(SAMPLES_DIR, &mut samples);
-        samples.sort();
-        samples
+        let mut library = SampleLibrary::new([SAMPLES_DIR]);
+        library.scan();
+        library
+            .samples()
+            .map(|sample| sample.path.clone())
+            .collect()
     }
 
     fn load_sample(&mut self, track_idx: usize, path: &PathBuf) {
@@ -135,13 +140,51 @@ impl SequencerApp {
         }
     }
 
+    /// Play a sample from the browser without loading it onto a track
+    fn preview_sample(&mut self, path: &PathBuf) {
+        let Some(session) = self.ensure_session() else {
+            return;
+        };
+        if let Err(e) = session.preview_sample(path) {
+            self.error_message = Some(format!("Failed to preview sample: {}", e));
+        }
+    }
+
     fn ticks_per_step() -> u64 {
         PPQN / 4
     }
 
+    /// The session's length for inserted samples, the default until there's one
+    fn insert_length(&self) -> InsertLength {
+        self.session
+            .as_ref()
+            .map_or_else(InsertLength::default, Session::insert_length)
+    }
+
+    /// Length of a step clip for the given sample at the current tempo
+    fn clip_length_ticks(&self, audio: &AudioArc) -> u64 {
+        let time_context = TimeContext::new(self.tempo, self.time_signature);
+        self.insert_length().length_ticks(
+            audio,
+            0,
+            &time_context.tempo_curve(),
+            time_context.time_signature,
+        )
+    }
+
+    fn rebuild_session_tracks(&mut self) {
+        if self.session.is_some() {
+            let tracks = self.build_transport_tracks();
+            if let Some(ref mut session) = self.session {
+                session.set_tracks(tracks);
+            }
+        }
+    }
+
     fn build_transport_tracks(&self) -> Vec<Track> {
         let ticks_per_step = Self::ticks_per_step();
         let ticks_per_bar = NUM_STEPS as u64 * ticks_per_step;
+        let mut ids = IdAllocator::default();
 
         self.tracks
             .iter()
@@ -163,22 +206,25 @@ impl SequencerApp {
                             if active {
                                 let waveform = WaveformData::from_audio_arc(audio, 512);
                                 let start_tick = (step_idx as u64) * ticks_per_step;
-                                // Use audio length in samples to calculate end tick
-                                let audio_frames =
-                                    audio.samples().len() / audio.channels() as usize;
-                                let audio_ticks = samples_to_ticks(
-                                    audio_frames as f64,
-                                    120.0, // default tempo for duration calculation
-                                    audio.sample_rate(),
+                                let audio_ticks = self.clip_length_ticks(audio);
+                                transport_track.insert_clip(
+                                    Clip {
+                                        id: ids.clip_id(),
+                                        start_tick,
+                                        end_tick: start_tick + audio_ticks,
+                                        content: ClipContent::Audio(audio.clone()),
+                                        waveform: Arc::new(waveform),
+                                        audio_offset: 0,
+                                        name: format!("{} {}", track.sample_name, segment_num),
+                                        fade_in_ticks: 0,
+                                        fade_out_ticks: 0,
+                                        gain: 1.0,
+                                        looped: false,
+                                        reversed: false,
+                                        source: Default::default(),
+                                    },
+                                    &mut ids,
                                 );
-                                transport_track.insert_clip(Clip {
-                                    start_tick,
-                                    end_tick: start_tick + audio_ticks,
-                                    audio: audio.clone(),
-                                    waveform: Arc::new(waveform),
-                                    audio_offset: 0,
-                                    name: format!("{} {}", track.sample_name, segment_num),
-                                });
                                 segment_num += 1;
                             }
                         }
@@ -192,22 +238,25 @@ impl SequencerApp {
                             if active {
                                 let waveform = WaveformData::from_audio_arc(audio, 512);
                                 let start_tick = bar_offset + (step_idx as u64) * ticks_per_step;
-                                // Use audio length in samples to calculate end tick
-                                let audio_frames =
-                                    audio.samples().len() / audio.channels() as usize;
-                                let audio_ticks = samples_to_ticks(
-                                    audio_frames as f64,
-                                    120.0, // default tempo for duration calculation
-                                    audio.sample_rate(),
+                                let audio_ticks = self.clip_length_ticks(audio);
+                                transport_track.insert_clip(
+                                    Clip {
+                                        id: ids.clip_id(),
+                                        start_tick,
+                                        end_tick: start_tick + audio_ticks,
+                                        content: ClipContent::Audio(audio.clone()),
+                                        waveform: Arc::new(waveform),
+                                        audio_offset: 0,
+                                        name: format!("{} {}", track.sample_name, segment_num),
+                                        fade_in_ticks: 0,
+                                        fade_out_ticks: 0,
+                                        gain: 1.0,
+                                        looped: false,
+                                        reversed: false,
+                                        source: Default::default(),
+                                    },
+                                    &mut ids,
                                 );
-                                transport_track.insert_clip(Clip {
-                                    start_tick,
-                                    end_tick: start_tick + audio_ticks,
-                                    audio: audio.clone(),
-                                    waveform: Arc::new(waveform),
-                                    audio_offset: 0,
-                                    name: format!("{} {}", track.sample_name, segment_num),
-                                });
                                 segment_num += 1;
                             }
                         }
@@ -224,23 +273,27 @@ impl SequencerApp {
     }
 
     fn start_playback(&mut self) {
-        // Reuse existing session if available (preserves resample cache)
-        if let Some(ref mut session) = self.session {
+        if let Some(session) = self.ensure_session() {
             session.play();
-            return;
         }
+    }
 
-        let tracks = self.build_transport_tracks();
-        match Session::new(tracks, self.tempo, self.time_signature) {
-            Ok(mut session) => {
-                session.play();
-                self.session = Some(session);
-                self.error_message = None;
-            }
-            Err(e) => {
-                self.error_message = Some(format!("Failed to start playback: {}", e));
+    /// The session, started with the current tracks if there isn't one yet
+    fn ensure_session(&mut self) -> Option<&mut Session> {
+        // Reuse existing session if available (preserves resample cache)
+        if self.session.is_none() {
+            let tracks = self.build_transport_tracks();
+            match Session::new(tracks, self.tempo, self.time_signature) {
+                Ok(session) => {
+                    self.session = Some(session);
+                    self.error_message = None;
+                }
+                Err(e) => {
+                    self.error_message = Some(format!("Failed to start audio: {}", e));
+                }
             }
         }
+        self.session.as_mut()
     }
 
     fn stop_playback(&mut self) {
@@ -288,6 +341,7 @@ impl SequencerApp {
         let ticks_per_bar = NUM_STEPS as u64 * ticks_per_step;
 
         let mut sample_refs: HashMap<String, SampleRef> = HashMap::new();
+        let mut ids = IdAllocator::default();
         let transport_tracks: Vec<Track> = self
             .tracks
             .iter()
@@ -310,25 +364,30 @@ impl SequencerApp {
                         if active {
                             let clip_name = format!("{} {}", track.sample_name, clip_num);
                             // Store as DevRoot reference (relative to samples/ directory)
-                            sample_refs.insert(
-                                clip_name.clone(),
-                                SampleRef::DevRoot(sample_path.clone()),
-                            );
+                            sample_refs
+                                .insert(clip_name.clone(), SampleRef::DevRoot(sample_path.clone()));
 
                             let waveform = WaveformData::from_audio_arc(audio, 512);
                             let start_tick = bar_offset + (step_idx as u64) * ticks_per_step;
-                            // Use audio length in samples to calculate end tick
-                            let audio_frames = audio.samples().len() / audio.channels() as usize;
-                            let audio_ticks =
-                                samples_to_ticks(audio_frames as f64, 120.0, audio.sample_rate());
-                            transport_track.insert_clip(Clip {
-                                start_tick,
-                                end_tick: start_tick + audio_ticks,
-                                audio: audio.clone(),
-                                waveform: Arc::new(waveform),
-                                audio_offset: 0,
-                                name: clip_name,
-                            });
+                            let audio_ticks = self.clip_length_ticks(audio);
+                            transport_track.insert_clip(
+                                Clip {
+                                    id: ids.clip_id(),
+                                    start_tick,
+                                    end_tick: start_tick + audio_ticks,
+                                    content: ClipContent::Audio(audio.clone()),
+                                    waveform: Arc::new(waveform),
+                                    audio_offset: 0,
+                                    name: clip_name,
+                                    fade_in_ticks: 0,
+                                    fade_out_ticks: 0,
+                                    gain: 1.0,
+                                    looped: false,
+                                    reversed: false,
+                                    source: ClipSource::from_file(sample_path, audio.sample_rate()),
+                                },
+                                &mut ids,
+                            );
                             clip_num += 1;
                         }
                     }
@@ -433,7 +492,7 @@ impl SequencerApp {
                                             .unwrap_or_else(|| "Unknown".to_string());
                                     }
                                     // Use the already-decoded audio from the segment
-                                    track_state.audio = Some(clip.audio.clone());
+                                    track_state.audio = clip.audio().cloned();
                                 }
                             }
                         }
@@ -475,12 +534,10 @@ impl SequencerApp {
                     for (step_idx, &active) in page_steps.iter().enumerate() {
                         if active {
                             let start_tick = bar_offset + (step_idx as u64) * ticks_per_step;
-                            // Calculate end tick from audio duration
-                            let audio_frames = audio.samples().len() / audio.channels() as usize;
-                            let audio_ticks =
-                                samples_to_ticks(audio_frames as f64, 120.0, audio.sample_rate());
+                            let audio_ticks = self.clip_length_ticks(audio);
 
                             segments.push(daw_core::ClipData {
+                                id: None,
                                 name: format!("{} {}", track.sample_name, segment_num),
                                 start_tick,
                                 end_tick: start_tick + audio_ticks,
@@ -488,6 +545,12 @@ impl SequencerApp {
                                     track.sample_path.clone().unwrap_or_default(),
                                 ),
                                 audio_offset: 0,
+                                fade_in_ticks: 0,
+                                fade_out_ticks: 0,
+                                gain: 1.0,
+                                looped: false,
+                                reversed: false,
+                                source: Default::default(),
                             });
                             segment_num += 1;
                         }
@@ -502,6 +565,14 @@ impl SequencerApp {
                     pan: 0.0,
                     enabled: true,
                     solo: false,
+                    color: None,
+                    metadata: Default::default(),
+                    parent: None,
+                    is_folder: false,
+                    automation: Vec::new(),
+                    effects: Vec::new(),
+                    is_bus: false,
+                    sends: Vec::new(),
                 })
             })
             .collect();
@@ -514,13 +585,132 @@ impl SequencerApp {
                 self.time_signature.denominator,
             ),
             tracks,
+            // Filled in from the tracks when the project is loaded
+            next_track_id: 0,
+            next_clip_id: 0,
+            end_behavior: self
+                .session
+                .as_ref()
+                .map_or(EndBehavior::default(), Session::end_behavior),
+            locators: self
+                .session
+                .as_ref()
+                .map(|session| session.locators().clone())
+                .unwrap_or_default(),
+            embedded: Default::default(),
+            session_state: None,
+            master: MasterData::default(),
+            tempo_map: Default::default(),
+            info: Default::default(),
+            sample_rate: None,
+        }
+    }
+
+    /// Pressing Esc twice in quick succession mutes everything and stops playback.
+    fn handle_panic_key(&mut self, ctx: &egui::Context) {
+        let Some(now) = ctx.input(|i| i.key_pressed(egui::Key::Escape).then_some(i.time)) else {
+            return;
+        };
+        match self.last_escape {
+            Some(last) if now - last <= PANIC_DOUBLE_PRESS_SECS => {
+                self.last_escape = None;
+                if let Some(ref mut session) = self.session {
+                    session.panic();
+                }
+                self.current_step = 0;
+            }
+            _ => self.last_escape = Some(now),
+        }
+    }
+
+    /// Pad keys play the sample of the matching track right away. With pad recording
+    /// on and playback running, the step under the playhead is set as well.
+    fn handle_pad_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let pressed: Vec<usize> = ctx.input(|i| {
+            if i.modifiers.any() {
+                return Vec::new();
+            }
+            (0..PAD_KEYS.len().min(self.tracks.len()))
+                .filter(|&track_idx| i.key_pressed(PAD_KEYS[track_idx]))
+                .filter(|&track_idx| self.tracks[track_idx].audio.is_some())
+                .collect()
+        });
+        if pressed.is_empty() {
+            return;
+        }
+
+        let mut steps_set = false;
+        for track_idx in pressed {
+            let track = &self.tracks[track_idx];
+            let (Some(audio), volume) = (track.audio.clone(), track.volume) else {
+                continue;
+            };
+            let Some(session) = self.ensure_session() else {
+                return;
+            };
+            let played = session.play_sample(track_idx as u64, &audio, volume);
+            let is_playing = session.is_playing();
+            if let Err(e) = played {
+                self.error_message = Some(format!("Failed to play sample: {}", e));
+            }
+
+            if self.record_pads && is_playing {
+                let track = &mut self.tracks[track_idx];
+                while track.pages.len() <= self.playback_page {
+                    track.pages.push([false; NUM_STEPS]);
+                }
+                track.pages[self.playback_page][self.current_step] = true;
+                steps_set = true;
+            }
+        }
+        if steps_set {
+            self.rebuild_session_tracks();
+        }
+    }
+
+    /// Number keys jump to locators 1-9; with Cmd/Ctrl held they store one instead.
+    fn handle_locator_keys(&mut self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
         }
+        let Some(ref mut session) = self.session else {
+            return;
+        };
+        const KEYS: [egui::Key; 9] = [
+            egui::Key::Num1,
+            egui::Key::Num2,
+            egui::Key::Num3,
+            egui::Key::Num4,
+            egui::Key::Num5,
+            egui::Key::Num6,
+            egui::Key::Num7,
+            egui::Key::Num8,
+            egui::Key::Num9,
+        ];
+        ctx.input(|i| {
+            for (key, n) in KEYS.into_iter().zip(LOCATORS) {
+                if !i.key_pressed(key) {
+                    continue;
+                }
+                if i.modifiers.command {
+                    session.set_locator_here(n);
+                } else {
+                    session.goto_locator(n);
+                }
+            }
+        });
     }
 }
 
 impl eframe::App for SequencerApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.update_playback_position();
+        self.handle_locator_keys(ctx);
+        self.handle_pad_keys(ctx);
+        self.handle_panic_key(ctx);
 
         let is_playing = self.session.as_ref().map_or(false, |s| s.is_playing());
         if is_playing {
@@ -567,6 +757,10 @@ impl eframe::App for SequencerApp {
                             if let Some(ref mut session) = self.session {
                                 session.set_tempo(t);
                             }
+                            // Audio-length clips span a different number of ticks now
+                            if self.insert_length() == InsertLength::AudioLength {
+                                self.rebuild_session_tracks();
+                            }
                         } else {
                             self.tempo_input = format!("{:.0}", self.tempo);
                         }
@@ -608,9 +802,48 @@ impl eframe::App for SequencerApp {
                     }
                 }
 
+                // Shown while the panic shortcut has the master muted
+                let master_muted = self.session.as_ref().is_some_and(Session::master_muted);
+                if master_muted && ui.button("🔇 Unmute").clicked() {
+                    if let Some(ref mut session) = self.session {
+                        session.set_master_muted(false);
+                    }
+                }
+
+                ui.checkbox(&mut self.record_pads, "⏺ Record Pads")
+                    .on_hover_text("Keys A to K play tracks 1 to 8; while recording, hits during playback set steps");
+
                 if ui.button("+ Add Track").clicked() {
                     self.tracks.push(SequencerTrackState::default());
                 }
+
+                ui.add_space(20.0);
+
+                // Clip length for each triggered step
+                ui.label("Insert as:");
+                let previous_length = self.insert_length();
+                let mut insert_length = previous_length;
+                egui::ComboBox::from_id_salt("insert_length")
+                    .selected_text(match insert_length {
+                        InsertLength::AudioLength => "Audio length",
+                        InsertLength::Beat => "1 beat",
+                        InsertLength::Bar => "1 bar",
+                    })
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(
+                            &mut insert_length,
+                            InsertLength::AudioLength,
+                            "Audio length",
+                        );
+                        ui.selectable_value(&mut insert_length, InsertLength::Beat, "1 beat");
+                        ui.selectable_value(&mut insert_length, InsertLength::Bar, "1 bar");
+                    });
+                if insert_length != previous_length {
+                    if let Some(session) = self.ensure_session() {
+                        session.set_insert_length(insert_length);
+                    }
+                    self.rebuild_session_tracks();
+                }
             });
 
             ui.separator();
@@ -752,6 +985,7 @@ impl eframe::App for SequencerApp {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 let samples = self.available_samples.clone();
                 let mut load_requests: Vec<(usize, PathBuf)> = Vec::new();
+                let mut preview_request: Option<PathBuf> = None;
                 let mut tracks_modified = false;
 
                 for (track_idx, track) in self.tracks.iter_mut().enumerate() {
@@ -773,9 +1007,15 @@ impl eframe::App for SequencerApp {
                                         .map(|s| s.to_string_lossy().to_string())
                                         .unwrap_or_else(|| "Unknown".to_string());
 
-                                    if ui.selectable_label(false, &name).clicked() {
-                                        load_requests.push((track_idx, sample_path.clone()));
-                                    }
+                                    ui.horizontal(|ui| {
+                                        if ui.small_button("🔊").on_hover_text("Preview").clicked()
+                                        {
+                                            preview_request = Some(sample_path.clone());
+                                        }
+                                        if ui.selectable_label(false, &name).clicked() {
+                                            load_requests.push((track_idx, sample_path.clone()));
+                                        }
+                                    });
                                 }
                             });
 
@@ -829,6 +1069,9 @@ impl eframe::App for SequencerApp {
                     });
                 }
 
+                if let Some(path) = preview_request {
+                    self.preview_sample(&path);
+                }
                 for (track_idx, path) in load_requests {
                     self.load_sample(track_idx, &path);
                 }
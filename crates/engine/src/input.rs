@@ -0,0 +1,158 @@
+//! Capturing audio from an input device, for recording.
+//!
+//! The input stream's callback only pushes samples into a lock-free queue; the
+//! session drains it from its own thread with [`AudioInput::read`], so recording
+//! never blocks the audio thread.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+/// Seconds of input the queue holds; more than that between reads is dropped
+const INPUT_QUEUE_SECONDS: usize = 10;
+
+/// Audio coming in from an input device, or from a queue fed by the caller.
+pub struct AudioInput {
+    samples: rtrb::Consumer<f32>,
+    channels: u16,
+    sample_rate: u32,
+    /// Frames the stream couldn't fit in the queue since the last
+    /// [`AudioInput::take_dropped_frames`]
+    dropped_frames: Arc<AtomicU64>,
+    /// Input stream, or `None` for an input fed through [`AudioInput::from_queue`]
+    _stream: Option<cpal::Stream>,
+}
+
+// SAFETY: like AudioEngineHandle, the stream is only touched when it's built and
+// dropped; samples reach this thread through the lock-free queue.
+unsafe impl Send for AudioInput {}
+
+impl AudioInput {
+    /// Open and start a stream on the default input device, at its default config
+    pub fn open() -> anyhow::Result<Self> {
+        let host = cpal::default_host();
+        let device = host
+            .default_input_device()
+            .ok_or_else(|| anyhow::anyhow!("no input device found"))?;
+        let config = device.default_input_config()?;
+        let channels = config.channels();
+        let sample_rate = config.sample_rate().0;
+        if config.sample_format() != cpal::SampleFormat::F32 {
+            anyhow::bail!("unsupported sample format '{}'", config.sample_format());
+        }
+
+        let (mut samples_tx, samples_rx) = rtrb::RingBuffer::<f32>::new(
+            sample_rate as usize * channels as usize * INPUT_QUEUE_SECONDS,
+        );
+        let dropped_frames = Arc::new(AtomicU64::new(0));
+
+        let dropped = dropped_frames.clone();
+        let stream = device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                let frames = push_frames(&mut samples_tx, data, channels as usize);
+                if frames > 0 {
+                    dropped.fetch_add(frames as u64, Ordering::Relaxed);
+                }
+            },
+            |err| eprintln!("input stream error: {err}"),
+            None,
+        )?;
+        stream.play()?;
+
+        Ok(Self {
+            samples: samples_rx,
+            channels,
+            sample_rate,
+            dropped_frames,
+            _stream: Some(stream),
+        })
+    }
+
+    /// An input that isn't connected to a device: interleaved samples pushed into
+    /// the returned queue are read as if they came from one. Holds up to
+    /// `capacity` samples at a time; what the caller can't push is its own to
+    /// count.
+    pub fn from_queue(
+        channels: u16,
+        sample_rate: u32,
+        capacity: usize,
+    ) -> (rtrb::Producer<f32>, Self) {
+        let (samples_tx, samples_rx) = rtrb::RingBuffer::<f32>::new(capacity);
+        let input = Self {
+            samples: samples_rx,
+            channels,
+            sample_rate,
+            dropped_frames: Arc::new(AtomicU64::new(0)),
+            _stream: None,
+        };
+        (samples_tx, input)
+    }
+
+    pub fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Append the interleaved samples that came in since the last read to `into`
+    pub fn read(&mut self, into: &mut Vec<f32>) {
+        let available = self.samples.slots();
+        if let Ok(chunk) = self.samples.read_chunk(available) {
+            into.extend(chunk);
+        }
+    }
+
+    /// Frames lost since the last call because the queue was full, as when
+    /// nothing read it for longer than it holds
+    pub fn take_dropped_frames(&self) -> u64 {
+        self.dropped_frames.swap(0, Ordering::Relaxed)
+    }
+}
+
+/// Push the whole frames of `data` that fit into `queue`, returning how many
+/// frames didn't, so a full queue never splits a frame across its channels
+fn push_frames(queue: &mut rtrb::Producer<f32>, data: &[f32], channels: usize) -> usize {
+    let frames = data.len() / channels;
+    let fits = frames.min(queue.slots() / channels);
+    if let Ok(chunk) = queue.write_chunk_uninit(fits * channels) {
+        chunk.fill_from_iter(data.iter().copied());
+    }
+    frames - fits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_queued_input_is_read_in_order() {
+        let (mut tx, mut input) = AudioInput::from_queue(2, 48000, 8);
+        for sample in [0.1, 0.2, 0.3, 0.4] {
+            tx.push(sample).unwrap();
+        }
+        let mut samples = Vec::new();
+        input.read(&mut samples);
+        tx.push(0.5).unwrap();
+        input.read(&mut samples);
+        input.read(&mut samples);
+        assert_eq!(samples, [0.1, 0.2, 0.3, 0.4, 0.5]);
+        assert_eq!(input.channels(), 2);
+    }
+
+    #[test]
+    fn test_a_full_queue_keeps_whole_frames_and_counts_the_rest() {
+        let (mut tx, mut rx) = rtrb::RingBuffer::<f32>::new(5);
+        let dropped = push_frames(&mut tx, &[0.1, 0.2, 0.3, 0.4, 0.5, 0.6], 2);
+        assert_eq!(dropped, 1);
+        assert_eq!(rx.slots(), 4);
+        let chunk = rx.read_chunk(4).unwrap();
+        assert_eq!(chunk.into_iter().collect::<Vec<_>>(), [0.1, 0.2, 0.3, 0.4]);
+        // The slot left over in a queue of five can't hold a frame
+        assert_eq!(push_frames(&mut tx, &[0.7, 0.8, 0.9, 1.0, 1.1, 1.2], 2), 1);
+        assert_eq!(push_frames(&mut tx, &[1.3, 1.4], 2), 1);
+    }
+}
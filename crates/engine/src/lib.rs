@@ -1,9 +1,30 @@
-use basedrop::{Collector, Handle, Shared};
+use basedrop::{Collector, Handle, Owned, Shared};
 use cpal::{
     FromSample, SizedSample,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
-use daw_transport::AudioArc;
+use daw_dsp::{Effect, InsertChain, MasterBus, MasterSettings, Processor};
+use daw_midi::MidiReceiver;
+use daw_transport::{AudioArc, Sampler, TrackAutomation};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod input;
+mod mixer;
+mod voices;
+
+pub use input::AudioInput;
+
+use mixer::{MasterGain, TrackState, count_in_block, live_block, measure_peak, play_block};
+use voices::Voices;
+
+/// Largest number of frames mixed in one pass; larger callbacks are split into
+/// blocks, so no processor is handed more than this at once
+pub const MAX_BLOCK_FRAMES: usize = 1024;
+
+/// Objects being handed back that the audio thread holds while the status queue
+/// is full; commands wait while they're all taken
+const RETURN_SLOTS: usize = 16;
 
 /// Engine-side clip with sample-based position (converted from ticks by core)
 #[derive(Clone)]
@@ -12,13 +33,52 @@ pub struct EngineClip {
     pub audio: AudioArc,
     pub offset: u64,         // offset into audio in samples (for trimmed clips)
     pub length: Option<u64>, // length in samples (None = full audio length minus offset)
+    pub fade_in: u64,        // fade-in length in samples from the clip start
+    pub fade_out: u64,       // crossfade tail in samples played past the clip length
+    pub gain: f32,           // linear clip gain on top of the track volume
+    pub looped: bool,        // repeat the audio for the whole length
+    pub reversed: bool,      // play the audio backwards
 }
 
+/// Engine-side MIDI note, in samples on the timeline (converted from ticks by core)
+pub type EngineNote = daw_transport::SamplerNote;
+
 /// Engine-side track
 #[derive(Clone)]
 pub struct EngineTrack {
+    /// Which track this is, so the state kept for it follows it when tracks are
+    /// added, removed or reordered
+    pub id: u64,
     pub clips: Vec<EngineClip>,
     pub volume: f32, // Linear gain multiplier (0.0 = silence, 1.0 = unity)
+    pub pan: f32,    // -1.0 left to 1.0 right, applied to stereo output only
+    /// Volume and pan lanes, followed instead of `volume` and `pan` where drawn
+    pub automation: TrackAutomation,
+    /// Settings of the insert effects the clips go through, in order, before
+    /// volume and pan. The effects run in the chain sent with
+    /// [`EngineCommand::SetTrackInserts`]; this only changes their settings.
+    pub effects: Vec<Effect>,
+    /// Whether this is an aux bus, which mixes the sends pointed at it
+    pub is_bus: bool,
+    /// Buses this track feeds, after its effects
+    pub sends: Vec<EngineSend>,
+    /// Ids of the [`PluginInsert`]s the clips go through after `effects`, in order
+    pub plugins: Vec<u64>,
+    /// Whether live MIDI input is routed to this track, as it is while armed
+    pub midi_input: bool,
+    /// Instrument playing `notes` and live MIDI input, with its samples at the
+    /// engine's rate
+    pub sampler: Option<Sampler>,
+    /// Notes of the track's MIDI clips, sorted by start
+    pub notes: Vec<EngineNote>,
+}
+
+/// Engine-side send from a track to a bus
+#[derive(Clone, Copy)]
+pub struct EngineSend {
+    pub bus: usize,      // index of the bus in the track list
+    pub level: f32,      // linear gain of the send
+    pub pre_fader: bool, // taken before the track's volume
 }
 
 type SharedTracks = Shared<Vec<EngineTrack>>;
@@ -26,20 +86,231 @@ type SharedTracks = Shared<Vec<EngineTrack>>;
 struct PlaybackState {
     playing: bool,
     position: u64, // sample position
+    end: Option<PlaybackEnd>,
+    loop_range: Option<LoopRange>,
+    /// Clicks counting in before playback starts
+    count_in: Option<CountIn>,
+    master: MasterGain,
+    /// Effects, gain, balance and limiting on the mix, before the master mute
+    master_bus: MasterBus,
+    /// Stop playing once the master has faded out, after [`EngineCommand::Panic`]
+    stop_when_silent: bool,
+}
+
+/// What playback does on reaching its end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndAction {
+    /// Pause at the end and report [`EngineStatus::ReachedEnd`]
+    Stop,
+    /// Jump back to sample 0 and keep playing
+    Loop,
+}
+
+/// Where playback ends and what happens there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlaybackEnd {
+    pub sample: u64,
+    pub action: EndAction,
+}
+
+/// A stretch of the timeline that playback cycles through: reaching `end` jumps
+/// back to `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// Metronome clicks played before playback starts, with the arrangement held
+/// where it is until they're over
+pub struct CountIn {
+    /// Clicks placed from sample 0 of the count-in
+    pub clicks: Shared<Vec<EngineTrack>>,
+    /// Length of the count-in in frames
+    pub length: u64,
+    /// Frames of it played so far
+    pub position: u64,
+}
+
+impl std::fmt::Debug for CountIn {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CountIn")
+            .field("length", &self.length)
+            .field("position", &self.position)
+            .finish()
+    }
+}
+
+/// A sample to play once from its start, on top of the arrangement
+pub struct VoiceStart {
+    /// Chosen by the caller; starting a voice fades out the one playing under the
+    /// same id
+    pub id: u64,
+    pub audio: Shared<AudioArc>,
+    /// Linear gain multiplier
+    pub gain: f32,
+}
+
+impl std::fmt::Debug for VoiceStart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VoiceStart")
+            .field("id", &self.id)
+            .field("frames", &self.audio.frames())
+            .field("gain", &self.gain)
+            .finish()
+    }
+}
+
+/// Settings for the master bus's insert effects, shared so the list is freed off
+/// the audio thread once it's replaced
+pub struct MasterEffects(pub Shared<Vec<Effect>>);
+
+impl std::fmt::Debug for MasterEffects {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("MasterEffects").field(&*self.0).finish()
+    }
+}
+
+/// An insert chain built off the audio thread, owned so the one it replaces is
+/// freed off it too
+pub struct Inserts(pub Owned<InsertChain>);
+
+impl std::fmt::Debug for Inserts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Inserts").finish_non_exhaustive()
+    }
+}
+
+/// A plugin's audio processing, handed to the engine under an id that tracks
+/// list in [`EngineTrack::plugins`]
+pub struct PluginInsert {
+    pub id: u64,
+    pub processor: Box<dyn Processor>,
+}
+
+impl std::fmt::Debug for PluginInsert {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PluginInsert")
+            .field("id", &self.id)
+            .finish()
+    }
 }
 
 /// Commands sent from core to engine
 #[derive(Debug)]
 pub enum EngineCommand {
     Play,
+    /// Play a count-in, then start playing
+    PlayAfterCountIn(CountIn),
+    /// Pause, or cancel a count-in
     Pause,
-    Seek { sample: u64 },
+    Seek {
+        sample: u64,
+    },
+    /// Set where playback ends (None = play on forever)
+    SetEnd(Option<PlaybackEnd>),
+    /// Cycle playback through a range (None = don't loop). Takes over from the end
+    /// while playback is before the range's end
+    SetLoop(Option<LoopRange>),
+    /// Mute or unmute the master output, with a short ramp
+    SetMasterMute(bool),
+    /// Change the master bus's gain, balance and limiter
+    SetMasterBus(MasterSettings),
+    /// Change the settings of the master bus's insert effects, without changing
+    /// which run
+    SetMasterEffects(MasterEffects),
+    /// Run the master bus through a new insert chain, carrying over the state of
+    /// the effects that stay
+    SetMasterInserts(Inserts),
+    /// Run the clips of the track with `id` through a new insert chain, carrying
+    /// over the state of the effects that stay; an empty chain removes it
+    SetTrackInserts {
+        track: u64,
+        inserts: Inserts,
+    },
+    /// Mute the master output and stop playing as soon as it has faded out; also
+    /// stops all voices
+    Panic,
+    /// Play a sample live, whether or not the arrangement is playing
+    StartVoice(VoiceStart),
+    /// Fade out the voice playing under an id
+    StopVoice {
+        id: u64,
+    },
+    /// Glide the voice playing under an id to a new linear gain
+    SetVoiceGain {
+        id: u64,
+        gain: f32,
+    },
+    /// Fade out every voice
+    StopAllVoices,
+    /// Make a plugin available to the tracks that list its id
+    AddPlugin(PluginInsert),
+    /// Stop running a plugin; it comes back as [`EngineStatus::PluginRemoved`]
+    RemovePlugin {
+        id: u64,
+    },
+    /// Route messages from a MIDI input to the tracks with `midi_input` (None =
+    /// no input). The one it replaces comes back as [`EngineStatus::MidiInputClosed`]
+    SetMidiInput(Option<MidiReceiver>),
 }
 
 /// Status updates sent from engine to core
 #[derive(Debug)]
 pub enum EngineStatus {
-    Position(u64), // current sample position
+    /// Sent once per callback
+    Position {
+        /// Sample position at the start of the callback
+        sample: u64,
+        /// Highest sample level on the left and right of the output during the
+        /// callback, as linear gain; mono output reports the same on both
+        peak: [f32; 2],
+    },
+    /// Playback stopped at a [`PlaybackEnd`] with [`EndAction::Stop`]
+    ReachedEnd,
+    /// A plugin taken out of the engine, handed back so it's dropped off the
+    /// audio thread
+    PluginRemoved(PluginInsert),
+    /// A MIDI input the engine stopped reading, handed back so it's dropped off the
+    /// audio thread
+    MidiInputClosed(MidiReceiver),
+}
+
+/// Objects the audio thread is done with, on their way back to core to be dropped
+/// there. Ones the status queue has no room for wait here and go out first in
+/// the next callback, so nothing is freed on the audio thread.
+struct Returns {
+    pending: Vec<EngineStatus>,
+}
+
+impl Returns {
+    fn new() -> Self {
+        Self {
+            pending: Vec::with_capacity(RETURN_SLOTS),
+        }
+    }
+
+    /// Whether there's room to hold one more if the status queue is full
+    fn has_room(&self) -> bool {
+        self.pending.len() < RETURN_SLOTS
+    }
+
+    /// Hand `status` back, or hold it until there's room in the queue
+    fn send(&mut self, status: EngineStatus, status_tx: &mut rtrb::Producer<EngineStatus>) {
+        if let Err(rtrb::PushError::Full(status)) = status_tx.push(status) {
+            self.pending.push(status);
+        }
+    }
+
+    /// Hand back what's been held, as far as the queue has room
+    fn flush(&mut self, status_tx: &mut rtrb::Producer<EngineStatus>) {
+        while let Some(status) = self.pending.pop() {
+            if let Err(rtrb::PushError::Full(status)) = status_tx.push(status) {
+                self.pending.push(status);
+                break;
+            }
+        }
+    }
 }
 
 pub struct AudioEngineHandle {
@@ -49,7 +320,13 @@ pub struct AudioEngineHandle {
     pub collector: Collector,
     pub handle: Handle,
     pub sample_rate: u32,
-    _stream: cpal::Stream,
+    /// Rate the device is asked for when it's opened, if not its default
+    preferred_sample_rate: Option<u32>,
+    /// Output stream, or `None` for an offline engine or after a failed restart
+    _stream: Option<cpal::Stream>,
+    offline: bool,
+    /// Set by the stream's error callback
+    stream_failed: Arc<AtomicBool>,
 }
 
 // SAFETY: AudioEngineHandle is safe to send between threads despite containing cpal::Stream.
@@ -59,21 +336,107 @@ pub struct AudioEngineHandle {
 unsafe impl Send for AudioEngineHandle {}
 
 pub fn start(tracks: Vec<EngineTrack>) -> anyhow::Result<AudioEngineHandle> {
+    start_with_sample_rate(tracks, None)
+}
+
+/// Start the engine like [`start`], opening the device at `sample_rate` if it
+/// supports it.
+///
+/// Devices that don't run at that rate are opened at their default one, so check
+/// [`AudioEngineHandle::sample_rate`] for the rate the engine got. Restarts ask for
+/// the same rate again.
+pub fn start_with_sample_rate(
+    tracks: Vec<EngineTrack>,
+    sample_rate: Option<u32>,
+) -> anyhow::Result<AudioEngineHandle> {
     let collector = Collector::new();
     let handle = collector.handle();
+    let output = open_output(&handle, tracks, sample_rate)?;
 
+    Ok(AudioEngineHandle {
+        commands: output.commands,
+        status: output.status,
+        tracks: output.tracks,
+        collector,
+        handle,
+        sample_rate: output.sample_rate,
+        preferred_sample_rate: sample_rate,
+        _stream: Some(output.stream),
+        offline: false,
+        stream_failed: output.stream_failed,
+    })
+}
+
+impl AudioEngineHandle {
+    /// Whether this engine has no output stream (see [`start_offline`])
+    pub fn is_offline(&self) -> bool {
+        self.offline
+    }
+
+    /// Whether the output stream has reported an error since it was started, or
+    /// the last restart failed
+    pub fn stream_failed(&self) -> bool {
+        self.stream_failed.load(Ordering::Relaxed)
+    }
+
+    /// Replace the output stream with a new one on the current default device.
+    ///
+    /// Used to recover from a stream that stopped running. The new stream starts
+    /// paused at position 0 with no tracks and fresh queues, so the caller has to
+    /// send tracks, seek and play again. `sample_rate` is updated, since the
+    /// default device may have changed. Offline engines are left as they are.
+    pub fn restart(&mut self) -> anyhow::Result<()> {
+        if self.is_offline() {
+            return Ok(());
+        }
+
+        // Close the old stream first; some hosts won't open a second one on the device
+        self._stream = None;
+        let output = open_output(&self.handle, Vec::new(), self.preferred_sample_rate)
+            .inspect_err(|_| {
+                self.stream_failed.store(true, Ordering::Relaxed);
+            })?;
+
+        self.commands = output.commands;
+        self.status = output.status;
+        self.tracks = output.tracks;
+        self.sample_rate = output.sample_rate;
+        self._stream = Some(output.stream);
+        self.stream_failed = output.stream_failed;
+        Ok(())
+    }
+}
+
+/// A running output stream and the queues connected to it
+struct Output {
+    commands: rtrb::Producer<EngineCommand>,
+    status: rtrb::Consumer<EngineStatus>,
+    tracks: rtrb::Producer<SharedTracks>,
+    sample_rate: u32,
+    stream: cpal::Stream,
+    stream_failed: Arc<AtomicBool>,
+}
+
+/// Open and start a stream on the default output device, at `sample_rate` if
+/// given and the device supports it
+fn open_output(
+    handle: &Handle,
+    tracks: Vec<EngineTrack>,
+    sample_rate: Option<u32>,
+) -> anyhow::Result<Output> {
     let (command_tx, command_rx) = rtrb::RingBuffer::<EngineCommand>::new(64);
     let (status_tx, status_rx) = rtrb::RingBuffer::<EngineStatus>::new(64);
     let (tracks_tx, tracks_rx) = rtrb::RingBuffer::<SharedTracks>::new(4);
 
-    let initial_tracks = Shared::new(&handle, tracks);
+    let initial_tracks = Shared::new(handle, tracks);
+    let stream_failed = Arc::new(AtomicBool::new(false));
 
     let host = cpal::default_host();
     let device = host
         .default_output_device()
         .ok_or_else(|| anyhow::anyhow!("no output device found"))?;
 
-    let config = device.default_output_config()?;
+    let config = output_config(&device, sample_rate)?;
     let sample_rate = config.sample_rate().0;
 
     let stream = match config.sample_format() {
@@ -84,21 +447,67 @@ pub fn start(tracks: Vec<EngineTrack>) -> anyhow::Result<AudioEngineHandle> {
             command_rx,
             tracks_rx,
             status_tx,
+            stream_failed.clone(),
         )?,
         sample_format => anyhow::bail!("unsupported sample format '{sample_format}'"),
     };
 
     stream.play()?;
 
-    Ok(AudioEngineHandle {
+    Ok(Output {
+        commands: command_tx,
+        status: status_rx,
+        tracks: tracks_tx,
+        sample_rate,
+        stream,
+        stream_failed,
+    })
+}
+
+/// The device's default output config, or one like it at `sample_rate` if the
+/// device supports that rate
+fn output_config(
+    device: &cpal::Device,
+    sample_rate: Option<u32>,
+) -> anyhow::Result<cpal::SupportedStreamConfig> {
+    let default = device.default_output_config()?;
+    let Some(rate) = sample_rate.filter(|&rate| rate != default.sample_rate().0) else {
+        return Ok(default);
+    };
+    let supported = device
+        .supported_output_configs()?
+        .filter(|range| {
+            range.sample_format() == default.sample_format()
+                && range.channels() == default.channels()
+        })
+        .find_map(|range| range.try_with_sample_rate(cpal::SampleRate(rate)));
+    Ok(supported.unwrap_or(default))
+}
+
+/// Create an engine handle that isn't connected to an audio device.
+///
+/// Commands and tracks sent to it are accepted and discarded, and it never reports a
+/// position. Used for headless sessions that only edit, save and render.
+pub fn start_offline(sample_rate: u32) -> AudioEngineHandle {
+    let collector = Collector::new();
+    let handle = collector.handle();
+
+    let (command_tx, _) = rtrb::RingBuffer::<EngineCommand>::new(64);
+    let (_, status_rx) = rtrb::RingBuffer::<EngineStatus>::new(64);
+    let (tracks_tx, _) = rtrb::RingBuffer::<SharedTracks>::new(4);
+
+    AudioEngineHandle {
         commands: command_tx,
         status: status_rx,
         tracks: tracks_tx,
         collector,
         handle,
         sample_rate,
-        _stream: stream,
-    })
+        preferred_sample_rate: None,
+        _stream: None,
+        offline: true,
+        stream_failed: Arc::new(AtomicBool::new(false)),
+    }
 }
 
 fn build_stream<T>(
@@ -108,6 +517,7 @@ fn build_stream<T>(
     mut command_rx: rtrb::Consumer<EngineCommand>,
     mut tracks_rx: rtrb::Consumer<SharedTracks>,
     mut status_tx: rtrb::Producer<EngineStatus>,
+    stream_failed: Arc<AtomicBool>,
 ) -> anyhow::Result<cpal::Stream>
 where
     T: SizedSample + FromSample<f32>,
@@ -117,9 +527,21 @@ where
     let mut state = PlaybackState {
         playing: false,
         position: 0,
+        end: None,
+        loop_range: None,
+        count_in: None,
+        master: MasterGain::default(),
+        master_bus: MasterBus::new(MasterSettings::default(), config.sample_rate.0),
+        stop_when_silent: false,
     };
 
     let mut current_tracks = initial_tracks;
+    let mut gains = TrackState::new(config.sample_rate.0, output_channels);
+    let mut count_in_gains = TrackState::new(config.sample_rate.0, output_channels);
+    let mut voices = Voices::new();
+    let mut midi_input: Option<MidiReceiver> = None;
+    let mut returns = Returns::new();
+    let mut mix_buffer = vec![0.0f32; MAX_BLOCK_FRAMES * output_channels];
 
     let stream = device.build_output_stream(
         config,
@@ -129,69 +551,172 @@ where
                 current_tracks = new_tracks;
             }
 
-            while let Ok(cmd) = command_rx.pop() {
+            // Anything still waiting to go back goes before new status
+            returns.flush(&mut status_tx);
+
+            // Commands wait while there'd be nowhere to hold what they hand back
+            while returns.has_room()
+                && let Ok(cmd) = command_rx.pop()
+            {
                 match cmd {
-                    EngineCommand::Play => state.playing = true,
-                    EngineCommand::Pause => state.playing = false,
+                    EngineCommand::Play => {
+                        state.playing = true;
+                        state.stop_when_silent = false;
+                    }
+                    EngineCommand::PlayAfterCountIn(count_in) => {
+                        state.playing = false;
+                        state.count_in = Some(count_in);
+                        state.stop_when_silent = false;
+                    }
+                    EngineCommand::Pause => {
+                        state.playing = false;
+                        state.count_in = None;
+                    }
                     EngineCommand::Seek { sample } => state.position = sample,
+                    EngineCommand::SetEnd(end) => state.end = end,
+                    EngineCommand::SetLoop(loop_range) => state.loop_range = loop_range,
+                    EngineCommand::SetMasterMute(muted) => state.master.set_muted(muted),
+                    EngineCommand::SetMasterBus(settings) => state.master_bus.set(settings),
+                    EngineCommand::SetMasterEffects(MasterEffects(effects)) => {
+                        state.master_bus.set_effects(&effects)
+                    }
+                    // The old chain goes back to be freed with `inserts`
+                    EngineCommand::SetMasterInserts(mut inserts) => {
+                        state.master_bus.replace_inserts(&mut inserts.0)
+                    }
+                    EngineCommand::SetTrackInserts { track, inserts } => {
+                        gains.set_inserts(track, inserts.0)
+                    }
+                    EngineCommand::Panic => {
+                        state.count_in = None;
+                        state.master.set_muted(true);
+                        state.stop_when_silent = true;
+                        voices.stop_all();
+                    }
+                    EngineCommand::StartVoice(start) => {
+                        voices.start(start.id, start.audio, start.gain);
+                    }
+                    EngineCommand::StopVoice { id } => voices.stop(id),
+                    EngineCommand::SetVoiceGain { id, gain } => voices.set_gain(id, gain),
+                    EngineCommand::StopAllVoices => voices.stop_all(),
+                    EngineCommand::AddPlugin(plugin) => gains.add_plugin(plugin),
+                    EngineCommand::RemovePlugin { id } => {
+                        if let Some(plugin) = gains.remove_plugin(id) {
+                            returns.send(EngineStatus::PluginRemoved(plugin), &mut status_tx);
+                        }
+                    }
+                    EngineCommand::SetMidiInput(input) => {
+                        if let Some(old) = std::mem::replace(&mut midi_input, input) {
+                            returns.send(EngineStatus::MidiInputClosed(old), &mut status_tx);
+                        }
+                    }
                 }
             }
 
-            let _ = status_tx.push(EngineStatus::Position(state.position));
+            // Live MIDI goes to the armed tracks in the next block mixed
+            if let Some(receiver) = &mut midi_input {
+                while let Some(message) = receiver.pop() {
+                    gains.receive_midi(message);
+                }
+            }
 
-            for frame in data.chunks_mut(output_channels) {
-                if state.playing {
-                    let mut mixed = vec![0.0f32; output_channels];
-
-                    for track in current_tracks.iter() {
-                        for clip in &track.clips {
-                            let clip_channels = clip.audio.channels() as usize;
-                            let clip_total_frames = clip.audio.frames();
-
-                            // Calculate effective length (accounting for offset and explicit length)
-                            let available_frames =
-                                clip_total_frames.saturating_sub(clip.offset as usize);
-                            let effective_length = match clip.length {
-                                Some(len) => (len as usize).min(available_frames),
-                                None => available_frames,
-                            };
-
-                            // clip.start is the timeline position, effective_length is how long it plays
-                            let clip_start = clip.start;
-                            let clip_end = clip_start + effective_length as u64;
-
-                            if state.position >= clip_start && state.position < clip_end {
-                                let timeline_offset = state.position - clip_start;
-                                // Add clip.offset to get the actual position in the audio buffer
-                                let frame_index =
-                                    (clip.offset as usize) + (timeline_offset as usize);
-
-                                if frame_index < clip_total_frames {
-                                    for (ch, mix_sample) in mixed.iter_mut().enumerate() {
-                                        let clip_ch = ch % clip_channels;
-                                        let idx = frame_index * clip_channels + clip_ch;
-                                        if idx < clip.audio.samples().len() {
-                                            *mix_sample += clip.audio.samples()[idx] * track.volume;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+            let position = state.position;
 
-                    for (ch, sample) in frame.iter_mut().enumerate() {
-                        *sample = T::from_sample(mixed[ch]);
-                    }
+            if !state.playing
+                && state.count_in.is_none()
+                && voices.is_empty()
+                && !gains.has_live_sound()
+            {
+                // Nothing is audible, so a mute or unmute can take effect at once
+                state.master.settle();
+                data.fill(T::from_sample(0.0));
+                let _ = status_tx.push(EngineStatus::Position {
+                    sample: position,
+                    peak: [0.0; 2],
+                });
+                return;
+            }
 
-                    state.position += 1;
-                } else {
-                    for sample in frame.iter_mut() {
-                        *sample = T::from_sample(0.0);
+            let mut peak = [0.0; 2];
+            // Mix in fixed-size blocks so the scratch buffer never reallocates
+            for block in data.chunks_mut(mix_buffer.len()) {
+                if !state.playing
+                    && state.count_in.is_none()
+                    && voices.is_empty()
+                    && !gains.has_live_sound()
+                {
+                    block.fill(T::from_sample(0.0));
+                    continue;
+                }
+
+                let mixed = &mut mix_buffer[..block.len()];
+                let mut counted = 0;
+                if let Some(count_in) = &mut state.count_in {
+                    counted = count_in_block(
+                        &count_in.clicks,
+                        &mut count_in_gains,
+                        &mut count_in.position,
+                        count_in.length,
+                        mixed,
+                        output_channels,
+                    );
+                    if count_in.position >= count_in.length {
+                        state.count_in = None;
+                        state.playing = true;
                     }
                 }
+                let played = &mut mixed[counted..];
+                if state.playing {
+                    let stopped = play_block(
+                        &current_tracks,
+                        &mut gains,
+                        &mut state.position,
+                        state.end,
+                        state.loop_range,
+                        played,
+                        output_channels,
+                    );
+                    if stopped {
+                        state.playing = false;
+                        let _ = status_tx.push(EngineStatus::ReachedEnd);
+                    }
+                } else if gains.has_live_sound() {
+                    // Samplers play live MIDI with the arrangement stopped
+                    live_block(
+                        &current_tracks,
+                        &mut gains,
+                        state.position,
+                        played,
+                        output_channels,
+                    );
+                } else {
+                    played.fill(0.0);
+                }
+                voices.mix(mixed, output_channels);
+
+                state.master_bus.process(mixed, output_channels);
+                state.master.apply(mixed, output_channels);
+                measure_peak(mixed, output_channels, &mut peak);
+                if state.stop_when_silent && state.master.is_silent() {
+                    state.playing = false;
+                    state.stop_when_silent = false;
+                }
+
+                for (sample, &value) in block.iter_mut().zip(mixed.iter()) {
+                    *sample = T::from_sample(value);
+                }
             }
+            // Live MIDI that found no block to play in
+            gains.drop_midi();
+            let _ = status_tx.push(EngineStatus::Position {
+                sample: position,
+                peak,
+            });
+        },
+        move |err| {
+            eprintln!("stream error: {err}");
+            stream_failed.store(true, Ordering::Relaxed);
         },
-        |err| eprintln!("stream error: {err}"),
         None,
     )?;
 
@@ -346,6 +871,35 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_returns_wait_for_room_in_the_status_queue() {
+        let (mut status_tx, mut status_rx) = rtrb::RingBuffer::<EngineStatus>::new(1);
+        let removed = |id| {
+            EngineStatus::PluginRemoved(PluginInsert {
+                id,
+                processor: Box::new(daw_dsp::InsertChain::default()),
+            })
+        };
+        let mut returns = Returns::new();
+        returns.send(removed(1), &mut status_tx);
+        returns.send(removed(2), &mut status_tx);
+        assert_eq!(returns.pending.len(), 1);
+
+        // Held until core makes room in the queue
+        returns.flush(&mut status_tx);
+        assert_eq!(returns.pending.len(), 1);
+        assert!(matches!(
+            status_rx.pop(),
+            Ok(EngineStatus::PluginRemoved(PluginInsert { id: 1, .. }))
+        ));
+        returns.flush(&mut status_tx);
+        assert!(returns.pending.is_empty());
+        assert!(matches!(
+            status_rx.pop(),
+            Ok(EngineStatus::PluginRemoved(PluginInsert { id: 2, .. }))
+        ));
+    }
+
     /// Helper function to count zero crossings in a signal
     fn count_zero_crossings(samples: &[f32]) -> usize {
         let mut count = 0;
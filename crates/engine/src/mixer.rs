@@ -0,0 +1,1224 @@
+use crate::{EndAction, EngineTrack, LoopRange, MAX_BLOCK_FRAMES, PlaybackEnd, PluginInsert};
+use basedrop::Owned;
+use daw_dsp::{InsertChain, Processor, Smoother};
+use daw_midi::MidiMessage;
+use daw_transport::{LiveNote, SamplerVoices, clip_source_frame, fade_gain, pan_gains};
+
+/// Time constant of the glide when a track's volume changes, in seconds
+const VOLUME_SMOOTHING_SECS: f32 = 0.005;
+
+/// Tracks whose state fits without allocating on the audio thread
+const PREALLOCATED_TRACKS: usize = 256;
+
+/// Sends per track whose levels fit without allocating on the audio thread
+const PREALLOCATED_SENDS: usize = 16;
+
+/// Plugins that can be added without allocating on the audio thread
+const PREALLOCATED_PLUGINS: usize = 64;
+
+/// Live MIDI messages held for the next block; more arriving in one callback are
+/// dropped rather than allocating
+const PREALLOCATED_MIDI: usize = 256;
+
+/// Controller that releases every held note
+const ALL_NOTES_OFF: u8 = 123;
+
+/// Smoothed volume, pan and send levels of each track and its running insert
+/// effects, carried from one block to the next.
+///
+/// Insert chains are built by core and matched to tracks by id, so their tails
+/// stay with their track when tracks are added, removed or reordered. Other
+/// state is matched to tracks by index. A track added since the last block
+/// starts at its volume; one whose volume changed glides to it.
+pub(crate) struct TrackState {
+    volumes: Vec<Smoother>,
+    pans: Vec<Smoother>,
+    /// Level of each of a track's sends, in the order of its `sends`
+    sends: Vec<Vec<Smoother>>,
+    /// Insert chains sent by core, by the id of the track they belong to
+    inserts: Vec<(u64, Owned<InsertChain>)>,
+    /// Notes each track's sampler is playing
+    samplers: Vec<SamplerVoices>,
+    /// Plugins added by core, run by the tracks that list their ids
+    plugins: Vec<PluginInsert>,
+    /// Where an effected track's clips are mixed before going through its inserts,
+    /// allocated up front for a whole block
+    scratch: Vec<f32>,
+    /// What's sent to each bus this block, by track index; empty for other tracks.
+    /// Each holds a whole block without allocating
+    bus_inputs: Vec<Vec<f32>>,
+    /// Live MIDI received since the last block
+    live_midi: Vec<LiveNote>,
+    /// The live MIDI each track plays this block, by track index; empty for
+    /// tracks without `midi_input`
+    track_midi: Vec<Vec<LiveNote>>,
+    time_constant: f32,
+    sample_rate: u32,
+}
+
+impl TrackState {
+    /// State for blocks of up to [`MAX_BLOCK_FRAMES`] frames of `channels`
+    /// channels, allocated up front for the first [`PREALLOCATED_TRACKS`] tracks
+    pub(crate) fn new(sample_rate: u32, channels: usize) -> Self {
+        let block_samples = MAX_BLOCK_FRAMES * channels;
+        Self {
+            volumes: Vec::with_capacity(PREALLOCATED_TRACKS),
+            pans: Vec::with_capacity(PREALLOCATED_TRACKS),
+            sends: (0..PREALLOCATED_TRACKS)
+                .map(|_| Vec::with_capacity(PREALLOCATED_SENDS))
+                .collect(),
+            inserts: Vec::with_capacity(PREALLOCATED_TRACKS),
+            samplers: (0..PREALLOCATED_TRACKS)
+                .map(|_| SamplerVoices::new())
+                .collect(),
+            plugins: Vec::with_capacity(PREALLOCATED_PLUGINS),
+            scratch: Vec::with_capacity(block_samples),
+            bus_inputs: (0..PREALLOCATED_TRACKS)
+                .map(|_| Vec::with_capacity(block_samples))
+                .collect(),
+            live_midi: Vec::with_capacity(PREALLOCATED_MIDI),
+            track_midi: (0..PREALLOCATED_TRACKS)
+                .map(|_| Vec::with_capacity(PREALLOCATED_MIDI))
+                .collect(),
+            time_constant: Smoother::frames_for(VOLUME_SMOOTHING_SECS, sample_rate),
+            sample_rate,
+        }
+    }
+
+    /// Run a track's clips through `chain` from now on, carrying over the state
+    /// of the effects that stay in it. An empty chain takes the track's away. The
+    /// chain replaced is dropped through its [`Owned`], off the audio thread.
+    pub(crate) fn set_inserts(&mut self, track: u64, mut chain: Owned<InsertChain>) {
+        match self.inserts.iter().position(|(id, _)| *id == track) {
+            Some(index) => {
+                chain.take_state(&mut self.inserts[index].1);
+                if chain.is_empty() {
+                    self.inserts.swap_remove(index);
+                } else {
+                    self.inserts[index].1 = chain;
+                }
+            }
+            None if !chain.is_empty() => self.inserts.push((track, chain)),
+            None => {}
+        }
+    }
+
+    pub(crate) fn add_plugin(&mut self, plugin: PluginInsert) {
+        self.plugins.push(plugin);
+    }
+
+    /// Take out the plugin with `id`, if it was added
+    pub(crate) fn remove_plugin(&mut self, id: u64) -> Option<PluginInsert> {
+        let index = self.plugins.iter().position(|plugin| plugin.id == id)?;
+        Some(self.plugins.swap_remove(index))
+    }
+
+    /// Hold a live MIDI message for the tracks with `midi_input` to play in the
+    /// next block, if it plays notes
+    pub(crate) fn receive_midi(&mut self, message: MidiMessage) {
+        let note = match message {
+            MidiMessage::NoteOn { note, velocity, .. } => LiveNote::On { note, velocity },
+            MidiMessage::NoteOff { note, .. } => LiveNote::Off { note },
+            MidiMessage::ControlChange {
+                controller: ALL_NOTES_OFF,
+                ..
+            } => LiveNote::AllOff,
+            _ => return,
+        };
+        if self.live_midi.len() < self.live_midi.capacity() {
+            self.live_midi.push(note);
+        }
+    }
+
+    /// Whether there's live MIDI to play or a sampler still sounding, so tracks
+    /// have to be mixed while the arrangement isn't playing
+    pub(crate) fn has_live_sound(&self) -> bool {
+        !self.live_midi.is_empty() || self.samplers.iter().any(SamplerVoices::is_sounding)
+    }
+
+    /// Forget the live MIDI held for the next block, when there's no block to
+    /// play it in
+    pub(crate) fn drop_midi(&mut self) {
+        self.live_midi.clear();
+    }
+
+    /// Hand the live MIDI held since the last block to each track with
+    /// `midi_input`, leaving the others none
+    fn route_midi(&mut self, tracks: &[EngineTrack]) {
+        // Only tracks past the preallocated ones allocate
+        while self.track_midi.len() < tracks.len() {
+            self.track_midi.push(Vec::with_capacity(PREALLOCATED_MIDI));
+        }
+        for (track, messages) in tracks.iter().zip(&mut self.track_midi) {
+            messages.clear();
+            if track.midi_input {
+                messages.extend_from_slice(&self.live_midi);
+            }
+        }
+        self.live_midi.clear();
+    }
+
+    /// Point each smoother at its track's volume, pan and send levels, as
+    /// automated at timeline sample `position`
+    fn follow(&mut self, tracks: &[EngineTrack], position: u64) {
+        self.volumes.truncate(tracks.len());
+        self.pans.truncate(tracks.len());
+        // Lists are kept rather than freed when tracks go, and only tracks past the
+        // preallocated ones allocate
+        while self.bus_inputs.len() < tracks.len() {
+            self.bus_inputs.push(Vec::new());
+        }
+        while self.sends.len() < tracks.len() {
+            self.sends.push(Vec::with_capacity(PREALLOCATED_SENDS));
+        }
+        while self.samplers.len() < tracks.len() {
+            self.samplers.push(SamplerVoices::new());
+        }
+        for levels in &mut self.sends[tracks.len()..] {
+            levels.clear();
+        }
+        // Notes of tracks that are gone stop rather than ringing on unheard
+        for voices in &mut self.samplers[tracks.len()..] {
+            voices.silence();
+        }
+        for (i, track) in tracks.iter().enumerate() {
+            let levels = &mut self.sends[i];
+            levels.truncate(track.sends.len());
+            for (k, send) in track.sends.iter().enumerate() {
+                match levels.get_mut(k) {
+                    Some(level) => level.set_target(send.level),
+                    None => levels.push(Smoother::one_pole(send.level, self.time_constant)),
+                }
+            }
+
+            let volume = track.automation.volume_at(position, track.volume);
+            let pan = track.automation.pan_at(position, track.pan);
+            match (self.volumes.get_mut(i), self.pans.get_mut(i)) {
+                (Some(smoothed_volume), Some(smoothed_pan)) => {
+                    smoothed_volume.set_target(volume);
+                    smoothed_pan.set_target(pan);
+                }
+                _ => {
+                    self.volumes
+                        .push(Smoother::one_pole(volume, self.time_constant));
+                    self.pans.push(Smoother::one_pole(pan, self.time_constant));
+                }
+            }
+        }
+    }
+}
+
+/// Mix the next `out.len() / channels` frames of playback from `*position`,
+/// advancing it and applying `end` and `loop_range`.
+///
+/// Reaching the end of `loop_range` jumps back to its start, as long as playback
+/// got there before `end`; playback past the range plays on to `end`. At a looping
+/// end playback continues from sample 0. Either jump happens within the same block.
+/// At a stopping end the rest of the block is silent and `true` is returned;
+/// `*position` is left at the end.
+pub(crate) fn play_block(
+    tracks: &[EngineTrack],
+    gains: &mut TrackState,
+    position: &mut u64,
+    end: Option<PlaybackEnd>,
+    loop_range: Option<LoopRange>,
+    mut out: &mut [f32],
+    channels: usize,
+) -> bool {
+    // Looping an empty range would never advance
+    let end = end.filter(|end| end.action == EndAction::Stop || end.sample > 0);
+    let loop_range = loop_range.filter(|range| range.end > range.start);
+
+    loop {
+        let frames = (out.len() / channels) as u64;
+        let until_end = end.map_or(u64::MAX, |end| end.sample.saturating_sub(*position));
+        let cycle =
+            loop_range.filter(|range| *position <= range.end && range.end - *position <= until_end);
+        let until_jump = cycle.map_or(until_end, |range| range.end - *position);
+        let (now, rest) = out.split_at_mut(frames.min(until_jump) as usize * channels);
+
+        mix_block(tracks, gains, *position, now, channels);
+        *position += (now.len() / channels) as u64;
+        if rest.is_empty() {
+            return false;
+        }
+
+        // Only reaching the end or the end of the loop cuts a block short
+        if let Some(range) = cycle {
+            *position = range.start;
+            out = rest;
+            continue;
+        }
+        match end.map(|end| end.action) {
+            Some(EndAction::Loop) => {
+                *position = 0;
+                out = rest;
+            }
+            _ => {
+                rest.fill(0.0);
+                return true;
+            }
+        }
+    }
+}
+
+/// Mix the count-in's clicks into the start of `out` from `*position`, up to its
+/// `length` in frames, advancing `*position`. Returns how many samples of `out`
+/// it filled; playback takes over from there once `*position` reaches `length`.
+pub(crate) fn count_in_block(
+    clicks: &[EngineTrack],
+    gains: &mut TrackState,
+    position: &mut u64,
+    length: u64,
+    out: &mut [f32],
+    channels: usize,
+) -> usize {
+    let frames = (out.len() / channels) as u64;
+    let counted = length.saturating_sub(*position).min(frames) as usize * channels;
+    mix_block(clicks, gains, *position, &mut out[..counted], channels);
+    *position += (counted / channels) as u64;
+    counted
+}
+
+/// Mix all tracks into an interleaved output block.
+///
+/// `out` holds `out.len() / channels` frames covering timeline samples
+/// `position..position + frames`. Clips that start or end inside the block are
+/// mixed from the exact frame where they begin or stop, so clip boundaries are
+/// sample-accurate regardless of the callback buffer size. Fades and clip gain are
+/// applied per frame, with a clip's fade-out tail playing past its length; looped
+/// clips wrap around their audio and reversed ones read it backwards. Track volume
+/// and pan glide to new values through `gains` rather than jumping at the block
+/// start, automation included: lanes are read once per block, at its start.
+/// A track with effects, plugins or sends is mixed on its own and run through its
+/// effects, then its plugins, before its volume and pan; the chain runs even while
+/// the track is silent, so tails ring out. Sends are added to their buses, which
+/// are mixed last, through their own effects, volume and pan. A track with a
+/// sampler plays its notes and any live MIDI through it, mixed with its clips
+/// before its effects.
+pub(crate) fn mix_block(
+    tracks: &[EngineTrack],
+    gains: &mut TrackState,
+    position: u64,
+    out: &mut [f32],
+    channels: usize,
+) {
+    mix_tracks(tracks, gains, position, true, out, channels);
+}
+
+/// Mix what's played live on the tracks while the arrangement isn't playing: the
+/// samplers of tracks taking MIDI input, through their effects, volume and pan,
+/// and the buses they send to. Other samplers are cut off. `position` is where
+/// the arrangement stands, for automation.
+pub(crate) fn live_block(
+    tracks: &[EngineTrack],
+    gains: &mut TrackState,
+    position: u64,
+    out: &mut [f32],
+    channels: usize,
+) {
+    mix_tracks(tracks, gains, position, false, out, channels);
+}
+
+/// Mix tracks like [`mix_block`], leaving out the arrangement unless `playing`
+fn mix_tracks(
+    tracks: &[EngineTrack],
+    gains: &mut TrackState,
+    position: u64,
+    playing: bool,
+    out: &mut [f32],
+    channels: usize,
+) {
+    out.fill(0.0);
+
+    let block_frames = (out.len() / channels) as u64;
+
+    gains.follow(tracks, position);
+    gains.route_midi(tracks);
+    for (track, input) in tracks.iter().zip(&mut gains.bus_inputs) {
+        input.clear();
+        if track.is_bus {
+            input.resize(out.len(), 0.0);
+        }
+    }
+    if gains.scratch.len() < out.len() {
+        gains.scratch.resize(out.len(), 0.0);
+    }
+
+    // Tracks first, then the buses they send to
+    let indices = 0..tracks.len();
+    let order = indices
+        .clone()
+        .filter(|&i| !tracks[i].is_bus)
+        .chain(indices.filter(|&i| tracks[i].is_bus));
+    for i in order {
+        let track = &tracks[i];
+        let played_live = track.midi_input && track.sampler.is_some();
+        if !playing && !played_live && !track.is_bus {
+            gains.samplers[i].silence();
+            continue;
+        }
+        let (volume, pan) = (&mut gains.volumes[i], &mut gains.pans[i]);
+        let inserts = gains
+            .inserts
+            .iter_mut()
+            .find(|(id, _)| *id == track.id)
+            .map(|(_, chain)| &mut **chain);
+        if inserts.is_none()
+            && track.plugins.is_empty()
+            && track.sends.is_empty()
+            && track.sampler.is_none()
+            && !track.is_bus
+        {
+            mix_clips(track, volume, pan, position, out, channels);
+            volume.skip(block_frames as u32);
+            pan.skip(block_frames as u32);
+            continue;
+        }
+
+        // Effects and sends come before the fader: the clips go through them at unity
+        let scratch = &mut gains.scratch[..out.len()];
+        scratch.fill(0.0);
+        let unity = Smoother::one_pole(1.0, gains.time_constant);
+        let center = Smoother::one_pole(0.0, gains.time_constant);
+        if playing {
+            mix_clips(track, &unity, &center, position, scratch, channels);
+        }
+        if let Some(sampler) = &track.sampler {
+            gains.samplers[i].play(
+                sampler,
+                playing.then_some(track.notes.as_slice()),
+                &gains.track_midi[i],
+                position,
+                gains.sample_rate,
+                scratch,
+                channels,
+            );
+        }
+        if track.is_bus {
+            for (sample, sent) in scratch.iter_mut().zip(&gains.bus_inputs[i]) {
+                *sample += sent;
+            }
+        }
+        if let Some(chain) = inserts {
+            chain.set(&track.effects);
+            chain.process(scratch, channels);
+        }
+        for id in &track.plugins {
+            if let Some(plugin) = gains.plugins.iter_mut().find(|plugin| plugin.id == *id) {
+                plugin.processor.process(scratch, channels);
+            }
+        }
+
+        let send_levels = &mut gains.sends[i];
+        let steady_pan = steady_pan_gains(pan, channels);
+        for (f, (frame, processed)) in out
+            .chunks_exact_mut(channels)
+            .zip(scratch.chunks_exact(channels))
+            .enumerate()
+        {
+            let gain = volume.next();
+            let channel_gains = steady_pan.unwrap_or_else(|| pan_gains(pan.next()));
+            for (ch, (mix_sample, sample)) in frame.iter_mut().zip(processed).enumerate() {
+                *mix_sample += sample * gain * channel_gains[ch % 2];
+            }
+            for (send, level) in track.sends.iter().zip(send_levels.iter_mut()) {
+                let level = level.next() * if send.pre_fader { 1.0 } else { gain };
+                let Some(input) = gains.bus_inputs.get_mut(send.bus) else {
+                    continue;
+                };
+                let Some(input) = input.get_mut(f * channels..(f + 1) * channels) else {
+                    continue;
+                };
+                for (sent, sample) in input.iter_mut().zip(processed) {
+                    *sent += sample * level;
+                }
+            }
+        }
+    }
+}
+
+/// Gains for each side while `pan` holds still, or `None` while it's gliding
+fn steady_pan_gains(pan: &Smoother, channels: usize) -> Option<[f32; 2]> {
+    // Pan only places sound between two speakers
+    if channels != 2 {
+        Some([1.0, 1.0])
+    } else {
+        pan.is_settled().then(|| pan_gains(pan.value()))
+    }
+}
+
+/// Add `track`'s clips to the block starting at timeline sample `position`,
+/// with `volume` and `pan` as they stand at its first frame
+fn mix_clips(
+    track: &EngineTrack,
+    volume: &Smoother,
+    pan: &Smoother,
+    position: u64,
+    out: &mut [f32],
+    channels: usize,
+) {
+    let block_end = position + (out.len() / channels) as u64;
+    for clip in &track.clips {
+        let clip_channels = clip.audio.channels() as usize;
+        let clip_total_frames = clip.audio.frames() as u64;
+
+        // Calculate effective length (accounting for offset and explicit length).
+        // Looped clips never run out of audio, so they play their full length
+        let available_frames = clip_total_frames.saturating_sub(clip.offset);
+        let effective_length = match clip.length {
+            Some(len) if clip.looped && clip_total_frames > 0 => len,
+            Some(len) => len.min(available_frames),
+            None => available_frames,
+        };
+
+        // The fade-out tail plays on past the clip's length while audio remains
+        let tail = if clip.looped && clip_total_frames > 0 {
+            clip.fade_out
+        } else {
+            clip.fade_out
+                .min(available_frames.saturating_sub(effective_length))
+        };
+
+        // clip.start is the timeline position, effective_length is how long it plays
+        let clip_start = clip.start;
+        let clip_end = clip_start + effective_length + tail;
+
+        let start = clip_start.max(position);
+        let end = clip_end.min(block_end);
+        if start >= end {
+            continue;
+        }
+
+        // Where the clip enters this block
+        let out_frame = (start - position) as usize;
+        let frames = (end - start) as usize;
+
+        let samples = clip.audio.samples();
+        let dest = &mut out[out_frame * channels..(out_frame + frames) * channels];
+
+        // Each clip sees the track volume as it will be at its own frames
+        let mut clip_volume = *volume;
+        clip_volume.skip(out_frame as u32);
+        let mut clip_pan = *pan;
+        clip_pan.skip(out_frame as u32);
+        let steady_pan = steady_pan_gains(&clip_pan, channels);
+
+        let first_clip_frame = start - clip_start;
+        for (i, frame) in dest.chunks_exact_mut(channels).enumerate() {
+            let clip_frame_idx = first_clip_frame + i as u64;
+            // Where that is in the audio buffer
+            let Some(source_frame) = clip_source_frame(
+                clip_frame_idx,
+                clip.offset,
+                clip_total_frames,
+                clip.looped,
+                clip.reversed,
+            ) else {
+                break;
+            };
+            let source_frame = source_frame as usize;
+            let clip_frame = &samples[source_frame * clip_channels..][..clip_channels];
+            let mut gain = clip_volume.next() * clip.gain;
+            if clip_frame_idx < clip.fade_in {
+                gain *= fade_gain(clip_frame_idx, clip.fade_in);
+            }
+            if clip_frame_idx >= effective_length {
+                gain *= fade_gain(
+                    effective_length + clip.fade_out - clip_frame_idx,
+                    clip.fade_out,
+                );
+            }
+            let channel_gains = steady_pan.unwrap_or_else(|| pan_gains(clip_pan.next()));
+            for (ch, mix_sample) in frame.iter_mut().enumerate() {
+                *mix_sample += clip_frame[ch % clip_channels] * gain * channel_gains[ch % 2];
+            }
+        }
+    }
+}
+
+/// Frames a master mute or unmute ramps over: short enough to feel instant, long
+/// enough not to click
+const MASTER_RAMP_FRAMES: u32 = 64;
+
+/// Gain on the master output, ramping towards muted or unmuted
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct MasterGain {
+    gain: Smoother,
+}
+
+impl Default for MasterGain {
+    fn default() -> Self {
+        Self {
+            gain: Smoother::linear(1.0, MASTER_RAMP_FRAMES),
+        }
+    }
+}
+
+impl MasterGain {
+    pub(crate) fn set_muted(&mut self, muted: bool) {
+        self.gain.set_target(if muted { 0.0 } else { 1.0 });
+    }
+
+    /// Whether the output is muted and done fading out
+    pub(crate) fn is_silent(&self) -> bool {
+        self.gain.is_settled() && self.gain.target() == 0.0
+    }
+
+    /// Jump straight to the target gain, skipping the ramp
+    pub(crate) fn settle(&mut self) {
+        self.gain.settle();
+    }
+
+    /// Apply the gain to an interleaved block, ramping it towards the target
+    pub(crate) fn apply(&mut self, out: &mut [f32], channels: usize) {
+        if self.gain.is_settled() {
+            if self.gain.value() == 0.0 {
+                out.fill(0.0);
+            }
+            return;
+        }
+
+        for frame in out.chunks_mut(channels) {
+            let gain = self.gain.next();
+            for sample in frame {
+                *sample *= gain;
+            }
+        }
+    }
+}
+
+/// Raise `peak` to the highest absolute sample on the left and right of an
+/// interleaved block; mono counts as both sides
+pub(crate) fn measure_peak(out: &[f32], channels: usize, peak: &mut [f32; 2]) {
+    for frame in out.chunks(channels) {
+        let right = frame.get(1).unwrap_or(&frame[0]);
+        peak[0] = peak[0].max(frame[0].abs());
+        peak[1] = peak[1].max(right.abs());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EngineClip, EngineNote, EngineSend};
+    use basedrop::Collector;
+    use daw_dsp::{DelaySettings, Effect};
+    use daw_transport::{
+        Adsr, AudioArc, AutomationCurve, AutomationLane, AutomationPoint, PPQN, Sampler,
+        TempoCurve, Track, TrackAutomation, TrackId,
+    };
+
+    /// Mono clip whose samples are 1.0, 2.0, 3.0, ... so positions are easy to check
+    fn ramp_clip(start: u64, frames: usize) -> EngineClip {
+        let samples = (1..=frames).map(|i| i as f32).collect();
+        EngineClip {
+            start,
+            audio: AudioArc::new(samples, 48000, 1),
+            offset: 0,
+            length: None,
+            fade_in: 0,
+            fade_out: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+        }
+    }
+
+    fn track(clips: Vec<EngineClip>) -> EngineTrack {
+        EngineTrack {
+            id: 0,
+            clips,
+            volume: 1.0,
+            pan: 0.0,
+            automation: TrackAutomation::default(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+            plugins: Vec::new(),
+            midi_input: false,
+            sampler: None,
+            notes: Vec::new(),
+        }
+    }
+
+    fn gains() -> TrackState {
+        TrackState::new(48000, 2)
+    }
+
+    #[test]
+    fn test_count_in_hands_over_within_the_block() {
+        let clicks = vec![track(vec![ramp_clip(0, 100)])];
+        let mut position = 4;
+        let mut out = vec![0.0; 4];
+
+        let counted = count_in_block(&clicks, &mut gains(), &mut position, 6, &mut out, 1);
+        assert_eq!(counted, 2);
+        assert_eq!(position, 6);
+        assert_eq!(&out[..2], &[5.0, 6.0]);
+
+        // Once it's over it takes nothing
+        assert_eq!(
+            count_in_block(&clicks, &mut gains(), &mut position, 6, &mut out, 1),
+            0
+        );
+    }
+
+    #[test]
+    fn test_play_block_stops_at_end() {
+        let tracks = vec![track(vec![ramp_clip(0, 100)])];
+        let end = PlaybackEnd {
+            sample: 6,
+            action: EndAction::Stop,
+        };
+        let mut position = 4;
+        let mut out = vec![0.0; 4];
+
+        assert!(play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            Some(end),
+            None,
+            &mut out,
+            1
+        ));
+        assert_eq!(out, vec![5.0, 6.0, 0.0, 0.0]);
+        assert_eq!(position, 6);
+
+        // Stopping exactly on a block boundary happens on the next block
+        let mut position = 2;
+        assert!(!play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            Some(end),
+            None,
+            &mut out,
+            1
+        ));
+        assert!(play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            Some(end),
+            None,
+            &mut out,
+            1
+        ));
+    }
+
+    #[test]
+    fn test_play_block_loops_within_block() {
+        let tracks = vec![track(vec![ramp_clip(0, 100)])];
+        let end = PlaybackEnd {
+            sample: 3,
+            action: EndAction::Loop,
+        };
+        let mut position = 1;
+        let mut out = vec![0.0; 6];
+
+        assert!(!play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            Some(end),
+            None,
+            &mut out,
+            1
+        ));
+        assert_eq!(out, vec![2.0, 3.0, 1.0, 2.0, 3.0, 1.0]);
+        assert_eq!(position, 1);
+
+        // Without an end playback simply continues
+        let mut position = 1;
+        play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            None,
+            None,
+            &mut out,
+            1,
+        );
+        assert_eq!(position, 7);
+    }
+
+    #[test]
+    fn test_play_block_cycles_the_loop_range() {
+        let tracks = vec![track(vec![ramp_clip(0, 100)])];
+        let loop_range = LoopRange { start: 2, end: 4 };
+        let mut position = 0;
+        let mut out = vec![0.0; 7];
+        assert!(!play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            None,
+            Some(loop_range),
+            &mut out,
+            1
+        ));
+        assert_eq!(out, vec![1.0, 2.0, 3.0, 4.0, 3.0, 4.0, 3.0]);
+        assert_eq!(position, 3);
+
+        // Reaching the loop's end on a block boundary jumps on the next block
+        let mut position = 2;
+        let mut out = vec![0.0; 2];
+        for _ in 0..2 {
+            play_block(
+                &tracks,
+                &mut gains(),
+                &mut position,
+                None,
+                Some(loop_range),
+                &mut out,
+                1,
+            );
+            assert_eq!(out, vec![3.0, 4.0]);
+        }
+
+        // Past the range, or with the end before it, the end applies
+        let end = PlaybackEnd {
+            sample: 3,
+            action: EndAction::Stop,
+        };
+        let mut position = 0;
+        let mut out = vec![0.0; 7];
+        assert!(play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            Some(end),
+            Some(loop_range),
+            &mut out,
+            1
+        ));
+        let mut position = 5;
+        play_block(
+            &tracks,
+            &mut gains(),
+            &mut position,
+            None,
+            Some(loop_range),
+            &mut out,
+            1,
+        );
+        assert_eq!(position, 12);
+    }
+
+    #[test]
+    fn test_clip_starting_mid_block_is_not_quantized() {
+        let tracks = vec![track(vec![ramp_clip(1_000_003, 100)])];
+        let mut out = vec![0.0; 512];
+
+        // Block starting at 1_000_000 covers the clip start three frames in
+        mix_block(&tracks, &mut gains(), 1_000_000, &mut out, 1);
+
+        assert_eq!(&out[..3], &[0.0, 0.0, 0.0]);
+        assert_eq!(out[3], 1.0);
+        assert_eq!(out[4], 2.0);
+        assert_eq!(out[102], 100.0);
+        assert_eq!(out[103], 0.0);
+    }
+
+    #[test]
+    fn test_block_size_does_not_change_output() {
+        let tracks = vec![track(vec![
+            ramp_clip(1_000_003, 1000),
+            ramp_clip(999_990, 50),
+        ])];
+        let start = 999_936;
+        let total = 2048;
+
+        let mut whole = vec![0.0; total];
+        mix_block(&tracks, &mut gains(), start, &mut whole, 1);
+
+        for block_size in [1, 7, 64, 512] {
+            let mut pieces = vec![0.0; total];
+            for (i, block) in pieces.chunks_mut(block_size).enumerate() {
+                mix_block(
+                    &tracks,
+                    &mut gains(),
+                    start + (i * block_size) as u64,
+                    block,
+                    1,
+                );
+            }
+            assert_eq!(pieces, whole, "block size {}", block_size);
+        }
+    }
+
+    #[test]
+    fn test_clip_offset_and_length() {
+        let mut clip = ramp_clip(10, 100);
+        clip.offset = 20;
+        clip.length = Some(5);
+        let tracks = vec![track(vec![clip])];
+        let mut out = vec![0.0; 32];
+
+        mix_block(&tracks, &mut gains(), 0, &mut out, 1);
+
+        // Plays frames 20..25 of the audio (values 21..=25) at timeline 10..15
+        assert_eq!(&out[9..16], &[0.0, 21.0, 22.0, 23.0, 24.0, 25.0, 0.0]);
+    }
+
+    #[test]
+    fn test_looped_and_reversed_clips_with_gain() {
+        let mut looped = ramp_clip(0, 3);
+        looped.length = Some(7);
+        looped.looped = true;
+        let mut reversed = ramp_clip(10, 4);
+        reversed.offset = 1;
+        reversed.reversed = true;
+        reversed.gain = 0.5;
+        let tracks = vec![track(vec![looped, reversed])];
+        let mut out = vec![0.0; 16];
+
+        mix_block(&tracks, &mut gains(), 0, &mut out, 1);
+
+        // The loop repeats 1, 2, 3 for its whole length
+        assert_eq!(&out[..8], &[1.0, 2.0, 3.0, 1.0, 2.0, 3.0, 1.0, 0.0]);
+        // Backwards from the second-to-last frame (3.0), at half gain
+        assert_eq!(&out[9..14], &[0.0, 1.5, 1.0, 0.5, 0.0]);
+    }
+
+    #[test]
+    fn test_mono_clip_fills_all_output_channels_with_volume() {
+        let mut tracks = vec![track(vec![ramp_clip(1, 2)])];
+        tracks[0].volume = 0.5;
+        let mut out = vec![0.0; 8];
+
+        mix_block(&tracks, &mut gains(), 0, &mut out, 2);
+
+        assert_eq!(out, vec![0.0, 0.0, 0.5, 0.5, 1.0, 1.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_volume_change_glides_across_blocks() {
+        let mut clip = ramp_clip(0, 4800);
+        clip.audio = AudioArc::new(vec![1.0; 4800], 48000, 1);
+        let mut tracks = vec![track(vec![clip])];
+        let start = 64;
+
+        // Render the same volume change in one block and in small ones
+        let render = |block_size: usize, tracks: &mut Vec<EngineTrack>| {
+            let mut gains = gains();
+            let mut warmup = vec![0.0; 64];
+            tracks[0].volume = 1.0;
+            mix_block(tracks, &mut gains, 0, &mut warmup, 1);
+            tracks[0].volume = 0.0;
+            let mut out = vec![0.0; 3600];
+            for (i, block) in out.chunks_mut(block_size).enumerate() {
+                mix_block(
+                    tracks,
+                    &mut gains,
+                    start + (i * block_size) as u64,
+                    block,
+                    1,
+                );
+            }
+            out
+        };
+        let whole = render(3600, &mut tracks);
+
+        // No jump: falls gradually and reaches silence well within the block
+        assert!(whole[0] > 0.99);
+        assert!(whole.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!(whole[1200] < 0.01);
+        assert_eq!(*whole.last().unwrap(), 0.0);
+
+        for block_size in [1, 7, 256] {
+            let pieces = render(block_size, &mut tracks);
+            for (i, (a, b)) in pieces.iter().zip(&whole).enumerate() {
+                assert!((a - b).abs() < 1e-6, "block size {block_size}, frame {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_volume_and_pan_follow_automation() {
+        let point = |tick, value, curve| AutomationPoint { tick, value, curve };
+        let mut volume = AutomationLane::new("volume");
+        volume.points = vec![
+            point(0, 1.0, AutomationCurve::Step),
+            point(PPQN, 0.0, AutomationCurve::Step),
+        ];
+        let mut pan = AutomationLane::new("pan");
+        pan.points = vec![point(0, 1.0, AutomationCurve::Linear)];
+        let mut automated = Track::new(TrackId(0), "Automated".to_string());
+        automated.automation = vec![volume, pan];
+
+        // A beat lasts 24000 samples at 120 BPM
+        let mut clip = ramp_clip(0, 48000);
+        clip.audio = AudioArc::new(vec![1.0; 48000], 48000, 1);
+        let mut tracks = vec![track(vec![clip])];
+        tracks[0].automation = TrackAutomation::new(
+            &[automated.clone()],
+            &automated,
+            &TempoCurve::constant(120.0),
+            48000,
+        );
+
+        let mut gains = gains();
+        let mut out = vec![0.0; 48000 * 2];
+        for (i, block) in out.chunks_mut(512).enumerate() {
+            mix_block(&tracks, &mut gains, (i * 256) as u64, block, 2);
+        }
+
+        // Panned hard right, until the volume drops away after the first beat
+        let frame = |sample: usize| [out[sample * 2], out[sample * 2 + 1]];
+        assert!(frame(1000)[0].abs() < 1e-6);
+        assert!((frame(1000)[1] - std::f32::consts::SQRT_2).abs() < 1e-6);
+        assert!(frame(24000)[1] > 1.0);
+        assert!(frame(26000)[1] < 0.01);
+    }
+
+    #[test]
+    fn test_effects_run_before_volume_and_ring_past_the_clips() {
+        let mut tracks = vec![track(vec![ramp_clip(0, 1)])];
+        tracks[0].id = 3;
+        tracks[0].volume = 0.5;
+        // 1 ms is 48 frames
+        tracks[0].effects = vec![Effect::Delay(DelaySettings {
+            time_ms: 1.0,
+            feedback: 0.0,
+            mix: 1.0,
+        })];
+
+        let collector = Collector::new();
+        let mut gains = gains();
+        let chain = InsertChain::new(&tracks[0].effects, 48000);
+        gains.set_inserts(3, Owned::new(&collector.handle(), chain));
+        let mut out = vec![0.0; 64];
+        mix_block(&tracks, &mut gains, 0, &mut out[..32], 1);
+        // A track added in front doesn't take over the echo on its way
+        tracks.insert(0, track(Vec::new()));
+        mix_block(&tracks, &mut gains, 32, &mut out[32..], 1);
+
+        // Only the echo is heard, in a block where no clip plays
+        assert_eq!(out[48], 0.5);
+        assert_eq!(out.iter().filter(|sample| **sample != 0.0).count(), 1);
+    }
+
+    /// Plugin stand-in that doubles what it's given
+    struct Double;
+
+    impl Processor for Double {
+        fn process(&mut self, block: &mut [f32], _channels: usize) {
+            block.iter_mut().for_each(|sample| *sample *= 2.0);
+        }
+    }
+
+    #[test]
+    fn test_plugins_run_by_id_before_volume() {
+        let mut tracks = vec![track(vec![ramp_clip(0, 4)]), track(vec![ramp_clip(0, 4)])];
+        tracks[0].volume = 0.5;
+        tracks[0].plugins = vec![7, 7];
+        // A plugin that hasn't arrived yet is skipped
+        tracks[1].plugins = vec![8];
+
+        let mut gains = gains();
+        gains.add_plugin(PluginInsert {
+            id: 7,
+            processor: Box::new(Double),
+        });
+        let mut out = vec![0.0; 4];
+        mix_block(&tracks, &mut gains, 0, &mut out, 1);
+        assert_eq!(out, [3.0, 6.0, 9.0, 12.0]);
+
+        assert!(gains.remove_plugin(7).is_some());
+        assert!(gains.remove_plugin(7).is_none());
+        mix_block(&tracks, &mut gains, 0, &mut out, 1);
+        assert_eq!(out, [1.5, 3.0, 4.5, 6.0]);
+    }
+
+    #[test]
+    fn test_live_midi_goes_to_armed_tracks_in_the_next_block() {
+        let mut tracks = vec![track(Vec::new()), track(Vec::new()), track(Vec::new())];
+        tracks[0].midi_input = true;
+        tracks[2].midi_input = true;
+        let message = MidiMessage::NoteOn {
+            channel: 0,
+            note: 60,
+            velocity: 100,
+        };
+        let note = LiveNote::On {
+            note: 60,
+            velocity: 100,
+        };
+
+        let mut gains = gains();
+        gains.receive_midi(message);
+        let mut out = vec![0.0; 4];
+        mix_block(&tracks, &mut gains, 0, &mut out, 1);
+        assert_eq!(gains.track_midi[..3], [vec![note], Vec::new(), vec![note]]);
+
+        // Each message is played once
+        mix_block(&tracks, &mut gains, 4, &mut out, 1);
+        assert!(gains.track_midi.iter().all(Vec::is_empty));
+
+        gains.receive_midi(message);
+        gains.drop_midi();
+        mix_block(&tracks, &mut gains, 8, &mut out, 1);
+        assert!(gains.track_midi.iter().all(Vec::is_empty));
+    }
+
+    /// A track whose sampler plays samples of 1.0 at full level on every note
+    fn sampler_track() -> EngineTrack {
+        let mut sampler = Sampler::pitched(AudioArc::new(vec![1.0; 4800], 48000, 1), 60);
+        sampler.envelope = Adsr {
+            attack: 0.0,
+            decay: 0.0,
+            sustain: 1.0,
+            release: 0.0,
+        };
+        EngineTrack {
+            sampler: Some(sampler),
+            ..track(Vec::new())
+        }
+    }
+
+    #[test]
+    fn test_sampler_plays_notes_through_the_track() {
+        let mut piano = sampler_track();
+        piano.volume = 0.5;
+        piano.notes = vec![EngineNote {
+            start: 2,
+            end: 6,
+            pitch: 64,
+            velocity: 127,
+        }];
+        let tracks = vec![piano];
+
+        let mut out = vec![0.0; 8];
+        mix_block(&tracks, &mut gains(), 0, &mut out, 1);
+        assert_eq!(out, [0.0, 0.0, 0.5, 0.5, 0.5, 0.5, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_live_block_plays_midi_on_armed_samplers_only() {
+        let mut armed = sampler_track();
+        armed.midi_input = true;
+        let mut clip = ramp_clip(0, 4);
+        clip.audio = AudioArc::new(vec![1.0; 4], 48000, 1);
+        let mut tracks = vec![armed, sampler_track(), track(vec![clip])];
+        tracks[1].notes = vec![EngineNote {
+            start: 0,
+            end: 4,
+            pitch: 60,
+            velocity: 127,
+        }];
+
+        let mut gains = gains();
+        assert!(!gains.has_live_sound());
+        gains.receive_midi(MidiMessage::NoteOn {
+            channel: 0,
+            note: 60,
+            velocity: 127,
+        });
+        assert!(gains.has_live_sound());
+        let mut out = vec![0.0; 4];
+        live_block(&tracks, &mut gains, 0, &mut out, 1);
+        // Neither the other track's notes nor the clips play
+        assert_eq!(out, [1.0; 4]);
+
+        gains.receive_midi(MidiMessage::NoteOff {
+            channel: 0,
+            note: 60,
+            velocity: 0,
+        });
+        live_block(&tracks, &mut gains, 0, &mut out, 1);
+        assert!(!gains.has_live_sound());
+    }
+
+    #[test]
+    fn test_sends_feed_buses_before_or_after_the_fader() {
+        let mut clip = ramp_clip(0, 64);
+        clip.audio = AudioArc::new(vec![1.0; 64], 48000, 1);
+        // The bus comes first in the list, but it's mixed after what it's sent
+        let mut bus = track(Vec::new());
+        bus.is_bus = true;
+        bus.volume = 0.5;
+        let mut vocal = track(vec![clip]);
+        vocal.volume = 0.5;
+        vocal.sends = vec![EngineSend {
+            bus: 0,
+            level: 0.5,
+            pre_fader: false,
+        }];
+        let mut tracks = vec![bus, vocal];
+
+        let mut out = vec![0.0; 32];
+        mix_block(&tracks, &mut gains(), 0, &mut out, 1);
+        // 0.5 straight through, and 0.5 * 0.5 sent to the bus at 0.5
+        assert_eq!(out, vec![0.625; 32]);
+
+        // Before the fader the vocal's own volume doesn't reach the bus
+        tracks[1].sends[0].pre_fader = true;
+        mix_block(&tracks, &mut gains(), 0, &mut out, 1);
+        assert_eq!(out, vec![0.75; 32]);
+    }
+
+    #[test]
+    fn test_crossfade_tail_overlaps_next_clip() {
+        let mut outgoing = ramp_clip(0, 100);
+        outgoing.audio = AudioArc::new(vec![1.0; 100], 48000, 1);
+        outgoing.length = Some(10);
+        outgoing.fade_out = 4;
+        let mut incoming = ramp_clip(10, 100);
+        incoming.audio = AudioArc::new(vec![1.0; 100], 48000, 1);
+        incoming.fade_in = 4;
+        let tracks = vec![track(vec![outgoing, incoming])];
+        let mut out = vec![0.0; 20];
+
+        mix_block(&tracks, &mut gains(), 0, &mut out, 1);
+
+        // Full level before the cut and after the crossfade
+        assert_eq!(&out[..10], &[1.0; 10]);
+        assert_eq!(&out[14..], &[1.0; 6]);
+        // During the crossfade both clips play, starting from the outgoing clip alone
+        assert_eq!(out[10], 1.0);
+        for (i, &sample) in out[10..14].iter().enumerate() {
+            let expected = fade_gain(4 - i as u64, 4) + fade_gain(i as u64, 4);
+            assert!((sample - expected).abs() < 1e-6, "frame {}", 10 + i);
+        }
+    }
+
+    #[test]
+    fn test_peak_is_measured_per_side() {
+        let mut peak = [0.0; 2];
+        measure_peak(&[0.5, -0.25, -0.75, 0.1], 2, &mut peak);
+        assert_eq!(peak, [0.75, 0.25]);
+        measure_peak(&[0.2, 0.0, 0.0], 3, &mut peak);
+        assert_eq!(peak, [0.75, 0.25]);
+        measure_peak(&[-0.5, 0.9], 1, &mut peak);
+        assert_eq!(peak, [0.9, 0.9]);
+    }
+
+    #[test]
+    fn test_master_mute_ramps_and_settles() {
+        let mut master = MasterGain::default();
+        master.set_muted(true);
+
+        // Fades out over the ramp, then stays silent
+        let mut out = vec![1.0; 2 * MASTER_RAMP_FRAMES as usize];
+        master.apply(&mut out, 2);
+        assert!(out[0] < 1.0 && out[0] > 0.9);
+        assert_eq!(out[0], out[1]);
+        assert!(out.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert_eq!(*out.last().unwrap(), 0.0);
+        assert!(master.is_silent());
+
+        let mut out = vec![1.0; 8];
+        master.apply(&mut out, 2);
+        assert_eq!(out, vec![0.0; 8]);
+
+        // Unmuting while nothing plays takes effect at once
+        master.set_muted(false);
+        master.settle();
+        let mut out = vec![1.0; 8];
+        master.apply(&mut out, 2);
+        assert_eq!(out, vec![1.0; 8]);
+    }
+}
@@ -0,0 +1,191 @@
+use basedrop::Shared;
+use daw_dsp::Smoother;
+use daw_transport::AudioArc;
+
+/// Voices that can sound at once; starting another steals the oldest
+pub(crate) const MAX_VOICES: usize = 32;
+
+/// Frames a stopped or stolen voice fades out over, so it doesn't click
+const RELEASE_FRAMES: u32 = 128;
+
+/// One sample playing from its start, outside the arrangement
+struct Voice {
+    id: u64,
+    audio: Shared<AudioArc>,
+    /// Next frame of the audio to play
+    position: usize,
+    gain: Smoother,
+    released: bool,
+}
+
+impl Voice {
+    fn release(&mut self) {
+        self.released = true;
+        self.gain.set_target(0.0);
+    }
+
+    fn is_finished(&self) -> bool {
+        self.position >= self.audio.frames() || self.released && self.gain.is_settled()
+    }
+}
+
+/// Samples triggered live, such as pads and previews, mixed on top of the
+/// arrangement whether or not it is playing.
+///
+/// Each voice plays its audio once from the start. Voices are keyed by an id
+/// chosen by the caller: starting a voice fades out whatever was playing under
+/// the same id, like a drum machine choking a retriggered pad.
+pub(crate) struct Voices {
+    voices: Vec<Voice>,
+}
+
+impl Voices {
+    pub(crate) fn new() -> Self {
+        Self {
+            voices: Vec::with_capacity(MAX_VOICES + 1),
+        }
+    }
+
+    /// Whether nothing is sounding
+    pub(crate) fn is_empty(&self) -> bool {
+        self.voices.is_empty()
+    }
+
+    /// Start playing `audio` from its start at `gain`
+    pub(crate) fn start(&mut self, id: u64, audio: Shared<AudioArc>, gain: f32) {
+        self.stop(id);
+        if self.voices.len() >= MAX_VOICES {
+            // Dropping the oldest voice hands its audio to the collector
+            self.voices.remove(0);
+        }
+        self.voices.push(Voice {
+            id,
+            audio,
+            position: 0,
+            gain: Smoother::linear(gain, RELEASE_FRAMES),
+            released: false,
+        });
+    }
+
+    /// Fade out the voice playing under `id`, if any
+    pub(crate) fn stop(&mut self, id: u64) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.id == id) {
+            voice.release();
+        }
+    }
+
+    /// Glide the voice playing under `id` to a new gain; a voice already fading
+    /// out keeps fading
+    pub(crate) fn set_gain(&mut self, id: u64, gain: f32) {
+        for voice in self.voices.iter_mut().filter(|voice| voice.id == id) {
+            if !voice.released {
+                voice.gain.set_target(gain);
+            }
+        }
+    }
+
+    /// Fade out every voice
+    pub(crate) fn stop_all(&mut self) {
+        for voice in &mut self.voices {
+            voice.release();
+        }
+    }
+
+    /// Add the next `out.len() / channels` frames of every voice into `out`
+    pub(crate) fn mix(&mut self, out: &mut [f32], channels: usize) {
+        for voice in &mut self.voices {
+            let voice_channels = voice.audio.channels() as usize;
+            let remaining = &voice.audio.samples()[voice.position * voice_channels..];
+            let mut played = 0;
+            for (frame, source) in out
+                .chunks_exact_mut(channels)
+                .zip(remaining.chunks_exact(voice_channels))
+            {
+                let gain = voice.gain.next();
+                for (ch, sample) in frame.iter_mut().enumerate() {
+                    *sample += source[ch % voice_channels] * gain;
+                }
+                played += 1;
+            }
+            voice.position += played;
+        }
+        self.voices.retain(|voice| !voice.is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use basedrop::Collector;
+
+    fn shared(collector: &Collector, samples: Vec<f32>) -> Shared<AudioArc> {
+        Shared::new(&collector.handle(), AudioArc::new(samples, 48000, 1))
+    }
+
+    #[test]
+    fn test_voice_plays_once_from_start() {
+        let collector = Collector::new();
+        let mut voices = Voices::new();
+        voices.start(1, shared(&collector, vec![1.0, 2.0, 3.0]), 0.5);
+
+        let mut out = vec![0.0; 4];
+        voices.mix(&mut out, 2);
+        assert_eq!(out, vec![0.5, 0.5, 1.0, 1.0]);
+
+        let mut out = vec![10.0; 4];
+        voices.mix(&mut out, 2);
+        assert_eq!(out, vec![11.5, 11.5, 10.0, 10.0]);
+        assert!(voices.is_empty());
+    }
+
+    #[test]
+    fn test_retrigger_chokes_same_id_only() {
+        let collector = Collector::new();
+        let mut voices = Voices::new();
+        let long = vec![1.0; 10_000];
+        voices.start(1, shared(&collector, long.clone()), 1.0);
+        voices.start(2, shared(&collector, long.clone()), 1.0);
+        voices.start(1, shared(&collector, long), 1.0);
+
+        // The first voice fades out over the release while the other two carry on
+        let mut out = vec![0.0; RELEASE_FRAMES as usize];
+        voices.mix(&mut out, 1);
+        assert!((out[0] - 3.0).abs() < 0.01);
+        assert!((out[RELEASE_FRAMES as usize - 1] - 2.0).abs() < 1e-6);
+        assert_eq!(voices.voices.len(), 2);
+
+        voices.stop_all();
+        let mut out = vec![0.0; RELEASE_FRAMES as usize];
+        voices.mix(&mut out, 1);
+        assert!(voices.is_empty());
+    }
+
+    #[test]
+    fn test_full_pool_steals_oldest() {
+        let collector = Collector::new();
+        let mut voices = Voices::new();
+        for id in 0..=MAX_VOICES as u64 {
+            voices.start(id, shared(&collector, vec![1.0; 100]), 1.0);
+        }
+        assert_eq!(voices.voices.len(), MAX_VOICES);
+        assert_eq!(voices.voices[0].id, 1);
+    }
+
+    #[test]
+    fn test_set_gain_glides_but_never_revives() {
+        let collector = Collector::new();
+        let mut voices = Voices::new();
+        voices.start(1, shared(&collector, vec![1.0; 10_000]), 1.0);
+        voices.set_gain(1, 0.5);
+        let mut out = vec![0.0; RELEASE_FRAMES as usize + 1];
+        voices.mix(&mut out, 1);
+        assert!(out[0] < 1.0 && out[0] > 0.5);
+        assert_eq!(out[RELEASE_FRAMES as usize], 0.5);
+
+        voices.stop(1);
+        voices.set_gain(1, 1.0);
+        let mut out = vec![0.0; RELEASE_FRAMES as usize];
+        voices.mix(&mut out, 1);
+        assert!(voices.is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+//! Opening MIDI input devices and the queue their messages travel through.
+
+use midir::{Ignore, MidiInputConnection};
+
+use crate::{MidiError, MidiMessage};
+
+/// Name this program goes by to the system's MIDI service
+const CLIENT_NAME: &str = "Cedar";
+
+/// Messages the queue from a device holds; more than that between two audio
+/// callbacks are dropped
+const MIDI_QUEUE_SIZE: usize = 1024;
+
+/// The sending end of a MIDI queue, fed by a device or by hand
+#[derive(Debug)]
+pub struct MidiSender(rtrb::Producer<MidiMessage>);
+
+impl MidiSender {
+    /// Queue a message. Returns `false` if the queue is full and it was dropped.
+    pub fn send(&mut self, message: MidiMessage) -> bool {
+        self.0.push(message).is_ok()
+    }
+}
+
+/// The receiving end of a MIDI queue, read on the audio thread
+#[derive(Debug)]
+pub struct MidiReceiver(rtrb::Consumer<MidiMessage>);
+
+impl MidiReceiver {
+    /// The oldest message not yet received, if any
+    pub fn pop(&mut self) -> Option<MidiMessage> {
+        self.0.pop().ok()
+    }
+}
+
+/// A lock-free queue for MIDI messages holding up to `capacity` of them, like
+/// the one a device feeds. Neither end locks or allocates.
+pub fn channel(capacity: usize) -> (MidiSender, MidiReceiver) {
+    let (sender, receiver) = rtrb::RingBuffer::new(capacity);
+    (MidiSender(sender), MidiReceiver(receiver))
+}
+
+fn new_client() -> Result<midir::MidiInput, MidiError> {
+    let mut client =
+        midir::MidiInput::new(CLIENT_NAME).map_err(|err| MidiError::Init(err.to_string()))?;
+    // Sysex, clock and active sensing would only crowd the queue
+    client.ignore(Ignore::All);
+    Ok(client)
+}
+
+/// Names of the MIDI input devices connected now, to open with [`MidiInput::open`]
+pub fn input_devices() -> Result<Vec<String>, MidiError> {
+    let client = new_client()?;
+    Ok(client
+        .ports()
+        .iter()
+        .filter_map(|port| client.port_name(port).ok())
+        .collect())
+}
+
+/// A MIDI input device, sending its messages to the [`MidiReceiver`] it was
+/// opened with until it's dropped.
+pub struct MidiInput {
+    device: String,
+    _connection: MidiInputConnection<MidiSender>,
+}
+
+impl MidiInput {
+    /// Connect to the input device called `device`, as listed by [`input_devices`]
+    pub fn open(device: &str) -> Result<(Self, MidiReceiver), MidiError> {
+        let client = new_client()?;
+        let port = client
+            .ports()
+            .into_iter()
+            .find(|port| client.port_name(port).is_ok_and(|name| name == device))
+            .ok_or_else(|| MidiError::NotFound(device.to_string()))?;
+
+        let (sender, receiver) = channel(MIDI_QUEUE_SIZE);
+        let connection = client
+            .connect(
+                &port,
+                CLIENT_NAME,
+                |_timestamp, bytes, sender: &mut MidiSender| {
+                    if let Some(message) = MidiMessage::parse(bytes) {
+                        sender.send(message);
+                    }
+                },
+                sender,
+            )
+            .map_err(|err| MidiError::Connect {
+                device: device.to_string(),
+                reason: err.to_string(),
+            })?;
+
+        let input = Self {
+            device: device.to_string(),
+            _connection: connection,
+        };
+        Ok((input, receiver))
+    }
+
+    /// Name of the device, as it was opened
+    pub fn device(&self) -> &str {
+        &self.device
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_channel_delivers_in_order_and_drops_when_full() {
+        let (mut sender, mut receiver) = channel(2);
+        let note = |note| MidiMessage::NoteOn {
+            channel: 0,
+            note,
+            velocity: 100,
+        };
+        assert!(sender.send(note(60)));
+        assert!(sender.send(note(62)));
+        assert!(!sender.send(note(64)));
+
+        assert_eq!(receiver.pop(), Some(note(60)));
+        assert_eq!(receiver.pop(), Some(note(62)));
+        assert_eq!(receiver.pop(), None);
+    }
+}
@@ -0,0 +1,26 @@
+//! MIDI input from hardware and virtual devices, for playing tracks live.
+//!
+//! A device is opened by name with [`MidiInput::open`], from the names
+//! [`input_devices`] lists. Its messages arrive on a thread of the MIDI
+//! library's, which only parses them and pushes them into a lock-free queue; the
+//! engine drains the other end, a [`MidiReceiver`], on the audio thread without
+//! locking or allocating.
+
+mod input;
+mod message;
+
+pub use input::{MidiInput, MidiReceiver, MidiSender, channel, input_devices};
+pub use message::MidiMessage;
+
+/// Something that went wrong finding or opening a MIDI device
+#[derive(Debug, thiserror::Error)]
+pub enum MidiError {
+    #[error("couldn't start MIDI: {0}")]
+    Init(String),
+
+    #[error("no MIDI input called {0}")]
+    NotFound(String),
+
+    #[error("couldn't connect to {device}: {reason}")]
+    Connect { device: String, reason: String },
+}
@@ -0,0 +1,144 @@
+//! The channel messages tracks respond to.
+
+/// A MIDI channel message, with channels counted from 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    /// Also sent for a note-on with velocity 0, which is how many devices
+    /// release notes
+    NoteOff {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    /// Bend from -8192 to 8191, 0 being the centre
+    PitchBend {
+        channel: u8,
+        value: i16,
+    },
+}
+
+impl MidiMessage {
+    /// Parse one complete message as it came from a device. Returns `None` for
+    /// messages tracks don't use, such as system messages, or ones cut short.
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let (&status, data) = bytes.split_first()?;
+        let channel = status & 0x0f;
+        let data = |i: usize| data.get(i).map(|byte| byte & 0x7f);
+        match status & 0xf0 {
+            0x80 => Some(Self::NoteOff {
+                channel,
+                note: data(0)?,
+                velocity: data(1)?,
+            }),
+            0x90 => {
+                let (note, velocity) = (data(0)?, data(1)?);
+                Some(if velocity == 0 {
+                    Self::NoteOff {
+                        channel,
+                        note,
+                        velocity: 0,
+                    }
+                } else {
+                    Self::NoteOn {
+                        channel,
+                        note,
+                        velocity,
+                    }
+                })
+            }
+            0xb0 => Some(Self::ControlChange {
+                channel,
+                controller: data(0)?,
+                value: data(1)?,
+            }),
+            0xe0 => {
+                let value = (data(1)? as i16) << 7 | data(0)? as i16;
+                Some(Self::PitchBend {
+                    channel,
+                    value: value - 8192,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notes_on_any_channel() {
+        assert_eq!(
+            MidiMessage::parse(&[0x93, 60, 100]),
+            Some(MidiMessage::NoteOn {
+                channel: 3,
+                note: 60,
+                velocity: 100
+            })
+        );
+        assert_eq!(
+            MidiMessage::parse(&[0x80, 60, 64]),
+            Some(MidiMessage::NoteOff {
+                channel: 0,
+                note: 60,
+                velocity: 64
+            })
+        );
+        // Running status aside, a note-on at velocity 0 releases the note
+        assert_eq!(
+            MidiMessage::parse(&[0x90, 60, 0]),
+            Some(MidiMessage::NoteOff {
+                channel: 0,
+                note: 60,
+                velocity: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_controllers_and_pitch_bend() {
+        assert_eq!(
+            MidiMessage::parse(&[0xb1, 64, 127]),
+            Some(MidiMessage::ControlChange {
+                channel: 1,
+                controller: 64,
+                value: 127
+            })
+        );
+        assert_eq!(
+            MidiMessage::parse(&[0xe0, 0x00, 0x40]),
+            Some(MidiMessage::PitchBend {
+                channel: 0,
+                value: 0
+            })
+        );
+        assert_eq!(
+            MidiMessage::parse(&[0xe0, 0x7f, 0x7f]),
+            Some(MidiMessage::PitchBend {
+                channel: 0,
+                value: 8191
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_skips_what_tracks_dont_use() {
+        // Clock, sysex, program change and a note cut short
+        assert_eq!(MidiMessage::parse(&[0xf8]), None);
+        assert_eq!(MidiMessage::parse(&[0xf0, 0x7e, 0xf7]), None);
+        assert_eq!(MidiMessage::parse(&[0xc0, 5]), None);
+        assert_eq!(MidiMessage::parse(&[0x90, 60]), None);
+        assert_eq!(MidiMessage::parse(&[]), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! Where a plugin's editor goes in the frontend.
+//!
+//! Plugins draw their own editors as a child of a native window. A frontend opens
+//! a window for the editor (gpui and Tauri windows both expose their native
+//! handle through `raw-window-handle`), wraps it in an [`EditorParent`], and sizes
+//! the window to the [`EditorSize`] the editor asks for.
+
+use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+use crate::PluginError;
+
+/// The native window a plugin's editor is embedded into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditorParent(RawWindowHandle);
+
+impl EditorParent {
+    pub fn new(handle: RawWindowHandle) -> Self {
+        Self(handle)
+    }
+
+    /// The parent for a window from any toolkit that exposes its native handle,
+    /// such as a gpui `Window` or a Tauri `Window`
+    pub fn from_window(window: &impl HasWindowHandle) -> Result<Self, PluginError> {
+        let handle = window
+            .window_handle()
+            .map_err(|err| PluginError::Window(err.to_string()))?;
+        Ok(Self(handle.as_raw()))
+    }
+
+    pub fn raw(&self) -> RawWindowHandle {
+        self.0
+    }
+}
+
+/// Size of a plugin's editor in pixels
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EditorSize {
+    pub width: u32,
+    pub height: u32,
+}
@@ -0,0 +1,48 @@
+//! Hosting third-party plugins.
+//!
+//! Plugins are loaded from the user's plugin folders, run as a [`daw_dsp::Processor`]
+//! on a track, and show their own editor inside a window the frontend opens for
+//! them (see [`EditorParent`]).
+//!
+//! # Features
+//!
+//! - `vst3` - scanning, loading and running VST3 plugins ([`Vst3Plugin`]) and
+//!   embedding their editors ([`Vst3Editor`])
+
+use std::path::PathBuf;
+
+mod editor;
+#[cfg(feature = "vst3")]
+mod vst3;
+
+pub use editor::{EditorParent, EditorSize};
+#[cfg(feature = "vst3")]
+pub use vst3::{
+    Vst3Editor, Vst3Info, Vst3Plugin, Vst3Processor, default_search_paths, find_bundles, scan,
+};
+
+/// Something that went wrong loading or talking to a plugin
+#[derive(Debug, thiserror::Error)]
+pub enum PluginError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("couldn't load {}: {reason}", path.display())]
+    Load { path: PathBuf, reason: String },
+
+    #[error("{} has no plugin {id}", path.display())]
+    NotFound { path: PathBuf, id: String },
+
+    /// A call into the plugin returned an error code
+    #[error("the plugin failed to {call} (result {result})")]
+    Call { call: &'static str, result: i32 },
+
+    #[error("the plugin has no editor")]
+    NoEditor,
+
+    #[error("the frontend's window has no handle: {0}")]
+    Window(String),
+
+    #[error("plugin editors can't be embedded in this kind of window")]
+    UnsupportedWindow,
+}
@@ -0,0 +1,648 @@
+//! The parts of the VST3 ABI the host uses, written out from the SDK headers.
+//!
+//! Interfaces are COM-style: an object starts with a pointer to its vtable, and
+//! every vtable starts with `FUnknown`'s three methods. Vtables list every method
+//! of their interface in order, called or not, so the slots line up.
+
+#![allow(dead_code)]
+
+use std::ffi::{c_char, c_void};
+use std::ptr::NonNull;
+
+pub type TResult = i32;
+pub type Tuid = [u8; 16];
+pub type TBool = u8;
+pub type ParamId = u32;
+/// UTF-16 string of at most 128 units, nul-terminated if shorter
+pub type String128 = [u16; 128];
+
+pub const RESULT_OK: TResult = 0;
+pub const RESULT_FALSE: TResult = 1;
+#[cfg(windows)]
+pub const NO_INTERFACE: TResult = 0x80004002_u32 as i32;
+#[cfg(not(windows))]
+pub const NO_INTERFACE: TResult = -1;
+#[cfg(windows)]
+pub const INVALID_ARGUMENT: TResult = 0x80070057_u32 as i32;
+#[cfg(not(windows))]
+pub const INVALID_ARGUMENT: TResult = 2;
+#[cfg(windows)]
+pub const NOT_IMPLEMENTED: TResult = 0x80004001_u32 as i32;
+#[cfg(not(windows))]
+pub const NOT_IMPLEMENTED: TResult = 3;
+
+pub const MEDIA_AUDIO: i32 = 0;
+pub const BUS_INPUT: i32 = 0;
+pub const BUS_OUTPUT: i32 = 1;
+pub const SPEAKER_STEREO: u64 = 0x3;
+pub const PROCESS_REALTIME: i32 = 0;
+pub const SAMPLE_32: i32 = 0;
+
+pub const SEEK_SET: i32 = 0;
+pub const SEEK_CUR: i32 = 1;
+pub const SEEK_END: i32 = 2;
+
+/// Category of the classes that process audio, effects and instruments alike
+pub const AUDIO_EFFECT_CLASS: &[u8] = b"Audio Module Class";
+pub const EDITOR_VIEW: &std::ffi::CStr = c"editor";
+
+/// An interface id from the four numbers the SDK declares it with. Windows
+/// builds of the SDK lay the first eight bytes out like a COM GUID.
+#[cfg(windows)]
+pub const fn uid(l1: u32, l2: u32, l3: u32, l4: u32) -> Tuid {
+    let [a, b, c, d] = l1.to_le_bytes();
+    let [e, f, g, h] = l2.to_be_bytes();
+    let [i, j, k, l] = l3.to_be_bytes();
+    let [m, n, o, p] = l4.to_be_bytes();
+    [a, b, c, d, f, e, h, g, i, j, k, l, m, n, o, p]
+}
+
+/// An interface id from the four numbers the SDK declares it with
+#[cfg(not(windows))]
+pub const fn uid(l1: u32, l2: u32, l3: u32, l4: u32) -> Tuid {
+    let [a, b, c, d] = l1.to_be_bytes();
+    let [e, f, g, h] = l2.to_be_bytes();
+    let [i, j, k, l] = l3.to_be_bytes();
+    let [m, n, o, p] = l4.to_be_bytes();
+    [a, b, c, d, e, f, g, h, i, j, k, l, m, n, o, p]
+}
+
+/// A vtable type, and the id of the interface it belongs to
+pub trait Interface {
+    const IID: Tuid;
+}
+
+/// A COM object as the host sees it: a pointer to its vtable
+#[repr(C)]
+pub struct Object<V> {
+    pub vtbl: *const V,
+}
+
+/// An owned reference to a plugin's object, released on drop
+pub struct ComPtr<V: Interface>(NonNull<Object<V>>);
+
+impl<V: Interface> ComPtr<V> {
+    /// Take over a reference the plugin handed out
+    ///
+    /// # Safety
+    /// `ptr` must be null or an object implementing `V` with a reference the
+    /// caller owns.
+    pub unsafe fn from_raw(ptr: *mut c_void) -> Option<Self> {
+        NonNull::new(ptr.cast()).map(Self)
+    }
+
+    pub fn as_ptr(&self) -> *mut c_void {
+        self.0.as_ptr().cast()
+    }
+
+    pub fn vtbl(&self) -> &V {
+        // SAFETY: the object is alive while we hold a reference, and so is its vtable
+        unsafe { &*(*self.0.as_ptr()).vtbl }
+    }
+
+    /// The same object as another interface, if it implements it
+    pub fn cast<U: Interface>(&self) -> Option<ComPtr<U>> {
+        query_interface(self.as_ptr())
+    }
+}
+
+impl<V: Interface> Drop for ComPtr<V> {
+    fn drop(&mut self) {
+        // SAFETY: every vtable starts with FUnknown's
+        unsafe {
+            let unknown = &*(*self.0.as_ptr().cast::<Object<FUnknownVtbl>>()).vtbl;
+            (unknown.release)(self.as_ptr());
+        }
+    }
+}
+
+/// Ask `object` for interface `U`
+pub fn query_interface<U: Interface>(object: *mut c_void) -> Option<ComPtr<U>> {
+    let mut result = std::ptr::null_mut();
+    // SAFETY: `object` is a live COM object; a successful query hands us a reference
+    unsafe {
+        let unknown = &*(*object.cast::<Object<FUnknownVtbl>>()).vtbl;
+        if (unknown.query_interface)(object, &U::IID, &mut result) != RESULT_OK {
+            return None;
+        }
+        ComPtr::from_raw(result)
+    }
+}
+
+#[repr(C)]
+pub struct FUnknownVtbl {
+    pub query_interface: unsafe extern "system" fn(
+        this: *mut c_void,
+        iid: *const Tuid,
+        obj: *mut *mut c_void,
+    ) -> TResult,
+    pub add_ref: unsafe extern "system" fn(this: *mut c_void) -> u32,
+    pub release: unsafe extern "system" fn(this: *mut c_void) -> u32,
+}
+
+impl Interface for FUnknownVtbl {
+    const IID: Tuid = uid(0x00000000, 0x00000000, 0xC0000000, 0x00000046);
+}
+
+// Plugin factory
+
+#[repr(C)]
+pub struct PFactoryInfo {
+    pub vendor: [c_char; 64],
+    pub url: [c_char; 256],
+    pub email: [c_char; 128],
+    pub flags: i32,
+}
+
+#[repr(C)]
+pub struct PClassInfo {
+    pub cid: Tuid,
+    pub cardinality: i32,
+    pub category: [c_char; 32],
+    pub name: [c_char; 64],
+}
+
+#[repr(C)]
+pub struct PClassInfo2 {
+    pub cid: Tuid,
+    pub cardinality: i32,
+    pub category: [c_char; 32],
+    pub name: [c_char; 64],
+    pub class_flags: u32,
+    pub sub_categories: [c_char; 128],
+    pub vendor: [c_char; 64],
+    pub version: [c_char; 64],
+    pub sdk_version: [c_char; 64],
+}
+
+#[repr(C)]
+pub struct IPluginFactoryVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_factory_info:
+        unsafe extern "system" fn(this: *mut c_void, info: *mut PFactoryInfo) -> TResult,
+    pub count_classes: unsafe extern "system" fn(this: *mut c_void) -> i32,
+    pub get_class_info:
+        unsafe extern "system" fn(this: *mut c_void, index: i32, info: *mut PClassInfo) -> TResult,
+    pub create_instance: unsafe extern "system" fn(
+        this: *mut c_void,
+        cid: *const c_char,
+        iid: *const c_char,
+        obj: *mut *mut c_void,
+    ) -> TResult,
+}
+
+impl Interface for IPluginFactoryVtbl {
+    const IID: Tuid = uid(0x7A4D811C, 0x52114A1F, 0xAED9D2EE, 0x0B43BF9F);
+}
+
+#[repr(C)]
+pub struct IPluginFactory2Vtbl {
+    pub factory: IPluginFactoryVtbl,
+    pub get_class_info2:
+        unsafe extern "system" fn(this: *mut c_void, index: i32, info: *mut PClassInfo2) -> TResult,
+}
+
+impl Interface for IPluginFactory2Vtbl {
+    const IID: Tuid = uid(0x0007B650, 0xF24B4C0B, 0xA464EDB9, 0xF00B2ABB);
+}
+
+// Component and processor
+
+#[repr(C)]
+pub struct IPluginBaseVtbl {
+    pub unknown: FUnknownVtbl,
+    pub initialize: unsafe extern "system" fn(this: *mut c_void, context: *mut c_void) -> TResult,
+    pub terminate: unsafe extern "system" fn(this: *mut c_void) -> TResult,
+}
+
+#[repr(C)]
+pub struct BusInfo {
+    pub media_type: i32,
+    pub direction: i32,
+    pub channel_count: i32,
+    pub name: String128,
+    pub bus_type: i32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+pub struct RoutingInfo {
+    pub media_type: i32,
+    pub bus_index: i32,
+    pub channel: i32,
+}
+
+#[repr(C)]
+pub struct IComponentVtbl {
+    pub base: IPluginBaseVtbl,
+    pub get_controller_class_id:
+        unsafe extern "system" fn(this: *mut c_void, class_id: *mut Tuid) -> TResult,
+    pub set_io_mode: unsafe extern "system" fn(this: *mut c_void, mode: i32) -> TResult,
+    pub get_bus_count:
+        unsafe extern "system" fn(this: *mut c_void, media_type: i32, direction: i32) -> i32,
+    pub get_bus_info: unsafe extern "system" fn(
+        this: *mut c_void,
+        media_type: i32,
+        direction: i32,
+        index: i32,
+        bus: *mut BusInfo,
+    ) -> TResult,
+    pub get_routing_info: unsafe extern "system" fn(
+        this: *mut c_void,
+        input: *mut RoutingInfo,
+        output: *mut RoutingInfo,
+    ) -> TResult,
+    pub activate_bus: unsafe extern "system" fn(
+        this: *mut c_void,
+        media_type: i32,
+        direction: i32,
+        index: i32,
+        state: TBool,
+    ) -> TResult,
+    pub set_active: unsafe extern "system" fn(this: *mut c_void, state: TBool) -> TResult,
+    pub set_state: unsafe extern "system" fn(this: *mut c_void, state: *mut c_void) -> TResult,
+    pub get_state: unsafe extern "system" fn(this: *mut c_void, state: *mut c_void) -> TResult,
+}
+
+impl Interface for IComponentVtbl {
+    const IID: Tuid = uid(0xE831FF31, 0xF2D54301, 0x928EBBEE, 0x25697802);
+}
+
+#[repr(C)]
+pub struct ProcessSetup {
+    pub process_mode: i32,
+    pub symbolic_sample_size: i32,
+    pub max_samples_per_block: i32,
+    pub sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct AudioBusBuffers {
+    pub num_channels: i32,
+    pub silence_flags: u64,
+    /// `channelBuffers32`, which shares its place with the 64-bit buffers
+    pub channel_buffers: *mut *mut f32,
+}
+
+#[repr(C)]
+pub struct Chord {
+    pub key_note: u8,
+    pub root_note: u8,
+    pub chord_mask: i16,
+}
+
+#[repr(C)]
+pub struct FrameRate {
+    pub frames_per_second: u32,
+    pub flags: u32,
+}
+
+#[repr(C)]
+pub struct ProcessContext {
+    pub state: u32,
+    pub sample_rate: f64,
+    pub project_time_samples: i64,
+    pub system_time: i64,
+    pub continuous_time_samples: i64,
+    pub project_time_music: f64,
+    pub bar_position_music: f64,
+    pub cycle_start_music: f64,
+    pub cycle_end_music: f64,
+    pub tempo: f64,
+    pub time_sig_numerator: i32,
+    pub time_sig_denominator: i32,
+    pub chord: Chord,
+    pub smpte_offset_subframes: i32,
+    pub frame_rate: FrameRate,
+    pub samples_to_next_clock: i32,
+}
+
+#[repr(C)]
+pub struct ProcessData {
+    pub process_mode: i32,
+    pub symbolic_sample_size: i32,
+    pub num_samples: i32,
+    pub num_inputs: i32,
+    pub num_outputs: i32,
+    pub inputs: *mut AudioBusBuffers,
+    pub outputs: *mut AudioBusBuffers,
+    pub input_parameter_changes: *mut c_void,
+    pub output_parameter_changes: *mut c_void,
+    pub input_events: *mut c_void,
+    pub output_events: *mut c_void,
+    pub process_context: *mut ProcessContext,
+}
+
+#[repr(C)]
+pub struct IAudioProcessorVtbl {
+    pub unknown: FUnknownVtbl,
+    pub set_bus_arrangements: unsafe extern "system" fn(
+        this: *mut c_void,
+        inputs: *mut u64,
+        num_ins: i32,
+        outputs: *mut u64,
+        num_outs: i32,
+    ) -> TResult,
+    pub get_bus_arrangement: unsafe extern "system" fn(
+        this: *mut c_void,
+        direction: i32,
+        index: i32,
+        arrangement: *mut u64,
+    ) -> TResult,
+    pub can_process_sample_size:
+        unsafe extern "system" fn(this: *mut c_void, symbolic_sample_size: i32) -> TResult,
+    pub get_latency_samples: unsafe extern "system" fn(this: *mut c_void) -> u32,
+    pub setup_processing:
+        unsafe extern "system" fn(this: *mut c_void, setup: *mut ProcessSetup) -> TResult,
+    pub set_processing: unsafe extern "system" fn(this: *mut c_void, state: TBool) -> TResult,
+    pub process: unsafe extern "system" fn(this: *mut c_void, data: *mut ProcessData) -> TResult,
+    pub get_tail_samples: unsafe extern "system" fn(this: *mut c_void) -> u32,
+}
+
+impl Interface for IAudioProcessorVtbl {
+    const IID: Tuid = uid(0x42043F99, 0xB7DA453C, 0xA569E79D, 0x9AAEC33D);
+}
+
+// Edit controller and editor
+
+#[repr(C)]
+pub struct ParameterInfo {
+    pub id: ParamId,
+    pub title: String128,
+    pub short_title: String128,
+    pub units: String128,
+    pub step_count: i32,
+    pub default_normalized_value: f64,
+    pub unit_id: i32,
+    pub flags: i32,
+}
+
+#[repr(C)]
+pub struct IEditControllerVtbl {
+    pub base: IPluginBaseVtbl,
+    pub set_component_state:
+        unsafe extern "system" fn(this: *mut c_void, state: *mut c_void) -> TResult,
+    pub set_state: unsafe extern "system" fn(this: *mut c_void, state: *mut c_void) -> TResult,
+    pub get_state: unsafe extern "system" fn(this: *mut c_void, state: *mut c_void) -> TResult,
+    pub get_parameter_count: unsafe extern "system" fn(this: *mut c_void) -> i32,
+    pub get_parameter_info: unsafe extern "system" fn(
+        this: *mut c_void,
+        index: i32,
+        info: *mut ParameterInfo,
+    ) -> TResult,
+    pub get_param_string_by_value: unsafe extern "system" fn(
+        this: *mut c_void,
+        id: ParamId,
+        value_normalized: f64,
+        string: *mut u16,
+    ) -> TResult,
+    pub get_param_value_by_string: unsafe extern "system" fn(
+        this: *mut c_void,
+        id: ParamId,
+        string: *const u16,
+        value_normalized: *mut f64,
+    ) -> TResult,
+    pub normalized_param_to_plain:
+        unsafe extern "system" fn(this: *mut c_void, id: ParamId, value_normalized: f64) -> f64,
+    pub plain_param_to_normalized:
+        unsafe extern "system" fn(this: *mut c_void, id: ParamId, plain_value: f64) -> f64,
+    pub get_param_normalized: unsafe extern "system" fn(this: *mut c_void, id: ParamId) -> f64,
+    pub set_param_normalized:
+        unsafe extern "system" fn(this: *mut c_void, id: ParamId, value: f64) -> TResult,
+    pub set_component_handler:
+        unsafe extern "system" fn(this: *mut c_void, handler: *mut c_void) -> TResult,
+    pub create_view:
+        unsafe extern "system" fn(this: *mut c_void, name: *const c_char) -> *mut c_void,
+}
+
+impl Interface for IEditControllerVtbl {
+    const IID: Tuid = uid(0xDCD7BBE3, 0x7742448D, 0xA874AACC, 0x979C759E);
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ViewRect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+#[repr(C)]
+pub struct IPlugViewVtbl {
+    pub unknown: FUnknownVtbl,
+    pub is_platform_type_supported:
+        unsafe extern "system" fn(this: *mut c_void, platform_type: *const c_char) -> TResult,
+    pub attached: unsafe extern "system" fn(
+        this: *mut c_void,
+        parent: *mut c_void,
+        platform_type: *const c_char,
+    ) -> TResult,
+    pub removed: unsafe extern "system" fn(this: *mut c_void) -> TResult,
+    pub on_wheel: unsafe extern "system" fn(this: *mut c_void, distance: f32) -> TResult,
+    pub on_key_down: unsafe extern "system" fn(
+        this: *mut c_void,
+        key: u16,
+        key_code: i16,
+        modifiers: i16,
+    ) -> TResult,
+    pub on_key_up: unsafe extern "system" fn(
+        this: *mut c_void,
+        key: u16,
+        key_code: i16,
+        modifiers: i16,
+    ) -> TResult,
+    pub get_size: unsafe extern "system" fn(this: *mut c_void, size: *mut ViewRect) -> TResult,
+    pub on_size: unsafe extern "system" fn(this: *mut c_void, new_size: *mut ViewRect) -> TResult,
+    pub on_focus: unsafe extern "system" fn(this: *mut c_void, state: TBool) -> TResult,
+    pub set_frame: unsafe extern "system" fn(this: *mut c_void, frame: *mut c_void) -> TResult,
+    pub can_resize: unsafe extern "system" fn(this: *mut c_void) -> TResult,
+    pub check_size_constraint:
+        unsafe extern "system" fn(this: *mut c_void, rect: *mut ViewRect) -> TResult,
+}
+
+impl Interface for IPlugViewVtbl {
+    const IID: Tuid = uid(0x5BC32507, 0xD06049EA, 0xA6151B52, 0x2B755B29);
+}
+
+#[repr(C)]
+pub struct IConnectionPointVtbl {
+    pub unknown: FUnknownVtbl,
+    pub connect: unsafe extern "system" fn(this: *mut c_void, other: *mut c_void) -> TResult,
+    pub disconnect: unsafe extern "system" fn(this: *mut c_void, other: *mut c_void) -> TResult,
+    pub notify: unsafe extern "system" fn(this: *mut c_void, message: *mut c_void) -> TResult,
+}
+
+impl Interface for IConnectionPointVtbl {
+    const IID: Tuid = uid(0x70A4156F, 0x6E6E4026, 0x989148BF, 0xAA60D8D1);
+}
+
+// Interfaces the host implements
+
+#[repr(C)]
+pub struct IHostApplicationVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_name: unsafe extern "system" fn(this: *mut c_void, name: *mut u16) -> TResult,
+    pub create_instance: unsafe extern "system" fn(
+        this: *mut c_void,
+        cid: *mut Tuid,
+        iid: *mut Tuid,
+        obj: *mut *mut c_void,
+    ) -> TResult,
+}
+
+impl Interface for IHostApplicationVtbl {
+    const IID: Tuid = uid(0x58E595CC, 0xDB2D4969, 0x8B6AAF8C, 0x36A664E5);
+}
+
+#[repr(C)]
+pub struct IComponentHandlerVtbl {
+    pub unknown: FUnknownVtbl,
+    pub begin_edit: unsafe extern "system" fn(this: *mut c_void, id: ParamId) -> TResult,
+    pub perform_edit:
+        unsafe extern "system" fn(this: *mut c_void, id: ParamId, value_normalized: f64) -> TResult,
+    pub end_edit: unsafe extern "system" fn(this: *mut c_void, id: ParamId) -> TResult,
+    pub restart_component: unsafe extern "system" fn(this: *mut c_void, flags: i32) -> TResult,
+}
+
+impl Interface for IComponentHandlerVtbl {
+    const IID: Tuid = uid(0x93A0BEA3, 0x0BD045DB, 0x8E890B0C, 0xC1E46AC6);
+}
+
+#[repr(C)]
+pub struct IPlugFrameVtbl {
+    pub unknown: FUnknownVtbl,
+    pub resize_view: unsafe extern "system" fn(
+        this: *mut c_void,
+        view: *mut c_void,
+        new_size: *mut ViewRect,
+    ) -> TResult,
+}
+
+impl Interface for IPlugFrameVtbl {
+    const IID: Tuid = uid(0x367FAF01, 0xAFA94693, 0x8D4DA2A0, 0xED0882A3);
+}
+
+#[repr(C)]
+pub struct IBStreamVtbl {
+    pub unknown: FUnknownVtbl,
+    pub read: unsafe extern "system" fn(
+        this: *mut c_void,
+        buffer: *mut c_void,
+        num_bytes: i32,
+        num_bytes_read: *mut i32,
+    ) -> TResult,
+    pub write: unsafe extern "system" fn(
+        this: *mut c_void,
+        buffer: *mut c_void,
+        num_bytes: i32,
+        num_bytes_written: *mut i32,
+    ) -> TResult,
+    pub seek: unsafe extern "system" fn(
+        this: *mut c_void,
+        pos: i64,
+        mode: i32,
+        result: *mut i64,
+    ) -> TResult,
+    pub tell: unsafe extern "system" fn(this: *mut c_void, pos: *mut i64) -> TResult,
+}
+
+impl Interface for IBStreamVtbl {
+    const IID: Tuid = uid(0xC3BF6EA2, 0x30994752, 0x9B6BF990, 0x1EE33E9B);
+}
+
+#[repr(C)]
+pub struct IParamValueQueueVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_parameter_id: unsafe extern "system" fn(this: *mut c_void) -> ParamId,
+    pub get_point_count: unsafe extern "system" fn(this: *mut c_void) -> i32,
+    pub get_point: unsafe extern "system" fn(
+        this: *mut c_void,
+        index: i32,
+        sample_offset: *mut i32,
+        value: *mut f64,
+    ) -> TResult,
+    pub add_point: unsafe extern "system" fn(
+        this: *mut c_void,
+        sample_offset: i32,
+        value: f64,
+        index: *mut i32,
+    ) -> TResult,
+}
+
+impl Interface for IParamValueQueueVtbl {
+    const IID: Tuid = uid(0x01263A18, 0xED074F6F, 0x98C9D356, 0x4686F9BA);
+}
+
+#[repr(C)]
+pub struct IParameterChangesVtbl {
+    pub unknown: FUnknownVtbl,
+    pub get_parameter_count: unsafe extern "system" fn(this: *mut c_void) -> i32,
+    pub get_parameter_data: unsafe extern "system" fn(this: *mut c_void, index: i32) -> *mut c_void,
+    pub add_parameter_data: unsafe extern "system" fn(
+        this: *mut c_void,
+        id: *const ParamId,
+        index: *mut i32,
+    ) -> *mut c_void,
+}
+
+impl Interface for IParameterChangesVtbl {
+    const IID: Tuid = uid(0xA4779663, 0x0BB64A4E, 0x8AD88A31, 0xD5D1FD13);
+}
+
+/// The module's entry point, exported as `GetPluginFactory`
+pub type GetPluginFactory = unsafe extern "system" fn() -> *mut c_void;
+
+/// Read a fixed-size, nul-terminated C string the plugin filled in
+pub fn c_string(chars: &[c_char]) -> String {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .map(|&c| c as u8)
+        .take_while(|&b| b != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Read a [`String128`] the plugin filled in
+pub fn string128(units: &[u16]) -> String {
+    let len = units.iter().position(|&u| u == 0).unwrap_or(units.len());
+    String::from_utf16_lossy(&units[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interface_ids_match_the_sdk_layout() {
+        let expected = [0, 0, 0, 0, 0, 0, 0, 0, 0xC0, 0, 0, 0, 0, 0, 0, 0x46];
+        assert_eq!(FUnknownVtbl::IID, expected);
+
+        #[cfg(windows)]
+        let expected = [
+            0x31, 0xFF, 0x31, 0xE8, 0xD5, 0xF2, 0x01, 0x43, 0x92, 0x8E, 0xBB, 0xEE, 0x25, 0x69,
+            0x78, 0x02,
+        ];
+        #[cfg(not(windows))]
+        let expected = [
+            0xE8, 0x31, 0xFF, 0x31, 0xF2, 0xD5, 0x43, 0x01, 0x92, 0x8E, 0xBB, 0xEE, 0x25, 0x69,
+            0x78, 0x02,
+        ];
+        assert_eq!(IComponentVtbl::IID, expected);
+    }
+
+    #[test]
+    fn test_strings_stop_at_the_nul() {
+        let mut name = [0 as c_char; 8];
+        for (c, b) in name.iter_mut().zip(b"Reverb") {
+            *c = *b as c_char;
+        }
+        assert_eq!(c_string(&name), "Reverb");
+
+        let mut title = [0u16; 128];
+        for (u, c) in title.iter_mut().zip("Mix".encode_utf16()) {
+            *u = c;
+        }
+        assert_eq!(string128(&title), "Mix");
+    }
+}
@@ -0,0 +1,723 @@
+//! The objects the host hands to plugins: the host application, the handler
+//! that hears about parameter edits, the frame around an editor, a memory stream
+//! for state, and the parameter changes passed in with each block.
+
+use std::ffi::c_void;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering, fence};
+
+use super::bindings::{
+    FUnknownVtbl, IBStreamVtbl, IComponentHandlerVtbl, IHostApplicationVtbl, IParamValueQueueVtbl,
+    IParameterChangesVtbl, IPlugFrameVtbl, Interface, NO_INTERFACE, NOT_IMPLEMENTED, ParamId,
+    RESULT_FALSE, RESULT_OK, SEEK_CUR, SEEK_END, SEEK_SET, TResult, Tuid, ViewRect,
+};
+use crate::EditorSize;
+
+/// Name the host gives plugins that ask
+const HOST_NAME: &str = "Cedar";
+
+/// A reference-counted object the host implements: its vtable, then its state
+#[repr(C)]
+struct HostObject<V: 'static, T> {
+    vtbl: &'static V,
+    refs: AtomicU32,
+    value: T,
+}
+
+/// What a host object answers to `queryInterface` with
+trait Implements {
+    const IIDS: &'static [Tuid];
+}
+
+const fn unknown_vtbl<V: 'static, T: Implements>() -> FUnknownVtbl {
+    FUnknownVtbl {
+        query_interface: query_interface::<V, T>,
+        add_ref: add_ref::<V, T>,
+        release: release::<V, T>,
+    }
+}
+
+unsafe extern "system" fn query_interface<V: 'static, T: Implements>(
+    this: *mut c_void,
+    iid: *const Tuid,
+    obj: *mut *mut c_void,
+) -> TResult {
+    // SAFETY: the plugin passes an interface id and somewhere to put the object
+    unsafe {
+        if T::IIDS.contains(&*iid) || *iid == FUnknownVtbl::IID {
+            add_ref::<V, T>(this);
+            *obj = this;
+            RESULT_OK
+        } else {
+            *obj = std::ptr::null_mut();
+            NO_INTERFACE
+        }
+    }
+}
+
+unsafe extern "system" fn add_ref<V: 'static, T>(this: *mut c_void) -> u32 {
+    // SAFETY: `this` is one of our objects, alive while the caller holds a reference
+    let object = unsafe { &*this.cast::<HostObject<V, T>>() };
+    object.refs.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe extern "system" fn release<V: 'static, T>(this: *mut c_void) -> u32 {
+    let object = this.cast::<HostObject<V, T>>();
+    // SAFETY: as in `add_ref`; the last reference frees the object
+    unsafe {
+        let refs = (*object).refs.fetch_sub(1, Ordering::Release) - 1;
+        if refs == 0 {
+            fence(Ordering::Acquire);
+            drop(Box::from_raw(object));
+        }
+        refs
+    }
+}
+
+/// The host's own reference to an object it implements
+pub(super) struct Owned<V: 'static, T> {
+    object: NonNull<HostObject<V, T>>,
+    _value: PhantomData<T>,
+}
+
+impl<V: 'static, T> Owned<V, T> {
+    fn new(vtbl: &'static V, value: T) -> Self {
+        let object = Box::new(HostObject {
+            vtbl,
+            refs: AtomicU32::new(1),
+            value,
+        });
+        Self {
+            object: NonNull::from(Box::leak(object)),
+            _value: PhantomData,
+        }
+    }
+
+    /// The object to hand to the plugin, which adds its own reference if it keeps it
+    pub(super) fn as_ptr(&self) -> *mut c_void {
+        self.object.as_ptr().cast()
+    }
+}
+
+impl<V: 'static, T> std::ops::Deref for Owned<V, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: our reference keeps the object alive
+        unsafe { &self.object.as_ref().value }
+    }
+}
+
+impl<V: 'static, T> Drop for Owned<V, T> {
+    fn drop(&mut self) {
+        // SAFETY: gives up the reference `new` started with
+        unsafe { release::<V, T>(self.as_ptr()) };
+    }
+}
+
+// SAFETY: the object is only reached through `&T` and the atomic count
+unsafe impl<V: 'static, T: Send + Sync> Send for Owned<V, T> {}
+unsafe impl<V: 'static, T: Send + Sync> Sync for Owned<V, T> {}
+
+/// # Safety
+/// `this` must be a live `HostObject<V, T>`.
+unsafe fn contents<'a, V: 'static, T>(this: *mut c_void) -> &'a T {
+    unsafe { &(*this.cast::<HostObject<V, T>>()).value }
+}
+
+// Host application
+
+/// What plugins are initialized with, to ask the host's name
+pub(super) struct HostApplication;
+
+impl Implements for HostApplication {
+    const IIDS: &'static [Tuid] = &[IHostApplicationVtbl::IID];
+}
+
+static HOST_APPLICATION_VTBL: IHostApplicationVtbl = IHostApplicationVtbl {
+    unknown: unknown_vtbl::<IHostApplicationVtbl, HostApplication>(),
+    get_name: host_get_name,
+    create_instance: host_create_instance,
+};
+
+impl HostApplication {
+    pub(super) fn new() -> Owned<IHostApplicationVtbl, Self> {
+        Owned::new(&HOST_APPLICATION_VTBL, Self)
+    }
+}
+
+unsafe extern "system" fn host_get_name(_this: *mut c_void, name: *mut u16) -> TResult {
+    // SAFETY: the plugin passes a String128
+    let name = unsafe { std::slice::from_raw_parts_mut(name, 128) };
+    name.fill(0);
+    for (unit, c) in name.iter_mut().zip(HOST_NAME.encode_utf16()) {
+        *unit = c;
+    }
+    RESULT_OK
+}
+
+unsafe extern "system" fn host_create_instance(
+    _this: *mut c_void,
+    _cid: *mut Tuid,
+    _iid: *mut Tuid,
+    obj: *mut *mut c_void,
+) -> TResult {
+    // Messages between a component and its controller go straight through their
+    // connection points, so there's nothing for plugins to create here
+    // SAFETY: the plugin passes somewhere to put the object
+    unsafe { *obj = std::ptr::null_mut() };
+    NOT_IMPLEMENTED
+}
+
+// Component handler
+
+/// A parameter set to a normalized value, from the controller to the processor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) struct ParameterChange {
+    pub(super) id: ParamId,
+    pub(super) value: f64,
+}
+
+/// Hears about parameters the user changes in the editor, and passes them on to
+/// the processor
+pub(super) struct ComponentHandler {
+    changes: Mutex<rtrb::Producer<ParameterChange>>,
+}
+
+impl Implements for ComponentHandler {
+    const IIDS: &'static [Tuid] = &[IComponentHandlerVtbl::IID];
+}
+
+static COMPONENT_HANDLER_VTBL: IComponentHandlerVtbl = IComponentHandlerVtbl {
+    unknown: unknown_vtbl::<IComponentHandlerVtbl, ComponentHandler>(),
+    begin_edit: handler_edit,
+    perform_edit: handler_perform_edit,
+    end_edit: handler_edit,
+    restart_component: handler_restart_component,
+};
+
+impl ComponentHandler {
+    pub(super) fn new(
+        changes: rtrb::Producer<ParameterChange>,
+    ) -> Owned<IComponentHandlerVtbl, Self> {
+        Owned::new(
+            &COMPONENT_HANDLER_VTBL,
+            Self {
+                changes: Mutex::new(changes),
+            },
+        )
+    }
+
+    /// Send changes to a new processor from now on
+    pub(super) fn reconnect(&self, changes: rtrb::Producer<ParameterChange>) {
+        if let Ok(mut current) = self.changes.lock() {
+            *current = changes;
+        }
+    }
+
+    /// Send a change to the processor; dropped if it's too far behind to take it
+    pub(super) fn send(&self, change: ParameterChange) {
+        if let Ok(mut changes) = self.changes.lock() {
+            let _ = changes.push(change);
+        }
+    }
+}
+
+unsafe extern "system" fn handler_edit(_this: *mut c_void, _id: ParamId) -> TResult {
+    RESULT_OK
+}
+
+unsafe extern "system" fn handler_perform_edit(
+    this: *mut c_void,
+    id: ParamId,
+    value: f64,
+) -> TResult {
+    // SAFETY: the plugin calls us on the handler it was given
+    let handler = unsafe { contents::<IComponentHandlerVtbl, ComponentHandler>(this) };
+    handler.send(ParameterChange { id, value });
+    RESULT_OK
+}
+
+unsafe extern "system" fn handler_restart_component(_this: *mut c_void, _flags: i32) -> TResult {
+    // Buses, latency and parameter titles are read when they're needed, so a
+    // change to any of them needs nothing from the host here
+    RESULT_OK
+}
+
+// Plug frame
+
+/// Where an editor asks to be resized, for the frontend to resize its window
+pub(super) struct PlugFrame {
+    requested: Mutex<Option<EditorSize>>,
+}
+
+impl Implements for PlugFrame {
+    const IIDS: &'static [Tuid] = &[IPlugFrameVtbl::IID];
+}
+
+static PLUG_FRAME_VTBL: IPlugFrameVtbl = IPlugFrameVtbl {
+    unknown: unknown_vtbl::<IPlugFrameVtbl, PlugFrame>(),
+    resize_view: frame_resize_view,
+};
+
+impl PlugFrame {
+    pub(super) fn new() -> Owned<IPlugFrameVtbl, Self> {
+        Owned::new(
+            &PLUG_FRAME_VTBL,
+            Self {
+                requested: Mutex::new(None),
+            },
+        )
+    }
+
+    /// The size the editor last asked for, if it asked since the last call
+    pub(super) fn take_requested(&self) -> Option<EditorSize> {
+        self.requested.lock().ok()?.take()
+    }
+}
+
+unsafe extern "system" fn frame_resize_view(
+    this: *mut c_void,
+    _view: *mut c_void,
+    new_size: *mut ViewRect,
+) -> TResult {
+    // SAFETY: the plugin calls us on the frame it was given, with its new size
+    let (frame, rect) = unsafe {
+        (
+            contents::<IPlugFrameVtbl, PlugFrame>(this),
+            new_size.as_ref(),
+        )
+    };
+    let Some(rect) = rect else {
+        return RESULT_FALSE;
+    };
+    if let Ok(mut requested) = frame.requested.lock() {
+        *requested = Some(rect_size(rect));
+    }
+    RESULT_OK
+}
+
+pub(super) fn rect_size(rect: &ViewRect) -> EditorSize {
+    EditorSize {
+        width: (rect.right - rect.left).max(0) as u32,
+        height: (rect.bottom - rect.top).max(0) as u32,
+    }
+}
+
+// Memory stream
+
+/// A seekable stream over bytes in memory, for getting and setting plugin state
+pub(super) struct MemoryStream {
+    data: Mutex<(Vec<u8>, usize)>,
+}
+
+impl Implements for MemoryStream {
+    const IIDS: &'static [Tuid] = &[IBStreamVtbl::IID];
+}
+
+static MEMORY_STREAM_VTBL: IBStreamVtbl = IBStreamVtbl {
+    unknown: unknown_vtbl::<IBStreamVtbl, MemoryStream>(),
+    read: stream_read,
+    write: stream_write,
+    seek: stream_seek,
+    tell: stream_tell,
+};
+
+impl MemoryStream {
+    /// A stream over `data`, positioned at its start
+    pub(super) fn new(data: Vec<u8>) -> Owned<IBStreamVtbl, Self> {
+        Owned::new(
+            &MEMORY_STREAM_VTBL,
+            Self {
+                data: Mutex::new((data, 0)),
+            },
+        )
+    }
+
+    pub(super) fn rewind(&self) {
+        if let Ok(mut data) = self.data.lock() {
+            data.1 = 0;
+        }
+    }
+}
+
+unsafe fn stream<'a>(this: *mut c_void) -> std::sync::MutexGuard<'a, (Vec<u8>, usize)> {
+    // SAFETY: the plugin calls us on the stream it was given
+    let stream = unsafe { contents::<IBStreamVtbl, MemoryStream>(this) };
+    stream
+        .data
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+unsafe extern "system" fn stream_read(
+    this: *mut c_void,
+    buffer: *mut c_void,
+    num_bytes: i32,
+    num_bytes_read: *mut i32,
+) -> TResult {
+    if buffer.is_null() || num_bytes < 0 {
+        return RESULT_FALSE;
+    }
+    let mut data = unsafe { stream(this) };
+    let (bytes, position) = &mut *data;
+    let start = (*position).min(bytes.len());
+    let read = (num_bytes as usize).min(bytes.len() - start);
+    // SAFETY: the plugin's buffer holds `num_bytes`; `read` is no more than that
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes[start..].as_ptr(), buffer.cast::<u8>(), read);
+        if let Some(num_bytes_read) = num_bytes_read.as_mut() {
+            *num_bytes_read = read as i32;
+        }
+    }
+    *position = start + read;
+    RESULT_OK
+}
+
+unsafe extern "system" fn stream_write(
+    this: *mut c_void,
+    buffer: *mut c_void,
+    num_bytes: i32,
+    num_bytes_written: *mut i32,
+) -> TResult {
+    if buffer.is_null() || num_bytes < 0 {
+        return RESULT_FALSE;
+    }
+    let mut data = unsafe { stream(this) };
+    let (bytes, position) = &mut *data;
+    // SAFETY: the plugin's buffer holds `num_bytes`
+    let written = unsafe { std::slice::from_raw_parts(buffer.cast::<u8>(), num_bytes as usize) };
+    let end = *position + written.len();
+    if bytes.len() < end {
+        bytes.resize(end, 0);
+    }
+    bytes[*position..end].copy_from_slice(written);
+    *position = end;
+    if let Some(num_bytes_written) = unsafe { num_bytes_written.as_mut() } {
+        *num_bytes_written = num_bytes;
+    }
+    RESULT_OK
+}
+
+unsafe extern "system" fn stream_seek(
+    this: *mut c_void,
+    pos: i64,
+    mode: i32,
+    result: *mut i64,
+) -> TResult {
+    let mut data = unsafe { stream(this) };
+    let (bytes, position) = &mut *data;
+    let base = match mode {
+        SEEK_SET => 0,
+        SEEK_CUR => *position as i64,
+        SEEK_END => bytes.len() as i64,
+        _ => return RESULT_FALSE,
+    };
+    let Some(target) = base.checked_add(pos).filter(|target| *target >= 0) else {
+        return RESULT_FALSE;
+    };
+    *position = target as usize;
+    if let Some(result) = unsafe { result.as_mut() } {
+        *result = target;
+    }
+    RESULT_OK
+}
+
+unsafe extern "system" fn stream_tell(this: *mut c_void, pos: *mut i64) -> TResult {
+    let data = unsafe { stream(this) };
+    match unsafe { pos.as_mut() } {
+        Some(pos) => {
+            *pos = data.1 as i64;
+            RESULT_OK
+        }
+        None => RESULT_FALSE,
+    }
+}
+
+// Parameter changes
+
+/// Most parameters that can change in one block; later changes wait for the next
+pub(super) const MAX_PARAMETER_CHANGES: usize = 64;
+
+/// One parameter's change in a block. The host sends one point per parameter, at
+/// the start of the block; plugins may add more to output queues, of which the
+/// last is kept.
+#[repr(C)]
+struct ParamValueQueue {
+    vtbl: &'static IParamValueQueueVtbl,
+    id: ParamId,
+    points: i32,
+    value: f64,
+}
+
+static PARAM_VALUE_QUEUE_VTBL: IParamValueQueueVtbl = IParamValueQueueVtbl {
+    unknown: FUnknownVtbl {
+        query_interface: queue_query_interface,
+        add_ref: unowned_add_ref,
+        release: unowned_add_ref,
+    },
+    get_parameter_id: queue_parameter_id,
+    get_point_count: queue_point_count,
+    get_point: queue_get_point,
+    add_point: queue_add_point,
+};
+
+/// The parameter changes passed in and out with a block, allocated up front so
+/// filling them on the audio thread doesn't allocate. Plugins only use them
+/// during the call, so they aren't reference counted.
+#[repr(C)]
+pub(super) struct ParameterChanges {
+    vtbl: &'static IParameterChangesVtbl,
+    queues: Vec<ParamValueQueue>,
+}
+
+static PARAMETER_CHANGES_VTBL: IParameterChangesVtbl = IParameterChangesVtbl {
+    unknown: FUnknownVtbl {
+        query_interface: changes_query_interface,
+        add_ref: unowned_add_ref,
+        release: unowned_add_ref,
+    },
+    get_parameter_count: changes_count,
+    get_parameter_data: changes_data,
+    add_parameter_data: changes_add_data,
+};
+
+impl ParameterChanges {
+    /// Boxed, since plugins hold on to the addresses of it and its queues
+    pub(super) fn new() -> Box<Self> {
+        Box::new(Self {
+            vtbl: &PARAMETER_CHANGES_VTBL,
+            queues: Vec::with_capacity(MAX_PARAMETER_CHANGES),
+        })
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.queues.clear();
+    }
+
+    pub(super) fn is_full(&self) -> bool {
+        self.queues.len() == MAX_PARAMETER_CHANGES
+    }
+
+    /// Set a parameter at the start of the block; a second change to the same
+    /// one replaces the first. Returns false if there's no room for another.
+    pub(super) fn set(&mut self, change: ParameterChange) -> bool {
+        self.queue(change.id).is_some_and(|queue| {
+            queue.points = 1;
+            queue.value = change.value;
+            true
+        })
+    }
+
+    pub(super) fn as_ptr(&mut self) -> *mut c_void {
+        (self as *mut Self).cast()
+    }
+
+    /// The queue for `id`, added if there's room
+    fn queue(&mut self, id: ParamId) -> Option<&mut ParamValueQueue> {
+        let index = match self.queues.iter().position(|queue| queue.id == id) {
+            Some(index) => index,
+            None if self.is_full() => return None,
+            None => {
+                self.queues.push(ParamValueQueue {
+                    vtbl: &PARAM_VALUE_QUEUE_VTBL,
+                    id,
+                    points: 0,
+                    value: 0.0,
+                });
+                self.queues.len() - 1
+            }
+        };
+        self.queues.get_mut(index)
+    }
+}
+
+unsafe extern "system" fn unowned_add_ref(_this: *mut c_void) -> u32 {
+    1
+}
+
+unsafe extern "system" fn changes_query_interface(
+    this: *mut c_void,
+    iid: *const Tuid,
+    obj: *mut *mut c_void,
+) -> TResult {
+    unsafe { unowned_query_interface(this, iid, obj, IParameterChangesVtbl::IID) }
+}
+
+unsafe extern "system" fn queue_query_interface(
+    this: *mut c_void,
+    iid: *const Tuid,
+    obj: *mut *mut c_void,
+) -> TResult {
+    unsafe { unowned_query_interface(this, iid, obj, IParamValueQueueVtbl::IID) }
+}
+
+unsafe fn unowned_query_interface(
+    this: *mut c_void,
+    iid: *const Tuid,
+    obj: *mut *mut c_void,
+    implements: Tuid,
+) -> TResult {
+    // SAFETY: the plugin passes an interface id and somewhere to put the object
+    unsafe {
+        let found = *iid == implements || *iid == FUnknownVtbl::IID;
+        *obj = if found { this } else { std::ptr::null_mut() };
+        if found { RESULT_OK } else { NO_INTERFACE }
+    }
+}
+
+unsafe fn changes<'a>(this: *mut c_void) -> &'a mut ParameterChanges {
+    // SAFETY: the plugin calls us on the changes it was passed, during the call
+    unsafe { &mut *this.cast::<ParameterChanges>() }
+}
+
+unsafe fn queue<'a>(this: *mut c_void) -> &'a mut ParamValueQueue {
+    // SAFETY: as for `changes`, on one of its queues
+    unsafe { &mut *this.cast::<ParamValueQueue>() }
+}
+
+unsafe extern "system" fn changes_count(this: *mut c_void) -> i32 {
+    unsafe { changes(this) }.queues.len() as i32
+}
+
+unsafe extern "system" fn changes_data(this: *mut c_void, index: i32) -> *mut c_void {
+    let changes = unsafe { changes(this) };
+    match usize::try_from(index)
+        .ok()
+        .and_then(|index| changes.queues.get_mut(index))
+    {
+        Some(queue) => (queue as *mut ParamValueQueue).cast(),
+        None => std::ptr::null_mut(),
+    }
+}
+
+unsafe extern "system" fn changes_add_data(
+    this: *mut c_void,
+    id: *const ParamId,
+    index: *mut i32,
+) -> *mut c_void {
+    let changes = unsafe { changes(this) };
+    let Some(&id) = (unsafe { id.as_ref() }) else {
+        return std::ptr::null_mut();
+    };
+    let Some(queue) = changes.queue(id) else {
+        return std::ptr::null_mut();
+    };
+    let queue = (queue as *mut ParamValueQueue).cast();
+    if let Some(index) = unsafe { index.as_mut() } {
+        *index = changes.queues.iter().position(|q| q.id == id).unwrap_or(0) as i32;
+    }
+    queue
+}
+
+unsafe extern "system" fn queue_parameter_id(this: *mut c_void) -> ParamId {
+    unsafe { queue(this) }.id
+}
+
+unsafe extern "system" fn queue_point_count(this: *mut c_void) -> i32 {
+    unsafe { queue(this) }.points
+}
+
+unsafe extern "system" fn queue_get_point(
+    this: *mut c_void,
+    index: i32,
+    sample_offset: *mut i32,
+    value: *mut f64,
+) -> TResult {
+    let queue = unsafe { queue(this) };
+    if index < 0 || index >= queue.points {
+        return RESULT_FALSE;
+    }
+    // SAFETY: the plugin passes somewhere to put the point
+    unsafe {
+        if let Some(sample_offset) = sample_offset.as_mut() {
+            *sample_offset = 0;
+        }
+        if let Some(value) = value.as_mut() {
+            *value = queue.value;
+        }
+    }
+    RESULT_OK
+}
+
+unsafe extern "system" fn queue_add_point(
+    this: *mut c_void,
+    _sample_offset: i32,
+    value: f64,
+    index: *mut i32,
+) -> TResult {
+    let queue = unsafe { queue(this) };
+    queue.points = 1;
+    queue.value = value;
+    if let Some(index) = unsafe { index.as_mut() } {
+        *index = 0;
+    }
+    RESULT_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vst3::bindings::{ComPtr, IBStreamVtbl};
+
+    #[test]
+    fn test_memory_stream_reads_back_what_was_written() {
+        let stream = MemoryStream::new(Vec::new());
+        // The plugin's own reference, as it would take one
+        let plugin: ComPtr<IBStreamVtbl> =
+            crate::vst3::bindings::query_interface(stream.as_ptr()).unwrap();
+        let vtbl = plugin.vtbl();
+        unsafe {
+            let mut written = 0;
+            let mut bytes = *b"state";
+            (vtbl.write)(plugin.as_ptr(), bytes.as_mut_ptr().cast(), 5, &mut written);
+            assert_eq!(written, 5);
+
+            let mut position = 0;
+            (vtbl.seek)(plugin.as_ptr(), -3, SEEK_END, &mut position);
+            assert_eq!(position, 2);
+            let mut read = [0u8; 8];
+            let mut count = 0;
+            (vtbl.read)(plugin.as_ptr(), read.as_mut_ptr().cast(), 8, &mut count);
+            assert_eq!(&read[..count as usize], b"ate");
+
+            stream.rewind();
+            (vtbl.read)(plugin.as_ptr(), read.as_mut_ptr().cast(), 8, &mut count);
+            assert_eq!(&read[..count as usize], b"state");
+        }
+    }
+
+    #[test]
+    fn test_parameter_changes_keep_the_last_value_of_each_parameter() {
+        let mut changes = ParameterChanges::new();
+        assert!(changes.set(ParameterChange { id: 7, value: 0.25 }));
+        assert!(changes.set(ParameterChange { id: 7, value: 0.5 }));
+        assert!(changes.set(ParameterChange { id: 9, value: 1.0 }));
+        let this = changes.as_ptr();
+        unsafe {
+            assert_eq!(changes_count(this), 2);
+            let queue = changes_data(this, 0);
+            assert_eq!(queue_parameter_id(queue), 7);
+            let (mut offset, mut value) = (-1, 0.0);
+            assert_eq!(
+                queue_get_point(queue, 0, &mut offset, &mut value),
+                RESULT_OK
+            );
+            assert_eq!((offset, value), (0, 0.5));
+            assert!(changes_data(this, 2).is_null());
+        }
+
+        for id in 10..(10 + MAX_PARAMETER_CHANGES as u32) {
+            changes.set(ParameterChange { id, value: 0.0 });
+        }
+        assert!(changes.is_full());
+        assert!(!changes.set(ParameterChange {
+            id: 1000,
+            value: 0.0
+        }));
+        changes.clear();
+        assert_eq!(unsafe { changes_count(changes.as_ptr()) }, 0);
+    }
+}
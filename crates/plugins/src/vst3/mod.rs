@@ -0,0 +1,647 @@
+//! VST3 hosting.
+//!
+//! [`scan`] finds the plugins installed in a set of folders. [`Vst3Plugin::load`]
+//! makes an instance of one, ready to process at a sample rate; its
+//! [`Vst3Processor`] goes to the audio thread, while the plugin itself stays on
+//! the thread the frontend runs on, where its editor is opened.
+//!
+//! Only the plugin's main audio input and output are connected, as stereo where
+//! the plugin accepts it. Transport position and tempo aren't passed to plugins
+//! yet, and on Linux editors that need the host's run loop don't open.
+
+use std::ffi::{CStr, c_void};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use raw_window_handle::RawWindowHandle;
+
+mod bindings;
+mod host;
+mod module;
+
+use bindings::{
+    AudioBusBuffers, BUS_INPUT, BUS_OUTPUT, ComPtr, EDITOR_VIEW, IAudioProcessorVtbl,
+    IComponentVtbl, IConnectionPointVtbl, IEditControllerVtbl, IPlugViewVtbl, MEDIA_AUDIO,
+    PROCESS_REALTIME, ProcessContext, ProcessData, ProcessSetup, RESULT_OK, SAMPLE_32,
+    SPEAKER_STEREO, TResult, ViewRect,
+};
+use host::{
+    ComponentHandler, HostApplication, MemoryStream, Owned, ParameterChange, ParameterChanges,
+    PlugFrame, rect_size,
+};
+use module::Module;
+pub use module::{default_search_paths, find_bundles};
+
+use crate::{EditorParent, EditorSize, PluginError};
+
+/// Parameter edits from the editor that can wait for the next block
+const PARAMETER_QUEUE_SIZE: usize = 256;
+
+/// A plugin found by [`scan`]: one audio class in a `.vst3` bundle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vst3Info {
+    pub name: String,
+    pub vendor: String,
+    pub version: String,
+    /// Subcategories separated by `|`, like `Fx|Reverb` or `Instrument|Synth`
+    pub category: String,
+    /// The bundle it's in
+    pub path: PathBuf,
+    /// Class id within the bundle
+    pub id: [u8; 16],
+}
+
+impl Vst3Info {
+    /// Whether it makes sound of its own rather than processing its input
+    pub fn is_instrument(&self) -> bool {
+        self.category
+            .split('|')
+            .any(|category| category == "Instrument")
+    }
+}
+
+/// The plugins in every bundle in `dirs` and the folders below them (see
+/// [`default_search_paths`]), and why each bundle that couldn't be loaded failed.
+///
+/// Scanning loads each bundle's library in this process, so a plugin that
+/// crashes while loading takes the host down with it.
+pub fn scan(dirs: &[PathBuf]) -> (Vec<Vst3Info>, Vec<PluginError>) {
+    let mut plugins = Vec::new();
+    let mut errors = Vec::new();
+    for bundle in find_bundles(dirs) {
+        match Module::open(&bundle) {
+            Ok(module) => plugins.extend(module.plugins()),
+            Err(err) => errors.push(err),
+        }
+    }
+    (plugins, errors)
+}
+
+/// A loaded plugin's component, processor and controller, shared between the
+/// [`Vst3Plugin`], its processor and its editor. Dropping the last of them shuts
+/// the plugin down and lets its library unload.
+struct Instance {
+    component: ComPtr<IComponentVtbl>,
+    processor: ComPtr<IAudioProcessorVtbl>,
+    controller: ComPtr<IEditControllerVtbl>,
+    /// Whether the controller is an object of its own, initialized separately
+    separate_controller: bool,
+    /// The component's and the controller's connection points, connected to each other
+    connection: Option<(ComPtr<IConnectionPointVtbl>, ComPtr<IConnectionPointVtbl>)>,
+    handler: Owned<bindings::IComponentHandlerVtbl, ComponentHandler>,
+    _host: Owned<bindings::IHostApplicationVtbl, HostApplication>,
+    /// Last, so the library unloads after everything above is released
+    _module: Arc<Module>,
+}
+
+// SAFETY: the processor is only called from the one audio thread at a time, and
+// the controller only from the thread that owns the `Vst3Plugin`
+unsafe impl Send for Instance {}
+unsafe impl Sync for Instance {}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // SAFETY: shutting down in the reverse order of `Vst3Plugin::load`
+        unsafe {
+            (self.processor.vtbl().set_processing)(self.processor.as_ptr(), 0);
+            (self.component.vtbl().set_active)(self.component.as_ptr(), 0);
+            if let Some((component, controller)) = &self.connection {
+                (component.vtbl().disconnect)(component.as_ptr(), controller.as_ptr());
+                (controller.vtbl().disconnect)(controller.as_ptr(), component.as_ptr());
+            }
+            (self.controller.vtbl().set_component_handler)(
+                self.controller.as_ptr(),
+                std::ptr::null_mut(),
+            );
+            if self.separate_controller {
+                (self.controller.vtbl().base.terminate)(self.controller.as_ptr());
+            }
+            (self.component.vtbl().base.terminate)(self.component.as_ptr());
+        }
+    }
+}
+
+/// Turn a plugin's result code into an error for `call`
+fn check(call: &'static str, result: TResult) -> Result<(), PluginError> {
+    if result == RESULT_OK {
+        Ok(())
+    } else {
+        Err(PluginError::Call { call, result })
+    }
+}
+
+/// A running VST3 plugin, on the frontend's side: where its editor is opened and
+/// its parameters are set from.
+///
+/// Its audio is processed by the [`Vst3Processor`] made with
+/// [`processor`](Self::processor), on the audio thread.
+pub struct Vst3Plugin {
+    info: Vst3Info,
+    instance: Arc<Instance>,
+    sample_rate: u32,
+    max_block_frames: usize,
+    /// Channels of the main input and output buses, as arranged
+    channels: (usize, usize),
+}
+
+impl Vst3Plugin {
+    /// Make an instance of the plugin `info` describes and start it processing at
+    /// `sample_rate`, in blocks of at most `max_block_frames`
+    pub fn load(
+        info: &Vst3Info,
+        sample_rate: u32,
+        max_block_frames: usize,
+    ) -> Result<Self, PluginError> {
+        let module = Arc::new(Module::open(&info.path)?);
+        let not_found = || PluginError::NotFound {
+            path: info.path.clone(),
+            id: info.name.clone(),
+        };
+        let component = module
+            .create::<IComponentVtbl>(&info.id)
+            .ok_or_else(not_found)?;
+        let host = HostApplication::new();
+        // SAFETY: each call follows the order the VST3 documentation lays out for
+        // setting up a plugin
+        unsafe {
+            check(
+                "initialize",
+                (component.vtbl().base.initialize)(component.as_ptr(), host.as_ptr()),
+            )?;
+            let Some(processor) = component.cast::<IAudioProcessorVtbl>() else {
+                (component.vtbl().base.terminate)(component.as_ptr());
+                return Err(not_found());
+            };
+
+            // The controller is either the component itself or a class of its own
+            let (controller, separate_controller) = match component.cast::<IEditControllerVtbl>() {
+                Some(controller) => (controller, false),
+                None => {
+                    let mut class_id = [0; 16];
+                    (component.vtbl().get_controller_class_id)(component.as_ptr(), &mut class_id);
+                    let Some(controller) = module.create::<IEditControllerVtbl>(&class_id) else {
+                        (component.vtbl().base.terminate)(component.as_ptr());
+                        return Err(not_found());
+                    };
+                    (controller.vtbl().base.initialize)(controller.as_ptr(), host.as_ptr());
+                    (controller, true)
+                }
+            };
+
+            let (producer, _) = rtrb::RingBuffer::new(PARAMETER_QUEUE_SIZE);
+            let handler = ComponentHandler::new(producer);
+            let connection = separate_controller
+                .then(|| {
+                    let component = component.cast::<IConnectionPointVtbl>()?;
+                    let controller = controller.cast::<IConnectionPointVtbl>()?;
+                    (component.vtbl().connect)(component.as_ptr(), controller.as_ptr());
+                    (controller.vtbl().connect)(controller.as_ptr(), component.as_ptr());
+                    Some((component, controller))
+                })
+                .flatten();
+
+            // Dropped from here on by the instance, which shuts the plugin down
+            let instance = Arc::new(Instance {
+                component,
+                processor,
+                controller,
+                separate_controller,
+                connection,
+                handler,
+                _host: host,
+                _module: module,
+            });
+
+            if separate_controller {
+                // The controller starts out showing the component's state
+                let state = MemoryStream::new(Vec::new());
+                let component = &instance.component;
+                if (component.vtbl().get_state)(component.as_ptr(), state.as_ptr()) == RESULT_OK {
+                    state.rewind();
+                    (instance.controller.vtbl().set_component_state)(
+                        instance.controller.as_ptr(),
+                        state.as_ptr(),
+                    );
+                }
+            }
+            (instance.controller.vtbl().set_component_handler)(
+                instance.controller.as_ptr(),
+                instance.handler.as_ptr(),
+            );
+
+            let channels = arrange_buses(&instance)?;
+            let mut setup = ProcessSetup {
+                process_mode: PROCESS_REALTIME,
+                symbolic_sample_size: SAMPLE_32,
+                max_samples_per_block: max_block_frames as i32,
+                sample_rate: sample_rate as f64,
+            };
+            let processor = &instance.processor;
+            check(
+                "set up processing",
+                (processor.vtbl().setup_processing)(processor.as_ptr(), &mut setup),
+            )?;
+            check(
+                "activate",
+                (instance.component.vtbl().set_active)(instance.component.as_ptr(), 1),
+            )?;
+            (processor.vtbl().set_processing)(processor.as_ptr(), 1);
+
+            Ok(Self {
+                info: info.clone(),
+                instance,
+                sample_rate,
+                max_block_frames,
+                channels,
+            })
+        }
+    }
+
+    pub fn info(&self) -> &Vst3Info {
+        &self.info
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Make the processor that runs the plugin on the audio thread.
+    ///
+    /// Edits made in the editor go to the newest processor, so only one should be
+    /// running at a time: make a new one when the old one was lost, such as with
+    /// the audio stream it ran on.
+    pub fn processor(&self) -> Vst3Processor {
+        let (producer, changes) = rtrb::RingBuffer::new(PARAMETER_QUEUE_SIZE);
+        self.instance.handler.reconnect(producer);
+
+        let (inputs, outputs) = self.channels;
+        let mut inputs = vec![vec![0.0; self.max_block_frames]; inputs];
+        let mut outputs = vec![vec![0.0; self.max_block_frames]; outputs];
+        Vst3Processor {
+            instance: self.instance.clone(),
+            changes,
+            input_changes: ParameterChanges::new(),
+            output_changes: ParameterChanges::new(),
+            input_pointers: inputs
+                .iter_mut()
+                .map(|channel| channel.as_mut_ptr())
+                .collect(),
+            output_pointers: outputs
+                .iter_mut()
+                .map(|channel| channel.as_mut_ptr())
+                .collect(),
+            inputs,
+            outputs,
+            max_block_frames: self.max_block_frames,
+            sample_rate: self.sample_rate,
+            position: 0,
+        }
+    }
+
+    /// Set a parameter to a normalized value between 0.0 and 1.0, in the
+    /// controller and the processor alike
+    pub fn set_parameter(&self, id: u32, value: f64) {
+        let value = value.clamp(0.0, 1.0);
+        let controller = &self.instance.controller;
+        // SAFETY: the controller is called from the thread that owns the plugin
+        unsafe { (controller.vtbl().set_param_normalized)(controller.as_ptr(), id, value) };
+        self.instance.handler.send(ParameterChange { id, value });
+    }
+
+    /// Open the plugin's editor inside `parent`. The window should then be sized
+    /// to the editor's [`size`](Vst3Editor::size).
+    pub fn open_editor(&self, parent: EditorParent) -> Result<Vst3Editor, PluginError> {
+        let (window, platform_type) = platform_view(parent.raw())?;
+        let controller = &self.instance.controller;
+        // SAFETY: the controller and view are called from the thread that owns the
+        // plugin, with a window handle the frontend keeps open
+        unsafe {
+            let view = (controller.vtbl().create_view)(controller.as_ptr(), EDITOR_VIEW.as_ptr());
+            let view = ComPtr::<IPlugViewVtbl>::from_raw(view).ok_or(PluginError::NoEditor)?;
+            if (view.vtbl().is_platform_type_supported)(view.as_ptr(), platform_type.as_ptr())
+                != RESULT_OK
+            {
+                return Err(PluginError::UnsupportedWindow);
+            }
+            let frame = PlugFrame::new();
+            (view.vtbl().set_frame)(view.as_ptr(), frame.as_ptr());
+            check(
+                "open its editor",
+                (view.vtbl().attached)(view.as_ptr(), window, platform_type.as_ptr()),
+            )?;
+            Ok(Vst3Editor {
+                view,
+                frame,
+                _instance: self.instance.clone(),
+            })
+        }
+    }
+}
+
+/// Activate the main audio buses and ask for them in stereo. Returns how many
+/// channels the main input and output have as the plugin arranged them.
+///
+/// # Safety
+/// Call before the plugin is set up for processing.
+unsafe fn arrange_buses(instance: &Instance) -> Result<(usize, usize), PluginError> {
+    let component = &instance.component;
+    let processor = &instance.processor;
+    let bus_count = |direction| unsafe {
+        (component.vtbl().get_bus_count)(component.as_ptr(), MEDIA_AUDIO, direction).max(0)
+    };
+    let (input_buses, output_buses) = (bus_count(BUS_INPUT), bus_count(BUS_OUTPUT));
+
+    // SAFETY: only buses the plugin reported are touched
+    unsafe {
+        for (direction, buses) in [(BUS_INPUT, input_buses), (BUS_OUTPUT, output_buses)] {
+            for index in 0..buses {
+                // Side-chains and extra outputs stay off
+                let active = u8::from(index == 0);
+                (component.vtbl().activate_bus)(
+                    component.as_ptr(),
+                    MEDIA_AUDIO,
+                    direction,
+                    index,
+                    active,
+                );
+            }
+        }
+
+        let arrangement = |direction, buses: i32| -> Vec<u64> {
+            (0..buses)
+                .map(|index| {
+                    let mut arrangement = SPEAKER_STEREO;
+                    if index > 0 {
+                        (processor.vtbl().get_bus_arrangement)(
+                            processor.as_ptr(),
+                            direction,
+                            index,
+                            &mut arrangement,
+                        );
+                    }
+                    arrangement
+                })
+                .collect()
+        };
+        let mut inputs = arrangement(BUS_INPUT, input_buses);
+        let mut outputs = arrangement(BUS_OUTPUT, output_buses);
+        // A plugin that won't take stereo keeps its own arrangement, read back below
+        (processor.vtbl().set_bus_arrangements)(
+            processor.as_ptr(),
+            inputs.as_mut_ptr(),
+            input_buses,
+            outputs.as_mut_ptr(),
+            output_buses,
+        );
+
+        let channels = |direction, buses| {
+            if buses == 0 {
+                return 0;
+            }
+            let mut arrangement = 0u64;
+            (processor.vtbl().get_bus_arrangement)(
+                processor.as_ptr(),
+                direction,
+                0,
+                &mut arrangement,
+            );
+            arrangement.count_ones() as usize
+        };
+        let channels = (
+            channels(BUS_INPUT, input_buses),
+            channels(BUS_OUTPUT, output_buses),
+        );
+        if channels.1 == 0 {
+            return Err(PluginError::Call {
+                call: "arrange an audio output",
+                result: bindings::RESULT_FALSE,
+            });
+        }
+        Ok(channels)
+    }
+}
+
+/// The native view and VST3 platform type for a frontend's window
+fn platform_view(parent: RawWindowHandle) -> Result<(*mut c_void, &'static CStr), PluginError> {
+    match parent {
+        RawWindowHandle::Win32(handle) => Ok((handle.hwnd.get() as *mut c_void, c"HWND")),
+        RawWindowHandle::AppKit(handle) => Ok((handle.ns_view.as_ptr(), c"NSView")),
+        RawWindowHandle::Xlib(handle) => Ok((handle.window as *mut c_void, c"X11EmbedWindowID")),
+        RawWindowHandle::Xcb(handle) => Ok((
+            handle.window.get() as usize as *mut c_void,
+            c"X11EmbedWindowID",
+        )),
+        _ => Err(PluginError::UnsupportedWindow),
+    }
+}
+
+/// Runs a [`Vst3Plugin`] on interleaved blocks, on the audio thread.
+///
+/// Blocks longer than the plugin was set up for are processed in parts. Mono
+/// blocks are fed to both sides of a stereo plugin and take its left output;
+/// channels past the plugin's output pass through untouched.
+pub struct Vst3Processor {
+    instance: Arc<Instance>,
+    changes: rtrb::Consumer<ParameterChange>,
+    input_changes: Box<ParameterChanges>,
+    output_changes: Box<ParameterChanges>,
+    /// Planar buffers of `max_block_frames` for each channel of the main buses
+    inputs: Vec<Vec<f32>>,
+    outputs: Vec<Vec<f32>>,
+    /// Where each of those buffers starts, as passed to the plugin
+    input_pointers: Vec<*mut f32>,
+    output_pointers: Vec<*mut f32>,
+    max_block_frames: usize,
+    sample_rate: u32,
+    /// Frames processed so far
+    position: i64,
+}
+
+// SAFETY: the buffer pointers point into buffers the processor owns, and the
+// plugin's processor is only called from the thread holding this
+unsafe impl Send for Vst3Processor {}
+
+impl Vst3Processor {
+    /// Process up to `max_block_frames` frames
+    fn process_part(&mut self, part: &mut [f32], channels: usize) {
+        let frames = part.len() / channels;
+
+        self.input_changes.clear();
+        while !self.input_changes.is_full() {
+            let Ok(change) = self.changes.pop() else {
+                break;
+            };
+            self.input_changes.set(change);
+        }
+        self.output_changes.clear();
+
+        for (ch, input) in self.inputs.iter_mut().enumerate() {
+            let source = if ch < channels { ch } else { 0 };
+            for (sample, frame) in input.iter_mut().zip(part.chunks_exact(channels)) {
+                *sample = frame[source];
+            }
+        }
+
+        let mut input_bus = AudioBusBuffers {
+            num_channels: self.inputs.len() as i32,
+            silence_flags: 0,
+            channel_buffers: self.input_pointers.as_mut_ptr(),
+        };
+        let mut output_bus = AudioBusBuffers {
+            num_channels: self.outputs.len() as i32,
+            silence_flags: 0,
+            channel_buffers: self.output_pointers.as_mut_ptr(),
+        };
+        // SAFETY: all zeroes is a valid context: no optional field is marked valid
+        let mut context: ProcessContext = unsafe { std::mem::zeroed() };
+        context.sample_rate = self.sample_rate as f64;
+        context.project_time_samples = self.position;
+        context.continuous_time_samples = self.position;
+        let mut data = ProcessData {
+            process_mode: PROCESS_REALTIME,
+            symbolic_sample_size: SAMPLE_32,
+            num_samples: frames as i32,
+            num_inputs: i32::from(!self.inputs.is_empty()),
+            num_outputs: 1,
+            inputs: &mut input_bus,
+            outputs: &mut output_bus,
+            input_parameter_changes: self.input_changes.as_ptr(),
+            output_parameter_changes: self.output_changes.as_ptr(),
+            input_events: std::ptr::null_mut(),
+            output_events: std::ptr::null_mut(),
+            process_context: &mut context,
+        };
+        let processor = &self.instance.processor;
+        // SAFETY: every buffer holds `max_block_frames`, no fewer than `frames`
+        let result = unsafe { (processor.vtbl().process)(processor.as_ptr(), &mut data) };
+        self.position += frames as i64;
+        if result != RESULT_OK {
+            // Leave the block as it came in rather than pass on whatever's in the buffers
+            return;
+        }
+
+        for (f, frame) in part.chunks_exact_mut(channels).enumerate() {
+            for (sample, output) in frame.iter_mut().zip(&self.outputs) {
+                *sample = output[f];
+            }
+        }
+    }
+}
+
+impl daw_dsp::Processor for Vst3Processor {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        if channels == 0 {
+            return;
+        }
+        for part in block.chunks_mut(self.max_block_frames * channels) {
+            self.process_part(part, channels);
+        }
+    }
+}
+
+/// A plugin's editor, embedded in the frontend's window until it's dropped.
+///
+/// Use and drop it on the thread it was opened on, the frontend's main thread:
+/// most plugins' views can only be touched from there.
+pub struct Vst3Editor {
+    view: ComPtr<IPlugViewVtbl>,
+    frame: Owned<bindings::IPlugFrameVtbl, PlugFrame>,
+    _instance: Arc<Instance>,
+}
+
+// SAFETY: the editor may be kept in state owned by another thread, such as a
+// session behind a mutex, as long as the view is only called from the main thread
+unsafe impl Send for Vst3Editor {}
+
+impl Vst3Editor {
+    /// The size the editor wants its window to be
+    pub fn size(&self) -> EditorSize {
+        let mut rect = ViewRect::default();
+        // SAFETY: the view fills in the rect
+        unsafe { (self.view.vtbl().get_size)(self.view.as_ptr(), &mut rect) };
+        rect_size(&rect)
+    }
+
+    /// Whether the user may resize the window
+    pub fn can_resize(&self) -> bool {
+        // SAFETY: a query without arguments
+        unsafe { (self.view.vtbl().can_resize)(self.view.as_ptr()) == RESULT_OK }
+    }
+
+    /// The size the editor asked its window to change to since the last call, if
+    /// it did. Once the window is resized, pass the size on with [`set_size`](Self::set_size).
+    pub fn requested_size(&self) -> Option<EditorSize> {
+        self.frame.take_requested()
+    }
+
+    /// Tell the editor its window changed size
+    pub fn set_size(&self, size: EditorSize) {
+        let mut rect = ViewRect {
+            left: 0,
+            top: 0,
+            right: size.width as i32,
+            bottom: size.height as i32,
+        };
+        // SAFETY: the view reads the rect
+        unsafe { (self.view.vtbl().on_size)(self.view.as_ptr(), &mut rect) };
+    }
+}
+
+impl Drop for Vst3Editor {
+    fn drop(&mut self) {
+        // SAFETY: detaching the view it was attached with
+        unsafe {
+            (self.view.vtbl().removed)(self.view.as_ptr());
+            (self.view.vtbl().set_frame)(self.view.as_ptr(), std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raw_window_handle::{WaylandWindowHandle, XlibWindowHandle};
+    use std::ptr::NonNull;
+
+    #[test]
+    fn test_editors_embed_in_windows_with_a_native_view() {
+        let x11 = RawWindowHandle::Xlib(XlibWindowHandle::new(42));
+        let (window, platform_type) = platform_view(x11).unwrap();
+        assert_eq!((window as usize, platform_type), (42, c"X11EmbedWindowID"));
+
+        let mut surface = 0u8;
+        let wayland =
+            RawWindowHandle::Wayland(WaylandWindowHandle::new(NonNull::from(&mut surface).cast()));
+        assert!(matches!(
+            platform_view(wayland),
+            Err(PluginError::UnsupportedWindow)
+        ));
+    }
+
+    #[test]
+    fn test_scanning_reports_bundles_that_fail_to_load() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("Broken.vst3"), b"not a library").unwrap();
+
+        let (plugins, errors) = scan(&[dir.path().to_path_buf()]);
+        assert!(plugins.is_empty());
+        assert!(
+            matches!(&errors[..], [PluginError::Load { path, .. }] if path.ends_with("Broken.vst3"))
+        );
+    }
+
+    #[test]
+    fn test_instruments_are_told_apart_by_category() {
+        let mut info = Vst3Info {
+            name: "Synth".to_string(),
+            vendor: String::new(),
+            version: String::new(),
+            category: "Instrument|Synth".to_string(),
+            path: PathBuf::new(),
+            id: [0; 16],
+        };
+        assert!(info.is_instrument());
+        info.category = "Fx|Reverb".to_string();
+        assert!(!info.is_instrument());
+    }
+}
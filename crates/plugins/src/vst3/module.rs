@@ -0,0 +1,358 @@
+//! Finding `.vst3` bundles and loading the library inside one.
+
+use std::ffi::c_void;
+use std::mem::ManuallyDrop;
+use std::path::{Path, PathBuf};
+
+use libloading::Library;
+
+use super::Vst3Info;
+use super::bindings::{
+    AUDIO_EFFECT_CLASS, ComPtr, GetPluginFactory, IPluginFactory2Vtbl, IPluginFactoryVtbl,
+    PClassInfo, PClassInfo2, PFactoryInfo, RESULT_OK, c_string,
+};
+use crate::PluginError;
+
+/// Folders VST3 plugins are installed to on this platform, for [`scan`](super::scan)
+pub fn default_search_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if cfg!(target_os = "windows") {
+        if let Some(common) = std::env::var_os("COMMONPROGRAMFILES") {
+            paths.push(PathBuf::from(common).join("VST3"));
+        }
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            paths.push(PathBuf::from(local).join("Programs/Common/VST3"));
+        }
+    } else if cfg!(target_os = "macos") {
+        if let Some(home) = home {
+            paths.push(home.join("Library/Audio/Plug-Ins/VST3"));
+        }
+        paths.push(PathBuf::from("/Library/Audio/Plug-Ins/VST3"));
+    } else {
+        if let Some(home) = home {
+            paths.push(home.join(".vst3"));
+        }
+        paths.push(PathBuf::from("/usr/lib/vst3"));
+        paths.push(PathBuf::from("/usr/local/lib/vst3"));
+    }
+    paths
+}
+
+/// Every `.vst3` bundle in `dirs` and the folders below them, in path order.
+/// Folders that don't exist are skipped.
+pub fn find_bundles(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut bundles = Vec::new();
+    let mut pending: Vec<PathBuf> = dirs.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("vst3"))
+            {
+                // A bundle is a folder too, but what's inside is the plugin's own
+                bundles.push(path);
+            } else if path.is_dir() {
+                pending.push(path);
+            }
+        }
+    }
+    bundles.sort();
+    bundles.dedup();
+    bundles
+}
+
+/// Folder inside a bundle's `Contents` with the binary for this platform
+fn architecture_folder() -> &'static str {
+    match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("windows", "aarch64") => "arm64-win",
+        ("windows", _) => "x86_64-win",
+        ("macos", _) => "MacOS",
+        (_, "aarch64") => "aarch64-linux",
+        _ => "x86_64-linux",
+    }
+}
+
+/// The library to load for a bundle. Single-file plugins, an older Windows
+/// layout, are the library themselves.
+pub(super) fn bundle_binary(bundle: &Path) -> PathBuf {
+    if bundle.is_file() {
+        return bundle.to_path_buf();
+    }
+    let stem = bundle.file_stem().unwrap_or_default();
+    let folder = bundle.join("Contents").join(architecture_folder());
+    match std::env::consts::OS {
+        "windows" => folder.join(stem).with_extension("vst3"),
+        "macos" => folder.join(stem),
+        _ => folder.join(stem).with_extension("so"),
+    }
+}
+
+/// A loaded plugin library and its factory. The library is unloaded once the
+/// factory and every plugin made by it are gone.
+pub(super) struct Module {
+    pub(super) path: PathBuf,
+    factory: ManuallyDrop<ComPtr<IPluginFactoryVtbl>>,
+    library: Library,
+}
+
+// SAFETY: factories are meant to be called from any thread
+unsafe impl Send for Module {}
+unsafe impl Sync for Module {}
+
+impl Module {
+    pub(super) fn open(bundle: &Path) -> Result<Self, PluginError> {
+        let load_error = |reason: String| PluginError::Load {
+            path: bundle.to_path_buf(),
+            reason,
+        };
+        let binary = bundle_binary(bundle);
+        // SAFETY: loading runs the plugin's initializers, which we trust as much as the plugin
+        let library =
+            unsafe { Library::new(&binary) }.map_err(|err| load_error(err.to_string()))?;
+        // SAFETY: the entry points have these signatures in every VST3 module
+        unsafe {
+            enter(&library, bundle).map_err(load_error)?;
+            let get_factory = library
+                .get::<GetPluginFactory>(b"GetPluginFactory\0")
+                .map_err(|err| load_error(err.to_string()))?;
+            let factory = ComPtr::from_raw(get_factory())
+                .ok_or_else(|| load_error("it has no plugin factory".to_string()))?;
+            Ok(Self {
+                path: bundle.to_path_buf(),
+                factory: ManuallyDrop::new(factory),
+                library,
+            })
+        }
+    }
+
+    /// The audio processors the module offers
+    pub(super) fn plugins(&self) -> Vec<Vst3Info> {
+        let factory = self.factory.vtbl();
+        let factory2 = self.factory.cast::<IPluginFactory2Vtbl>();
+        // SAFETY: the factory fills in the structs it's passed
+        let vendor = unsafe {
+            let mut info: PFactoryInfo = std::mem::zeroed();
+            if (factory.get_factory_info)(self.factory.as_ptr(), &mut info) == RESULT_OK {
+                c_string(&info.vendor)
+            } else {
+                String::new()
+            }
+        };
+        let count = unsafe { (factory.count_classes)(self.factory.as_ptr()) };
+
+        let mut plugins = Vec::new();
+        for index in 0..count.max(0) {
+            let info = match &factory2 {
+                Some(factory2) => unsafe {
+                    let mut info: PClassInfo2 = std::mem::zeroed();
+                    if (factory2.vtbl().get_class_info2)(factory2.as_ptr(), index, &mut info)
+                        != RESULT_OK
+                    {
+                        continue;
+                    }
+                    if !is_audio_class(&info.category) {
+                        continue;
+                    }
+                    let class_vendor = c_string(&info.vendor);
+                    Vst3Info {
+                        name: c_string(&info.name),
+                        vendor: if class_vendor.is_empty() {
+                            vendor.clone()
+                        } else {
+                            class_vendor
+                        },
+                        version: c_string(&info.version),
+                        category: c_string(&info.sub_categories),
+                        path: self.path.clone(),
+                        id: info.cid,
+                    }
+                },
+                None => unsafe {
+                    let mut info: PClassInfo = std::mem::zeroed();
+                    if (factory.get_class_info)(self.factory.as_ptr(), index, &mut info)
+                        != RESULT_OK
+                    {
+                        continue;
+                    }
+                    if !is_audio_class(&info.category) {
+                        continue;
+                    }
+                    Vst3Info {
+                        name: c_string(&info.name),
+                        vendor: vendor.clone(),
+                        version: String::new(),
+                        category: String::new(),
+                        path: self.path.clone(),
+                        id: info.cid,
+                    }
+                },
+            };
+            plugins.push(info);
+        }
+        plugins
+    }
+
+    /// Make an instance of class `id`, as the interface `V`
+    pub(super) fn create<V: super::bindings::Interface>(&self, id: &[u8; 16]) -> Option<ComPtr<V>> {
+        let mut object: *mut c_void = std::ptr::null_mut();
+        // SAFETY: ids are passed as pointers to their 16 bytes
+        unsafe {
+            let result = (self.factory.vtbl().create_instance)(
+                self.factory.as_ptr(),
+                id.as_ptr().cast(),
+                V::IID.as_ptr().cast(),
+                &mut object,
+            );
+            if result != RESULT_OK {
+                return None;
+            }
+            ComPtr::from_raw(object)
+        }
+    }
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        // The factory goes before the module is told it's being unloaded
+        // SAFETY: we're done with everything the module made, and the factory
+        // isn't touched again
+        unsafe {
+            ManuallyDrop::drop(&mut self.factory);
+            exit(&self.library);
+        }
+    }
+}
+
+fn is_audio_class(category: &[std::ffi::c_char]) -> bool {
+    c_string(category).as_bytes() == AUDIO_EFFECT_CLASS
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn enter(library: &Library, _bundle: &Path) -> Result<(), String> {
+    type ModuleEntry = unsafe extern "C" fn(*mut c_void) -> bool;
+    // SAFETY: `ModuleEntry` takes the library's handle, which it may ignore
+    unsafe {
+        if let Ok(entry) = library.get::<ModuleEntry>(b"ModuleEntry\0")
+            && !entry(std::ptr::null_mut())
+        {
+            return Err("its ModuleEntry failed".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn exit(library: &Library) {
+    type ModuleExit = unsafe extern "C" fn() -> bool;
+    if let Ok(exit) = unsafe { library.get::<ModuleExit>(b"ModuleExit\0") } {
+        unsafe { exit() };
+    }
+}
+
+#[cfg(windows)]
+unsafe fn enter(library: &Library, _bundle: &Path) -> Result<(), String> {
+    type InitDll = unsafe extern "system" fn() -> bool;
+    unsafe {
+        if let Ok(entry) = library.get::<InitDll>(b"InitDll\0")
+            && !entry()
+        {
+            return Err("its InitDll failed".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+unsafe fn exit(library: &Library) {
+    type ExitDll = unsafe extern "system" fn() -> bool;
+    if let Ok(exit) = unsafe { library.get::<ExitDll>(b"ExitDll\0") } {
+        unsafe { exit() };
+    }
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn enter(library: &Library, bundle: &Path) -> Result<(), String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    unsafe extern "C" {
+        fn CFURLCreateFromFileSystemRepresentation(
+            allocator: *const c_void,
+            buffer: *const u8,
+            length: isize,
+            is_directory: u8,
+        ) -> *const c_void;
+        fn CFBundleCreate(allocator: *const c_void, url: *const c_void) -> *mut c_void;
+        fn CFRelease(object: *const c_void);
+    }
+    type BundleEntry = unsafe extern "C" fn(*mut c_void) -> bool;
+
+    let path = bundle.as_os_str().as_bytes();
+    // SAFETY: `bundleEntry` takes a CFBundleRef for the plugin's bundle, which it
+    // keeps a reference to if it needs one
+    unsafe {
+        let Ok(entry) = library.get::<BundleEntry>(b"bundleEntry\0") else {
+            return Ok(());
+        };
+        let url = CFURLCreateFromFileSystemRepresentation(
+            std::ptr::null(),
+            path.as_ptr(),
+            path.len() as isize,
+            1,
+        );
+        if url.is_null() {
+            return Err("its bundle path isn't valid".to_string());
+        }
+        let cf_bundle = CFBundleCreate(std::ptr::null(), url);
+        CFRelease(url);
+        if cf_bundle.is_null() {
+            return Err("its bundle couldn't be opened".to_string());
+        }
+        let entered = entry(cf_bundle);
+        CFRelease(cf_bundle);
+        if !entered {
+            return Err("its bundleEntry failed".to_string());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+unsafe fn exit(library: &Library) {
+    type BundleExit = unsafe extern "C" fn() -> bool;
+    if let Ok(exit) = unsafe { library.get::<BundleExit>(b"bundleExit\0") } {
+        unsafe { exit() };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundles_are_found_without_looking_inside_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let vendor = dir.path().join("Vendor");
+        let bundle = vendor.join("Reverb.vst3");
+        std::fs::create_dir_all(bundle.join("Contents").join(architecture_folder())).unwrap();
+        std::fs::create_dir_all(bundle.join("Contents/Resources/Nested.vst3")).unwrap();
+        std::fs::write(dir.path().join("Delay.VST3"), b"").unwrap();
+        std::fs::write(dir.path().join("readme.txt"), b"").unwrap();
+
+        let found = find_bundles(&[dir.path().to_path_buf(), dir.path().join("missing")]);
+        assert_eq!(found, vec![dir.path().join("Delay.VST3"), bundle.clone()]);
+
+        let binary = bundle_binary(&bundle);
+        assert!(binary.starts_with(bundle.join("Contents").join(architecture_folder())));
+        assert_eq!(binary.file_stem().unwrap(), "Reverb");
+        assert_eq!(
+            bundle_binary(&dir.path().join("Delay.VST3")),
+            dir.path().join("Delay.VST3")
+        );
+    }
+}
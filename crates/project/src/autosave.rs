@@ -0,0 +1,119 @@
+//! Autosaves kept next to a project for recovering after a crash.
+//!
+//! An autosave is a complete project file at `song.autosave` beside `song.dawproj`,
+//! so samples resolve and the peak file is shared the same way. It only exists while
+//! it holds something the project file doesn't: writing one that matches the project
+//! removes it instead. One still there when the project is opened again therefore
+//! holds work that was never saved.
+
+use crate::ProjectError;
+use crate::container::same_project;
+use std::path::{Path, PathBuf};
+
+/// Extension of autosave files, which replaces the project's
+const AUTOSAVE_EXTENSION: &str = "autosave";
+/// Extension of an autosave being written, renamed into place once complete
+const AUTOSAVE_TEMP_EXTENSION: &str = "autosave-tmp";
+
+/// Path of the autosave for a project, e.g. `song.dawproj` -> `song.autosave`
+pub fn autosave_path(project_path: &Path) -> PathBuf {
+    project_path.with_extension(AUTOSAVE_EXTENSION)
+}
+
+/// The autosave of the project at `project_path`, if there is one that differs
+/// from the project file (or the project file can't be read).
+pub fn stale_autosave(project_path: &Path) -> Option<PathBuf> {
+    let path = autosave_path(project_path);
+    let autosave = std::fs::read(&path).ok()?;
+    match std::fs::read(project_path) {
+        Ok(project) if same_project(&project, &autosave) => None,
+        _ => Some(path),
+    }
+}
+
+/// Autosave the project at `project_path`.
+///
+/// `write` saves the project to the path it's given. The result goes through a
+/// temporary file, so a crash while writing leaves the previous autosave intact.
+/// Returns whether the autosave holds unsaved changes; if it matches the project
+/// file, no autosave is kept.
+pub fn write_autosave(
+    project_path: &Path,
+    write: impl FnOnce(&Path) -> Result<(), ProjectError>,
+) -> Result<bool, ProjectError> {
+    let temp = project_path.with_extension(AUTOSAVE_TEMP_EXTENSION);
+    if let Err(err) = write(&temp) {
+        let _ = std::fs::remove_file(&temp);
+        return Err(err);
+    }
+
+    let unsaved = match std::fs::read(project_path) {
+        Ok(project) => !same_project(&project, &std::fs::read(&temp)?),
+        Err(_) => true,
+    };
+    if unsaved {
+        std::fs::rename(&temp, autosave_path(project_path))?;
+    } else {
+        std::fs::remove_file(&temp)?;
+        discard_autosave(project_path)?;
+    }
+    Ok(unsaved)
+}
+
+/// Remove the autosave of the project at `project_path`, if there is one
+pub fn discard_autosave(project_path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(autosave_path(project_path)) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write(contents: &'static str) -> impl FnOnce(&Path) -> Result<(), ProjectError> {
+        move |path| Ok(std::fs::write(path, contents)?)
+    }
+
+    #[test]
+    fn test_autosave_only_kept_while_it_differs() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("song.dawproj");
+        std::fs::write(&project, "saved").unwrap();
+        assert_eq!(autosave_path(&project), dir.path().join("song.autosave"));
+
+        assert!(write_autosave(&project, write("edited")).unwrap());
+        assert_eq!(stale_autosave(&project), Some(autosave_path(&project)));
+
+        // Back to what's saved, so there's nothing to recover
+        assert!(!write_autosave(&project, write("saved")).unwrap());
+        assert!(!autosave_path(&project).exists());
+        assert_eq!(stale_autosave(&project), None);
+        assert!(!project.with_extension(AUTOSAVE_TEMP_EXTENSION).exists());
+    }
+
+    #[test]
+    fn test_failed_autosave_keeps_the_previous_one() {
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("song.dawproj");
+        std::fs::write(&project, "saved").unwrap();
+        write_autosave(&project, write("edited")).unwrap();
+
+        let failed = write_autosave(&project, |path| {
+            std::fs::write(path, "half")?;
+            Err(std::io::Error::other("disk full").into())
+        });
+        assert!(failed.is_err());
+        assert_eq!(
+            std::fs::read_to_string(autosave_path(&project)).unwrap(),
+            "edited"
+        );
+        assert!(!project.with_extension(AUTOSAVE_TEMP_EXTENSION).exists());
+
+        discard_autosave(&project).unwrap();
+        discard_autosave(&project).unwrap();
+        assert_eq!(stale_autosave(&project), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! Gathering a project's samples into its own folder.
+//!
+//! A project refers to samples by path, relative to itself or to the dev root, so it
+//! only opens elsewhere if those files come along. [`collect_samples`] copies every
+//! sample that lives outside the project directory into its [`AUDIO_DIR`] and points
+//! the references at the copies, leaving a folder that can be moved as a whole.
+//! [`rebase_samples`] is the lighter option when saving elsewhere: it leaves the files
+//! where they are and only rewrites the references so they still find them.
+
+use crate::{AUDIO_DIR, PathContext, ProjectError, SampleRef};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// What [`collect_samples`] did.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CollectReport {
+    /// Files copied into the project, at their new paths
+    pub copied: Vec<PathBuf>,
+    /// Samples that couldn't be found, by clip name; their references are kept as
+    /// they were
+    pub missing: Vec<String>,
+}
+
+/// Copy the samples behind `sample_refs` into the [`AUDIO_DIR`] of the project
+/// described by `to`, rewriting the references to point at the copies.
+///
+/// References are resolved with `from`, the context they were made in. Samples
+/// already inside `to`'s project directory aren't copied but are referenced
+/// relative to it. Each file is copied once however many clips use it, under its
+/// own name; a different file already there by that name gets a numbered name
+/// (`kick-2.wav`) instead, while an identical one is reused.
+pub fn collect_samples(
+    sample_refs: &mut HashMap<String, SampleRef>,
+    from: &PathContext,
+    to: &PathContext,
+) -> Result<CollectReport, ProjectError> {
+    let audio_dir = to.project_root.join(AUDIO_DIR);
+    let mut report = CollectReport::default();
+    let mut collected: HashMap<PathBuf, SampleRef> = HashMap::new();
+
+    // In name order, so which of two same-named files gets the plain name is stable
+    let mut names: Vec<String> = sample_refs.keys().cloned().collect();
+    names.sort();
+    for name in names {
+        let Some(source) = from.resolve(&sample_refs[&name]) else {
+            report.missing.push(name);
+            continue;
+        };
+        let sample_ref = match collected.get(&source) {
+            Some(sample_ref) => sample_ref.clone(),
+            None => {
+                let sample_ref = if source.starts_with(&to.project_root) {
+                    to.sample_ref_for(&source)
+                } else {
+                    let (dest, copied) = copy_into(&source, &audio_dir)?;
+                    if copied {
+                        report.copied.push(dest.clone());
+                    }
+                    to.sample_ref_for(&dest)
+                };
+                collected.insert(source, sample_ref.clone());
+                sample_ref
+            }
+        };
+        sample_refs.insert(name, sample_ref);
+    }
+    Ok(report)
+}
+
+/// Rewrite the project-relative references in `sample_refs`, made in `from`, so they
+/// point at the same files from the project described by `to`.
+///
+/// Files inside `to`'s project directory are referenced relative to it, anything
+/// else by its full path, whether or not the file exists right now. Dev root and
+/// embedded references don't depend on where the project is and are left alone.
+pub fn rebase_samples(
+    sample_refs: &mut HashMap<String, SampleRef>,
+    from: &PathContext,
+    to: &PathContext,
+) {
+    let from_root = absolute_root(&from.project_root);
+    let to = PathContext {
+        project_root: absolute_root(&to.project_root),
+        ..to.clone()
+    };
+    if from_root == to.project_root {
+        return;
+    }
+    for sample_ref in sample_refs.values_mut() {
+        if let SampleRef::ProjectRelative(path) = sample_ref {
+            *sample_ref = to.sample_ref_for(&without_parent_dirs(&from_root.join(path)));
+        }
+    }
+}
+
+/// `root` as an absolute path, an empty one meaning the working directory
+fn absolute_root(root: &Path) -> PathBuf {
+    let root = if root.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        root
+    };
+    std::path::absolute(root).unwrap_or_else(|_| root.to_path_buf())
+}
+
+/// `path` with each `..` taking out the folder before it, so a file reached through
+/// another folder is recognised as being inside it
+fn without_parent_dirs(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir if result.file_name().is_some() => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+/// Copy `source` into `dir` under the first free or identical name, returning where
+/// it ended up and whether it had to be copied
+fn copy_into(source: &Path, dir: &Path) -> std::io::Result<(PathBuf, bool)> {
+    std::fs::create_dir_all(dir)?;
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "sample".to_string());
+    let extension = source
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+
+    let contents = std::fs::read(source)?;
+    for number in 1.. {
+        let name = match number {
+            1 => format!("{}{}", stem, extension),
+            n => format!("{}-{}{}", stem, n, extension),
+        };
+        let dest = dir.join(name);
+        match std::fs::read(&dest) {
+            Ok(existing) if existing == contents => return Ok((dest, false)),
+            Ok(_) => continue,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                std::fs::write(&dest, &contents)?;
+                return Ok((dest, true));
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!("ran out of file numbers")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_samples_are_copied_into_the_project() {
+        let dir = tempdir().unwrap();
+        let dev_root = dir.path().join("dev");
+        let old = dir.path().join("old");
+        let new = dir.path().join("new");
+        std::fs::create_dir_all(dev_root.join("samples/cr78")).unwrap();
+        std::fs::create_dir_all(old.join("audio")).unwrap();
+        std::fs::create_dir_all(new.join("audio")).unwrap();
+        std::fs::write(dev_root.join("samples/cr78/kick.wav"), b"cr78 kick").unwrap();
+        std::fs::write(old.join("audio/kick.wav"), b"old kick").unwrap();
+        std::fs::write(new.join("audio/kick.wav"), b"someone else's kick").unwrap();
+        std::fs::write(new.join("audio/hat.wav"), b"hat").unwrap();
+
+        let mut sample_refs = HashMap::from([
+            ("a".to_string(), SampleRef::DevRoot("cr78/kick.wav".into())),
+            ("b".to_string(), SampleRef::DevRoot("cr78/kick.wav".into())),
+            (
+                "c".to_string(),
+                SampleRef::ProjectRelative("audio/kick.wav".into()),
+            ),
+            (
+                "d".to_string(),
+                SampleRef::ProjectRelative(new.join("audio/hat.wav")),
+            ),
+            ("e".to_string(), SampleRef::DevRoot("gone.wav".into())),
+        ]);
+        let from = PathContext::from_project_path(&old.join("song.dawproj"))
+            .with_dev_root(dev_root.clone());
+        let to = PathContext::from_project_path(&new.join("song.dawproj"));
+        let report = collect_samples(&mut sample_refs, &from, &to).unwrap();
+
+        // Both kicks clash with the file already there, and each other
+        let project = |path: &str| SampleRef::ProjectRelative(path.into());
+        assert_eq!(sample_refs["a"], project("audio/kick-2.wav"));
+        assert_eq!(sample_refs["b"], project("audio/kick-2.wav"));
+        assert_eq!(sample_refs["c"], project("audio/kick-3.wav"));
+        assert_eq!(sample_refs["d"], project("audio/hat.wav"));
+        assert_eq!(sample_refs["e"], SampleRef::DevRoot("gone.wav".into()));
+        assert_eq!(
+            report.copied,
+            vec![new.join("audio/kick-2.wav"), new.join("audio/kick-3.wav")]
+        );
+        assert_eq!(report.missing, vec!["e".to_string()]);
+        assert_eq!(
+            std::fs::read(new.join("audio/kick-3.wav")).unwrap(),
+            b"old kick"
+        );
+        assert_eq!(
+            std::fs::read(new.join("audio/kick.wav")).unwrap(),
+            b"someone else's kick"
+        );
+
+        // Collecting again finds everything in place
+        let report = collect_samples(&mut sample_refs, &to, &to).unwrap();
+        assert!(report.copied.is_empty());
+        assert_eq!(sample_refs["a"], project("audio/kick-2.wav"));
+    }
+
+    #[test]
+    fn test_references_are_rebased_onto_the_new_project() {
+        let dir = tempdir().unwrap();
+        let old = dir.path().join("old");
+        let new = dir.path().join("new");
+        let project = |path: &Path| SampleRef::ProjectRelative(path.to_path_buf());
+
+        let mut sample_refs = HashMap::from([
+            ("kick".to_string(), project(Path::new("audio/kick.wav"))),
+            (
+                "shared".to_string(),
+                project(Path::new("../new/audio/hat.wav")),
+            ),
+            (
+                "far".to_string(),
+                project(&dir.path().join("elsewhere/snare.wav")),
+            ),
+            (
+                "dev".to_string(),
+                SampleRef::DevRoot("cr78/kick.wav".into()),
+            ),
+            (
+                "embedded".to_string(),
+                SampleRef::Embedded("clap.wav".into()),
+            ),
+        ]);
+        let from = PathContext::from_project_path(&old.join("song.dawproj"));
+        let to = PathContext::from_project_path(&new.join("song.dawproj"));
+        let before = sample_refs.clone();
+        rebase_samples(&mut sample_refs, &from, &from);
+        assert_eq!(sample_refs, before);
+
+        rebase_samples(&mut sample_refs, &from, &to);
+        assert_eq!(sample_refs["kick"], project(&old.join("audio/kick.wav")));
+        assert_eq!(sample_refs["shared"], project(Path::new("audio/hat.wav")));
+        assert_eq!(
+            sample_refs["far"],
+            project(&dir.path().join("elsewhere/snare.wav"))
+        );
+        assert_eq!(sample_refs["dev"], before["dev"]);
+        assert_eq!(sample_refs["embedded"], before["embedded"]);
+
+        // Moving back makes the ones inside the old project relative again
+        rebase_samples(&mut sample_refs, &to, &from);
+        assert_eq!(sample_refs["kick"], before["kick"]);
+    }
+}
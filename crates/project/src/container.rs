@@ -0,0 +1,193 @@
+//! Projects saved as a zip archive, for sharing as a single file.
+//!
+//! The archive holds the project as `project.json`, the peak file as
+//! `peaks.dawpeaks`, and embedded samples as files under `audio/`, where they take
+//! their original size instead of growing by a third as base64. Loading recognizes
+//! the archive by its first bytes, so it opens like any other project file. The
+//! bare JSON file stays the simplest to read and diff during development.
+
+use crate::peaks::PeakFile;
+use crate::{EmbeddedAudio, Project, ProjectError};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+
+/// First bytes of a zip archive, which mark a zip project file
+pub(crate) const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+
+const PROJECT_ENTRY: &str = "project.json";
+const PEAKS_ENTRY: &str = "peaks.dawpeaks";
+const AUDIO_FOLDER: &str = "audio/";
+
+/// Write `project` as a zip archive, with its embedded samples as files of their own
+/// and `peaks` (the bytes of a peak file) if given.
+pub(crate) fn write_zip<W: Write + Seek>(
+    writer: W,
+    mut project: Project,
+    peaks: Option<&[u8]>,
+) -> Result<W, ProjectError> {
+    // Fixed timestamps, so saving an unchanged project gives the same bytes
+    let options = SimpleFileOptions::default().last_modified_time(DateTime::default());
+    let deflated = options.compression_method(CompressionMethod::Deflated);
+    // Audio files are mostly compressed already
+    let stored = options.compression_method(CompressionMethod::Stored);
+
+    let embedded = std::mem::take(&mut project.embedded);
+    let mut zip = ZipWriter::new(writer);
+    zip.start_file(PROJECT_ENTRY, deflated)?;
+    serde_json::to_writer_pretty(&mut zip, &project)?;
+    if let Some(peaks) = peaks {
+        zip.start_file(PEAKS_ENTRY, stored)?;
+        zip.write_all(peaks)?;
+    }
+    for (name, audio) in &embedded {
+        zip.start_file(format!("{}{}", AUDIO_FOLDER, name), stored)?;
+        zip.write_all(&audio.0)?;
+    }
+    Ok(zip.finish()?)
+}
+
+/// Read the project from a zip archive, with the samples under `audio/` as its
+/// embedded ones
+pub(crate) fn read_zip(reader: impl Read + Seek) -> Result<Project, ProjectError> {
+    let mut zip = ZipArchive::new(reader)?;
+    let mut project: Project = serde_json::from_reader(zip.by_name(PROJECT_ENTRY)?)?;
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        let Some(name) = entry.name().strip_prefix(AUDIO_FOLDER) else {
+            continue;
+        };
+        if !entry.is_file() || name.is_empty() {
+            continue;
+        }
+        let name = name.to_string();
+        // Not sized from the entry's header, which a damaged archive can get wrong
+        let mut bytes = Vec::new();
+        entry.read_to_end(&mut bytes)?;
+        project.embedded.insert(name, EmbeddedAudio(bytes));
+    }
+    Ok(project)
+}
+
+/// The peak file stored in the zip archive at `path`, or `None` if it has none or
+/// can't be read
+pub(crate) fn read_zip_peaks(path: &Path) -> Option<PeakFile> {
+    let mut zip = ZipArchive::new(BufReader::new(File::open(path).ok()?)).ok()?;
+    let entry = zip.by_name(PEAKS_ENTRY).ok()?;
+    Some(PeakFile::read(entry))
+}
+
+/// Whether two project files hold the same project.
+///
+/// Zip archives that differ only in their peaks are the same project, since the
+/// peaks are worked out from the audio; any other files have to match exactly.
+pub(crate) fn same_project(a: &[u8], b: &[u8]) -> bool {
+    if a == b {
+        return true;
+    }
+    if !a.starts_with(&ZIP_MAGIC) || !b.starts_with(&ZIP_MAGIC) {
+        return false;
+    }
+    match (zip_entries(a), zip_entries(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Every entry of a zip archive but its peaks, by name
+fn zip_entries(bytes: &[u8]) -> Result<BTreeMap<String, Vec<u8>>, ProjectError> {
+    let mut zip = ZipArchive::new(Cursor::new(bytes))?;
+    let mut entries = BTreeMap::new();
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        if entry.name() == PEAKS_ENTRY {
+            continue;
+        }
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        entries.insert(entry.name().to_string(), contents);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn project_with_embedded_sample() -> Project {
+        let mut project: Project = serde_json::from_str(
+            r#"{ "name": "Sketch", "tempo": 90.0, "time_signature": [3, 4], "tracks": [] }"#,
+        )
+        .unwrap();
+        project.embedded =
+            BTreeMap::from([("kick.wav".to_string(), EmbeddedAudio(b"RIFF kick".to_vec()))]);
+        project
+    }
+
+    #[test]
+    fn test_zip_holds_project_peaks_and_audio() {
+        let peaks = rmp_serde::to_vec(&PeakFile::default()).unwrap();
+        let bytes = write_zip(
+            Cursor::new(Vec::new()),
+            project_with_embedded_sample(),
+            Some(&peaks),
+        )
+        .unwrap()
+        .into_inner();
+        assert!(bytes.starts_with(&ZIP_MAGIC));
+
+        let mut zip = ZipArchive::new(Cursor::new(&bytes)).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["audio/kick.wav", "peaks.dawpeaks", "project.json"]);
+        // The samples are files of their own, not base64 in the JSON
+        let json: serde_json::Value =
+            serde_json::from_reader(zip.by_name("project.json").unwrap()).unwrap();
+        assert!(json.get("embedded").is_none());
+
+        let project = read_zip(Cursor::new(&bytes)).unwrap();
+        assert_eq!(project.name, "Sketch");
+        assert_eq!(project.time_signature, (3, 4));
+        assert_eq!(project.embedded["kick.wav"].0, b"RIFF kick");
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sketch.dawproj");
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(read_zip_peaks(&path).is_some());
+
+        // Different peaks are still the same project; different audio isn't
+        let zip = |project: Project, peaks: Option<&[u8]>| {
+            write_zip(Cursor::new(Vec::new()), project, peaks)
+                .unwrap()
+                .into_inner()
+        };
+        assert!(same_project(
+            &bytes,
+            &zip(project_with_embedded_sample(), None)
+        ));
+        let mut changed = project_with_embedded_sample();
+        changed.embedded.get_mut("kick.wav").unwrap().0.push(0);
+        assert!(!same_project(&bytes, &zip(changed, Some(&peaks))));
+        assert!(!same_project(&bytes, b"{}"));
+    }
+
+    #[test]
+    fn test_zip_without_peaks_or_audio() {
+        let mut project = project_with_embedded_sample();
+        project.embedded.clear();
+        let bytes = write_zip(Cursor::new(Vec::new()), project, None)
+            .unwrap()
+            .into_inner();
+        let project = read_zip(Cursor::new(&bytes)).unwrap();
+        assert!(project.embedded.is_empty());
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sketch.dawproj");
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(read_zip_peaks(&path).is_none());
+    }
+}
@@ -0,0 +1,708 @@
+//! DAWproject export, for opening an arrangement in Bitwig Studio, Studio One and
+//! other DAWs that read the open interchange format.
+//!
+//! A `.dawproject` file is a zip archive holding `project.xml` (tracks, mixer and
+//! arrangement), `metadata.xml` (title, artist, comment) and the audio the clips
+//! play, under `audio/`. Positions are written in beats, tracks keep their volume,
+//! pan, mute, solo, color and folder nesting, and locators become markers. Clip
+//! gain and reversed playback have no equivalent here and are left out, as are
+//! clips whose samples can't be found.
+
+use crate::{PathContext, ProjectError, ProjectInfo, SampleRef, TempoMap, embed_samples};
+use daw_transport::{AudioArc, Clip, ClipContent, NoteList, PPQN, Track};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::{Seek, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+const AUDIO_FOLDER: &str = "audio/";
+
+/// Write the arrangement to a DAWproject file at `path`.
+///
+/// `tempo` and `time_signature` are what the arrangement starts with and
+/// `tempo_map` holds the changes after that. Audio clips are looked up in
+/// `sample_refs` by name and their files, resolved with `ctx`, are copied into the
+/// archive once each.
+#[allow(clippy::too_many_arguments)]
+pub fn export_dawproject(
+    path: &Path,
+    name: &str,
+    info: &ProjectInfo,
+    tempo: f64,
+    time_signature: (u32, u32),
+    tempo_map: &TempoMap,
+    locators: &BTreeMap<u8, u64>,
+    tracks: &[Track],
+    sample_refs: &HashMap<String, SampleRef>,
+    ctx: &PathContext,
+) -> Result<(), ProjectError> {
+    // The same naming as embedded samples: one file per sample, numbered on clashes
+    let mut files = sample_refs.clone();
+    let audio = embed_samples(&mut files, ctx)?;
+    let files: HashMap<String, String> = files
+        .into_iter()
+        .filter_map(|(clip_name, sample_ref)| match sample_ref {
+            SampleRef::Embedded(file) => Some((clip_name, file.to_string_lossy().into_owned())),
+            _ => None,
+        })
+        .collect();
+
+    let arrangement = Arrangement {
+        tempo,
+        time_signature,
+        tempo_map,
+        locators,
+        tracks,
+        files: &files,
+    };
+    let mut zip = ZipWriter::new(std::io::BufWriter::new(std::fs::File::create(path)?));
+    write_archive(
+        &mut zip,
+        &arrangement.project_xml(),
+        &metadata_xml(name, info),
+    )?;
+    // Audio files are mostly compressed already
+    let stored = entry_options().compression_method(CompressionMethod::Stored);
+    for (file, bytes) in &audio {
+        zip.start_file(format!("{}{}", AUDIO_FOLDER, file), stored)?;
+        zip.write_all(&bytes.0)?;
+    }
+    zip.finish()?.flush()?;
+    Ok(())
+}
+
+fn entry_options() -> SimpleFileOptions {
+    // Fixed timestamps, so exporting the same arrangement gives the same bytes
+    SimpleFileOptions::default().last_modified_time(DateTime::default())
+}
+
+fn write_archive<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    project: &str,
+    metadata: &str,
+) -> Result<(), ProjectError> {
+    let deflated = entry_options().compression_method(CompressionMethod::Deflated);
+    zip.start_file("project.xml", deflated)?;
+    zip.write_all(project.as_bytes())?;
+    zip.start_file("metadata.xml", deflated)?;
+    zip.write_all(metadata.as_bytes())?;
+    Ok(())
+}
+
+fn metadata_xml(name: &str, info: &ProjectInfo) -> String {
+    let mut xml = Xml::default();
+    xml.open("MetaData", &[]);
+    xml.text("Title", name);
+    if !info.author.is_empty() {
+        xml.text("Artist", &info.author);
+    }
+    if !info.notes.is_empty() {
+        xml.text("Comment", &info.notes);
+    }
+    xml.close();
+    xml.finish()
+}
+
+/// Everything `project.xml` is written from
+struct Arrangement<'a> {
+    tempo: f64,
+    time_signature: (u32, u32),
+    tempo_map: &'a TempoMap,
+    locators: &'a BTreeMap<u8, u64>,
+    tracks: &'a [Track],
+    /// File in the archive's audio folder, by clip name
+    files: &'a HashMap<String, String>,
+}
+
+impl Arrangement<'_> {
+    fn project_xml(&self) -> String {
+        let mut xml = Xml::default();
+        xml.open("Project", &[("version", "1.0".to_string())]);
+        xml.empty(
+            "Application",
+            &[
+                ("name", "daw".to_string()),
+                ("version", env!("CARGO_PKG_VERSION").to_string()),
+            ],
+        );
+
+        let tempo_id = xml.id();
+        let time_signature_id = xml.id();
+        xml.open("Transport", &[]);
+        xml.empty(
+            "Tempo",
+            &[
+                ("unit", "bpm".to_string()),
+                ("value", self.tempo.to_string()),
+                ("id", tempo_id.clone()),
+                ("name", "Tempo".to_string()),
+            ],
+        );
+        xml.empty(
+            "TimeSignature",
+            &[
+                ("numerator", self.time_signature.0.to_string()),
+                ("denominator", self.time_signature.1.to_string()),
+                ("id", time_signature_id.clone()),
+            ],
+        );
+        xml.close();
+
+        // Tracks are nested in their folders; every track gets an id up front so
+        // lanes can refer to it
+        let track_ids: HashMap<u64, String> = self
+            .tracks
+            .iter()
+            .map(|track| (track.id.0, xml.id()))
+            .collect();
+        let master_channel = xml.id();
+        xml.open("Structure", &[]);
+        for track in self.children(None) {
+            self.write_track(&mut xml, track, &track_ids, &master_channel);
+        }
+        let master_id = xml.id();
+        xml.open(
+            "Track",
+            &[
+                ("contentType", "audio notes".to_string()),
+                ("loaded", "true".to_string()),
+                ("id", master_id),
+                ("name", "Master".to_string()),
+            ],
+        );
+        xml.open(
+            "Channel",
+            &[
+                ("audioChannels", "2".to_string()),
+                ("role", "master".to_string()),
+                ("solo", "false".to_string()),
+                ("id", master_channel),
+            ],
+        );
+        mixer(&mut xml, 1.0, 0.0, true);
+        xml.close();
+        xml.close();
+        xml.close();
+
+        let arrangement_id = xml.id();
+        xml.open("Arrangement", &[("id", arrangement_id)]);
+        self.write_tempo_changes(&mut xml, &tempo_id, &time_signature_id);
+        if !self.locators.is_empty() {
+            let markers_id = xml.id();
+            xml.open("Markers", &[("id", markers_id)]);
+            for (number, &tick) in self.locators {
+                xml.empty(
+                    "Marker",
+                    &[
+                        ("time", beats(tick)),
+                        ("name", format!("Locator {}", number)),
+                    ],
+                );
+            }
+            xml.close();
+        }
+        let lanes_id = xml.id();
+        xml.open(
+            "Lanes",
+            &[("timeUnit", "beats".to_string()), ("id", lanes_id)],
+        );
+        for track in self.tracks.iter().filter(|track| !track.is_folder) {
+            let lanes_id = xml.id();
+            xml.open(
+                "Lanes",
+                &[("track", track_ids[&track.id.0].clone()), ("id", lanes_id)],
+            );
+            let clips_id = xml.id();
+            xml.open("Clips", &[("id", clips_id)]);
+            for clip in track.clips() {
+                self.write_clip(&mut xml, clip);
+            }
+            xml.close();
+            xml.close();
+        }
+        xml.close();
+        xml.close();
+        xml.close();
+        xml.finish()
+    }
+
+    /// The tracks in `folder`, or at the top if `None`, in order. Tracks whose
+    /// folder doesn't exist are put at the top.
+    fn children(&self, folder: Option<u64>) -> impl Iterator<Item = &Track> {
+        self.tracks.iter().filter(move |track| {
+            let parent = track
+                .parent
+                .as_ref()
+                .map(|parent| parent.0)
+                .filter(|&parent| self.tracks.iter().any(|t| t.id.0 == parent && t.is_folder));
+            parent == folder
+        })
+    }
+
+    fn write_track(
+        &self,
+        xml: &mut Xml,
+        track: &Track,
+        track_ids: &HashMap<u64, String>,
+        destination: &str,
+    ) {
+        let has_audio = track.clips().iter().any(|clip| clip.audio().is_some());
+        let has_notes = track
+            .clips()
+            .iter()
+            .any(|clip| matches!(clip.content, ClipContent::Midi(_)));
+        let content_type = match (track.is_folder, has_audio, has_notes) {
+            (true, _, _) => "tracks",
+            (false, true, true) => "audio notes",
+            (false, false, true) => "notes",
+            _ => "audio",
+        };
+        let mut attributes = vec![
+            ("contentType", content_type.to_string()),
+            ("loaded", "true".to_string()),
+            ("id", track_ids[&track.id.0].clone()),
+            ("name", track.name.clone()),
+        ];
+        if let Some([r, g, b]) = track.color {
+            attributes.push(("color", format!("#{:02x}{:02x}{:02x}", r, g, b)));
+        }
+        xml.open("Track", &attributes);
+
+        let channel_id = xml.id();
+        let role = if track.is_folder { "submix" } else { "regular" };
+        xml.open(
+            "Channel",
+            &[
+                ("audioChannels", "2".to_string()),
+                ("destination", destination.to_string()),
+                ("role", role.to_string()),
+                ("solo", track.solo.to_string()),
+                ("id", channel_id.clone()),
+            ],
+        );
+        mixer(xml, track.volume, track.pan, track.enabled);
+        xml.close();
+
+        if track.is_folder {
+            for child in self.children(Some(track.id.0)) {
+                self.write_track(xml, child, track_ids, &channel_id);
+            }
+        }
+        xml.close();
+    }
+
+    fn write_tempo_changes(&self, xml: &mut Xml, tempo_id: &str, time_signature_id: &str) {
+        let time_signatures = &self.tempo_map.time_signature_changes;
+        if !time_signatures.is_empty() {
+            let id = xml.id();
+            xml.open("TimeSignatureAutomation", &[("id", id)]);
+            xml.empty("Target", &[("parameter", time_signature_id.to_string())]);
+            let start = (0, self.time_signature);
+            let changes = time_signatures
+                .iter()
+                .map(|change| (change.tick, change.time_signature));
+            for (tick, (numerator, denominator)) in std::iter::once(start).chain(changes) {
+                xml.empty(
+                    "TimeSignaturePoint",
+                    &[
+                        ("time", beats(tick)),
+                        ("numerator", numerator.to_string()),
+                        ("denominator", denominator.to_string()),
+                    ],
+                );
+            }
+            xml.close();
+        }
+
+        let tempos = &self.tempo_map.tempo_changes;
+        if !tempos.is_empty() {
+            let id = xml.id();
+            xml.open(
+                "TempoAutomation",
+                &[("unit", "bpm".to_string()), ("id", id)],
+            );
+            xml.empty("Target", &[("parameter", tempo_id.to_string())]);
+            let start = (0, self.tempo);
+            let changes = tempos.iter().map(|change| (change.tick, change.bpm));
+            for (tick, bpm) in std::iter::once(start).chain(changes) {
+                xml.empty(
+                    "RealPoint",
+                    &[
+                        ("time", beats(tick)),
+                        ("value", bpm.to_string()),
+                        ("interpolation", "hold".to_string()),
+                    ],
+                );
+            }
+            xml.close();
+        }
+    }
+
+    fn write_clip(&self, xml: &mut Xml, clip: &Clip) {
+        let mut attributes = vec![
+            ("time", beats(clip.start_tick)),
+            ("duration", beats(clip.duration_ticks())),
+            ("name", clip.name.clone()),
+        ];
+        if clip.fade_in_ticks > 0 || clip.fade_out_ticks > 0 {
+            attributes.push(("fadeTimeUnit", "beats".to_string()));
+            attributes.push(("fadeInTime", beats(clip.fade_in_ticks)));
+            attributes.push(("fadeOutTime", beats(clip.fade_out_ticks)));
+        }
+
+        match &clip.content {
+            ClipContent::Audio(audio) => {
+                let Some(file) = self.files.get(&clip.name) else {
+                    return;
+                };
+                self.write_audio_clip(xml, attributes, clip, audio, file);
+            }
+            ClipContent::Midi(notes) => {
+                attributes.push(("playStart", "0".to_string()));
+                xml.open("Clip", &attributes);
+                write_notes(xml, notes);
+                xml.close();
+            }
+        }
+    }
+
+    /// An audio clip playing the whole file through a warp from seconds to beats
+    /// at the starting tempo, started at its trim and looped over the file if set
+    fn write_audio_clip(
+        &self,
+        xml: &mut Xml,
+        mut attributes: Vec<(&'static str, String)>,
+        clip: &Clip,
+        audio: &AudioArc,
+        file: &str,
+    ) {
+        let sample_rate = audio.sample_rate().max(1) as f64;
+        let seconds = audio.frames() as f64 / sample_rate;
+        let beats_per_second = self.tempo / 60.0;
+        let offset = clip.audio_offset as f64 / sample_rate * beats_per_second;
+        attributes.push(("playStart", offset.to_string()));
+        if clip.looped {
+            attributes.push(("loopStart", "0".to_string()));
+            attributes.push(("loopEnd", (seconds * beats_per_second).to_string()));
+        }
+        xml.open("Clip", &attributes);
+
+        let warps_id = xml.id();
+        xml.open(
+            "Warps",
+            &[
+                ("timeUnit", "beats".to_string()),
+                ("contentTimeUnit", "seconds".to_string()),
+                ("id", warps_id),
+            ],
+        );
+        let audio_id = xml.id();
+        xml.open(
+            "Audio",
+            &[
+                ("algorithm", "raw".to_string()),
+                ("channels", audio.channels().to_string()),
+                ("duration", seconds.to_string()),
+                ("sampleRate", audio.sample_rate().to_string()),
+                ("id", audio_id),
+            ],
+        );
+        xml.empty("File", &[("path", format!("{}{}", AUDIO_FOLDER, file))]);
+        xml.close();
+        for (time, content_time) in [(0.0, 0.0), (seconds * beats_per_second, seconds)] {
+            xml.empty(
+                "Warp",
+                &[
+                    ("time", time.to_string()),
+                    ("contentTime", content_time.to_string()),
+                ],
+            );
+        }
+        xml.close();
+        xml.close();
+    }
+}
+
+fn write_notes(xml: &mut Xml, notes: &NoteList) {
+    let id = xml.id();
+    xml.open("Notes", &[("id", id)]);
+    for note in notes.notes() {
+        xml.empty(
+            "Note",
+            &[
+                ("time", beats(note.start_tick)),
+                ("duration", beats(note.duration_ticks)),
+                ("channel", "0".to_string()),
+                ("key", note.pitch.min(127).to_string()),
+                ("vel", (note.velocity.min(127) as f64 / 127.0).to_string()),
+                ("rel", "0.5".to_string()),
+            ],
+        );
+    }
+    xml.close();
+}
+
+/// A channel's volume, pan and mute. Pan goes from 0 (left) to 1 (right).
+fn mixer(xml: &mut Xml, volume: f32, pan: f32, enabled: bool) {
+    let ids = [xml.id(), xml.id(), xml.id()];
+    let [mute_id, pan_id, volume_id] = ids;
+    xml.empty(
+        "Mute",
+        &[
+            ("value", (!enabled).to_string()),
+            ("id", mute_id),
+            ("name", "Mute".to_string()),
+        ],
+    );
+    xml.empty(
+        "Pan",
+        &[
+            ("max", "1.0".to_string()),
+            ("min", "0.0".to_string()),
+            ("unit", "normalized".to_string()),
+            ("value", ((pan.clamp(-1.0, 1.0) + 1.0) / 2.0).to_string()),
+            ("id", pan_id),
+            ("name", "Pan".to_string()),
+        ],
+    );
+    xml.empty(
+        "Volume",
+        &[
+            ("max", "2.0".to_string()),
+            ("min", "0.0".to_string()),
+            ("unit", "linear".to_string()),
+            ("value", volume.to_string()),
+            ("id", volume_id),
+            ("name", "Volume".to_string()),
+        ],
+    );
+}
+
+/// `ticks` as a number of beats
+fn beats(ticks: u64) -> String {
+    (ticks as f64 / PPQN as f64).to_string()
+}
+
+/// Indented XML, written element by element
+#[derive(Default)]
+struct Xml {
+    out: String,
+    open: Vec<&'static str>,
+    next_id: usize,
+}
+
+impl Xml {
+    /// A new id for an element, unique within the document
+    fn id(&mut self) -> String {
+        self.next_id += 1;
+        format!("id{}", self.next_id - 1)
+    }
+
+    fn open(&mut self, tag: &'static str, attributes: &[(&str, String)]) {
+        self.start(tag, attributes);
+        self.out.push_str(">\n");
+        self.open.push(tag);
+    }
+
+    fn empty(&mut self, tag: &str, attributes: &[(&str, String)]) {
+        self.start(tag, attributes);
+        self.out.push_str("/>\n");
+    }
+
+    fn text(&mut self, tag: &str, text: &str) {
+        self.start(tag, &[]);
+        let _ = writeln!(self.out, ">{}</{}>", escape(text), tag);
+    }
+
+    fn close(&mut self) {
+        let tag = self.open.pop().expect("no element to close");
+        self.indent();
+        let _ = writeln!(self.out, "</{}>", tag);
+    }
+
+    fn start(&mut self, tag: &str, attributes: &[(&str, String)]) {
+        self.indent();
+        self.out.push('<');
+        self.out.push_str(tag);
+        for (name, value) in attributes {
+            let _ = write!(self.out, " {}=\"{}\"", name, escape(value));
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.open.len() {
+            self.out.push_str("  ");
+        }
+    }
+
+    fn finish(self) -> String {
+        debug_assert!(self.open.is_empty());
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n{}",
+            self.out
+        )
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TempoChange;
+    use daw_transport::{ClipId, ClipSource, IdAllocator, Note, TrackId, WaveformData};
+    use std::io::Read;
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    fn clip(name: &str, start_tick: u64, end_tick: u64, content: ClipContent) -> Clip {
+        Clip {
+            id: ClipId(0),
+            start_tick,
+            end_tick,
+            content,
+            waveform: Arc::new(WaveformData::from_audio_arc(
+                &AudioArc::new(vec![], 48000, 1),
+                512,
+            )),
+            audio_offset: 0,
+            name: name.to_string(),
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: ClipSource::default(),
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(r#"Drums & "Bass" <1>"#),
+            "Drums &amp; &quot;Bass&quot; &lt;1&gt;"
+        );
+    }
+
+    #[test]
+    fn test_arrangement_is_exported_with_its_audio() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("kick.wav"), b"RIFF kick").unwrap();
+
+        let mut ids = IdAllocator::default();
+        let mut folder = Track::new(TrackId(0), "Band".to_string());
+        folder.is_folder = true;
+        let mut drums = Track::new(TrackId(1), "Drums & Perc".to_string());
+        drums.parent = Some(TrackId(0));
+        drums.pan = -1.0;
+        drums.color = Some([255, 0, 16]);
+        // Half a second of stereo audio, trimmed by a quarter second
+        let mut kick = clip(
+            "kick",
+            PPQN,
+            2 * PPQN,
+            ClipContent::Audio(AudioArc::new(vec![0.0; 48000], 48000, 2)),
+        );
+        kick.audio_offset = 12000;
+        kick.fade_in_ticks = PPQN / 2;
+        drums.insert_clip(kick, &mut ids);
+        let mut keys = Track::new(TrackId(2), "Keys".to_string());
+        keys.enabled = false;
+        let note = Note {
+            start_tick: 0,
+            duration_ticks: PPQN,
+            pitch: 60,
+            velocity: 127,
+        };
+        keys.insert_clip(
+            clip(
+                "chords",
+                0,
+                4 * PPQN,
+                ClipContent::Midi(NoteList::new(vec![note])),
+            ),
+            &mut ids,
+        );
+
+        let tempo_map = TempoMap {
+            tempo_changes: vec![TempoChange {
+                tick: 8 * PPQN,
+                bpm: 90.0,
+            }],
+            time_signature_changes: vec![],
+        };
+        let sample_refs = HashMap::from([(
+            "kick".to_string(),
+            SampleRef::ProjectRelative("kick.wav".into()),
+        )]);
+        let path = dir.path().join("song.dawproject");
+        export_dawproject(
+            &path,
+            "Song",
+            &ProjectInfo {
+                author: "Korbin".to_string(),
+                ..Default::default()
+            },
+            120.0,
+            (4, 4),
+            &tempo_map,
+            &BTreeMap::from([(1, 4 * PPQN)]),
+            &[folder, drums, keys],
+            &sample_refs,
+            &PathContext::from_project_path(&dir.path().join("song.dawproj")),
+        )
+        .unwrap();
+
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(names, ["audio/kick.wav", "metadata.xml", "project.xml"]);
+        let mut read = |name: &str| {
+            let mut text = String::new();
+            zip.by_name(name)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap();
+            text
+        };
+        assert!(read("metadata.xml").contains("<Artist>Korbin</Artist>"));
+        let project = read("project.xml");
+        assert!(project.starts_with("<?xml"));
+
+        // The drums sit in the folder and send to its channel
+        let folder_at = project.find(r#"contentType="tracks""#).unwrap();
+        let drums_at = project
+            .find(r##"name="Drums &amp; Perc" color="#ff0010""##)
+            .unwrap();
+        let master_at = project.find(r#"name="Master""#).unwrap();
+        assert!(folder_at < drums_at && drums_at < master_at);
+        assert!(project.contains(r#"role="submix""#));
+        assert!(project.contains(r#"unit="normalized" value="0""#));
+        assert!(project.contains(r#"<Mute value="true""#));
+
+        // Positions in beats; the trim is a quarter second at 120 BPM
+        assert!(project.contains(
+            r#"<Clip time="1" duration="1" name="kick" fadeTimeUnit="beats" fadeInTime="0.5" fadeOutTime="0" playStart="0.5">"#
+        ));
+        assert!(project.contains(r#"<File path="audio/kick.wav"/>"#));
+        assert!(project.contains(r#"<Warp time="1" contentTime="0.5"/>"#));
+        assert!(project.contains(r#"<Note time="0" duration="1" channel="0" key="60" vel="1""#));
+        assert!(project.contains(r#"<RealPoint time="8" value="90" interpolation="hold"/>"#));
+        assert!(project.contains(r#"<Marker time="4" name="Locator 1"/>"#));
+    }
+}
@@ -0,0 +1,680 @@
+//! Differences between two versions of a project.
+//!
+//! [`diff`] compares two saved projects and lists what changed from one to the
+//! other, for telling the user what an autosave holds that the project file
+//! doesn't, or for showing a project's history. Tracks and clips are matched by
+//! id, so a renamed track or a clip dragged to another track shows up as a change
+//! rather than as a removal and an addition. Clips saved before clips had ids are
+//! matched by track, name and position instead.
+//!
+//! Values are compared as they're saved and reported as text, ready to show. The
+//! save timestamps, the next free ids and the cursor and zoom saved with the
+//! project change without the arrangement changing, so they're left out.
+
+use crate::{ClipData, Project, TempoMap, TrackData};
+use daw_dsp::{Effect, LimiterSettings};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Display;
+
+/// A change from one version of a project to another, as found by [`diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProjectChange {
+    /// A project-wide setting changed, such as `tempo` or `name`
+    Setting(SettingChange),
+    /// A numbered locator was set, moved or cleared
+    Locator {
+        number: u8,
+        from: Option<u64>,
+        to: Option<u64>,
+    },
+    TrackAdded {
+        track_id: u64,
+        name: String,
+    },
+    TrackRemoved {
+        track_id: u64,
+        name: String,
+    },
+    /// A track moved to another place in the track list
+    TrackMoved {
+        track_id: u64,
+        from: usize,
+        to: usize,
+    },
+    /// One of a track's settings changed, such as `volume` or `name`
+    TrackSetting {
+        track_id: u64,
+        change: SettingChange,
+    },
+    ClipAdded {
+        track_id: u64,
+        clip: ClipKey,
+    },
+    ClipRemoved {
+        track_id: u64,
+        clip: ClipKey,
+    },
+    /// A clip moved in time, to another track, or both
+    ClipMoved {
+        clip: ClipKey,
+        from_track: u64,
+        to_track: u64,
+        from_tick: u64,
+        to_tick: u64,
+    },
+    /// One of a clip's settings changed, such as `gain` or `length`
+    ClipSetting {
+        track_id: u64,
+        clip: ClipKey,
+        change: SettingChange,
+    },
+}
+
+/// A setting with different values in the two versions
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingChange {
+    pub name: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+/// The clip a change is about
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipKey {
+    /// `None` for clips saved before clips had ids
+    pub id: Option<u64>,
+    /// The clip's name in the newer version, or the older one if it was removed
+    pub name: String,
+}
+
+/// List the changes that turn project `a` into project `b`.
+///
+/// Project settings come first, then tracks in `b`'s order (removed ones last),
+/// each followed by changes to its clips.
+pub fn diff(a: &Project, b: &Project) -> Vec<ProjectChange> {
+    let mut changes: Vec<ProjectChange> = [
+        setting("name", &a.name, &b.name),
+        setting("tempo", a.tempo, b.tempo),
+        setting(
+            "time_signature",
+            time_signature(a.time_signature),
+            time_signature(b.time_signature),
+        ),
+        setting(
+            "tempo_map",
+            tempo_map(&a.tempo_map),
+            tempo_map(&b.tempo_map),
+        ),
+        setting(
+            "sample_rate",
+            optional(a.sample_rate),
+            optional(b.sample_rate),
+        ),
+        setting(
+            "end_behavior",
+            format!("{:?}", a.end_behavior),
+            format!("{:?}", b.end_behavior),
+        ),
+        setting("author", &a.info.author, &b.info.author),
+        setting("notes", &a.info.notes, &b.info.notes),
+        setting("key", optional(a.info.key), optional(b.info.key)),
+        setting("master_gain_db", a.master.gain_db, b.master.gain_db),
+        setting("master_pan", a.master.pan, b.master.pan),
+        setting(
+            "master_limiter",
+            limiter(a.master.limiter),
+            limiter(b.master.limiter),
+        ),
+        setting(
+            "master_effects",
+            effects(&a.master.effects),
+            effects(&b.master.effects),
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .map(ProjectChange::Setting)
+    .collect();
+
+    let numbers: BTreeSet<u8> = a
+        .locators
+        .keys()
+        .chain(b.locators.keys())
+        .copied()
+        .collect();
+    for number in numbers {
+        let (from, to) = (a.locators.get(&number), b.locators.get(&number));
+        if from != to {
+            changes.push(ProjectChange::Locator {
+                number,
+                from: from.copied(),
+                to: to.copied(),
+            });
+        }
+    }
+
+    diff_tracks(a, b, &mut changes);
+    changes
+}
+
+fn diff_tracks(a: &Project, b: &Project, changes: &mut Vec<ProjectChange>) {
+    let old_tracks: HashMap<u64, (usize, &TrackData)> = a
+        .tracks
+        .iter()
+        .enumerate()
+        .map(|(index, track)| (track.id, (index, track)))
+        .collect();
+    let new_ids: BTreeSet<u64> = b.tracks.iter().map(|track| track.id).collect();
+
+    // Tracks outside the longest run kept in the same order are the ones that moved
+    let kept_old: Vec<u64> = a
+        .tracks
+        .iter()
+        .map(|track| track.id)
+        .filter(|id| new_ids.contains(id))
+        .collect();
+    let kept_new: Vec<u64> = b
+        .tracks
+        .iter()
+        .map(|track| track.id)
+        .filter(|id| old_tracks.contains_key(id))
+        .collect();
+    let in_order = longest_common_subsequence(&kept_old, &kept_new);
+
+    let old_clips = clips_by_key(a);
+    let new_clips = clips_by_key(b);
+
+    for (index, new) in b.tracks.iter().enumerate() {
+        match old_tracks.get(&new.id) {
+            None => changes.push(ProjectChange::TrackAdded {
+                track_id: new.id,
+                name: new.name.clone(),
+            }),
+            Some(&(old_index, old)) => {
+                if !in_order.contains(&new.id) {
+                    changes.push(ProjectChange::TrackMoved {
+                        track_id: new.id,
+                        from: old_index,
+                        to: index,
+                    });
+                }
+                changes.extend(
+                    track_settings(old, new)
+                        .into_iter()
+                        .flatten()
+                        .map(|change| ProjectChange::TrackSetting {
+                            track_id: new.id,
+                            change,
+                        }),
+                );
+            }
+        }
+
+        for clip in &new.clips {
+            let key = clip_key(new.id, clip);
+            let label = ClipKey {
+                id: clip.id,
+                name: clip.name.clone(),
+            };
+            let Some(&(old_track, old)) = old_clips.get(&key) else {
+                changes.push(ProjectChange::ClipAdded {
+                    track_id: new.id,
+                    clip: label,
+                });
+                continue;
+            };
+            if old_track != new.id || old.start_tick != clip.start_tick {
+                changes.push(ProjectChange::ClipMoved {
+                    clip: label.clone(),
+                    from_track: old_track,
+                    to_track: new.id,
+                    from_tick: old.start_tick,
+                    to_tick: clip.start_tick,
+                });
+            }
+            changes.extend(
+                clip_settings(old, clip)
+                    .into_iter()
+                    .flatten()
+                    .map(|change| ProjectChange::ClipSetting {
+                        track_id: new.id,
+                        clip: label.clone(),
+                        change,
+                    }),
+            );
+        }
+    }
+
+    for old in &a.tracks {
+        for clip in &old.clips {
+            if !new_clips.contains_key(&clip_key(old.id, clip)) {
+                changes.push(ProjectChange::ClipRemoved {
+                    track_id: old.id,
+                    clip: ClipKey {
+                        id: clip.id,
+                        name: clip.name.clone(),
+                    },
+                });
+            }
+        }
+    }
+    for old in &a.tracks {
+        if !new_ids.contains(&old.id) {
+            changes.push(ProjectChange::TrackRemoved {
+                track_id: old.id,
+                name: old.name.clone(),
+            });
+        }
+    }
+}
+
+/// How a clip is matched between versions: by id, or by track, name and position
+/// for clips without one
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum MatchKey {
+    Id(u64),
+    Position(u64, String, u64),
+}
+
+fn clip_key(track_id: u64, clip: &ClipData) -> MatchKey {
+    match clip.id {
+        Some(id) => MatchKey::Id(id),
+        None => MatchKey::Position(track_id, clip.name.clone(), clip.start_tick),
+    }
+}
+
+/// Every clip in the project with the id of the track it's on
+fn clips_by_key(project: &Project) -> HashMap<MatchKey, (u64, &ClipData)> {
+    project
+        .tracks
+        .iter()
+        .flat_map(|track| {
+            track
+                .clips
+                .iter()
+                .map(move |clip| (clip_key(track.id, clip), (track.id, clip)))
+        })
+        .collect()
+}
+
+fn track_settings(a: &TrackData, b: &TrackData) -> [Option<SettingChange>; 13] {
+    [
+        setting("name", &a.name, &b.name),
+        setting("volume", a.volume, b.volume),
+        setting("pan", a.pan, b.pan),
+        setting("enabled", a.enabled, b.enabled),
+        setting("solo", a.solo, b.solo),
+        setting("color", color(a.color), color(b.color)),
+        setting("folder", a.is_folder, b.is_folder),
+        setting("parent", optional(a.parent), optional(b.parent)),
+        setting(
+            "metadata",
+            format!("{:?}", a.metadata),
+            format!("{:?}", b.metadata),
+        ),
+        (a.automation != b.automation)
+            .then(|| setting("automation", automation(a), automation(b)))
+            .flatten(),
+        setting("effects", effects(&a.effects), effects(&b.effects)),
+        setting("bus", a.is_bus, b.is_bus),
+        setting("sends", sends(a), sends(b)),
+    ]
+}
+
+fn clip_settings(a: &ClipData, b: &ClipData) -> [Option<SettingChange>; 9] {
+    [
+        setting("name", &a.name, &b.name),
+        setting(
+            "length",
+            a.end_tick.saturating_sub(a.start_tick),
+            b.end_tick.saturating_sub(b.start_tick),
+        ),
+        setting("sample", &a.sample_ref, &b.sample_ref),
+        setting("audio_offset", a.audio_offset, b.audio_offset),
+        setting("fade_in_ticks", a.fade_in_ticks, b.fade_in_ticks),
+        setting("fade_out_ticks", a.fade_out_ticks, b.fade_out_ticks),
+        setting("gain", a.gain, b.gain),
+        setting("looped", a.looped, b.looped),
+        setting("reversed", a.reversed, b.reversed),
+    ]
+}
+
+/// The change to `name`, if its values differ
+fn setting(name: &'static str, from: impl Display, to: impl Display) -> Option<SettingChange> {
+    let (from, to) = (from.to_string(), to.to_string());
+    (from != to).then_some(SettingChange { name, from, to })
+}
+
+fn optional(value: Option<impl Display>) -> String {
+    value.map_or_else(|| "none".to_string(), |value| value.to_string())
+}
+
+fn time_signature((numerator, denominator): (u32, u32)) -> String {
+    format!("{}/{}", numerator, denominator)
+}
+
+fn color(color: Option<[u8; 3]>) -> String {
+    optional(color.map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b)))
+}
+
+/// The automated parameters and how many points each has, like `volume (4 points)`
+fn automation(track: &TrackData) -> String {
+    let lanes: Vec<String> = track
+        .automation
+        .iter()
+        .map(|lane| format!("{} ({} points)", lane.parameter, lane.points.len()))
+        .collect();
+    if lanes.is_empty() {
+        "none".to_string()
+    } else {
+        lanes.join(", ")
+    }
+}
+
+/// The insert effects in order with their settings, like `delay (time_ms 250, ...)`
+fn effects(effects: &[Effect]) -> String {
+    let effects: Vec<String> = effects
+        .iter()
+        .map(|effect| {
+            let parameters: Vec<String> = effect
+                .parameters()
+                .iter()
+                .map(|parameter| format!("{} {}", parameter.name, parameter.value))
+                .collect();
+            format!("{} ({})", effect.kind(), parameters.join(", "))
+        })
+        .collect();
+    if effects.is_empty() {
+        "none".to_string()
+    } else {
+        effects.join(", ")
+    }
+}
+
+/// The buses fed and how, like `track 4 at 0.5 pre-fader`
+/// The limiter's ceiling and release, like `-1 dB, 100 ms`
+fn limiter(limiter: Option<LimiterSettings>) -> String {
+    optional(limiter.map(|limiter| format!("{} dB, {} ms", limiter.ceiling_db, limiter.release_ms)))
+}
+
+fn sends(track: &TrackData) -> String {
+    let sends: Vec<String> = track
+        .sends
+        .iter()
+        .map(|send| {
+            let tap = if send.pre_fader { " pre-fader" } else { "" };
+            format!("track {} at {}{}", send.bus, send.level, tap)
+        })
+        .collect();
+    if sends.is_empty() {
+        "none".to_string()
+    } else {
+        sends.join(", ")
+    }
+}
+
+fn tempo_map(map: &TempoMap) -> String {
+    let tempos = map
+        .tempo_changes
+        .iter()
+        .map(|change| format!("{} BPM at {}", change.bpm, change.tick));
+    let time_signatures = map.time_signature_changes.iter().map(|change| {
+        format!(
+            "{} at {}",
+            time_signature(change.time_signature),
+            change.tick
+        )
+    });
+    let changes: Vec<String> = tempos.chain(time_signatures).collect();
+    if changes.is_empty() {
+        "none".to_string()
+    } else {
+        changes.join(", ")
+    }
+}
+
+/// The items of the longest sequence found in both `a` and `b` in the same order
+fn longest_common_subsequence(a: &[u64], b: &[u64]) -> BTreeSet<u64> {
+    // lengths[i][j] is the length for a[i..] and b[j..]
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = if a[i] == b[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut common = BTreeSet::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            common.insert(a[i]);
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MasterData, SampleRef, TempoChange};
+    use std::path::PathBuf;
+
+    fn clip(id: u64, name: &str, start_tick: u64) -> ClipData {
+        ClipData {
+            id: Some(id),
+            start_tick,
+            end_tick: start_tick + 960,
+            sample_ref: SampleRef::ProjectRelative(PathBuf::from(format!("{}.wav", name))),
+            audio_offset: 0,
+            name: name.to_string(),
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: Default::default(),
+        }
+    }
+
+    fn track(id: u64, name: &str, clips: Vec<ClipData>) -> TrackData {
+        TrackData {
+            id,
+            name: name.to_string(),
+            clips,
+            volume: 1.0,
+            pan: 0.0,
+            enabled: true,
+            solo: false,
+            color: None,
+            metadata: Default::default(),
+            parent: None,
+            is_folder: false,
+            automation: Vec::new(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+        }
+    }
+
+    fn project(tracks: Vec<TrackData>) -> Project {
+        Project {
+            name: "Song".to_string(),
+            info: Default::default(),
+            sample_rate: None,
+            tempo: 120.0,
+            time_signature: (4, 4),
+            tempo_map: Default::default(),
+            tracks,
+            next_track_id: 0,
+            next_clip_id: 0,
+            end_behavior: Default::default(),
+            locators: Default::default(),
+            embedded: Default::default(),
+            session_state: None,
+            master: MasterData::default(),
+        }
+    }
+
+    fn key(id: u64, name: &str) -> ClipKey {
+        ClipKey {
+            id: Some(id),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_identical_projects_have_no_changes() {
+        let a = project(vec![track(0, "Drums", vec![clip(0, "kick", 0)])]);
+        let mut b = a.clone();
+        // Saving again only moves things that aren't part of the arrangement
+        b.info.modified = Some(1_700_000_000);
+        b.next_clip_id = 5;
+        assert!(diff(&a, &b).is_empty());
+    }
+
+    #[test]
+    fn test_project_settings_and_locators() {
+        let a = project(vec![]);
+        let mut b = a.clone();
+        b.tempo = 96.0;
+        b.time_signature = (6, 8);
+        b.tempo_map.tempo_changes.push(TempoChange {
+            tick: 3840,
+            bpm: 140.0,
+        });
+        b.locators.insert(2, 1920);
+        b.master.gain_db = -3.0;
+
+        let setting = |name, from: &str, to: &str| {
+            ProjectChange::Setting(SettingChange {
+                name,
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+        };
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                setting("tempo", "120", "96"),
+                setting("time_signature", "4/4", "6/8"),
+                setting("tempo_map", "none", "140 BPM at 3840"),
+                setting("master_gain_db", "0", "-3"),
+                ProjectChange::Locator {
+                    number: 2,
+                    from: None,
+                    to: Some(1920),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_track_and_clip_changes() {
+        let a = project(vec![
+            track(0, "Drums", vec![clip(0, "kick", 0), clip(1, "snare", 960)]),
+            track(1, "Bass", vec![clip(2, "bass", 0)]),
+            track(2, "Old", vec![]),
+        ]);
+        let mut drums = track(0, "Drums", vec![clip(1, "snare", 960)]);
+        drums.volume = 0.5;
+        let mut bass = clip(2, "bass", 0);
+        bass.gain = 0.5;
+        let mut b = project(vec![
+            track(3, "Keys", vec![]),
+            track(1, "Bass", vec![bass]),
+            drums,
+        ]);
+        // The kick was dragged over to the bass track and a bar later
+        b.tracks[1].clips.push(clip(0, "kick", 3840));
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                ProjectChange::TrackAdded {
+                    track_id: 3,
+                    name: "Keys".to_string(),
+                },
+                ProjectChange::ClipSetting {
+                    track_id: 1,
+                    clip: key(2, "bass"),
+                    change: SettingChange {
+                        name: "gain",
+                        from: "1".to_string(),
+                        to: "0.5".to_string(),
+                    },
+                },
+                ProjectChange::ClipMoved {
+                    clip: key(0, "kick"),
+                    from_track: 0,
+                    to_track: 1,
+                    from_tick: 0,
+                    to_tick: 3840,
+                },
+                ProjectChange::TrackMoved {
+                    track_id: 0,
+                    from: 0,
+                    to: 2,
+                },
+                ProjectChange::TrackSetting {
+                    track_id: 0,
+                    change: SettingChange {
+                        name: "volume",
+                        from: "1".to_string(),
+                        to: "0.5".to_string(),
+                    },
+                },
+                ProjectChange::TrackRemoved {
+                    track_id: 2,
+                    name: "Old".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_clips_without_ids_match_by_position() {
+        let unidentified = |start_tick| ClipData {
+            id: None,
+            ..clip(0, "kick", start_tick)
+        };
+        let a = project(vec![track(0, "Drums", vec![unidentified(0)])]);
+        let b = project(vec![track(0, "Drums", vec![unidentified(960)])]);
+        let kick = ClipKey {
+            id: None,
+            name: "kick".to_string(),
+        };
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                ProjectChange::ClipAdded {
+                    track_id: 0,
+                    clip: kick.clone(),
+                },
+                ProjectChange::ClipRemoved {
+                    track_id: 0,
+                    clip: kick,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_longest_common_subsequence() {
+        assert_eq!(
+            longest_common_subsequence(&[1, 2, 3, 4], &[2, 3, 4, 1]),
+            BTreeSet::from([2, 3, 4])
+        );
+        assert!(longest_common_subsequence(&[], &[1]).is_empty());
+    }
+}
@@ -0,0 +1,321 @@
+//! Samples stored inside the project file.
+//!
+//! Small sketches are easiest to pass around as one file. With samples embedded,
+//! the project keeps each sample file's bytes, in whatever format it was (so a
+//! FLAC stays compressed), and clips refer to them with [`SampleRef::Embedded`].
+//! Decoding works on files, so loading writes the embedded samples out to a
+//! folder in the user's cache directory first, named after their contents so
+//! opening the same project again reuses it, and resolves the references there.
+//! Folders no project has been loaded from for a month are deleted.
+
+use crate::{PathContext, ProjectError, SampleRef};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Folder in the user cache directory that embedded samples are written out to
+const EMBEDDED_DIR: &str = "embedded";
+
+/// File in each folder of written out samples, touched whenever it's used
+const LAST_USED: &str = ".last-used";
+
+/// How long a folder of written out samples is kept after it was last used
+const KEEP_UNUSED: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// The bytes of a sample file stored in a project, kept as base64 in JSON files
+/// and as raw bytes in binary ones.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EmbeddedAudio(pub Vec<u8>);
+
+impl Serialize for EmbeddedAudio {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&STANDARD.encode(&self.0))
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EmbeddedAudio {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(EmbeddedAudioVisitor)
+    }
+}
+
+struct EmbeddedAudioVisitor;
+
+impl<'de> Visitor<'de> for EmbeddedAudioVisitor {
+    type Value = EmbeddedAudio;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("base64 text or bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, encoded: &str) -> Result<Self::Value, E> {
+        STANDARD
+            .decode(encoded)
+            .map(EmbeddedAudio)
+            .map_err(de::Error::custom)
+    }
+
+    fn visit_bytes<E: de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+        Ok(EmbeddedAudio(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, bytes: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(EmbeddedAudio(bytes))
+    }
+}
+
+/// Read the files behind `sample_refs` into a set of embedded samples, rewriting
+/// the references to [`SampleRef::Embedded`].
+///
+/// References are resolved with `ctx`. Each file is embedded once however many
+/// clips use it, under its file name; a different file with the same name gets a
+/// numbered one (`kick-2.wav`). Samples that can't be found keep their references.
+pub fn embed_samples(
+    sample_refs: &mut HashMap<String, SampleRef>,
+    ctx: &PathContext,
+) -> Result<BTreeMap<String, EmbeddedAudio>, ProjectError> {
+    let mut embedded: BTreeMap<String, EmbeddedAudio> = BTreeMap::new();
+    let mut names_by_path: HashMap<PathBuf, String> = HashMap::new();
+
+    // In name order, so which of two same-named files gets the plain name is stable
+    let mut clip_names: Vec<String> = sample_refs.keys().cloned().collect();
+    clip_names.sort();
+    for clip_name in clip_names {
+        let Some(path) = ctx.resolve(&sample_refs[&clip_name]) else {
+            continue;
+        };
+        let name = match names_by_path.get(&path) {
+            Some(name) => name.clone(),
+            None => {
+                let audio = EmbeddedAudio(std::fs::read(&path)?);
+                let name = free_name(&path, &audio, &embedded);
+                embedded.insert(name.clone(), audio);
+                names_by_path.insert(path, name.clone());
+                name
+            }
+        };
+        sample_refs.insert(clip_name, SampleRef::Embedded(PathBuf::from(name)));
+    }
+    Ok(embedded)
+}
+
+/// Name to embed the file at `path` under: its own, unless a different file
+/// already took it
+fn free_name(
+    path: &Path,
+    audio: &EmbeddedAudio,
+    taken: &BTreeMap<String, EmbeddedAudio>,
+) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "sample".to_string());
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|number| match number {
+            1 => format!("{}{}", stem, extension),
+            n => format!("{}-{}{}", stem, n, extension),
+        })
+        .find(|name| taken.get(name).is_none_or(|existing| existing == audio))
+        .expect("ran out of file numbers")
+}
+
+/// Write `embedded` out to a folder in the user cache directory and return the
+/// folder.
+///
+/// The folder is named after the samples' contents, so files already written by an
+/// earlier load of the same samples are reused once their contents check out.
+pub(crate) fn write_embedded(
+    embedded: &BTreeMap<String, EmbeddedAudio>,
+) -> Result<PathBuf, ProjectError> {
+    let root = daw_decode::user_cache_dir()
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no user cache directory to write embedded samples to",
+            )
+        })?
+        .join(EMBEDDED_DIR);
+    write_embedded_in(&root, embedded)
+}
+
+/// [`write_embedded`] into a folder of `root`, deleting its folders that haven't
+/// been used for a while
+fn write_embedded_in(
+    root: &Path,
+    embedded: &BTreeMap<String, EmbeddedAudio>,
+) -> Result<PathBuf, ProjectError> {
+    let dir = root.join(format!("{:016x}", hash_of(embedded)));
+    std::fs::create_dir_all(&dir)?;
+
+    for (name, audio) in embedded {
+        // Names come from the project file, so keep them inside the folder
+        let Some(file_name) = Path::new(name).file_name() else {
+            continue;
+        };
+        let path = dir.join(file_name);
+        if std::fs::read(&path).is_ok_and(|bytes| hash_of(&bytes) == hash_of(&audio.0)) {
+            continue;
+        }
+        // Written whole to a file of our own and renamed, so a concurrent load never
+        // decodes half a file and nothing already there is written through
+        let partial = dir.join(format!(".{}.partial", file_name.to_string_lossy()));
+        match std::fs::remove_file(&partial) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+            _ => {}
+        }
+        let mut file = std::fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(&partial)?;
+        file.write_all(&audio.0)?;
+        drop(file);
+        std::fs::rename(&partial, &path)?;
+    }
+
+    std::fs::write(dir.join(LAST_USED), [])?;
+    remove_unused(root);
+    Ok(dir)
+}
+
+/// Delete the folders in `root` whose samples haven't been used for
+/// [`KEEP_UNUSED`]; ones that can't be checked or deleted are left
+fn remove_unused(root: &Path) {
+    let Ok(folders) = std::fs::read_dir(root) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for folder in folders.filter_map(Result::ok) {
+        if !folder.file_type().is_ok_and(|kind| kind.is_dir()) {
+            continue;
+        }
+        // A folder another load is still filling has no marker yet, but is new
+        let last_used = std::fs::metadata(folder.path().join(LAST_USED))
+            .or_else(|_| folder.metadata())
+            .and_then(|meta| meta.modified())
+            .unwrap_or(now);
+        if now.duration_since(last_used).unwrap_or_default() > KEEP_UNUSED {
+            let _ = std::fs::remove_dir_all(folder.path());
+        }
+    }
+}
+
+fn hash_of(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_samples_are_embedded_once_by_name() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a")).unwrap();
+        std::fs::create_dir_all(dir.path().join("b")).unwrap();
+        std::fs::write(dir.path().join("a/kick.wav"), b"first kick").unwrap();
+        std::fs::write(dir.path().join("b/kick.wav"), b"second kick").unwrap();
+
+        let project = |path: &str| SampleRef::ProjectRelative(path.into());
+        let mut sample_refs = HashMap::from([
+            ("one".to_string(), project("a/kick.wav")),
+            ("two".to_string(), project("a/kick.wav")),
+            ("three".to_string(), project("b/kick.wav")),
+            ("four".to_string(), project("missing.wav")),
+        ]);
+        let ctx = PathContext::from_project_path(&dir.path().join("song.dawproj"));
+        let embedded = embed_samples(&mut sample_refs, &ctx).unwrap();
+
+        assert_eq!(embedded.len(), 2);
+        assert_eq!(embedded["kick.wav"].0, b"first kick");
+        assert_eq!(embedded["kick-2.wav"].0, b"second kick");
+        assert_eq!(sample_refs["one"], SampleRef::Embedded("kick.wav".into()));
+        assert_eq!(sample_refs["two"], SampleRef::Embedded("kick.wav".into()));
+        assert_eq!(
+            sample_refs["three"],
+            SampleRef::Embedded("kick-2.wav".into())
+        );
+        assert_eq!(sample_refs["four"], project("missing.wav"));
+    }
+
+    #[test]
+    fn test_embedded_samples_are_written_out_and_resolved() {
+        let embedded = BTreeMap::from([(
+            "kick.wav".to_string(),
+            EmbeddedAudio(b"embedded test kick".to_vec()),
+        )]);
+        let json = serde_json::to_string(&embedded).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BTreeMap<String, EmbeddedAudio>>(&json).unwrap(),
+            embedded
+        );
+        // Stored as raw bytes in MessagePack rather than base64
+        let packed = rmp_serde::to_vec_named(&embedded).unwrap();
+        assert!(packed.len() < json.len());
+        assert_eq!(
+            rmp_serde::from_slice::<BTreeMap<String, EmbeddedAudio>>(&packed).unwrap(),
+            embedded
+        );
+
+        let root = tempdir().unwrap();
+        let dir = write_embedded_in(root.path(), &embedded).unwrap();
+        assert_eq!(write_embedded_in(root.path(), &embedded).unwrap(), dir);
+        let ctx = PathContext {
+            embedded_dir: Some(dir.clone()),
+            ..PathContext::from_project_path(Path::new("song.dawproj"))
+        };
+        let path = ctx
+            .resolve(&SampleRef::Embedded("kick.wav".into()))
+            .unwrap();
+        assert_eq!(path, dir.join("kick.wav"));
+        assert_eq!(std::fs::read(path).unwrap(), b"embedded test kick");
+    }
+
+    #[test]
+    fn test_written_out_samples_are_checked_and_old_folders_removed() {
+        let root = tempdir().unwrap();
+        let embedded = BTreeMap::from([(
+            "kick.wav".to_string(),
+            EmbeddedAudio(b"the real kick".to_vec()),
+        )]);
+        let dir = write_embedded_in(root.path(), &embedded).unwrap();
+
+        // A different file of the same length is replaced, not served
+        std::fs::write(dir.join("kick.wav"), b"not the kick!").unwrap();
+        write_embedded_in(root.path(), &embedded).unwrap();
+        assert_eq!(
+            std::fs::read(dir.join("kick.wav")).unwrap(),
+            b"the real kick"
+        );
+
+        // Another project's samples, last used long ago, are deleted
+        let other = BTreeMap::from([("snare.wav".to_string(), EmbeddedAudio(b"snare".to_vec()))]);
+        let old = write_embedded_in(root.path(), &other).unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(old.join(LAST_USED))
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH)
+            .unwrap();
+        write_embedded_in(root.path(), &embedded).unwrap();
+        assert!(!old.exists());
+        assert!(dir.join("kick.wav").exists());
+    }
+}
@@ -1,14 +1,39 @@
+mod autosave;
+mod collect;
+mod container;
+#[cfg(feature = "dawproject")]
+mod dawproject;
+mod diff;
+mod embed;
 mod load;
+mod midi_file;
+mod peaks;
+mod relink;
 mod save;
+mod validate;
 
+use daw_dsp::{Effect, LimiterSettings};
+use daw_transport::{AutomationLane, ClipSource, MusicalKey};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
+pub use autosave::{autosave_path, discard_autosave, stale_autosave, write_autosave};
+pub use collect::{CollectReport, collect_samples, rebase_samples};
+#[cfg(feature = "dawproject")]
+pub use dawproject::export_dawproject;
+pub use diff::{ClipKey, ProjectChange, SettingChange, diff};
+pub use embed::{EmbeddedAudio, embed_samples};
 pub use load::{
-    LoadedProject, OfflineClip, ProjectMetadata, load_project, load_project_metadata,
-    load_project_with_sample_rate,
+    DamagedSample, LoadedProject, OfflineClip, ProjectMetadata, load_project,
+    load_project_metadata, load_project_with_cache, load_project_with_progress,
+    load_project_with_sample_rate, read_project,
 };
-pub use save::save_project;
+pub use midi_file::export_midi;
+pub use peaks::{PeakCache, peaks_path};
+pub use relink::find_missing_samples;
+pub use save::{ProjectFormat, SaveOptions, backup_path, save_project};
+pub use validate::{SampleIssue, ValidationReport, validate};
 
 /// A reference to an audio sample with explicit path semantics.
 ///
@@ -26,6 +51,18 @@ pub enum SampleRef {
     /// e.g., "audio/kick.wav" resolves to `{project_dir}/audio/kick.wav`
     #[serde(rename = "project")]
     ProjectRelative(PathBuf),
+
+    /// Name of a sample stored inside the project file.
+    /// e.g., "kick.wav" resolves to `{embedded_dir}/kick.wav` once loading has
+    /// written the project's embedded samples out
+    #[serde(rename = "embedded")]
+    Embedded(PathBuf),
+
+    /// Path inside one of the user's sample libraries, by the library's name.
+    /// e.g., root "Splice" and "kicks/909.wav" resolve to `{Splice folder}/kicks/909.wav`
+    /// on whichever machine has a library by that name
+    #[serde(rename = "library")]
+    Library { root_name: String, path: PathBuf },
 }
 
 impl SampleRef {
@@ -34,6 +71,8 @@ impl SampleRef {
         match self {
             SampleRef::DevRoot(p) => p,
             SampleRef::ProjectRelative(p) => p,
+            SampleRef::Embedded(p) => p,
+            SampleRef::Library { path, .. } => path,
         }
     }
 }
@@ -43,6 +82,28 @@ impl std::fmt::Display for SampleRef {
         match self {
             SampleRef::DevRoot(p) => write!(f, "dev_root:{}", p.display()),
             SampleRef::ProjectRelative(p) => write!(f, "project:{}", p.display()),
+            SampleRef::Embedded(p) => write!(f, "embedded:{}", p.display()),
+            SampleRef::Library { root_name, path } => {
+                write!(f, "library:{}/{}", root_name, path.display())
+            }
+        }
+    }
+}
+
+/// A sample library folder that [`SampleRef::Library`] references resolve in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SampleRoot {
+    /// Name projects refer to the library by, the same on every machine
+    pub name: String,
+    /// Where the library is on this machine
+    pub path: PathBuf,
+}
+
+impl SampleRoot {
+    pub fn new(name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            path: path.into(),
         }
     }
 }
@@ -59,6 +120,15 @@ pub struct PathContext {
     /// Optional dev/workspace root for resolving DevRoot samples.
     /// When set, DevRoot("cr78/kick.wav") resolves to `{dev_root}/samples/cr78/kick.wav`.
     pub dev_root: Option<PathBuf>,
+
+    /// Folder the project's embedded samples were written out to, if it has any.
+    /// Embedded("kick.wav") resolves to `{embedded_dir}/kick.wav`.
+    pub embedded_dir: Option<PathBuf>,
+
+    /// The user's sample libraries, searched in order for Library samples.
+    /// Library { root_name: "Splice", path: "kicks/909.wav" } resolves to
+    /// `{path of the "Splice" root}/kicks/909.wav`.
+    pub sample_roots: Vec<SampleRoot>,
 }
 
 impl PathContext {
@@ -73,6 +143,8 @@ impl PathContext {
                 .map(|p| p.to_path_buf())
                 .unwrap_or_default(),
             dev_root: None,
+            embedded_dir: None,
+            sample_roots: Vec::new(),
         }
     }
 
@@ -82,6 +154,12 @@ impl PathContext {
         self
     }
 
+    /// Add a sample library after the ones already searched.
+    pub fn with_sample_root(mut self, root: SampleRoot) -> Self {
+        self.sample_roots.push(root);
+        self
+    }
+
     /// Resolve a SampleRef to an absolute path.
     ///
     /// Returns `None` if the resolved path doesn't exist or if the required
@@ -105,16 +183,317 @@ impl PathContext {
                     None
                 }
             }
+            SampleRef::Embedded(name) => {
+                // Only ever a file directly in the folder, whatever the project says
+                let resolved = self.embedded_dir.as_ref()?.join(name.file_name()?);
+                if resolved.exists() {
+                    Some(resolved)
+                } else {
+                    None
+                }
+            }
+            SampleRef::Library { root_name, path } => {
+                // The named library first, then the others in case it goes by
+                // another name on this machine
+                let named = self.sample_roots.iter().filter(|r| r.name == *root_name);
+                let others = self.sample_roots.iter().filter(|r| r.name != *root_name);
+                named
+                    .chain(others)
+                    .map(|root| root.path.join(path))
+                    .find(|resolved| resolved.exists())
+            }
+        }
+    }
+
+    /// The reference to store for the file at `path`.
+    ///
+    /// Files inside the project directory, the dev samples directory or a sample
+    /// library get a reference relative to it, checked in that order. Anything else
+    /// is referenced by its full path, which a project reference resolves to as-is.
+    pub fn sample_ref_for(&self, path: &Path) -> SampleRef {
+        if let Ok(rel_path) = path.strip_prefix(&self.project_root) {
+            return SampleRef::ProjectRelative(rel_path.to_path_buf());
+        }
+        if let Some(dev_root) = &self.dev_root
+            && let Ok(rel_path) = path.strip_prefix(dev_root.join("samples"))
+        {
+            return SampleRef::DevRoot(rel_path.to_path_buf());
+        }
+        for root in &self.sample_roots {
+            if let Ok(rel_path) = path.strip_prefix(&root.path) {
+                return SampleRef::Library {
+                    root_name: root.name.clone(),
+                    path: rel_path.to_path_buf(),
+                };
+            }
         }
+        SampleRef::ProjectRelative(path.to_path_buf())
+    }
+}
+
+/// Folder in the project directory that recordings, bounces and collected samples
+/// are written to
+pub const AUDIO_DIR: &str = "audio";
+
+/// Write a recording into the project's [`AUDIO_DIR`] and cache it there.
+///
+/// Goes through [`daw_decode::AudioCache::ingest_recording`], so the file gets the
+/// next free `recording-NNN.wav` name and later loads of it come from `cache`.
+/// Returns the project-relative reference to store in clips playing it.
+pub fn ingest_recording(
+    cache: &mut daw_decode::AudioCache,
+    audio: &daw_transport::AudioArc,
+    ctx: &PathContext,
+) -> anyhow::Result<SampleRef> {
+    let path = cache.ingest_recording(audio, &ctx.project_root.join(AUDIO_DIR))?;
+    Ok(ctx.sample_ref_for(&path))
+}
+
+/// Write a bounced clip into the project's [`AUDIO_DIR`] and cache it there, like
+/// [`ingest_recording`] but as the next free `bounce-NNN.wav`.
+pub fn ingest_bounce(
+    cache: &mut daw_decode::AudioCache,
+    audio: &daw_transport::AudioArc,
+    ctx: &PathContext,
+) -> anyhow::Result<SampleRef> {
+    let path = cache.ingest_bounce(audio, &ctx.project_root.join(AUDIO_DIR))?;
+    Ok(ctx.sample_ref_for(&path))
+}
+
+/// What playback does when it reaches the end of the arrangement.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EndBehavior {
+    /// Keep playing into silence
+    #[default]
+    Continue,
+    /// Stop after the last clip
+    Stop,
+    /// Jump back to the start and keep playing
+    Loop,
+}
+
+impl EndBehavior {
+    fn is_continue(&self) -> bool {
+        *self == Self::Continue
+    }
+}
+
+/// Snap mode for cursor placement and editing operations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SnapMode {
+    /// No snapping - cursor placed exactly where clicked
+    None,
+    /// Snap to quarter note beats (PPQN)
+    Beat,
+    /// Snap to half beats (PPQN / 2)
+    HalfBeat,
+    /// Snap to quarter beats (PPQN / 4)
+    QuarterBeat,
+    /// Snap to bars (PPQN * beats_per_bar)
+    Bar,
+}
+
+/// Where the user left off, so opening the project again puts them back there.
+///
+/// Saved alongside the arrangement; projects without it open with the defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionState {
+    /// Edit cursor position in ticks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor_tick: Option<u64>,
+    pub snap_mode: SnapMode,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub metronome_enabled: bool,
+    /// Metronome volume (0.0 to 1.0)
+    pub metronome_volume: f32,
+    /// Horizontal zoom of the arrangement, in the frontend's own units
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zoom: Option<f32>,
+    /// Section playback cycles through, whether or not looping is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loop_region: Option<LoopRegion>,
+    /// Section recordings are kept to, whether or not punching in is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub punch_region: Option<PunchRegion>,
+    /// Bars of metronome played before playback starts
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub count_in_bars: u32,
+    /// Bars before the cursor that playback starts from
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub pre_roll_bars: u32,
+}
+
+/// A section of the arrangement that playback cycles through while looping is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoopRegion {
+    pub start_tick: u64,
+    pub end_tick: u64,
+    /// Whether playback is cycling through it; kept off so the region can be
+    /// turned back on where it was
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub enabled: bool,
+}
+
+/// A section of the arrangement that recordings replace, leaving the rest of each
+/// take out, while punching in is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PunchRegion {
+    pub start_tick: u64,
+    pub end_tick: u64,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub enabled: bool,
+}
+
+/// A change to a new tempo partway through the arrangement
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TempoChange {
+    pub tick: u64,
+    /// Beats per minute from `tick` on
+    pub bpm: f64,
+}
+
+/// A change to a new time signature partway through the arrangement
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSignatureChange {
+    pub tick: u64,
+    /// Time signature from `tick` on, as (beats per bar, beat unit)
+    pub time_signature: (u32, u32),
+}
+
+/// Tempo and time signature changes over the arrangement.
+///
+/// The project's `tempo` and `time_signature` hold the values it starts with, as
+/// in projects saved before tempo maps; these lists only hold the changes after
+/// that, in tick order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TempoMap {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tempo_changes: Vec<TempoChange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time_signature_changes: Vec<TimeSignatureChange>,
+}
+
+impl TempoMap {
+    pub fn is_empty(&self) -> bool {
+        self.tempo_changes.is_empty() && self.time_signature_changes.is_empty()
+    }
+
+    /// Tempo in effect at `tick`, for a project starting at `initial`
+    pub fn tempo_at(&self, initial: f64, tick: u64) -> f64 {
+        self.tempo_changes
+            .iter()
+            .take_while(|change| change.tick <= tick)
+            .last()
+            .map_or(initial, |change| change.bpm)
+    }
+
+    /// Time signature in effect at `tick`, for a project starting in `initial`
+    pub fn time_signature_at(&self, initial: (u32, u32), tick: u64) -> (u32, u32) {
+        self.time_signature_changes
+            .iter()
+            .take_while(|change| change.tick <= tick)
+            .last()
+            .map_or(initial, |change| change.time_signature)
+    }
+
+    /// Put the changes in tick order and drop ones that can't be played: tempos
+    /// that aren't positive and time signatures with a zero in them
+    pub fn normalize(&mut self) {
+        self.tempo_changes
+            .retain(|change| change.bpm.is_finite() && change.bpm > 0.0);
+        self.tempo_changes.sort_by_key(|change| change.tick);
+        self.time_signature_changes
+            .retain(|change| change.time_signature.0 > 0 && change.time_signature.1 > 0);
+        self.time_signature_changes
+            .sort_by_key(|change| change.tick);
+    }
+}
+
+/// Who made the project, notes about it and when it was made.
+///
+/// Projects without any of it load with it empty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProjectInfo {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub author: String,
+    /// Free-form description and notes
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub notes: String,
+    /// When the project was first saved, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<u64>,
+    /// When the project was last saved, in seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<u64>,
+    /// Key and scale the song is in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<MusicalKey>,
+}
+
+impl ProjectInfo {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub name: String,
+    /// Author, notes, timestamps and key
+    #[serde(default, skip_serializing_if = "ProjectInfo::is_empty")]
+    pub info: ProjectInfo,
     pub tempo: f64,
     pub time_signature: (u32, u32),
+    /// Tempo and time signature changes after the start
+    #[serde(default, skip_serializing_if = "TempoMap::is_empty")]
+    pub tempo_map: TempoMap,
+    /// Sample rate the project was made at, which playback and rendering prefer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
     pub tracks: Vec<TrackData>,
+    /// Id the next new track gets. Older projects without it start after the
+    /// highest id in use.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub next_track_id: u64,
+    /// Id the next new clip gets
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub next_clip_id: u64,
+    /// What playback does after the last clip
+    #[serde(default, skip_serializing_if = "EndBehavior::is_continue")]
+    pub end_behavior: EndBehavior,
+    /// Numbered locator positions in ticks, keyed by number (1-9)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub locators: BTreeMap<u8, u64>,
+    /// Sample files stored in the project, by the name `SampleRef::Embedded` uses
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub embedded: BTreeMap<String, EmbeddedAudio>,
+    /// Cursor, snapping and other state to restore when the project is opened
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_state: Option<SessionState>,
+    /// What the summed tracks go through on their way out
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub master: MasterData,
+}
+
+/// The master channel, which every track is summed into.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MasterData {
+    /// Gain on the whole mix in dB
+    pub gain_db: f32,
+    /// Balance from the left (-1.0) to the right (1.0)
+    pub pan: f32,
+    /// Peak limiter, last before the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limiter: Option<LimiterSettings>,
+    /// Insert effects, in the order the mix goes through them
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "known_effects"
+    )]
+    pub effects: Vec<Effect>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -126,16 +505,114 @@ pub struct TrackData {
     pub pan: f32,
     pub enabled: bool,
     pub solo: bool,
+    /// Display color as RGB
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[u8; 3]>,
+    /// Freeform user notes and metadata
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub metadata: BTreeMap<String, String>,
+    /// Id of the folder track this track is grouped under
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<u64>,
+    /// Whether this is a folder track
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_folder: bool,
+    /// Automation lanes, each a parameter id and its points
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub automation: Vec<AutomationLane>,
+    /// Insert effects, in the order audio goes through them
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "known_effects"
+    )]
+    pub effects: Vec<Effect>,
+    /// Whether this is an aux bus, which mixes what other tracks send it
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub is_bus: bool,
+    /// Buses this track feeds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sends: Vec<SendData>,
+}
+
+/// Audio a track feeds to a bus.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SendData {
+    /// Id of the bus track
+    pub bus: u64,
+    pub level: f32,
+    /// Whether the send is taken before the track's volume
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pre_fader: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClipData {
+    /// Clip id (None in projects saved before clips had ids)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u64>,
     pub start_tick: u64,
     pub end_tick: u64,
     /// Reference to the audio sample for this clip.
     pub sample_ref: SampleRef,
     pub audio_offset: u64,
     pub name: String,
+    /// Fade-in length in ticks
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub fade_in_ticks: u64,
+    /// Crossfade tail in ticks played past `end_tick`
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub fade_out_ticks: u64,
+    /// Linear clip gain on top of the track volume
+    #[serde(default = "unity_gain", skip_serializing_if = "is_unity_gain")]
+    pub gain: f32,
+    /// Whether the audio repeats for as long as the clip lasts
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub looped: bool,
+    /// Whether the audio plays backwards
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub reversed: bool,
+    /// Where the clip's audio was imported from
+    #[serde(default, skip_serializing_if = "ClipSource::is_empty")]
+    pub source: ClipSource,
+}
+
+fn is_zero<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Effects this version knows, leaving out ones added by a newer version so the
+/// rest of the project still opens
+fn known_effects<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Effect>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum MaybeEffect {
+        Known(Effect),
+        Unknown(serde::de::IgnoredAny),
+    }
+
+    let effects = Vec::<MaybeEffect>::deserialize(deserializer)?;
+    Ok(effects
+        .into_iter()
+        .filter_map(|effect| match effect {
+            MaybeEffect::Known(effect) => Some(effect),
+            MaybeEffect::Unknown(_) => None,
+        })
+        .collect())
+}
+
+fn unity_gain() -> f32 {
+    1.0
+}
+
+fn is_unity_gain(value: &f32) -> bool {
+    *value == 1.0
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -149,6 +626,12 @@ pub enum ProjectError {
     #[error("Deserialization error: {0}")]
     Deserialize(#[from] rmp_serde::decode::Error),
 
+    #[error("Encoding error: {0}")]
+    Encode(#[from] rmp_serde::encode::Error),
+
+    #[error("Zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
     #[error("Failed to decode audio file '{path}': {source}")]
     AudioDecode {
         path: PathBuf,
@@ -172,44 +655,111 @@ mod tests {
                     name: "Drums".to_string(),
                     clips: vec![
                         ClipData {
+                            id: None,
                             start_tick: 0,
                             end_tick: 960,
                             sample_ref: SampleRef::DevRoot(PathBuf::from("audio/kick.wav")),
                             audio_offset: 0,
                             name: "Kick".to_string(),
+                            fade_in_ticks: 0,
+                            fade_out_ticks: 0,
+                            gain: 1.0,
+                            looped: false,
+                            reversed: false,
+                            source: Default::default(),
                         },
                         ClipData {
+                            id: None,
                             start_tick: 960,
                             end_tick: 1920,
                             sample_ref: SampleRef::DevRoot(PathBuf::from("audio/snare.wav")),
                             audio_offset: 0,
                             name: "Snare".to_string(),
+                            fade_in_ticks: 0,
+                            fade_out_ticks: 0,
+                            gain: 1.0,
+                            looped: false,
+                            reversed: false,
+                            source: Default::default(),
                         },
                     ],
                     volume: 1.0,
                     pan: 0.0,
                     enabled: true,
                     solo: false,
+                    color: None,
+                    metadata: Default::default(),
+                    parent: None,
+                    is_folder: false,
+                    automation: Vec::new(),
+                    effects: Vec::new(),
+                    is_bus: false,
+                    sends: Vec::new(),
                 },
                 TrackData {
                     id: 2,
                     name: "Hi-Hats".to_string(),
                     clips: vec![ClipData {
+                        id: None,
                         start_tick: 480,
                         end_tick: 960,
                         sample_ref: SampleRef::DevRoot(PathBuf::from("audio/hihat.wav")),
                         audio_offset: 0,
                         name: "Hi-Hat".to_string(),
+                        fade_in_ticks: 0,
+                        fade_out_ticks: 0,
+                        gain: 1.0,
+                        looped: false,
+                        reversed: false,
+                        source: Default::default(),
                     }],
                     volume: 0.8,
                     pan: 0.0,
                     enabled: true,
                     solo: false,
+                    color: None,
+                    metadata: Default::default(),
+                    parent: None,
+                    is_folder: false,
+                    automation: Vec::new(),
+                    effects: Vec::new(),
+                    is_bus: false,
+                    sends: Vec::new(),
                 },
             ],
+            next_track_id: 0,
+            next_clip_id: 0,
+            end_behavior: EndBehavior::default(),
+            locators: Default::default(),
+            embedded: Default::default(),
+            session_state: None,
+            master: MasterData::default(),
+            tempo_map: Default::default(),
+            info: Default::default(),
+            sample_rate: None,
         }
     }
 
+    #[test]
+    fn test_ingested_recording_is_referenced_in_project_audio_folder() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let ctx = PathContext::from_project_path(&dir.path().join("song.dawproj"));
+        let mut cache = daw_decode::AudioCache::new();
+        let take = daw_transport::AudioArc::new(vec![0.25; 960], 48000, 2);
+
+        let sample_ref = ingest_recording(&mut cache, &take, &ctx).expect("ingest");
+        assert_eq!(
+            sample_ref,
+            SampleRef::ProjectRelative(PathBuf::from("audio/recording-001.wav"))
+        );
+        let path = ctx.resolve(&sample_ref).expect("written");
+        let audio = cache.get_or_load_direct(&path, None).expect("cached");
+        assert_eq!(audio.samples(), take.samples());
+
+        let next = ingest_recording(&mut cache, &take, &ctx).expect("ingest");
+        assert_eq!(next.path(), Path::new("audio/recording-002.wav"));
+    }
+
     #[test]
     fn test_project_serialization_roundtrip() {
         let project = sample_project();
@@ -229,16 +779,31 @@ mod tests {
             id: 42,
             name: "Test Track".to_string(),
             clips: vec![ClipData {
+                id: None,
                 start_tick: 1920,
                 end_tick: 2880,
                 sample_ref: SampleRef::DevRoot(PathBuf::from("samples/test.wav")),
                 audio_offset: 0,
                 name: "Test".to_string(),
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source: Default::default(),
             }],
             volume: 0.75,
             pan: 0.0,
             enabled: true,
             solo: false,
+            color: None,
+            metadata: Default::default(),
+            parent: None,
+            is_folder: false,
+            automation: Vec::new(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
         };
 
         let json = serde_json::to_string(&track).expect("serialize");
@@ -256,11 +821,18 @@ mod tests {
     #[test]
     fn test_clip_data_serialization() {
         let clip = ClipData {
+            id: None,
             start_tick: 4800,
             end_tick: 5760,
             sample_ref: SampleRef::ProjectRelative(PathBuf::from("audio/local.wav")),
             audio_offset: 0,
             name: "Audio".to_string(),
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: Default::default(),
         };
 
         let json = serde_json::to_string(&clip).expect("serialize");
@@ -269,6 +841,23 @@ mod tests {
         assert_eq!(decoded.start_tick, clip.start_tick);
         assert_eq!(decoded.end_tick, clip.end_tick);
         assert_eq!(decoded.sample_ref, clip.sample_ref);
+        // Unity gain and no flags are left out, as in projects saved before them
+        assert!(!json.contains("gain"));
+        assert!(!json.contains("looped"));
+        assert_eq!(decoded.gain, 1.0);
+        assert!(!decoded.reversed);
+
+        let edited = ClipData {
+            gain: 0.5,
+            looped: true,
+            reversed: true,
+            ..clip
+        };
+        let json = serde_json::to_string(&edited).expect("serialize");
+        let decoded: ClipData = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(decoded.gain, 0.5);
+        assert!(decoded.looped);
+        assert!(decoded.reversed);
     }
 
     #[test]
@@ -286,6 +875,55 @@ mod tests {
         assert!(json.contains("project"));
         let decoded: SampleRef = serde_json::from_str(&json).expect("deserialize");
         assert_eq!(decoded, project_rel);
+
+        // Test Library serialization
+        let library = SampleRef::Library {
+            root_name: "Splice".to_string(),
+            path: PathBuf::from("kicks/909.wav"),
+        };
+        let json = serde_json::to_string(&library).expect("serialize");
+        assert_eq!(
+            json,
+            r#"{"kind":"library","path":{"root_name":"Splice","path":"kicks/909.wav"}}"#
+        );
+        let decoded: SampleRef = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(decoded, library);
+        assert_eq!(library.to_string(), "library:Splice/kicks/909.wav");
+    }
+
+    #[test]
+    fn test_library_samples_resolve_in_sample_roots() {
+        use tempfile::tempdir;
+
+        let temp = tempdir().unwrap();
+        let drums = temp.path().join("drums");
+        let loops = temp.path().join("loops");
+        std::fs::create_dir_all(drums.join("kicks")).unwrap();
+        std::fs::create_dir_all(&loops).unwrap();
+        std::fs::write(drums.join("kicks/909.wav"), b"fake wav").unwrap();
+        std::fs::write(loops.join("break.wav"), b"fake wav").unwrap();
+
+        let ctx = PathContext::from_project_path(&temp.path().join("song/song.dawproj"))
+            .with_sample_root(SampleRoot::new("Loops", &loops))
+            .with_sample_root(SampleRoot::new("Drums", &drums));
+        let library = |root_name: &str, path: &str| SampleRef::Library {
+            root_name: root_name.to_string(),
+            path: PathBuf::from(path),
+        };
+        assert_eq!(
+            ctx.resolve(&library("Drums", "kicks/909.wav")),
+            Some(drums.join("kicks/909.wav"))
+        );
+        // A library named differently on this machine is still searched
+        assert_eq!(
+            ctx.resolve(&library("Breaks", "break.wav")),
+            Some(loops.join("break.wav"))
+        );
+        assert_eq!(ctx.resolve(&library("Drums", "kicks/808.wav")), None);
+        assert_eq!(
+            ctx.sample_ref_for(&drums.join("kicks/909.wav")),
+            library("Drums", "kicks/909.wav")
+        );
     }
 
     #[test]
@@ -295,6 +933,16 @@ mod tests {
             tempo: 140.0,
             time_signature: (3, 4),
             tracks: vec![],
+            next_track_id: 0,
+            next_clip_id: 0,
+            end_behavior: EndBehavior::default(),
+            locators: Default::default(),
+            embedded: Default::default(),
+            session_state: None,
+            master: MasterData::default(),
+            tempo_map: Default::default(),
+            info: Default::default(),
+            sample_rate: None,
         };
 
         let json = serde_json::to_string(&project).expect("serialize");
@@ -306,6 +954,137 @@ mod tests {
         assert!(decoded.tracks.is_empty());
     }
 
+    #[test]
+    fn test_session_state_serialization() {
+        let mut project = sample_project();
+        project.session_state = Some(SessionState {
+            cursor_tick: Some(1920),
+            snap_mode: SnapMode::HalfBeat,
+            metronome_enabled: true,
+            metronome_volume: 0.5,
+            zoom: Some(80.0),
+            loop_region: Some(LoopRegion {
+                start_tick: 3840,
+                end_tick: 7680,
+                enabled: true,
+            }),
+            punch_region: Some(PunchRegion {
+                start_tick: 960,
+                end_tick: 1920,
+                enabled: false,
+            }),
+            count_in_bars: 1,
+            pre_roll_bars: 2,
+        });
+
+        let json = serde_json::to_string(&project).expect("serialize");
+        assert!(json.contains(r#""snap_mode":"half_beat""#));
+        let decoded: Project = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(decoded.session_state, project.session_state);
+
+        // Projects saved without it open with the session's defaults
+        project.session_state = None;
+        let json = serde_json::to_string(&project).expect("serialize");
+        assert!(!json.contains("session_state"));
+        let decoded: Project = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(decoded.session_state, None);
+    }
+
+    #[test]
+    fn test_master_channel_serialization() {
+        let mut project = sample_project();
+        let json = serde_json::to_string(&project).expect("serialize");
+        assert!(!json.contains("master"));
+
+        project.master = MasterData {
+            gain_db: -3.0,
+            pan: 0.25,
+            limiter: Some(LimiterSettings::default()),
+            effects: vec![Effect::from_kind("compressor").unwrap()],
+        };
+        let json = serde_json::to_string(&project).expect("serialize");
+        let decoded: Project = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(decoded.master, project.master);
+
+        // Settings left out take their defaults, and unknown effects are dropped
+        let master: MasterData =
+            serde_json::from_str(r#"{ "pan": -1.0, "effects": [{ "kind": "flanger" }] }"#)
+                .expect("deserialize");
+        assert_eq!(
+            master,
+            MasterData {
+                pan: -1.0,
+                ..MasterData::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_tempo_map_serialization() {
+        let mut project = sample_project();
+        project.tempo_map = TempoMap {
+            tempo_changes: vec![TempoChange {
+                tick: 3840,
+                bpm: 96.5,
+            }],
+            time_signature_changes: vec![TimeSignatureChange {
+                tick: 7680,
+                time_signature: (3, 4),
+            }],
+        };
+
+        let json = serde_json::to_string(&project).expect("serialize");
+        let decoded: Project = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(decoded.tempo_map, project.tempo_map);
+        let packed = rmp_serde::to_vec_named(&project).expect("encode");
+        let decoded: Project = rmp_serde::from_slice(&packed).expect("decode");
+        assert_eq!(decoded.tempo_map, project.tempo_map);
+
+        // Files from before tempo maps only have the single tempo and time signature
+        let old = r#"{"name":"Old","tempo":100.0,"time_signature":[5,4],"tracks":[]}"#;
+        let decoded: Project = serde_json::from_str(old).expect("deserialize");
+        assert!(decoded.tempo_map.is_empty());
+        assert_eq!(decoded.tempo_map.tempo_at(decoded.tempo, 10_000), 100.0);
+        assert_eq!(
+            decoded
+                .tempo_map
+                .time_signature_at(decoded.time_signature, 10_000),
+            (5, 4)
+        );
+        project.tempo_map = TempoMap::default();
+        let json = serde_json::to_string(&project).expect("serialize");
+        assert!(!json.contains("tempo_map"));
+    }
+
+    #[test]
+    fn test_project_info_serialization() {
+        let mut project = sample_project();
+        let json = serde_json::to_string(&project).expect("serialize");
+        assert!(!json.contains("info"));
+
+        project.info = ProjectInfo {
+            author: "Korbin".to_string(),
+            notes: "Second verse needs work".to_string(),
+            created: Some(1_700_000_000),
+            modified: Some(1_700_086_400),
+            key: MusicalKey::parse("F#m"),
+        };
+        let json = serde_json::to_string(&project).expect("serialize");
+        let decoded: Project = serde_json::from_str(&json).expect("deserialize");
+        assert_eq!(decoded.info, project.info);
+        let packed = rmp_serde::to_vec_named(&project).expect("encode");
+        let decoded: Project = rmp_serde::from_slice(&packed).expect("decode");
+        assert_eq!(decoded.info, project.info);
+
+        // Only what's set is written
+        project.info = ProjectInfo {
+            author: "Korbin".to_string(),
+            ..Default::default()
+        };
+        let json = serde_json::to_string(&project).expect("serialize");
+        assert!(json.contains(r#""info":{"author":"Korbin"}"#));
+    }
+
     #[test]
     fn test_track_with_no_clips() {
         let track = TrackData {
@@ -316,6 +1095,14 @@ mod tests {
             pan: 0.0,
             enabled: true,
             solo: false,
+            color: None,
+            metadata: Default::default(),
+            parent: None,
+            is_folder: false,
+            automation: Vec::new(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
         };
 
         let json = serde_json::to_string(&track).expect("serialize");
@@ -325,6 +1112,148 @@ mod tests {
         assert!(decoded.clips.is_empty());
     }
 
+    #[test]
+    fn test_track_color_and_metadata() {
+        let mut track = sample_project().tracks.remove(0);
+        track.color = Some([220, 80, 40]);
+        track
+            .metadata
+            .insert("notes".to_string(), "Retake the fill".to_string());
+
+        let json = serde_json::to_string(&track).expect("serialize");
+        let decoded: TrackData = serde_json::from_str(&json).expect("deserialize");
+
+        assert_eq!(decoded.color, Some([220, 80, 40]));
+        assert_eq!(decoded.metadata.get("notes").unwrap(), "Retake the fill");
+
+        // Projects saved before these fields existed still load
+        let json = r#"{"id":1,"name":"Old","clips":[],"volume":1.0,"pan":0.0,"enabled":true,"solo":false}"#;
+        let decoded: TrackData = serde_json::from_str(json).expect("deserialize");
+        assert_eq!(decoded.color, None);
+        assert!(decoded.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_track_folder_fields() {
+        let mut project = sample_project();
+        project.tracks[0].is_folder = true;
+        project.tracks[1].parent = Some(1);
+
+        let json = serde_json::to_string(&project).expect("serialize");
+        let decoded: Project = serde_json::from_str(&json).expect("deserialize");
+        assert!(decoded.tracks[0].is_folder);
+        assert_eq!(decoded.tracks[0].parent, None);
+        assert_eq!(decoded.tracks[1].parent, Some(1));
+        assert!(!decoded.tracks[1].is_folder);
+
+        // Top-level regular tracks don't write the fields at all
+        let json = serde_json::to_string(&sample_project().tracks[0]).expect("serialize");
+        assert!(!json.contains("parent") && !json.contains("is_folder"));
+    }
+
+    #[test]
+    fn test_bus_and_sends() {
+        let mut project = sample_project();
+        project.tracks[0].is_bus = true;
+        project.tracks[1].sends = vec![SendData {
+            bus: 1,
+            level: 0.5,
+            pre_fader: true,
+        }];
+
+        let json = serde_json::to_string(&project).expect("serialize");
+        let decoded: Project = serde_json::from_str(&json).expect("deserialize");
+        assert!(decoded.tracks[0].is_bus);
+        assert_eq!(decoded.tracks[1].sends, project.tracks[1].sends);
+        assert!(json.contains(r#""sends":[{"bus":1,"level":0.5,"pre_fader":true}]"#));
+
+        // Regular tracks without sends don't write the fields at all
+        let json = serde_json::to_string(&sample_project().tracks[0]).expect("serialize");
+        assert!(!json.contains("is_bus") && !json.contains("sends"));
+    }
+
+    #[test]
+    fn test_track_automation() {
+        use daw_transport::{AutomationCurve, AutomationPoint};
+
+        let mut project = sample_project();
+        let mut volume = AutomationLane::new("volume");
+        volume.points = vec![
+            AutomationPoint {
+                tick: 0,
+                value: 1.0,
+                curve: AutomationCurve::Linear,
+            },
+            AutomationPoint {
+                tick: 960,
+                value: 0.5,
+                curve: AutomationCurve::Step,
+            },
+        ];
+        project.tracks[0].automation = vec![volume.clone()];
+
+        for format in [ProjectFormat::Json, ProjectFormat::MessagePack] {
+            let bytes = match format {
+                ProjectFormat::Json => serde_json::to_vec(&project).expect("serialize"),
+                ProjectFormat::MessagePack => rmp_serde::to_vec_named(&project).expect("encode"),
+            };
+            let decoded: Project = match format {
+                ProjectFormat::Json => serde_json::from_slice(&bytes).expect("deserialize"),
+                ProjectFormat::MessagePack => rmp_serde::from_slice(&bytes).expect("decode"),
+            };
+            assert_eq!(decoded.tracks[0].automation, vec![volume.clone()]);
+            assert!(decoded.tracks[1].automation.is_empty());
+        }
+
+        // Linear points and tracks without automation leave the fields out
+        let json = serde_json::to_string(&project.tracks[0]).expect("serialize");
+        assert!(json.contains(r#"{"tick":0,"value":1.0},{"tick":960,"value":0.5,"curve":"step"}"#));
+        let json = serde_json::to_string(&project.tracks[1]).expect("serialize");
+        assert!(!json.contains("automation"));
+
+        // Curves from newer versions read as linear
+        let lane: AutomationLane = serde_json::from_str(
+            r#"{"parameter":"pan","points":[{"tick":0,"value":0.0,"curve":"bezier"}]}"#,
+        )
+        .expect("deserialize");
+        assert_eq!(lane.points[0].curve, AutomationCurve::Linear);
+    }
+
+    #[test]
+    fn test_track_effects() {
+        let mut project = sample_project();
+        let mut delay = Effect::from_kind("delay").unwrap();
+        delay.set_parameter("time_ms", 500.0);
+        project.tracks[0].effects = vec![delay, Effect::from_kind("reverb").unwrap()];
+
+        for format in [ProjectFormat::Json, ProjectFormat::MessagePack] {
+            let bytes = match format {
+                ProjectFormat::Json => serde_json::to_vec(&project).expect("serialize"),
+                ProjectFormat::MessagePack => rmp_serde::to_vec_named(&project).expect("encode"),
+            };
+            let decoded: Project = match format {
+                ProjectFormat::Json => serde_json::from_slice(&bytes).expect("deserialize"),
+                ProjectFormat::MessagePack => rmp_serde::from_slice(&bytes).expect("decode"),
+            };
+            assert_eq!(decoded.tracks[0].effects, project.tracks[0].effects);
+            assert!(decoded.tracks[1].effects.is_empty());
+        }
+
+        // Each effect is saved by kind, and settings it leaves out take their defaults
+        let json = serde_json::to_string(&project.tracks[0]).expect("serialize");
+        assert!(json.contains(r#""effects":[{"kind":"delay","time_ms":500.0,"#));
+        let json = serde_json::to_string(&project.tracks[1]).expect("serialize");
+        assert!(!json.contains("effects"));
+        let effect: Effect = serde_json::from_str(r#"{"kind":"reverb","mix":1.0}"#).unwrap();
+        assert_eq!(effect.parameters()[0].value, 0.5);
+
+        // Kinds from a newer version are left out rather than failing the load
+        let mut json = serde_json::to_value(&project.tracks[0]).expect("serialize");
+        json["effects"][0]["kind"] = "chorus".into();
+        let track: TrackData = serde_json::from_value(json).expect("deserialize");
+        assert_eq!(track.effects, project.tracks[0].effects[1..]);
+    }
+
     #[test]
     fn test_project_clone() {
         let project = sample_project();
@@ -362,6 +1291,8 @@ mod tests {
         let ctx = PathContext {
             project_root: project_dir.clone(),
             dev_root: Some(dev_root.to_path_buf()),
+            embedded_dir: None,
+            sample_roots: Vec::new(),
         };
 
         // Test DevRoot resolution
@@ -373,5 +1304,17 @@ mod tests {
         // Test missing file returns None
         let missing_ref = SampleRef::DevRoot(PathBuf::from("cr78/missing.wav"));
         assert!(ctx.resolve(&missing_ref).is_none());
+
+        // And the other way round, from files to references
+        assert_eq!(ctx.sample_ref_for(&test_audio), sample_ref);
+        assert_eq!(
+            ctx.sample_ref_for(&project_dir.join("audio/vox.wav")),
+            SampleRef::ProjectRelative(PathBuf::from("audio/vox.wav"))
+        );
+        let elsewhere = temp.path().join("downloads/kick.wav");
+        assert_eq!(
+            ctx.sample_ref_for(&elsewhere),
+            SampleRef::ProjectRelative(elsewhere.clone())
+        );
     }
 }
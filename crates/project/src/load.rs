@@ -1,10 +1,23 @@
-use crate::{PathContext, Project, ProjectError, SampleRef};
-use daw_transport::{Clip, Track, TrackId, WaveformData};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
-use std::path::Path;
+use crate::container::{ZIP_MAGIC, read_zip, read_zip_peaks};
+use crate::embed::write_embedded;
+use crate::peaks::{PeakCache, PeakFile, peaks_path};
+use crate::{
+    ClipData, EndBehavior, MasterData, PathContext, Project, ProjectError, ProjectFormat,
+    ProjectInfo, SampleRef, SaveOptions, SessionState, TempoMap,
+};
+use daw_transport::{
+    AudioArc, Clip, ClipContent, ClipId, IdAllocator, Track, TrackId, TrackSend, WaveformData,
+};
+use rayon::prelude::*;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// How often loading progress is reported while waiting for audio
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(50);
+/// First bytes of a zstd frame, which mark a compressed project file
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
 
 /// Information about a clip whose audio file could not be loaded.
 #[derive(Debug, Clone)]
@@ -20,13 +33,36 @@ pub struct OfflineClip {
     pub name: String,
     /// Error message describing why the audio couldn't be loaded
     pub error: String,
+    /// The clip as saved, with an id, for putting it back once its sample is found
+    /// (see [`OfflineClip::relink`])
+    pub clip: ClipData,
+}
+
+/// A sample that decoded only in part, with the damage silenced.
+///
+/// Only reported when the cache passed in has lossy decoding on (see
+/// [`daw_decode::AudioCache::with_lossy_decoding`]); otherwise such files fail to
+/// decode and their clips are offline.
+#[derive(Debug, Clone)]
+pub struct DamagedSample {
+    /// The sample reference from the project file, as used by the first clip playing it
+    pub sample_ref: SampleRef,
+    /// The file it resolved to
+    pub path: PathBuf,
+    /// What was patched over
+    pub issues: Vec<daw_decode::DecodeIssue>,
 }
 
 #[derive(Debug)]
 pub struct LoadedProject {
     pub name: String,
+    pub info: ProjectInfo,
     pub tempo: f64,
     pub time_signature: (u32, u32),
+    /// Tempo and time signature changes after the start, in tick order
+    pub tempo_map: TempoMap,
+    /// Sample rate the project prefers, if it names one
+    pub sample_rate: Option<u32>,
     pub tracks: Vec<Track>,
     /// Mapping from clip name to sample reference
     pub sample_refs: HashMap<String, SampleRef>,
@@ -34,38 +70,89 @@ pub struct LoadedProject {
     pub cache: daw_decode::AudioCache,
     /// Clips that couldn't be loaded due to missing or invalid audio files
     pub offline_clips: Vec<OfflineClip>,
+    /// Samples that loaded with parts of them silenced, once per file
+    pub damaged_samples: Vec<DamagedSample>,
+    /// Allocator for new track and clip ids, past every id in the project
+    pub ids: IdAllocator,
+    pub end_behavior: EndBehavior,
+    /// Numbered locator positions in ticks
+    pub locators: BTreeMap<u8, u64>,
+    /// Folder the project's embedded samples were written out to, or `None` if it
+    /// embeds none. Resolve their references with it as the
+    /// [`PathContext::embedded_dir`].
+    pub embedded_dir: Option<PathBuf>,
+    /// Format and compression the file was written with, to save it the same way
+    pub save_options: SaveOptions,
+    /// Cursor, snapping and other state saved with the project, if any
+    pub session_state: Option<SessionState>,
+    /// The master channel's settings and effects
+    pub master: MasterData,
 }
 
 #[derive(Debug, Clone)]
 pub struct ProjectMetadata {
     pub name: String,
+    pub info: ProjectInfo,
     pub tempo: f64,
     pub time_signature: (u32, u32),
+    pub sample_rate: Option<u32>,
     pub track_count: usize,
     pub segment_count: usize,
 }
 
-fn load_project_data(path: &Path) -> Result<Project, ProjectError> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+pub(crate) fn load_project_data(path: &Path) -> Result<(Project, SaveOptions), ProjectError> {
+    let mut bytes = std::fs::read(path)?;
+    if bytes.starts_with(&ZIP_MAGIC) {
+        let project = read_zip(std::io::Cursor::new(bytes))?;
+        let options = SaveOptions {
+            zip: true,
+            ..Default::default()
+        };
+        return Ok((project, options));
+    }
+
+    let compress = bytes.starts_with(&ZSTD_MAGIC);
+    if compress {
+        bytes = zstd::decode_all(bytes.as_slice())?;
+    }
 
     // Try JSON first, fall back to MessagePack
-    serde_json::from_reader(reader).or_else(|_| {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        rmp_serde::decode::from_read(reader).map_err(ProjectError::from)
-    })
+    let (project, format) = match serde_json::from_slice(&bytes) {
+        Ok(project) => (project, ProjectFormat::Json),
+        Err(json_err) => match rmp_serde::decode::from_slice(&bytes) {
+            Ok(project) => (project, ProjectFormat::MessagePack),
+            // A file that looks like JSON is reported with what's wrong with the JSON
+            Err(_) if bytes.trim_ascii_start().starts_with(b"{") => return Err(json_err.into()),
+            Err(err) => return Err(err.into()),
+        },
+    };
+    let options = SaveOptions {
+        format,
+        compress,
+        ..Default::default()
+    };
+    Ok((project, options))
+}
+
+/// Read the project file at `path` as it's saved, without loading any audio.
+///
+/// Useful for comparing versions of a project with [`diff`](crate::diff).
+pub fn read_project(path: &Path) -> Result<Project, ProjectError> {
+    let (project, _) = load_project_data(path)?;
+    Ok(project)
 }
 
 pub fn load_project_metadata(path: &Path) -> Result<ProjectMetadata, ProjectError> {
-    let project = load_project_data(path)?;
+    let (project, _) = load_project_data(path)?;
 
     let clip_count: usize = project.tracks.iter().map(|t| t.clips.len()).sum();
 
     Ok(ProjectMetadata {
         name: project.name,
+        info: project.info,
         tempo: project.tempo,
         time_signature: project.time_signature,
+        sample_rate: project.sample_rate,
         track_count: project.tracks.len(),
         segment_count: clip_count,
     })
@@ -75,17 +162,124 @@ pub fn load_project(path: &Path, ctx: &PathContext) -> Result<LoadedProject, Pro
     load_project_with_sample_rate(path, None, ctx)
 }
 
+/// Load a project, resampling its audio to `target_sample_rate` if given.
+///
+/// Each distinct file is decoded once, in parallel with the others on rayon's
+/// thread pool, however many clips use it.
 pub fn load_project_with_sample_rate(
     path: &Path,
     target_sample_rate: Option<u32>,
     ctx: &PathContext,
 ) -> Result<LoadedProject, ProjectError> {
-    let project = load_project_data(path)?;
+    load_project_with_cache(path, target_sample_rate, ctx, daw_decode::AudioCache::new())
+}
+
+/// Load a project, decoding audio through the given cache.
+///
+/// Pass a cache backed by a [`daw_decode::DiskCache`] to reuse audio decoded by
+/// earlier loads. The cache is returned in [`LoadedProject::cache`].
+pub fn load_project_with_cache(
+    path: &Path,
+    target_sample_rate: Option<u32>,
+    ctx: &PathContext,
+    cache: daw_decode::AudioCache,
+) -> Result<LoadedProject, ProjectError> {
+    load_project_with_progress(path, target_sample_rate, ctx, cache, None, |_| {})
+}
+
+/// Load a project like [`load_project_with_cache`], reporting progress.
+///
+/// Audio files are decoded in parallel on a background thread pool. `progress`
+/// is called on the calling thread with the fraction of audio loaded so far,
+/// from 0.0 to 1.0, several times a second while loading.
+///
+/// Waveform peaks missing from the project's peak file are looked up in
+/// `peak_cache`, if given, before being computed, and computed ones are added to it.
+pub fn load_project_with_progress(
+    path: &Path,
+    target_sample_rate: Option<u32>,
+    ctx: &PathContext,
+    cache: daw_decode::AudioCache,
+    peak_cache: Option<&PeakCache>,
+    progress: impl FnMut(f32),
+) -> Result<LoadedProject, ProjectError> {
+    load_project_watching_files(
+        path,
+        target_sample_rate,
+        ctx,
+        cache,
+        peak_cache,
+        progress,
+        |_, _| {},
+    )
+}
+
+/// [`load_project_with_progress`], also passing each file's own progress to
+/// `file_progress` on the thread decoding it
+fn load_project_watching_files(
+    path: &Path,
+    target_sample_rate: Option<u32>,
+    ctx: &PathContext,
+    mut cache: daw_decode::AudioCache,
+    peak_cache: Option<&PeakCache>,
+    mut progress: impl FnMut(f32),
+    file_progress: impl Fn(&Path, f32) + Clone + Send + 'static,
+) -> Result<LoadedProject, ProjectError> {
+    let (project, save_options) = load_project_data(path)?;
+    let mut ids = project_ids(&project);
+    let mut tempo_map = project.tempo_map.clone();
+    tempo_map.normalize();
+
+    // Embedded samples are written out to files and decoded like any other
+    let embedded_dir = if project.embedded.is_empty() {
+        None
+    } else {
+        Some(write_embedded(&project.embedded)?)
+    };
+    let embedded_ctx = PathContext {
+        embedded_dir: embedded_dir.clone(),
+        ..ctx.clone()
+    };
+    let ctx = if embedded_dir.is_some() {
+        &embedded_ctx
+    } else {
+        ctx
+    };
+
+    // Start decoding every distinct file in the background
+    let mut started = HashSet::new();
+    let mut pending = Vec::new();
+    for clip_data in project.tracks.iter().flat_map(|track| &track.clips) {
+        if let Some(abs_path) = ctx.resolve(&clip_data.sample_ref)
+            && started.insert(abs_path.clone())
+        {
+            let file_progress = file_progress.clone();
+            let file = abs_path.clone();
+            pending.push(
+                cache.get_or_load_async(&abs_path, target_sample_rate, move |fraction| {
+                    file_progress(&file, fraction)
+                }),
+            );
+        }
+    }
+
+    let total = pending.len().max(1) as f32;
+    let mut loaded = HashMap::new();
+    for (i, load) in pending.into_iter().enumerate() {
+        while !load.wait_timeout(PROGRESS_INTERVAL) {
+            progress((i as f32 + load.progress()) / total);
+        }
+        let path = load.path().to_path_buf();
+        loaded.insert(path, cache.finish_load(load));
+        progress((i + 1) as f32 / total);
+    }
 
-    let mut cache = daw_decode::AudioCache::new();
     let mut tracks = Vec::new();
     let mut sample_refs = HashMap::new();
     let mut offline_clips = Vec::new();
+    let mut damaged_samples: Vec<DamagedSample> = Vec::new();
+    // Decoded clips per track, waiting for their waveforms
+    let mut pending_clips: Vec<Vec<(&ClipData, PathBuf, AudioArc)>> = Vec::new();
 
     for track_data in &project.tracks {
         let mut track = Track::new(TrackId(track_data.id), track_data.name.clone());
@@ -93,6 +287,27 @@ pub fn load_project_with_sample_rate(
         track.pan = track_data.pan;
         track.enabled = track_data.enabled;
         track.solo = track_data.solo;
+        track.color = track_data.color;
+        track.metadata = track_data.metadata.clone();
+        track.parent = track_data.parent.map(TrackId);
+        track.is_folder = track_data.is_folder;
+        track.automation = track_data.automation.clone();
+        for lane in &mut track.automation {
+            lane.normalize();
+        }
+        track.effects = track_data.effects.clone();
+        track.is_bus = track_data.is_bus;
+        track.sends = track_data
+            .sends
+            .iter()
+            .map(|send| TrackSend {
+                bus: TrackId(send.bus),
+                level: send.level,
+                pre_fader: send.pre_fader,
+            })
+            .collect();
+
+        let mut track_clips = Vec::new();
 
         for clip_data in &track_data.clips {
             // Try to resolve the sample reference to an absolute path
@@ -100,22 +315,23 @@ pub fn load_project_with_sample_rate(
 
             match resolved_path {
                 Some(abs_path) => {
-                    // Try to load the audio
-                    match cache.get_or_load_direct(&abs_path, target_sample_rate) {
+                    match &loaded[&abs_path] {
                         Ok(audio) => {
+                            let issues = cache.decode_issues(&abs_path);
+                            if !issues.is_empty()
+                                && !damaged_samples
+                                    .iter()
+                                    .any(|damaged| damaged.path == abs_path)
+                            {
+                                damaged_samples.push(DamagedSample {
+                                    sample_ref: clip_data.sample_ref.clone(),
+                                    path: abs_path.clone(),
+                                    issues: issues.to_vec(),
+                                });
+                            }
                             sample_refs
                                 .insert(clip_data.name.clone(), clip_data.sample_ref.clone());
-
-                            let waveform = WaveformData::from_audio_arc(&audio, 512);
-
-                            track.insert_clip(Clip {
-                                start_tick: clip_data.start_tick,
-                                end_tick: clip_data.end_tick,
-                                audio,
-                                waveform: Arc::new(waveform),
-                                audio_offset: clip_data.audio_offset,
-                                name: clip_data.name.clone(),
-                            });
+                            track_clips.push((clip_data, abs_path, audio.clone()));
                         }
                         Err(e) => {
                             // Audio file exists but couldn't be decoded
@@ -126,6 +342,7 @@ pub fn load_project_with_sample_rate(
                                 end_tick: clip_data.end_tick,
                                 name: clip_data.name.clone(),
                                 error: format!("Failed to decode: {}", e),
+                                clip: offline_clip_data(clip_data, &mut ids),
                             });
                         }
                     }
@@ -142,51 +359,303 @@ pub fn load_project_with_sample_rate(
                             "Sample not found: {:?}",
                             clip_data.sample_ref.path().display()
                         ),
+                        clip: offline_clip_data(clip_data, &mut ids),
                     });
                 }
             }
         }
 
         tracks.push(track);
+        pending_clips.push(track_clips);
+    }
+
+    // A zip project brings its own peaks, for when it's opened without its peak file
+    let zipped_peaks = (save_options.zip && !peaks_path(path).exists())
+        .then(|| read_zip_peaks(path))
+        .flatten();
+    let mut peaks = zipped_peaks.unwrap_or_else(|| PeakFile::load(&peaks_path(path)));
+    let (waveforms, changed) = generate_waveforms(
+        pending_clips
+            .iter()
+            .flatten()
+            .map(|(clip_data, abs_path, audio)| WaveformSource {
+                sample_ref: &clip_data.sample_ref,
+                path: abs_path,
+                content_hash: cache.known_content_hash(abs_path),
+                audio,
+            }),
+        &mut peaks,
+        peak_cache,
+    );
+    if changed {
+        // The peak file only speeds up later loads, so failing to write it isn't an error
+        let _ = peaks.save(&peaks_path(path));
+    }
+
+    for (track, track_clips) in tracks.iter_mut().zip(pending_clips) {
+        for (clip_data, _, audio) in track_clips {
+            let waveform = waveforms[&waveform_key(&audio)].clone();
+
+            let id = match clip_data.id {
+                Some(id) => ClipId(id),
+                None => ids.clip_id(),
+            };
+            track.insert_clip(
+                Clip {
+                    id,
+                    start_tick: clip_data.start_tick,
+                    end_tick: clip_data.end_tick,
+                    content: ClipContent::Audio(audio),
+                    waveform,
+                    audio_offset: clip_data.audio_offset,
+                    name: clip_data.name.clone(),
+                    fade_in_ticks: clip_data.fade_in_ticks,
+                    fade_out_ticks: clip_data.fade_out_ticks,
+                    gain: clip_data.gain,
+                    looped: clip_data.looped,
+                    reversed: clip_data.reversed,
+                    source: clip_data.source.clone(),
+                },
+                &mut ids,
+            );
+        }
     }
 
     Ok(LoadedProject {
         name: project.name,
+        info: project.info,
         tempo: project.tempo,
         time_signature: project.time_signature,
+        tempo_map,
+        sample_rate: project.sample_rate,
         tracks,
         sample_refs,
         cache,
         offline_clips,
+        damaged_samples,
+        ids,
+        end_behavior: project.end_behavior,
+        locators: project.locators,
+        embedded_dir,
+        save_options,
+        session_state: project.session_state,
+        master: project.master,
     })
 }
 
+/// An allocator that continues from the project's stored next ids, skipping any
+/// id already in use in case the stored ones are missing or behind.
+/// `clip_data` to keep for an offline clip, given an id if it was saved without one
+fn offline_clip_data(clip_data: &ClipData, ids: &mut IdAllocator) -> ClipData {
+    ClipData {
+        id: Some(clip_data.id.unwrap_or_else(|| ids.clip_id().0)),
+        ..clip_data.clone()
+    }
+}
+
+fn project_ids(project: &Project) -> IdAllocator {
+    let used_track_ids = project.tracks.iter().map(|t| t.id);
+    let used_clip_ids = project
+        .tracks
+        .iter()
+        .flat_map(|t| &t.clips)
+        .filter_map(|c| c.id);
+    IdAllocator::new(
+        used_track_ids
+            .map(|id| id + 1)
+            .fold(project.next_track_id, u64::max),
+        used_clip_ids
+            .map(|id| id + 1)
+            .fold(project.next_clip_id, u64::max),
+    )
+}
+
+/// Identity of a decoded buffer; clips sharing a cached file share one waveform
+type WaveformKey = (usize, usize);
+
+fn waveform_key(audio: &AudioArc) -> WaveformKey {
+    (audio.samples().as_ptr() as usize, audio.len())
+}
+
+/// A clip's decoded audio and where it came from, for looking up its peaks
+struct WaveformSource<'a> {
+    sample_ref: &'a SampleRef,
+    path: &'a Path,
+    /// Hash of the file's contents, keying the shared peak cache
+    content_hash: Option<u64>,
+    audio: &'a AudioArc,
+}
+
+/// Get one waveform per distinct buffer, reusing peaks stored next to the project
+/// or in the shared peak cache and computing the rest concurrently.
+///
+/// Waveforms not found in `peaks` are added to it; the returned flag says whether
+/// any were. Computed waveforms are also added to `peak_cache`.
+fn generate_waveforms<'a>(
+    clips: impl Iterator<Item = WaveformSource<'a>>,
+    peaks: &mut PeakFile,
+    peak_cache: Option<&PeakCache>,
+) -> (HashMap<WaveformKey, Arc<WaveformData>>, bool) {
+    let mut waveforms = HashMap::new();
+    let mut missing: HashMap<WaveformKey, WaveformSource> = HashMap::new();
+    let mut changed = false;
+    for clip in clips {
+        let key = waveform_key(clip.audio);
+        if waveforms.contains_key(&key) || missing.contains_key(&key) {
+            continue;
+        }
+        let sample_rate = clip.audio.sample_rate();
+        if let Some(waveform) = peaks.get(clip.sample_ref, sample_rate, clip.path) {
+            waveforms.insert(key, Arc::new(waveform));
+            continue;
+        }
+        let cached = peak_cache
+            .zip(clip.content_hash)
+            .and_then(|(peak_cache, hash)| peak_cache.get(hash, sample_rate));
+        match cached {
+            Some(waveform) => {
+                peaks.insert(clip.sample_ref, sample_rate, clip.path, &waveform);
+                changed = true;
+                waveforms.insert(key, Arc::new(waveform));
+            }
+            None => {
+                missing.insert(key, clip);
+            }
+        }
+    }
+
+    let computed: Vec<_> = missing
+        .into_par_iter()
+        .map(|(key, clip)| {
+            let waveform = WaveformData::from_audio_arc(clip.audio, 512);
+            (key, clip, waveform)
+        })
+        .collect();
+
+    changed |= !computed.is_empty();
+    for (key, clip, waveform) in computed {
+        let sample_rate = clip.audio.sample_rate();
+        peaks.insert(clip.sample_ref, sample_rate, clip.path, &waveform);
+        if let (Some(peak_cache), Some(hash)) = (peak_cache, clip.content_hash) {
+            // The shared cache only saves time later, so failing to write it isn't an error
+            let _ = peak_cache.put(hash, sample_rate, &waveform);
+        }
+        waveforms.insert(key, Arc::new(waveform));
+    }
+    (waveforms, changed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::{ClipData, Project, TrackData};
+    use crate::{TempoChange, TimeSignatureChange};
     use std::path::PathBuf;
     use tempfile::tempdir;
 
-    fn write_test_wav(path: &Path) {
-        use std::io::Write;
-        let mut file = std::fs::File::create(path).expect("create wav");
-        let header: [u8; 44] = [
-            0x52, 0x49, 0x46, 0x46, // "RIFF"
-            0x24, 0x00, 0x00, 0x00, // file size - 8
-            0x57, 0x41, 0x56, 0x45, // "WAVE"
-            0x66, 0x6d, 0x74, 0x20, // "fmt "
-            0x10, 0x00, 0x00, 0x00, // format chunk size
-            0x01, 0x00, // PCM format
-            0x01, 0x00, // 1 channel
-            0x44, 0xac, 0x00, 0x00, // 44100 sample rate
-            0x88, 0x58, 0x01, 0x00, // byte rate
-            0x02, 0x00, // block align
-            0x10, 0x00, // bits per sample
-            0x64, 0x61, 0x74, 0x61, // "data"
-            0x00, 0x00, 0x00, 0x00, // data size (0 samples)
-        ];
-        file.write_all(&header).expect("write wav header");
+    /// Write a mono 16-bit WAV at 44.1 kHz holding `samples`
+    fn write_pcm_wav(path: &Path, samples: &[i16]) {
+        let data_len = samples.len() as u32 * 2;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&[0x01, 0x00, 0x01, 0x00]); // PCM, 1 channel
+        bytes.extend_from_slice(&44100u32.to_le_bytes());
+        bytes.extend_from_slice(&88200u32.to_le_bytes());
+        bytes.extend_from_slice(&[0x02, 0x00, 0x10, 0x00]); // block align, bits per sample
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        std::fs::write(path, bytes).expect("write wav");
+    }
+
+    /// A project at 120 BPM in 4/4 holding `tracks`
+    fn project(name: &str, tracks: Vec<TrackData>) -> Project {
+        Project {
+            name: name.to_string(),
+            info: Default::default(),
+            tempo: 120.0,
+            time_signature: (4, 4),
+            tempo_map: Default::default(),
+            sample_rate: None,
+            tracks,
+            next_track_id: 0,
+            next_clip_id: 0,
+            end_behavior: EndBehavior::default(),
+            locators: Default::default(),
+            embedded: Default::default(),
+            session_state: None,
+            master: MasterData::default(),
+        }
+    }
+
+    /// An enabled track at full volume holding `clips`
+    fn track(id: u64, name: &str, clips: Vec<ClipData>) -> TrackData {
+        TrackData {
+            id,
+            name: name.to_string(),
+            clips,
+            volume: 1.0,
+            pan: 0.0,
+            enabled: true,
+            solo: false,
+            color: None,
+            metadata: Default::default(),
+            parent: None,
+            is_folder: false,
+            automation: Vec::new(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+        }
+    }
+
+    /// A beat-long clip of `sample_ref` from `start_tick`, playing it from the start
+    fn clip(name: &str, sample_ref: SampleRef, start_tick: u64) -> ClipData {
+        ClipData {
+            id: None,
+            start_tick,
+            end_tick: start_tick + 960,
+            sample_ref,
+            audio_offset: 0,
+            name: name.to_string(),
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: Default::default(),
+        }
+    }
+
+    /// Write a mono IMA ADPCM WAV of two packets (17 blocks of 65 frames each), the
+    /// second of which fails to decode
+    fn write_damaged_wav(path: &Path) {
+        let mut data = Vec::new();
+        for block in 0..34 {
+            // A step index past 88 is invalid
+            let step_index = if block == 17 { 200 } else { 0 };
+            data.extend_from_slice(&[0, 0, step_index, 0]);
+            data.extend_from_slice(&[0; 32]);
+        }
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(40 + data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&20u32.to_le_bytes());
+        bytes.extend_from_slice(&[0x11, 0x00, 0x01, 0x00]); // IMA ADPCM, 1 channel
+        bytes.extend_from_slice(&44100u32.to_le_bytes());
+        bytes.extend_from_slice(&24420u32.to_le_bytes());
+        bytes.extend_from_slice(&[36, 0x00, 0x04, 0x00]); // block align, bits per sample
+        bytes.extend_from_slice(&[0x02, 0x00, 65, 0x00]); // extra size, frames per block
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        std::fs::write(path, bytes).expect("write wav");
     }
 
     #[test]
@@ -194,6 +663,8 @@ mod tests {
         let ctx = PathContext {
             project_root: PathBuf::from("/nonexistent"),
             dev_root: None,
+            embedded_dir: None,
+            sample_roots: Vec::new(),
         };
         let result = load_project(Path::new("/nonexistent/project.dawproj"), &ctx);
         assert!(result.is_err());
@@ -218,28 +689,17 @@ mod tests {
         let project_path = dir.path().join("test.dawproj");
         let audio_path = dir.path().join("sample.wav");
 
-        write_test_wav(&audio_path);
+        write_pcm_wav(&audio_path, &[]);
 
-        let project = Project {
-            name: "Project Relative Test".to_string(),
-            tempo: 120.0,
-            time_signature: (4, 4),
-            tracks: vec![TrackData {
-                id: 1,
-                name: "Sample Track".to_string(),
-                clips: vec![ClipData {
-                    start_tick: 0,
-                    end_tick: 960,
-                    sample_ref: SampleRef::ProjectRelative(PathBuf::from("sample.wav")),
-                    audio_offset: 0,
-                    name: "Sample Clip".to_string(),
-                }],
-                volume: 1.0,
-                pan: 0.0,
-                enabled: true,
-                solo: false,
-            }],
-        };
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("sample.wav"));
+        let project = project(
+            "Project Relative Test",
+            vec![track(
+                1,
+                "Sample Track",
+                vec![clip("Sample Clip", sample_ref, 0)],
+            )],
+        );
 
         let file = std::fs::File::create(&project_path).expect("create");
         let writer = std::io::BufWriter::new(file);
@@ -252,6 +712,320 @@ mod tests {
         assert!(loaded.offline_clips.is_empty());
     }
 
+    #[test]
+    fn test_load_project_reports_progress() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("test.dawproj");
+        write_pcm_wav(&dir.path().join("sample.wav"), &[]);
+
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("sample.wav"));
+        let project = project(
+            "Progress Test",
+            vec![track(
+                1,
+                "Sample Track",
+                vec![
+                    clip("Sample Clip", sample_ref.clone(), 0),
+                    clip("Sample Clip", sample_ref, 960),
+                ],
+            )],
+        );
+        let file = std::fs::File::create(&project_path).expect("create");
+        serde_json::to_writer(std::io::BufWriter::new(file), &project).expect("encode");
+
+        let ctx = PathContext::from_project_path(&project_path);
+        let mut reported = Vec::new();
+        let loaded = load_project_with_progress(
+            &project_path,
+            Some(48000),
+            &ctx,
+            daw_decode::AudioCache::new(),
+            None,
+            |fraction| reported.push(fraction),
+        )
+        .expect("load");
+
+        assert_eq!(loaded.tracks[0].clips().len(), 2);
+        // The shared sample is decoded once
+        assert_eq!(loaded.cache.originals_count(), 1);
+        assert_eq!(reported.last(), Some(&1.0));
+        assert!(reported.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_load_project_decodes_each_distinct_sample_once() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("kit.dawproj");
+        // The cache is keyed by content, so each file needs different samples
+        for (i, name) in ["kick", "snare", "hat"].into_iter().enumerate() {
+            write_pcm_wav(&dir.path().join(format!("{name}.wav")), &[i as i16 + 1; 64]);
+        }
+
+        let sample = |name: &str, start_tick| {
+            let sample_ref = SampleRef::ProjectRelative(PathBuf::from(format!("{name}.wav")));
+            clip(name, sample_ref, start_tick)
+        };
+        let project = project(
+            "Kit",
+            vec![
+                track(1, "Track 1", vec![sample("kick", 0), sample("snare", 960)]),
+                track(
+                    2,
+                    "Track 2",
+                    vec![sample("hat", 0), sample("kick", 960), sample("hat", 1920)],
+                ),
+            ],
+        );
+        let file = std::fs::File::create(&project_path).expect("create");
+        serde_json::to_writer(file, &project).expect("encode");
+
+        let ctx = PathContext::from_project_path(&project_path);
+        let loaded = load_project_with_sample_rate(&project_path, Some(48000), &ctx).expect("load");
+
+        // Files are decoded side by side on the thread pool, once each however many
+        // clips on however many tracks use them
+        assert_eq!(loaded.cache.originals_count(), 3);
+        let clips: Vec<&Clip> = loaded.tracks.iter().flat_map(|t| t.clips()).collect();
+        assert_eq!(clips.len(), 5);
+        assert!(
+            clips
+                .iter()
+                .all(|clip| clip.audio().unwrap().sample_rate() == 48000)
+        );
+        let kicks: Vec<&AudioArc> = clips
+            .iter()
+            .filter(|clip| clip.name == "kick")
+            .map(|clip| clip.audio().unwrap())
+            .collect();
+        assert_eq!(waveform_key(kicks[0]), waveform_key(kicks[1]));
+    }
+
+    #[test]
+    fn test_load_project_decodes_files_at_the_same_time() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("stems.dawproj");
+        let names = ["drums", "bass", "keys"];
+        for (i, name) in names.into_iter().enumerate() {
+            write_pcm_wav(
+                &dir.path().join(format!("{name}.wav")),
+                &[i as i16 + 1; 4410],
+            );
+        }
+
+        let project = project(
+            "Stems",
+            names
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    let sample_ref =
+                        SampleRef::ProjectRelative(PathBuf::from(format!("{name}.wav")));
+                    track(i as u64 + 1, name, vec![clip(name, sample_ref, 0)])
+                })
+                .collect(),
+        );
+        let file = std::fs::File::create(&project_path).expect("create");
+        serde_json::to_writer(file, &project).expect("encode");
+
+        // Each file's first report waits for the others' to arrive, which only
+        // happens if all of them are being decoded at once. Decoded one after
+        // another, each would wait out the timeout alone.
+        let arrived = Arc::new((
+            std::sync::Mutex::new(HashMap::<PathBuf, std::thread::ThreadId>::new()),
+            std::sync::Condvar::new(),
+        ));
+        let all_met = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let file_progress = {
+            let arrived = arrived.clone();
+            let all_met = all_met.clone();
+            move |path: &Path, _fraction: f32| {
+                let (files, condvar) = &*arrived;
+                let mut files = files.lock().unwrap();
+                if files.contains_key(path) {
+                    return;
+                }
+                files.insert(path.to_path_buf(), std::thread::current().id());
+                condvar.notify_all();
+                let (files, _) = condvar
+                    .wait_timeout_while(files, Duration::from_secs(5), |files| {
+                        files.len() < names.len()
+                    })
+                    .unwrap();
+                if files.len() < names.len() {
+                    all_met.store(false, std::sync::atomic::Ordering::SeqCst);
+                }
+            }
+        };
+
+        // One thread for each file, and one for the load waiting on them
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(names.len() + 1)
+            .build()
+            .expect("pool");
+        let ctx = PathContext::from_project_path(&project_path);
+        let loaded = pool
+            .install(|| {
+                load_project_watching_files(
+                    &project_path,
+                    Some(48000),
+                    &ctx,
+                    daw_decode::AudioCache::new(),
+                    None,
+                    |_| {},
+                    file_progress,
+                )
+            })
+            .expect("load");
+
+        assert_eq!(loaded.cache.originals_count(), 3);
+        assert!(all_met.load(std::sync::atomic::Ordering::SeqCst));
+        let threads: HashSet<_> = arrived.0.lock().unwrap().values().copied().collect();
+        assert_eq!(threads.len(), names.len());
+    }
+
+    #[test]
+    fn test_load_project_reports_damaged_samples() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("damaged.dawproj");
+        write_damaged_wav(&dir.path().join("damaged.wav"));
+
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("damaged.wav"));
+        let project = project(
+            "Damaged",
+            vec![track(
+                1,
+                "Track",
+                vec![
+                    clip("Damaged", sample_ref.clone(), 0),
+                    clip("Damaged", sample_ref, 960),
+                ],
+            )],
+        );
+        let file = std::fs::File::create(&project_path).expect("create");
+        serde_json::to_writer(file, &project).expect("encode");
+        let ctx = PathContext::from_project_path(&project_path);
+
+        // Decoding strictly takes the clips offline
+        let loaded = load_project(&project_path, &ctx).expect("load");
+        assert_eq!(loaded.offline_clips.len(), 2);
+        assert!(loaded.damaged_samples.is_empty());
+
+        let cache = daw_decode::AudioCache::new().with_lossy_decoding();
+        let loaded = load_project_with_cache(&project_path, None, &ctx, cache).expect("load");
+        assert!(loaded.offline_clips.is_empty());
+        assert_eq!(loaded.tracks[0].clips().len(), 2);
+        assert_eq!(
+            loaded.tracks[0].clips()[0].audio().unwrap().frames(),
+            34 * 65
+        );
+        // Reported once for the file, however many clips use it
+        assert_eq!(loaded.damaged_samples.len(), 1);
+        let damaged = &loaded.damaged_samples[0];
+        assert_eq!(damaged.path, dir.path().join("damaged.wav"));
+        assert_eq!(damaged.issues.len(), 1);
+        assert_eq!(damaged.issues[0].frame, 17 * 65);
+    }
+
+    #[test]
+    fn test_load_project_continues_ids() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("ids.dawproj");
+        write_pcm_wav(&dir.path().join("sample.wav"), &[]);
+
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("sample.wav"));
+        let clips = vec![
+            ClipData {
+                id: Some(5),
+                ..clip("Clip", sample_ref.clone(), 0)
+            },
+            // From a project saved before clips had ids
+            clip("Clip", sample_ref, 960),
+        ];
+        let project = Project {
+            next_track_id: 10,
+            ..project("Ids", vec![track(3, "Track", clips)])
+        };
+        let file = std::fs::File::create(&project_path).expect("create");
+        serde_json::to_writer(file, &project).expect("encode");
+
+        let ctx = PathContext::from_project_path(&project_path);
+        let mut loaded = load_project(&project_path, &ctx).expect("load");
+
+        let clip_ids: Vec<ClipId> = loaded.tracks[0].clips().iter().map(|c| c.id).collect();
+        assert_eq!(clip_ids, vec![ClipId(5), ClipId(6)]);
+        assert_eq!(loaded.ids.track_id(), TrackId(10));
+        assert_eq!(loaded.ids.clip_id(), ClipId(7));
+    }
+
+    #[test]
+    fn test_load_project_reuses_stored_peaks() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("peaks.dawproj");
+        let audio_path = dir.path().join("sample.wav");
+        write_pcm_wav(&audio_path, &[]);
+
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("sample.wav"));
+        let project = project(
+            "Peaks",
+            vec![track(1, "Track", vec![clip("Clip", sample_ref.clone(), 0)])],
+        );
+        let file = std::fs::File::create(&project_path).expect("create");
+        serde_json::to_writer(file, &project).expect("encode");
+
+        // The first load computes the peaks and stores them next to the project
+        let ctx = PathContext::from_project_path(&project_path);
+        load_project(&project_path, &ctx).expect("load");
+        assert!(peaks_path(&project_path).exists());
+
+        // Later loads use the stored peaks instead of recomputing
+        let stored = WaveformData {
+            peaks: vec![(-0.5, 0.5)],
+            samples_per_bucket: 512,
+        };
+        let mut peaks = PeakFile::load(&peaks_path(&project_path));
+        peaks.insert(&sample_ref, 44100, &audio_path, &stored);
+        peaks.save(&peaks_path(&project_path)).expect("save peaks");
+
+        let loaded = load_project(&project_path, &ctx).expect("load");
+        assert_eq!(*loaded.tracks[0].clips()[0].waveform, stored);
+
+        // A copy of the project and its sample elsewhere finds the peaks in the shared cache
+        let peak_cache = PeakCache::new(dir.path().join("peak-cache"));
+        let load_with_peak_cache = |project_path: &Path| {
+            load_project_with_progress(
+                project_path,
+                None,
+                &PathContext::from_project_path(project_path),
+                daw_decode::AudioCache::new(),
+                Some(&peak_cache),
+                |_| {},
+            )
+            .expect("load")
+        };
+        let copy_dir = dir.path().join("copy");
+        std::fs::create_dir(&copy_dir).expect("create dir");
+        let copy_path = copy_dir.join("copy.dawproj");
+        std::fs::copy(&project_path, &copy_path).expect("copy project");
+        std::fs::copy(&audio_path, copy_dir.join("sample.wav")).expect("copy sample");
+
+        std::fs::remove_file(peaks_path(&project_path)).expect("remove peaks");
+        let loaded = load_with_peak_cache(&project_path);
+        let hash = loaded
+            .cache
+            .known_content_hash(&audio_path)
+            .expect("content hash");
+        let cached = WaveformData {
+            peaks: vec![(-0.25, 0.25)],
+            samples_per_bucket: 512,
+        };
+        assert!(peak_cache.get(hash, 44100).is_some());
+        peak_cache.put(hash, 44100, &cached).expect("put");
+
+        let loaded = load_with_peak_cache(&copy_path);
+        assert_eq!(*loaded.tracks[0].clips()[0].waveform, cached);
+        assert!(peaks_path(&copy_path).exists());
+    }
+
     #[test]
     fn test_load_project_with_dev_root_sample() {
         let dir = tempdir().expect("tempdir");
@@ -263,35 +1037,20 @@ mod tests {
         let project_path = project_dir.join("test.dawproj");
         let audio_path = samples_dir.join("kick.wav");
 
-        write_test_wav(&audio_path);
+        write_pcm_wav(&audio_path, &[]);
 
-        let project = Project {
-            name: "Dev Root Test".to_string(),
-            tempo: 120.0,
-            time_signature: (4, 4),
-            tracks: vec![TrackData {
-                id: 1,
-                name: "Drums".to_string(),
-                clips: vec![ClipData {
-                    start_tick: 0,
-                    end_tick: 960,
-                    sample_ref: SampleRef::DevRoot(PathBuf::from("drums/kick.wav")),
-                    audio_offset: 0,
-                    name: "Kick".to_string(),
-                }],
-                volume: 1.0,
-                pan: 0.0,
-                enabled: true,
-                solo: false,
-            }],
-        };
+        let sample_ref = SampleRef::DevRoot(PathBuf::from("drums/kick.wav"));
+        let project = project(
+            "Dev Root Test",
+            vec![track(1, "Drums", vec![clip("Kick", sample_ref, 0)])],
+        );
 
         let file = std::fs::File::create(&project_path).expect("create");
         let writer = std::io::BufWriter::new(file);
         serde_json::to_writer(writer, &project).expect("encode");
 
-        let ctx = PathContext::from_project_path(&project_path)
-            .with_dev_root(dir.path().to_path_buf());
+        let ctx =
+            PathContext::from_project_path(&project_path).with_dev_root(dir.path().to_path_buf());
         let loaded = load_project(&project_path, &ctx).expect("load");
 
         assert_eq!(loaded.tracks[0].clips()[0].start_tick, 0);
@@ -303,26 +1062,15 @@ mod tests {
         let dir = tempdir().expect("tempdir");
         let project_path = dir.path().join("test.dawproj");
 
-        let project = Project {
-            name: "Missing Audio".to_string(),
-            tempo: 120.0,
-            time_signature: (4, 4),
-            tracks: vec![TrackData {
-                id: 1,
-                name: "Missing Track".to_string(),
-                clips: vec![ClipData {
-                    start_tick: 0,
-                    end_tick: 960,
-                    sample_ref: SampleRef::ProjectRelative(PathBuf::from("nonexistent.wav")),
-                    audio_offset: 0,
-                    name: "Missing Clip".to_string(),
-                }],
-                volume: 1.0,
-                pan: 0.0,
-                enabled: true,
-                solo: false,
-            }],
-        };
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("nonexistent.wav"));
+        let project = project(
+            "Missing Audio",
+            vec![track(
+                1,
+                "Missing Track",
+                vec![clip("Missing Clip", sample_ref, 0)],
+            )],
+        );
 
         let file = std::fs::File::create(&project_path).expect("create");
         let writer = std::io::BufWriter::new(file);
@@ -342,16 +1090,62 @@ mod tests {
         assert!(loaded.offline_clips[0].error.contains("Sample not found"));
     }
 
+    #[test]
+    fn test_offline_clip_is_relinked_onto_its_track() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("missing.dawproj");
+        let mut clip = ClipData {
+            audio_offset: 2,
+            gain: 0.5,
+            reversed: true,
+            ..clip(
+                "kick",
+                SampleRef::ProjectRelative(PathBuf::from("audio/kick.wav")),
+                960,
+            )
+        };
+        let project = project("Missing", vec![track(1, "Drums", vec![clip.clone()])]);
+        std::fs::write(&project_path, serde_json::to_vec(&project).unwrap()).unwrap();
+
+        let ctx = PathContext::from_project_path(&project_path);
+        let mut loaded = load_project(&project_path, &ctx).expect("load");
+        // Given an id, so it keeps the same one however often it's saved offline
+        clip.id = Some(0);
+        assert_eq!(loaded.offline_clips[0].clip.id, clip.id);
+
+        let moved = dir.path().join("moved/kick.wav");
+        std::fs::create_dir_all(moved.parent().unwrap()).unwrap();
+        write_pcm_wav(&moved, &[0, 100, 200, 300]);
+        assert!(
+            loaded
+                .relink(0, &dir.path().join("nope.wav"), None, &ctx)
+                .is_err()
+        );
+        assert_eq!(loaded.offline_clips.len(), 1);
+
+        let id = loaded.relink(0, &moved, None, &ctx).expect("relink");
+        assert_eq!(id, ClipId(0));
+        assert!(loaded.offline_clips.is_empty());
+        assert_eq!(
+            loaded.sample_refs["kick"],
+            SampleRef::ProjectRelative(PathBuf::from("moved/kick.wav"))
+        );
+        let relinked = &loaded.tracks[0].clips()[0];
+        assert_eq!((relinked.start_tick, relinked.audio_offset), (960, 2));
+        assert_eq!(relinked.gain, 0.5);
+        assert!(relinked.reversed);
+        assert_eq!(relinked.audio().unwrap().frames(), 4);
+    }
+
     #[test]
     fn test_load_empty_project() {
         let dir = tempdir().expect("tempdir");
         let project_path = dir.path().join("empty.dawproj");
 
         let project = Project {
-            name: "Empty".to_string(),
             tempo: 90.0,
             time_signature: (6, 8),
-            tracks: vec![],
+            ..project("Empty", vec![])
         };
 
         let file = std::fs::File::create(&project_path).expect("create");
@@ -368,4 +1162,39 @@ mod tests {
         assert!(loaded.sample_refs.is_empty());
         assert!(loaded.offline_clips.is_empty());
     }
+
+    #[test]
+    fn test_load_project_sorts_tempo_map() {
+        let dir = tempdir().expect("tempdir");
+        let project_path = dir.path().join("tempo.dawproj");
+        let tempo = |tick, bpm| TempoChange { tick, bpm };
+        let project = Project {
+            tempo_map: TempoMap {
+                tempo_changes: vec![tempo(7680, 90.0), tempo(3840, 0.0), tempo(1920, 140.0)],
+                time_signature_changes: vec![TimeSignatureChange {
+                    tick: 3840,
+                    time_signature: (7, 8),
+                }],
+            },
+            ..project("Tempo", vec![])
+        };
+        std::fs::write(&project_path, serde_json::to_vec(&project).unwrap()).unwrap();
+
+        let ctx = PathContext::from_project_path(&project_path);
+        let loaded = load_project(&project_path, &ctx).expect("load");
+
+        // In order, without the change to a tempo of zero
+        assert_eq!(
+            loaded.tempo_map.tempo_changes,
+            vec![tempo(1920, 140.0), tempo(7680, 90.0)]
+        );
+        assert_eq!(loaded.tempo_map.tempo_at(loaded.tempo, 0), 120.0);
+        assert_eq!(loaded.tempo_map.tempo_at(loaded.tempo, 5000), 140.0);
+        assert_eq!(
+            loaded
+                .tempo_map
+                .time_signature_at(loaded.time_signature, 3840),
+            (7, 8)
+        );
+    }
 }
@@ -0,0 +1,307 @@
+//! Standard MIDI File export, for continuing an arrangement in another DAW.
+//!
+//! The file is type 1 at the project's own resolution ([`PPQN`] ticks per quarter
+//! note), so positions carry over exactly. The first track holds the tempo, time
+//! signatures and locators (as markers); every track with MIDI clips follows as a
+//! track of its own, with its clips' notes on one channel. Audio isn't part of a
+//! MIDI file, so audio clips are left out.
+
+use crate::{ProjectError, TempoMap};
+use daw_transport::{ClipContent, PPQN, Track};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Slowest tempo a MIDI file can hold: the longest quarter note fits in 24 bits
+const MAX_MICROS_PER_QUARTER: u64 = 0xFF_FFFF;
+
+/// Write the arrangement to a Standard MIDI File at `path`.
+///
+/// `tempo` and `time_signature` are what the arrangement starts with and
+/// `tempo_map` holds the changes after that. Locators become markers named after
+/// their numbers.
+pub fn export_midi(
+    path: &Path,
+    name: &str,
+    tempo: f64,
+    time_signature: (u32, u32),
+    tempo_map: &TempoMap,
+    locators: &BTreeMap<u8, u64>,
+    tracks: &[Track],
+) -> Result<(), ProjectError> {
+    let bytes = encode(name, tempo, time_signature, tempo_map, locators, tracks);
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+fn encode(
+    name: &str,
+    tempo: f64,
+    time_signature: (u32, u32),
+    tempo_map: &TempoMap,
+    locators: &BTreeMap<u8, u64>,
+    tracks: &[Track],
+) -> Vec<u8> {
+    let mut chunks = vec![conductor_track(
+        name,
+        tempo,
+        time_signature,
+        tempo_map,
+        locators,
+    )];
+    let note_tracks = tracks.iter().filter(|track| {
+        track
+            .clips()
+            .iter()
+            .any(|clip| matches!(clip.content, ClipContent::Midi(_)))
+    });
+    for (index, track) in note_tracks.enumerate() {
+        chunks.push(note_track(track, (index % 16) as u8));
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"MThd");
+    bytes.extend_from_slice(&6u32.to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&(chunks.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&(PPQN as u16).to_be_bytes());
+    for events in chunks {
+        let data = encode_events(events);
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&data);
+    }
+    bytes
+}
+
+/// An event at an absolute tick, as the bytes that follow its delta time
+type Event = (u64, Vec<u8>);
+
+/// Tempo, time signatures and markers
+fn conductor_track(
+    name: &str,
+    tempo: f64,
+    time_signature: (u32, u32),
+    tempo_map: &TempoMap,
+    locators: &BTreeMap<u8, u64>,
+) -> Vec<Event> {
+    let mut events = vec![(0, meta(0x03, name.as_bytes()))];
+    events.push((0, tempo_event(tempo)));
+    events.push((0, time_signature_event(time_signature)));
+    for change in &tempo_map.tempo_changes {
+        events.push((change.tick, tempo_event(change.bpm)));
+    }
+    for change in &tempo_map.time_signature_changes {
+        events.push((change.tick, time_signature_event(change.time_signature)));
+    }
+    for (number, &tick) in locators {
+        events.push((tick, meta(0x06, format!("Locator {}", number).as_bytes())));
+    }
+    events
+}
+
+/// The notes of a track's MIDI clips on `channel`, cut off where each clip ends
+fn note_track(track: &Track, channel: u8) -> Vec<Event> {
+    let mut events = vec![(0, meta(0x03, track.name.as_bytes()))];
+    // Note-offs sort before note-ons at the same tick, so repeated notes retrigger
+    let mut notes: Vec<(u64, bool, u8, u8)> = Vec::new();
+    for clip in track.clips() {
+        let ClipContent::Midi(note_list) = &clip.content else {
+            continue;
+        };
+        for note in note_list.notes() {
+            let start = clip.start_tick + note.start_tick;
+            let end = (clip.start_tick + note.end_tick()).min(clip.end_tick);
+            if start >= end {
+                continue;
+            }
+            notes.push((start, true, note.pitch & 0x7F, note.velocity.clamp(1, 127)));
+            notes.push((end, false, note.pitch & 0x7F, 0));
+        }
+    }
+    notes.sort_by_key(|&(tick, on, _, _)| (tick, on));
+    for (tick, on, pitch, velocity) in notes {
+        let status = if on { 0x90 } else { 0x80 } | channel;
+        events.push((tick, vec![status, pitch, velocity]));
+    }
+    events
+}
+
+fn meta(kind: u8, data: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0xFF, kind];
+    write_var_len(&mut bytes, data.len() as u64);
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+fn tempo_event(bpm: f64) -> Vec<u8> {
+    let micros = (60_000_000.0 / bpm).round() as u64;
+    let micros = micros.clamp(1, MAX_MICROS_PER_QUARTER) as u32;
+    meta(0x51, &micros.to_be_bytes()[1..])
+}
+
+fn time_signature_event((numerator, denominator): (u32, u32)) -> Vec<u8> {
+    // The beat unit is stored as a power of two; 24 clocks per click, 8 32nds per quarter
+    let power = denominator.max(1).ilog2() as u8;
+    meta(0x58, &[numerator.clamp(1, 255) as u8, power, 24, 8])
+}
+
+/// Sort `events` by tick, keeping the order of events at the same tick, and encode
+/// them with delta times and an end-of-track event
+fn encode_events(mut events: Vec<Event>) -> Vec<u8> {
+    events.sort_by_key(|(tick, _)| *tick);
+    let end = events.last().map_or(0, |(tick, _)| *tick);
+    events.push((end, vec![0xFF, 0x2F, 0x00]));
+
+    let mut bytes = Vec::new();
+    let mut last = 0;
+    for (tick, event) in events {
+        write_var_len(&mut bytes, tick - last);
+        bytes.extend_from_slice(&event);
+        last = tick;
+    }
+    bytes
+}
+
+/// Write `value` as a MIDI variable-length quantity, seven bits per byte
+fn write_var_len(bytes: &mut Vec<u8>, value: u64) {
+    let mut groups = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        groups.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    bytes.extend(groups.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TempoChange, TimeSignatureChange};
+    use daw_transport::{
+        AudioArc, Clip, ClipId, ClipSource, IdAllocator, Note, NoteList, TrackId, WaveformData,
+    };
+    use std::sync::Arc;
+
+    fn midi_clip(start_tick: u64, end_tick: u64, notes: Vec<Note>) -> Clip {
+        Clip {
+            id: ClipId(0),
+            start_tick,
+            end_tick,
+            content: ClipContent::Midi(NoteList::new(notes)),
+            waveform: Arc::new(WaveformData::from_audio_arc(
+                &AudioArc::new(vec![], 48000, 1),
+                512,
+            )),
+            audio_offset: 0,
+            name: "notes".to_string(),
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: ClipSource::default(),
+        }
+    }
+
+    /// Split a file into its chunks
+    fn chunks(bytes: &[u8]) -> Vec<(&[u8], &[u8])> {
+        let mut chunks = Vec::new();
+        let mut rest = bytes;
+        while rest.len() >= 8 {
+            let len = u32::from_be_bytes(rest[4..8].try_into().unwrap()) as usize;
+            chunks.push((&rest[..4], &rest[8..8 + len]));
+            rest = &rest[8 + len..];
+        }
+        chunks
+    }
+
+    #[test]
+    fn test_var_len_quantities() {
+        let encode = |value| {
+            let mut bytes = Vec::new();
+            write_var_len(&mut bytes, value);
+            bytes
+        };
+        assert_eq!(encode(0), vec![0x00]);
+        assert_eq!(encode(0x7F), vec![0x7F]);
+        assert_eq!(encode(0x80), vec![0x81, 0x00]);
+        assert_eq!(encode(960), vec![0x87, 0x40]);
+        assert_eq!(encode(0x0FFF_FFFF), vec![0xFF, 0xFF, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn test_arrangement_is_written_as_type_1_file() {
+        let mut ids = IdAllocator::default();
+        let note = |start_tick, pitch| Note {
+            start_tick,
+            duration_ticks: 480,
+            pitch,
+            velocity: 100,
+        };
+        let mut keys = Track::new(TrackId(0), "Keys".to_string());
+        // The second note runs past the clip's end and is cut off there
+        keys.insert_clip(
+            midi_clip(960, 1920, vec![note(0, 60), note(720, 64)]),
+            &mut ids,
+        );
+        let audio_only = Track::new(TrackId(1), "Drums".to_string());
+        let tempo_map = TempoMap {
+            tempo_changes: vec![TempoChange {
+                tick: 3840,
+                bpm: 60.0,
+            }],
+            time_signature_changes: vec![TimeSignatureChange {
+                tick: 3840,
+                time_signature: (6, 8),
+            }],
+        };
+        let locators = BTreeMap::from([(2, 1920)]);
+
+        let bytes = encode(
+            "Song",
+            120.0,
+            (4, 4),
+            &tempo_map,
+            &locators,
+            &[keys, audio_only],
+        );
+        let chunks = chunks(&bytes);
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].0, b"MThd");
+        assert_eq!(chunks[0].1, &[0, 1, 0, 2, 0x03, 0xC0]);
+
+        let conductor = chunks[1].1;
+        assert_eq!(
+            conductor,
+            [
+                &[0x00, 0xFF, 0x03, 4][..],
+                b"Song",
+                // 500000 microseconds per quarter at 120 BPM, then 4/4
+                &[0x00, 0xFF, 0x51, 3, 0x07, 0xA1, 0x20],
+                &[0x00, 0xFF, 0x58, 4, 4, 2, 24, 8],
+                &[0x8F, 0x00, 0xFF, 0x06, 9],
+                b"Locator 2",
+                // One second per quarter at 60 BPM, then 6/8
+                &[0x8F, 0x00, 0xFF, 0x51, 3, 0x0F, 0x42, 0x40],
+                &[0x00, 0xFF, 0x58, 4, 6, 3, 24, 8],
+                &[0x00, 0xFF, 0x2F, 0x00],
+            ]
+            .concat()
+        );
+
+        let keys = chunks[2].1;
+        assert_eq!(
+            keys,
+            [
+                &[0x00, 0xFF, 0x03, 4][..],
+                b"Keys",
+                &[0x87, 0x40, 0x90, 60, 100],
+                &[0x83, 0x60, 0x80, 60, 0],
+                &[0x81, 0x70, 0x90, 64, 100],
+                &[0x81, 0x70, 0x80, 64, 0],
+                &[0x00, 0xFF, 0x2F, 0x00],
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,260 @@
+//! Waveform peaks stored next to a project file and in the user cache directory.
+//!
+//! Computing peaks means reading every sample of every clip, which dominates load
+//! time for large projects. The peak file keeps them between loads, keyed by sample
+//! reference and sample rate. Each entry records the size and modification time of
+//! its source file so edited audio is recomputed, and a content hash so a damaged
+//! entry is never drawn.
+//!
+//! [`PeakCache`] backs the peak file with a directory shared by all projects, keyed
+//! by the contents of the audio file, so a sample used in many projects has its
+//! peaks computed once.
+
+use crate::SampleRef;
+use daw_transport::WaveformData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Path of the peak file for a project, e.g. `song.dawproj` -> `song.dawpeaks`
+pub fn peaks_path(project_path: &Path) -> PathBuf {
+    project_path.with_extension("dawpeaks")
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct PeakFile {
+    entries: HashMap<String, PeakEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PeakEntry {
+    source_len: u64,
+    source_modified: SystemTime,
+    hash: u64,
+    waveform: WaveformData,
+}
+
+impl PeakFile {
+    /// Read a peak file, treating a missing or unreadable file as empty
+    pub(crate) fn load(path: &Path) -> Self {
+        File::open(path)
+            .map(|file| Self::read(BufReader::new(file)))
+            .unwrap_or_default()
+    }
+
+    /// Read a peak file from `reader`, treating an unreadable one as empty
+    pub(crate) fn read(reader: impl Read) -> Self {
+        rmp_serde::decode::from_read(reader).unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> anyhow::Result<()> {
+        // Write to a temporary file first so a crash never leaves a partial peak file
+        let temp = path.with_extension("dawpeaks.tmp");
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        rmp_serde::encode::write(&mut writer, self)?;
+        drop(writer);
+        fs::rename(&temp, path)?;
+        Ok(())
+    }
+
+    /// Stored peaks for `sample_ref` at `sample_rate`, if `source` hasn't changed
+    pub(crate) fn get(
+        &self,
+        sample_ref: &SampleRef,
+        sample_rate: u32,
+        source: &Path,
+    ) -> Option<WaveformData> {
+        let entry = self.entries.get(&entry_key(sample_ref, sample_rate))?;
+        let (len, modified) = source_identity(source)?;
+        (entry.source_len == len
+            && entry.source_modified == modified
+            && entry.hash == entry.waveform.content_hash())
+        .then(|| entry.waveform.clone())
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        sample_ref: &SampleRef,
+        sample_rate: u32,
+        source: &Path,
+        waveform: &WaveformData,
+    ) {
+        let Some((source_len, source_modified)) = source_identity(source) else {
+            return;
+        };
+        self.entries.insert(
+            entry_key(sample_ref, sample_rate),
+            PeakEntry {
+                source_len,
+                source_modified,
+                hash: waveform.content_hash(),
+                waveform: waveform.clone(),
+            },
+        );
+    }
+}
+
+/// Directory of waveform peaks shared between projects.
+///
+/// Entries are named by the content hash of their audio file (see
+/// [`daw_decode::AudioCache::known_content_hash`]) and the sample rate the peaks were
+/// computed at. Like the decoded audio cache, every failure is a cache miss.
+#[derive(Debug, Clone)]
+pub struct PeakCache {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedPeaks {
+    hash: u64,
+    waveform: WaveformData,
+}
+
+impl PeakCache {
+    /// Create a cache in `dir`, which is created on first write.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Create a cache in the platform's user cache directory, e.g. `~/.cache/daw/peaks`.
+    ///
+    /// Returns `None` if the cache directory can't be determined.
+    pub fn in_default_location() -> Option<Self> {
+        daw_decode::user_cache_dir().map(|dir| Self::new(dir.join("peaks")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Stored peaks for audio with `content_hash` at `sample_rate`
+    pub fn get(&self, content_hash: u64, sample_rate: u32) -> Option<WaveformData> {
+        let file = File::open(self.entry_path(content_hash, sample_rate)).ok()?;
+        let cached: CachedPeaks = rmp_serde::decode::from_read(BufReader::new(file)).ok()?;
+        (cached.hash == cached.waveform.content_hash()).then_some(cached.waveform)
+    }
+
+    /// Store peaks for audio with `content_hash` at `sample_rate`
+    pub fn put(
+        &self,
+        content_hash: u64,
+        sample_rate: u32,
+        waveform: &WaveformData,
+    ) -> anyhow::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.entry_path(content_hash, sample_rate);
+        // Another process may read the entry while it's written, so write it aside first
+        let temp = path.with_extension(format!("tmp{}", std::process::id()));
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        rmp_serde::encode::write(
+            &mut writer,
+            &CachedPeaks {
+                hash: waveform.content_hash(),
+                waveform: waveform.clone(),
+            },
+        )?;
+        drop(writer);
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    /// Delete every entry in the cache
+    pub fn clear(&self) -> anyhow::Result<()> {
+        match fs::remove_dir_all(&self.dir) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn entry_path(&self, content_hash: u64, sample_rate: u32) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}-{}.peaks", content_hash, sample_rate))
+    }
+}
+
+fn entry_key(sample_ref: &SampleRef, sample_rate: u32) -> String {
+    format!("{}@{}", sample_ref, sample_rate)
+}
+
+fn source_identity(source: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::metadata(source).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn waveform() -> WaveformData {
+        WaveformData {
+            peaks: vec![(-0.5, 0.5), (-0.25, 0.75)],
+            samples_per_bucket: 512,
+        }
+    }
+
+    #[test]
+    fn test_peak_file_round_trip() {
+        let dir = tempdir().expect("tempdir");
+        let source = dir.path().join("kick.wav");
+        fs::write(&source, b"audio").expect("write");
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("kick.wav"));
+
+        let mut peaks = PeakFile::default();
+        peaks.insert(&sample_ref, 44100, &source, &waveform());
+        let path = peaks_path(&dir.path().join("song.dawproj"));
+        peaks.save(&path).expect("save");
+
+        let loaded = PeakFile::load(&path);
+        assert_eq!(loaded.get(&sample_ref, 44100, &source), Some(waveform()));
+        assert_eq!(loaded.get(&sample_ref, 48000, &source), None);
+    }
+
+    #[test]
+    fn test_changed_source_is_stale() {
+        let dir = tempdir().expect("tempdir");
+        let source = dir.path().join("kick.wav");
+        fs::write(&source, b"audio").expect("write");
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("kick.wav"));
+
+        let mut peaks = PeakFile::default();
+        peaks.insert(&sample_ref, 44100, &source, &waveform());
+        fs::write(&source, b"longer audio").expect("write");
+
+        assert_eq!(peaks.get(&sample_ref, 44100, &source), None);
+    }
+
+    #[test]
+    fn test_corrupt_entry_is_ignored() {
+        let dir = tempdir().expect("tempdir");
+        let source = dir.path().join("kick.wav");
+        fs::write(&source, b"audio").expect("write");
+        let sample_ref = SampleRef::ProjectRelative(PathBuf::from("kick.wav"));
+
+        let mut peaks = PeakFile::default();
+        peaks.insert(&sample_ref, 44100, &source, &waveform());
+        for entry in peaks.entries.values_mut() {
+            entry.waveform.peaks[0].0 = 0.0;
+        }
+
+        assert_eq!(peaks.get(&sample_ref, 44100, &source), None);
+    }
+
+    #[test]
+    fn test_peak_cache_round_trip() {
+        let dir = tempdir().expect("tempdir");
+        let cache = PeakCache::new(dir.path().join("peaks"));
+
+        assert_eq!(cache.get(0xfeed, 44100), None);
+        cache.put(0xfeed, 44100, &waveform()).expect("put");
+        assert_eq!(cache.get(0xfeed, 44100), Some(waveform()));
+        assert_eq!(cache.get(0xfeed, 48000), None);
+        assert_eq!(cache.get(0xbeef, 44100), None);
+
+        cache.clear().expect("clear");
+        assert_eq!(cache.get(0xfeed, 44100), None);
+    }
+}
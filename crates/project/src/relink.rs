@@ -0,0 +1,184 @@
+//! Bringing back clips whose samples went missing.
+//!
+//! Loading keeps a clip whose sample can't be found as an [`OfflineClip`], holding
+//! everything the project saved about it. Once the file turns up somewhere else,
+//! [`OfflineClip::relink`] decodes it and rebuilds the clip, and
+//! [`find_missing_samples`] looks through a folder for files with the names the
+//! missing samples had.
+
+use crate::{LoadedProject, OfflineClip, PathContext, ProjectError, SampleRef};
+use daw_transport::{Clip, ClipContent, ClipId, IdAllocator, WaveformData};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+impl OfflineClip {
+    /// Rebuild the clip with its audio from the file at `path`.
+    ///
+    /// The audio is decoded through `cache`, resampled to `target_sample_rate` if
+    /// given, and the clip keeps everything else it was saved with. Returns the
+    /// clip and the reference the project should store for its new file, made with
+    /// `ctx`.
+    pub fn relink(
+        &self,
+        path: &Path,
+        cache: &mut daw_decode::AudioCache,
+        target_sample_rate: Option<u32>,
+        ctx: &PathContext,
+        ids: &mut IdAllocator,
+    ) -> Result<(Clip, SampleRef), ProjectError> {
+        let path = std::path::absolute(path)?;
+        let audio = cache
+            .get_or_load_direct(&path, target_sample_rate)
+            .map_err(|source| ProjectError::AudioDecode {
+                path: path.clone(),
+                source,
+            })?;
+        let clip_data = &self.clip;
+        let clip = Clip {
+            id: clip_data.id.map(ClipId).unwrap_or_else(|| ids.clip_id()),
+            start_tick: clip_data.start_tick,
+            end_tick: clip_data.end_tick,
+            waveform: Arc::new(WaveformData::from_audio_arc(&audio, 512)),
+            content: ClipContent::Audio(audio),
+            audio_offset: clip_data.audio_offset,
+            name: clip_data.name.clone(),
+            fade_in_ticks: clip_data.fade_in_ticks,
+            fade_out_ticks: clip_data.fade_out_ticks,
+            gain: clip_data.gain,
+            looped: clip_data.looped,
+            reversed: clip_data.reversed,
+            source: clip_data.source.clone(),
+        };
+        Ok((clip, ctx.sample_ref_for(&path)))
+    }
+}
+
+impl LoadedProject {
+    /// Relink the offline clip at `index` to the file at `path` and put it back on
+    /// its track (see [`OfflineClip::relink`]).
+    ///
+    /// Returns the clip's id. If the file can't be decoded, the clip stays offline.
+    pub fn relink(
+        &mut self,
+        index: usize,
+        path: &Path,
+        target_sample_rate: Option<u32>,
+        ctx: &PathContext,
+    ) -> Result<ClipId, ProjectError> {
+        let (clip, sample_ref) = self.offline_clips[index].relink(
+            path,
+            &mut self.cache,
+            target_sample_rate,
+            ctx,
+            &mut self.ids,
+        )?;
+        let offline = self.offline_clips.remove(index);
+        let id = clip.id;
+        self.sample_refs.insert(clip.name.clone(), sample_ref);
+        if let Some(track) = self
+            .tracks
+            .iter_mut()
+            .find(|track| track.id == offline.track_id)
+        {
+            track.insert_clip(clip, &mut self.ids);
+        }
+        Ok(id)
+    }
+}
+
+/// Look through `folder` and the folders inside it for the missing samples of
+/// `offline_clips`, by file name.
+///
+/// Returns the index of each offline clip whose sample was found with the file to
+/// relink it to. Where several files have the name, the one the fewest folders
+/// down is used, then the first by path.
+pub fn find_missing_samples(offline_clips: &[OfflineClip], folder: &Path) -> Vec<(usize, PathBuf)> {
+    let mut files: HashMap<std::ffi::OsString, PathBuf> = HashMap::new();
+    let mut folders = vec![folder.to_path_buf()];
+    while !folders.is_empty() {
+        let mut next = Vec::new();
+        for folder in folders {
+            let Ok(entries) = std::fs::read_dir(&folder) else {
+                continue;
+            };
+            let mut paths: Vec<PathBuf> = entries.flatten().map(|entry| entry.path()).collect();
+            paths.sort();
+            for path in paths {
+                if path.is_dir() {
+                    next.push(path);
+                } else if let Some(name) = path.file_name() {
+                    files.entry(name.to_os_string()).or_insert(path);
+                }
+            }
+        }
+        next.sort();
+        folders = next;
+    }
+
+    offline_clips
+        .iter()
+        .enumerate()
+        .filter_map(|(index, offline)| {
+            let name = offline.sample_ref.path().file_name()?;
+            Some((index, files.get(name)?.clone()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClipData;
+    use daw_transport::TrackId;
+    use tempfile::tempdir;
+
+    fn offline(name: &str, path: &str) -> OfflineClip {
+        let sample_ref = SampleRef::ProjectRelative(path.into());
+        OfflineClip {
+            track_id: TrackId(0),
+            sample_ref: sample_ref.clone(),
+            start_tick: 960,
+            end_tick: 1920,
+            name: name.to_string(),
+            error: "Sample not found".to_string(),
+            clip: ClipData {
+                id: Some(7),
+                start_tick: 960,
+                end_tick: 1920,
+                sample_ref,
+                audio_offset: 100,
+                name: name.to_string(),
+                fade_in_ticks: 48,
+                fade_out_ticks: 0,
+                gain: 0.5,
+                looped: false,
+                reversed: true,
+                source: Default::default(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_missing_samples_are_found_by_name() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("drums/old")).unwrap();
+        std::fs::write(dir.path().join("drums/kick.wav"), b"kick").unwrap();
+        std::fs::write(dir.path().join("drums/old/kick.wav"), b"old kick").unwrap();
+        std::fs::write(dir.path().join("drums/old/snare.wav"), b"snare").unwrap();
+
+        let offline_clips = [
+            offline("kick", "audio/kick.wav"),
+            offline("hat", "audio/hat.wav"),
+            offline("snare", "/somewhere/else/snare.wav"),
+        ];
+        let found = find_missing_samples(&offline_clips, dir.path());
+        assert_eq!(
+            found,
+            vec![
+                (0, dir.path().join("drums/kick.wav")),
+                (2, dir.path().join("drums/old/snare.wav")),
+            ]
+        );
+    }
+}
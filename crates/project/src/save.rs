@@ -1,22 +1,82 @@
-use crate::{ClipData, Project, ProjectError, SampleRef, TrackData};
-use daw_transport::Track;
-use std::collections::HashMap;
+use crate::container::write_zip;
+use crate::peaks::peaks_path;
+use crate::{
+    ClipData, EmbeddedAudio, EndBehavior, MasterData, OfflineClip, Project, ProjectError,
+    ProjectInfo, SampleRef, SendData, SessionState, TempoMap, TrackData,
+};
+use daw_transport::{IdAllocator, Track};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::fs::File;
-use std::io::BufWriter;
-use std::path::Path;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
 
+/// Encoding a project file is written in. Loading accepts either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectFormat {
+    /// Readable and diffable, but large for big projects
+    #[default]
+    Json,
+    /// Compact binary MessagePack
+    MessagePack,
+}
+
+/// How [`save_project`] writes the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveOptions {
+    pub format: ProjectFormat,
+    /// Compress the file with zstd
+    pub compress: bool,
+    /// Keep this many earlier versions of the file, as `song.dawproj.bak.1`
+    /// (the newest) to `song.dawproj.bak.N`
+    pub backups: usize,
+    /// Write a zip archive holding `project.json`, the peak file and the embedded
+    /// samples, instead of a bare project file. The project inside is always JSON,
+    /// so `format` and `compress` don't apply.
+    pub zip: bool,
+}
+
+/// Path of the `n`th newest backup [`save_project`] keeps of the file at `path`
+pub fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak.{}", n));
+    path.with_file_name(name)
+}
+
+/// Write a project file to `path`.
+///
+/// The file is written next to `path` first and renamed over it once complete,
+/// so a crash mid-save leaves the previous version intact. With
+/// [`SaveOptions::backups`] set, earlier versions are kept as numbered backups.
+#[allow(clippy::too_many_arguments)]
 pub fn save_project(
     path: &Path,
     name: String,
+    info: &ProjectInfo,
     tempo: f64,
     time_signature: (u32, u32),
+    tempo_map: &TempoMap,
+    sample_rate: Option<u32>,
+    end_behavior: EndBehavior,
+    locators: &BTreeMap<u8, u64>,
+    master: &MasterData,
     tracks: &[Track],
+    offline_clips: &[OfflineClip],
+    ids: &IdAllocator,
     sample_refs: &HashMap<String, SampleRef>,
+    embedded: BTreeMap<String, EmbeddedAudio>,
+    session_state: Option<SessionState>,
+    options: &SaveOptions,
 ) -> Result<(), ProjectError> {
     let project = Project {
         name,
+        info: info.clone(),
         tempo,
         time_signature,
+        tempo_map: tempo_map.clone(),
+        sample_rate,
         tracks: tracks
             .iter()
             .map(|track| TrackData {
@@ -26,35 +86,141 @@ pub fn save_project(
                     .clips()
                     .iter()
                     .filter_map(|clip| {
+                        // MIDI clips aren't stored in the project format yet
+                        clip.audio()?;
                         // Only save clips that have a sample reference
                         sample_refs.get(&clip.name).map(|sample_ref| ClipData {
+                            id: Some(clip.id.0),
                             name: clip.name.clone(),
                             start_tick: clip.start_tick,
                             end_tick: clip.end_tick,
                             audio_offset: clip.audio_offset,
                             sample_ref: sample_ref.clone(),
+                            fade_in_ticks: clip.fade_in_ticks,
+                            fade_out_ticks: clip.fade_out_ticks,
+                            gain: clip.gain,
+                            looped: clip.looped,
+                            reversed: clip.reversed,
+                            source: clip.source.clone(),
                         })
                     })
+                    // Clips waiting for their samples are kept as they were loaded
+                    .chain(
+                        offline_clips
+                            .iter()
+                            .filter(|offline| offline.track_id == track.id)
+                            .map(|offline| offline.clip.clone()),
+                    )
                     .collect(),
                 volume: track.volume,
                 pan: track.pan,
                 enabled: track.enabled,
                 solo: track.solo,
+                color: track.color,
+                metadata: track.metadata.clone(),
+                parent: track.parent.as_ref().map(|parent| parent.0),
+                is_folder: track.is_folder,
+                automation: track.automation.clone(),
+                effects: track.effects.clone(),
+                is_bus: track.is_bus,
+                sends: track
+                    .sends
+                    .iter()
+                    .map(|send| SendData {
+                        bus: send.bus.0,
+                        level: send.level,
+                        pre_fader: send.pre_fader,
+                    })
+                    .collect(),
             })
             .collect(),
+        next_track_id: ids.next_track_id(),
+        next_clip_id: ids.next_clip_id(),
+        end_behavior,
+        locators: locators.clone(),
+        embedded,
+        session_state,
+        master: master.clone(),
     };
 
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
-    serde_json::to_writer_pretty(writer, &project)?;
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let result = write_file(&temp, project, &peaks_path(path), options)
+        .and_then(|()| Ok(rotate_backups(path, options.backups)?))
+        .and_then(|()| Ok(std::fs::rename(&temp, path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    result
+}
 
+/// Write `project` to a new file at `path` and make sure it's on disk.
+///
+/// A zip archive takes in the peak file at `peaks`, if there is one.
+fn write_file(
+    path: &Path,
+    project: Project,
+    peaks: &Path,
+    options: &SaveOptions,
+) -> Result<(), ProjectError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    if options.zip {
+        let peaks = std::fs::read(peaks).ok();
+        writer = write_zip(writer, project, peaks.as_deref())?;
+    } else if options.compress {
+        let mut encoder = zstd::Encoder::new(writer, 0)?;
+        write_encoded(&mut encoder, &project, options.format)?;
+        writer = encoder.finish()?;
+    } else {
+        write_encoded(&mut writer, &project, options.format)?;
+    }
+    writer.flush()?;
+    writer.get_ref().sync_all()?;
+    Ok(())
+}
+
+/// Shift the backups of `path` up by one, dropping the oldest of `count`, and keep
+/// the current file as the newest
+fn rotate_backups(path: &Path, count: usize) -> std::io::Result<()> {
+    if count == 0 || !path.is_file() {
+        return Ok(());
+    }
+    match std::fs::remove_file(backup_path(path, count)) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    for n in (1..count).rev() {
+        let backup = backup_path(path, n);
+        if backup.exists() {
+            std::fs::rename(&backup, backup_path(path, n + 1))?;
+        }
+    }
+    // A hard link keeps the current file in place until the new one replaces it
+    let newest = backup_path(path, 1);
+    if std::fs::hard_link(path, &newest).is_err() {
+        std::fs::copy(path, &newest)?;
+    }
+    Ok(())
+}
+
+fn write_encoded(
+    writer: &mut impl Write,
+    project: &Project,
+    format: ProjectFormat,
+) -> Result<(), ProjectError> {
+    match format {
+        ProjectFormat::Json => serde_json::to_writer_pretty(writer, project)?,
+        // Fields are written by name, since optional ones are left out when empty
+        ProjectFormat::MessagePack => rmp_serde::encode::write_named(writer, project)?,
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use daw_transport::{AudioArc, Clip, Track, TrackId, WaveformData};
+    use daw_transport::{AudioArc, Clip, ClipContent, ClipId, Track, TrackId, WaveformData};
     use std::path::PathBuf;
     use std::sync::Arc;
     use tempfile::tempdir;
@@ -63,24 +229,45 @@ mod tests {
         let audio = AudioArc::new(vec![0.0; 1000], 44100, 2);
         let waveform = Arc::new(WaveformData::from_audio_arc(&audio, 512));
 
+        let mut ids = IdAllocator::default();
         let mut track = Track::new(TrackId(1), "Test Track".to_string());
         track.volume = 0.9;
-        track.insert_clip(Clip {
-            start_tick: 0,
-            end_tick: 960,
-            audio: audio.clone(),
-            waveform: waveform.clone(),
-            audio_offset: 0,
-            name: "Kick".to_string(),
-        });
-        track.insert_clip(Clip {
-            start_tick: 960,
-            end_tick: 1920,
-            audio: audio.clone(),
-            waveform: waveform.clone(),
-            audio_offset: 0,
-            name: "Snare".to_string(),
-        });
+        track.insert_clip(
+            Clip {
+                id: ClipId(0),
+                start_tick: 0,
+                end_tick: 960,
+                content: ClipContent::Audio(audio.clone()),
+                waveform: waveform.clone(),
+                audio_offset: 0,
+                name: "Kick".to_string(),
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source: Default::default(),
+            },
+            &mut ids,
+        );
+        track.insert_clip(
+            Clip {
+                id: ClipId(1),
+                start_tick: 960,
+                end_tick: 1920,
+                content: ClipContent::Audio(audio.clone()),
+                waveform: waveform.clone(),
+                audio_offset: 0,
+                name: "Snare".to_string(),
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source: Default::default(),
+            },
+            &mut ids,
+        );
 
         let mut sample_refs = HashMap::new();
         sample_refs.insert(
@@ -105,10 +292,21 @@ mod tests {
         save_project(
             &path,
             "Test Project".to_string(),
+            &ProjectInfo::default(),
             120.0,
             (4, 4),
+            &TempoMap::default(),
+            None,
+            EndBehavior::Continue,
+            &BTreeMap::new(),
+            &MasterData::default(),
             &[track],
+            &[],
+            &IdAllocator::default(),
             &sample_refs,
+            BTreeMap::new(),
+            None,
+            &SaveOptions::default(),
         )
         .expect("save");
 
@@ -125,10 +323,21 @@ mod tests {
         save_project(
             &path,
             "My Song".to_string(),
+            &ProjectInfo::default(),
             140.0,
             (3, 4),
+            &TempoMap::default(),
+            None,
+            EndBehavior::Continue,
+            &BTreeMap::new(),
+            &MasterData::default(),
             &[track],
+            &[],
+            &IdAllocator::default(),
             &sample_refs,
+            BTreeMap::new(),
+            None,
+            &SaveOptions::default(),
         )
         .expect("save");
 
@@ -148,6 +357,63 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_save_project_as_compressed_messagepack() {
+        let dir = tempdir().expect("tempdir");
+        let (track, sample_refs) = create_test_track();
+        let save = |name: &str, options: SaveOptions| {
+            let path = dir.path().join(name);
+            save_project(
+                &path,
+                "Big Song".to_string(),
+                &ProjectInfo::default(),
+                128.0,
+                (7, 8),
+                &TempoMap::default(),
+                None,
+                EndBehavior::Stop,
+                &BTreeMap::from([(2, 1920)]),
+                &MasterData::default(),
+                std::slice::from_ref(&track),
+                &[],
+                &IdAllocator::default(),
+                &sample_refs,
+                BTreeMap::new(),
+                None,
+                &options,
+            )
+            .expect("save");
+            path
+        };
+        let json = save("song.dawproj", SaveOptions::default());
+        let packed = save(
+            "packed.dawproj",
+            SaveOptions {
+                format: ProjectFormat::MessagePack,
+                compress: true,
+                ..Default::default()
+            },
+        );
+
+        let packed_bytes = std::fs::read(&packed).expect("read");
+        assert!(packed_bytes.len() < std::fs::read(&json).expect("read").len());
+        let unpacked = zstd::decode_all(packed_bytes.as_slice()).expect("decompress");
+        let loaded: crate::Project = rmp_serde::from_slice(&unpacked).expect("decode");
+        assert_eq!(loaded.time_signature, (7, 8));
+        assert_eq!(loaded.locators, BTreeMap::from([(2, 1920)]));
+        assert_eq!(
+            loaded.tracks[0].clips[1].sample_ref,
+            SampleRef::DevRoot(PathBuf::from("drums/snare.wav"))
+        );
+
+        // Loading tells the formats apart by themselves
+        for path in [json, packed] {
+            let metadata = crate::load_project_metadata(&path).expect("load");
+            assert_eq!(metadata.name, "Big Song");
+            assert_eq!(metadata.segment_count, 2);
+        }
+    }
+
     #[test]
     fn test_save_empty_project() {
         let dir = tempdir().expect("tempdir");
@@ -156,10 +422,21 @@ mod tests {
         save_project(
             &path,
             "Empty".to_string(),
+            &ProjectInfo::default(),
             120.0,
             (4, 4),
+            &TempoMap::default(),
+            None,
+            EndBehavior::Loop,
+            &BTreeMap::from([(1, 960), (9, 7680)]),
+            &MasterData::default(),
+            &[],
             &[],
+            &IdAllocator::default(),
             &HashMap::new(),
+            BTreeMap::new(),
+            None,
+            &SaveOptions::default(),
         )
         .expect("save");
 
@@ -168,6 +445,86 @@ mod tests {
         let loaded: crate::Project = serde_json::from_reader(reader).expect("decode");
 
         assert!(loaded.tracks.is_empty());
+        assert_eq!(loaded.end_behavior, EndBehavior::Loop);
+        assert_eq!(loaded.locators, BTreeMap::from([(1, 960), (9, 7680)]));
+    }
+
+    #[test]
+    fn test_saving_keeps_rotating_backups() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("song.dawproj");
+        let options = SaveOptions {
+            backups: 2,
+            ..Default::default()
+        };
+        let save = |name: &str| {
+            save_project(
+                &path,
+                name.to_string(),
+                &ProjectInfo::default(),
+                120.0,
+                (4, 4),
+                &TempoMap::default(),
+                None,
+                EndBehavior::Continue,
+                &BTreeMap::new(),
+                &MasterData::default(),
+                &[],
+                &[],
+                &IdAllocator::default(),
+                &HashMap::new(),
+                BTreeMap::new(),
+                None,
+                &options,
+            )
+            .expect("save");
+        };
+        let name_in = |path: &Path| crate::load_project_metadata(path).expect("load").name;
+
+        save("First");
+        assert!(!backup_path(&path, 1).exists());
+        for name in ["Second", "Third", "Fourth"] {
+            save(name);
+        }
+
+        assert_eq!(name_in(&path), "Fourth");
+        assert_eq!(name_in(&backup_path(&path, 1)), "Third");
+        assert_eq!(name_in(&backup_path(&path, 2)), "Second");
+        assert!(!backup_path(&path, 3).exists());
+        // Nothing is left behind from writing the new versions
+        assert_eq!(std::fs::read_dir(dir.path()).expect("read dir").count(), 3);
+    }
+
+    #[test]
+    fn test_failed_save_leaves_previous_version() {
+        let dir = tempdir().expect("tempdir");
+        let path = dir.path().join("song.dawproj");
+        std::fs::write(&path, b"previous").expect("write");
+        // A folder where the temporary file goes makes writing it fail
+        std::fs::create_dir(dir.path().join("song.dawproj.tmp")).expect("mkdir");
+
+        let result = save_project(
+            &path,
+            "Song".to_string(),
+            &ProjectInfo::default(),
+            120.0,
+            (4, 4),
+            &TempoMap::default(),
+            None,
+            EndBehavior::Continue,
+            &BTreeMap::new(),
+            &MasterData::default(),
+            &[],
+            &[],
+            &IdAllocator::default(),
+            &HashMap::new(),
+            BTreeMap::new(),
+            None,
+            &SaveOptions::default(),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read(&path).expect("read"), b"previous");
     }
 
     #[test]
@@ -178,24 +535,46 @@ mod tests {
         let audio = AudioArc::new(vec![0.0; 100], 44100, 2);
         let waveform = Arc::new(WaveformData::from_audio_arc(&audio, 512));
 
+        let mut ids = IdAllocator::default();
         let mut track = Track::new(TrackId(1), "Track".to_string());
-        track.insert_clip(Clip {
-            start_tick: 0,
-            end_tick: 960,
-            audio,
-            waveform,
-            audio_offset: 0,
-            name: "Clip Without Ref".to_string(),
-        });
+        track.insert_clip(
+            Clip {
+                id: ClipId(2),
+                start_tick: 0,
+                end_tick: 960,
+                content: ClipContent::Audio(audio),
+                waveform,
+                audio_offset: 0,
+                name: "Clip Without Ref".to_string(),
+                fade_in_ticks: 0,
+                fade_out_ticks: 0,
+                gain: 1.0,
+                looped: false,
+                reversed: false,
+                source: Default::default(),
+            },
+            &mut ids,
+        );
 
         // Save with empty sample_refs - clip should be skipped
         save_project(
             &path,
             "Test".to_string(),
+            &ProjectInfo::default(),
             120.0,
             (4, 4),
+            &TempoMap::default(),
+            None,
+            EndBehavior::Continue,
+            &BTreeMap::new(),
+            &MasterData::default(),
             &[track],
+            &[],
+            &IdAllocator::default(),
             &HashMap::new(),
+            BTreeMap::new(),
+            None,
+            &SaveOptions::default(),
         )
         .expect("save");
 
@@ -0,0 +1,207 @@
+//! Checking a project file before loading it.
+//!
+//! Loading decodes every sample, which takes a while for big projects. [`validate`]
+//! only reads the project file and looks for its samples, so frontends can warn
+//! about missing or unreadable audio before starting a long load.
+
+use crate::load::load_project_data;
+use crate::{PathContext, ProjectError, SampleRef, SaveOptions};
+use std::path::{Path, PathBuf};
+
+/// What [`validate`] found in a project file
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub name: String,
+    /// Format and compression the file is written in
+    pub save_options: SaveOptions,
+    pub track_count: usize,
+    pub clip_count: usize,
+    /// Number of distinct samples the clips play
+    pub sample_count: usize,
+    /// Samples whose files weren't found; their clips would load offline
+    pub missing_samples: Vec<SampleIssue>,
+    /// Samples found in a format that can't be decoded
+    pub unsupported_samples: Vec<SampleIssue>,
+}
+
+impl ValidationReport {
+    /// Whether every sample was found in a format that can be decoded
+    pub fn is_ok(&self) -> bool {
+        self.missing_samples.is_empty() && self.unsupported_samples.is_empty()
+    }
+}
+
+/// A sample with a problem, and the clips playing it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleIssue {
+    pub sample_ref: SampleRef,
+    /// The file it resolved to, if it's on disk
+    pub path: Option<PathBuf>,
+    /// Names of the clips playing it
+    pub clips: Vec<String>,
+}
+
+/// Check the project file at `path` without loading any audio.
+///
+/// Fails if the file can't be read or doesn't hold a project. Otherwise every
+/// sample reference is resolved with `ctx`, and embedded samples are looked up in
+/// the file, to report the ones that are missing or in a format that can't be
+/// decoded. Files aren't opened, so one that's damaged inside still only shows up
+/// when it's loaded.
+pub fn validate(path: &Path, ctx: &PathContext) -> Result<ValidationReport, ProjectError> {
+    let (project, save_options) = load_project_data(path)?;
+
+    // Each distinct sample with the clips playing it, in the order they're first used
+    let mut samples: Vec<(&SampleRef, Vec<String>)> = Vec::new();
+    for clip in project.tracks.iter().flat_map(|track| &track.clips) {
+        match samples
+            .iter_mut()
+            .find(|(sample_ref, _)| **sample_ref == clip.sample_ref)
+        {
+            Some((_, clips)) => clips.push(clip.name.clone()),
+            None => samples.push((&clip.sample_ref, vec![clip.name.clone()])),
+        }
+    }
+
+    let mut report = ValidationReport {
+        name: project.name.clone(),
+        save_options,
+        track_count: project.tracks.len(),
+        clip_count: project.tracks.iter().map(|track| track.clips.len()).sum(),
+        sample_count: samples.len(),
+        ..Default::default()
+    };
+    for (sample_ref, clips) in samples {
+        let (found, path) = match sample_ref {
+            // Loading writes these out from the project itself
+            SampleRef::Embedded(name) => {
+                let found = name
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| project.embedded.contains_key(name));
+                (found, None)
+            }
+            _ => {
+                let path = ctx.resolve(sample_ref);
+                (path.is_some(), path)
+            }
+        };
+        let issue = SampleIssue {
+            sample_ref: sample_ref.clone(),
+            path,
+            clips,
+        };
+        if !found {
+            report.missing_samples.push(issue);
+        } else if !daw_decode::is_supported_audio(
+            issue.path.as_deref().unwrap_or(sample_ref.path()),
+        ) {
+            report.unsupported_samples.push(issue);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ClipData, EmbeddedAudio, Project, TrackData};
+    use std::collections::BTreeMap;
+    use tempfile::tempdir;
+
+    fn clip(name: &str, sample_ref: SampleRef) -> ClipData {
+        ClipData {
+            id: None,
+            name: name.to_string(),
+            start_tick: 0,
+            end_tick: 960,
+            sample_ref,
+            audio_offset: 0,
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_missing_and_unsupported_samples() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("kick.wav"), b"not decoded").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"not audio").unwrap();
+
+        let project = |path: &str| SampleRef::ProjectRelative(path.into());
+        let mut project_data: Project = serde_json::from_str(
+            r#"{ "name": "Check", "tempo": 120.0, "time_signature": [4, 4], "tracks": [] }"#,
+        )
+        .unwrap();
+        project_data.tracks = vec![TrackData {
+            id: 0,
+            name: "Drums".to_string(),
+            clips: vec![
+                clip("kick 1", project("kick.wav")),
+                clip("kick 2", project("kick.wav")),
+                clip("snare", project("snare.wav")),
+                clip("notes", project("notes.txt")),
+                clip("clap", SampleRef::Embedded("clap.wav".into())),
+                clip("hat", SampleRef::Embedded("hat.wav".into())),
+            ],
+            volume: 1.0,
+            pan: 0.0,
+            enabled: true,
+            solo: false,
+            color: None,
+            metadata: Default::default(),
+            parent: None,
+            is_folder: false,
+            automation: Vec::new(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+        }];
+        project_data.embedded =
+            BTreeMap::from([("clap.wav".to_string(), EmbeddedAudio(b"RIFF".to_vec()))]);
+        let path = dir.path().join("check.dawproj");
+        std::fs::write(&path, serde_json::to_vec(&project_data).unwrap()).unwrap();
+
+        let report = validate(&path, &PathContext::from_project_path(&path)).unwrap();
+        assert_eq!(report.name, "Check");
+        assert_eq!(report.save_options, SaveOptions::default());
+        assert_eq!((report.track_count, report.clip_count), (1, 6));
+        assert_eq!(report.sample_count, 5);
+        assert!(!report.is_ok());
+
+        let missing: Vec<(String, &[String])> = report
+            .missing_samples
+            .iter()
+            .map(|issue| (issue.sample_ref.to_string(), issue.clips.as_slice()))
+            .collect();
+        assert_eq!(
+            missing,
+            [
+                ("project:snare.wav".to_string(), &["snare".to_string()][..]),
+                ("embedded:hat.wav".to_string(), &["hat".to_string()][..]),
+            ]
+        );
+        assert_eq!(
+            report.unsupported_samples,
+            [SampleIssue {
+                sample_ref: project("notes.txt"),
+                path: Some(dir.path().join("notes.txt")),
+                clips: vec!["notes".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_validate_reports_schema_errors() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("broken.dawproj");
+        std::fs::write(&path, br#"{ "name": "Broken", "tempo": "fast" }"#).unwrap();
+        let err = validate(&path, &PathContext::from_project_path(&path)).unwrap_err();
+        // Reported as the JSON it is, not as a MessagePack file
+        assert!(matches!(err, ProjectError::Serialize(_)), "{}", err);
+    }
+}
@@ -0,0 +1,187 @@
+//! Dither for renders written as integers.
+//!
+//! Rounding to 16 or 24 bits leaves an error that follows the signal, which turns
+//! quiet fades and reverb tails into distortion. Adding a little noise first
+//! decorrelates it into a steady hiss; noise shaping moves most of that hiss up
+//! towards frequencies the ear is less sensitive to.
+
+use serde::{Deserialize, Serialize};
+
+use daw_dsp::Processor;
+use daw_transport::AudioArc;
+
+/// How samples are rounded when a render is written as integers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dither {
+    /// Round to the nearest step
+    #[default]
+    None,
+    /// Triangular noise one step either way (TPDF) before rounding
+    Triangular,
+    /// Triangular noise, with the rounding error fed back so the noise is mostly
+    /// heard at high frequencies
+    NoiseShaped,
+}
+
+impl Dither {
+    /// `buffer` rounded onto the steps of a `bits`-bit integer, still as floats so
+    /// writing it at that depth keeps the values as they are.
+    ///
+    /// The noise comes from a fixed seed, so the same mix dithers the same way
+    /// every time.
+    pub fn apply(self, buffer: &AudioArc, bits: u32) -> AudioArc {
+        if self == Dither::None {
+            return buffer.clone();
+        }
+        let mut samples = buffer.samples().to_vec();
+        self.ditherer(bits, buffer.channels())
+            .process(&mut samples, buffer.channels() as usize);
+        AudioArc::new(samples, buffer.sample_rate(), buffer.channels())
+    }
+
+    /// Dither as a [`Processor`], for a render written a block at a time; blocks
+    /// come out the same as [`apply`](Self::apply) on the whole mix
+    pub fn ditherer(self, bits: u32, channels: u16) -> Ditherer {
+        Ditherer {
+            dither: self,
+            scale: (1u32 << (bits - 1)) as f32,
+            noise: Noise::default(),
+            errors: vec![0.0; channels as usize],
+        }
+    }
+}
+
+/// [`Dither`] carried across blocks, made by [`Dither::ditherer`].
+pub struct Ditherer {
+    dither: Dither,
+    scale: f32,
+    noise: Noise,
+    /// Rounding error of the last frame, per channel
+    errors: Vec<f32>,
+}
+
+impl Processor for Ditherer {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        if self.dither == Dither::None {
+            return;
+        }
+        for frame in block.chunks_exact_mut(channels) {
+            for (sample, error) in frame.iter_mut().zip(&mut self.errors) {
+                let wanted = *sample * self.scale
+                    - match self.dither {
+                        Dither::NoiseShaped => *error,
+                        _ => 0.0,
+                    };
+                let rounded = (wanted + self.noise.triangular()).round();
+                *error = rounded - wanted;
+                *sample = rounded / self.scale;
+            }
+        }
+    }
+}
+
+/// xorshift64*, plenty for dither noise
+struct Noise(u64);
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self(0x9E37_79B9_7F4A_7C15)
+    }
+}
+
+impl Noise {
+    /// Uniform in [0, 1)
+    fn uniform(&mut self) -> f32 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Triangular in (-1, 1), the sum of two uniform values
+    fn triangular(&mut self) -> f32 {
+        self.uniform() - self.uniform()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 16-bit sine a third of a step high
+    fn faint_sine() -> AudioArc {
+        let samples = (0..48000)
+            .map(|i| (i as f32 * 0.01).sin() / 3.0 / 32768.0)
+            .collect();
+        AudioArc::new(samples, 48000, 1)
+    }
+
+    #[test]
+    fn test_dither_keeps_signal_below_a_step() {
+        let sine = faint_sine();
+        // Rounding alone loses it entirely
+        assert!(
+            sine.samples()
+                .iter()
+                .all(|&sample| (sample * 32768.0).round() == 0.0)
+        );
+
+        for dither in [Dither::Triangular, Dither::NoiseShaped] {
+            let dithered = dither.apply(&sine, 16);
+            // On the 16-bit grid and near the signal
+            for (&out, &sample) in dithered.samples().iter().zip(sine.samples()) {
+                let steps = out * 32768.0;
+                assert_eq!(steps, steps.round());
+                assert!((steps - sample * 32768.0).abs() <= 3.0, "{dither:?}");
+            }
+            // The sine still shows, as what the dithered signal has in common with it
+            let correlation: f32 = dithered
+                .samples()
+                .iter()
+                .zip(sine.samples())
+                .map(|(a, b)| a * b)
+                .sum();
+            assert!(correlation > 0.0, "{dither:?}");
+            // The same every time
+            assert_eq!(dither.apply(&sine, 16).samples(), dithered.samples());
+        }
+    }
+
+    #[test]
+    fn test_noise_shaping_moves_noise_up() {
+        let sine = faint_sine();
+        // Noise energy left after summing runs of eight samples, a crude low-pass
+        let low_noise = |dither: Dither| {
+            let error: Vec<f32> = dither
+                .apply(&sine, 16)
+                .samples()
+                .iter()
+                .zip(sine.samples())
+                .map(|(a, b)| (a - b) * 32768.0)
+                .collect();
+            error
+                .windows(8)
+                .map(|run| run.iter().sum::<f32>().powi(2))
+                .sum::<f32>()
+        };
+        assert!(low_noise(Dither::NoiseShaped) < low_noise(Dither::Triangular) / 2.0);
+    }
+
+    #[test]
+    fn test_dithering_in_blocks_matches_the_whole_mix() {
+        let sine = faint_sine();
+        let mut ditherer = Dither::NoiseShaped.ditherer(16, 1);
+        let mut blocks = sine.samples().to_vec();
+        for block in blocks.chunks_mut(999) {
+            ditherer.process(block, 1);
+        }
+        assert_eq!(blocks, Dither::NoiseShaped.apply(&sine, 16).samples());
+    }
+
+    #[test]
+    fn test_no_dither_leaves_samples_alone() {
+        let sine = faint_sine();
+        assert_eq!(Dither::None.apply(&sine, 16).samples(), sine.samples());
+    }
+}
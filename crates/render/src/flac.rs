@@ -0,0 +1,131 @@
+//! FLAC renders, encoded with `flacenc`.
+//!
+//! The encoder's default settings pick linear prediction per block and channel,
+//! which takes a typical mix to around half its size; any FLAC decoder reads it.
+
+use std::path::Path;
+
+use daw_transport::AudioArc;
+use flacenc::component::BitRepr;
+use flacenc::error::Verify;
+
+use crate::{RenderFileType, RenderFormat, to_int};
+
+/// Write `buffer` to a FLAC file at `path`. FLAC stores integers only, so
+/// [`RenderFormat::Float32`] is written as 24-bit and samples beyond full scale
+/// are clipped.
+pub fn write_flac(buffer: &AudioArc, path: &Path, format: RenderFormat) -> anyhow::Result<()> {
+    let channels = buffer.channels() as usize;
+    anyhow::ensure!(
+        (1..=8).contains(&channels),
+        "FLAC holds 1 to 8 channels, not {}",
+        channels
+    );
+    let bits = RenderFileType::Flac.integer_bits(format).unwrap_or(24);
+    let samples: Vec<i32> = buffer
+        .samples()
+        .iter()
+        .map(|&sample| to_int(sample, bits))
+        .collect();
+
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, error)| anyhow::anyhow!("FLAC encoder settings: {:?}", error))?;
+    let source = flacenc::source::MemSource::from_samples(
+        &samples,
+        channels,
+        bits as usize,
+        buffer.sample_rate() as usize,
+    );
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|error| anyhow::anyhow!("FLAC encoding failed: {:?}", error))?;
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream
+        .write(&mut sink)
+        .map_err(|error| anyhow::anyhow!("FLAC encoding failed: {:?}", error))?;
+    std::fs::write(path, sink.as_slice())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_flac_compresses_a_tone() {
+        let samples = (0..20000)
+            .flat_map(|i| {
+                let sample = (i as f32 * 0.05).sin() * 0.5;
+                [sample, -sample]
+            })
+            .collect();
+        let buffer = AudioArc::new(samples, 44100, 2);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.flac");
+        write_flac(&buffer, &path, RenderFormat::Int16).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..4], b"fLaC");
+        assert!(bytes.len() < 20000 * 2 * 2 / 2, "{}", bytes.len());
+    }
+
+    /// The samples Symphonia's FLAC decoder reads back from `path`
+    fn decode(path: &Path) -> Vec<f32> {
+        use symphonia::core::audio::SampleBuffer;
+        use symphonia::core::io::MediaSourceStream;
+
+        let file = std::fs::File::open(path).unwrap();
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(
+                &Default::default(),
+                stream,
+                &Default::default(),
+                &Default::default(),
+            )
+            .unwrap()
+            .format;
+        let track = format.default_track().unwrap();
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &Default::default())
+            .unwrap();
+        let mut samples = Vec::new();
+        while let Ok(packet) = format.next_packet() {
+            let decoded = decoder.decode(&packet).unwrap();
+            let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+            buffer.copy_interleaved_ref(decoded);
+            samples.extend_from_slice(buffer.samples());
+        }
+        samples
+    }
+
+    #[test]
+    fn test_write_flac_decodes_to_the_same_samples() {
+        // A tone, then noise that doesn't predict, then silence, over more than
+        // one block and with a short one at the end
+        let mut noise = 1u32;
+        let samples: Vec<f32> = (0..4096 * 3 + 100)
+            .flat_map(|i| {
+                noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let sample = match i / 4096 {
+                    0 => (i as f32 * 0.01).sin() * 0.8,
+                    1 => noise as f32 / u32::MAX as f32 - 0.5,
+                    _ => 0.0,
+                };
+                [sample, sample * 0.5]
+            })
+            .collect();
+        let buffer = AudioArc::new(samples.clone(), 48000, 2);
+        let dir = tempfile::tempdir().unwrap();
+        for (format, bits) in [(RenderFormat::Int16, 16), (RenderFormat::Int24, 24)] {
+            let path = dir.path().join(format!("{bits}.flac"));
+            write_flac(&buffer, &path, format).unwrap();
+            let full_scale = (1 << (bits - 1)) as f32;
+            let expected: Vec<f32> = samples
+                .iter()
+                .map(|&sample| to_int(sample, bits) as f32 / full_scale)
+                .collect();
+            assert_eq!(decode(&path), expected);
+        }
+    }
+}
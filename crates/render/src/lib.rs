@@ -1,122 +1,1454 @@
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use daw_transport::{AudioArc, PPQN, Track};
+use daw_dsp::{Effect, InsertChain, Processor};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-pub fn ticks_to_samples(ticks: f64, tempo: f64, sample_rate: u32) -> f64 {
-    let seconds_per_beat = 60.0 / tempo;
-    let seconds_per_tick = seconds_per_beat / PPQN as f64;
-    ticks * seconds_per_tick * sample_rate as f64
-}
+use daw_transport::{
+    AudioArc, Clip, EffectiveMix, LoudnessMeter, PPQN, Sampler, SamplerNote, SamplerVoices,
+    TempoCurve, Track, TrackAutomation, TruePeakMeter, any_soloed, clip_source_frame,
+    effective_mix, fade_gain, gain_to_db, pan_gains, routed_sends, sampler_notes,
+};
+
+mod dither;
+mod flac;
+mod normalize;
+mod stems;
+#[cfg(feature = "vorbis")]
+mod vorbis;
+mod watermark;
+
+pub use dither::{Dither, Ditherer};
+pub use flac::write_flac;
+pub use normalize::Normalization;
+pub use stems::render_stems;
+#[cfg(feature = "vorbis")]
+pub use vorbis::write_vorbis;
+pub use watermark::{Watermark, WatermarkMarker, WatermarkSound};
+
+/// Frames each thread mixes at a time, about a second and a half at 44.1 kHz
+const RENDER_CHUNK_FRAMES: usize = 1 << 16;
 
-fn samples_to_ticks(samples: f64, tempo: f64, sample_rate: u32) -> f64 {
-    let seconds_per_beat = 60.0 / tempo;
-    let seconds_per_tick = seconds_per_beat / PPQN as f64;
-    samples / (seconds_per_tick * sample_rate as f64)
+/// Frames a sampler plays at a time, short enough that the notes starting and
+/// ending in them all fit in its voices' event list
+const SAMPLER_BLOCK_FRAMES: usize = 1024;
+
+/// Sample position of `ticks`, walking the tempo changes before it the same way
+/// playback does
+pub fn ticks_to_samples(ticks: f64, tempo: &TempoCurve, sample_rate: u32) -> f64 {
+    tempo.ticks_to_samples(ticks, sample_rate)
 }
 
-fn calculate_end_tick(tracks: &[Track]) -> u64 {
+/// Tick a full render ends at: the end of the last clip on a track that's heard,
+/// including its fade-out tail.
+pub fn render_end_tick(tracks: &[Track]) -> u64 {
+    let any_soloed = any_soloed(tracks);
     let mut max_end_tick = 0u64;
-    for track in tracks {
-        if !track.enabled {
+    for (track, mix) in tracks.iter().zip(effective_mix(tracks)) {
+        if !mix.audible(any_soloed) {
             continue;
         }
         for clip in track.clips() {
-            max_end_tick = max_end_tick.max(clip.end_tick);
+            max_end_tick = max_end_tick.max(clip.end_tick + clip.fade_out_ticks);
         }
     }
     max_end_tick
 }
 
-pub fn render_timeline(tracks: &[Track], tempo: f64, sample_rate: u32, channels: u16) -> AudioArc {
-    let end_tick = calculate_end_tick(tracks);
-    let total_samples = ticks_to_samples(end_tick as f64, tempo, sample_rate) as usize;
-    let output_channels = channels as usize;
+pub fn render_timeline(
+    tracks: &[Track],
+    tempo: &TempoCurve,
+    sample_rate: u32,
+    channels: u16,
+) -> AudioArc {
+    let end_tick = render_end_tick(tracks);
+    render_range(tracks, tempo, sample_rate, channels, 0..end_tick)
+}
+
+/// Render the ticks in `range` only, for bouncing a loop or a selection.
+///
+/// Clips running into the range from before it play from the matching point, and
+/// ones running past its end are cut off there.
+pub fn render_range(
+    tracks: &[Track],
+    tempo: &TempoCurve,
+    sample_rate: u32,
+    channels: u16,
+    range: Range<u64>,
+) -> AudioArc {
+    mix_tracks(tracks, tempo, sample_rate, channels, range, None)
+}
 
-    // Pre-convert all clips to sample space and resample to output sample rate
-    struct RenderClip {
-        start_sample: u64,
-        end_sample: u64,
-        offset: u64, // offset into audio in samples
-        audio: AudioArc,
+/// Mix the tracks in `range`, or only the track at index `only`, which still
+/// takes the mute and volume of the folders it's in
+fn mix_tracks(
+    tracks: &[Track],
+    tempo: &TempoCurve,
+    sample_rate: u32,
+    channels: u16,
+    range: Range<u64>,
+    only: Option<usize>,
+) -> AudioArc {
+    Mixdown::new(tracks, tempo, sample_rate, channels, range, only).mix()
+}
+
+/// Render one track's clips in `range` to stereo, for freezing the track.
+///
+/// MIDI clips are played by the track's sampler, if it has one. Like
+/// [`render_clip`], the track's volume, pan, mute, automation, effects and sends
+/// are left out, so the audio can take the place of the clips on the same track
+/// and still go through the track's controls.
+pub fn render_track(
+    track: &Track,
+    tempo: &TempoCurve,
+    sample_rate: u32,
+    range: Range<u64>,
+) -> AudioArc {
+    let first_sample = ticks_to_samples(range.start as f64, tempo, sample_rate) as u64;
+    let end_sample = ticks_to_samples(range.end as f64, tempo, sample_rate) as u64;
+    Mixdown {
+        tracks: vec![RenderTrack {
+            volume: 1.0,
+            channel_gains: [1.0, 1.0],
+            automation: TrackAutomation::default(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+            clips: render_clips(track, tempo, sample_rate),
+            sampler: render_sampler(track, sample_rate),
+            notes: sampler_notes(track, tempo, sample_rate),
+        }],
+        first_sample,
+        frames: end_sample.saturating_sub(first_sample) as usize,
+        channels: 2,
+        sample_rate,
     }
+    .mix()
+}
 
-    let mut render_tracks: Vec<(f32, Vec<RenderClip>)> = Vec::new();
+/// A clip converted to sample space and resampled to the output rate
+struct RenderClip {
+    start_sample: u64,
+    end_sample: u64,
+    offset: u64, // offset into audio in samples
+    audio: AudioArc,
+    fade_in: u64,  // fade-in length in samples
+    fade_out: u64, // crossfade tail in samples played past end_sample
+    gain: f32,
+    looped: bool,
+    reversed: bool,
+}
 
-    for track in tracks {
-        if !track.enabled {
-            continue;
+struct RenderTrack {
+    volume: f32,
+    /// Gain of each output channel, from the track's pan
+    channel_gains: [f32; 2],
+    /// Volume and pan lanes, followed per frame instead of `volume` and `pan`
+    automation: TrackAutomation,
+    /// Insert effects the clips go through before volume and pan
+    effects: Vec<Effect>,
+    /// Whether this is an aux bus, which mixes the sends pointed at it
+    is_bus: bool,
+    sends: Vec<RenderSend>,
+    clips: Vec<RenderClip>,
+    /// The instrument playing `notes`, resampled to the output rate
+    sampler: Option<Sampler>,
+    notes: Vec<SamplerNote>,
+}
+
+impl RenderTrack {
+    /// Whether the track goes straight to the mix, without a sampler, effects or
+    /// buses
+    fn is_plain(&self) -> bool {
+        self.sampler.is_none() && self.effects.is_empty() && self.sends.is_empty() && !self.is_bus
+    }
+}
+
+/// A send from a track to a bus
+struct RenderSend {
+    /// Index of the bus among the mixed tracks
+    bus: usize,
+    level: f32,
+    pre_fader: bool,
+}
+
+/// The tracks in a range, ready to be mixed any block at a time
+struct Mixdown {
+    tracks: Vec<RenderTrack>,
+    /// Sample the range starts at
+    first_sample: u64,
+    /// Frames in the range
+    frames: usize,
+    channels: usize,
+    sample_rate: u32,
+}
+
+/// What a track that isn't plain carries from one block of a render to the next
+struct TrackChain {
+    /// Index of the track in [`Mixdown::tracks`]
+    index: usize,
+    inserts: InsertChain,
+    voices: SamplerVoices,
+}
+
+impl Mixdown {
+    fn new(
+        tracks: &[Track],
+        tempo: &TempoCurve,
+        sample_rate: u32,
+        channels: u16,
+        range: Range<u64>,
+        only: Option<usize>,
+    ) -> Self {
+        let first_sample = ticks_to_samples(range.start as f64, tempo, sample_rate) as u64;
+        let end_sample = ticks_to_samples(range.end as f64, tempo, sample_rate) as u64;
+        let output_channels = channels as usize;
+
+        // Folder mute, solo and volume apply to the tracks inside them, and a solo
+        // silences the rest like it does in playback
+        let any_soloed = any_soloed(tracks);
+        let mixed: Vec<(&Track, EffectiveMix)> = tracks
+            .iter()
+            .zip(effective_mix(tracks))
+            .enumerate()
+            .filter(|(index, (_, mix))| {
+                mix.audible(any_soloed) && only.is_none_or(|only| only == *index)
+            })
+            .map(|(_, mixed)| mixed)
+            .collect();
+        let mixed_tracks: Vec<&Track> = mixed.iter().map(|(track, _)| *track).collect();
+
+        let mut render_tracks: Vec<RenderTrack> = Vec::new();
+        for &(track, mix) in &mixed {
+            render_tracks.push(RenderTrack {
+                volume: mix.volume,
+                // Pan only places sound between two speakers
+                channel_gains: if output_channels == 2 {
+                    pan_gains(track.pan)
+                } else {
+                    [1.0, 1.0]
+                },
+                automation: TrackAutomation::new(tracks, track, tempo, sample_rate),
+                // Folders hold no audio of their own to run through effects
+                effects: if track.is_folder {
+                    Vec::new()
+                } else {
+                    track.effects.clone()
+                },
+                is_bus: track.is_bus,
+                sends: routed_sends(track, &mixed_tracks)
+                    .map(|(bus, send)| RenderSend {
+                        bus,
+                        level: send.level,
+                        pre_fader: send.pre_fader,
+                    })
+                    .collect(),
+                clips: render_clips(track, tempo, sample_rate),
+                sampler: render_sampler(track, sample_rate),
+                notes: sampler_notes(track, tempo, sample_rate),
+            });
         }
 
-        let mut render_clips = Vec::new();
-        for clip in track.clips() {
-            // Resample if needed (cheap clone if already at target rate)
-            let resampled_audio = if clip.audio.sample_rate() != sample_rate {
-                match clip.audio.resample(sample_rate) {
-                    Ok(audio) => audio,
-                    Err(_) => continue, // Skip clip if resampling fails
+        Self {
+            tracks: render_tracks,
+            first_sample,
+            frames: end_sample.saturating_sub(first_sample) as usize,
+            channels: output_channels,
+            sample_rate,
+        }
+    }
+
+    /// The whole range, split into chunks mixed on rayon's threads. Tracks with a
+    /// sampler, effects or sends, and buses, are added afterwards, one chunk after
+    /// the other.
+    fn mix(&self) -> AudioArc {
+        let mut samples = vec![0.0f32; self.frames * self.channels];
+        samples
+            .par_chunks_mut(RENDER_CHUNK_FRAMES * self.channels)
+            .enumerate()
+            .for_each(|(chunk, out)| self.mix_block(out, chunk * RENDER_CHUNK_FRAMES));
+        let mut chains = self.track_chains();
+        for (chunk, out) in samples
+            .chunks_mut(RENDER_CHUNK_FRAMES * self.channels)
+            .enumerate()
+        {
+            self.mix_routed(&mut chains, out, chunk * RENDER_CHUNK_FRAMES);
+        }
+        AudioArc::new(samples, self.sample_rate, self.channels as u16)
+    }
+
+    /// Add the mix of the tracks without effects or sends, over the frames from
+    /// `offset` frames into the range, to `out`.
+    ///
+    /// Works in sample space like the engine does, mixing each clip over just the
+    /// frames it covers. Clips are added in track order, so every frame sums the
+    /// same way however the range is split into blocks.
+    fn mix_block(&self, out: &mut [f32], offset: usize) {
+        for track in self.tracks.iter().filter(|track| track.is_plain()) {
+            self.mix_clips(track, out, offset, true);
+        }
+    }
+
+    /// A fresh insert chain and sampler voices for each track that isn't plain
+    fn track_chains(&self) -> Vec<TrackChain> {
+        self.tracks
+            .iter()
+            .enumerate()
+            .filter(|(_, track)| !track.is_plain())
+            .map(|(index, track)| TrackChain {
+                index,
+                inserts: InsertChain::new(&track.effects, self.sample_rate),
+                voices: SamplerVoices::new(),
+            })
+            .collect()
+    }
+
+    /// Add the tracks with a sampler, effects or sends and then the buses to
+    /// `out`, the frames from `offset` frames into the range, running them
+    /// through their `chains` from [`Mixdown::track_chains`].
+    ///
+    /// Effects and sampler voices carry state from one block to the next, so unlike
+    /// [`Mixdown::mix_block`] this has to be called for the blocks in order. The
+    /// tracks are processed in parallel, in chunks the same size whatever the
+    /// length of `out`, and added in track order.
+    fn mix_routed(&self, chains: &mut [TrackChain], out: &mut [f32], offset: usize) {
+        let (mut buses, mut senders): (Vec<_>, Vec<_>) = chains
+            .iter_mut()
+            .partition(|chain| self.tracks[chain.index].is_bus);
+        let mut bus_inputs: Vec<Vec<f32>> = self
+            .tracks
+            .iter()
+            .map(|track| {
+                if track.is_bus {
+                    vec![0.0; out.len()]
+                } else {
+                    Vec::new()
                 }
+            })
+            .collect();
+
+        let processed: Vec<Vec<f32>> = senders
+            .par_iter_mut()
+            .map(|chain| self.process_track(chain, vec![0.0; out.len()], offset))
+            .collect();
+        for (chain, processed) in senders.iter().zip(&processed) {
+            self.fader(
+                &self.tracks[chain.index],
+                processed,
+                out,
+                offset,
+                &mut bus_inputs,
+            );
+        }
+
+        // Then the buses, each fed what was sent to it
+        let inputs: Vec<Vec<f32>> = buses
+            .iter()
+            .map(|chain| std::mem::take(&mut bus_inputs[chain.index]))
+            .collect();
+        let processed: Vec<Vec<f32>> = buses
+            .par_iter_mut()
+            .zip(inputs)
+            .map(|(chain, input)| self.process_track(chain, input, offset))
+            .collect();
+        for (chain, processed) in buses.iter().zip(&processed) {
+            self.fader(
+                &self.tracks[chain.index],
+                processed,
+                out,
+                offset,
+                &mut bus_inputs,
+            );
+        }
+    }
+
+    /// The track of `chain` over the frames from `offset` frames into the range,
+    /// its clips and sampler added to `input` and run through its inserts, before
+    /// volume and pan
+    fn process_track(
+        &self,
+        chain: &mut TrackChain,
+        mut input: Vec<f32>,
+        offset: usize,
+    ) -> Vec<f32> {
+        let track = &self.tracks[chain.index];
+        let chunk_samples = RENDER_CHUNK_FRAMES * self.channels;
+        for (chunk, block) in input.chunks_mut(chunk_samples).enumerate() {
+            let chunk_offset = offset + chunk * RENDER_CHUNK_FRAMES;
+            self.mix_clips(track, block, chunk_offset, false);
+            if let Some(sampler) = &track.sampler {
+                let sampler_samples = SAMPLER_BLOCK_FRAMES * self.channels;
+                for (i, out) in block.chunks_mut(sampler_samples).enumerate() {
+                    let position =
+                        self.first_sample + (chunk_offset + i * SAMPLER_BLOCK_FRAMES) as u64;
+                    chain.voices.play(
+                        sampler,
+                        Some(&track.notes),
+                        &[],
+                        position,
+                        self.sample_rate,
+                        out,
+                        self.channels,
+                    );
+                }
+            }
+            chain.inserts.process(block, self.channels);
+        }
+        input
+    }
+
+    /// Add a track's `processed` audio to `out` through its volume and pan, and
+    /// to the `bus_inputs` its sends go to
+    fn fader(
+        &self,
+        track: &RenderTrack,
+        processed: &[f32],
+        out: &mut [f32],
+        offset: usize,
+        bus_inputs: &mut [Vec<f32>],
+    ) {
+        let channels = self.channels;
+        let start = self.first_sample + offset as u64;
+        let frames = processed
+            .chunks_exact(channels)
+            .zip(out.chunks_exact_mut(channels));
+        for (frame, (position, (processed, mixed))) in (start..).zip(frames).enumerate() {
+            let gain = track.automation.volume_at(position, track.volume);
+            let channel_gains = self.channel_gains(track, position);
+            for (ch, (mixed, sample)) in mixed.iter_mut().zip(processed).enumerate() {
+                *mixed += sample * gain * channel_gains[ch % 2];
+            }
+            for send in &track.sends {
+                let level = send.level * if send.pre_fader { 1.0 } else { gain };
+                let input = &mut bus_inputs[send.bus][frame * channels..][..channels];
+                for (sent, sample) in input.iter_mut().zip(processed) {
+                    *sent += sample * level;
+                }
+            }
+        }
+    }
+
+    /// Gain of each output channel for `track` at `position`, following its pan lane
+    fn channel_gains(&self, track: &RenderTrack, position: u64) -> [f32; 2] {
+        match &track.automation.pan {
+            Some(pan) if self.channels == 2 => pan_gains(pan.value_at(position)),
+            _ => track.channel_gains,
+        }
+    }
+
+    /// Add `track`'s clips over the frames from `offset` frames into the range to
+    /// `out`, through the track's volume and pan when `fader` is set
+    fn mix_clips(&self, track: &RenderTrack, out: &mut [f32], offset: usize, fader: bool) {
+        let output_channels = self.channels;
+        let chunk_start = self.first_sample + offset as u64;
+        let chunk_end = chunk_start + (out.len() / output_channels) as u64;
+
+        for clip in &track.clips {
+            let from = clip.start_sample.max(chunk_start);
+            let to = (clip.end_sample + clip.fade_out).min(chunk_end);
+            let clip_channels = clip.audio.channels() as usize;
+            let source = clip.audio.samples();
+            let audio_frames = source.len() as u64 / clip_channels as u64;
+
+            for position in from..to {
+                let timeline_offset = position - clip.start_sample;
+                let Some(source_frame_idx) = clip_source_frame(
+                    timeline_offset,
+                    clip.offset,
+                    audio_frames,
+                    clip.looped,
+                    clip.reversed,
+                ) else {
+                    continue;
+                };
+                let mut gain = clip.gain;
+                if fader {
+                    gain *= track.automation.volume_at(position, track.volume);
+                }
+                if timeline_offset < clip.fade_in {
+                    gain *= fade_gain(timeline_offset, clip.fade_in);
+                }
+                if position >= clip.end_sample {
+                    gain *= fade_gain(clip.end_sample + clip.fade_out - position, clip.fade_out);
+                }
+                let channel_gains = if fader {
+                    self.channel_gains(track, position)
+                } else {
+                    [1.0, 1.0]
+                };
+                let frame_idx = (position - chunk_start) as usize;
+                for ch in 0..output_channels {
+                    let clip_ch = ch % clip_channels;
+                    let src_idx = source_frame_idx as usize * clip_channels + clip_ch;
+                    let dst_idx = frame_idx * output_channels + ch;
+                    if src_idx < source.len() {
+                        out[dst_idx] += source[src_idx] * gain * channel_gains[ch % 2];
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A track's audio clips, resampled and placed in sample space
+fn render_clips(track: &Track, tempo: &TempoCurve, sample_rate: u32) -> Vec<RenderClip> {
+    // Resampling is the slow part, so clips are prepared in parallel
+    track
+        .clips()
+        .par_iter()
+        .filter_map(|clip| {
+            let audio = clip.audio()?;
+
+            // Resample if needed (cheap clone if already at target rate);
+            // skip the clip if resampling fails
+            let resampled_audio = if audio.sample_rate() != sample_rate {
+                audio.resample(sample_rate).ok()?
             } else {
-                clip.audio.clone()
+                audio.clone()
             };
 
             // Convert tick positions to sample positions
             let start_sample = ticks_to_samples(clip.start_tick as f64, tempo, sample_rate) as u64;
             let end_sample = ticks_to_samples(clip.end_tick as f64, tempo, sample_rate) as u64;
 
-            render_clips.push(RenderClip {
+            Some(RenderClip {
                 start_sample,
                 end_sample,
                 offset: clip.audio_offset,
                 audio: resampled_audio,
-            });
-        }
-        render_tracks.push((track.volume, render_clips));
-    }
+                fade_in: tempo.span_to_samples(clip.start_tick, clip.fade_in_ticks, sample_rate)
+                    as u64,
+                fade_out: tempo.span_to_samples(clip.end_tick, clip.fade_out_ticks, sample_rate)
+                    as u64,
+                gain: clip.gain,
+                looped: clip.looped,
+                reversed: clip.reversed,
+            })
+        })
+        .collect()
+}
 
-    // Render in sample space (like the engine does)
-    let mut samples = vec![0.0f32; total_samples * output_channels];
+/// A track's sampler resampled to the output rate, if it has one that plays. Like
+/// the engine, a sampler that can't be resampled stays silent.
+fn render_sampler(track: &Track, sample_rate: u32) -> Option<Sampler> {
+    track
+        .sampler
+        .as_ref()
+        .filter(|_| !track.is_folder && !track.is_bus)
+        .and_then(|sampler| sampler.resample(sample_rate).ok())
+}
 
-    for frame_idx in 0..total_samples {
-        let position = frame_idx as u64;
+/// Render `range` straight to a WAV file in `format` a block at a time, so memory
+/// use stays the same however long the render runs, returning what was written.
+///
+/// Each block goes through `processors` in order, such as the master bus, a
+/// [`Watermark::marker`] and a [`Dither::ditherer`], before it's written. The file
+/// comes out the same as [`render_range`] with that processing and [`write_wav`].
+#[allow(clippy::too_many_arguments)]
+pub fn render_range_to_wav(
+    tracks: &[Track],
+    tempo: &TempoCurve,
+    sample_rate: u32,
+    channels: u16,
+    range: Range<u64>,
+    processors: &mut [&mut dyn Processor],
+    path: &Path,
+    format: RenderFormat,
+) -> anyhow::Result<RenderSummary> {
+    let mixdown = Mixdown::new(tracks, tempo, sample_rate, channels, range, None);
+    stream_wav(&mixdown, processors, path, format)
+}
 
-        for (track_volume, render_clips) in &render_tracks {
-            for clip in render_clips {
-                if position >= clip.start_sample && position < clip.end_sample {
-                    let timeline_offset = position - clip.start_sample;
-                    // Add clip.offset to get the actual position in the audio buffer
-                    let source_frame_idx = (clip.offset as usize) + (timeline_offset as usize);
-                    let clip_channels = clip.audio.channels() as usize;
+/// Mix `mixdown` into `path` a few blocks at a time, one per thread
+fn stream_wav(
+    mixdown: &Mixdown,
+    processors: &mut [&mut dyn Processor],
+    path: &Path,
+    format: RenderFormat,
+) -> anyhow::Result<RenderSummary> {
+    let channels = mixdown.channels;
+    let sample_rate = mixdown.sample_rate;
+    let mut writer =
+        hound::WavWriter::create(path, wav_spec(channels as u16, sample_rate, format))?;
+    let mut meter = SummaryMeter::new(sample_rate, channels as u16);
+    let mut chains = mixdown.track_chains();
 
-                    for ch in 0..output_channels {
-                        let clip_ch = ch % clip_channels;
-                        let src_idx = source_frame_idx * clip_channels + clip_ch;
-                        let dst_idx = frame_idx * output_channels + ch;
-                        if src_idx < clip.audio.samples().len() {
-                            samples[dst_idx] += clip.audio.samples()[src_idx] * track_volume;
-                        }
-                    }
-                }
+    let batch_frames = RENDER_CHUNK_FRAMES * rayon::current_num_threads().max(1);
+    let mut batch = vec![0.0f32; batch_frames.min(mixdown.frames) * channels];
+    let mut offset = 0;
+    while offset < mixdown.frames {
+        let frames = (mixdown.frames - offset).min(batch_frames);
+        let batch = &mut batch[..frames * channels];
+        batch.fill(0.0);
+        batch
+            .par_chunks_mut(RENDER_CHUNK_FRAMES * channels)
+            .enumerate()
+            .for_each(|(chunk, out)| mixdown.mix_block(out, offset + chunk * RENDER_CHUNK_FRAMES));
+        mixdown.mix_routed(&mut chains, batch, offset);
+        for block in batch.chunks_mut(RENDER_CHUNK_FRAMES * channels) {
+            for processor in processors.iter_mut() {
+                processor.process(block, channels);
             }
         }
+        write_wav_samples(&mut writer, batch, format)?;
+        meter.push(batch);
+        offset += frames;
     }
 
-    AudioArc::new(samples, sample_rate, channels)
+    writer.finalize()?;
+    Ok(meter.finish(path))
+}
+
+/// `mix` run through a master bus `processor`, such as the session's
+/// [`MasterBus`](daw_dsp::MasterBus), in blocks the way the engine runs it.
+pub fn process_master(mix: &AudioArc, processor: &mut dyn Processor) -> AudioArc {
+    let channels = mix.channels() as usize;
+    let mut samples = mix.samples().to_vec();
+    for block in samples.chunks_mut(RENDER_CHUNK_FRAMES * channels) {
+        processor.process(block, channels);
+    }
+    AudioArc::new(samples, mix.sample_rate(), mix.channels())
 }
 
-pub fn write_wav(buffer: &AudioArc, path: &Path) -> anyhow::Result<()> {
-    let spec = hound::WavSpec {
-        channels: buffer.channels(),
-        sample_rate: buffer.sample_rate(),
-        bits_per_sample: 32,
-        sample_format: hound::SampleFormat::Float,
+/// Render one clip on its own, the way it sounds in the arrangement.
+///
+/// The result starts at the clip's `start_tick` and runs through its crossfade tail,
+/// with the trim, fades, gain, looping and reversal baked in and the clip's own
+/// channel count.
+/// Track volume and pan are left out, so the audio can take the clip's place on the
+/// same track. Returns `None` for MIDI clips or if the audio can't be resampled.
+pub fn render_clip(clip: &Clip, tempo: &TempoCurve, sample_rate: u32) -> Option<AudioArc> {
+    let audio = clip.audio()?;
+    let audio = if audio.sample_rate() != sample_rate {
+        audio.resample(sample_rate).ok()?
+    } else {
+        audio.clone()
     };
 
-    let mut writer = hound::WavWriter::create(path, spec)?;
+    // Converted from absolute ticks like render_timeline, so both agree to the frame
+    let start_sample = ticks_to_samples(clip.start_tick as f64, tempo, sample_rate) as u64;
+    let end_sample = ticks_to_samples(clip.end_tick as f64, tempo, sample_rate) as u64;
+    let fade_in = tempo.span_to_samples(clip.start_tick, clip.fade_in_ticks, sample_rate) as u64;
+    let fade_out = tempo.span_to_samples(clip.end_tick, clip.fade_out_ticks, sample_rate) as u64;
+    let body = end_sample - start_sample;
+    let total = body + fade_out;
 
-    for &sample in buffer.samples() {
-        writer.write_sample(sample)?;
+    let channels = audio.channels() as usize;
+    let source = audio.samples();
+    let audio_frames = (source.len() / channels) as u64;
+    let mut samples = vec![0.0f32; total as usize * channels];
+    for (position, frame) in samples.chunks_exact_mut(channels).enumerate() {
+        let position = position as u64;
+        let Some(source_frame) = clip_source_frame(
+            position,
+            clip.audio_offset,
+            audio_frames,
+            clip.looped,
+            clip.reversed,
+        ) else {
+            break;
+        };
+        let start = source_frame as usize * channels;
+        let source_frame = &source[start..start + channels];
+        let mut gain = clip.gain;
+        if position < fade_in {
+            gain *= fade_gain(position, fade_in);
+        }
+        if position >= body {
+            gain *= fade_gain(total - position, fade_out);
+        }
+        for (out, &sample) in frame.iter_mut().zip(source_frame) {
+            *out = sample * gain;
+        }
+    }
+
+    Some(AudioArc::new(samples, sample_rate, audio.channels()))
+}
+
+/// Sample format of a rendered WAV file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderFormat {
+    /// 16-bit integer, as on CD and for most distribution
+    Int16,
+    /// 24-bit integer, for mastering and archiving
+    Int24,
+    /// 32-bit float, keeping levels above full scale
+    #[default]
+    Float32,
+}
+
+impl RenderFormat {
+    pub fn bits_per_sample(self) -> u16 {
+        match self {
+            RenderFormat::Int16 => 16,
+            RenderFormat::Int24 => 24,
+            RenderFormat::Float32 => 32,
+        }
     }
+}
 
+/// Write `buffer` to a WAV file in `format`. Integer formats clip samples beyond
+/// full scale.
+pub fn write_wav(buffer: &AudioArc, path: &Path, format: RenderFormat) -> anyhow::Result<()> {
+    let spec = wav_spec(buffer.channels(), buffer.sample_rate(), format);
+    let mut writer = hound::WavWriter::create(path, spec)?;
+    write_wav_samples(&mut writer, buffer.samples(), format)?;
     writer.finalize()?;
     Ok(())
 }
+
+fn wav_spec(channels: u16, sample_rate: u32, format: RenderFormat) -> hound::WavSpec {
+    hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: format.bits_per_sample(),
+        sample_format: match format {
+            RenderFormat::Float32 => hound::SampleFormat::Float,
+            RenderFormat::Int16 | RenderFormat::Int24 => hound::SampleFormat::Int,
+        },
+    }
+}
+
+fn write_wav_samples<W: std::io::Write + std::io::Seek>(
+    writer: &mut hound::WavWriter<W>,
+    samples: &[f32],
+    format: RenderFormat,
+) -> anyhow::Result<()> {
+    match format {
+        RenderFormat::Float32 => {
+            for &sample in samples {
+                writer.write_sample(sample)?;
+            }
+        }
+        RenderFormat::Int16 | RenderFormat::Int24 => {
+            let bits = format.bits_per_sample() as u32;
+            for &sample in samples {
+                writer.write_sample(to_int(sample, bits))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Kind of file a render is written as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderFileType {
+    #[default]
+    Wav,
+    /// Lossless and about half the size; floats are written as 24-bit
+    Flac,
+    /// Lossy Vorbis in an Ogg file, around a seventh of the size
+    #[cfg(feature = "vorbis")]
+    Vorbis,
+}
+
+impl RenderFileType {
+    /// The type named by the extension of `path`, WAV if it has none. `.ogg` is
+    /// Vorbis with the `vorbis` feature. Fails for formats renders
+    /// can't be written in, such as MP3.
+    pub fn for_path(path: &Path) -> anyhow::Result<Self> {
+        let Some(extension) = path.extension() else {
+            return Ok(RenderFileType::Wav);
+        };
+        match extension.to_string_lossy().to_lowercase().as_str() {
+            "wav" | "wave" => Ok(RenderFileType::Wav),
+            "flac" => Ok(RenderFileType::Flac),
+            #[cfg(feature = "vorbis")]
+            "ogg" | "oga" => Ok(RenderFileType::Vorbis),
+            other => anyhow::bail!("can't render to .{} files, only {}", other, SUPPORTED_TYPES),
+        }
+    }
+
+    /// Bits per sample `format` is written with as integers in this type of file,
+    /// or `None` if it's written as floats
+    pub fn integer_bits(self, format: RenderFormat) -> Option<u32> {
+        match (self, format) {
+            // The encoder takes floats, whatever the format
+            #[cfg(feature = "vorbis")]
+            (RenderFileType::Vorbis, _) => None,
+            (_, RenderFormat::Int16) => Some(16),
+            (_, RenderFormat::Int24) | (RenderFileType::Flac, RenderFormat::Float32) => Some(24),
+            (RenderFileType::Wav, RenderFormat::Float32) => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            RenderFileType::Wav => "wav",
+            RenderFileType::Flac => "flac",
+            #[cfg(feature = "vorbis")]
+            RenderFileType::Vorbis => "ogg",
+        }
+    }
+}
+
+/// The extensions [`RenderFileType::for_path`] takes, for its error
+#[cfg(not(feature = "vorbis"))]
+const SUPPORTED_TYPES: &str = ".wav or .flac";
+#[cfg(feature = "vorbis")]
+const SUPPORTED_TYPES: &str = ".wav, .flac or .ogg";
+
+/// Write `buffer` to `path` as `file_type` in `format`
+pub fn write_audio_file(
+    buffer: &AudioArc,
+    path: &Path,
+    file_type: RenderFileType,
+    format: RenderFormat,
+) -> anyhow::Result<()> {
+    match file_type {
+        RenderFileType::Wav => write_wav(buffer, path, format),
+        RenderFileType::Flac => write_flac(buffer, path, format),
+        #[cfg(feature = "vorbis")]
+        RenderFileType::Vorbis => write_vorbis(buffer, path),
+    }
+}
+
+/// `sample` as a `bits`-bit integer, clipped at full scale
+fn to_int(sample: f32, bits: u32) -> i32 {
+    let full_scale = (1i32 << (bits - 1)) as f32;
+    // `as` turns NaN into silence
+    (sample * full_scale)
+        .round()
+        .clamp(-full_scale, full_scale - 1.0) as i32
+}
+
+/// Extra time rendered after the last clip ends, so decays ring out instead of
+/// being cut off.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderTail {
+    Seconds(f64),
+    Bars(u32),
+}
+
+impl RenderTail {
+    /// Length of the tail in ticks at `tempo` and `time_signature`
+    pub fn ticks(self, tempo: f64, time_signature: (u32, u32)) -> u64 {
+        match self {
+            RenderTail::Seconds(seconds) => {
+                (seconds.max(0.0) * tempo / 60.0 * PPQN as f64).round() as u64
+            }
+            RenderTail::Bars(bars) => {
+                let (numerator, denominator) = time_signature;
+                bars as u64 * PPQN * 4 * numerator as u64 / denominator.max(1) as u64
+            }
+        }
+    }
+}
+
+/// Choices for a render beyond what the arrangement itself says.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Bring the finished mix to a loudness or peak level
+    pub normalize: Option<Normalization>,
+    /// Mark the bounce as a demo by laying a tone or voice tag over the finished mix
+    pub watermark: Option<Watermark>,
+    /// Sample format the file is written in
+    pub format: RenderFormat,
+    /// How samples are rounded when the file holds integers
+    pub dither: Dither,
+    /// Kind of file to write, instead of the one the path's extension names
+    pub file_type: Option<RenderFileType>,
+    /// Tick to start the render at, instead of the start of the arrangement
+    pub start_tick: Option<u64>,
+    /// Tick to end the render at, instead of the end of the last clip
+    pub end_tick: Option<u64>,
+    /// Time to keep rendering after the last clip ends; not added to `end_tick`
+    pub tail: Option<RenderTail>,
+    /// Threads to mix on, instead of one per core
+    pub threads: Option<usize>,
+    /// Mix in the metronome's click, for practice tracks and scratch audio
+    pub metronome: bool,
+}
+
+/// What a finished render produced, for telling the user about it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderSummary {
+    /// File the render was written to
+    pub path: PathBuf,
+    /// Length of the rendered audio
+    pub duration: Duration,
+    /// Highest sample level in dBFS (`-inf` for silence)
+    pub peak_db: f32,
+    /// Integrated loudness in LUFS (`-inf` for silence or less than 400 ms of audio)
+    pub loudness_lufs: f32,
+    /// Highest level between samples in dBTP (`-inf` for silence)
+    pub true_peak_db: f32,
+    /// Gain normalization applied to the mix in dB, 0 without it
+    pub gain_db: f32,
+    /// Average level across all channels in dBFS (`-inf` for silence)
+    pub rms_db: f32,
+    /// Samples over full scale, which clip when written as integers or played back
+    pub clipped_samples: usize,
+}
+
+impl RenderSummary {
+    /// Measure `buffer`, which was written to `path`
+    pub fn of(buffer: &AudioArc, path: &Path) -> Self {
+        let mut meter = SummaryMeter::new(buffer.sample_rate(), buffer.channels());
+        meter.push(buffer.samples());
+        meter.finish(path)
+    }
+}
+
+/// Measures a render as it's written, for its [`RenderSummary`]
+struct SummaryMeter {
+    sample_rate: u32,
+    channels: u16,
+    samples: u64,
+    peak: f32,
+    sum_squares: f64,
+    clipped_samples: usize,
+    loudness: LoudnessMeter,
+    true_peak: TruePeakMeter,
+}
+
+impl SummaryMeter {
+    fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            channels,
+            samples: 0,
+            peak: 0.0,
+            sum_squares: 0.0,
+            clipped_samples: 0,
+            loudness: LoudnessMeter::new(sample_rate, channels),
+            true_peak: TruePeakMeter::new(channels),
+        }
+    }
+
+    fn push(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.peak = self.peak.max(sample.abs());
+            // Accumulate in f64 so long renders don't lose precision
+            self.sum_squares += sample as f64 * sample as f64;
+            if sample.abs() > 1.0 {
+                self.clipped_samples += 1;
+            }
+        }
+        self.samples += samples.len() as u64;
+        self.loudness.push(samples);
+        self.true_peak.push(samples);
+    }
+
+    fn finish(self, path: &Path) -> RenderSummary {
+        let frames = self.samples / self.channels.max(1) as u64;
+        let rms = if self.samples == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.samples as f64).sqrt() as f32
+        };
+        RenderSummary {
+            path: path.to_path_buf(),
+            duration: Duration::from_secs_f64(frames as f64 / self.sample_rate as f64),
+            peak_db: gain_to_db(self.peak),
+            loudness_lufs: self.loudness.finish(),
+            true_peak_db: gain_to_db(self.true_peak.finish()),
+            gain_db: 0.0,
+            rms_db: gain_to_db(rms),
+            clipped_samples: self.clipped_samples,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use daw_transport::{
+        AutomationCurve, AutomationLane, AutomationPoint, ClipContent, ClipId, ClipSource,
+        IdAllocator, Note, NoteList, TrackId, TrackSend, WaveformData,
+    };
+    use std::sync::Arc;
+
+    fn ramp_clip(start_tick: u64, end_tick: u64) -> Clip {
+        let samples = (0..48000).map(|i| 0.25 + i as f32 / 96000.0).collect();
+        let audio = AudioArc::new(samples, 48000, 1);
+        Clip {
+            id: ClipId(0),
+            start_tick,
+            end_tick,
+            waveform: Arc::new(WaveformData::from_audio_arc(&audio, 512)),
+            content: ClipContent::Audio(audio),
+            audio_offset: 1200,
+            name: "ramp".to_string(),
+            fade_in_ticks: PPQN / 4,
+            fade_out_ticks: PPQN / 8,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: ClipSource::default(),
+        }
+    }
+
+    #[test]
+    fn test_render_clip_matches_its_part_of_the_timeline() {
+        let clip = ramp_clip(PPQN, PPQN * 2);
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        track.insert_clip(clip.clone(), &mut IdAllocator::default());
+        let timeline = render_timeline(&[track], &TempoCurve::constant(120.0), 48000, 1);
+
+        let bounced = render_clip(&clip, &TempoCurve::constant(120.0), 48000).unwrap();
+        assert_eq!(bounced.channels(), 1);
+        // Half a beat of body plus the eighth-beat tail
+        assert_eq!(bounced.frames(), 24000 + 3000);
+        assert_eq!(bounced.samples(), &timeline.samples()[24000..]);
+        // Faded in from silence, trimmed into the ramp
+        assert_eq!(bounced.samples()[0], 0.0);
+        assert!((bounced.samples()[12000] - (0.25 + 13200.0 / 96000.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_clips_after_a_tempo_change_land_where_the_curve_puts_them() {
+        // Two beats at 120 take a second, then a beat at 60 takes another, so the
+        // clip starts two seconds in and lasts one
+        let tempo = TempoCurve::new(120.0, [(PPQN * 2, 60.0)]);
+        let mut clip = ramp_clip(PPQN * 3, PPQN * 4);
+        clip.fade_in_ticks = 0;
+        clip.fade_out_ticks = 0;
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        track.insert_clip(clip.clone(), &mut IdAllocator::default());
+        let timeline = render_timeline(&[track], &tempo, 48000, 1);
+
+        assert_eq!(timeline.frames(), 48000 * 3);
+        assert!(timeline.samples()[..96000].iter().all(|&s| s == 0.0));
+        assert_eq!(timeline.samples()[96000], 0.25 + 1200.0 / 96000.0);
+        let bounced = render_clip(&clip, &tempo, 48000).unwrap();
+        assert_eq!(bounced.samples(), &timeline.samples()[96000..]);
+    }
+
+    #[test]
+    fn test_render_track_leaves_out_the_track_controls() {
+        let first = ramp_clip(PPQN, PPQN * 2);
+        let second = ramp_clip(PPQN * 3, PPQN * 4);
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        track.insert_clip(first.clone(), &mut IdAllocator::default());
+        track.insert_clip(second.clone(), &mut IdAllocator::default());
+        track.volume = 0.5;
+        track.pan = 1.0;
+        track.enabled = false;
+
+        let tempo = TempoCurve::constant(120.0);
+        let frozen = render_track(&track, &tempo, 48000, PPQN..PPQN * 4 + PPQN / 8);
+        assert_eq!(frozen.channels(), 2);
+        assert_eq!(frozen.frames(), 3 * 24000 + 3000);
+        // Each clip lands where it starts, the same in both channels
+        let left: Vec<f32> = frozen.samples().iter().step_by(2).copied().collect();
+        let right: Vec<f32> = frozen
+            .samples()
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .copied()
+            .collect();
+        assert_eq!(left, right);
+        let first = render_clip(&first, &tempo, 48000).unwrap();
+        let second = render_clip(&second, &tempo, 48000).unwrap();
+        assert_eq!(&left[..first.frames()], first.samples());
+        assert_eq!(&left[48000..], second.samples());
+    }
+
+    #[test]
+    fn test_render_clip_loops_and_reverses_with_gain() {
+        // Two seconds of a one-second ramp, so the loop wraps
+        let mut clip = ramp_clip(PPQN, PPQN * 5);
+        clip.gain = 0.5;
+        clip.looped = true;
+        clip.reversed = true;
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        track.insert_clip(clip.clone(), &mut IdAllocator::default());
+        let timeline = render_timeline(&[track], &TempoCurve::constant(120.0), 48000, 1);
+
+        let bounced = render_clip(&clip, &TempoCurve::constant(120.0), 48000).unwrap();
+        assert_eq!(bounced.frames(), 96000 + 3000);
+        assert_eq!(bounced.samples(), &timeline.samples()[24000..]);
+        // Backwards from before the trim point, then round again from the end
+        let frame = |index: u32| 0.5 * (0.25 + index as f32 / 96000.0);
+        assert!((bounced.samples()[12000] - frame(48000 - 1 - 13200)).abs() < 1e-6);
+        assert!((bounced.samples()[48000] - frame(48000 - 1 - 1200)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_render_range_matches_its_part_of_the_timeline() {
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        track.insert_clip(ramp_clip(PPQN, PPQN * 2), &mut IdAllocator::default());
+        let timeline = render_timeline(
+            std::slice::from_ref(&track),
+            &TempoCurve::constant(120.0),
+            48000,
+            1,
+        );
+
+        // From inside the clip to past the end of everything
+        let range = render_range(
+            &[track],
+            &TempoCurve::constant(120.0),
+            48000,
+            1,
+            PPQN * 3 / 2..PPQN * 3,
+        );
+        assert_eq!(range.frames(), 36000);
+        assert_eq!(&range.samples()[..15000], &timeline.samples()[36000..]);
+        assert!(range.samples()[15000..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_overlapping_clips_sum_in_track_order() {
+        let mut ids = IdAllocator::default();
+        let mut first = Track::new(TrackId(1), "First".to_string());
+        first.volume = 0.5;
+        let mut second = Track::new(TrackId(2), "Second".to_string());
+        let clips = [
+            (0, ramp_clip(0, PPQN * 2)),
+            (1, ramp_clip(PPQN, PPQN * 3)),
+            (0, ramp_clip(PPQN * 2, PPQN * 4)),
+        ];
+        for (track, clip) in &clips {
+            [&mut first, &mut second][*track].insert_clip(clip.clone(), &mut ids);
+        }
+        let timeline = render_timeline(&[first, second], &TempoCurve::constant(120.0), 48000, 1);
+
+        // Each clip bounced on its own and added up in the same order
+        let mut expected = vec![0.0f32; timeline.frames()];
+        for (track, clip) in [&clips[0], &clips[2], &clips[1]] {
+            let volume = if *track == 0 { 0.5 } else { 1.0 };
+            let start = clip.start_tick as usize * 24000 / PPQN as usize;
+            let bounced = render_clip(clip, &TempoCurve::constant(120.0), 48000).unwrap();
+            for (out, &sample) in expected[start..].iter_mut().zip(bounced.samples()) {
+                *out += sample * volume;
+            }
+        }
+        assert_eq!(timeline.samples(), &expected[..]);
+    }
+
+    #[test]
+    fn test_render_is_the_same_on_any_number_of_threads() {
+        // Long enough to be mixed in several chunks
+        let mut clip = ramp_clip(PPQN, PPQN * 9);
+        clip.looped = true;
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        track.insert_clip(clip.clone(), &mut IdAllocator::default());
+        let tracks = [track];
+
+        let render_on = |threads: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| render_timeline(&tracks, &TempoCurve::constant(120.0), 48000, 2))
+        };
+        let single = render_on(1);
+        assert!(single.frames() > RENDER_CHUNK_FRAMES * 3);
+        assert_eq!(render_on(4).samples(), single.samples());
+
+        let bounced = render_clip(&clip, &TempoCurve::constant(120.0), 48000).unwrap();
+        let left: Vec<f32> = single.samples().iter().step_by(2).copied().collect();
+        assert_eq!(&left[24000..], bounced.samples());
+    }
+
+    #[test]
+    fn test_streamed_wav_matches_the_buffered_render() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut clip = ramp_clip(PPQN, PPQN * 13);
+        clip.looped = true;
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        track.insert_clip(clip, &mut IdAllocator::default());
+        // Effects carry over from one batch to the next
+        let mut reverbed = track.clone();
+        reverbed.effects = vec![daw_dsp::Effect::from_kind("reverb").unwrap()];
+        let mut echo = Track::new_bus(TrackId(2), "Echo".to_string());
+        echo.effects = vec![daw_dsp::Effect::from_kind("delay").unwrap()];
+        track.sends = vec![TrackSend {
+            bus: TrackId(2),
+            level: 0.5,
+            pre_fader: false,
+        }];
+        let tracks = [track, reverbed, echo];
+        let tempo = TempoCurve::constant(120.0);
+        let range = PPQN / 2..PPQN * 13;
+        let master = daw_dsp::MasterSettings {
+            gain_db: 3.0,
+            pan: 0.0,
+            limiter: Some(daw_dsp::LimiterSettings::default()),
+        };
+
+        // Mixed, processed and written in one go
+        let buffered = render_range(&tracks, &tempo, 48000, 2, range.clone());
+        let buffered = process_master(&buffered, &mut daw_dsp::MasterBus::new(master, 48000));
+        let buffered = Dither::Triangular.apply(&buffered, 16);
+        let buffered_path = dir.path().join("buffered.wav");
+        write_wav(&buffered, &buffered_path, RenderFormat::Int16).unwrap();
+
+        // Two threads' worth of chunks at a time, so it takes several batches
+        let streamed_path = dir.path().join("streamed.wav");
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let summary = pool
+            .install(|| {
+                render_range_to_wav(
+                    &tracks,
+                    &tempo,
+                    48000,
+                    2,
+                    range,
+                    &mut [
+                        &mut daw_dsp::MasterBus::new(master, 48000),
+                        &mut Dither::Triangular.ditherer(16, 2),
+                    ],
+                    &streamed_path,
+                    RenderFormat::Int16,
+                )
+            })
+            .unwrap();
+
+        assert!(buffered.frames() > RENDER_CHUNK_FRAMES * 2 * 2);
+        assert_eq!(
+            std::fs::read(&streamed_path).unwrap(),
+            std::fs::read(&buffered_path).unwrap()
+        );
+        assert_eq!(
+            summary,
+            RenderSummary::of(&buffered, &streamed_path),
+            "measured as it was written"
+        );
+    }
+
+    #[test]
+    fn test_effects_run_before_the_track_volume() {
+        let mut ids = IdAllocator::default();
+        let mut track = Track::new(TrackId(1), "Echo".to_string());
+        track.insert_clip(ramp_clip(0, PPQN), &mut ids);
+        track.volume = 0.5;
+        let dry = render_timeline(&[track.clone()], &TempoCurve::constant(120.0), 48000, 1);
+
+        // 100 ms is 4800 frames
+        track.effects = vec![Effect::Delay(daw_dsp::DelaySettings {
+            time_ms: 100.0,
+            feedback: 0.0,
+            mix: 1.0,
+        })];
+        let echoed = render_timeline(&[track], &TempoCurve::constant(120.0), 48000, 1);
+        assert_eq!(&echoed.samples()[..4800], &[0.0; 4800]);
+        for frame in [6000, 12000, 20000] {
+            assert_eq!(echoed.samples()[frame + 4800], dry.samples()[frame]);
+        }
+    }
+
+    #[test]
+    fn test_sends_are_mixed_through_their_bus() {
+        let mut ids = IdAllocator::default();
+        let mut track = Track::new(TrackId(1), "Vocal".to_string());
+        track.insert_clip(ramp_clip(0, PPQN), &mut ids);
+        track.volume = 0.5;
+        let mut bus = Track::new_bus(TrackId(2), "Bus".to_string());
+        bus.volume = 0.5;
+        let tempo = TempoCurve::constant(120.0);
+        let alone = render_timeline(&[track.clone(), bus.clone()], &tempo, 48000, 1);
+
+        let send = |pre_fader| TrackSend {
+            bus: TrackId(2),
+            level: 0.5,
+            pre_fader,
+        };
+        let level = |pre_fader: bool| {
+            let mut track = track.clone();
+            track.sends = vec![send(pre_fader)];
+            let mix = render_timeline(&[track, bus.clone()], &tempo, 48000, 1);
+            mix.samples()[12000] / alone.samples()[12000]
+        };
+        // After the fader the send follows the track's volume down
+        assert!((level(false) - 1.25).abs() < 1e-5);
+        assert!((level(true) - 1.5).abs() < 1e-5);
+
+        // A muted bus silences the send, but a solo elsewhere doesn't
+        let mut sending = track.clone();
+        sending.sends = vec![send(false)];
+        sending.solo = true;
+        let soloed = render_timeline(&[sending.clone(), bus.clone()], &tempo, 48000, 1);
+        assert!((soloed.samples()[12000] / alone.samples()[12000] - 1.25).abs() < 1e-5);
+        bus.enabled = false;
+        let muted = render_timeline(&[sending, bus], &tempo, 48000, 1);
+        assert!((muted.samples()[12000] - alone.samples()[12000]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_pan_places_tracks_between_the_channels() {
+        let mut ids = IdAllocator::default();
+        let mut left = Track::new(TrackId(1), "Left".to_string());
+        left.pan = -1.0;
+        left.insert_clip(ramp_clip(0, PPQN), &mut ids);
+        let mut right = Track::new(TrackId(2), "Right".to_string());
+        right.pan = 0.5;
+        right.insert_clip(ramp_clip(PPQN, PPQN * 2), &mut ids);
+        let mix = render_timeline(&[left, right], &TempoCurve::constant(120.0), 48000, 2);
+        let frame = |index: usize| [mix.samples()[index * 2], mix.samples()[index * 2 + 1]];
+
+        let [l, r] = frame(12000);
+        let level = 0.25 + 13200.0 / 96000.0;
+        assert!((l - level * std::f32::consts::SQRT_2).abs() < 1e-5);
+        assert!(r.abs() < 1e-6);
+
+        let [l, r] = frame(24000 + 12000);
+        let [pan_left, pan_right] = pan_gains(0.5);
+        assert!((l - level * pan_left).abs() < 1e-5);
+        assert!((r - level * pan_right).abs() < 1e-5);
+        assert!(r > l);
+    }
+
+    #[test]
+    fn test_volume_and_pan_automation_is_followed_per_frame() {
+        let mut ids = IdAllocator::default();
+        let mut track = Track::new(TrackId(1), "Sweep".to_string());
+        track.insert_clip(ramp_clip(0, PPQN), &mut ids);
+        let line = |parameter: &str, from: f32, to: f32| {
+            let mut lane = AutomationLane::new(parameter);
+            for (tick, value) in [(0, from), (PPQN, to)] {
+                lane.add_point(AutomationPoint {
+                    tick,
+                    value,
+                    curve: AutomationCurve::Linear,
+                });
+            }
+            lane
+        };
+        track.automation = vec![line("volume", 0.0, 1.0), line("pan", -1.0, 1.0)];
+        let mix = render_timeline(&[track], &TempoCurve::constant(120.0), 48000, 2);
+        let frame = |index: usize| [mix.samples()[index * 2], mix.samples()[index * 2 + 1]];
+
+        // Halfway through the beat: half volume, panned to the center
+        let level = 0.25 + 13200.0 / 96000.0;
+        let [l, r] = frame(12000);
+        assert!((l - level * 0.5).abs() < 1e-5);
+        assert!((r - level * 0.5).abs() < 1e-5);
+
+        let [l, r] = frame(18000);
+        assert!(r > l);
+    }
+
+    #[test]
+    fn test_solo_silences_the_other_tracks() {
+        let mut ids = IdAllocator::default();
+        let mut first = Track::new(TrackId(1), "First".to_string());
+        first.insert_clip(ramp_clip(0, PPQN), &mut ids);
+        let mut second = Track::new(TrackId(2), "Second".to_string());
+        second.insert_clip(ramp_clip(0, PPQN * 2), &mut ids);
+        second.solo = true;
+        let tracks = [first.clone(), second.clone()];
+
+        let mix = render_timeline(&tracks, &TempoCurve::constant(120.0), 48000, 1);
+        let alone = render_timeline(&[second], &TempoCurve::constant(120.0), 48000, 1);
+        assert_eq!(mix.samples(), alone.samples());
+
+        // The end follows what's heard too
+        let mut tracks = tracks;
+        tracks[0].insert_clip(ramp_clip(PPQN * 4, PPQN * 5), &mut ids);
+        assert_eq!(render_end_tick(&tracks), PPQN * 2 + PPQN / 8);
+    }
+
+    #[test]
+    fn test_summary_counts_clipped_samples() {
+        let buffer = AudioArc::new(vec![0.5, -0.5, 1.0, -1.5, 2.0, 0.0], 48000, 2);
+        let summary = RenderSummary::of(&buffer, Path::new("mix.wav"));
+        assert_eq!(summary.clipped_samples, 2);
+        assert_eq!(summary.peak_db, gain_to_db(2.0));
+        let rms = ((0.25 + 0.25 + 1.0 + 2.25 + 4.0) / 6.0f32).sqrt();
+        assert!((summary.rms_db - gain_to_db(rms)).abs() < 1e-4);
+        assert_eq!(summary.duration, Duration::from_secs_f64(3.0 / 48000.0));
+
+        let silence = RenderSummary::of(&AudioArc::new(vec![0.0; 4], 48000, 2), Path::new(""));
+        assert_eq!(silence.rms_db, f32::NEG_INFINITY);
+        assert_eq!(silence.clipped_samples, 0);
+    }
+
+    #[test]
+    fn test_tail_ticks() {
+        assert_eq!(RenderTail::Seconds(1.5).ticks(120.0, (4, 4)), PPQN * 3);
+        assert_eq!(RenderTail::Bars(2).ticks(120.0, (4, 4)), PPQN * 8);
+        assert_eq!(RenderTail::Bars(1).ticks(90.0, (6, 8)), PPQN * 3);
+    }
+
+    #[test]
+    fn test_write_wav_in_each_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let buffer = AudioArc::new(vec![0.0, 0.5, -1.0, 1.5], 48000, 2);
+
+        for format in [
+            RenderFormat::Int16,
+            RenderFormat::Int24,
+            RenderFormat::Float32,
+        ] {
+            let path = dir.path().join(format!("{:?}.wav", format));
+            write_wav(&buffer, &path, format).unwrap();
+            let reader = hound::WavReader::open(&path).unwrap();
+            let spec = reader.spec();
+            assert_eq!((spec.channels, spec.sample_rate), (2, 48000));
+            assert_eq!(spec.bits_per_sample, format.bits_per_sample());
+            if format == RenderFormat::Float32 {
+                let samples: Vec<f32> = reader.into_samples().map(Result::unwrap).collect();
+                assert_eq!(samples, buffer.samples());
+            } else {
+                let full_scale = 1 << (format.bits_per_sample() - 1);
+                let samples: Vec<i32> = reader.into_samples().map(Result::unwrap).collect();
+                // Clipped at full scale
+                assert_eq!(samples, [0, full_scale / 2, -full_scale, full_scale - 1]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_midi_clips_play_through_the_track_sampler() {
+        // A beat-long note a beat into a two-beat clip
+        let mut clip = ramp_clip(0, PPQN * 2);
+        clip.content = ClipContent::Midi(NoteList::new(vec![Note {
+            start_tick: PPQN,
+            duration_ticks: PPQN,
+            pitch: 60,
+            velocity: 127,
+        }]));
+        let mut track = Track::new(TrackId(1), "Sampler".to_string());
+        track.insert_clip(clip, &mut IdAllocator::default());
+        let tempo = TempoCurve::constant(120.0);
+        assert!(
+            render_timeline(std::slice::from_ref(&track), &tempo, 48000, 1)
+                .samples()
+                .iter()
+                .all(|&sample| sample == 0.0)
+        );
+
+        track.sampler = Some(Sampler::pitched(
+            AudioArc::new(vec![1.0; 96000], 48000, 1),
+            60,
+        ));
+        let frozen = render_track(&track, &tempo, 48000, 0..PPQN * 4);
+        let mix = render_range(&[track], &tempo, 48000, 1, 0..PPQN * 4);
+        let left: Vec<f32> = frozen.samples().iter().step_by(2).copied().collect();
+        for samples in [mix.samples(), &left[..]] {
+            assert!(samples[..24000].iter().all(|&sample| sample == 0.0));
+            // Held across every block the sampler plays, then released
+            assert!(samples[24200..48000].iter().all(|&sample| sample > 0.5));
+            assert!(samples[52000..].iter().all(|&sample| sample == 0.0));
+        }
+    }
+
+    #[test]
+    fn test_file_type_follows_the_extension() {
+        let file_type = |path| RenderFileType::for_path(Path::new(path));
+        assert_eq!(file_type("mix").unwrap(), RenderFileType::Wav);
+        assert_eq!(file_type("mix.FLAC").unwrap(), RenderFileType::Flac);
+        #[cfg(feature = "vorbis")]
+        assert_eq!(file_type("mix.ogg").unwrap(), RenderFileType::Vorbis);
+        assert!(file_type("mix.mp3").is_err());
+    }
+
+    #[test]
+    fn test_render_clip_skips_midi() {
+        let mut clip = ramp_clip(0, PPQN);
+        clip.content = ClipContent::Midi(Default::default());
+        assert!(render_clip(&clip, &TempoCurve::constant(120.0), 48000).is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//! Bringing a finished mix to a target level.
+//!
+//! Streaming services and broadcasters ask for a loudness and a true peak ceiling,
+//! so a render can be turned up or down to meet them. The whole mix gets the same
+//! gain; nothing is limited or compressed.
+
+use daw_transport::{AudioArc, db_to_gain, gain_to_db};
+
+/// Target level for a render.
+///
+/// With a loudness target the mix is brought to it, turned down further if that
+/// would put its true peak over `true_peak_db`. With only a true peak target, the
+/// mix is peak-normalized to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalization {
+    /// Integrated loudness to reach, in LUFS, such as -14 for streaming
+    pub loudness_lufs: Option<f32>,
+    /// Highest true peak allowed, in dBTP, such as -1
+    pub true_peak_db: Option<f32>,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Self {
+            loudness_lufs: Some(-14.0),
+            true_peak_db: Some(-1.0),
+        }
+    }
+}
+
+impl Normalization {
+    /// Gain in dB that brings `mix` to the target, or 0 if it can't be measured,
+    /// such as for silence
+    pub fn gain_db(&self, mix: &AudioArc) -> f32 {
+        let peak_gain = self
+            .true_peak_db
+            .map(|target| target - gain_to_db(mix.true_peak()));
+        let gain = match self.loudness_lufs {
+            Some(target) => {
+                let loudness_gain = target - mix.integrated_loudness();
+                // Audio too short or quiet to measure isn't brought to the ceiling instead
+                if !loudness_gain.is_finite() {
+                    return 0.0;
+                }
+                peak_gain.map_or(loudness_gain, |peak_gain| loudness_gain.min(peak_gain))
+            }
+            None => peak_gain.unwrap_or(0.0),
+        };
+        if gain.is_finite() { gain } else { 0.0 }
+    }
+
+    /// `mix` at the target level, with the gain applied in dB
+    pub fn apply(&self, mix: &AudioArc) -> (AudioArc, f32) {
+        let gain_db = self.gain_db(mix);
+        if gain_db == 0.0 {
+            return (mix.clone(), 0.0);
+        }
+        (mix.with_gain(db_to_gain(gain_db)), gain_db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(amplitude: f32) -> AudioArc {
+        let samples = (0..48000 * 2)
+            .flat_map(|frame| {
+                let value =
+                    amplitude * (frame as f32 * 997.0 / 48000.0 * std::f32::consts::TAU).sin();
+                [value, value]
+            })
+            .collect();
+        AudioArc::new(samples, 48000, 2)
+    }
+
+    #[test]
+    fn test_normalize_to_loudness() {
+        let target = Normalization {
+            loudness_lufs: Some(-14.0),
+            true_peak_db: None,
+        };
+        let (normalized, gain_db) = target.apply(&tone(0.1));
+        // A -20 dBFS stereo tone reads -20 LUFS
+        assert!((gain_db - 6.0).abs() < 0.1, "{gain_db}");
+        assert!((normalized.integrated_loudness() + 14.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_true_peak_ceiling_holds_loudness_back() {
+        // -14 LUFS would take this tone's peaks to -14 dBTP
+        let target = Normalization {
+            loudness_lufs: Some(-14.0),
+            true_peak_db: Some(-16.0),
+        };
+        let (normalized, gain_db) = target.apply(&tone(0.1));
+        assert!((gain_db - 4.0).abs() < 0.05, "{gain_db}");
+        assert!((gain_to_db(normalized.true_peak()) + 16.0).abs() < 0.05);
+        assert!((normalized.integrated_loudness() + 16.0).abs() < 0.1);
+
+        // Only a ceiling peak-normalizes
+        let target = Normalization {
+            loudness_lufs: None,
+            true_peak_db: Some(-1.0),
+        };
+        let (normalized, _) = target.apply(&tone(0.1));
+        assert!((gain_to_db(normalized.true_peak()) + 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_silence_and_short_audio_are_left_alone() {
+        // Too short for a loudness reading
+        let click = AudioArc::new(vec![0.0, 0.5, 0.0], 48000, 1);
+        assert_eq!(Normalization::default().gain_db(&click), 0.0);
+
+        let silence = AudioArc::new(vec![0.0; 96000], 48000, 2);
+        let (normalized, gain_db) = Normalization::default().apply(&silence);
+        assert_eq!(gain_db, 0.0);
+        assert_eq!(normalized.samples(), silence.samples());
+    }
+}
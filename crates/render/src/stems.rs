@@ -0,0 +1,149 @@
+//! Rendering each track to its own file.
+//!
+//! Stems let a mix be carried on in another DAW or by a collaborator: every track
+//! comes out as a WAV file of the same length, so they line up when imported at the
+//! same position.
+
+use std::path::{Path, PathBuf};
+
+use daw_transport::{TempoCurve, Track, any_soloed, effective_mix};
+
+use crate::{Mixdown, RenderFormat, render_end_tick, stream_wav};
+
+/// Render each track that plays audio to its own stereo WAV file in `out_dir`,
+/// returning the files written in track order.
+///
+/// Stems run from the start of the arrangement to the end of the full mix and carry
+/// the track's clips, volume and pan, along with the mute and volume of folders
+/// it's in.
+/// Each stem is written as it's mixed, so only a few blocks of it are held at once.
+/// Muted tracks, tracks silenced by a solo, folders and tracks without audio clips
+/// are left out. Files are named after their track, numbered in order
+/// (`01 Kick.wav`), with characters that aren't allowed in file names replaced.
+/// `out_dir` is created if it doesn't exist.
+pub fn render_stems(
+    tracks: &[Track],
+    tempo: &TempoCurve,
+    sample_rate: u32,
+    out_dir: &Path,
+) -> anyhow::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(out_dir)?;
+    let range = 0..render_end_tick(tracks);
+    let any_soloed = any_soloed(tracks);
+
+    let mut paths = Vec::new();
+    for (index, (track, mix)) in tracks.iter().zip(effective_mix(tracks)).enumerate() {
+        let has_audio = track.clips().iter().any(|clip| clip.audio().is_some());
+        if !mix.audible(any_soloed) || track.is_folder || !has_audio {
+            continue;
+        }
+        let mixdown = Mixdown::new(tracks, tempo, sample_rate, 2, range.clone(), Some(index));
+        let path = out_dir.join(format!(
+            "{:02} {}.wav",
+            paths.len() + 1,
+            file_name_for(&track.name)
+        ));
+        stream_wav(&mixdown, &mut [], &path, RenderFormat::Float32)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// `name` with the characters file systems reject replaced by `_`
+fn file_name_for(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    // Windows drops trailing dots and spaces
+    let name = name.trim_end_matches(['.', ' ']).trim_start();
+    if name.is_empty() {
+        "Track".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_timeline;
+    use daw_transport::{
+        AudioArc, Clip, ClipContent, ClipId, ClipSource, IdAllocator, PPQN, TrackId, WaveformData,
+    };
+    use std::sync::Arc;
+
+    fn clip(start_tick: u64, level: f32) -> Clip {
+        let audio = AudioArc::new(vec![level; 48000], 48000, 1);
+        Clip {
+            id: ClipId(0),
+            start_tick,
+            end_tick: start_tick + PPQN,
+            waveform: Arc::new(WaveformData::from_audio_arc(&audio, 512)),
+            content: ClipContent::Audio(audio),
+            audio_offset: 0,
+            name: "clip".to_string(),
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: ClipSource::default(),
+        }
+    }
+
+    #[test]
+    fn test_stems_add_up_to_the_mix() {
+        let mut ids = IdAllocator::default();
+        let mut drums = Track::new_folder(TrackId(0), "Drums".to_string());
+        drums.volume = 0.5;
+        let mut kick = Track::new(TrackId(1), "Kick/Sub?".to_string());
+        kick.parent = Some(TrackId(0));
+        kick.insert_clip(clip(0, 0.5), &mut ids);
+        let mut bass = Track::new(TrackId(2), "Bass".to_string());
+        bass.insert_clip(clip(PPQN, 0.25), &mut ids);
+        let mut muted = Track::new(TrackId(3), "Muted".to_string());
+        muted.enabled = false;
+        muted.insert_clip(clip(0, 1.0), &mut ids);
+        let empty = Track::new(TrackId(4), "Empty".to_string());
+        let tracks = vec![drums, kick, bass, muted, empty];
+
+        let dir = tempfile::tempdir().unwrap();
+        let out_dir = dir.path().join("stems");
+        let paths = render_stems(&tracks, &TempoCurve::constant(120.0), 48000, &out_dir).unwrap();
+        assert_eq!(
+            paths,
+            [
+                out_dir.join("01 Kick_Sub_.wav"),
+                out_dir.join("02 Bass.wav")
+            ]
+        );
+
+        let mix = render_timeline(&tracks, &TempoCurve::constant(120.0), 48000, 2);
+        let stems: Vec<Vec<f32>> = paths
+            .iter()
+            .map(|path| {
+                let reader = hound::WavReader::open(path).unwrap();
+                reader.into_samples().map(Result::unwrap).collect()
+            })
+            .collect();
+        assert!(stems.iter().all(|stem| stem.len() == mix.samples().len()));
+        // The kick at the folder's volume, then the bass
+        assert_eq!(stems[0][0], 0.25);
+        assert_eq!(stems[1][0], 0.0);
+        for (i, &sample) in mix.samples().iter().enumerate() {
+            assert_eq!(stems[0][i] + stems[1][i], sample);
+        }
+    }
+
+    #[test]
+    fn test_file_name_for_replaces_unsafe_characters() {
+        assert_eq!(file_name_for("Lead: Vox*2"), "Lead_ Vox_2");
+        assert_eq!(file_name_for(" Pad... "), "Pad");
+        assert_eq!(file_name_for("..."), "Track");
+    }
+}
@@ -0,0 +1,102 @@
+//! Lossy renders as Vorbis in an Ogg file, for sharing a mix where size matters
+//! more than the last bit of quality.
+//!
+//! Vorbis codes any sample rate, so the mix is written at its own. Encoding is
+//! done by libvorbis through `vorbis_rs`, which writes the Ogg pages and their
+//! granule positions so players end the audio on its last frame.
+
+use std::io::{BufWriter, Write};
+use std::num::{NonZeroU8, NonZeroU32};
+use std::path::Path;
+
+use anyhow::Context;
+use daw_transport::AudioArc;
+use vorbis_rs::{VorbisBitrateManagementStrategy, VorbisEncoderBuilder};
+
+/// Frames handed to the encoder at a time
+const BLOCK_FRAMES: usize = 4096;
+
+/// libvorbis quality from -0.1 to 1.0; 0.6 is around 190 kb/s for stereo,
+/// transparent for most music
+const QUALITY: f32 = 0.6;
+
+/// Write `buffer` to an Ogg Vorbis file at `path`. Vorbis holds floats, so no
+/// sample format applies and nothing is clipped before encoding.
+pub fn write_vorbis(buffer: &AudioArc, path: &Path) -> anyhow::Result<()> {
+    let sample_rate =
+        NonZeroU32::new(buffer.sample_rate()).context("Vorbis files need a sample rate")?;
+    let channels = u8::try_from(buffer.channels())
+        .ok()
+        .and_then(NonZeroU8::new)
+        .with_context(|| {
+            format!(
+                "Vorbis files hold 1 to 255 channels, not {}",
+                buffer.channels()
+            )
+        })?;
+
+    let mut file = BufWriter::new(std::fs::File::create(path)?);
+    let mut encoder = VorbisEncoderBuilder::new(sample_rate, channels, &mut file)?
+        .bitrate_management_strategy(VorbisBitrateManagementStrategy::QualityVbr {
+            target_quality: QUALITY,
+        })
+        .build()?;
+    let planar = buffer.planar();
+    for start in (0..buffer.frames()).step_by(BLOCK_FRAMES) {
+        let end = (start + BLOCK_FRAMES).min(buffer.frames());
+        let block: Vec<&[f32]> = planar.iter().map(|channel| &channel[start..end]).collect();
+        encoder.encode_audio_block(&block)?;
+    }
+    encoder.finish()?;
+    file.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::io::MediaSourceStream;
+
+    #[test]
+    fn test_write_vorbis_keeps_the_length_and_rate() {
+        let samples = (0..44100)
+            .flat_map(|i| {
+                let sample = (i as f32 * 0.05).sin() * 0.5;
+                [sample, -sample]
+            })
+            .collect();
+        let buffer = AudioArc::new(samples, 44100, 2);
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tone.ogg");
+        write_vorbis(&buffer, &path).unwrap();
+
+        let file = std::fs::File::open(&path).unwrap();
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let mut format = symphonia::default::get_probe()
+            .format(
+                &Default::default(),
+                stream,
+                &Default::default(),
+                &Default::default(),
+            )
+            .unwrap()
+            .format;
+        let track = format.default_track().unwrap();
+        assert_eq!(track.codec_params.sample_rate, Some(44100));
+        let mut decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &Default::default())
+            .unwrap();
+        let mut decoded = Vec::new();
+        while let Ok(packet) = format.next_packet() {
+            let audio = decoder.decode(&packet).unwrap();
+            let mut samples = SampleBuffer::<f32>::new(audio.capacity() as u64, *audio.spec());
+            samples.copy_interleaved_ref(audio);
+            decoded.extend_from_slice(samples.samples());
+        }
+        // Every frame, and none of the padding
+        assert_eq!(decoded.len(), buffer.samples().len());
+        // Far smaller than the 16-bit PCM it came from
+        assert!(std::fs::metadata(&path).unwrap().len() < 44100 * 2 * 2 / 4);
+    }
+}
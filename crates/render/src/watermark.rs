@@ -0,0 +1,250 @@
+//! Marking a bounce as a demo.
+//!
+//! A [`Watermark`] lays a quiet tone or a recorded voice tag over a finished mix at a
+//! regular interval, so a work-in-progress can be sent out for listening without
+//! handing over a clean master.
+
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use daw_dsp::Processor;
+use daw_transport::{AudioArc, db_to_gain, fade_gain};
+
+/// Length of the fade at each end of a generated tone, so it starts and stops without
+/// a click
+const TONE_FADE_SECS: f32 = 0.01;
+
+/// What a [`Watermark`] lays over the mix.
+#[derive(Debug, Clone)]
+pub enum WatermarkSound {
+    /// A sine tone
+    Tone {
+        /// Pitch in Hz
+        frequency: f32,
+        /// How long each beep lasts
+        length: Duration,
+    },
+    /// A recording, such as a spoken "demo" tag, played as it is
+    Tag(AudioArc),
+}
+
+/// A sound laid over a render at a regular interval, marking it as a demo.
+///
+/// The first mark sits at the very start so even a short bounce carries one, and
+/// another follows every [`interval`](Self::interval) until the end. Marks are mixed
+/// in at [`level_db`](Self::level_db) at their peak, whatever the level of the tag
+/// recording.
+///
+/// # Examples
+///
+/// ```
+/// use daw_render::Watermark;
+/// use daw_transport::AudioArc;
+/// use std::time::Duration;
+///
+/// let mix = AudioArc::new(vec![0.0; 2 * 44100 * 30], 44100, 2);
+/// let watermark = Watermark {
+///     interval: Duration::from_secs(10),
+///     ..Watermark::default()
+/// };
+/// let marked = watermark.apply(&mix).unwrap();
+/// assert!(marked.peak() > 0.0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Watermark {
+    /// Time from the start of one mark to the start of the next
+    pub interval: Duration,
+    /// Peak level of each mark in dBFS
+    pub level_db: f32,
+    /// What to lay over the mix
+    pub sound: WatermarkSound,
+}
+
+impl Default for Watermark {
+    /// A half-second 1 kHz beep at -30 dBFS every 15 seconds
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(15),
+            level_db: -30.0,
+            sound: WatermarkSound::Tone {
+                frequency: 1000.0,
+                length: Duration::from_millis(500),
+            },
+        }
+    }
+}
+
+impl Watermark {
+    /// Return a copy of `mix` with the watermark laid over it.
+    ///
+    /// Fails only when a tag recorded at another sample rate can't be resampled to
+    /// the mix's rate. A zero interval is treated as a single mark at the start.
+    pub fn apply(&self, mix: &AudioArc) -> anyhow::Result<AudioArc> {
+        let mut samples = mix.samples().to_vec();
+        self.marker(mix.sample_rate())?
+            .process(&mut samples, mix.channels() as usize);
+        Ok(AudioArc::new(samples, mix.sample_rate(), mix.channels()))
+    }
+
+    /// The watermark as a [`Processor`], for a render written a block at a time;
+    /// blocks come out the same as [`apply`](Self::apply) on the whole mix
+    pub fn marker(&self, sample_rate: u32) -> anyhow::Result<WatermarkMarker> {
+        Ok(WatermarkMarker {
+            mark: self.mark(sample_rate)?,
+            interval: (self.interval.as_secs_f64() * sample_rate as f64) as usize,
+            position: 0,
+        })
+    }
+
+    /// One mark at `sample_rate`, scaled to the watermark's level
+    fn mark(&self, sample_rate: u32) -> anyhow::Result<AudioArc> {
+        match &self.sound {
+            WatermarkSound::Tone { frequency, length } => {
+                let frames = (length.as_secs_f32() * sample_rate as f32) as u64;
+                let fade = ((TONE_FADE_SECS * sample_rate as f32) as u64).min(frames / 2);
+                let gain = db_to_gain(self.level_db);
+                let samples = (0..frames)
+                    .map(|frame| {
+                        let envelope = if frame < fade {
+                            fade_gain(frame, fade)
+                        } else if frames - frame < fade {
+                            fade_gain(frames - frame, fade)
+                        } else {
+                            1.0
+                        };
+                        let phase = TAU * frequency * frame as f32 / sample_rate as f32;
+                        phase.sin() * gain * envelope
+                    })
+                    .collect();
+                Ok(AudioArc::new(samples, sample_rate, 1))
+            }
+            WatermarkSound::Tag(tag) => Ok(tag.resample(sample_rate)?.normalize(self.level_db)),
+        }
+    }
+}
+
+/// A [`Watermark`] laid over a mix one block after another, made by
+/// [`Watermark::marker`].
+pub struct WatermarkMarker {
+    mark: AudioArc,
+    /// Frames from the start of one mark to the start of the next, 0 for one mark
+    interval: usize,
+    /// Frame of the mix the next block starts at
+    position: usize,
+}
+
+impl Processor for WatermarkMarker {
+    fn process(&mut self, block: &mut [f32], channels: usize) {
+        let mark_channels = self.mark.channels() as usize;
+        let mark_frames = self.mark.frames();
+        let block_start = self.position;
+        let block_end = block_start + block.len() / channels;
+        self.position = block_end;
+
+        // Marks are laid in order, so ones that overlap add up the same way however
+        // the mix is split into blocks
+        let mut start = match self.interval {
+            0 => 0,
+            interval => block_start.saturating_sub(mark_frames) / interval * interval,
+        };
+        while start < block_end {
+            let from = start.max(block_start);
+            let to = (start + mark_frames).min(block_end);
+            for frame in from..to {
+                let mark_frame = &self.mark.samples()[(frame - start) * mark_channels..];
+                for ch in 0..channels {
+                    block[(frame - block_start) * channels + ch] += mark_frame[ch % mark_channels];
+                }
+            }
+            if self.interval == 0 {
+                break;
+            }
+            start += self.interval;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use daw_transport::gain_to_db;
+
+    fn silence(seconds: usize, sample_rate: u32) -> AudioArc {
+        AudioArc::new(
+            vec![0.0; seconds * sample_rate as usize * 2],
+            sample_rate,
+            2,
+        )
+    }
+
+    /// Frames at which the audio goes from silence to sound
+    fn onsets(audio: &AudioArc) -> Vec<usize> {
+        let frames: Vec<bool> = audio
+            .samples()
+            .chunks_exact(audio.channels() as usize)
+            .map(|frame| frame.iter().any(|&sample| sample != 0.0))
+            .collect();
+        (0..frames.len())
+            .filter(|&i| frames[i] && (i == 0 || !frames[i - 1]))
+            .collect()
+    }
+
+    #[test]
+    fn test_tone_repeats_at_interval_and_level() {
+        let mix = silence(10, 8000);
+        let watermark = Watermark {
+            interval: Duration::from_secs(4),
+            level_db: -24.0,
+            sound: WatermarkSound::Tone {
+                frequency: 500.0,
+                length: Duration::from_millis(250),
+            },
+        };
+        let marked = watermark.apply(&mix).unwrap();
+
+        assert_eq!(marked.frames(), mix.frames());
+        // The first sample of a sine is zero, so each mark shows up one frame in
+        assert_eq!(onsets(&marked), vec![1, 32001, 64001]);
+        assert!((gain_to_db(marked.peak()) - -24.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_tag_is_mixed_in_at_level() {
+        let mix = AudioArc::new(vec![0.5; 2 * 1000], 1000, 2);
+        let tag = AudioArc::new(vec![1.0, -1.0, 1.0, -1.0], 1000, 1);
+        let watermark = Watermark {
+            interval: Duration::from_millis(400),
+            level_db: -6.0,
+            sound: WatermarkSound::Tag(tag),
+        };
+        let marked = watermark.apply(&mix).unwrap();
+
+        let level = db_to_gain(-6.0);
+        let left: Vec<f32> = marked.samples().iter().step_by(2).copied().collect();
+        assert!((left[0] - (0.5 + level)).abs() < 1e-6);
+        assert!((left[1] - (0.5 - level)).abs() < 1e-6);
+        assert_eq!(left[4], 0.5);
+        assert!((left[400] - (0.5 + level)).abs() < 1e-6);
+        assert!((left[800] - (0.5 + level)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_marking_in_blocks_matches_the_whole_mix() {
+        // Marks longer than the interval, so they overlap
+        let mix = silence(3, 1000);
+        let watermark = Watermark {
+            interval: Duration::from_millis(300),
+            level_db: -12.0,
+            sound: WatermarkSound::Tone {
+                frequency: 50.0,
+                length: Duration::from_millis(500),
+            },
+        };
+        let mut marker = watermark.marker(1000).unwrap();
+        let mut blocks = mix.samples().to_vec();
+        for block in blocks.chunks_mut(2 * 77) {
+            marker.process(block, 2);
+        }
+        assert_eq!(blocks, watermark.apply(&mix).unwrap().samples());
+    }
+}
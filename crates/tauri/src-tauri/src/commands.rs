@@ -4,11 +4,19 @@
 //! Each command locks the AppState, performs an operation on the Session,
 //! and returns a SessionSnapshot to keep the frontend in sync.
 
-use crate::dto::{session_to_snapshot, SessionSnapshot};
+use crate::dto::{
+    session_to_snapshot, AdsrDto, AutomationCurveDto, DitherDto, EndBehaviorDto, LimiterDto, LoadProgressEvent, NormalizationDto, RecentProjectDto,
+    RenderCompleteEvent, RenderFormatDto, RenderTailDto, SamplerLoadDto, SamplerSamplesDto, SaveOptionsDto, SessionSnapshot,
+    SoloModeDto, WatermarkDto,
+};
 use crate::state::AppState;
-use daw_core::Session;
+use daw_core::{
+    ClipId, Effect, MasterSettings, MusicalKey, RecentProjects, RenderOptions, Sampler,
+    SaveAsSamples, Session, TrackId,
+};
 use std::path::Path;
-use tauri::State;
+use tauri::{AppHandle, Emitter, State};
+use tauri_plugin_opener::OpenerExt;
 
 // Use anyhow::Error directly as Tauri supports it via InvokeError
 type CommandResult<T> = Result<T, String>;
@@ -19,13 +27,26 @@ type CommandResult<T> = Result<T, String>;
 
 /// Load a project file and create a new session.
 ///
-/// Returns a snapshot of the loaded session.
-#[tauri::command]
+/// Runs off the main thread and emits "load-progress" events while the
+/// project's audio is decoded. The session then watches its samples, so the
+/// poll loop reloads any that are saved from another program. Returns a
+/// snapshot of the loaded session.
+#[tauri::command(async)]
 pub fn session_load_project(
     path: String,
-    state: State<AppState>,
+    app: AppHandle,
+    state: State<'_, AppState>,
 ) -> CommandResult<SessionSnapshot> {
-    let session = Session::from_project(Path::new(&path)).map_err(|e| e.to_string())?;
+    let mut session = Session::from_project_with_progress(Path::new(&path), |fraction| {
+        let _ = app.emit("load-progress", LoadProgressEvent { fraction });
+    })
+    .map_err(|e| e.to_string())?;
+    // Samples edited in another program are reloaded when saved; without file
+    // watching the project still works, it just needs reopening to hear edits
+    if let Err(err) = session.set_watching_samples(true) {
+        eprintln!("Not watching samples for changes: {err}");
+    }
+    remember_project(Path::new(&path), &session);
     let snapshot = session_to_snapshot(&session);
 
     let mut session_lock = state
@@ -37,6 +58,29 @@ pub fn session_load_project(
     Ok(snapshot)
 }
 
+/// Put a project at the top of the recent list.
+///
+/// The list is a convenience, so failing to save it doesn't fail the command.
+fn remember_project(path: &Path, session: &Session) {
+    let mut recent = RecentProjects::load();
+    recent.add(path, session.name());
+    if let Err(err) = recent.save() {
+        eprintln!("Failed to save recent projects: {err}");
+    }
+}
+
+/// List recently opened projects, most recent first.
+///
+/// Projects whose files are gone are dropped from the list first.
+#[tauri::command]
+pub fn recent_projects_list() -> CommandResult<Vec<RecentProjectDto>> {
+    let mut recent = RecentProjects::load();
+    if !recent.prune().is_empty() {
+        recent.save().map_err(|e| e.to_string())?;
+    }
+    Ok(recent.list().iter().map(RecentProjectDto::from).collect())
+}
+
 /// Get the current session state without modifying it.
 ///
 /// Returns an error if no session is loaded.
@@ -65,18 +109,129 @@ pub fn session_save(state: State<AppState>) -> CommandResult<()> {
         .map_err(|_| "Failed to acquire session lock".to_string())?;
 
     let session = session_lock
-        .as_ref()
+        .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
     session.save_in_place().map_err(|e| e.to_string())?;
     Ok(())
 }
 
-/// Save the current session to a new path.
+/// Save the current session to a new path, either rewriting the references to
+/// samples next to the old project or copying them into an `audio` folder next to
+/// the new one.
+///
+/// Returns the names of samples that couldn't be found. Returns an error if no
+/// session is loaded.
+#[tauri::command]
+pub fn session_save_as(
+    path: String,
+    copy_samples: bool,
+    state: State<AppState>,
+) -> CommandResult<Vec<String>> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let samples = if copy_samples {
+        SaveAsSamples::Copy
+    } else {
+        SaveAsSamples::Reference
+    };
+    let report = session
+        .save_as(Path::new(&path), samples)
+        .map_err(|e| e.to_string())?;
+    remember_project(Path::new(&path), session);
+    Ok(report.missing)
+}
+
+/// Save the current session to a new path with copies of its samples in an
+/// `audio` folder next to it, so the project can be moved as a whole.
+///
+/// Returns the names of samples that couldn't be found and were left where they
+/// were referenced. Returns an error if no session is loaded.
+#[tauri::command]
+pub fn session_collect_and_save(
+    path: String,
+    state: State<AppState>,
+) -> CommandResult<Vec<String>> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let report = session
+        .save_with_samples(Path::new(&path))
+        .map_err(|e| e.to_string())?;
+    remember_project(Path::new(&path), session);
+    Ok(report.missing)
+}
+
+/// Set the project's author, notes and key.
+///
+/// `key` is a name like "F#m" or "Bb", or `null` for none. Returns an error if
+/// the key can't be read or no session is loaded.
+#[tauri::command]
+pub fn project_set_info(
+    author: String,
+    notes: String,
+    key: Option<String>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let key = key
+        .map(|key| MusicalKey::parse(&key).ok_or_else(|| format!("Unknown key: {}", key)))
+        .transpose()?;
+
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_author(author);
+    session.set_notes(notes);
+    session.set_key(key);
+    Ok(session_to_snapshot(session))
+}
+
+/// Set whether saving stores the sample files inside the project file, so it
+/// can be shared as a single file.
+///
+/// Returns an error if no session is loaded.
+#[tauri::command]
+pub fn session_set_embed_samples(enabled: bool, state: State<AppState>) -> CommandResult<()> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_embed_samples(enabled);
+    Ok(())
+}
+
+/// Set the format and compression the project is saved and autosaved with.
 ///
 /// Returns an error if no session is loaded.
 #[tauri::command]
-pub fn session_save_as(path: String, state: State<AppState>) -> CommandResult<()> {
+pub fn session_set_save_options(
+    options: SaveOptionsDto,
+    state: State<AppState>,
+) -> CommandResult<()> {
     let mut session_lock = state
         .session
         .lock()
@@ -86,16 +241,91 @@ pub fn session_save_as(path: String, state: State<AppState>) -> CommandResult<()
         .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
-    session.save(Path::new(&path)).map_err(|e| e.to_string())?;
-    session.set_project_path(path.into());
+    session.set_save_options(options.into());
     Ok(())
 }
 
 /// Render the current session to a WAV file.
 ///
+/// Emits a "render-complete" event with the file's length and levels, and
+/// returns the same. The file is written in `format`, 32-bit float if not
+/// given, and integer formats are rounded with `dither`. With `normalize`, the mix is brought to a loudness or true peak
+/// level first. With `watermark`, a demo tone or voice tag is laid over it.
+/// `start_tick` and `end_tick` render just that part of the arrangement, from
+/// its start or to its end if left out, with `tail` after that end. `metronome`
+/// mixes in the click. With `open_when_done`, the file is then
+/// opened in the default player. Returns an error if no session is loaded.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub fn session_render(
+    path: String,
+    open_when_done: Option<bool>,
+    normalize: Option<NormalizationDto>,
+    watermark: Option<WatermarkDto>,
+    format: Option<RenderFormatDto>,
+    dither: Option<DitherDto>,
+    start_tick: Option<u64>,
+    end_tick: Option<u64>,
+    tail: Option<RenderTailDto>,
+    metronome: Option<bool>,
+    app: AppHandle,
+    state: State<AppState>,
+) -> CommandResult<RenderCompleteEvent> {
+    let session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_ref()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let options = RenderOptions {
+        normalize: normalize.map(Into::into),
+        watermark: watermark.map(|w| w.to_watermark()).transpose()?,
+        format: format.map(Into::into).unwrap_or_default(),
+        dither: dither.map(Into::into).unwrap_or_default(),
+        start_tick,
+        end_tick,
+        tail: tail.map(Into::into),
+        metronome: metronome.unwrap_or(false),
+        ..Default::default()
+    };
+    let summary = session
+        .render_to_file_with(Path::new(&path), &options)
+        .map_err(|e| e.to_string())?;
+    let event = RenderCompleteEvent::from(&summary);
+    let _ = app.emit("render-complete", event.clone());
+
+    if open_when_done.unwrap_or(false) {
+        app.opener()
+            .open_path(&path, None::<&str>)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(event)
+}
+
+/// Show a rendered file in the system file manager.
+#[tauri::command]
+pub fn render_reveal(path: String, app: AppHandle) -> CommandResult<()> {
+    app.opener()
+        .reveal_item_in_dir(&path)
+        .map_err(|e| e.to_string())
+}
+
+/// Open a rendered file in the default audio player.
+#[tauri::command]
+pub fn render_open(path: String, app: AppHandle) -> CommandResult<()> {
+    app.opener()
+        .open_path(&path, None::<&str>)
+        .map_err(|e| e.to_string())
+}
+
+/// Export tempo, time signatures, locators and MIDI clips to a Standard MIDI File.
+///
 /// Returns an error if no session is loaded.
 #[tauri::command]
-pub fn session_render(path: String, state: State<AppState>) -> CommandResult<()> {
+pub fn session_export_midi(path: String, state: State<AppState>) -> CommandResult<()> {
     let session_lock = state
         .session
         .lock()
@@ -106,9 +336,8 @@ pub fn session_render(path: String, state: State<AppState>) -> CommandResult<()>
         .ok_or_else(|| "No session loaded".to_string())?;
 
     session
-        .render_to_file(Path::new(&path))
-        .map_err(|e| e.to_string())?;
-    Ok(())
+        .export_midi(Path::new(&path))
+        .map_err(|e| e.to_string())
 }
 
 // ============================================================================
@@ -179,13 +408,31 @@ pub fn transport_seek_to_tick(tick: u64, state: State<AppState>) -> CommandResul
     Ok(session_to_snapshot(session))
 }
 
-// ============================================================================
-// Track Commands
-// ============================================================================
+/// Set what playback does after the last clip.
+#[tauri::command]
+pub fn transport_set_end_behavior(
+    end_behavior: EndBehaviorDto,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
 
-/// Toggle a track's enabled state (mute/unmute).
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_end_behavior(end_behavior.into());
+    Ok(session_to_snapshot(session))
+}
+
+/// Set whether stopping returns the playhead to where playback started.
 #[tauri::command]
-pub fn track_toggle_enabled(track_id: u64, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+pub fn transport_set_return_to_start(
+    enabled: bool,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
     let mut session_lock = state
         .session
         .lock()
@@ -195,13 +442,13 @@ pub fn track_toggle_enabled(track_id: u64, state: State<AppState>) -> CommandRes
         .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
-    session.toggle_track_enabled(track_id);
+    session.set_return_to_start(enabled);
     Ok(session_to_snapshot(session))
 }
 
-/// Exclusively solo a track (unsolos all others).
+/// Set how many bars of metronome count in before playback starts (0-2).
 #[tauri::command]
-pub fn track_solo_exclusive(track_id: u64, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+pub fn transport_set_count_in(bars: u32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
     let mut session_lock = state
         .session
         .lock()
@@ -211,13 +458,13 @@ pub fn track_solo_exclusive(track_id: u64, state: State<AppState>) -> CommandRes
         .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
-    session.solo_track_exclusive(track_id);
+    session.set_count_in_bars(bars);
     Ok(session_to_snapshot(session))
 }
 
-/// Set a track's volume.
+/// Set how many bars before the cursor playback starts from.
 #[tauri::command]
-pub fn track_set_volume(track_id: u64, volume: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+pub fn transport_set_pre_roll(bars: u32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
     let mut session_lock = state
         .session
         .lock()
@@ -227,13 +474,13 @@ pub fn track_set_volume(track_id: u64, volume: f32, state: State<AppState>) -> C
         .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
-    session.set_track_volume(track_id, volume);
+    session.set_pre_roll_bars(bars);
     Ok(session_to_snapshot(session))
 }
 
-/// Set a track's pan (-1.0 to 1.0).
+/// Store the current position in a numbered locator (1-9).
 #[tauri::command]
-pub fn track_set_pan(track_id: u64, pan: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+pub fn locator_set_here(n: u8, state: State<AppState>) -> CommandResult<SessionSnapshot> {
     let mut session_lock = state
         .session
         .lock()
@@ -243,17 +490,33 @@ pub fn track_set_pan(track_id: u64, pan: f32, state: State<AppState>) -> Command
         .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
-    session.set_track_pan(track_id, pan);
+    session.set_locator_here(n);
     Ok(session_to_snapshot(session))
 }
 
-// ============================================================================
-// Metronome Commands
-// ============================================================================
+/// Jump to a numbered locator (1-9), if it is set.
+#[tauri::command]
+pub fn locator_goto(n: u8, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
 
-/// Toggle the metronome on/off.
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.goto_locator(n);
+    Ok(session_to_snapshot(session))
+}
+
+/// Loop playback between two ticks and turn looping on.
 #[tauri::command]
-pub fn metronome_toggle(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+pub fn transport_set_loop(
+    start_tick: u64,
+    end_tick: u64,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
     let mut session_lock = state
         .session
         .lock()
@@ -263,13 +526,13 @@ pub fn metronome_toggle(state: State<AppState>) -> CommandResult<SessionSnapshot
         .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
-    session.toggle_metronome();
+    session.set_loop(start_tick, end_tick);
     Ok(session_to_snapshot(session))
 }
 
-/// Set the metronome volume (0.0 to 1.0).
+/// Turn looping on or off, keeping the loop region.
 #[tauri::command]
-pub fn metronome_set_volume(volume: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+pub fn transport_toggle_loop(state: State<AppState>) -> CommandResult<SessionSnapshot> {
     let mut session_lock = state
         .session
         .lock()
@@ -279,7 +542,1288 @@ pub fn metronome_set_volume(volume: f32, state: State<AppState>) -> CommandResul
         .as_mut()
         .ok_or_else(|| "No session loaded".to_string())?;
 
-    session.set_metronome_volume(volume);
+    session.toggle_loop();
+    Ok(session_to_snapshot(session))
+}
+
+/// Remove the loop region.
+#[tauri::command]
+pub fn transport_clear_loop(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.clear_loop();
+    Ok(session_to_snapshot(session))
+}
+
+/// Keep recordings to the section between two ticks and turn punching in on.
+#[tauri::command]
+pub fn transport_set_punch(
+    start_tick: u64,
+    end_tick: u64,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_punch(start_tick, end_tick);
+    Ok(session_to_snapshot(session))
+}
+
+/// Turn punching in on or off, keeping the punch region.
+#[tauri::command]
+pub fn transport_toggle_punch(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.toggle_punch();
+    Ok(session_to_snapshot(session))
+}
+
+/// Remove the punch region, so whole takes are kept.
+#[tauri::command]
+pub fn transport_clear_punch(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.clear_punch();
+    Ok(session_to_snapshot(session))
+}
+
+/// Mute the master output and stop playback right away, for feedback or a runaway loop.
+#[tauri::command]
+pub fn transport_panic(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.panic();
+    Ok(session_to_snapshot(session))
+}
+
+/// Mute or unmute the master output.
+#[tauri::command]
+pub fn transport_set_master_muted(
+    muted: bool,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_master_muted(muted);
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// Pad Commands
+// ============================================================================
+
+/// Play the pad for a MIDI note; the lowest pad note plays the first track with a clip.
+#[tauri::command]
+pub fn pad_trigger_note(
+    note: u8,
+    velocity: u8,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.trigger_note(note, velocity);
     Ok(session_to_snapshot(session))
 }
 
+/// Arm or disarm recording of pad hits into clips.
+#[tauri::command]
+pub fn pad_set_recording(armed: bool, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_pad_recording(armed);
+    Ok(session_to_snapshot(session))
+}
+
+/// Start recording the input device onto the armed tracks, starting playback if
+/// needed.
+#[tauri::command]
+pub fn transport_record_start(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.start_recording().map_err(|e| e.to_string())?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Stop recording and playback, and put the take on the armed tracks.
+#[tauri::command]
+pub fn transport_record_stop(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.stop_recording().map_err(|e| e.to_string())?;
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// MIDI Input Commands
+// ============================================================================
+
+/// List the MIDI input devices connected now.
+#[tauri::command]
+pub fn midi_list_inputs() -> CommandResult<Vec<String>> {
+    daw_core::Session::midi_input_devices().map_err(|e| e.to_string())
+}
+
+/// Play a MIDI input device on the armed tracks, or stop listening with no device.
+#[tauri::command]
+pub fn midi_set_input(
+    device: Option<String>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session
+        .set_midi_input_device(device.as_deref())
+        .map_err(|e| e.to_string())?;
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// Track Commands
+// ============================================================================
+
+/// Toggle a track's enabled state (mute/unmute).
+#[tauri::command]
+pub fn track_toggle_enabled(track_id: u64, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.toggle_track_enabled(track_id);
+    Ok(session_to_snapshot(session))
+}
+
+/// Press a track's solo button, following the solo mode.
+///
+/// Pass `alternate` when a modifier key is held to use the other mode for this press.
+#[tauri::command]
+pub fn track_solo_exclusive(
+    track_id: u64,
+    alternate: Option<bool>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.solo_track(track_id, alternate.unwrap_or(false));
+    Ok(session_to_snapshot(session))
+}
+
+/// Set what pressing a solo button does to the other tracks.
+#[tauri::command]
+pub fn track_set_solo_mode(
+    solo_mode: SoloModeDto,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_solo_mode(solo_mode.into());
+    Ok(session_to_snapshot(session))
+}
+
+/// Set a track's volume.
+#[tauri::command]
+pub fn track_set_volume(track_id: u64, volume: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_track_volume(track_id, volume);
+    Ok(session_to_snapshot(session))
+}
+
+/// Set a track's pan (-1.0 to 1.0).
+#[tauri::command]
+pub fn track_set_pan(track_id: u64, pan: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_track_pan(track_id, pan);
+    Ok(session_to_snapshot(session))
+}
+
+/// Set a track's display color as RGB, or clear it with `null`.
+#[tauri::command]
+pub fn track_set_color(
+    track_id: u64,
+    color: Option<[u8; 3]>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_track_color(track_id, color);
+    Ok(session_to_snapshot(session))
+}
+
+/// Set a track metadata entry, or remove it with a `null` value.
+#[tauri::command]
+pub fn track_set_metadata(
+    track_id: u64,
+    key: String,
+    value: Option<String>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_track_metadata(track_id, key, value);
+    Ok(session_to_snapshot(session))
+}
+
+/// Append an empty track.
+#[tauri::command]
+pub fn track_add(name: String, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.add_track(name);
+    Ok(session_to_snapshot(session))
+}
+
+/// Remove a track and its clips.
+#[tauri::command]
+pub fn track_remove(track_id: u64, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.remove_track(track_id);
+    Ok(session_to_snapshot(session))
+}
+
+/// Arm or disarm a track for recording.
+#[tauri::command]
+pub fn track_set_armed(
+    track_id: u64,
+    armed: bool,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_track_armed(track_id, armed);
+    Ok(session_to_snapshot(session))
+}
+
+/// Add a point to a track's automation lane for `parameter` ("volume" or
+/// "pan"), starting the lane if there isn't one.
+#[tauri::command]
+pub fn automation_add_point(
+    track_id: u64,
+    parameter: String,
+    tick: u64,
+    value: f32,
+    curve: Option<AutomationCurveDto>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let curve = curve.map(Into::into).unwrap_or_default();
+    session
+        .add_automation_point(track_id, &parameter, tick, value, curve)
+        .ok_or_else(|| format!("Can't automate {} on track {}", parameter, track_id))?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Move a point in a track's automation lane to a new tick and value.
+#[tauri::command]
+pub fn automation_move_point(
+    track_id: u64,
+    parameter: String,
+    index: usize,
+    tick: u64,
+    value: f32,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session
+        .move_automation_point(track_id, &parameter, index, tick, value)
+        .ok_or_else(|| format!("No {} point {} on track {}", parameter, index, track_id))?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Change how a point in a track's automation lane moves on to the next one.
+#[tauri::command]
+pub fn automation_set_curve(
+    track_id: u64,
+    parameter: String,
+    index: usize,
+    curve: AutomationCurveDto,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_automation_curve(track_id, &parameter, index, curve.into());
+    Ok(session_to_snapshot(session))
+}
+
+/// Delete a point from a track's automation lane.
+#[tauri::command]
+pub fn automation_delete_point(
+    track_id: u64,
+    parameter: String,
+    index: usize,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.delete_automation_point(track_id, &parameter, index) {
+        return Err(format!(
+            "No {} point {} on track {}",
+            parameter, index, track_id
+        ));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Add a built-in effect ("eq", "compressor", "delay" or "reverb") with its
+/// default settings to the end of a track's inserts.
+#[tauri::command]
+pub fn track_add_effect(
+    track_id: u64,
+    kind: String,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let effect = Effect::from_kind(&kind).ok_or_else(|| format!("No effect {}", kind))?;
+    session
+        .add_track_effect(track_id, effect)
+        .ok_or_else(|| format!("Can't add effects to track {}", track_id))?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Remove an insert effect from a track.
+#[tauri::command]
+pub fn track_remove_effect(
+    track_id: u64,
+    index: usize,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.remove_track_effect(track_id, index) {
+        return Err(format!("No effect {} on track {}", index, track_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Move an insert effect to another place in a track's order.
+#[tauri::command]
+pub fn track_move_effect(
+    track_id: u64,
+    from: usize,
+    to: usize,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.move_track_effect(track_id, from, to) {
+        return Err(format!(
+            "Can't move effect {} to {} on track {}",
+            from, to, track_id
+        ));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Set one of an insert effect's parameters, kept within its range.
+#[tauri::command]
+pub fn track_set_effect_parameter(
+    track_id: u64,
+    index: usize,
+    name: String,
+    value: f32,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.set_track_effect_parameter(track_id, index, &name, value) {
+        return Err(format!(
+            "No {} on effect {} of track {}",
+            name, index, track_id
+        ));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// Sampler Commands
+// ============================================================================
+
+/// Give a track a sampler loaded from sample files, replacing any it had. The
+/// envelope and polyphony are left at their defaults unless given.
+#[tauri::command]
+pub fn track_set_sampler(
+    track_id: u64,
+    sampler: SamplerLoadDto,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let mut load = |path: &str| {
+        session
+            .load_sampler_sample(Path::new(path))
+            .map_err(|e| format!("Failed to load {}: {}", path, e))
+    };
+    let mut loaded = match sampler.samples {
+        SamplerSamplesDto::Pitched { path, root_note } => Sampler::pitched(load(&path)?, root_note),
+        SamplerSamplesDto::PerNote { samples } => Sampler::per_note(
+            samples
+                .iter()
+                .map(|(&note, path)| Ok((note, load(path)?)))
+                .collect::<CommandResult<_>>()?,
+        ),
+    };
+    if let Some(envelope) = sampler.envelope {
+        loaded.envelope = envelope.into();
+    }
+    if let Some(polyphony) = sampler.polyphony {
+        loaded.polyphony = polyphony;
+    }
+    if !session.set_track_sampler(track_id, Some(loaded)) {
+        return Err(format!("Track {} can't have a sampler", track_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Take a track's sampler away.
+#[tauri::command]
+pub fn track_remove_sampler(
+    track_id: u64,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_track_sampler(track_id, None);
+    Ok(session_to_snapshot(session))
+}
+
+/// Change the attack, decay, sustain and release of a track's sampler.
+#[tauri::command]
+pub fn track_set_sampler_envelope(
+    track_id: u64,
+    envelope: AdsrDto,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.set_track_sampler_envelope(track_id, envelope.into()) {
+        return Err(format!("No sampler on track {}", track_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Append an empty aux bus for tracks to send to.
+#[tauri::command]
+pub fn track_add_bus(name: String, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.add_bus_track(name);
+    Ok(session_to_snapshot(session))
+}
+
+/// Send a track to a bus, replacing any send it had to that bus.
+#[tauri::command]
+pub fn track_set_send(
+    track_id: u64,
+    bus_id: u64,
+    level: f32,
+    pre_fader: bool,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.set_track_send(track_id, bus_id, level, pre_fader) {
+        return Err(format!("Can't send track {} to bus {}", track_id, bus_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Stop sending a track to a bus.
+#[tauri::command]
+pub fn track_remove_send(
+    track_id: u64,
+    bus_id: u64,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.remove_track_send(track_id, bus_id) {
+        return Err(format!("No send from track {} to bus {}", track_id, bus_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Rename a track or folder.
+#[tauri::command]
+pub fn track_rename(
+    track_id: u64,
+    name: String,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.rename_track(track_id, name);
+    Ok(session_to_snapshot(session))
+}
+
+/// Move a track, with anything inside it, to a new position in the track list.
+#[tauri::command]
+pub fn track_move(
+    track_id: u64,
+    new_index: usize,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.move_track(track_id, new_index);
+    Ok(session_to_snapshot(session))
+}
+
+/// Group tracks into a new folder track.
+#[tauri::command]
+pub fn track_group(
+    track_ids: Vec<u64>,
+    name: String,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session
+        .group_tracks(&track_ids, name)
+        .ok_or_else(|| "No tracks to group".to_string())?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Remove a folder track, moving its tracks up one level.
+#[tauri::command]
+pub fn track_ungroup(folder_id: u64, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.ungroup_tracks(folder_id);
+    Ok(session_to_snapshot(session))
+}
+
+/// Move a track into a folder track, or to the top level with `null`.
+#[tauri::command]
+pub fn track_set_parent(
+    track_id: u64,
+    parent: Option<u64>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_track_parent(track_id, parent);
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// Clip Commands
+// ============================================================================
+
+/// Move a clip to a tick on a track, which can be the one it's on.
+#[tauri::command]
+pub fn clip_move(
+    clip_id: u64,
+    track_id: u64,
+    start_tick: u64,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.move_clip(ClipId(clip_id), track_id, start_tick) {
+        return Err(format!("Can't move clip {} to track {}", clip_id, track_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Remove a clip from its track.
+#[tauri::command]
+pub fn clip_delete(clip_id: u64, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.delete_clip(ClipId(clip_id)) {
+        return Err(format!("No clip {}", clip_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Put a copy of a clip right after it.
+#[tauri::command]
+pub fn clip_duplicate(clip_id: u64, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session
+        .duplicate_clip(ClipId(clip_id))
+        .ok_or_else(|| format!("No clip {}", clip_id))?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Cut a clip in two at a tick inside it.
+#[tauri::command]
+pub fn clip_split(
+    clip_id: u64,
+    tick: u64,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session
+        .split_clip_at(ClipId(clip_id), tick)
+        .ok_or_else(|| format!("Can't split clip {} at tick {}", clip_id, tick))?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Move a clip's edges, keeping its audio in place on the timeline.
+#[tauri::command]
+pub fn clip_trim(
+    clip_id: u64,
+    start_tick: u64,
+    end_tick: u64,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.trim_clip(ClipId(clip_id), start_tick, end_tick) {
+        return Err(format!("Can't trim clip {}", clip_id));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Undo the last clip edit, if there is one.
+#[tauri::command]
+pub fn edit_undo(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.undo();
+    Ok(session_to_snapshot(session))
+}
+
+/// Redo the last clip edit undone, if there is one.
+#[tauri::command]
+pub fn edit_redo(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.redo();
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// Sample Commands
+// ============================================================================
+
+/// Swap the audio of every clip using a sample for another file.
+///
+/// `old` names the sample by reference (`project:audio/kick.wav`), by the path in
+/// its reference, or by the file it resolves to. Runs off the main thread since the
+/// new file has to be decoded. Returns an error if no clip uses the sample.
+#[tauri::command(async)]
+pub fn sample_replace(
+    old: String,
+    new_path: String,
+    state: State<'_, AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let replaced = session
+        .replace_sample(&old, Path::new(&new_path))
+        .map_err(|e| e.to_string())?;
+    if replaced == 0 {
+        return Err(format!("No clips use {}", old));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Import an audio file onto a track at `tick`, as when it's dropped there.
+///
+/// Runs off the main thread since the file has to be decoded. Returns an error if
+/// the track can't hold clips or the file can't be decoded.
+#[tauri::command(async)]
+pub fn sample_import(
+    path: String,
+    track_id: u64,
+    tick: u64,
+    state: State<'_, AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session
+        .import_audio_file(Path::new(&path), TrackId(track_id), tick)
+        .map_err(|e| e.to_string())?;
+    Ok(session_to_snapshot(session))
+}
+
+/// Audition a sample file without adding it to the arrangement.
+///
+/// Runs off the main thread since the file may have to be decoded.
+#[tauri::command(async)]
+pub fn sample_preview(path: String, state: State<'_, AppState>) -> CommandResult<()> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session
+        .preview_sample(Path::new(&path))
+        .map_err(|e| e.to_string())
+}
+
+/// Stop the sample being previewed.
+#[tauri::command]
+pub fn sample_stop_preview(state: State<AppState>) -> CommandResult<()> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.stop_preview();
+    Ok(())
+}
+
+/// Set the volume of sample previews (0.0 to 1.0).
+#[tauri::command]
+pub fn sample_set_preview_volume(
+    volume: f32,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_preview_volume(volume);
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// Metronome Commands
+// ============================================================================
+
+/// Toggle the metronome on/off.
+#[tauri::command]
+pub fn metronome_toggle(state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.toggle_metronome();
+    Ok(session_to_snapshot(session))
+}
+
+/// Set the metronome volume (0.0 to 1.0).
+#[tauri::command]
+pub fn metronome_set_volume(volume: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    session.set_metronome_volume(volume);
+    Ok(session_to_snapshot(session))
+}
+
+// ============================================================================
+// Master Commands
+// ============================================================================
+
+/// Set the master gain in dB.
+#[tauri::command]
+pub fn master_set_gain(gain_db: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let settings = MasterSettings {
+        gain_db,
+        ..session.master_bus()
+    };
+    session.set_master_bus(settings);
+    Ok(session_to_snapshot(session))
+}
+
+/// Set the master balance (-1.0 left to 1.0 right).
+#[tauri::command]
+pub fn master_set_pan(pan: f32, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let settings = MasterSettings {
+        pan,
+        ..session.master_bus()
+    };
+    session.set_master_bus(settings);
+    Ok(session_to_snapshot(session))
+}
+
+/// Turn the master limiter on with the given settings, or off with `None`.
+#[tauri::command]
+pub fn master_set_limiter(
+    limiter: Option<LimiterDto>,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let settings = MasterSettings {
+        limiter: limiter.map(Into::into),
+        ..session.master_bus()
+    };
+    session.set_master_bus(settings);
+    Ok(session_to_snapshot(session))
+}
+
+/// Add a built-in effect, by kind, to the end of the master inserts.
+#[tauri::command]
+pub fn master_add_effect(kind: String, state: State<AppState>) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    let effect = Effect::from_kind(&kind).ok_or_else(|| format!("No effect {}", kind))?;
+    session.add_master_effect(effect);
+    Ok(session_to_snapshot(session))
+}
+
+/// Remove an insert effect from the master.
+#[tauri::command]
+pub fn master_remove_effect(
+    index: usize,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.remove_master_effect(index) {
+        return Err(format!("No effect {} on the master", index));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Move a master insert effect to another place in the order.
+#[tauri::command]
+pub fn master_move_effect(
+    from: usize,
+    to: usize,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.move_master_effect(from, to) {
+        return Err(format!(
+            "Can't move effect {} to {} on the master",
+            from, to
+        ));
+    }
+    Ok(session_to_snapshot(session))
+}
+
+/// Set one of a master insert effect's parameters, kept within its range.
+#[tauri::command]
+pub fn master_set_effect_parameter(
+    index: usize,
+    name: String,
+    value: f32,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+
+    if !session.set_master_effect_parameter(index, &name, value) {
+        return Err(format!("No {} on effect {} of the master", name, index));
+    }
+    Ok(session_to_snapshot(session))
+}
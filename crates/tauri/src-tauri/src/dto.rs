@@ -4,6 +4,7 @@
 //! They represent snapshots of the Session state at a point in time.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Complete snapshot of the session state.
 ///
@@ -13,13 +14,99 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "camelCase")]
 pub struct SessionSnapshot {
     pub name: String,
+    pub info: ProjectInfoDto,
     pub tempo: f64,
     pub time_signature: TimeSignatureDto,
     pub max_tick: u64,
     pub current_tick: u64,
     pub playback_state: PlaybackStateDto,
+    pub end_behavior: EndBehaviorDto,
+    pub return_to_start: bool,
+    /// Bars of metronome played before playback starts
+    pub count_in_bars: u32,
+    /// Bars before the cursor that playback starts from
+    pub pre_roll_bars: u32,
+    pub solo_mode: SoloModeDto,
+    pub master_muted: bool,
+    /// Gain, balance, limiter and effects on the summed tracks
+    pub master: MasterDto,
+    /// Whether pad hits during playback are recorded as clips
+    pub pad_recording: bool,
+    /// Whether a take is being recorded from the input device
+    pub recording: bool,
+    /// MIDI input device played on the armed tracks
+    pub midi_input: Option<String>,
+    /// Linear gain of sample previews
+    pub preview_volume: f32,
+    /// Locator number to tick, for the locators that are set
+    pub locators: BTreeMap<u8, u64>,
+    /// Section playback cycles through while looping is on
+    pub loop_region: Option<LoopRegionDto>,
+    /// Section recordings are kept to while punching in
+    pub punch_region: Option<PunchRegionDto>,
     pub tracks: Vec<TrackSummary>,
     pub metronome: MetronomeState,
+    /// Whether there's a clip edit to undo
+    pub can_undo: bool,
+    /// Whether there's an undone clip edit to redo
+    pub can_redo: bool,
+}
+
+/// The master channel's settings and insert effects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MasterDto {
+    pub gain_db: f32,
+    /// Balance from -1.0 (left) to 1.0 (right)
+    pub pan: f32,
+    pub limiter: Option<LimiterDto>,
+    /// Insert effects, in the order the mix goes through them
+    pub effects: Vec<EffectDto>,
+}
+
+/// Peak limiter settings on the master.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LimiterDto {
+    /// Highest sample level let through, in dBFS
+    pub ceiling_db: f32,
+    pub release_ms: f32,
+}
+
+impl From<LimiterDto> for daw_core::LimiterSettings {
+    fn from(limiter: LimiterDto) -> Self {
+        Self {
+            ceiling_db: limiter.ceiling_db,
+            release_ms: limiter.release_ms,
+        }
+    }
+}
+
+impl From<daw_core::LimiterSettings> for LimiterDto {
+    fn from(limiter: daw_core::LimiterSettings) -> Self {
+        Self {
+            ceiling_db: limiter.ceiling_db,
+            release_ms: limiter.release_ms,
+        }
+    }
+}
+
+/// Loop region in ticks, with whether playback is cycling through it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoopRegionDto {
+    pub start_tick: u64,
+    pub end_tick: u64,
+    pub enabled: bool,
+}
+
+/// Punch region in ticks, with whether recordings are kept to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PunchRegionDto {
+    pub start_tick: u64,
+    pub end_tick: u64,
+    pub enabled: bool,
 }
 
 /// Time signature representation for the frontend.
@@ -39,6 +126,46 @@ pub enum PlaybackStateDto {
     Paused,
 }
 
+/// What playback does after the last clip.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EndBehaviorDto {
+    Continue,
+    Stop,
+    Loop,
+}
+
+/// What pressing a track's solo button does to the other tracks.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SoloModeDto {
+    Exclusive,
+    Additive,
+}
+
+/// Encoding project files are saved in.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProjectFormatDto {
+    Json,
+    MessagePack,
+}
+
+/// How project files are saved, as chosen in the app's settings.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveOptionsDto {
+    pub format: ProjectFormatDto,
+    /// Compress the file with zstd
+    pub compress: bool,
+    /// How many earlier versions of the file to keep as `.bak.N` backups
+    #[serde(default)]
+    pub backups: usize,
+    /// Save a zip archive holding the project, its peaks and embedded samples
+    #[serde(default)]
+    pub zip: bool,
+}
+
 /// Summary of a track with its clips.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -49,17 +176,303 @@ pub struct TrackSummary {
     pub solo: bool,
     pub volume: f32,
     pub pan: f32,
+    pub color: Option<[u8; 3]>,
+    pub metadata: BTreeMap<String, String>,
+    /// Id of the folder track this track is grouped under
+    pub parent: Option<u64>,
+    pub is_folder: bool,
+    /// Whether the track is an aux bus, mixing what other tracks send it
+    pub is_bus: bool,
+    /// Buses the track feeds, on top of what it plays itself
+    pub sends: Vec<TrackSendDto>,
+    /// Whether the track gets a clip of the next recorded take
+    pub armed: bool,
+    /// Automation lanes, at most one per parameter
+    pub automation: Vec<AutomationLaneDto>,
+    /// Insert effects, in the order the track's audio goes through them
+    pub effects: Vec<EffectDto>,
+    /// Instrument playing the track's MIDI clips and live MIDI input
+    pub sampler: Option<SamplerDto>,
     pub clips: Vec<ClipSummary>,
 }
 
+/// Audio a track feeds to a bus.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackSendDto {
+    /// Id of the bus track
+    pub bus: u64,
+    /// Linear gain of the send (0.0 to 1.0)
+    pub level: f32,
+    /// Whether the send is taken before the track's volume
+    pub pre_fader: bool,
+}
+
+/// A built-in insert effect and its settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectDto {
+    /// "eq", "compressor", "delay" or "reverb"
+    pub kind: String,
+    pub parameters: Vec<EffectParameterDto>,
+}
+
+impl From<&daw_core::Effect> for EffectDto {
+    fn from(effect: &daw_core::Effect) -> Self {
+        Self {
+            kind: effect.kind().to_string(),
+            parameters: effect
+                .parameters()
+                .into_iter()
+                .map(|parameter| EffectParameterDto {
+                    name: parameter.name.to_string(),
+                    value: parameter.value,
+                    min: parameter.min,
+                    max: parameter.max,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A track's sampler, without its samples.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplerDto {
+    /// Note the one sample plays at its own pitch, or `None` when notes each
+    /// have a sample of their own
+    pub root_note: Option<u8>,
+    /// Notes with a sample of their own
+    pub notes: Vec<u8>,
+    pub envelope: AdsrDto,
+    /// Notes that sound at once
+    pub polyphony: usize,
+}
+
+impl From<&daw_core::Sampler> for SamplerDto {
+    fn from(sampler: &daw_core::Sampler) -> Self {
+        let (root_note, notes) = match &sampler.map {
+            daw_core::SamplerMap::Pitched { root_note, .. } => (Some(*root_note), Vec::new()),
+            daw_core::SamplerMap::PerNote(samples) => (None, samples.keys().copied().collect()),
+        };
+        Self {
+            root_note,
+            notes,
+            envelope: sampler.envelope.into(),
+            polyphony: sampler.polyphony,
+        }
+    }
+}
+
+/// Attack, decay and release in seconds, and the sustain level from 0 to 1.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdsrDto {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl From<daw_core::Adsr> for AdsrDto {
+    fn from(adsr: daw_core::Adsr) -> Self {
+        Self {
+            attack: adsr.attack,
+            decay: adsr.decay,
+            sustain: adsr.sustain,
+            release: adsr.release,
+        }
+    }
+}
+
+impl From<AdsrDto> for daw_core::Adsr {
+    fn from(adsr: AdsrDto) -> Self {
+        Self {
+            attack: adsr.attack,
+            decay: adsr.decay,
+            sustain: adsr.sustain,
+            release: adsr.release,
+        }
+    }
+}
+
+/// Sample files to load a sampler from, and its settings if not the defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplerLoadDto {
+    pub samples: SamplerSamplesDto,
+    pub envelope: Option<AdsrDto>,
+    pub polyphony: Option<usize>,
+}
+
+/// One sample repitched across the keyboard, or a sample for each note.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum SamplerSamplesDto {
+    Pitched { path: String, root_note: u8 },
+    PerNote { samples: BTreeMap<u8, String> },
+}
+
+/// One of an effect's settings, with the range it takes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EffectParameterDto {
+    pub name: String,
+    pub value: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+/// A VST3 plugin, as found by a scan or loaded on a track.
+#[cfg(feature = "vst3")]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginDto {
+    pub name: String,
+    pub vendor: String,
+    pub version: String,
+    /// Subcategories separated by `|`, like "Fx|Reverb"
+    pub category: String,
+    /// The `.vst3` bundle it's in
+    pub path: String,
+    /// Class id within the bundle, as 32 hex digits
+    pub id: String,
+    pub instrument: bool,
+}
+
+#[cfg(feature = "vst3")]
+impl From<&daw_core::Vst3Info> for PluginDto {
+    fn from(info: &daw_core::Vst3Info) -> Self {
+        Self {
+            name: info.name.clone(),
+            vendor: info.vendor.clone(),
+            version: info.version.clone(),
+            category: info.category.clone(),
+            path: info.path.to_string_lossy().into_owned(),
+            id: info.id.iter().map(|byte| format!("{:02x}", byte)).collect(),
+            instrument: info.is_instrument(),
+        }
+    }
+}
+
+#[cfg(feature = "vst3")]
+impl TryFrom<PluginDto> for daw_core::Vst3Info {
+    type Error = String;
+
+    fn try_from(plugin: PluginDto) -> Result<Self, String> {
+        let bad_id = || format!("Bad plugin id {}", plugin.id);
+        if plugin.id.len() != 32 {
+            return Err(bad_id());
+        }
+        let mut id = [0; 16];
+        for (i, byte) in id.iter_mut().enumerate() {
+            *byte = plugin
+                .id
+                .get(i * 2..i * 2 + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(bad_id)?;
+        }
+        Ok(Self {
+            name: plugin.name,
+            vendor: plugin.vendor,
+            version: plugin.version,
+            category: plugin.category,
+            path: plugin.path.into(),
+            id,
+        })
+    }
+}
+
+/// Points drawn for one parameter of a track, such as "volume" or "pan".
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutomationLaneDto {
+    pub parameter: String,
+    pub points: Vec<AutomationPointDto>,
+}
+
+/// A value at a tick in an automation lane.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutomationPointDto {
+    pub tick: u64,
+    pub value: f32,
+    /// Shape of the line from here to the next point
+    pub curve: AutomationCurveDto,
+}
+
+/// How an automation point moves on to the next one.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AutomationCurveDto {
+    Linear,
+    Step,
+    Exponential,
+}
+
+impl From<AutomationCurveDto> for daw_core::AutomationCurve {
+    fn from(curve: AutomationCurveDto) -> Self {
+        match curve {
+            AutomationCurveDto::Linear => daw_core::AutomationCurve::Linear,
+            AutomationCurveDto::Step => daw_core::AutomationCurve::Step,
+            AutomationCurveDto::Exponential => daw_core::AutomationCurve::Exponential,
+        }
+    }
+}
+
+impl From<daw_core::AutomationCurve> for AutomationCurveDto {
+    fn from(curve: daw_core::AutomationCurve) -> Self {
+        match curve {
+            daw_core::AutomationCurve::Linear => AutomationCurveDto::Linear,
+            daw_core::AutomationCurve::Step => AutomationCurveDto::Step,
+            daw_core::AutomationCurve::Exponential => AutomationCurveDto::Exponential,
+        }
+    }
+}
+
+/// Author, notes, timestamps and key of the project.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectInfoDto {
+    pub author: String,
+    pub notes: String,
+    /// When the project was first saved, in seconds since the Unix epoch
+    pub created: Option<u64>,
+    /// When the project was last saved, in seconds since the Unix epoch
+    pub modified: Option<u64>,
+    /// Key the song is in, e.g. "F#m"
+    pub key: Option<String>,
+}
+
+impl From<&daw_core::ProjectInfo> for ProjectInfoDto {
+    fn from(info: &daw_core::ProjectInfo) -> Self {
+        Self {
+            author: info.author.clone(),
+            notes: info.notes.clone(),
+            created: info.created,
+            modified: info.modified,
+            key: info.key.map(|key| key.to_string()),
+        }
+    }
+}
+
 /// Summary of a clip with its timeline position.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClipSummary {
+    pub id: u64,
     pub start_tick: u64,
     pub end_tick: u64,
     pub audio_offset: u64,
     pub name: String,
+    /// Tempo of the clip's source material in BPM
+    pub source_bpm: Option<f64>,
+    /// Key of the clip's source material, e.g. "F#m"
+    pub source_key: Option<String>,
 }
 
 /// Metronome state.
@@ -78,6 +491,258 @@ pub struct MetronomeState {
 pub struct SessionTickEvent {
     pub tick: u64,
     pub playback_state: PlaybackStateDto,
+    /// Peak level on the left and right of the output since the last tick, as
+    /// linear gain
+    pub master_peak: [f32; 2],
+}
+
+/// Event payload for project loading progress.
+///
+/// Emitted while `session_load_project` decodes the project's audio.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadProgressEvent {
+    /// Fraction of the audio loaded, from 0.0 to 1.0
+    pub fraction: f32,
+}
+
+/// Event payload for a finished render.
+///
+/// Emitted as "render-complete" by `session_render`, which also returns it.
+/// Levels are `None` when the render is silent (or, for loudness, too short
+/// to measure).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderCompleteEvent {
+    /// File the render was written to
+    pub path: String,
+    /// Length of the render in seconds
+    pub duration_secs: f64,
+    /// Highest sample level in dBFS
+    pub peak_db: Option<f32>,
+    /// Integrated loudness in LUFS
+    pub loudness_lufs: Option<f32>,
+    /// Highest level between samples in dBTP
+    pub true_peak_db: Option<f32>,
+    /// Gain normalization applied in dB
+    pub gain_db: f32,
+    /// Average level in dBFS
+    pub rms_db: Option<f32>,
+    /// Samples over full scale, for warning about clipping
+    pub clipped_samples: usize,
+}
+
+impl From<&daw_core::RenderSummary> for RenderCompleteEvent {
+    fn from(summary: &daw_core::RenderSummary) -> Self {
+        Self {
+            path: summary.path.to_string_lossy().into_owned(),
+            duration_secs: summary.duration.as_secs_f64(),
+            peak_db: Some(summary.peak_db).filter(|db| db.is_finite()),
+            loudness_lufs: Some(summary.loudness_lufs).filter(|lufs| lufs.is_finite()),
+            true_peak_db: Some(summary.true_peak_db).filter(|db| db.is_finite()),
+            gain_db: summary.gain_db,
+            rms_db: Some(summary.rms_db).filter(|db| db.is_finite()),
+            clipped_samples: summary.clipped_samples,
+        }
+    }
+}
+
+/// A project in the recent list, most recent first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentProjectDto {
+    pub path: String,
+    pub name: String,
+}
+
+impl From<&daw_core::RecentProject> for RecentProjectDto {
+    fn from(project: &daw_core::RecentProject) -> Self {
+        Self {
+            path: project.path.to_string_lossy().into_owned(),
+            name: project.name.clone(),
+        }
+    }
+}
+
+/// Demo watermark to lay over a render, as chosen in the render dialog.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatermarkDto {
+    /// Seconds from the start of one mark to the start of the next
+    pub interval_secs: f64,
+    /// Peak level of each mark in dBFS
+    pub level_db: f32,
+    /// Audio file with a voice tag to use instead of the default beep
+    pub tag_path: Option<String>,
+}
+
+/// Level to normalize a render to, as chosen in the render dialog.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizationDto {
+    /// Integrated loudness to reach in LUFS
+    pub loudness_lufs: Option<f32>,
+    /// Highest true peak allowed in dBTP
+    pub true_peak_db: Option<f32>,
+}
+
+impl From<NormalizationDto> for daw_core::Normalization {
+    fn from(normalization: NormalizationDto) -> Self {
+        daw_core::Normalization {
+            loudness_lufs: normalization.loudness_lufs,
+            true_peak_db: normalization.true_peak_db,
+        }
+    }
+}
+
+/// Time rendered after the last clip, as chosen in the render dialog.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "unit", content = "length", rename_all = "camelCase")]
+pub enum RenderTailDto {
+    Seconds(f64),
+    Bars(u32),
+}
+
+impl From<RenderTailDto> for daw_core::RenderTail {
+    fn from(tail: RenderTailDto) -> Self {
+        match tail {
+            RenderTailDto::Seconds(seconds) => daw_core::RenderTail::Seconds(seconds),
+            RenderTailDto::Bars(bars) => daw_core::RenderTail::Bars(bars),
+        }
+    }
+}
+
+/// How an integer render is rounded, as chosen in the render dialog.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DitherDto {
+    None,
+    Triangular,
+    NoiseShaped,
+}
+
+impl From<DitherDto> for daw_core::Dither {
+    fn from(dither: DitherDto) -> Self {
+        match dither {
+            DitherDto::None => daw_core::Dither::None,
+            DitherDto::Triangular => daw_core::Dither::Triangular,
+            DitherDto::NoiseShaped => daw_core::Dither::NoiseShaped,
+        }
+    }
+}
+
+/// Sample format of a render, as chosen in the render dialog.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderFormatDto {
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl From<RenderFormatDto> for daw_core::RenderFormat {
+    fn from(format: RenderFormatDto) -> Self {
+        match format {
+            RenderFormatDto::Int16 => daw_core::RenderFormat::Int16,
+            RenderFormatDto::Int24 => daw_core::RenderFormat::Int24,
+            RenderFormatDto::Float32 => daw_core::RenderFormat::Float32,
+        }
+    }
+}
+
+impl WatermarkDto {
+    /// The watermark to render with, decoding the voice tag if there is one
+    pub fn to_watermark(&self) -> Result<daw_core::Watermark, String> {
+        let mut watermark = daw_core::Watermark {
+            interval: std::time::Duration::from_secs_f64(self.interval_secs.max(0.0)),
+            level_db: self.level_db,
+            ..daw_core::Watermark::default()
+        };
+        if let Some(tag_path) = &self.tag_path {
+            let tag = daw_core::decode_audio_arc(std::path::Path::new(tag_path), None)
+                .map_err(|e| e.to_string())?;
+            watermark.sound = daw_core::WatermarkSound::Tag(tag);
+        }
+        Ok(watermark)
+    }
+}
+
+/// Event payload for problems with the audio stream.
+///
+/// Emitted by the background poll loop when the session's watchdog acts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum AudioStatusEvent {
+    Stalled,
+    Restarted,
+    RestartFailed { error: String },
+}
+
+/// Event payload for samples reloaded after changing on disk.
+///
+/// Emitted by the background poll loop, with the session as it is after the
+/// reload so waveforms can be redrawn. `error` is set instead when a changed
+/// sample couldn't be read.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SamplesReloadedEvent {
+    pub names: Vec<String>,
+    pub error: Option<String>,
+    pub snapshot: SessionSnapshot,
+}
+
+/// Event payload for a periodic autosave that couldn't be written
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutosaveFailedEvent {
+    pub error: String,
+}
+
+/// Event payload for a MIDI input device that couldn't be reopened after the
+/// audio stream restarted; MIDI input is off until another device is chosen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiInputLostEvent {
+    pub error: String,
+}
+
+/// What the poll loop sends the frontend for a session event
+pub enum SessionEventDto {
+    AudioStatus(AudioStatusEvent),
+    SamplesReloaded(SamplesReloadedEvent),
+    AutosaveFailed(AutosaveFailedEvent),
+    MidiInputLost(MidiInputLostEvent),
+}
+
+impl SessionEventDto {
+    pub fn from_event(event: daw_core::SessionEvent, session: &daw_core::Session) -> Self {
+        let reloaded = |names, error| {
+            SessionEventDto::SamplesReloaded(SamplesReloadedEvent {
+                names,
+                error,
+                snapshot: session_to_snapshot(session),
+            })
+        };
+        match event {
+            daw_core::SessionEvent::AudioStalled => {
+                SessionEventDto::AudioStatus(AudioStatusEvent::Stalled)
+            }
+            daw_core::SessionEvent::AudioRestarted => {
+                SessionEventDto::AudioStatus(AudioStatusEvent::Restarted)
+            }
+            daw_core::SessionEvent::AudioRestartFailed(error) => {
+                SessionEventDto::AudioStatus(AudioStatusEvent::RestartFailed { error })
+            }
+            daw_core::SessionEvent::SamplesReloaded(names) => reloaded(names, None),
+            daw_core::SessionEvent::SampleReloadFailed(error) => reloaded(Vec::new(), Some(error)),
+            daw_core::SessionEvent::AutosaveFailed(error) => {
+                SessionEventDto::AutosaveFailed(AutosaveFailedEvent { error })
+            }
+            daw_core::SessionEvent::MidiInputLost(error) => {
+                SessionEventDto::MidiInputLost(MidiInputLostEvent { error })
+            }
+        }
+    }
 }
 
 impl From<daw_core::PlaybackState> for PlaybackStateDto {
@@ -90,6 +755,58 @@ impl From<daw_core::PlaybackState> for PlaybackStateDto {
     }
 }
 
+impl From<daw_core::EndBehavior> for EndBehaviorDto {
+    fn from(end_behavior: daw_core::EndBehavior) -> Self {
+        match end_behavior {
+            daw_core::EndBehavior::Continue => EndBehaviorDto::Continue,
+            daw_core::EndBehavior::Stop => EndBehaviorDto::Stop,
+            daw_core::EndBehavior::Loop => EndBehaviorDto::Loop,
+        }
+    }
+}
+
+impl From<EndBehaviorDto> for daw_core::EndBehavior {
+    fn from(end_behavior: EndBehaviorDto) -> Self {
+        match end_behavior {
+            EndBehaviorDto::Continue => daw_core::EndBehavior::Continue,
+            EndBehaviorDto::Stop => daw_core::EndBehavior::Stop,
+            EndBehaviorDto::Loop => daw_core::EndBehavior::Loop,
+        }
+    }
+}
+
+impl From<SaveOptionsDto> for daw_core::SaveOptions {
+    fn from(options: SaveOptionsDto) -> Self {
+        daw_core::SaveOptions {
+            format: match options.format {
+                ProjectFormatDto::Json => daw_core::ProjectFormat::Json,
+                ProjectFormatDto::MessagePack => daw_core::ProjectFormat::MessagePack,
+            },
+            compress: options.compress,
+            backups: options.backups,
+            zip: options.zip,
+        }
+    }
+}
+
+impl From<daw_core::SoloMode> for SoloModeDto {
+    fn from(solo_mode: daw_core::SoloMode) -> Self {
+        match solo_mode {
+            daw_core::SoloMode::Exclusive => SoloModeDto::Exclusive,
+            daw_core::SoloMode::Additive => SoloModeDto::Additive,
+        }
+    }
+}
+
+impl From<SoloModeDto> for daw_core::SoloMode {
+    fn from(solo_mode: SoloModeDto) -> Self {
+        match solo_mode {
+            SoloModeDto::Exclusive => daw_core::SoloMode::Exclusive,
+            SoloModeDto::Additive => daw_core::SoloMode::Additive,
+        }
+    }
+}
+
 impl From<daw_core::TimeSignature> for TimeSignatureDto {
     fn from(ts: daw_core::TimeSignature) -> Self {
         Self {
@@ -105,11 +822,39 @@ impl From<daw_core::TimeSignature> for TimeSignatureDto {
 pub fn session_to_snapshot(session: &daw_core::Session) -> SessionSnapshot {
     SessionSnapshot {
         name: session.name().to_string(),
+        info: session.info().into(),
         tempo: session.tempo(),
         time_signature: session.time_signature().into(),
         max_tick: session.max_tick(),
         current_tick: session.current_tick(),
         playback_state: session.playback_state().into(),
+        end_behavior: session.end_behavior().into(),
+        return_to_start: session.return_to_start(),
+        count_in_bars: session.count_in_bars(),
+        pre_roll_bars: session.pre_roll_bars(),
+        solo_mode: session.solo_mode().into(),
+        master_muted: session.master_muted(),
+        master: MasterDto {
+            gain_db: session.master_bus().gain_db,
+            pan: session.master_bus().pan,
+            limiter: session.master_bus().limiter.map(Into::into),
+            effects: session.master_effects().iter().map(Into::into).collect(),
+        },
+        pad_recording: session.pad_recording(),
+        recording: session.is_recording(),
+        midi_input: session.midi_input_device().map(str::to_string),
+        preview_volume: session.preview_volume(),
+        locators: session.locators().clone(),
+        loop_region: session.loop_region().map(|region| LoopRegionDto {
+            start_tick: region.start_tick,
+            end_tick: region.end_tick,
+            enabled: region.enabled,
+        }),
+        punch_region: session.punch_region().map(|region| PunchRegionDto {
+            start_tick: region.start_tick,
+            end_tick: region.end_tick,
+            enabled: region.enabled,
+        }),
         tracks: session
             .tracks()
             .iter()
@@ -120,14 +865,50 @@ pub fn session_to_snapshot(session: &daw_core::Session) -> SessionSnapshot {
                 solo: track.solo,
                 volume: track.volume,
                 pan: track.pan,
+                color: track.color,
+                metadata: track.metadata.clone(),
+                parent: track.parent.as_ref().map(|parent| parent.0),
+                is_folder: track.is_folder,
+                is_bus: track.is_bus,
+                sends: track
+                    .sends
+                    .iter()
+                    .map(|send| TrackSendDto {
+                        bus: send.bus.0,
+                        level: send.level,
+                        pre_fader: send.pre_fader,
+                    })
+                    .collect(),
+                armed: session.is_track_armed(track.id.0),
+                automation: track
+                    .automation
+                    .iter()
+                    .map(|lane| AutomationLaneDto {
+                        parameter: lane.parameter.clone(),
+                        points: lane
+                            .points
+                            .iter()
+                            .map(|point| AutomationPointDto {
+                                tick: point.tick,
+                                value: point.value,
+                                curve: point.curve.into(),
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+                effects: track.effects.iter().map(Into::into).collect(),
+                sampler: track.sampler.as_ref().map(Into::into),
                 clips: track
                     .clips()
                     .iter()
                     .map(|clip| ClipSummary {
+                        id: clip.id.0,
                         start_tick: clip.start_tick,
                         end_tick: clip.end_tick,
                         audio_offset: clip.audio_offset,
                         name: clip.name.clone(),
+                        source_bpm: clip.source.bpm,
+                        source_key: clip.source.key.map(|key| key.to_string()),
                     })
                     .collect(),
             })
@@ -136,6 +917,7 @@ pub fn session_to_snapshot(session: &daw_core::Session) -> SessionSnapshot {
             enabled: session.metronome_enabled(),
             volume: session.metronome_volume(),
         },
+        can_undo: session.can_undo(),
+        can_redo: session.can_redo(),
     }
 }
-
@@ -5,6 +5,8 @@
 
 mod commands;
 mod dto;
+#[cfg(feature = "vst3")]
+mod plugins;
 mod poll;
 mod state;
 
@@ -23,21 +25,116 @@ pub fn run() {
             commands::session_load_project,
             commands::session_get_state,
             commands::session_save,
+            commands::recent_projects_list,
             commands::session_save_as,
+            commands::session_collect_and_save,
+            commands::project_set_info,
+            commands::session_set_embed_samples,
+            commands::session_set_save_options,
             commands::session_render,
+            commands::render_reveal,
+            commands::render_open,
+            commands::session_export_midi,
             // Transport commands
             commands::transport_play,
             commands::transport_pause,
             commands::transport_stop,
             commands::transport_seek_to_tick,
+            commands::transport_set_end_behavior,
+            commands::transport_set_return_to_start,
+            commands::transport_set_count_in,
+            commands::transport_set_pre_roll,
+            commands::locator_set_here,
+            commands::locator_goto,
+            commands::transport_set_loop,
+            commands::transport_toggle_loop,
+            commands::transport_clear_loop,
+            commands::transport_set_punch,
+            commands::transport_toggle_punch,
+            commands::transport_clear_punch,
+            commands::transport_panic,
+            commands::transport_set_master_muted,
+            commands::pad_trigger_note,
+            commands::pad_set_recording,
+            commands::transport_record_start,
+            commands::transport_record_stop,
+            // MIDI input commands
+            commands::midi_list_inputs,
+            commands::midi_set_input,
             // Track commands
             commands::track_toggle_enabled,
             commands::track_solo_exclusive,
+            commands::track_set_solo_mode,
             commands::track_set_volume,
             commands::track_set_pan,
+            commands::track_set_color,
+            commands::track_set_metadata,
+            commands::track_add,
+            commands::track_remove,
+            commands::track_set_armed,
+            commands::track_rename,
+            commands::track_move,
+            commands::track_group,
+            commands::track_ungroup,
+            commands::track_set_parent,
+            // Automation commands
+            commands::automation_add_point,
+            commands::automation_move_point,
+            commands::automation_set_curve,
+            commands::automation_delete_point,
+            // Effect commands
+            commands::track_add_effect,
+            commands::track_remove_effect,
+            commands::track_move_effect,
+            commands::track_set_effect_parameter,
+            // Sampler commands
+            commands::track_set_sampler,
+            commands::track_remove_sampler,
+            commands::track_set_sampler_envelope,
+            // Plugin commands
+            #[cfg(feature = "vst3")]
+            plugins::plugin_scan,
+            #[cfg(feature = "vst3")]
+            plugins::track_plugins,
+            #[cfg(feature = "vst3")]
+            plugins::track_add_plugin,
+            #[cfg(feature = "vst3")]
+            plugins::track_remove_plugin,
+            #[cfg(feature = "vst3")]
+            plugins::track_set_plugin_parameter,
+            #[cfg(feature = "vst3")]
+            plugins::plugin_open_editor,
+            #[cfg(feature = "vst3")]
+            plugins::plugin_close_editor,
+            // Send commands
+            commands::track_add_bus,
+            commands::track_set_send,
+            commands::track_remove_send,
+            // Clip commands
+            commands::clip_move,
+            commands::clip_delete,
+            commands::clip_duplicate,
+            commands::clip_split,
+            commands::clip_trim,
+            commands::edit_undo,
+            commands::edit_redo,
+            // Sample commands
+            commands::sample_replace,
+            commands::sample_import,
+            commands::sample_preview,
+            commands::sample_stop_preview,
+            commands::sample_set_preview_volume,
             // Metronome commands
             commands::metronome_toggle,
             commands::metronome_set_volume,
+            // Master commands
+            commands::master_set_gain,
+            commands::master_set_pan,
+            commands::master_set_limiter,
+            commands::master_add_effect,
+            commands::master_remove_effect,
+            commands::master_move_effect,
+            commands::master_set_effect_parameter,
         ])
         .setup(|app| {
             // Build the main app menu
@@ -58,6 +155,9 @@ pub fn run() {
             let render_item = MenuItemBuilder::with_id("render", "Render...")
                 .accelerator("CmdOrCtrl+R")
                 .build(app)?;
+            let render_demo_item = MenuItemBuilder::with_id("render_demo", "Render Demo...")
+                .accelerator("CmdOrCtrl+Shift+R")
+                .build(app)?;
 
             let file_menu = SubmenuBuilder::new(app, "File")
                 .item(&open_item)
@@ -66,6 +166,7 @@ pub fn run() {
                 .item(&save_as_item)
                 .separator()
                 .item(&render_item)
+                .item(&render_demo_item)
                 .build()?;
 
             // Build the full menu bar
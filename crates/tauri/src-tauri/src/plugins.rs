@@ -0,0 +1,194 @@
+//! Tauri commands for VST3 plugins on tracks, built with the `vst3` feature.
+//!
+//! A plugin's editor is embedded in a native window of its own, opened by
+//! [`plugin_open_editor`]. The webview never draws it: the plugin paints straight
+//! into the window, which is closed along with the editor.
+
+use crate::dto::{PluginDto, SessionSnapshot, session_to_snapshot};
+use crate::state::AppState;
+use daw_core::{EditorParent, Session};
+use std::sync::mpsc;
+use tauri::{AppHandle, Manager, PhysicalSize, State, Window, WindowEvent};
+
+type CommandResult<T> = Result<T, String>;
+
+/// Run `f` on the loaded session, from a command or the main thread alike.
+fn with_session<T>(
+    state: &AppState,
+    f: impl FnOnce(&mut Session) -> CommandResult<T>,
+) -> CommandResult<T> {
+    let mut session_lock = state
+        .session
+        .lock()
+        .map_err(|_| "Failed to acquire session lock".to_string())?;
+
+    let session = session_lock
+        .as_mut()
+        .ok_or_else(|| "No session loaded".to_string())?;
+    f(session)
+}
+
+/// List the VST3 plugins installed in the platform's standard plugin folders.
+///
+/// Bundles that can't be loaded are left out and logged. Runs off the main
+/// thread, since scanning loads every bundle.
+#[tauri::command(async)]
+pub fn plugin_scan() -> CommandResult<Vec<PluginDto>> {
+    let (plugins, errors) = Session::scan_plugins();
+    for err in errors {
+        eprintln!("Skipping plugin: {err}");
+    }
+    Ok(plugins.iter().map(Into::into).collect())
+}
+
+/// List a track's plugins, in the order its audio goes through them.
+#[tauri::command]
+pub fn track_plugins(track_id: u64, state: State<AppState>) -> CommandResult<Vec<PluginDto>> {
+    with_session(&state, |session| {
+        Ok(session
+            .track_plugins(track_id)
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    })
+}
+
+/// Load a plugin found by `plugin_scan` onto the end of a track's plugins.
+#[tauri::command]
+pub fn track_add_plugin(
+    track_id: u64,
+    plugin: PluginDto,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    let info = plugin.try_into()?;
+    with_session(&state, |session| {
+        session
+            .add_track_plugin(track_id, &info)
+            .map_err(|e| e.to_string())?;
+        Ok(session_to_snapshot(session))
+    })
+}
+
+/// Remove a plugin from a track. Its editor window has to be closed first.
+#[tauri::command]
+pub fn track_remove_plugin(
+    track_id: u64,
+    index: usize,
+    state: State<AppState>,
+) -> CommandResult<SessionSnapshot> {
+    with_session(&state, |session| {
+        if !session.remove_track_plugin(track_id, index) {
+            return Err(format!("No plugin {} on track {}", index, track_id));
+        }
+        Ok(session_to_snapshot(session))
+    })
+}
+
+/// Set one of a plugin's parameters to a normalized value between 0.0 and 1.0.
+#[tauri::command]
+pub fn track_set_plugin_parameter(
+    track_id: u64,
+    index: usize,
+    parameter: u32,
+    value: f64,
+    state: State<AppState>,
+) -> CommandResult<()> {
+    with_session(&state, |session| {
+        if !session.set_track_plugin_parameter(track_id, index, parameter, value) {
+            return Err(format!("No plugin {} on track {}", index, track_id));
+        }
+        Ok(())
+    })
+}
+
+/// Label of the window a plugin's editor is embedded in
+fn editor_label(track_id: u64, index: usize) -> String {
+    format!("plugin-{}-{}", track_id, index)
+}
+
+/// Open a plugin's editor in a native window of its own, or bring it to the
+/// front if it's already open.
+///
+/// Async, because windows can't be created from commands on the main thread;
+/// the editor itself is then attached on the main thread, where plugins expect it.
+#[tauri::command(async)]
+pub fn plugin_open_editor(track_id: u64, index: usize, app: AppHandle) -> CommandResult<()> {
+    let label = editor_label(track_id, index);
+    if let Some(window) = app.get_window(&label) {
+        return window.set_focus().map_err(|e| e.to_string());
+    }
+
+    let title = with_session(&app.state::<AppState>(), |session| {
+        session
+            .track_plugins(track_id)
+            .get(index)
+            .map(|info| info.name.clone())
+            .ok_or_else(|| format!("No plugin {} on track {}", index, track_id))
+    })?;
+    let window = Window::builder(&app, &label)
+        .title(title)
+        .resizable(false)
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let (opened_tx, opened_rx) = mpsc::channel();
+    let editor_window = window.clone();
+    let handle = app.clone();
+    app.run_on_main_thread(move || {
+        let _ = opened_tx.send(attach_editor(&handle, &editor_window, track_id, index));
+    })
+    .map_err(|e| e.to_string())?;
+
+    let opened = opened_rx
+        .recv()
+        .map_err(|_| "The editor was never opened".to_string())
+        .and_then(|opened| opened);
+    if opened.is_err() {
+        let _ = window.destroy();
+    }
+    opened
+}
+
+/// Embed a plugin's editor in `window`, size the window to it, and close the
+/// editor when the window is closed. Runs on the main thread.
+fn attach_editor(
+    app: &AppHandle,
+    window: &Window,
+    track_id: u64,
+    index: usize,
+) -> CommandResult<()> {
+    let parent = EditorParent::from_window(window).map_err(|e| e.to_string())?;
+    let size = with_session(&app.state::<AppState>(), |session| {
+        session
+            .open_plugin_editor(track_id, index, parent)
+            .map_err(|e| e.to_string())
+    })?;
+    window
+        .set_size(PhysicalSize::new(size.width, size.height))
+        .map_err(|e| e.to_string())?;
+
+    // The editor lets go of the window before it's destroyed
+    let handle = app.clone();
+    window.on_window_event(move |event| {
+        if let WindowEvent::CloseRequested { .. } = event {
+            let _ = with_session(&handle.state::<AppState>(), |session| {
+                session.close_plugin_editor(track_id, index);
+                Ok(())
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Close a plugin's editor and its window, if it's open.
+#[tauri::command]
+pub fn plugin_close_editor(track_id: u64, index: usize, app: AppHandle) -> CommandResult<()> {
+    if let Some(window) = app.get_window(&editor_label(track_id, index)) {
+        with_session(&app.state::<AppState>(), |session| {
+            session.close_plugin_editor(track_id, index);
+            Ok(())
+        })?;
+        window.destroy().map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
@@ -3,7 +3,7 @@
 //! This module runs an async task that polls the Session at ~60 Hz (every 16ms)
 //! to retrieve playback position updates and emit events to the frontend.
 
-use crate::dto::SessionTickEvent;
+use crate::dto::{SessionEventDto, SessionTickEvent};
 use crate::state::AppState;
 use std::time::Duration;
 use tauri::{AppHandle, Emitter, Manager};
@@ -11,11 +11,15 @@ use tauri::{AppHandle, Emitter, Manager};
 /// Start the background poll loop.
 ///
 /// This spawns an async task that runs for the lifetime of the application.
-/// It polls the session every 16ms and emits "session-tick" events when
-/// the playback position changes.
+/// It polls the session every 16ms and emits "session-tick" events, with the
+/// master's peak level, when the playback position or the level changes,
+/// "audio-status" events when the audio
+/// stream stalls or is restarted, and "samples-reloaded" events when samples
+/// edited on disk are swapped in.
 pub fn start_poll_loop(app: AppHandle) {
     tauri::async_runtime::spawn(async move {
         let mut interval = tokio::time::interval(Duration::from_millis(16));
+        let mut last_peak = [0.0; 2];
         
         loop {
             interval.tick().await;
@@ -38,17 +42,34 @@ pub fn start_poll_loop(app: AppHandle) {
                 let tick_changed = session.poll();
                 let current_tick = session.current_tick();
                 let playback_state = session.playback_state();
+                let master_peak = session.master_peak();
                 
-                // Emit event if tick changed or if we're playing (for smooth updates)
-                if tick_changed.is_some() || session.is_playing() {
+                // Emit event if tick changed or if we're playing (for smooth updates),
+                // and once more after the meter falls silent
+                if tick_changed.is_some() || session.is_playing() || master_peak != last_peak {
+                    last_peak = master_peak;
                     let event = SessionTickEvent {
                         tick: current_tick,
                         playback_state: playback_state.into(),
+                        master_peak,
                     };
                     
                     // Emit the event to all frontend listeners
                     let _ = app.emit("session-tick", event);
                 }
+
+                for event in session.take_events() {
+                    let _ = match SessionEventDto::from_event(event, session) {
+                        SessionEventDto::AudioStatus(status) => app.emit("audio-status", status),
+                        SessionEventDto::SamplesReloaded(reloaded) => {
+                            app.emit("samples-reloaded", reloaded)
+                        }
+                        SessionEventDto::AutosaveFailed(failed) => {
+                            app.emit("autosave-failed", failed)
+                        }
+                        SessionEventDto::MidiInputLost(lost) => app.emit("midi-input-lost", lost),
+                    };
+                }
             }
         }
     });
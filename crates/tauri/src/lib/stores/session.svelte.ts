@@ -18,6 +18,8 @@ export interface ClipSummary {
   name: string;
   startTick: number;
   endTick: number;
+  sourceBpm: number | null;
+  sourceKey: string | null;
 }
 
 export interface TrackSummary {
@@ -27,9 +29,105 @@ export interface TrackSummary {
   soloed: boolean;
   volume: number;
   pan: number;
+  color: [number, number, number] | null;
+  metadata: Record<string, string>;
+  parent: number | null;
+  isFolder: boolean;
+  /** Whether the track is an aux bus, mixing what other tracks send it */
+  isBus: boolean;
+  /** Buses the track feeds, on top of what it plays itself */
+  sends: TrackSend[];
+  /** Whether the track gets a clip of the next recorded take */
+  armed: boolean;
+  /** Automation lanes, at most one per parameter */
+  automation: AutomationLane[];
+  /** Insert effects, in the order the track's audio goes through them */
+  effects: Effect[];
+  /** Instrument playing the track's MIDI clips and live MIDI input */
+  sampler: Sampler | null;
   clips: ClipSummary[];
 }
 
+/** Attack, decay and release in seconds, and the sustain level from 0 to 1 */
+export interface Adsr {
+  attack: number;
+  decay: number;
+  sustain: number;
+  release: number;
+}
+
+/** A track's sampler, without its samples */
+export interface Sampler {
+  /** Note the one sample plays at its own pitch, or null when notes each have a sample of their own */
+  rootNote: number | null;
+  /** Notes with a sample of their own */
+  notes: number[];
+  envelope: Adsr;
+  /** Notes that sound at once */
+  polyphony: number;
+}
+
+/** Kinds of built-in insert effect */
+export type EffectKind = "eq" | "compressor" | "delay" | "reverb";
+
+/** One of an effect's settings, with the range it takes */
+export interface EffectParameter {
+  name: string;
+  value: number;
+  min: number;
+  max: number;
+}
+
+/** Audio a track feeds to a bus */
+export interface TrackSend {
+  /** Id of the bus track */
+  bus: number;
+  /** Linear gain of the send (0 to 1) */
+  level: number;
+  /** Whether the send is taken before the track's volume */
+  preFader: boolean;
+}
+
+/** Peak limiter settings on the master */
+export interface Limiter {
+  /** Highest sample level let through, in dBFS */
+  ceilingDb: number;
+  releaseMs: number;
+}
+
+/** The master channel's settings and insert effects */
+export interface MasterState {
+  gainDb: number;
+  /** Balance from -1 (left) to 1 (right) */
+  pan: number;
+  limiter: Limiter | null;
+  /** Insert effects, in the order the mix goes through them */
+  effects: Effect[];
+}
+
+/** A built-in insert effect and its settings */
+export interface Effect {
+  kind: EffectKind;
+  parameters: EffectParameter[];
+}
+
+/** How an automation point moves on to the next one */
+export type AutomationCurve = "linear" | "step" | "exponential";
+
+/** A value at a tick in an automation lane */
+export interface AutomationPoint {
+  tick: number;
+  value: number;
+  /** Shape of the line from here to the next point */
+  curve: AutomationCurve;
+}
+
+/** Points drawn for one parameter of a track, such as "volume" or "pan" */
+export interface AutomationLane {
+  parameter: string;
+  points: AutomationPoint[];
+}
+
 export interface MetronomeState {
   enabled: boolean;
   volume: number;
@@ -37,25 +135,259 @@ export interface MetronomeState {
 
 export type PlaybackState = "stopped" | "playing" | "paused";
 
+export type EndBehavior = "continue" | "stop" | "loop";
+
+export type SoloMode = "exclusive" | "additive";
+
+/** Author, notes, timestamps and key of the project */
+export interface ProjectInfo {
+  author: string;
+  notes: string;
+  /** When the project was first saved, in seconds since the Unix epoch */
+  created: number | null;
+  /** When the project was last saved, in seconds since the Unix epoch */
+  modified: number | null;
+  /** Key the song is in, e.g. "F#m" */
+  key: string | null;
+}
+
+export interface LoopRegion {
+  startTick: number;
+  endTick: number;
+  /** Whether playback is cycling through the region */
+  enabled: boolean;
+}
+
+export interface PunchRegion {
+  startTick: number;
+  endTick: number;
+  /** Whether recordings are kept to the region */
+  enabled: boolean;
+}
+
 export interface SessionSnapshot {
   name: string;
+  info: ProjectInfo;
   tempo: number;
   timeSignature: TimeSignature;
   maxTick: number;
   currentTick: number;
   playbackState: PlaybackState;
+  endBehavior: EndBehavior;
+  returnToStart: boolean;
+  /** Bars of metronome played before playback starts */
+  countInBars: number;
+  /** Bars before the cursor that playback starts from */
+  preRollBars: number;
+  soloMode: SoloMode;
+  masterMuted: boolean;
+  /** Gain, balance, limiter and effects on the summed tracks */
+  master: MasterState;
+  /** Whether pad hits during playback are recorded as clips */
+  padRecording: boolean;
+  /** Whether a take is being recorded from the input device */
+  recording: boolean;
+  /** MIDI input device played on the armed tracks */
+  midiInput: string | null;
+  /** Linear gain of sample previews */
+  previewVolume: number;
+  /** Locator number (1-9) to tick, for the locators that are set */
+  locators: Record<number, number>;
+  /** Section playback cycles through while looping is on */
+  loopRegion: LoopRegion | null;
+  /** Section recordings are kept to while punching in */
+  punchRegion: PunchRegion | null;
   tracks: TrackSummary[];
   metronome: MetronomeState;
+  /** Whether there's a clip edit to undo */
+  canUndo: boolean;
+  /** Whether there's an undone clip edit to redo */
+  canRedo: boolean;
 }
 
 export interface SessionTickEvent {
   tick: number;
   playbackState: PlaybackState;
+  /** Peak level on the left and right of the output since the last tick, as linear gain */
+  masterPeak: [number, number];
+}
+
+export interface LoadProgressEvent {
+  fraction: number;
+}
+
+export interface RenderCompleteEvent {
+  path: string;
+  durationSecs: number;
+  /** Highest sample level in dBFS, or null for a silent render */
+  peakDb: number | null;
+  /** Integrated loudness in LUFS, or null if silent or too short to measure */
+  loudnessLufs: number | null;
+  /** Highest level between samples in dBTP, or null for a silent render */
+  truePeakDb: number | null;
+  /** Gain normalization applied in dB, 0 without it */
+  gainDb: number;
+  /** Average level in dBFS, or null for a silent render */
+  rmsDb: number | null;
+  /** Samples over full scale, which clip when written as integers or played back */
+  clippedSamples: number;
+}
+
+/** Level to bring renders to; either target can be left out */
+export interface Normalization {
+  /** Integrated loudness to reach in LUFS */
+  loudnessLufs: number | null;
+  /** Highest true peak allowed in dBTP */
+  truePeakDb: number | null;
+}
+
+/** Sample format of rendered WAV files */
+export type RenderFormat = "int16" | "int24" | "float32";
+
+/** Time rendered after the last clip, so decays ring out */
+export type RenderTail = { unit: "seconds"; length: number } | { unit: "bars"; length: number };
+
+/** How renders in integer formats are rounded */
+export type Dither = "none" | "triangular" | "noiseShaped";
+
+/** Part of the arrangement to render, in ticks; either end can be left open */
+export interface RenderRegion {
+  startTick?: number;
+  endTick?: number;
+}
+
+/** Demo tone or voice tag laid over a render */
+export interface WatermarkOptions {
+  /** Seconds from the start of one mark to the start of the next */
+  intervalSecs: number;
+  /** Peak level of each mark in dBFS */
+  levelDb: number;
+  /** Audio file with a voice tag, or null for a beep */
+  tagPath: string | null;
+}
+
+/** Encoding and compression project files are saved with */
+export interface SaveOptions {
+  /** JSON is readable; MessagePack is much smaller for big projects */
+  format: "json" | "messagepack";
+  /** Compress the file with zstd */
+  compress: boolean;
+  /** How many earlier versions of the file to keep as `.bak.N` backups */
+  backups: number;
+  /** Save a zip archive holding the project, its peaks and embedded samples */
+  zip: boolean;
+}
+
+/** A project in the recent list */
+export interface RecentProject {
+  path: string;
+  name: string;
+}
+
+export interface SamplesReloadedEvent {
+  /** Samples reloaded after being saved from another program */
+  names: string[];
+  /** Set when a changed sample couldn't be read; it's tried again on its next change */
+  error: string | null;
+  snapshot: SessionSnapshot;
+}
+
+export interface AutosaveFailedEvent {
+  error: string;
+}
+
+export interface MidiInputLostEvent {
+  error: string;
+}
+
+export type AudioStatusEvent =
+  | { kind: "stalled" }
+  | { kind: "restarted" }
+  | { kind: "restartFailed"; error: string };
+
+const OPEN_AFTER_RENDER_KEY = "daw-open-after-render";
+const DEMO_WATERMARK_KEY = "daw-demo-watermark";
+const RENDER_FORMAT_KEY = "daw-render-format";
+const RENDER_NORMALIZATION_KEY = "daw-render-normalization";
+const RENDER_DITHER_KEY = "daw-render-dither";
+const RENDER_TAIL_KEY = "daw-render-tail";
+const RENDER_METRONOME_KEY = "daw-render-metronome";
+
+function loadTail(): RenderTail | null {
+  if (typeof window === "undefined") return null;
+  try {
+    const stored = localStorage.getItem(RENDER_TAIL_KEY);
+    return stored ? JSON.parse(stored) : null;
+  } catch {
+    return null;
+  }
+}
+
+function loadNormalization(): Normalization | null {
+  if (typeof window === "undefined") return null;
+  try {
+    const stored = localStorage.getItem(RENDER_NORMALIZATION_KEY);
+    return stored ? JSON.parse(stored) : null;
+  } catch {
+    return null;
+  }
+}
+const SAVE_OPTIONS_KEY = "daw-save-options";
+
+const DEFAULT_WATERMARK: WatermarkOptions = { intervalSecs: 15, levelDb: -30, tagPath: null };
+
+function loadWatermark(): WatermarkOptions {
+  if (typeof window === "undefined") return DEFAULT_WATERMARK;
+  try {
+    const stored = localStorage.getItem(DEMO_WATERMARK_KEY);
+    return stored ? { ...DEFAULT_WATERMARK, ...JSON.parse(stored) } : DEFAULT_WATERMARK;
+  } catch {
+    return DEFAULT_WATERMARK;
+  }
+}
+
+const DEFAULT_SAVE_OPTIONS: SaveOptions = {
+  format: "json",
+  compress: false,
+  backups: 0,
+  zip: false,
+};
+
+function loadSaveOptions(): SaveOptions {
+  if (typeof window === "undefined") return DEFAULT_SAVE_OPTIONS;
+  try {
+    const stored = localStorage.getItem(SAVE_OPTIONS_KEY);
+    return stored ? { ...DEFAULT_SAVE_OPTIONS, ...JSON.parse(stored) } : DEFAULT_SAVE_OPTIONS;
+  } catch {
+    return DEFAULT_SAVE_OPTIONS;
+  }
 }
 
 class SessionStore {
   private _session = $state<SessionSnapshot | null>(null);
+  private _lastRender = $state<RenderCompleteEvent | null>(null);
+  private _openAfterRender = $state(
+    typeof window !== "undefined" && localStorage.getItem(OPEN_AFTER_RENDER_KEY) === "true",
+  );
+  private _demoWatermark = $state<WatermarkOptions>(loadWatermark());
+  private _renderTail = $state<RenderTail | null>(loadTail());
+  private _renderMetronome = $state(
+    typeof window !== "undefined" && localStorage.getItem(RENDER_METRONOME_KEY) === "true",
+  );
+  private _renderDither = $state<Dither>(
+    (typeof window !== "undefined" && (localStorage.getItem(RENDER_DITHER_KEY) as Dither | null)) ||
+      "none",
+  );
+  private _renderNormalization = $state<Normalization | null>(loadNormalization());
+  private _renderFormat = $state<RenderFormat>(
+    (typeof window !== "undefined" &&
+      (localStorage.getItem(RENDER_FORMAT_KEY) as RenderFormat | null)) ||
+      "float32",
+  );
+  private _saveOptions = $state<SaveOptions>(loadSaveOptions());
   private _loading = $state(false);
+  private _loadProgress = $state(0);
+  private _masterPeak = $state<[number, number]>([0, 0]);
   private _error = $state<string | null>(null);
 
   constructor() {
@@ -65,6 +397,38 @@ class SessionStore {
         this._session.currentTick = event.payload.tick;
         this._session.playbackState = event.payload.playbackState;
       }
+      this._masterPeak = event.payload.masterPeak;
+    });
+
+    listen<LoadProgressEvent>("load-progress", (event) => {
+      this._loadProgress = event.payload.fraction;
+    });
+
+    listen<RenderCompleteEvent>("render-complete", (event) => {
+      this._lastRender = event.payload;
+    });
+
+    listen<SamplesReloadedEvent>("samples-reloaded", (event) => {
+      this._session = event.payload.snapshot;
+      if (event.payload.error) {
+        console.error("Failed to reload edited sample:", event.payload.error);
+      }
+    });
+
+    listen<AutosaveFailedEvent>("autosave-failed", (event) => {
+      console.error("Autosave failed:", event.payload.error);
+    });
+
+    listen<MidiInputLostEvent>("midi-input-lost", (event) => {
+      console.error("MIDI input device was lost:", event.payload.error);
+    });
+
+    listen<AudioStatusEvent>("audio-status", (event) => {
+      if (event.payload.kind === "restartFailed") {
+        console.error("Audio stream stopped and could not be restarted:", event.payload.error);
+      } else {
+        console.warn("Audio stream", event.payload.kind);
+      }
     });
   }
 
@@ -76,10 +440,112 @@ class SessionStore {
     return this._loading;
   }
 
+  /** Fraction of the project's audio loaded while `loading`, from 0 to 1 */
+  get loadProgress() {
+    return this._loadProgress;
+  }
+
   get error() {
     return this._error;
   }
 
+  /** Peak level on the left and right of the output, as linear gain */
+  get masterPeak() {
+    return this._masterPeak;
+  }
+
+  /** The most recent finished render, until dismissed */
+  get lastRender() {
+    return this._lastRender;
+  }
+
+  /** Whether finished renders open in the default player */
+  get openAfterRender() {
+    return this._openAfterRender;
+  }
+
+  setOpenAfterRender(enabled: boolean) {
+    this._openAfterRender = enabled;
+    localStorage.setItem(OPEN_AFTER_RENDER_KEY, String(enabled));
+  }
+
+  /** Time rendered after the last clip, or null to stop where it ends */
+  get renderTail() {
+    return this._renderTail;
+  }
+
+  setRenderTail(tail: RenderTail | null) {
+    this._renderTail = tail;
+    localStorage.setItem(RENDER_TAIL_KEY, JSON.stringify(tail));
+  }
+
+  /** Whether renders include the metronome's click */
+  get renderMetronome() {
+    return this._renderMetronome;
+  }
+
+  setRenderMetronome(enabled: boolean) {
+    this._renderMetronome = enabled;
+    localStorage.setItem(RENDER_METRONOME_KEY, String(enabled));
+  }
+
+  /** How renders in integer formats are rounded */
+  get renderDither() {
+    return this._renderDither;
+  }
+
+  setRenderDither(dither: Dither) {
+    this._renderDither = dither;
+    localStorage.setItem(RENDER_DITHER_KEY, dither);
+  }
+
+  /** Level renders are normalized to, or null to leave them as mixed */
+  get renderNormalization() {
+    return this._renderNormalization;
+  }
+
+  setRenderNormalization(normalization: Normalization | null) {
+    this._renderNormalization = normalization;
+    localStorage.setItem(RENDER_NORMALIZATION_KEY, JSON.stringify(normalization));
+  }
+
+  /** Sample format renders are written in */
+  get renderFormat() {
+    return this._renderFormat;
+  }
+
+  setRenderFormat(format: RenderFormat) {
+    this._renderFormat = format;
+    localStorage.setItem(RENDER_FORMAT_KEY, format);
+  }
+
+  /** Watermark used by demo renders */
+  get demoWatermark() {
+    return this._demoWatermark;
+  }
+
+  setDemoWatermark(watermark: WatermarkOptions) {
+    this._demoWatermark = watermark;
+    localStorage.setItem(DEMO_WATERMARK_KEY, JSON.stringify(watermark));
+  }
+
+  /** How projects are saved, applied to every session that's loaded */
+  get saveOptions() {
+    return this._saveOptions;
+  }
+
+  async setSaveOptions(options: SaveOptions): Promise<void> {
+    this._saveOptions = options;
+    localStorage.setItem(SAVE_OPTIONS_KEY, JSON.stringify(options));
+    if (this._session) {
+      await invoke("session_set_save_options", { options });
+    }
+  }
+
+  dismissRender() {
+    this._lastRender = null;
+  }
+
   get isPlaying() {
     return this._session?.playbackState === "playing";
   }
@@ -89,10 +555,12 @@ class SessionStore {
    */
   async loadProject(path: string): Promise<void> {
     this._loading = true;
+    this._loadProgress = 0;
     this._error = null;
 
     try {
       const snapshot = await invoke<SessionSnapshot>("session_load_project", { path });
+      await invoke("session_set_save_options", { options: this._saveOptions });
       this._session = snapshot;
     } catch (err) {
       this._error = err instanceof Error ? err.message : String(err);
@@ -102,6 +570,14 @@ class SessionStore {
     }
   }
 
+  /**
+   * Recently opened projects, most recent first. Ones whose files are gone
+   * are left out.
+   */
+  async recentProjects(): Promise<RecentProject[]> {
+    return await invoke<RecentProject[]>("recent_projects_list");
+  }
+
   /**
    * Get the current session state.
    */
@@ -127,11 +603,153 @@ class SessionStore {
   }
 
   /**
-   * Save the current session to a new path.
+   * Save the current session to a new path. Samples next to the old project are
+   * referenced where they are, or copied into an `audio` folder next to the new
+   * one with `copySamples`. Returns the names of samples that couldn't be found.
+   */
+  async saveAs(path: string, copySamples = false): Promise<string[]> {
+    try {
+      return await invoke<string[]>("session_save_as", { path, copySamples });
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Set the project's author, notes and key (a name like "F#m", or null).
+   */
+  async setInfo(author: string, notes: string, key: string | null): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("project_set_info", { author, notes, key });
+      this._session = snapshot;
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Save to a new path with copies of every sample in an `audio` folder next to
+   * the project. Returns the names of samples that couldn't be found.
+   */
+  async collectAndSave(path: string): Promise<string[]> {
+    try {
+      return await invoke<string[]>("session_collect_and_save", { path });
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Store the sample files inside the project file from the next save on, so
+   * it can be shared as a single file.
+   */
+  async setEmbedSamples(enabled: boolean): Promise<void> {
+    try {
+      await invoke("session_set_embed_samples", { enabled });
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Render the current session to a WAV file in `renderFormat` with
+   * `renderDither`, normalized to `renderNormalization` and running `renderTail`
+   * past the last clip, with the click if `renderMetronome` is set, opening it
+   * afterwards if `openAfterRender` is set.
+   * With `demo`, the `demoWatermark` is laid over the mix. With `region`, only the
+   * ticks from `startTick` to `endTick` are rendered, such as a loop or a selection.
+   */
+  async render(
+    path: string,
+    demo = false,
+    region: RenderRegion | null = null,
+  ): Promise<RenderCompleteEvent> {
+    try {
+      const summary = await invoke<RenderCompleteEvent>("session_render", {
+        path,
+        openWhenDone: this._openAfterRender,
+        normalize: this._renderNormalization,
+        watermark: demo ? this._demoWatermark : null,
+        format: this._renderFormat,
+        dither: this._renderDither,
+        startTick: region?.startTick ?? null,
+        endTick: region?.endTick ?? null,
+        tail: this._renderTail,
+        metronome: this._renderMetronome,
+      });
+      this._lastRender = summary;
+      return summary;
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Show a rendered file in the system file manager.
+   */
+  async revealRender(path: string): Promise<void> {
+    await invoke("render_reveal", { path });
+  }
+
+  /**
+   * Open a rendered file in the default audio player.
+   */
+  async openRender(path: string): Promise<void> {
+    await invoke("render_open", { path });
+  }
+
+  /**
+   * Export tempo, time signatures, locators and MIDI clips to a .mid file.
+   */
+  async exportMidi(path: string): Promise<void> {
+    try {
+      await invoke("session_export_midi", { path });
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Swap the audio of every clip using a sample for another file.
+   *
+   * `old` is the sample's reference (`project:audio/kick.wav`), the path in it,
+   * or the file it resolves to.
+   */
+  async replaceSample(old: string, newPath: string): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("sample_replace", { old, newPath });
+      this._session = snapshot;
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Import an audio file onto a track at `tick`, as when it's dropped there.
+   */
+  async importAudioFile(path: string, trackId: number, tick: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("sample_import", { path, trackId, tick });
+      this._session = snapshot;
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Audition a sample file without adding it to the arrangement.
    */
-  async saveAs(path: string): Promise<void> {
+  async previewSample(path: string): Promise<void> {
     try {
-      await invoke("session_save_as", { path });
+      await invoke("sample_preview", { path });
     } catch (err) {
       this._error = err instanceof Error ? err.message : String(err);
       throw err;
@@ -139,11 +757,19 @@ class SessionStore {
   }
 
   /**
-   * Render the current session to a WAV file.
+   * Stop the sample being previewed.
    */
-  async render(path: string): Promise<void> {
+  async stopPreview(): Promise<void> {
+    await invoke("sample_stop_preview");
+  }
+
+  /**
+   * Set the volume of sample previews (0.0 to 1.0).
+   */
+  async setPreviewVolume(volume: number): Promise<void> {
     try {
-      await invoke("session_render", { path });
+      const snapshot = await invoke<SessionSnapshot>("sample_set_preview_volume", { volume });
+      this._session = snapshot;
     } catch (err) {
       this._error = err instanceof Error ? err.message : String(err);
       throw err;
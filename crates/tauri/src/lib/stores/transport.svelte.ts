@@ -6,7 +6,7 @@
  */
 
 import { invoke } from "@tauri-apps/api/core";
-import { sessionStore, type SessionSnapshot } from "./session.svelte";
+import { sessionStore, type EndBehavior, type SessionSnapshot } from "./session.svelte";
 
 class TransportStore {
   private _error = $state<string | null>(null);
@@ -68,6 +68,291 @@ class TransportStore {
     }
   }
 
+  /**
+   * Set what playback does after the last clip.
+   */
+  async setEndBehavior(endBehavior: EndBehavior): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_set_end_behavior", { endBehavior });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Set whether stopping returns the playhead to where playback started.
+   */
+  async setReturnToStart(enabled: boolean): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_set_return_to_start", { enabled });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Set how many bars of metronome count in before playback starts (0-2).
+   */
+  async setCountIn(bars: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_set_count_in", { bars });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Set how many bars before the cursor playback starts from.
+   */
+  async setPreRoll(bars: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_set_pre_roll", { bars });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Store the current position in a numbered locator (1-9).
+   */
+  async setLocatorHere(n: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("locator_set_here", { n });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Jump to a numbered locator (1-9), if it is set.
+   */
+  async gotoLocator(n: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("locator_goto", { n });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Loop playback between two ticks and turn looping on.
+   */
+  async setLoop(startTick: number, endTick: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_set_loop", { startTick, endTick });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Turn looping on or off, keeping the loop region.
+   */
+  async toggleLoop(): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_toggle_loop");
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Remove the loop region.
+   */
+  async clearLoop(): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_clear_loop");
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Keep recordings to the section between two ticks and turn punching in on.
+   */
+  async setPunch(startTick: number, endTick: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_set_punch", { startTick, endTick });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Turn punching in on or off, keeping the punch region.
+   */
+  async togglePunch(): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_toggle_punch");
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Remove the punch region, so whole takes are kept.
+   */
+  async clearPunch(): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_clear_punch");
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Start recording the input device onto the armed tracks.
+   */
+  async startRecording(): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_record_start");
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Stop recording and put the take on the armed tracks.
+   */
+  async stopRecording(): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_record_stop");
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Mute the master output and stop playback right away.
+   */
+  async panic(): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_panic");
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Mute or unmute the master output.
+   */
+  async setMasterMuted(muted: boolean): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("transport_set_master_muted", { muted });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Set the master gain in dB.
+   */
+  async setMasterGain(gainDb: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("master_set_gain", { gainDb });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Set the master balance (-1 left to 1 right).
+   */
+  async setMasterPan(pan: number): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("master_set_pan", { pan });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Play the pad for a MIDI note (see `PAD_BASE_NOTE`), recording it if armed.
+   */
+  async triggerPadNote(note: number, velocity = 100): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("pad_trigger_note", { note, velocity });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Arm or disarm recording of pad hits into clips.
+   */
+  async setPadRecording(armed: boolean): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("pad_set_recording", { armed });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * List the MIDI input devices connected now.
+   */
+  async listMidiInputs(): Promise<string[]> {
+    try {
+      return await invoke<string[]>("midi_list_inputs");
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
+  /**
+   * Play a MIDI input device on the armed tracks, or stop listening with `null`.
+   */
+  async setMidiInput(device: string | null): Promise<void> {
+    try {
+      const snapshot = await invoke<SessionSnapshot>("midi_set_input", { device });
+      this.updateSession(snapshot);
+    } catch (err) {
+      this._error = err instanceof Error ? err.message : String(err);
+      throw err;
+    }
+  }
+
   /**
    * Toggle play/pause.
    */
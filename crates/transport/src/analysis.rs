@@ -0,0 +1,546 @@
+//! Level analysis and simple corrective processing for `AudioArc`.
+
+use crate::AudioArc;
+
+/// Convert decibels to a linear gain factor
+pub fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// Convert a linear gain factor to decibels (`-inf` for silence)
+pub fn gain_to_db(gain: f32) -> f32 {
+    20.0 * gain.log10()
+}
+
+impl AudioArc {
+    /// Largest absolute sample value across all channels (linear, 1.0 = 0 dBFS).
+    ///
+    /// Returns 0.0 for empty audio.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daw_transport::AudioArc;
+    ///
+    /// let audio = AudioArc::new(vec![0.25, -0.5, 0.1, 0.2], 44100, 2);
+    /// assert_eq!(audio.peak(), 0.5);
+    /// ```
+    pub fn peak(&self) -> f32 {
+        self.samples()
+            .iter()
+            .fold(0.0f32, |peak, &sample| peak.max(sample.abs()))
+    }
+
+    /// Highest level between samples as well as at them (linear), as ITU-R BS.1770
+    /// estimates it by looking at four times the sample rate.
+    ///
+    /// The waveform a converter rebuilds from the samples can overshoot them, so this
+    /// is the level that can clip on playback or in a lossy encode even when
+    /// [`peak`](Self::peak) stays under full scale. Returns 0.0 for empty audio.
+    pub fn true_peak(&self) -> f32 {
+        let mut meter = TruePeakMeter::new(self.channels());
+        meter.push(self.samples());
+        meter.finish()
+    }
+
+    /// Root mean square level across all channels (linear).
+    ///
+    /// Returns 0.0 for empty audio.
+    pub fn rms(&self) -> f32 {
+        let samples = self.samples();
+        if samples.is_empty() {
+            return 0.0;
+        }
+        // Accumulate in f64 so long files don't lose precision
+        let sum_squares: f64 = samples.iter().map(|&s| s as f64 * s as f64).sum();
+        (sum_squares / samples.len() as f64).sqrt() as f32
+    }
+
+    /// Integrated loudness in LUFS, as defined by ITU-R BS.1770.
+    ///
+    /// The audio is K-weighted, measured in overlapping 400 ms blocks, and blocks
+    /// below -70 LUFS or more than 10 LU under the ungated level are left out, so
+    /// silence and quiet passages don't drag the result down. Returns `-inf` for
+    /// audio shorter than one block or entirely below the gate.
+    ///
+    /// Surround channels of 5.1 audio are weighted up and the LFE channel is left
+    /// out; every other layout weights all channels equally.
+    pub fn integrated_loudness(&self) -> f32 {
+        let mut meter = LoudnessMeter::new(self.sample_rate(), self.channels());
+        meter.push(self.samples());
+        meter.finish()
+    }
+
+    /// Scale the audio so its peak sits at `target_db` dBFS.
+    ///
+    /// Silent audio is returned unchanged, since no gain can bring it to the target.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daw_transport::AudioArc;
+    ///
+    /// let audio = AudioArc::new(vec![0.25, -0.5], 44100, 1);
+    /// let normalized = audio.normalize(0.0);
+    /// assert_eq!(normalized.samples(), &[0.5, -1.0]);
+    /// ```
+    pub fn normalize(&self, target_db: f32) -> AudioArc {
+        let peak = self.peak();
+        if peak == 0.0 {
+            return self.clone();
+        }
+        self.with_gain(db_to_gain(target_db) / peak)
+    }
+
+    /// Return a copy with every sample multiplied by `gain`.
+    pub fn with_gain(&self, gain: f32) -> AudioArc {
+        let samples = self.samples().iter().map(|&s| s * gain).collect();
+        AudioArc::new(samples, self.sample_rate(), self.channels())
+    }
+
+    /// Subtract each channel's mean, centering the waveform around zero.
+    pub fn remove_dc_offset(&self) -> AudioArc {
+        let channels = self.channels() as usize;
+        let frames = self.frames();
+        if frames == 0 {
+            return self.clone();
+        }
+
+        let mut sums = vec![0.0f64; channels];
+        for frame in self.samples().chunks_exact(channels) {
+            for (sum, &sample) in sums.iter_mut().zip(frame) {
+                *sum += sample as f64;
+            }
+        }
+        let offsets: Vec<f32> = sums
+            .iter()
+            .map(|sum| (sum / frames as f64) as f32)
+            .collect();
+
+        let mut samples = self.samples().to_vec();
+        for frame in samples.chunks_exact_mut(channels) {
+            for (sample, offset) in frame.iter_mut().zip(&offsets) {
+                *sample -= offset;
+            }
+        }
+        AudioArc::new(samples, self.sample_rate(), self.channels())
+    }
+}
+
+/// [`AudioArc::true_peak`] measured a block at a time, for audio that never exists
+/// all at once, such as a render streamed to disk.
+#[derive(Debug, Clone)]
+pub struct TruePeakMeter {
+    channels: usize,
+    /// Interpolation kernel for each point between two samples
+    kernels: Vec<[f32; TRUE_PEAK_TAPS]>,
+    /// Interleaved frames from five before the next point to look at, onwards;
+    /// frames before the start are silence
+    window: Vec<f32>,
+    /// Frames pushed so far
+    frames: u64,
+    /// Frames whose points have been looked at
+    measured: u64,
+    peak: f32,
+}
+
+/// Samples either side of a point the true peak interpolation looks at
+const TRUE_PEAK_HALF_WIDTH: usize = 6;
+const TRUE_PEAK_TAPS: usize = 2 * TRUE_PEAK_HALF_WIDTH;
+const TRUE_PEAK_OVERSAMPLING: usize = 4;
+
+impl TruePeakMeter {
+    pub fn new(channels: u16) -> Self {
+        // Lanczos-windowed sinc for each point between two samples
+        let sinc = |t: f64| {
+            if t == 0.0 {
+                1.0
+            } else {
+                let x = std::f64::consts::PI * t;
+                x.sin() / x
+            }
+        };
+        let half_width = TRUE_PEAK_HALF_WIDTH as f64;
+        let kernels = (1..TRUE_PEAK_OVERSAMPLING)
+            .map(|phase| {
+                let offset = phase as f64 / TRUE_PEAK_OVERSAMPLING as f64;
+                std::array::from_fn(|index| {
+                    let t = offset - (index as f64 + 1.0 - half_width);
+                    (sinc(t) * sinc(t / half_width)) as f32
+                })
+            })
+            .collect();
+        let channels = channels as usize;
+        Self {
+            channels,
+            kernels,
+            window: vec![0.0; (TRUE_PEAK_HALF_WIDTH - 1) * channels],
+            frames: 0,
+            measured: 0,
+            peak: 0.0,
+        }
+    }
+
+    /// Measure the next interleaved samples
+    pub fn push(&mut self, samples: &[f32]) {
+        self.peak = samples
+            .iter()
+            .fold(self.peak, |peak, sample| peak.max(sample.abs()));
+        self.frames += (samples.len() / self.channels.max(1)) as u64;
+        self.window.extend_from_slice(samples);
+        self.measure();
+    }
+
+    /// Highest level found (linear), looking past the last sample as if silence
+    /// followed
+    pub fn finish(mut self) -> f32 {
+        self.window.extend(std::iter::repeat_n(
+            0.0,
+            TRUE_PEAK_HALF_WIDTH * self.channels,
+        ));
+        self.measure();
+        self.peak
+    }
+
+    /// Look at every point the window has enough samples around, then drop the
+    /// frames no later point needs
+    fn measure(&mut self) {
+        let channels = self.channels;
+        if channels == 0 {
+            return;
+        }
+        let window_frames = self.window.len() / channels;
+        let mut first = 0;
+        while first + TRUE_PEAK_TAPS <= window_frames && self.measured < self.frames {
+            for channel in 0..channels {
+                for kernel in &self.kernels {
+                    let value: f32 = kernel
+                        .iter()
+                        .enumerate()
+                        .map(|(tap, weight)| {
+                            self.window[(first + tap) * channels + channel] * weight
+                        })
+                        .sum();
+                    self.peak = self.peak.max(value.abs());
+                }
+            }
+            first += 1;
+            self.measured += 1;
+        }
+        self.window.drain(..first * channels);
+    }
+}
+
+/// [`AudioArc::integrated_loudness`] measured a block at a time, for audio that
+/// never exists all at once, such as a render streamed to disk.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    sample_rate: u32,
+    filters: Vec<KWeighting>,
+    /// Frames in each 100 ms step
+    step: usize,
+    /// Mean square of the K-weighted signal per channel, in complete steps
+    step_power: Vec<f64>,
+    /// Power of the step being filled, per channel
+    current: Vec<f64>,
+    /// Frames in the step being filled
+    filled: usize,
+    frames: usize,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            filters: vec![KWeighting::new(sample_rate); channels as usize],
+            step: sample_rate as usize / 10,
+            step_power: Vec::new(),
+            current: vec![0.0; channels as usize],
+            filled: 0,
+            frames: 0,
+        }
+    }
+
+    /// Measure the next interleaved samples
+    pub fn push(&mut self, samples: &[f32]) {
+        let channels = self.filters.len();
+        if channels == 0 || self.step == 0 {
+            return;
+        }
+        for frame in samples.chunks_exact(channels) {
+            for ((power, filter), &sample) in
+                self.current.iter_mut().zip(&mut self.filters).zip(frame)
+            {
+                let weighted = filter.process(sample as f64);
+                *power += weighted * weighted;
+            }
+            self.frames += 1;
+            self.filled += 1;
+            if self.filled == self.step {
+                self.step_power.append(&mut self.current);
+                self.current = vec![0.0; channels];
+                self.filled = 0;
+            }
+        }
+    }
+
+    /// Integrated loudness in LUFS of everything pushed, `-inf` for audio shorter
+    /// than one block or entirely below the gate
+    pub fn finish(self) -> f32 {
+        let channels = self.filters.len();
+        let block = self.sample_rate as usize * 4 / 10;
+        if block == 0 || self.frames < block {
+            return f32::NEG_INFINITY;
+        }
+        let steps = self.step_power.len() / channels;
+        let step_power = &self.step_power;
+
+        // Each 400 ms block spans four steps, overlapping the next by 75%
+        let weights: Vec<f64> = (0..channels)
+            .map(|channel| match (channels, channel) {
+                (6, 3) => 0.0,
+                (6, 4 | 5) => 1.41,
+                _ => 1.0,
+            })
+            .collect();
+        let blocks: Vec<f64> = (0..=steps.saturating_sub(4))
+            .filter(|_| steps >= 4)
+            .map(|first| {
+                (0..channels)
+                    .map(|channel| {
+                        let power: f64 = (first..first + 4)
+                            .map(|s| step_power[s * channels + channel])
+                            .sum();
+                        weights[channel] * power / block as f64
+                    })
+                    .sum()
+            })
+            .collect();
+
+        let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+        let gated_mean = |threshold: f64| {
+            let gated: Vec<f64> = blocks
+                .iter()
+                .copied()
+                .filter(|&power| loudness(power) > threshold)
+                .collect();
+            (!gated.is_empty()).then(|| gated.iter().sum::<f64>() / gated.len() as f64)
+        };
+
+        const ABSOLUTE_GATE: f64 = -70.0;
+        const RELATIVE_GATE: f64 = -10.0;
+        let Some(ungated) = gated_mean(ABSOLUTE_GATE) else {
+            return f32::NEG_INFINITY;
+        };
+        let threshold = (loudness(ungated) + RELATIVE_GATE).max(ABSOLUTE_GATE);
+        gated_mean(threshold).map_or(f32::NEG_INFINITY, |power| loudness(power) as f32)
+    }
+}
+
+/// The two-stage K-weighting filter of ITU-R BS.1770: a high shelf modelling the
+/// head, followed by a high-pass
+#[derive(Debug, Clone, Copy)]
+struct KWeighting {
+    stages: [Biquad; 2],
+}
+
+impl KWeighting {
+    /// Coefficients derived for `sample_rate`, matching the standard's 48 kHz ones
+    fn new(sample_rate: u32) -> Self {
+        let rate = sample_rate as f64;
+
+        let (f0, gain_db, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let vh = 10f64.powf(gain_db / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        let (f0, q) = (38.13547087602444, 0.5003270373238773);
+        let k = (std::f64::consts::PI * f0 / rate).tan();
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass = Biquad::new(
+            [1.0, -2.0, 1.0],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        Self {
+            stages: [shelf, high_pass],
+        }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.stages
+            .iter_mut()
+            .fold(sample, |sample, stage| stage.process(sample))
+    }
+}
+
+/// Direct form II transposed biquad with a normalized `a0`
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self {
+            b,
+            a,
+            state: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_peak_and_rms() {
+        let audio = AudioArc::new(vec![1.0, -1.0, 1.0, -1.0], 48000, 1);
+        assert_eq!(audio.peak(), 1.0);
+        assert_eq!(audio.rms(), 1.0);
+
+        let square = AudioArc::new(vec![0.5, -0.5], 48000, 2);
+        assert_eq!(square.rms(), 0.5);
+    }
+
+    #[test]
+    fn test_empty_audio() {
+        let audio = AudioArc::new(Vec::new(), 48000, 2);
+        assert_eq!(audio.peak(), 0.0);
+        assert_eq!(audio.rms(), 0.0);
+        assert!(audio.normalize(-1.0).is_empty());
+        assert!(audio.remove_dc_offset().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_to_target() {
+        let audio = AudioArc::new(vec![0.1, -0.2, 0.05], 48000, 1);
+        let normalized = audio.normalize(-6.0);
+
+        assert!((gain_to_db(normalized.peak()) + 6.0).abs() < 1e-4);
+        // Relative levels are preserved
+        assert!((normalized.samples()[0] / normalized.samples()[1] + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_normalize_silence_is_unchanged() {
+        let audio = AudioArc::new(vec![0.0; 8], 48000, 2);
+        assert_eq!(audio.normalize(0.0).samples(), &[0.0; 8]);
+    }
+
+    #[test]
+    fn test_remove_dc_offset_per_channel() {
+        // Left sits at +0.5, right at -0.25
+        let audio = AudioArc::new(vec![0.6, -0.2, 0.4, -0.3], 48000, 2);
+        let centered = audio.remove_dc_offset();
+
+        let expected = [0.1, 0.05, -0.1, -0.05];
+        for (sample, expected) in centered.samples().iter().zip(expected) {
+            assert!((sample - expected).abs() < 1e-6);
+        }
+    }
+
+    fn sine(amplitude: f32, seconds: f32, sample_rate: u32, channels: u16) -> AudioArc {
+        let frames = (seconds * sample_rate as f32) as usize;
+        let samples = (0..frames)
+            .flat_map(|frame| {
+                let t = frame as f32 / sample_rate as f32;
+                let value = amplitude * (t * 997.0 * std::f32::consts::TAU).sin();
+                std::iter::repeat_n(value, channels as usize)
+            })
+            .collect();
+        AudioArc::new(samples, sample_rate, channels)
+    }
+
+    #[test]
+    fn test_integrated_loudness_of_reference_tones() {
+        // A full-scale 1 kHz tone in one channel reads -3.01 LUFS, whatever the rate
+        for rate in [44100, 48000, 96000] {
+            let loudness = sine(1.0, 2.0, rate, 1).integrated_loudness();
+            assert!((loudness + 3.01).abs() < 0.1, "{rate} Hz: {loudness}");
+        }
+
+        // Both channels of a -20 dBFS stereo tone add up to -20 LUFS
+        let loudness = sine(0.1, 2.0, 48000, 2).integrated_loudness();
+        assert!((loudness + 20.0).abs() < 0.1, "{loudness}");
+    }
+
+    #[test]
+    fn test_integrated_loudness_gates_silence() {
+        // Trailing silence is gated out instead of lowering the reading by the 4.8 dB
+        // an ungated average would; only blocks straddling the end still count
+        let tone = sine(0.1, 2.0, 48000, 2);
+        let mut samples = tone.samples().to_vec();
+        samples.extend(std::iter::repeat_n(0.0, 48000 * 2 * 4));
+        let padded = AudioArc::new(samples, 48000, 2);
+        let difference = padded.integrated_loudness() - tone.integrated_loudness();
+        assert!(difference.abs() < 0.5, "{difference}");
+
+        assert_eq!(
+            AudioArc::new(vec![0.0; 96000], 48000, 2).integrated_loudness(),
+            f32::NEG_INFINITY
+        );
+        assert_eq!(
+            sine(1.0, 0.2, 48000, 1).integrated_loudness(),
+            f32::NEG_INFINITY
+        );
+    }
+
+    #[test]
+    fn test_meters_match_whole_buffer_measurements() {
+        let mut samples = sine(0.3, 1.5, 48000, 2).samples().to_vec();
+        samples.extend([0.0, 0.0, 0.9, -0.9]);
+        let audio = AudioArc::new(samples, 48000, 2);
+
+        let mut loudness = LoudnessMeter::new(48000, 2);
+        let mut true_peak = TruePeakMeter::new(2);
+        for block in audio.samples().chunks(2 * 1000 + 6) {
+            loudness.push(block);
+            true_peak.push(block);
+        }
+        assert_eq!(loudness.finish(), audio.integrated_loudness());
+        assert_eq!(true_peak.finish(), audio.true_peak());
+    }
+
+    #[test]
+    fn test_true_peak_finds_peaks_between_samples() {
+        // A quarter of the sample rate, sampled 45 degrees off its peaks
+        let samples = (0..4800)
+            .map(|i| (std::f32::consts::FRAC_PI_2 * i as f32 + std::f32::consts::FRAC_PI_4).sin())
+            .collect();
+        let audio = AudioArc::new(samples, 48000, 1);
+        assert!((gain_to_db(audio.peak()) + 3.01).abs() < 0.01);
+        let true_peak = gain_to_db(audio.true_peak());
+        assert!(true_peak.abs() < 0.2, "{true_peak}");
+
+        // Never below the sample peak
+        let click = AudioArc::new(vec![0.0, 0.0, 0.5, 0.0, 0.0], 48000, 1);
+        assert!(click.true_peak() >= 0.5);
+        assert_eq!(AudioArc::new(Vec::new(), 48000, 2).true_peak(), 0.0);
+    }
+
+    #[test]
+    fn test_analysis_respects_slices() {
+        let audio = AudioArc::new(vec![0.9, 0.1, 0.2], 48000, 1);
+        assert_eq!(audio.slice(1..).peak(), 0.2);
+    }
+}
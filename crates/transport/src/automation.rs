@@ -0,0 +1,320 @@
+//! Automation lanes: a track parameter's value drawn over the arrangement.
+//!
+//! Lanes are kept with their track and saved with the project. Playback and
+//! renders follow a track's `"volume"` and `"pan"` lanes through a
+//! [`TrackAutomation`], which places them in sample space like clips are.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{TempoCurve, Track, track_with_ancestors};
+
+/// Parameter id of a lane drawing the track's volume, as a linear gain
+pub const VOLUME_PARAMETER: &str = "volume";
+/// Parameter id of a lane drawing the track's pan, from -1.0 left to 1.0 right
+pub const PAN_PARAMETER: &str = "pan";
+
+/// How sharply an exponential curve bends; higher waits longer before moving
+const EXPONENTIAL_BEND: f32 = 4.0;
+
+/// How the value moves from one point to the next.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AutomationCurve {
+    /// Hold the value until the next point
+    Step,
+    /// Change slowly at first and quickly towards the next point
+    Exponential,
+    /// A straight line to the next point. Curves this version doesn't know, from
+    /// projects saved by a newer one, are read as this.
+    #[default]
+    #[serde(other)]
+    Linear,
+}
+
+/// A value at a position in a lane
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AutomationPoint {
+    pub tick: u64,
+    pub value: f32,
+    /// Shape of the line from here to the next point
+    #[serde(default, skip_serializing_if = "is_linear")]
+    pub curve: AutomationCurve,
+}
+
+fn is_linear(curve: &AutomationCurve) -> bool {
+    *curve == AutomationCurve::Linear
+}
+
+/// The points drawn for one parameter of a track.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AutomationLane {
+    /// Id of the automated parameter, such as `"volume"` or `"pan"`
+    pub parameter: String,
+    /// Points in tick order
+    #[serde(default)]
+    pub points: Vec<AutomationPoint>,
+}
+
+impl AutomationCurve {
+    /// Value a fraction `t` (0.0 to 1.0) of the way from `from` to `to`
+    pub fn interpolate(self, from: f32, to: f32, t: f32) -> f32 {
+        let shape = match self {
+            Self::Step => 0.0,
+            Self::Linear => t,
+            Self::Exponential => (EXPONENTIAL_BEND * t).exp_m1() / EXPONENTIAL_BEND.exp_m1(),
+        };
+        from + (to - from) * shape
+    }
+}
+
+/// Value at `position` along points given as `(position, value, curve)`, in
+/// order: each point's value held before the first and after the last, and the
+/// last of several points at the same position winning from there on.
+fn value_along<P>(
+    points: &[P],
+    position: f64,
+    point: impl Fn(&P) -> (f64, f32, AutomationCurve),
+) -> Option<f32> {
+    let next = points.partition_point(|p| point(p).0 <= position);
+    let Some(previous) = next.checked_sub(1) else {
+        return points.first().map(|p| point(p).1);
+    };
+    let (from_position, from, curve) = point(&points[previous]);
+    let Some((to_position, to, _)) = points.get(next).map(&point) else {
+        return Some(from);
+    };
+    let t = (position - from_position) / (to_position - from_position);
+    Some(curve.interpolate(from, to, t as f32))
+}
+
+impl AutomationLane {
+    pub fn new(parameter: impl Into<String>) -> Self {
+        Self {
+            parameter: parameter.into(),
+            points: Vec::new(),
+        }
+    }
+
+    /// Value drawn at `tick`, or `None` for a lane without points
+    pub fn value_at(&self, tick: f64) -> Option<f32> {
+        value_along(&self.points, tick, |point| {
+            (point.tick as f64, point.value, point.curve)
+        })
+    }
+
+    /// Add a point, after any others already at its tick. Returns its index.
+    pub fn add_point(&mut self, point: AutomationPoint) -> usize {
+        let index = self.points.partition_point(|p| p.tick <= point.tick);
+        self.points.insert(index, point);
+        index
+    }
+
+    /// Move the point at `index` to `tick` and `value`, keeping its curve.
+    /// Returns its new index, or `None` if there's no such point.
+    pub fn move_point(&mut self, index: usize, tick: u64, value: f32) -> Option<usize> {
+        let mut point = self.remove_point(index)?;
+        point.tick = tick;
+        point.value = value;
+        Some(self.add_point(point))
+    }
+
+    /// Take the point at `index` out of the lane
+    pub fn remove_point(&mut self, index: usize) -> Option<AutomationPoint> {
+        (index < self.points.len()).then(|| self.points.remove(index))
+    }
+
+    /// The lane placed in sample space at `sample_rate`, with every value
+    /// multiplied by `scale`. `None` for a lane without points.
+    pub fn to_envelope(
+        &self,
+        tempo: &TempoCurve,
+        sample_rate: u32,
+        scale: f32,
+    ) -> Option<AutomationEnvelope> {
+        if self.points.is_empty() {
+            return None;
+        }
+        let points = self
+            .points
+            .iter()
+            .map(|point| EnvelopePoint {
+                sample: tempo.ticks_to_samples(point.tick as f64, sample_rate) as u64,
+                value: point.value * scale,
+                curve: point.curve,
+            })
+            .collect();
+        Some(AutomationEnvelope { points })
+    }
+
+    /// Put the points in tick order and drop ones without a usable value, as
+    /// after reading them from a file.
+    ///
+    /// Points at the same tick keep their order, so a jump can be drawn with two.
+    pub fn normalize(&mut self) {
+        self.points.retain(|point| point.value.is_finite());
+        self.points.sort_by_key(|point| point.tick);
+    }
+}
+
+/// A lane placed in sample space, for following it while mixing.
+///
+/// Never empty. Every curve is a blend of its two points' values, so scaling
+/// the values scales the whole line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AutomationEnvelope {
+    points: Vec<EnvelopePoint>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct EnvelopePoint {
+    sample: u64,
+    value: f32,
+    curve: AutomationCurve,
+}
+
+impl AutomationEnvelope {
+    /// Value at timeline sample `sample`
+    pub fn value_at(&self, sample: u64) -> f32 {
+        value_along(&self.points, sample as f64, |point| {
+            (point.sample as f64, point.value, point.curve)
+        })
+        .unwrap_or_default()
+    }
+}
+
+/// A track's volume and pan lanes as playback and renders follow them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackAutomation {
+    /// Gain to mix at instead of the track's effective volume. Already includes
+    /// the volume of the folders the track is in; folders' own lanes aren't
+    /// followed.
+    pub volume: Option<AutomationEnvelope>,
+    /// Pan to use instead of the track's
+    pub pan: Option<AutomationEnvelope>,
+}
+
+impl TrackAutomation {
+    /// `track`'s lanes placed in sample space at `sample_rate`
+    pub fn new(tracks: &[Track], track: &Track, tempo: &TempoCurve, sample_rate: u32) -> Self {
+        let lane = |parameter: &str| {
+            track
+                .automation
+                .iter()
+                .find(|lane| lane.parameter == parameter)
+        };
+        let folder_volume = track_with_ancestors(tracks, track)
+            .skip(1)
+            .map(|folder| folder.volume)
+            .product();
+        Self {
+            volume: lane(VOLUME_PARAMETER)
+                .and_then(|lane| lane.to_envelope(tempo, sample_rate, folder_volume)),
+            pan: lane(PAN_PARAMETER).and_then(|lane| lane.to_envelope(tempo, sample_rate, 1.0)),
+        }
+    }
+
+    /// Gain at `sample`, or `volume` where there's no volume lane
+    pub fn volume_at(&self, sample: u64, volume: f32) -> f32 {
+        self.volume
+            .as_ref()
+            .map_or(volume, |envelope| envelope.value_at(sample))
+    }
+
+    /// Pan at `sample`, or `pan` where there's no pan lane
+    pub fn pan_at(&self, sample: u64, pan: f32) -> f32 {
+        self.pan
+            .as_ref()
+            .map_or(pan, |envelope| envelope.value_at(sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PPQN, TrackId};
+
+    #[test]
+    fn test_normalize_sorts_points_and_drops_unusable_values() {
+        let point = |tick, value| AutomationPoint {
+            tick,
+            value,
+            curve: AutomationCurve::Linear,
+        };
+        let mut lane = AutomationLane::new("pan");
+        lane.points = vec![point(960, 0.5), point(0, f32::NAN), point(0, -1.0)];
+        lane.normalize();
+        assert_eq!(lane.points, vec![point(0, -1.0), point(960, 0.5)]);
+    }
+
+    #[test]
+    fn test_values_follow_each_points_curve() {
+        let point = |tick, value, curve| AutomationPoint { tick, value, curve };
+        let mut lane = AutomationLane::new("volume");
+        assert_eq!(lane.value_at(0.0), None);
+        lane.points = vec![
+            point(100, 0.0, AutomationCurve::Linear),
+            point(200, 1.0, AutomationCurve::Step),
+            point(300, 0.5, AutomationCurve::Exponential),
+            point(400, 1.5, AutomationCurve::Linear),
+            // A jump back down at the same tick
+            point(400, 0.0, AutomationCurve::Linear),
+        ];
+
+        assert_eq!(lane.value_at(0.0), Some(0.0));
+        assert_eq!(lane.value_at(150.0), Some(0.5));
+        assert_eq!(lane.value_at(299.0), Some(1.0));
+        assert_eq!(lane.value_at(300.0), Some(0.5));
+        // Exponential starts slow, then catches up
+        let halfway = lane.value_at(350.0).unwrap();
+        assert!(halfway > 0.5 && halfway < 0.75);
+        assert_eq!(lane.value_at(400.0), Some(0.0));
+        assert_eq!(lane.value_at(1000.0), Some(0.0));
+    }
+
+    #[test]
+    fn test_points_are_kept_in_tick_order_when_edited() {
+        let point = |tick, value| AutomationPoint {
+            tick,
+            value,
+            curve: AutomationCurve::Step,
+        };
+        let mut lane = AutomationLane::new("pan");
+        assert_eq!(lane.add_point(point(960, 0.5)), 0);
+        assert_eq!(lane.add_point(point(0, -1.0)), 0);
+        assert_eq!(lane.add_point(point(960, 1.0)), 2);
+
+        assert_eq!(lane.move_point(0, 2000, 0.0), Some(2));
+        assert_eq!(lane.points[2], point(2000, 0.0));
+        assert_eq!(lane.move_point(3, 0, 0.0), None);
+        assert_eq!(lane.remove_point(0), Some(point(960, 0.5)));
+        assert_eq!(lane.points, vec![point(960, 1.0), point(2000, 0.0)]);
+    }
+
+    #[test]
+    fn test_track_automation_includes_folder_volume() {
+        let mut folder = Track::new_folder(TrackId(0), "Drums".to_string());
+        folder.volume = 0.5;
+        let mut track = Track::new(TrackId(1), "Kick".to_string());
+        track.parent = Some(TrackId(0));
+        let mut volume = AutomationLane::new(VOLUME_PARAMETER);
+        volume.add_point(AutomationPoint {
+            tick: 0,
+            value: 0.0,
+            curve: AutomationCurve::Linear,
+        });
+        volume.add_point(AutomationPoint {
+            tick: PPQN,
+            value: 1.0,
+            curve: AutomationCurve::Linear,
+        });
+        track.automation.push(volume);
+        let tracks = vec![folder, track];
+
+        // A beat is half a second at 120 BPM
+        let automation =
+            TrackAutomation::new(&tracks, &tracks[1], &TempoCurve::constant(120.0), 1000);
+        assert_eq!(automation.volume_at(250, 1.0), 0.25);
+        assert_eq!(automation.volume_at(5000, 1.0), 0.5);
+        assert_eq!(automation.pan_at(250, -0.5), -0.5);
+    }
+}
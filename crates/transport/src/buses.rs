@@ -0,0 +1,71 @@
+//! Aux buses: tracks that mix what other tracks send them, so one reverb can
+//! serve many tracks.
+//!
+//! A bus holds no clips of its own. Each track's `sends` name the buses it feeds
+//! and how much of its audio goes to each, taken after its effects and either
+//! before or after its volume. The bus then goes through its own effects, volume
+//! and pan like any track. Buses can't send on to other buses, so there are no
+//! loops to break.
+
+use crate::{Track, TrackId};
+
+/// Audio a track feeds to a bus, on top of what it plays itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackSend {
+    /// The bus track it goes to
+    pub bus: TrackId,
+    /// Linear gain of the send (0.0 = nothing, 1.0 = unity)
+    pub level: f32,
+    /// Whether the send is taken before the track's volume, so the bus keeps
+    /// getting it while the track is faded down. Pan applies to neither.
+    pub pre_fader: bool,
+}
+
+/// Each of `track`'s sends that reaches a bus among `mixed`, the tracks being
+/// mixed, with the bus's index in `mixed`.
+///
+/// Sends to tracks that aren't buses or aren't being mixed, such as a muted bus,
+/// are left out, as are sends from buses and folders.
+pub fn routed_sends<'a>(
+    track: &'a Track,
+    mixed: &'a [&Track],
+) -> impl Iterator<Item = (usize, &'a TrackSend)> {
+    let sends = if track.is_bus || track.is_folder {
+        &[][..]
+    } else {
+        &track.sends[..]
+    };
+    sends.iter().filter_map(|send| {
+        let bus = mixed.iter().position(|t| t.is_bus && t.id == send.bus)?;
+        Some((bus, send))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sends_reach_only_buses_being_mixed() {
+        let reverb = Track::new_bus(TrackId(0), "Reverb".to_string());
+        let bass = Track::new(TrackId(2), "Bass".to_string());
+        let mut vocal = Track::new(TrackId(3), "Vocal".to_string());
+        let send = |bus| TrackSend {
+            bus: TrackId(bus),
+            level: 0.5,
+            pre_fader: false,
+        };
+        vocal.sends = vec![send(1), send(2), send(0), send(9)];
+
+        // Bus 1 is muted, so it's not among the mixed tracks
+        let mixed = [&reverb, &bass, &vocal];
+        let routed: Vec<(usize, u64)> = routed_sends(&vocal, &mixed)
+            .map(|(bus, send)| (bus, send.bus.0))
+            .collect();
+        assert_eq!(routed, vec![(0, 0)]);
+
+        let mut reverb = reverb.clone();
+        reverb.sends = vec![send(0)];
+        assert_eq!(routed_sends(&reverb, &[&reverb]).count(), 0);
+    }
+}
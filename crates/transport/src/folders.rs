@@ -0,0 +1,144 @@
+//! Folder tracks, which group other tracks under them.
+//!
+//! A track's `parent` names the folder it sits in. Folders hold no clips of their
+//! own; instead their mute, solo and volume apply to everything nested inside them.
+
+use crate::{Track, TrackId};
+
+/// Mix state of a track once the folders it's nested in are taken into account.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EffectiveMix {
+    /// The track and every folder above it are enabled
+    pub enabled: bool,
+    /// The track or a folder above it is soloed, or it's a bus: soloing tracks
+    /// doesn't silence the buses they send to
+    pub soloed: bool,
+    /// The track's volume multiplied by the volume of every folder above it
+    pub volume: f32,
+}
+
+impl EffectiveMix {
+    /// Whether the track is heard, given whether anything in the session is soloed
+    pub fn audible(&self, any_soloed: bool) -> bool {
+        self.enabled && (!any_soloed || self.soloed)
+    }
+}
+
+/// The track followed by the folders it's nested in, innermost first.
+///
+/// Stops at a missing parent, and after `tracks.len()` steps so a corrupt
+/// parent cycle can't loop forever.
+pub fn track_with_ancestors<'a>(
+    tracks: &'a [Track],
+    track: &'a Track,
+) -> impl Iterator<Item = &'a Track> {
+    std::iter::successors(Some(track), |track| {
+        let parent = track.parent.as_ref()?;
+        tracks.iter().find(|t| &t.id == parent)
+    })
+    .take(tracks.len().max(1))
+}
+
+/// Whether `track_id` is `ancestor_id` or nested anywhere inside it
+pub fn is_within(tracks: &[Track], track_id: &TrackId, ancestor_id: &TrackId) -> bool {
+    tracks
+        .iter()
+        .find(|t| &t.id == track_id)
+        .is_some_and(|track| track_with_ancestors(tracks, track).any(|t| &t.id == ancestor_id))
+}
+
+/// Whether any track is soloed, which silences the tracks that aren't.
+///
+/// Pass it to [`EffectiveMix::audible`], so playback and renders agree on what
+/// is heard.
+pub fn any_soloed(tracks: &[Track]) -> bool {
+    tracks.iter().any(|track| track.solo)
+}
+
+/// Effective mix of every track, in the same order as `tracks`.
+pub fn effective_mix(tracks: &[Track]) -> Vec<EffectiveMix> {
+    tracks
+        .iter()
+        .map(|track| {
+            track_with_ancestors(tracks, track).fold(
+                EffectiveMix {
+                    enabled: true,
+                    soloed: false,
+                    volume: 1.0,
+                },
+                |mix, t| EffectiveMix {
+                    enabled: mix.enabled && t.enabled,
+                    soloed: mix.soloed || t.solo || t.is_bus,
+                    volume: mix.volume * t.volume,
+                },
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracks() -> Vec<Track> {
+        let mut drums = Track::new_folder(TrackId(0), "Drums".to_string());
+        drums.volume = 0.5;
+        let mut kick = Track::new(TrackId(1), "Kick".to_string());
+        kick.parent = Some(TrackId(0));
+        kick.volume = 0.8;
+        let mut snare = Track::new(TrackId(2), "Snare".to_string());
+        snare.parent = Some(TrackId(0));
+        let bass = Track::new(TrackId(3), "Bass".to_string());
+        vec![drums, kick, snare, bass]
+    }
+
+    #[test]
+    fn test_folder_volume_and_mute_apply_to_children() {
+        let mut tracks = tracks();
+        let mix = effective_mix(&tracks);
+        assert_eq!(mix[1].volume, 0.4);
+        assert_eq!(mix[2].volume, 0.5);
+        assert_eq!(mix[3].volume, 1.0);
+
+        tracks[0].enabled = false;
+        let mix = effective_mix(&tracks);
+        assert!(!mix[1].enabled && !mix[2].enabled);
+        assert!(mix[3].enabled);
+    }
+
+    #[test]
+    fn test_soloing_a_folder_solos_its_children() {
+        let mut tracks = tracks();
+        tracks[0].solo = true;
+        let audible: Vec<bool> = effective_mix(&tracks)
+            .iter()
+            .map(|mix| mix.audible(true))
+            .collect();
+        assert_eq!(audible, vec![true, true, true, false]);
+    }
+
+    #[test]
+    fn test_buses_are_heard_through_a_solo() {
+        let mut tracks = tracks();
+        tracks.push(Track::new_bus(TrackId(4), "Reverb".to_string()));
+        tracks[3].solo = true;
+        let audible: Vec<bool> = effective_mix(&tracks)
+            .iter()
+            .map(|mix| mix.audible(true))
+            .collect();
+        assert_eq!(audible, vec![false, false, false, true, true]);
+
+        // Muting a bus still silences it
+        tracks[4].enabled = false;
+        assert!(!effective_mix(&tracks)[4].audible(true));
+    }
+
+    #[test]
+    fn test_parent_cycle_terminates() {
+        let mut tracks = tracks();
+        tracks[0].parent = Some(TrackId(1));
+        assert!(is_within(&tracks, &TrackId(0), &TrackId(1)));
+        assert!(!is_within(&tracks, &TrackId(3), &TrackId(0)));
+        assert_eq!(effective_mix(&tracks).len(), 4);
+    }
+}
@@ -0,0 +1,96 @@
+//! Allocation of track and clip ids.
+
+use crate::{Track, TrackId};
+
+/// Identifies a clip within a project.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ClipId(pub u64);
+
+/// Hands out track and clip ids that are never reused within a project.
+///
+/// Ids only ever count up, so removing a track or clip and adding another can't
+/// produce a duplicate. The next ids are saved with the project so this holds
+/// across save and reload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdAllocator {
+    next_track_id: u64,
+    next_clip_id: u64,
+}
+
+impl IdAllocator {
+    pub fn new(next_track_id: u64, next_clip_id: u64) -> Self {
+        Self {
+            next_track_id,
+            next_clip_id,
+        }
+    }
+
+    /// An allocator whose ids come after every track and clip in `tracks`
+    pub fn for_tracks(tracks: &[Track]) -> Self {
+        let mut ids = Self::default();
+        ids.reserve_tracks(tracks);
+        ids
+    }
+
+    /// The id the next allocated track will get
+    pub fn next_track_id(&self) -> u64 {
+        self.next_track_id
+    }
+
+    /// The id the next allocated clip will get
+    pub fn next_clip_id(&self) -> u64 {
+        self.next_clip_id
+    }
+
+    pub fn track_id(&mut self) -> TrackId {
+        let id = self.next_track_id;
+        self.next_track_id += 1;
+        TrackId(id)
+    }
+
+    pub fn clip_id(&mut self) -> ClipId {
+        let id = self.next_clip_id;
+        self.next_clip_id += 1;
+        ClipId(id)
+    }
+
+    /// Make sure ids already used by `tracks` and their clips are never handed out
+    pub fn reserve_tracks(&mut self, tracks: &[Track]) {
+        for track in tracks {
+            self.next_track_id = self.next_track_id.max(track.id.0 + 1);
+            for clip in track.clips() {
+                self.next_clip_id = self.next_clip_id.max(clip.id.0 + 1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids_count_up() {
+        let mut ids = IdAllocator::new(3, 10);
+        assert_eq!(ids.track_id(), TrackId(3));
+        assert_eq!(ids.track_id(), TrackId(4));
+        assert_eq!(ids.clip_id(), ClipId(10));
+        assert_eq!((ids.next_track_id(), ids.next_clip_id()), (5, 11));
+    }
+
+    #[test]
+    fn test_reserve_skips_existing_ids() {
+        let tracks = vec![
+            Track::new(TrackId(7), "A".to_string()),
+            Track::new(TrackId(2), "B".to_string()),
+        ];
+        let mut ids = IdAllocator::new(4, 0);
+        ids.reserve_tracks(&tracks);
+        assert_eq!(ids.track_id(), TrackId(8));
+
+        // Never moves backwards
+        let mut ids = IdAllocator::new(20, 0);
+        ids.reserve_tracks(&tracks);
+        assert_eq!(ids.track_id(), TrackId(20));
+    }
+}
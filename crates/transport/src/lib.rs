@@ -1,12 +1,49 @@
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
 use std::sync::Arc;
 
-use rubato::{
-    Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
+use daw_dsp::Effect;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+mod analysis;
+mod automation;
+mod buses;
+mod folders;
+mod ids;
+mod midi;
+mod planar;
+mod resample;
+mod sampler;
+mod sampler_voices;
+mod source;
+mod tempo;
+
+pub use analysis::{LoudnessMeter, TruePeakMeter, db_to_gain, gain_to_db};
+pub use automation::{
+    AutomationCurve, AutomationEnvelope, AutomationLane, AutomationPoint, PAN_PARAMETER,
+    TrackAutomation, VOLUME_PARAMETER,
 };
+pub use buses::{TrackSend, routed_sends};
+pub use folders::{EffectiveMix, any_soloed, effective_mix, is_within, track_with_ancestors};
+pub use ids::{ClipId, IdAllocator};
+pub use midi::{Note, NoteList};
+pub use planar::{deinterleave, deinterleave_into, interleave, interleave_into};
+pub use resample::{
+    RESAMPLE_CHUNK_FRAMES, StreamResampler, resample_audio, resample_audio_arc,
+    resample_audio_arc_with_progress,
+};
+pub use sampler::{Adsr, MAX_POLYPHONY, Sampler, SamplerMap};
+pub use sampler_voices::{LiveNote, SamplerNote, SamplerVoices, sampler_notes};
+pub use source::{ClipSource, MusicalKey};
+pub use tempo::TempoCurve;
 
 /// Pulses Per Quarter Note - defines timing resolution
 pub const PPQN: u64 = 960;
 
+/// Minimum number of waveform buckets computed per parallel task
+const WAVEFORM_BUCKETS_PER_TASK: usize = 256;
+
 /// Legacy audio buffer type - use AudioArc for new code
 #[derive(Debug, Clone)]
 pub struct AudioBuffer {
@@ -26,13 +63,17 @@ pub struct AudioBuffer {
 /// # Memory Layout
 ///
 /// ```text
-/// AudioArc (24 bytes on stack)
+/// AudioArc (40 bytes on stack)
 /// ├─ samples: Arc<[f32]> (16 bytes) ────> Heap: [f32; N]
+/// ├─ start: usize (8 bytes)    ─┐ window into the shared samples,
+/// ├─ len: usize (8 bytes)      ─┘ in interleaved samples
 /// ├─ sample_rate: u32 (4 bytes)
 /// └─ channels: u16 (2 bytes)
 /// ```
 ///
 /// Cloning an `AudioArc` only increments the reference count, making it very cheap.
+/// [`slice`](AudioArc::slice) creates a view of a frame range that shares the same
+/// underlying samples, so regions can be referenced without copying.
 ///
 /// # Examples
 ///
@@ -56,6 +97,10 @@ pub struct AudioArc {
     /// Raw interleaved samples stored in a reference-counted slice.
     /// This is the actual audio data that can be shared between multiple AudioArc instances.
     samples: Arc<[f32]>,
+    /// Index of the first sample of this view in `samples`
+    start: usize,
+    /// Number of samples in this view
+    len: usize,
     /// Sample rate in Hz (e.g., 44100, 48000)
     sample_rate: u32,
     /// Number of interleaved channels (e.g., 1 for mono, 2 for stereo)
@@ -92,8 +137,11 @@ impl AudioArc {
             0,
             "samples.len() must be divisible by channels"
         );
+        let len = samples.len();
         Self {
             samples: Arc::from(samples),
+            start: 0,
+            len,
             sample_rate,
             channels,
         }
@@ -114,25 +162,80 @@ impl AudioArc {
             0,
             "samples.len() must be divisible by channels"
         );
+        let len = samples.len();
         Self {
             samples,
+            start: 0,
+            len,
             sample_rate,
             channels,
         }
     }
 
+    /// Create a view of a range of frames that shares the same sample data.
+    ///
+    /// The range is relative to this view, so slicing a slice works as expected.
+    /// No samples are copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or its start is greater than its end.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daw_transport::AudioArc;
+    ///
+    /// let samples = vec![0.0, 0.1, 1.0, 1.1, 2.0, 2.1]; // 3 stereo frames
+    /// let audio = AudioArc::new(samples, 44100, 2);
+    ///
+    /// let tail = audio.slice(1..);
+    /// assert_eq!(tail.frames(), 2);
+    /// assert_eq!(tail.samples(), &[1.0, 1.1, 2.0, 2.1]);
+    /// ```
+    pub fn slice(&self, frame_range: impl RangeBounds<usize>) -> Self {
+        let frames = self.frames();
+        let start = match frame_range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match frame_range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => frames,
+        };
+        assert!(start <= end, "slice start {} is after end {}", start, end);
+        assert!(
+            end <= frames,
+            "slice end {} is out of bounds ({} frames)",
+            end,
+            frames
+        );
+
+        let channels = self.channels as usize;
+        Self {
+            samples: self.samples.clone(),
+            start: self.start + start * channels,
+            len: (end - start) * channels,
+            sample_rate: self.sample_rate,
+            channels: self.channels,
+        }
+    }
+
     /// Get a slice of all interleaved samples.
     ///
     /// For stereo audio, the format is [L, R, L, R, ...].
     #[inline]
     pub fn samples(&self) -> &[f32] {
-        &self.samples
+        &self.samples[self.start..self.start + self.len]
     }
 
     /// Get a reference to the inner `Arc<[f32]>` for advanced use cases.
     ///
     /// This allows access to the underlying Arc for checking reference counts
-    /// or other Arc-specific operations.
+    /// or other Arc-specific operations. For a slice, this is the whole shared
+    /// buffer, not just the frames in view.
     pub fn samples_arc(&self) -> &Arc<[f32]> {
         &self.samples
     }
@@ -154,19 +257,19 @@ impl AudioArc {
     /// For stereo with 4 samples, this returns 2 frames.
     #[inline]
     pub fn frames(&self) -> usize {
-        self.samples.len() / self.channels as usize
+        self.len / self.channels as usize
     }
 
     /// Get the total number of samples (frames * channels).
     #[inline]
     pub fn len(&self) -> usize {
-        self.samples.len()
+        self.len
     }
 
     /// Check if the audio buffer is empty.
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.samples.is_empty()
+        self.len == 0
     }
 
     /// Get the duration in seconds.
@@ -200,7 +303,102 @@ impl AudioArc {
             "channel index out of bounds"
         );
         let channels = self.channels as usize;
-        (0..self.frames()).map(move |frame| self.samples[frame * channels + channel])
+        let samples = self.samples();
+        (0..self.frames()).map(move |frame| samples[frame * channels + channel])
+    }
+
+    /// Copy the samples of this view into one `Vec` per channel.
+    ///
+    /// For consumers that work on planar data, such as resampling and FFT analysis.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daw_transport::AudioArc;
+    ///
+    /// let audio = AudioArc::new(vec![0.0, 1.0, 0.5, 1.5], 44100, 2);
+    /// let planar = audio.planar();
+    /// assert_eq!(planar[0], &[0.0, 0.5]);
+    /// assert_eq!(planar[1], &[1.0, 1.5]);
+    /// ```
+    pub fn planar(&self) -> Vec<Vec<f32>> {
+        deinterleave(self.samples(), self.channels as usize)
+    }
+
+    /// Append the samples of this view to `planar`, one buffer per channel.
+    ///
+    /// Buffers with room for the samples aren't reallocated, so a plugin host can
+    /// fill buffers it set up beforehand from the audio thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `planar` doesn't hold one buffer per channel.
+    pub fn planar_into(&self, planar: &mut [Vec<f32>]) {
+        assert_eq!(
+            planar.len(),
+            self.channels as usize,
+            "need one buffer per channel"
+        );
+        deinterleave_into(self.samples(), planar);
+    }
+
+    /// Copy the samples of this view into one `f64` `Vec` per channel.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daw_transport::AudioArc;
+    ///
+    /// let audio = AudioArc::new(vec![0.0, 1.0, 0.5, 1.5], 44100, 2);
+    /// assert_eq!(audio.planar_f64()[1], &[1.0, 1.5]);
+    /// ```
+    pub fn planar_f64(&self) -> Vec<Vec<f64>> {
+        deinterleave(&self.to_f64(), self.channels as usize)
+    }
+
+    /// Create an `AudioArc` from per-channel sample data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `planar` is empty or the channels differ in length.
+    pub fn from_planar<C: AsRef<[f32]>>(planar: &[C], sample_rate: u32) -> Self {
+        Self::new(interleave(planar), sample_rate, planar.len() as u16)
+    }
+
+    /// Create an `AudioArc` from per-channel `f64` sample data.
+    ///
+    /// Samples are stored as `f32`, so precision beyond that is lost.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `planar` is empty or the channels differ in length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daw_transport::AudioArc;
+    ///
+    /// let audio = AudioArc::from_planar_f64(&[vec![0.1f64, 0.2]], 48000);
+    /// assert_eq!(audio.samples(), &[0.1f32, 0.2]);
+    /// ```
+    pub fn from_planar_f64<C: AsRef<[f64]>>(planar: &[C], sample_rate: u32) -> Self {
+        let samples = interleave(planar).into_iter().map(|s| s as f32).collect();
+        Self::new(samples, sample_rate, planar.len() as u16)
+    }
+
+    /// Create an `AudioArc` from interleaved `f64` samples, stored as `f32`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `channels` is 0 or if `samples.len()` is not divisible by `channels`.
+    pub fn from_f64(samples: &[f64], sample_rate: u32, channels: u16) -> Self {
+        let samples = samples.iter().map(|&s| s as f32).collect();
+        Self::new(samples, sample_rate, channels)
+    }
+
+    /// Copy the samples of this view to interleaved `f64`.
+    pub fn to_f64(&self) -> Vec<f64> {
+        self.samples().iter().map(|&s| s as f64).collect()
     }
 
     /// Resample this audio to a target sample rate.
@@ -236,7 +434,7 @@ impl AudioArc {
     /// for compatibility with old APIs.
     pub fn to_audio_buffer(&self) -> AudioBuffer {
         AudioBuffer {
-            samples: self.samples.to_vec(),
+            samples: self.samples().to_vec(),
             sample_rate: self.sample_rate,
             channels: self.channels,
         }
@@ -261,13 +459,53 @@ impl std::fmt::Debug for AudioArc {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Min/max peaks of a mono mix-down, for drawing waveforms.
+///
+/// Serializable so peaks can be stored alongside a project and reloaded instead of
+/// being recomputed from the audio.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct WaveformData {
     pub peaks: Vec<(f32, f32)>,
     pub samples_per_bucket: usize,
 }
 
 impl WaveformData {
+    /// Hash of the bucket size and peak values.
+    ///
+    /// Uses FNV-1a over the exact bit patterns, so the value is the same across
+    /// builds and platforms and can be stored to verify persisted peaks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use daw_transport::{AudioArc, WaveformData};
+    ///
+    /// let audio = AudioArc::new(vec![0.5, -0.5, 0.25, 0.0], 44100, 1);
+    /// let a = WaveformData::from_audio_arc(&audio, 2);
+    /// let b = WaveformData::from_audio_arc(&audio, 2);
+    /// assert_eq!(a.content_hash(), b.content_hash());
+    /// ```
+    pub fn content_hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let values = [self.samples_per_bucket as u64, self.peaks.len() as u64]
+            .into_iter()
+            .chain(
+                self.peaks
+                    .iter()
+                    .flat_map(|&(min, max)| [min.to_bits() as u64, max.to_bits() as u64]),
+            );
+
+        let mut hash = OFFSET_BASIS;
+        for value in values {
+            for byte in value.to_le_bytes() {
+                hash = (hash ^ byte as u64).wrapping_mul(PRIME);
+            }
+        }
+        hash
+    }
+
     /// Generate waveform data from an `AudioArc`.
     ///
     /// This computes min/max peaks for visualization, downsampling the audio into
@@ -288,90 +526,133 @@ impl WaveformData {
     /// let waveform = WaveformData::from_audio_arc(&audio, 512);
     /// ```
     pub fn from_audio_arc(audio: &AudioArc, samples_per_bucket: usize) -> Self {
-        let frames = audio.frames();
-        let num_buckets = (frames + samples_per_bucket - 1) / samples_per_bucket;
-        let mut peaks = Vec::with_capacity(num_buckets);
-        let channels = audio.channels() as usize;
-        let samples = audio.samples();
-
-        for bucket_idx in 0..num_buckets {
-            let start = bucket_idx * samples_per_bucket;
-            let end = ((bucket_idx + 1) * samples_per_bucket).min(frames);
-
-            let mut min_val: f32 = 0.0;
-            let mut max_val: f32 = 0.0;
-
-            for frame_idx in start..end {
-                // Mix down to mono
-                let mut sum: f32 = 0.0;
-                for ch in 0..channels {
-                    let idx = frame_idx * channels + ch;
-                    if idx < samples.len() {
-                        sum += samples[idx];
-                    }
+        Self::from_interleaved(audio.samples(), audio.channels(), samples_per_bucket)
+    }
+
+    /// Legacy method for generating waveform data from AudioBuffer
+    pub fn from_audio_buffer(buffer: &AudioBuffer, samples_per_bucket: usize) -> Self {
+        Self::from_interleaved(&buffer.samples, buffer.channels, samples_per_bucket)
+    }
+
+    /// Compute peaks for interleaved samples, splitting the buckets across the rayon
+    /// thread pool so long files don't stall loading.
+    fn from_interleaved(samples: &[f32], channels: u16, samples_per_bucket: usize) -> Self {
+        let channels = channels as usize;
+
+        let peaks = samples
+            .par_chunks(samples_per_bucket * channels)
+            .with_min_len(WAVEFORM_BUCKETS_PER_TASK)
+            .map(|bucket| {
+                let mut min_val: f32 = 0.0;
+                let mut max_val: f32 = 0.0;
+
+                for frame in bucket.chunks_exact(channels) {
+                    // Mix down to mono
+                    let mono_sample = frame.iter().sum::<f32>() / channels as f32;
+                    min_val = min_val.min(mono_sample);
+                    max_val = max_val.max(mono_sample);
                 }
-                let mono_sample = sum / channels as f32;
-                min_val = min_val.min(mono_sample);
-                max_val = max_val.max(mono_sample);
-            }
 
-            peaks.push((min_val, max_val));
-        }
+                (min_val, max_val)
+            })
+            .collect();
 
         Self {
             peaks,
             samples_per_bucket,
         }
     }
+}
 
-    /// Legacy method for generating waveform data from AudioBuffer
-    pub fn from_audio_buffer(buffer: &AudioBuffer, samples_per_bucket: usize) -> Self {
-        let samples_per_channel = buffer.samples.len() / buffer.channels as usize;
-        let num_buckets = (samples_per_channel + samples_per_bucket - 1) / samples_per_bucket;
-        let mut peaks = Vec::with_capacity(num_buckets);
-
-        for bucket_idx in 0..num_buckets {
-            let start = bucket_idx * samples_per_bucket;
-            let end = ((bucket_idx + 1) * samples_per_bucket).min(samples_per_channel);
-
-            let mut min_val: f32 = 0.0;
-            let mut max_val: f32 = 0.0;
-
-            for sample_idx in start..end {
-                let mut sum: f32 = 0.0;
-                for ch in 0..buffer.channels as usize {
-                    let idx = sample_idx * buffer.channels as usize + ch;
-                    if idx < buffer.samples.len() {
-                        sum += buffer.samples[idx];
-                    }
-                }
-                let mono_sample = sum / buffer.channels as f32;
-                min_val = min_val.min(mono_sample);
-                max_val = max_val.max(mono_sample);
-            }
+/// What a clip plays: recorded audio, or notes for an instrument.
+#[derive(Debug, Clone)]
+pub enum ClipContent {
+    Audio(AudioArc),
+    Midi(NoteList),
+}
 
-            peaks.push((min_val, max_val));
+impl ClipContent {
+    /// Content and audio offset left after cutting `ticks` off the start
+    fn trim_start(self, audio_offset: u64, ticks: u64) -> (Self, u64) {
+        match self {
+            ClipContent::Audio(audio) => {
+                // Approximate: audio_offset is in samples, and we don't know the tempo here
+                let offset = audio_offset + ticks_to_samples_approx(ticks, audio.sample_rate());
+                (ClipContent::Audio(audio), offset)
+            }
+            ClipContent::Midi(notes) => (ClipContent::Midi(notes.trim_start(ticks)), audio_offset),
         }
+    }
 
-        Self {
-            peaks,
-            samples_per_bucket,
+    /// Content and audio offset for a clip starting at `start_tick` after its start
+    /// moves to `new_start`, earlier or later.
+    ///
+    /// Unlike [`trim_start`](Self::trim_start) the offset follows `tempo`, counting
+    /// the frames playback puts between the two ticks, so audio cut in two plays on
+    /// without a gap. Audio can't start before its first frame, so `new_start` is
+    /// pulled in to where the offset runs out; the start it ended up at is returned.
+    fn move_start(
+        self,
+        audio_offset: u64,
+        start_tick: u64,
+        new_start: u64,
+        tempo: &TempoCurve,
+    ) -> (Self, u64, u64) {
+        match self {
+            ClipContent::Audio(audio) => {
+                let sample_rate = audio.sample_rate();
+                let position = |tick: u64| tempo.ticks_to_samples(tick as f64, sample_rate) as u64;
+                let start = position(start_tick);
+                if new_start >= start_tick {
+                    let offset = audio_offset + (position(new_start) - start);
+                    return (ClipContent::Audio(audio), offset, new_start);
+                }
+                let earliest = tempo
+                    .samples_to_ticks(start.saturating_sub(audio_offset) as f64, sample_rate)
+                    .ceil() as u64;
+                let new_start = new_start.max(earliest).min(start_tick);
+                let offset = audio_offset.saturating_sub(start - position(new_start));
+                (ClipContent::Audio(audio), offset, new_start)
+            }
+            ClipContent::Midi(notes) => {
+                let notes = if new_start >= start_tick {
+                    notes.trim_start(new_start - start_tick)
+                } else {
+                    notes.delay(start_tick - new_start)
+                };
+                (ClipContent::Midi(notes), audio_offset, new_start)
+            }
         }
     }
 }
 
-/// A clip of audio on the timeline with explicit start and end positions.
+/// A clip on the timeline with explicit start and end positions.
 /// Clips are non-overlapping within a track - the Track enforces this invariant.
 #[derive(Debug, Clone)]
 pub struct Clip {
+    pub id: ClipId,
     pub start_tick: u64,
     pub end_tick: u64,
-    pub audio: AudioArc,
+    pub content: ClipContent,
+    /// Waveform overview for audio clips (empty for MIDI clips)
     pub waveform: Arc<WaveformData>,
     /// Offset into the audio in samples (for trimmed starts)
     pub audio_offset: u64,
     /// Display name for UI
     pub name: String,
+    /// Fade-in length in ticks from `start_tick` (0 = no fade)
+    pub fade_in_ticks: u64,
+    /// Crossfade tail in ticks. The clip keeps playing past `end_tick` for this
+    /// long while fading out under the following clip (0 = hard cut)
+    pub fade_out_ticks: u64,
+    /// Linear gain applied to the clip on top of its track's volume (1.0 = unity)
+    pub gain: f32,
+    /// Repeat the audio for as long as the clip lasts instead of stopping at its end
+    pub looped: bool,
+    /// Play the audio backwards. `audio_offset` then counts from the end of the audio
+    pub reversed: bool,
+    /// Where the clip's audio was imported from
+    pub source: ClipSource,
 }
 
 impl Clip {
@@ -379,6 +660,80 @@ impl Clip {
     pub fn duration_ticks(&self) -> u64 {
         self.end_tick - self.start_tick
     }
+
+    /// The clip's audio, or `None` for a MIDI clip
+    pub fn audio(&self) -> Option<&AudioArc> {
+        match &self.content {
+            ClipContent::Audio(audio) => Some(audio),
+            ClipContent::Midi(_) => None,
+        }
+    }
+
+    /// The clip's notes, or `None` for an audio clip
+    pub fn notes(&self) -> Option<&NoteList> {
+        match &self.content {
+            ClipContent::Audio(_) => None,
+            ClipContent::Midi(notes) => Some(notes),
+        }
+    }
+}
+
+/// Frame of a clip's audio heard `position` frames into the clip, or `None` once
+/// the audio has run out.
+///
+/// `offset` is where the clip starts in the audio as it's heard, so for a reversed
+/// clip it counts back from the last frame. A looped clip wraps around to the
+/// start of the audio (`offset` included), so it never runs out.
+#[inline]
+pub fn clip_source_frame(
+    position: u64,
+    offset: u64,
+    audio_frames: u64,
+    looped: bool,
+    reversed: bool,
+) -> Option<u64> {
+    let mut frame = offset + position;
+    if looped && audio_frames > 0 {
+        frame %= audio_frames;
+    }
+    if frame >= audio_frames {
+        return None;
+    }
+    Some(if reversed {
+        audio_frames - 1 - frame
+    } else {
+        frame
+    })
+}
+
+/// Equal-power fade gain `position` frames into a fade of `length` frames.
+///
+/// Rises from 0.0 to 1.0 over the fade. A fade-out uses the mirrored position,
+/// `fade_gain(length - position, length)`, so a fade-out and fade-in of the same
+/// length sum to constant power.
+#[inline]
+pub fn fade_gain(position: u64, length: u64) -> f32 {
+    if position >= length {
+        return 1.0;
+    }
+    let t = position as f32 / length as f32;
+    (t * std::f32::consts::FRAC_PI_2).sin()
+}
+
+/// Left and right gain for a track panned to `pan` (-1.0 left to 1.0 right).
+///
+/// Equal power, so a sound keeps its loudness as it moves across, scaled so a
+/// centered track plays at unity and a hard-panned one 3 dB louder on its side.
+#[inline]
+pub fn pan_gains(pan: f32) -> [f32; 2] {
+    if pan == 0.0 {
+        return [1.0, 1.0];
+    }
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * std::f32::consts::FRAC_PI_4;
+    [
+        angle.cos() * std::f32::consts::SQRT_2,
+        angle.sin() * std::f32::consts::SQRT_2,
+    ]
 }
 
 #[derive(Debug, Clone)]
@@ -392,6 +747,27 @@ pub struct Track {
     pub pan: f32,
     pub enabled: bool,
     pub solo: bool,
+    /// Display color as RGB (None = let the frontend pick one)
+    pub color: Option<[u8; 3]>,
+    /// Freeform user notes and metadata
+    pub metadata: BTreeMap<String, String>,
+    /// Folder track this track is grouped under (None = top level)
+    pub parent: Option<TrackId>,
+    /// Whether this is a folder track, which groups other tracks instead of holding clips
+    pub is_folder: bool,
+    /// Automation lanes, at most one per parameter. Playback and renders follow
+    /// the volume and pan lanes.
+    pub automation: Vec<AutomationLane>,
+    /// Insert effects, which the track's audio goes through in order before its
+    /// volume and pan. Folders' effects aren't run.
+    pub effects: Vec<Effect>,
+    /// Whether this is an aux bus, which mixes what other tracks send it
+    pub is_bus: bool,
+    /// Buses this track feeds, at most one send per bus
+    pub sends: Vec<TrackSend>,
+    /// Instrument playing the track's MIDI clips, and live MIDI input while it's
+    /// armed. Not stored in the project format yet, like MIDI clips.
+    pub sampler: Option<Sampler>,
 }
 
 impl Track {
@@ -404,6 +780,31 @@ impl Track {
             pan: 0.0,
             enabled: true,
             solo: false,
+            color: None,
+            metadata: BTreeMap::new(),
+            parent: None,
+            is_folder: false,
+            automation: Vec::new(),
+            effects: Vec::new(),
+            is_bus: false,
+            sends: Vec::new(),
+            sampler: None,
+        }
+    }
+
+    /// Create an empty folder track
+    pub fn new_folder(id: TrackId, name: String) -> Self {
+        Self {
+            is_folder: true,
+            ..Self::new(id, name)
+        }
+    }
+
+    /// Create an empty aux bus track
+    pub fn new_bus(id: TrackId, name: String) -> Self {
+        Self {
+            is_bus: true,
+            ..Self::new(id, name)
         }
     }
 
@@ -417,11 +818,48 @@ impl Track {
         self.clips.clear();
     }
 
+    /// Change clips in place with `edit`, which is called for every clip in order.
+    ///
+    /// Clips keep their start and never run into the next clip: one that grows is
+    /// cut off where the next clip starts, as inserting it would.
+    pub fn edit_clips(&mut self, mut edit: impl FnMut(&mut Clip)) {
+        for i in 0..self.clips.len() {
+            let clip = &mut self.clips[i];
+            let start_tick = clip.start_tick;
+            edit(clip);
+            clip.start_tick = start_tick;
+            clip.end_tick = clip.end_tick.max(start_tick + 1);
+
+            if let Some(next_start) = self.clips.get(i + 1).map(|next| next.start_tick) {
+                let clip = &mut self.clips[i];
+                clip.end_tick = clip.end_tick.min(next_start);
+            }
+        }
+    }
+
     /// Insert a clip, trimming/splitting/removing any overlapping clips.
     /// The new clip takes priority - existing clips in its range are modified.
-    pub fn insert_clip(&mut self, new_clip: Clip) {
+    ///
+    /// Splitting an existing clip in two gives the right half a new id from `ids`.
+    pub fn insert_clip(&mut self, new_clip: Clip, ids: &mut IdAllocator) {
+        self.insert_clip_with_crossfade(new_clip, 0, ids);
+    }
+
+    /// Insert a clip like [`insert_clip`](Track::insert_clip), crossfading at
+    /// every cut it makes into a neighbor.
+    ///
+    /// Where a clip is cut off by the one after it, the outgoing clip gets a
+    /// `fade_out_ticks` tail that plays under the incoming clip's `fade_in_ticks`.
+    /// The crossfade is limited to the incoming clip's duration.
+    pub fn insert_clip_with_crossfade(
+        &mut self,
+        mut new_clip: Clip,
+        crossfade_ticks: u64,
+        ids: &mut IdAllocator,
+    ) {
         let new_start = new_clip.start_tick;
         let new_end = new_clip.end_tick;
+        let new_crossfade = crossfade_ticks.min(new_clip.duration_ticks());
 
         // Process existing clips
         let mut result: Vec<Clip> = Vec::new();
@@ -442,65 +880,94 @@ impl Track {
 
                     // Left part: from ex_start to new_start
                     let left = Clip {
+                        id: existing.id,
                         start_tick: ex_start,
                         end_tick: new_start,
-                        audio: existing.audio.clone(),
+                        content: existing.content.clone(),
                         waveform: existing.waveform.clone(),
                         audio_offset: existing.audio_offset,
                         name: existing.name.clone(),
+                        fade_in_ticks: existing.fade_in_ticks.min(new_start - ex_start),
+                        fade_out_ticks: new_crossfade,
+                        gain: existing.gain,
+                        looped: existing.looped,
+                        reversed: existing.reversed,
+                        source: existing.source.clone(),
                     };
                     result.push(left);
+                    new_clip.fade_in_ticks = new_clip.fade_in_ticks.max(new_crossfade);
 
                     // Right part: from new_end to ex_end
-                    // Calculate the audio offset for the right part
-                    let ticks_into_audio = new_end - ex_start;
-                    let _samples_per_tick = existing.audio.sample_rate() as f64 / 960.0 * 0.5; // At 120 BPM
-                    // More accurate: we need tempo, but for now approximate
-                    // Actually, store audio_offset in samples, so we need to convert ticks to samples
-                    // This is tricky without tempo - let's use a simpler approach
-                    let right_offset = existing.audio_offset
-                        + ticks_to_samples_approx(ticks_into_audio, existing.audio.sample_rate());
+                    let (right_content, right_offset) = existing
+                        .content
+                        .trim_start(existing.audio_offset, new_end - ex_start);
 
+                    let right_crossfade = crossfade_ticks.min(ex_end - new_end);
                     let right = Clip {
+                        id: ids.clip_id(),
                         start_tick: new_end,
                         end_tick: ex_end,
-                        audio: existing.audio,
+                        content: right_content,
                         waveform: existing.waveform,
                         audio_offset: right_offset,
                         name: existing.name,
+                        fade_in_ticks: right_crossfade,
+                        fade_out_ticks: existing.fade_out_ticks,
+                        gain: existing.gain,
+                        looped: existing.looped,
+                        reversed: existing.reversed,
+                        source: existing.source,
                     };
                     result.push(right);
+                    new_clip.fade_out_ticks = new_clip.fade_out_ticks.max(right_crossfade);
                 } else if new_start <= ex_start {
                     // New covers the start - trim existing's start
-                    let trim_ticks = new_end - ex_start;
-                    let trim_samples =
-                        ticks_to_samples_approx(trim_ticks, existing.audio.sample_rate());
+                    let (content, audio_offset) = existing
+                        .content
+                        .trim_start(existing.audio_offset, new_end - ex_start);
 
+                    let right_crossfade = crossfade_ticks.min(ex_end - new_end);
                     let trimmed = Clip {
+                        id: existing.id,
                         start_tick: new_end,
                         end_tick: ex_end,
-                        audio: existing.audio,
+                        content,
                         waveform: existing.waveform,
-                        audio_offset: existing.audio_offset + trim_samples,
+                        audio_offset,
                         name: existing.name,
+                        fade_in_ticks: right_crossfade,
+                        fade_out_ticks: existing.fade_out_ticks,
+                        gain: existing.gain,
+                        looped: existing.looped,
+                        reversed: existing.reversed,
+                        source: existing.source,
                     };
 
                     if trimmed.start_tick < trimmed.end_tick {
                         result.push(trimmed);
+                        new_clip.fade_out_ticks = new_clip.fade_out_ticks.max(right_crossfade);
                     }
                 } else {
                     // New covers the end - trim existing's end
                     let trimmed = Clip {
+                        id: existing.id,
                         start_tick: ex_start,
                         end_tick: new_start,
-                        audio: existing.audio,
+                        content: existing.content,
                         waveform: existing.waveform,
                         audio_offset: existing.audio_offset,
                         name: existing.name,
+                        fade_in_ticks: existing.fade_in_ticks.min(new_start - ex_start),
+                        fade_out_ticks: new_crossfade,
+                        gain: existing.gain,
+                        looped: existing.looped,
+                        reversed: existing.reversed,
+                        source: existing.source,
                     };
 
                     if trimmed.start_tick < trimmed.end_tick {
                         result.push(trimmed);
+                        new_clip.fade_in_ticks = new_clip.fade_in_ticks.max(new_crossfade);
                     }
                 }
             } else {
@@ -518,11 +985,106 @@ impl Track {
         self.clips = result;
     }
 
+    /// The clip with id `id`, if it's on this track
+    pub fn clip(&self, id: ClipId) -> Option<&Clip> {
+        self.clips.iter().find(|clip| clip.id == id)
+    }
+
+    /// Take the clip with id `id` off the track
+    pub fn remove_clip(&mut self, id: ClipId) -> Option<Clip> {
+        let index = self.clips.iter().position(|clip| clip.id == id)?;
+        Some(self.clips.remove(index))
+    }
+
+    /// Cut a clip in two at `tick` and return the id of the right part, taken
+    /// from `ids`.
+    ///
+    /// The left part keeps the clip's id and fade-in, the right part its fade-out,
+    /// and the cut between them is hard, so the two play on as the clip did.
+    /// Returns `None` if there's no such clip or `tick` isn't inside it.
+    pub fn split_clip(
+        &mut self,
+        id: ClipId,
+        tick: u64,
+        tempo: &TempoCurve,
+        ids: &mut IdAllocator,
+    ) -> Option<ClipId> {
+        let index = self.clips.iter().position(|clip| clip.id == id)?;
+        let left = &mut self.clips[index];
+        if tick <= left.start_tick || tick >= left.end_tick {
+            return None;
+        }
+
+        let (content, audio_offset, _) =
+            left.content
+                .clone()
+                .move_start(left.audio_offset, left.start_tick, tick, tempo);
+        let right = Clip {
+            id: ids.clip_id(),
+            start_tick: tick,
+            content,
+            audio_offset,
+            fade_in_ticks: 0,
+            ..left.clone()
+        };
+        left.end_tick = tick;
+        left.fade_in_ticks = left.fade_in_ticks.min(tick - left.start_tick);
+        left.fade_out_ticks = 0;
+
+        let right_id = right.id;
+        self.clips.insert(index + 1, right);
+        Some(right_id)
+    }
+
+    /// Move a clip's edges to `start_tick` and `end_tick`, keeping its audio where
+    /// it is on the timeline.
+    ///
+    /// An edge moved out stops at the neighboring clip, and a start moved earlier
+    /// stops where the audio begins. The fade-in is shortened if the clip gets
+    /// shorter than it. Returns whether the clip was trimmed, which it isn't if
+    /// there's no such clip or the edges would leave it empty.
+    pub fn trim_clip(
+        &mut self,
+        id: ClipId,
+        start_tick: u64,
+        end_tick: u64,
+        tempo: &TempoCurve,
+    ) -> bool {
+        let Some(index) = self.clips.iter().position(|clip| clip.id == id) else {
+            return false;
+        };
+        let earliest = index
+            .checked_sub(1)
+            .map_or(0, |previous| self.clips[previous].end_tick);
+        let latest = self
+            .clips
+            .get(index + 1)
+            .map_or(u64::MAX, |next| next.start_tick);
+        let clip = &mut self.clips[index];
+
+        let (content, audio_offset, start_tick) = clip.content.clone().move_start(
+            clip.audio_offset,
+            clip.start_tick,
+            start_tick.max(earliest),
+            tempo,
+        );
+        let end_tick = end_tick.min(latest);
+        if start_tick >= end_tick {
+            return false;
+        }
+        clip.content = content;
+        clip.audio_offset = audio_offset;
+        clip.start_tick = start_tick;
+        clip.end_tick = end_tick;
+        clip.fade_in_ticks = clip.fade_in_ticks.min(end_tick - start_tick);
+        true
+    }
+
     /// Build from a list of clips, inserting each one (resolving overlaps)
-    pub fn from_clips(id: TrackId, name: String, clips: Vec<Clip>) -> Self {
+    pub fn from_clips(id: TrackId, name: String, clips: Vec<Clip>, ids: &mut IdAllocator) -> Self {
         let mut track = Self::new(id, name);
         for clip in clips {
-            track.insert_clip(clip);
+            track.insert_clip(clip, ids);
         }
         track
     }
@@ -549,126 +1111,6 @@ pub fn samples_to_ticks(samples: f64, tempo: f64, sample_rate: u32) -> u64 {
     (seconds / seconds_per_tick) as u64
 }
 
-/// Resample an `AudioArc` to a target sample rate.
-///
-/// This performs high-quality sinc interpolation resampling. If the audio is already
-/// at the target rate, returns a cheap clone.
-///
-/// # Examples
-///
-/// ```
-/// use daw_transport::{AudioArc, resample_audio_arc};
-///
-/// let audio = AudioArc::new(vec![0.0; 44100], 44100, 1);
-/// let resampled = resample_audio_arc(&audio, 48000).unwrap();
-/// assert_eq!(resampled.sample_rate(), 48000);
-/// ```
-pub fn resample_audio_arc(audio: &AudioArc, target_sample_rate: u32) -> anyhow::Result<AudioArc> {
-    // If already at target rate, return a cheap clone
-    if audio.sample_rate == target_sample_rate {
-        return Ok(audio.clone());
-    }
-
-    let channels = audio.channels as usize;
-    let input_frames = audio.frames();
-
-    // Calculate output length
-    let resample_ratio = target_sample_rate as f64 / audio.sample_rate as f64;
-    let output_frames = (input_frames as f64 * resample_ratio).ceil() as usize;
-
-    // Convert interleaved samples to per-channel format for rubato
-    let mut input_channels = vec![Vec::with_capacity(input_frames); channels];
-    for frame_idx in 0..input_frames {
-        for ch in 0..channels {
-            input_channels[ch].push(audio.samples()[frame_idx * channels + ch]);
-        }
-    }
-
-    // Create resampler with high quality settings
-    let params = SincInterpolationParameters {
-        sinc_len: 256,
-        f_cutoff: 0.95,
-        interpolation: SincInterpolationType::Linear,
-        oversampling_factor: 256,
-        window: WindowFunction::BlackmanHarris2,
-    };
-
-    let mut resampler =
-        SincFixedIn::<f32>::new(resample_ratio, 2.0, params, input_frames, channels)?;
-
-    // Process resampling
-    let output_channels = resampler.process(&input_channels, None)?;
-
-    // Convert back to interleaved format
-    let mut output_samples = Vec::with_capacity(output_frames * channels);
-    for frame_idx in 0..output_channels[0].len() {
-        for ch in 0..channels {
-            output_samples.push(output_channels[ch][frame_idx]);
-        }
-    }
-
-    Ok(AudioArc::new(
-        output_samples,
-        target_sample_rate,
-        audio.channels,
-    ))
-}
-
-/// Resample an audio buffer to a target sample rate (legacy API)
-pub fn resample_audio(
-    buffer: &AudioBuffer,
-    target_sample_rate: u32,
-) -> anyhow::Result<AudioBuffer> {
-    // If already at target rate, return a clone
-    if buffer.sample_rate == target_sample_rate {
-        return Ok(buffer.clone());
-    }
-
-    let channels = buffer.channels as usize;
-    let input_frames = buffer.samples.len() / channels;
-
-    // Calculate output length
-    let resample_ratio = target_sample_rate as f64 / buffer.sample_rate as f64;
-    let output_frames = (input_frames as f64 * resample_ratio).ceil() as usize;
-
-    // Convert interleaved samples to per-channel format for rubato
-    let mut input_channels = vec![Vec::with_capacity(input_frames); channels];
-    for frame_idx in 0..input_frames {
-        for ch in 0..channels {
-            input_channels[ch].push(buffer.samples[frame_idx * channels + ch]);
-        }
-    }
-
-    // Create resampler with high quality settings
-    let params = SincInterpolationParameters {
-        sinc_len: 256,
-        f_cutoff: 0.95,
-        interpolation: SincInterpolationType::Linear,
-        oversampling_factor: 256,
-        window: WindowFunction::BlackmanHarris2,
-    };
-
-    let mut resampler =
-        SincFixedIn::<f32>::new(resample_ratio, 2.0, params, input_frames, channels)?;
-
-    // Process resampling
-    let output_channels = resampler.process(&input_channels, None)?;
-
-    // Convert back to interleaved format
-    let mut output_samples = Vec::with_capacity(output_frames * channels);
-    for frame_idx in 0..output_channels[0].len() {
-        for ch in 0..channels {
-            output_samples.push(output_channels[ch][frame_idx]);
-        }
-    }
-
-    Ok(AudioBuffer {
-        samples: output_samples,
-        sample_rate: target_sample_rate,
-        channels: buffer.channels,
-    })
-}
-
 #[cfg(test)]
 mod audio_arc_tests {
     use super::*;
@@ -733,6 +1175,82 @@ mod audio_arc_tests {
         assert_eq!(Arc::strong_count(audio2.samples_arc()), 2);
     }
 
+    #[test]
+    fn test_audio_arc_slice_shares_data() {
+        let samples: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let audio = AudioArc::new(samples, 44100, 2);
+
+        let slice = audio.slice(2..5);
+        assert_eq!(slice.frames(), 3);
+        assert_eq!(slice.len(), 6);
+        assert_eq!(slice.samples(), &[4.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        assert_eq!(slice.sample_rate(), 44100);
+        assert_eq!(slice.channels(), 2);
+
+        // No copy: both views point at the same allocation
+        assert!(Arc::ptr_eq(audio.samples_arc(), slice.samples_arc()));
+        assert_eq!(Arc::strong_count(audio.samples_arc()), 2);
+    }
+
+    #[test]
+    fn test_audio_arc_slice_of_slice() {
+        let samples: Vec<f32> = (0..10).map(|i| i as f32).collect();
+        let audio = AudioArc::new(samples, 44100, 1);
+
+        let inner = audio.slice(2..8).slice(1..=2);
+        assert_eq!(inner.samples(), &[3.0, 4.0]);
+
+        let right: Vec<f32> = audio.slice(8..).channel(0).collect();
+        assert_eq!(right, vec![8.0, 9.0]);
+
+        let empty = audio.slice(5..5);
+        assert!(empty.is_empty());
+        assert_eq!(empty.frames(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_audio_arc_slice_out_of_bounds() {
+        let audio = AudioArc::new(vec![0.0; 8], 44100, 2);
+        audio.slice(2..5);
+    }
+
+    #[test]
+    fn test_audio_arc_planar_follows_slice() {
+        let samples = vec![0.0, 0.1, 1.0, 1.1, 2.0, 2.1, 3.0, 3.1];
+        let audio = AudioArc::new(samples, 44100, 2);
+        let slice = audio.slice(1..3);
+
+        assert_eq!(slice.planar(), vec![vec![1.0, 2.0], vec![1.1, 2.1]]);
+
+        // Buffers set up with room are filled in place
+        let mut planar = vec![Vec::with_capacity(4), Vec::with_capacity(4)];
+        let before = planar[0].as_ptr();
+        slice.planar_into(&mut planar);
+        audio.slice(3..).planar_into(&mut planar);
+        assert_eq!(planar, vec![vec![1.0, 2.0, 3.0], vec![1.1, 2.1, 3.1]]);
+        assert_eq!(planar[0].as_ptr(), before);
+    }
+
+    #[test]
+    fn test_audio_arc_from_planar() {
+        let audio = AudioArc::from_planar(&[vec![0.0, 1.0], vec![0.5, 1.5]], 48000);
+        assert_eq!(audio.channels(), 2);
+        assert_eq!(audio.samples(), &[0.0, 0.5, 1.0, 1.5]);
+    }
+
+    #[test]
+    fn test_audio_arc_f64_conversions() {
+        let audio = AudioArc::from_planar_f64(&[[0.5f64, -0.25], [1.0, 0.0]], 96000);
+        assert_eq!(audio.channels(), 2);
+        assert_eq!(audio.samples(), &[0.5, 1.0, -0.25, 0.0]);
+        assert_eq!(audio.planar_f64(), vec![vec![0.5, -0.25], vec![1.0, 0.0]]);
+        assert_eq!(audio.slice(1..).to_f64(), vec![-0.25, 0.0]);
+
+        let interleaved = AudioArc::from_f64(&[0.5, 0.25, 0.125, 0.0], 96000, 2);
+        assert_eq!(interleaved.to_f64(), vec![0.5, 0.25, 0.125, 0.0]);
+    }
+
     #[test]
     fn test_audio_arc_samples_access() {
         let samples = vec![1.0, 2.0, 3.0, 4.0];
@@ -957,6 +1475,46 @@ mod audio_arc_tests {
         assert!(debug_str.contains("duration_secs"));
     }
 
+    #[test]
+    fn test_waveform_matches_serial_peaks() {
+        // Long enough to be split across several parallel tasks, with a partial last bucket
+        let audio = generate_sine_wave(3.0, 44100, 4.0, 2);
+        let samples_per_bucket = 100;
+        let waveform = WaveformData::from_audio_arc(&audio, samples_per_bucket);
+
+        let expected: Vec<(f32, f32)> = audio
+            .samples()
+            .chunks(samples_per_bucket * 2)
+            .map(|bucket| {
+                bucket
+                    .chunks(2)
+                    .map(|frame| (frame[0] + frame[1]) / 2.0)
+                    .fold((0.0f32, 0.0f32), |(lo, hi), s| (lo.min(s), hi.max(s)))
+            })
+            .collect();
+
+        assert_eq!(waveform.samples_per_bucket, samples_per_bucket);
+        assert_eq!(
+            waveform.peaks.len(),
+            audio.frames().div_ceil(samples_per_bucket)
+        );
+        assert_eq!(waveform.peaks, expected);
+    }
+
+    #[test]
+    fn test_waveform_content_hash() {
+        let audio = generate_sine_wave(440.0, 44100, 0.1, 1);
+        let waveform = WaveformData::from_audio_arc(&audio, 64);
+        assert_eq!(waveform.content_hash(), waveform.clone().content_hash());
+
+        let mut changed = waveform.clone();
+        changed.peaks[3].1 += 0.01;
+        assert_ne!(changed.content_hash(), waveform.content_hash());
+
+        let coarser = WaveformData::from_audio_arc(&audio, 128);
+        assert_ne!(coarser.content_hash(), waveform.content_hash());
+    }
+
     /// Helper function to count zero crossings in a signal
     fn count_zero_crossings(samples: &[f32]) -> usize {
         let mut count = 0;
@@ -970,3 +1528,268 @@ mod audio_arc_tests {
         count
     }
 }
+
+#[cfg(test)]
+mod track_tests {
+    use super::*;
+
+    fn clip(name: &str, start_tick: u64, end_tick: u64) -> Clip {
+        let audio = AudioArc::new(vec![0.0; 48000], 48000, 1);
+        Clip {
+            id: ClipId(0),
+            start_tick,
+            end_tick,
+            waveform: Arc::new(WaveformData::from_audio_arc(&audio, 512)),
+            content: ClipContent::Audio(audio),
+            audio_offset: 0,
+            name: name.to_string(),
+            fade_in_ticks: 0,
+            fade_out_ticks: 0,
+            gain: 1.0,
+            looped: false,
+            reversed: false,
+            source: ClipSource::default(),
+        }
+    }
+
+    #[test]
+    fn test_insert_clip_is_a_hard_cut() {
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(clip("a", 0, 1920), &mut ids);
+        track.insert_clip(clip("b", 960, 2880), &mut ids);
+
+        let clips = track.clips();
+        assert_eq!(clips[0].end_tick, 960);
+        assert_eq!(clips[0].fade_out_ticks, 0);
+        assert_eq!(clips[1].fade_in_ticks, 0);
+    }
+
+    #[test]
+    fn test_edit_clips_keeps_clips_apart() {
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(clip("a", 0, 960), &mut ids);
+        track.insert_clip(clip("b", 1920, 2880), &mut ids);
+
+        track.edit_clips(|clip| {
+            clip.start_tick += 100;
+            clip.end_tick += 1920;
+        });
+
+        let ranges: Vec<_> = track
+            .clips()
+            .iter()
+            .map(|clip| (clip.start_tick, clip.end_tick))
+            .collect();
+        assert_eq!(ranges, vec![(0, 1920), (1920, 4800)]);
+    }
+
+    #[test]
+    fn test_crossfade_into_inserted_clip() {
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(clip("a", 0, 1920), &mut ids);
+        track.insert_clip_with_crossfade(clip("b", 960, 2880), 240, &mut ids);
+
+        let clips = track.clips();
+        assert_eq!(clips[0].name, "a");
+        assert_eq!(clips[0].end_tick, 960);
+        assert_eq!(clips[0].fade_out_ticks, 240);
+        assert_eq!(clips[1].name, "b");
+        assert_eq!(clips[1].fade_in_ticks, 240);
+    }
+
+    #[test]
+    fn test_crossfade_on_both_sides_of_split() {
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(clip("a", 0, 3840), &mut ids);
+        track.insert_clip_with_crossfade(clip("b", 960, 1920), 480, &mut ids);
+
+        let clips = track.clips();
+        assert_eq!(clips.len(), 3);
+        assert_eq!(clips[0].fade_out_ticks, 480);
+        assert_eq!(
+            (clips[1].fade_in_ticks, clips[1].fade_out_ticks),
+            (480, 480)
+        );
+        assert_eq!(clips[2].start_tick, 1920);
+        assert_eq!(clips[2].fade_in_ticks, 480);
+    }
+
+    #[test]
+    fn test_split_gives_right_part_a_new_id() {
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::new(0, 2);
+        let mut a = clip("a", 0, 3840);
+        a.id = ClipId(0);
+        let mut b = clip("b", 960, 1920);
+        b.id = ClipId(1);
+        track.insert_clip(a, &mut ids);
+        track.insert_clip(b, &mut ids);
+
+        let clip_ids: Vec<ClipId> = track.clips().iter().map(|c| c.id).collect();
+        assert_eq!(clip_ids, vec![ClipId(0), ClipId(1), ClipId(2)]);
+        assert_eq!(ids.next_clip_id(), 3);
+    }
+
+    #[test]
+    fn test_crossfade_limited_to_incoming_clip() {
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(clip("a", 0, 1920), &mut ids);
+        track.insert_clip_with_crossfade(clip("b", 960, 1200), 960, &mut ids);
+
+        assert_eq!(track.clips()[0].fade_out_ticks, 240);
+        assert_eq!(track.clips()[1].fade_in_ticks, 240);
+    }
+
+    #[test]
+    fn test_split_midi_clip_shifts_notes() {
+        let note = |start_tick, pitch| Note {
+            start_tick,
+            duration_ticks: 240,
+            pitch,
+            velocity: 100,
+        };
+        let mut midi = clip("midi", 0, 3840);
+        midi.content = ClipContent::Midi(NoteList::new(vec![note(0, 60), note(2880, 64)]));
+
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(midi, &mut ids);
+        track.insert_clip(clip("a", 960, 1920), &mut ids);
+
+        let clips = track.clips();
+        assert_eq!(clips[0].notes().unwrap().notes()[0], note(0, 60));
+        assert!(clips[1].audio().is_some());
+        // The right part starts at 1920, so the note at 2880 is now 960 ticks in
+        assert_eq!(clips[2].notes().unwrap().notes(), &[note(960, 64)]);
+        assert_eq!(clips[2].audio_offset, 0);
+    }
+
+    #[test]
+    fn test_split_clip_plays_on_from_the_cut() {
+        // At 60 BPM a beat is a second, 48000 frames
+        let tempo = TempoCurve::constant(60.0);
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::new(0, 1);
+        let mut a = clip("a", PPQN, PPQN * 3);
+        a.id = ClipId(0);
+        a.audio_offset = 100;
+        a.fade_in_ticks = PPQN * 2;
+        a.fade_out_ticks = 120;
+        track.insert_clip(a, &mut ids);
+        let id = track.clips()[0].id;
+
+        assert_eq!(track.split_clip(id, PPQN, &tempo, &mut ids), None);
+        assert_eq!(
+            track.split_clip(ClipId(99), PPQN * 2, &tempo, &mut ids),
+            None
+        );
+        let right = track.split_clip(id, PPQN * 2, &tempo, &mut ids).unwrap();
+
+        let clips = track.clips();
+        assert_eq!((clips[0].id, clips[0].end_tick), (id, PPQN * 2));
+        assert_eq!((clips[0].fade_in_ticks, clips[0].fade_out_ticks), (PPQN, 0));
+        assert_eq!((clips[1].id, clips[1].start_tick), (right, PPQN * 2));
+        assert_eq!((clips[1].fade_in_ticks, clips[1].fade_out_ticks), (0, 120));
+        assert_eq!(clips[1].audio_offset, 48100);
+    }
+
+    #[test]
+    fn test_trim_clip_stops_at_neighbors_and_the_audio() {
+        let tempo = TempoCurve::constant(60.0);
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(clip("a", 0, PPQN), &mut ids);
+        let mut b = clip("b", PPQN * 4, PPQN * 5);
+        b.id = ClipId(1);
+        b.audio_offset = 24000;
+        track.insert_clip(b, &mut ids);
+        track.insert_clip(clip("c", PPQN * 6, PPQN * 7), &mut ids);
+        let id = ClipId(1);
+
+        // Half a second of audio before the start, and c in the way of the end
+        assert!(track.trim_clip(id, 0, PPQN * 10, &tempo));
+        let b = &track.clips()[1];
+        assert_eq!((b.start_tick, b.end_tick), (PPQN * 7 / 2, PPQN * 6));
+        assert_eq!(b.audio_offset, 0);
+
+        assert!(track.trim_clip(id, PPQN * 4, PPQN * 5, &tempo));
+        assert_eq!(track.clips()[1].audio_offset, 24000);
+        assert!(!track.trim_clip(id, PPQN * 5, PPQN * 5, &tempo));
+    }
+
+    #[test]
+    fn test_trimming_a_midi_clip_earlier_delays_its_notes() {
+        let note = Note {
+            start_tick: 0,
+            duration_ticks: 240,
+            pitch: 60,
+            velocity: 100,
+        };
+        let mut midi = clip("midi", PPQN, PPQN * 2);
+        midi.content = ClipContent::Midi(NoteList::new(vec![note]));
+        let mut track = Track::new(TrackId(1), "Track".to_string());
+        let mut ids = IdAllocator::default();
+        track.insert_clip(midi, &mut ids);
+        let id = track.clips()[0].id;
+
+        assert!(track.trim_clip(id, 0, PPQN * 2, &TempoCurve::constant(120.0)));
+        let clip = &track.clips()[0];
+        assert_eq!(clip.start_tick, 0);
+        assert_eq!(clip.notes().unwrap().notes()[0].start_tick, PPQN);
+    }
+
+    #[test]
+    fn test_fade_gain_is_equal_power() {
+        assert_eq!(fade_gain(0, 100), 0.0);
+        assert_eq!(fade_gain(100, 100), 1.0);
+        for position in 0..=100 {
+            let fade_in = fade_gain(position, 100);
+            let fade_out = fade_gain(100 - position, 100);
+            assert!((fade_in * fade_in + fade_out * fade_out - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_pan_gains_are_equal_power() {
+        assert_eq!(pan_gains(0.0), [1.0, 1.0]);
+        let [left, right] = pan_gains(-1.0);
+        assert!((left - std::f32::consts::SQRT_2).abs() < 1e-6 && right.abs() < 1e-6);
+        let [left, right] = pan_gains(2.0);
+        assert!(left.abs() < 1e-6 && (right - std::f32::consts::SQRT_2).abs() < 1e-6);
+        for step in -10..=10 {
+            let [left, right] = pan_gains(step as f32 / 10.0);
+            assert!((left * left + right * right - 2.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_clip_source_frame_loops_and_reverses() {
+        let frames = |offset, looped, reversed| {
+            (0..6)
+                .map(|position| clip_source_frame(position, offset, 4, looped, reversed))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            frames(1, false, false),
+            [Some(1), Some(2), Some(3), None, None, None]
+        );
+        assert_eq!(
+            frames(1, false, true),
+            [Some(2), Some(1), Some(0), None, None, None]
+        );
+        assert_eq!(
+            frames(1, true, false),
+            [Some(1), Some(2), Some(3), Some(0), Some(1), Some(2)]
+        );
+        assert_eq!(
+            frames(1, true, true),
+            [Some(2), Some(1), Some(0), Some(3), Some(2), Some(1)]
+        );
+        assert_eq!(clip_source_frame(0, 0, 0, true, false), None);
+    }
+}
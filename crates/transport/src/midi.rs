@@ -0,0 +1,120 @@
+//! MIDI note data for clips that drive instruments instead of playing audio.
+
+/// A MIDI note, positioned in ticks relative to the start of its clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Note {
+    pub start_tick: u64,
+    pub duration_ticks: u64,
+    /// MIDI note number (60 = middle C)
+    pub pitch: u8,
+    /// MIDI velocity (1-127)
+    pub velocity: u8,
+}
+
+impl Note {
+    /// Clip-relative tick where this note is released
+    pub fn end_tick(&self) -> u64 {
+        self.start_tick + self.duration_ticks
+    }
+}
+
+/// The notes of a MIDI clip, always sorted by start tick.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NoteList {
+    notes: Vec<Note>,
+}
+
+impl NoteList {
+    pub fn new(mut notes: Vec<Note>) -> Self {
+        notes.sort_by_key(|note| note.start_tick);
+        Self { notes }
+    }
+
+    /// Get read-only access to the notes
+    pub fn notes(&self) -> &[Note] {
+        &self.notes
+    }
+
+    /// Insert a note, keeping the list sorted
+    pub fn insert(&mut self, note: Note) {
+        let index = self
+            .notes
+            .partition_point(|existing| existing.start_tick <= note.start_tick);
+        self.notes.insert(index, note);
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// Tick where the last note is released (0 if there are no notes)
+    pub fn end_tick(&self) -> u64 {
+        self.notes.iter().map(Note::end_tick).max().unwrap_or(0)
+    }
+
+    /// Notes left after cutting `ticks` off the start, shifted to the new start.
+    ///
+    /// Notes that begin before the cut are dropped rather than retriggered mid-note.
+    pub(crate) fn trim_start(&self, ticks: u64) -> Self {
+        let first = self.notes.partition_point(|note| note.start_tick < ticks);
+        Self {
+            notes: self.notes[first..]
+                .iter()
+                .map(|note| Note {
+                    start_tick: note.start_tick - ticks,
+                    ..*note
+                })
+                .collect(),
+        }
+    }
+
+    /// Notes moved `ticks` later, for a clip whose start moved earlier by that much
+    pub(crate) fn delay(&self, ticks: u64) -> Self {
+        Self {
+            notes: self
+                .notes
+                .iter()
+                .map(|note| Note {
+                    start_tick: note.start_tick + ticks,
+                    ..*note
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(start_tick: u64, pitch: u8) -> Note {
+        Note {
+            start_tick,
+            duration_ticks: 240,
+            pitch,
+            velocity: 100,
+        }
+    }
+
+    #[test]
+    fn test_notes_stay_sorted() {
+        let mut notes = NoteList::new(vec![note(960, 62), note(0, 60)]);
+        notes.insert(note(480, 61));
+
+        let pitches: Vec<u8> = notes.notes().iter().map(|n| n.pitch).collect();
+        assert_eq!(pitches, vec![60, 61, 62]);
+        assert_eq!(notes.end_tick(), 1200);
+    }
+
+    #[test]
+    fn test_trim_start_shifts_and_drops_cut_notes() {
+        let notes = NoteList::new(vec![note(0, 60), note(480, 61), note(960, 62)]);
+        let trimmed = notes.trim_start(480);
+
+        assert_eq!(trimmed.notes(), &[note(0, 61), note(480, 62)]);
+    }
+}
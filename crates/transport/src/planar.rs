@@ -0,0 +1,210 @@
+//! Conversion between interleaved and planar sample layouts.
+//!
+//! Audio is stored interleaved (`[L, R, L, R, ...]`), but resampling and spectral
+//! analysis work per channel. These helpers convert in cache-sized blocks, with
+//! dedicated mono and stereo paths written as straight slice loops the compiler can
+//! vectorize. They are generic over the sample type so `f64` data converts the same
+//! way as `f32`.
+
+/// Number of frames converted per block in the general multichannel path
+const CONVERT_BLOCK_FRAMES: usize = 256;
+
+/// Split interleaved samples into one `Vec` per channel.
+///
+/// # Panics
+///
+/// Panics if `channels` is 0 or `interleaved.len()` is not a multiple of `channels`.
+///
+/// # Examples
+///
+/// ```
+/// use daw_transport::deinterleave;
+///
+/// let planar = deinterleave(&[0.0, 1.0, 0.1, 1.1], 2);
+/// assert_eq!(planar, vec![vec![0.0, 0.1], vec![1.0, 1.1]]);
+/// ```
+pub fn deinterleave<T: Copy + Default>(interleaved: &[T], channels: usize) -> Vec<Vec<T>> {
+    assert!(channels > 0, "channels must be greater than 0");
+    let frames = interleaved.len() / channels;
+    let mut planar = vec![Vec::with_capacity(frames); channels];
+    deinterleave_into(interleaved, &mut planar);
+    planar
+}
+
+/// Append interleaved samples to per-channel buffers, one buffer per channel.
+///
+/// # Panics
+///
+/// Panics if `planar` is empty or `interleaved.len()` is not a multiple of
+/// `planar.len()`.
+pub fn deinterleave_into<T: Copy + Default>(interleaved: &[T], planar: &mut [Vec<T>]) {
+    let channels = planar.len();
+    assert!(channels > 0, "channels must be greater than 0");
+    assert!(
+        interleaved.len().is_multiple_of(channels),
+        "input length {} is not a multiple of {} channels",
+        interleaved.len(),
+        channels
+    );
+    let frames = interleaved.len() / channels;
+
+    match planar {
+        [mono] => mono.extend_from_slice(interleaved),
+        [left, right] => {
+            let start = left.len();
+            left.resize(start + frames, T::default());
+            right.resize(start + frames, T::default());
+            for ((frame, l), r) in interleaved
+                .chunks_exact(2)
+                .zip(&mut left[start..])
+                .zip(&mut right[start..])
+            {
+                *l = frame[0];
+                *r = frame[1];
+            }
+        }
+        _ => {
+            for channel in planar.iter_mut() {
+                channel.reserve(frames);
+            }
+            for block in interleaved.chunks(CONVERT_BLOCK_FRAMES * channels) {
+                for (ch, channel) in planar.iter_mut().enumerate() {
+                    channel.extend(block[ch..].iter().step_by(channels));
+                }
+            }
+        }
+    }
+}
+
+/// Interleave equal-length channels into a single buffer.
+///
+/// # Panics
+///
+/// Panics if `planar` is empty or the channels differ in length.
+///
+/// # Examples
+///
+/// ```
+/// use daw_transport::interleave;
+///
+/// let interleaved = interleave(&[vec![0.0, 0.1], vec![1.0, 1.1]]);
+/// assert_eq!(interleaved, vec![0.0, 1.0, 0.1, 1.1]);
+/// ```
+pub fn interleave<T: Copy + Default, C: AsRef<[T]>>(planar: &[C]) -> Vec<T> {
+    assert!(!planar.is_empty(), "channels must be greater than 0");
+    let frames = planar[0].as_ref().len();
+    assert!(
+        planar
+            .iter()
+            .all(|channel| channel.as_ref().len() == frames),
+        "channels must all have the same length"
+    );
+    let mut interleaved = Vec::with_capacity(frames * planar.len());
+    interleave_into(planar, frames, &mut interleaved);
+    interleaved
+}
+
+/// Interleave the first `frames` frames of each channel, appending to `output`.
+///
+/// # Panics
+///
+/// Panics if `planar` is empty or any channel is shorter than `frames`.
+pub fn interleave_into<T: Copy + Default, C: AsRef<[T]>>(
+    planar: &[C],
+    frames: usize,
+    output: &mut Vec<T>,
+) {
+    let channels = planar.len();
+    assert!(channels > 0, "channels must be greater than 0");
+
+    let start = output.len();
+    output.resize(start + frames * channels, T::default());
+    let dest = &mut output[start..];
+
+    match planar {
+        [mono] => dest.copy_from_slice(&mono.as_ref()[..frames]),
+        [left, right] => {
+            for ((frame, &l), &r) in dest
+                .chunks_exact_mut(2)
+                .zip(&left.as_ref()[..frames])
+                .zip(&right.as_ref()[..frames])
+            {
+                frame[0] = l;
+                frame[1] = r;
+            }
+        }
+        _ => {
+            for (block_idx, block) in dest.chunks_mut(CONVERT_BLOCK_FRAMES * channels).enumerate() {
+                let first_frame = block_idx * CONVERT_BLOCK_FRAMES;
+                let block_frames = block.len() / channels;
+                for (ch, channel) in planar.iter().enumerate() {
+                    let source = &channel.as_ref()[first_frame..first_frame + block_frames];
+                    for (sample, &value) in block[ch..].iter_mut().step_by(channels).zip(source) {
+                        *sample = value;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interleaved frames where each sample encodes its frame and channel
+    fn numbered(frames: usize, channels: usize) -> Vec<f32> {
+        (0..frames * channels)
+            .map(|i| (i / channels) as f32 + (i % channels) as f32 / 10.0)
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip_all_channel_counts() {
+        // Enough frames to span several blocks in the general path
+        let frames = CONVERT_BLOCK_FRAMES * 3 + 17;
+        for channels in 1..=6 {
+            let interleaved = numbered(frames, channels);
+            let planar = deinterleave(&interleaved, channels);
+
+            assert_eq!(planar.len(), channels);
+            for (ch, channel) in planar.iter().enumerate() {
+                assert_eq!(channel.len(), frames);
+                assert_eq!(channel[5], 5.0 + ch as f32 / 10.0);
+            }
+            assert_eq!(interleave(&planar), interleaved, "{} channels", channels);
+        }
+    }
+
+    #[test]
+    fn test_deinterleave_into_appends() {
+        let mut planar = vec![vec![9.0], vec![9.5]];
+        deinterleave_into(&[0.0, 0.1, 1.0, 1.1], &mut planar);
+        assert_eq!(planar, vec![vec![9.0, 0.0, 1.0], vec![9.5, 0.1, 1.1]]);
+    }
+
+    #[test]
+    fn test_interleave_into_takes_leading_frames() {
+        let planar = [
+            vec![0.0, 1.0, 2.0],
+            vec![0.1, 1.1, 2.1],
+            vec![0.2, 1.2, 2.2],
+        ];
+        let mut output = vec![-1.0];
+        interleave_into(&planar, 2, &mut output);
+        assert_eq!(output, vec![-1.0, 0.0, 0.1, 0.2, 1.0, 1.1, 1.2]);
+    }
+
+    #[test]
+    fn test_f64_round_trip() {
+        let planar = deinterleave(&[0.0f64, 1.0, 0.1, 1.1, 0.2, 1.2], 2);
+        assert_eq!(planar, vec![vec![0.0, 0.1, 0.2], vec![1.0, 1.1, 1.2]]);
+        assert_eq!(interleave(&planar), vec![0.0, 1.0, 0.1, 1.1, 0.2, 1.2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a multiple")]
+    fn test_deinterleave_partial_frame_panics() {
+        deinterleave(&[0.0, 1.0, 2.0], 2);
+    }
+}
@@ -0,0 +1,397 @@
+//! Block-based sample rate conversion.
+//!
+//! Resampling runs through [`StreamResampler`], which feeds rubato a fixed-size block of
+//! input at a time. Memory use is bounded by the block size rather than the length of the
+//! input, so hour-long recordings resample without allocating input-sized scratch buffers.
+
+use rubato::{
+    Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
+};
+
+use crate::{AudioArc, AudioBuffer, deinterleave_into, interleave_into};
+
+/// Default number of input frames processed per block
+pub const RESAMPLE_CHUNK_FRAMES: usize = 4096;
+
+/// Streaming resampler for interleaved audio.
+///
+/// Input is pushed with [`process`](Self::process) in arbitrarily sized pieces and
+/// resampled in fixed blocks; call [`finish`](Self::finish) once at the end to flush
+/// the filter. Output frame `n` lines up with input time `n / ratio`, and the total
+/// output length is `ceil(input_frames * ratio)`.
+///
+/// # Examples
+///
+/// ```
+/// use daw_transport::StreamResampler;
+///
+/// let input = vec![0.0; 2 * 44100];
+/// let mut resampler = StreamResampler::new(44100, 48000, 2).unwrap();
+/// let mut output = Vec::new();
+/// for block in input.chunks(2 * 1000) {
+///     resampler.process(block, &mut output).unwrap();
+/// }
+/// resampler.finish(&mut output).unwrap();
+/// assert_eq!(output.len(), 2 * 48000);
+/// ```
+pub struct StreamResampler {
+    resampler: SincFixedIn<f32>,
+    channels: usize,
+    source_rate: u32,
+    target_rate: u32,
+    chunk_frames: usize,
+    /// Pending planar input, always shorter than one block
+    input: Vec<Vec<f32>>,
+    /// Planar scratch output for one block
+    output: Vec<Vec<f32>>,
+    frames_in: u64,
+    frames_out: u64,
+}
+
+impl StreamResampler {
+    /// Create a resampler using the default block size.
+    pub fn new(source_rate: u32, target_rate: u32, channels: u16) -> anyhow::Result<Self> {
+        Self::with_chunk_size(source_rate, target_rate, channels, RESAMPLE_CHUNK_FRAMES)
+    }
+
+    /// Create a resampler that processes `chunk_frames` input frames per block.
+    pub fn with_chunk_size(
+        source_rate: u32,
+        target_rate: u32,
+        channels: u16,
+        chunk_frames: usize,
+    ) -> anyhow::Result<Self> {
+        anyhow::ensure!(channels > 0, "channels must be greater than 0");
+        anyhow::ensure!(chunk_frames > 0, "chunk size must be greater than 0");
+        anyhow::ensure!(
+            source_rate > 0 && target_rate > 0,
+            "sample rates must be greater than 0"
+        );
+
+        let channels = channels as usize;
+        let ratio = target_rate as f64 / source_rate as f64;
+
+        // High quality settings, shared by every resampling path
+        let params = SincInterpolationParameters {
+            sinc_len: 256,
+            f_cutoff: 0.95,
+            interpolation: SincInterpolationType::Linear,
+            oversampling_factor: 256,
+            window: WindowFunction::BlackmanHarris2,
+        };
+
+        let resampler = SincFixedIn::<f32>::new(ratio, 2.0, params, chunk_frames, channels)?;
+        let output = resampler.output_buffer_allocate(true);
+
+        Ok(Self {
+            resampler,
+            channels,
+            source_rate,
+            target_rate,
+            chunk_frames,
+            input: vec![Vec::with_capacity(chunk_frames); channels],
+            output,
+            frames_in: 0,
+            frames_out: 0,
+        })
+    }
+
+    /// Ratio of output rate to input rate
+    pub fn ratio(&self) -> f64 {
+        self.target_rate as f64 / self.source_rate as f64
+    }
+
+    /// Number of input frames consumed so far
+    pub fn frames_in(&self) -> u64 {
+        self.frames_in
+    }
+
+    /// Number of output frames produced so far
+    pub fn frames_out(&self) -> u64 {
+        self.frames_out
+    }
+
+    /// Output length in frames for `input_frames` frames of input
+    pub fn output_frames_for(&self, input_frames: u64) -> u64 {
+        // Integer math so exact ratios don't round up an extra frame
+        (input_frames as u128 * self.target_rate as u128).div_ceil(self.source_rate as u128) as u64
+    }
+
+    /// Push interleaved input and append any completed output to `output`.
+    ///
+    /// `input` must contain whole frames.
+    pub fn process(&mut self, input: &[f32], output: &mut Vec<f32>) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            input.len().is_multiple_of(self.channels),
+            "input length {} is not a multiple of {} channels",
+            input.len(),
+            self.channels
+        );
+
+        let mut remaining = input;
+        loop {
+            let space = self.chunk_frames - self.input[0].len();
+            let pushed = space.min(remaining.len() / self.channels);
+            let (block, rest) = remaining.split_at(pushed * self.channels);
+            deinterleave_into(block, &mut self.input);
+            remaining = rest;
+            self.frames_in += pushed as u64;
+
+            if self.input[0].len() < self.chunk_frames {
+                return Ok(());
+            }
+
+            let (_, produced) =
+                self.resampler
+                    .process_into_buffer(&self.input, &mut self.output, None)?;
+            for channel in &mut self.input {
+                channel.clear();
+            }
+            self.emit(produced, output);
+        }
+    }
+
+    /// Flush pending input and the filter tail, appending the remaining output.
+    ///
+    /// After this call the total output length is exactly
+    /// [`output_frames_for`](Self::output_frames_for) of the consumed input.
+    pub fn finish(&mut self, output: &mut Vec<f32>) -> anyhow::Result<()> {
+        let expected = self.output_frames_for(self.frames_in);
+
+        if !self.input[0].is_empty() {
+            let (_, produced) = self.resampler.process_partial_into_buffer(
+                Some(&self.input),
+                &mut self.output,
+                None,
+            )?;
+            for channel in &mut self.input {
+                channel.clear();
+            }
+            let remaining = expected.saturating_sub(self.frames_out) as usize;
+            self.emit(produced.min(remaining), output);
+        }
+
+        // Push zeros through until the filter tail has come out
+        while self.frames_out < expected {
+            let (_, produced) = self.resampler.process_partial_into_buffer(
+                None::<&[Vec<f32>]>,
+                &mut self.output,
+                None,
+            )?;
+            anyhow::ensure!(produced > 0, "resampler produced no output while flushing");
+            let remaining = (expected - self.frames_out) as usize;
+            self.emit(produced.min(remaining), output);
+        }
+
+        Ok(())
+    }
+
+    /// Interleave the first `frames` frames of the scratch output into `output`.
+    fn emit(&mut self, frames: usize, output: &mut Vec<f32>) {
+        interleave_into(&self.output, frames, output);
+        self.frames_out += frames as u64;
+    }
+}
+
+/// Resample interleaved samples through a [`StreamResampler`], one block at a time.
+///
+/// `progress` is called after each block with the fraction of input consumed, ending at 1.0.
+fn resample_interleaved(
+    samples: &[f32],
+    channels: u16,
+    source_rate: u32,
+    target_rate: u32,
+    mut progress: impl FnMut(f32),
+) -> anyhow::Result<Vec<f32>> {
+    let mut resampler = StreamResampler::new(source_rate, target_rate, channels)?;
+    let input_frames = (samples.len() / channels as usize) as u64;
+
+    let mut output =
+        Vec::with_capacity(resampler.output_frames_for(input_frames) as usize * channels as usize);
+    for block in samples.chunks(RESAMPLE_CHUNK_FRAMES * channels as usize) {
+        resampler.process(block, &mut output)?;
+        progress(resampler.frames_in() as f32 / input_frames as f32);
+    }
+    resampler.finish(&mut output)?;
+    progress(1.0);
+
+    Ok(output)
+}
+
+/// Resample an `AudioArc` to a target sample rate.
+///
+/// This performs high-quality sinc interpolation resampling in fixed-size blocks. If the
+/// audio is already at the target rate, returns a cheap clone.
+///
+/// # Examples
+///
+/// ```
+/// use daw_transport::{AudioArc, resample_audio_arc};
+///
+/// let audio = AudioArc::new(vec![0.0; 44100], 44100, 1);
+/// let resampled = resample_audio_arc(&audio, 48000).unwrap();
+/// assert_eq!(resampled.sample_rate(), 48000);
+/// ```
+pub fn resample_audio_arc(audio: &AudioArc, target_sample_rate: u32) -> anyhow::Result<AudioArc> {
+    resample_audio_arc_with_progress(audio, target_sample_rate, |_| {})
+}
+
+/// Resample an `AudioArc`, reporting progress for long files.
+///
+/// `progress` receives the fraction of the input processed so far, from 0.0 to 1.0,
+/// after every block of [`RESAMPLE_CHUNK_FRAMES`] frames. It is not called when no
+/// resampling is needed.
+///
+/// # Examples
+///
+/// ```
+/// use daw_transport::{AudioArc, resample_audio_arc_with_progress};
+///
+/// let audio = AudioArc::new(vec![0.0; 44100], 44100, 1);
+/// let mut last = 0.0;
+/// let resampled = resample_audio_arc_with_progress(&audio, 48000, |p| last = p).unwrap();
+/// assert_eq!(resampled.frames(), 48000);
+/// assert_eq!(last, 1.0);
+/// ```
+pub fn resample_audio_arc_with_progress(
+    audio: &AudioArc,
+    target_sample_rate: u32,
+    progress: impl FnMut(f32),
+) -> anyhow::Result<AudioArc> {
+    // If already at target rate, return a cheap clone
+    if audio.sample_rate() == target_sample_rate {
+        return Ok(audio.clone());
+    }
+
+    let output = resample_interleaved(
+        audio.samples(),
+        audio.channels(),
+        audio.sample_rate(),
+        target_sample_rate,
+        progress,
+    )?;
+
+    Ok(AudioArc::new(output, target_sample_rate, audio.channels()))
+}
+
+/// Resample an audio buffer to a target sample rate (legacy API)
+pub fn resample_audio(
+    buffer: &AudioBuffer,
+    target_sample_rate: u32,
+) -> anyhow::Result<AudioBuffer> {
+    // If already at target rate, return a clone
+    if buffer.sample_rate == target_sample_rate {
+        return Ok(buffer.clone());
+    }
+
+    let samples = resample_interleaved(
+        &buffer.samples,
+        buffer.channels,
+        buffer.sample_rate,
+        target_sample_rate,
+        |_| {},
+    )?;
+
+    Ok(AudioBuffer {
+        samples,
+        sample_rate: target_sample_rate,
+        channels: buffer.channels,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    fn sine(frequency: f32, sample_rate: u32, frames: usize, channels: usize) -> Vec<f32> {
+        (0..frames)
+            .flat_map(|i| {
+                let sample = (2.0 * PI * frequency * i as f32 / sample_rate as f32).sin();
+                std::iter::repeat_n(sample, channels)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_output_length_is_exact() {
+        for (from, to, frames) in [
+            (44100, 48000, 44100),
+            (48000, 44100, 12345),
+            (22050, 96000, 1000),
+            (96000, 44100, 7),
+        ] {
+            let input = sine(440.0, from, frames, 2);
+            let output = resample_interleaved(&input, 2, from, to, |_| {}).unwrap();
+            let expected = (frames as f64 * to as f64 / from as f64).ceil() as usize;
+            assert_eq!(output.len(), expected * 2, "{} -> {} Hz", from, to);
+        }
+    }
+
+    #[test]
+    fn test_block_size_does_not_change_output() {
+        let input = sine(440.0, 44100, 20000, 2);
+
+        let mut small = StreamResampler::with_chunk_size(44100, 48000, 2, 256).unwrap();
+        let mut small_out = Vec::new();
+        // Feed uneven pieces to exercise partial blocks
+        for piece in input.chunks(2 * 333) {
+            small.process(piece, &mut small_out).unwrap();
+        }
+        small.finish(&mut small_out).unwrap();
+
+        let whole = resample_interleaved(&input, 2, 44100, 48000, |_| {}).unwrap();
+
+        assert_eq!(small_out.len(), whole.len());
+        // Blocks only change rubato's internal scheduling, not the filter
+        let max_diff = small_out
+            .iter()
+            .zip(&whole)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0f32, f32::max);
+        assert!(max_diff < 1e-3, "max difference {}", max_diff);
+    }
+
+    #[test]
+    fn test_output_is_time_aligned() {
+        // An impulse should come out at the scaled position, not shifted by the filter
+        let mut input = vec![0.0; 10000];
+        input[4410] = 1.0;
+        let output = resample_interleaved(&input, 1, 44100, 88200, |_| {}).unwrap();
+
+        let peak_index = output
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+            .map(|(i, _)| i)
+            .unwrap();
+        assert!(
+            (peak_index as i64 - 8820).abs() <= 1,
+            "impulse landed at {}",
+            peak_index
+        );
+    }
+
+    #[test]
+    fn test_progress_reports_each_block() {
+        let input = vec![0.0; RESAMPLE_CHUNK_FRAMES * 5 / 2];
+        let mut reports = Vec::new();
+        resample_interleaved(&input, 1, 44100, 48000, |p| reports.push(p)).unwrap();
+
+        // Two full blocks, the partial last block, then completion after the flush
+        assert_eq!(reports, vec![0.4, 0.8, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_rejects_partial_frames() {
+        let mut resampler = StreamResampler::new(44100, 48000, 2).unwrap();
+        let mut output = Vec::new();
+        assert!(resampler.process(&[0.0; 3], &mut output).is_err());
+    }
+
+    #[test]
+    fn test_empty_input() {
+        let output = resample_interleaved(&[], 2, 44100, 48000, |_| {}).unwrap();
+        assert!(output.is_empty());
+    }
+}
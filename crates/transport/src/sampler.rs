@@ -0,0 +1,147 @@
+//! Settings of the built-in sampler, the instrument that plays a track's MIDI
+//! clips and live MIDI input.
+//!
+//! A sampler either plays one sample across the keyboard, repitched from the
+//! note it was recorded at, or a sample of its own for each note, such as the
+//! pieces of a drum kit. Each note is shaped by an ADSR envelope, and up to
+//! [`Sampler::polyphony`] of them sound at once.
+
+use std::collections::BTreeMap;
+
+use crate::AudioArc;
+
+/// Most notes a sampler can play at once
+pub const MAX_POLYPHONY: usize = 64;
+
+/// Where a sampler's notes get their sound
+#[derive(Debug, Clone)]
+pub enum SamplerMap {
+    /// One sample for every note, played faster for notes above `root_note` and
+    /// slower for notes below
+    Pitched { audio: AudioArc, root_note: u8 },
+    /// A sample for each note, played as recorded; notes without one are silent
+    PerNote(BTreeMap<u8, AudioArc>),
+}
+
+/// Attack, decay, sustain and release of each note the sampler plays.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adsr {
+    /// Seconds to rise from silence to full level once a note starts
+    pub attack: f32,
+    /// Seconds to fall from full level to `sustain`
+    pub decay: f32,
+    /// Level held while the note is down, from 0.0 to 1.0
+    pub sustain: f32,
+    /// Seconds to fade out once the note is released
+    pub release: f32,
+}
+
+impl Default for Adsr {
+    fn default() -> Self {
+        // Short enough to sound like the sample, long enough not to click
+        Self {
+            attack: 0.002,
+            decay: 0.0,
+            sustain: 1.0,
+            release: 0.05,
+        }
+    }
+}
+
+/// A track's sampler instrument.
+#[derive(Debug, Clone)]
+pub struct Sampler {
+    pub map: SamplerMap,
+    pub envelope: Adsr,
+    /// Notes that sound at once, up to [`MAX_POLYPHONY`]; a note beyond that cuts
+    /// off the one that started first
+    pub polyphony: usize,
+}
+
+impl Sampler {
+    /// A sampler playing `audio` on every note, at its own pitch on `root_note`
+    pub fn pitched(audio: AudioArc, root_note: u8) -> Self {
+        Self::new(SamplerMap::Pitched { audio, root_note })
+    }
+
+    /// A sampler playing a sample of its own on each note in `samples`
+    pub fn per_note(samples: BTreeMap<u8, AudioArc>) -> Self {
+        Self::new(SamplerMap::PerNote(samples))
+    }
+
+    fn new(map: SamplerMap) -> Self {
+        Self {
+            map,
+            envelope: Adsr::default(),
+            polyphony: 16,
+        }
+    }
+
+    /// The sample `note` plays and how fast it's played through: 2.0 is an
+    /// octave up. `None` if the note has no sample.
+    pub fn sample_for(&self, note: u8) -> Option<(&AudioArc, f64)> {
+        match &self.map {
+            SamplerMap::Pitched { audio, root_note } => {
+                let semitones = note as f64 - *root_note as f64;
+                Some((audio, (semitones / 12.0).exp2()))
+            }
+            SamplerMap::PerNote(samples) => samples.get(&note).map(|audio| (audio, 1.0)),
+        }
+    }
+
+    /// This sampler with its samples at `sample_rate`, for playing at that rate
+    pub fn resample(&self, sample_rate: u32) -> anyhow::Result<Self> {
+        let map = match &self.map {
+            SamplerMap::Pitched { audio, root_note } => SamplerMap::Pitched {
+                audio: audio.resample(sample_rate)?,
+                root_note: *root_note,
+            },
+            SamplerMap::PerNote(samples) => SamplerMap::PerNote(
+                samples
+                    .iter()
+                    .map(|(&note, audio)| Ok((note, audio.resample(sample_rate)?)))
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+        };
+        Ok(Self {
+            map,
+            envelope: self.envelope,
+            polyphony: self.polyphony.clamp(1, MAX_POLYPHONY),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(sample_rate: u32) -> AudioArc {
+        AudioArc::new(vec![0.0; 100], sample_rate, 1)
+    }
+
+    #[test]
+    fn test_pitched_sampler_repitches_from_the_root() {
+        let sampler = Sampler::pitched(audio(48000), 60);
+        assert_eq!(sampler.sample_for(60).map(|(_, rate)| rate), Some(1.0));
+        assert_eq!(sampler.sample_for(72).map(|(_, rate)| rate), Some(2.0));
+        assert_eq!(sampler.sample_for(48).map(|(_, rate)| rate), Some(0.5));
+    }
+
+    #[test]
+    fn test_per_note_sampler_plays_only_mapped_notes() {
+        let sampler = Sampler::per_note(BTreeMap::from([(36, audio(48000))]));
+        assert_eq!(sampler.sample_for(36).map(|(_, rate)| rate), Some(1.0));
+        assert!(sampler.sample_for(37).is_none());
+    }
+
+    #[test]
+    fn test_resample_keeps_polyphony_in_range() {
+        let mut sampler = Sampler::per_note(BTreeMap::from([(36, audio(44100))]));
+        sampler.polyphony = 0;
+        let resampled = sampler.resample(44100).unwrap();
+        assert_eq!(resampled.polyphony, 1);
+
+        sampler.polyphony = 1000;
+        assert_eq!(sampler.resample(44100).unwrap().polyphony, MAX_POLYPHONY);
+    }
+}
@@ -0,0 +1,560 @@
+//! Playing a [`Sampler`]: the voices sounding its notes, carried from one block to
+//! the next, for the engine's live playback and for renders alike.
+
+use crate::{Adsr, MAX_POLYPHONY, Sampler, TempoCurve, Track};
+
+/// Frames a voice cut off by a new note fades out over, so it doesn't click
+const STEAL_FRAMES: f32 = 64.0;
+
+/// Note starts and stops one block can hold; more are dropped rather than
+/// allocating
+const PREALLOCATED_EVENTS: usize = 512;
+
+/// A note for a sampler to play, in samples on the timeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SamplerNote {
+    pub start: u64,
+    /// Where the note is released, after `start`
+    pub end: u64,
+    pub pitch: u8,
+    pub velocity: u8,
+}
+
+/// A note played live, such as from a MIDI keyboard, taking effect at the start
+/// of the block it's played in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LiveNote {
+    On {
+        note: u8,
+        velocity: u8,
+    },
+    Off {
+        note: u8,
+    },
+    /// Release every held note, live ones and the arrangement's
+    AllOff,
+}
+
+/// The notes of `track`'s MIDI clips at `sample_rate`, placed on the timeline by
+/// `tempo`, for its sampler to play. Notes are cut off where their clip ends.
+pub fn sampler_notes(track: &Track, tempo: &TempoCurve, sample_rate: u32) -> Vec<SamplerNote> {
+    let ticks_to_samples = |ticks: u64| tempo.ticks_to_samples(ticks as f64, sample_rate) as u64;
+    track
+        .clips()
+        .iter()
+        .filter_map(|clip| Some((clip, clip.notes()?)))
+        .flat_map(|(clip, notes)| {
+            notes
+                .notes()
+                .iter()
+                .filter(|note| note.start_tick < clip.duration_ticks())
+                .map(move |note| {
+                    let start = ticks_to_samples(clip.start_tick + note.start_tick);
+                    let end = (clip.start_tick + note.end_tick()).min(clip.end_tick);
+                    SamplerNote {
+                        start,
+                        end: ticks_to_samples(end).max(start + 1),
+                        pitch: note.pitch,
+                        velocity: note.velocity,
+                    }
+                })
+        })
+        .collect()
+}
+
+/// Something a sampler does at a frame of the block
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NoteEvent {
+    /// Release the notes the arrangement is holding
+    ArrangementOff,
+    /// Release every note, live ones too
+    AllOff,
+    Off {
+        note: u8,
+        live: bool,
+    },
+    On {
+        note: u8,
+        velocity: u8,
+        live: bool,
+    },
+}
+
+impl NoteEvent {
+    /// Events at the same frame release notes before starting new ones, so a
+    /// note repeated straight after itself is played again
+    fn order(&self) -> u8 {
+        match self {
+            Self::ArrangementOff | Self::AllOff => 0,
+            Self::Off { .. } => 1,
+            Self::On { .. } => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Attack,
+    Decay,
+    Sustain,
+    /// Falling to silence by `step` each frame
+    Release {
+        step: f32,
+    },
+}
+
+/// One note playing its sample
+#[derive(Debug, Clone, Copy)]
+struct Voice {
+    note: u8,
+    /// Played from live MIDI input rather than the arrangement
+    live: bool,
+    velocity: f32,
+    /// Frame of the sample to play next, between frames while repitched
+    position: f64,
+    level: f32,
+    stage: Stage,
+}
+
+impl Voice {
+    fn release(&mut self, frames: f32) {
+        if !matches!(self.stage, Stage::Release { .. }) {
+            self.stage = Stage::Release {
+                step: self.level / frames.max(1.0),
+            };
+        }
+    }
+
+    fn is_held(&self) -> bool {
+        !matches!(self.stage, Stage::Release { .. })
+    }
+
+    fn is_finished(&self) -> bool {
+        matches!(self.stage, Stage::Release { .. }) && self.level <= 0.0
+    }
+
+    /// Move the envelope on a frame and return its level
+    fn next_level(&mut self, envelope: &EnvelopeSteps) -> f32 {
+        match self.stage {
+            Stage::Attack => {
+                self.level += envelope.attack;
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= envelope.decay;
+                if self.level <= envelope.sustain {
+                    self.level = envelope.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => self.level = envelope.sustain,
+            Stage::Release { step } => self.level = (self.level - step).max(0.0),
+        }
+        self.level
+    }
+}
+
+/// An [`Adsr`] as changes in level per frame
+struct EnvelopeSteps {
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release_frames: f32,
+}
+
+impl EnvelopeSteps {
+    fn new(adsr: &Adsr, sample_rate: u32) -> Self {
+        let frames = |seconds: f32| (seconds * sample_rate as f32).max(1.0);
+        let sustain = adsr.sustain.clamp(0.0, 1.0);
+        Self {
+            attack: 1.0 / frames(adsr.attack),
+            decay: (1.0 - sustain) / frames(adsr.decay),
+            sustain,
+            release_frames: frames(adsr.release),
+        }
+    }
+}
+
+/// The notes a track's sampler is playing, carried from one block to the next.
+///
+/// Voices only remember their note and how far through its sample they are; the
+/// sample itself is looked up in the track's [`Sampler`] each block, so none is
+/// ever dropped on the audio thread. Playing never allocates.
+#[derive(Debug, Clone)]
+pub struct SamplerVoices {
+    voices: Vec<Voice>,
+    events: Vec<(usize, NoteEvent)>,
+    /// Timeline sample the arrangement's notes were played up to, or `None` when
+    /// the last block wasn't played from the arrangement
+    next_position: Option<u64>,
+    /// Index of the first of the arrangement's notes that may still sound; every
+    /// note before it was released in an earlier block
+    cursor: usize,
+    /// Address and length of the notes `cursor` indexes, to notice new ones
+    cursor_notes: (usize, usize),
+}
+
+impl Default for SamplerVoices {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SamplerVoices {
+    pub fn new() -> Self {
+        Self {
+            // Stolen voices fade out alongside the ones that replaced them
+            voices: Vec::with_capacity(MAX_POLYPHONY * 2),
+            events: Vec::with_capacity(PREALLOCATED_EVENTS),
+            next_position: None,
+            cursor: 0,
+            cursor_notes: (0, 0),
+        }
+    }
+
+    /// Whether any note is still sounding
+    pub fn is_sounding(&self) -> bool {
+        !self.voices.is_empty()
+    }
+
+    /// Stop every note at once
+    pub fn silence(&mut self) {
+        self.voices.clear();
+        self.next_position = None;
+    }
+
+    /// Add the next `out.len() / channels` frames of `sampler` into `out`.
+    ///
+    /// With `notes`, the block covers timeline samples from `position` and the
+    /// notes starting and ending in it are played at their frames; a jump in
+    /// `position` since the last block releases the notes the arrangement was
+    /// holding. `live` notes all take effect at the block's start.
+    #[allow(clippy::too_many_arguments)]
+    pub fn play(
+        &mut self,
+        sampler: &Sampler,
+        notes: Option<&[SamplerNote]>,
+        live: &[LiveNote],
+        position: u64,
+        sample_rate: u32,
+        out: &mut [f32],
+        channels: usize,
+    ) {
+        let frames = out.len() / channels;
+        let block_end = position + frames as u64;
+        let jumped = self.next_position != Some(position);
+        self.events.clear();
+        if notes.is_none() || jumped {
+            self.events.push((0, NoteEvent::ArrangementOff));
+        }
+        for note in live {
+            let event = match *note {
+                LiveNote::On { note, velocity } => NoteEvent::On {
+                    note,
+                    velocity,
+                    live: true,
+                },
+                LiveNote::Off { note } => NoteEvent::Off { note, live: true },
+                LiveNote::AllOff => NoteEvent::AllOff,
+            };
+            self.push_event(0, event);
+        }
+        if let Some(notes) = notes {
+            let identity = (notes.as_ptr() as usize, notes.len());
+            if jumped || self.cursor_notes != identity {
+                self.cursor = 0;
+                self.cursor_notes = identity;
+            }
+            // Notes are in start order, so skip the ones released before this block
+            while notes
+                .get(self.cursor)
+                .is_some_and(|note| note.end < position)
+            {
+                self.cursor += 1;
+            }
+            let sounding = &notes[self.cursor..];
+            let upcoming = sounding.partition_point(|note| note.start < block_end);
+            for note in &sounding[..upcoming] {
+                if note.start >= position {
+                    let event = NoteEvent::On {
+                        note: note.pitch,
+                        velocity: note.velocity,
+                        live: false,
+                    };
+                    self.push_event((note.start - position) as usize, event);
+                }
+                if (position..block_end).contains(&note.end) {
+                    let event = NoteEvent::Off {
+                        note: note.pitch,
+                        live: false,
+                    };
+                    self.push_event((note.end - position) as usize, event);
+                }
+            }
+        }
+        self.next_position = notes.map(|_| block_end);
+        self.events
+            .sort_unstable_by_key(|(frame, event)| (*frame, event.order()));
+
+        let envelope = EnvelopeSteps::new(&sampler.envelope, sample_rate);
+        let polyphony = sampler.polyphony.clamp(1, MAX_POLYPHONY);
+        let mut frame = 0;
+        for i in 0..self.events.len() {
+            let (at, event) = self.events[i];
+            self.render(
+                sampler,
+                &envelope,
+                &mut out[frame * channels..at * channels],
+                channels,
+            );
+            frame = at;
+            self.apply(event, &envelope, polyphony);
+        }
+        self.render(sampler, &envelope, &mut out[frame * channels..], channels);
+    }
+
+    fn push_event(&mut self, frame: usize, event: NoteEvent) {
+        if self.events.len() < self.events.capacity() {
+            self.events.push((frame, event));
+        }
+    }
+
+    fn apply(&mut self, event: NoteEvent, envelope: &EnvelopeSteps, polyphony: usize) {
+        match event {
+            NoteEvent::ArrangementOff => {
+                for voice in self.voices.iter_mut().filter(|voice| !voice.live) {
+                    voice.release(envelope.release_frames);
+                }
+            }
+            NoteEvent::AllOff => {
+                for voice in &mut self.voices {
+                    voice.release(envelope.release_frames);
+                }
+            }
+            NoteEvent::Off { note, live } => {
+                for voice in self
+                    .voices
+                    .iter_mut()
+                    .filter(|voice| voice.note == note && voice.live == live)
+                {
+                    voice.release(envelope.release_frames);
+                }
+            }
+            NoteEvent::On {
+                note,
+                velocity,
+                live,
+            } => {
+                // The oldest held note makes way once the sampler is full
+                if self.voices.iter().filter(|voice| voice.is_held()).count() >= polyphony
+                    && let Some(oldest) = self.voices.iter_mut().find(|voice| voice.is_held())
+                {
+                    oldest.release(STEAL_FRAMES);
+                }
+                if self.voices.len() == self.voices.capacity() {
+                    self.voices.remove(0);
+                }
+                self.voices.push(Voice {
+                    note,
+                    live,
+                    velocity: velocity as f32 / 127.0,
+                    position: 0.0,
+                    level: 0.0,
+                    stage: Stage::Attack,
+                });
+            }
+        }
+    }
+
+    /// Add every voice into `out`, reading its sample with linear interpolation
+    fn render(
+        &mut self,
+        sampler: &Sampler,
+        envelope: &EnvelopeSteps,
+        out: &mut [f32],
+        channels: usize,
+    ) {
+        for voice in &mut self.voices {
+            let Some((audio, rate)) = sampler.sample_for(voice.note) else {
+                voice.level = 0.0;
+                voice.stage = Stage::Release { step: 0.0 };
+                continue;
+            };
+            let sample_channels = audio.channels() as usize;
+            let sample_frames = audio.frames();
+            let samples = audio.samples();
+            for frame in out.chunks_exact_mut(channels) {
+                let index = voice.position as usize;
+                if index >= sample_frames {
+                    // The sample ran out before the note did
+                    voice.level = 0.0;
+                    voice.stage = Stage::Release { step: 0.0 };
+                    break;
+                }
+                let next = (index + 1).min(sample_frames - 1);
+                let fraction = (voice.position - index as f64) as f32;
+                let gain = voice.next_level(envelope) * voice.velocity;
+                for (ch, sample) in frame.iter_mut().enumerate() {
+                    let ch = ch % sample_channels;
+                    let from = samples[index * sample_channels + ch];
+                    let to = samples[next * sample_channels + ch];
+                    *sample += (from + (to - from) * fraction) * gain;
+                }
+                voice.position += rate;
+            }
+        }
+        self.voices.retain(|voice| !voice.is_finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AudioArc;
+    use std::collections::BTreeMap;
+
+    /// Full level from the first frame, silent from the frame after release
+    fn instant() -> Adsr {
+        Adsr {
+            attack: 0.0,
+            decay: 0.0,
+            sustain: 1.0,
+            release: 0.0,
+        }
+    }
+
+    /// A sampler whose mapped notes each play samples of 1.0
+    fn sampler(notes: &[u8]) -> Sampler {
+        let samples = notes
+            .iter()
+            .map(|&note| (note, AudioArc::new(vec![1.0; 1000], 48000, 1)))
+            .collect::<BTreeMap<_, _>>();
+        let mut sampler = Sampler::per_note(samples);
+        sampler.envelope = instant();
+        sampler
+    }
+
+    fn note(start: u64, end: u64, pitch: u8) -> SamplerNote {
+        SamplerNote {
+            start,
+            end,
+            pitch,
+            velocity: 127,
+        }
+    }
+
+    #[test]
+    fn test_notes_play_from_their_start_to_their_end() {
+        let sampler = sampler(&[60]);
+        let notes = [note(2, 5, 60)];
+        let mut voices = SamplerVoices::new();
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, Some(&notes), &[], 0, 48000, &mut out, 1);
+        assert_eq!(out, [0.0, 0.0, 1.0, 1.0]);
+
+        // Released at frame 5, fading out over a frame
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, Some(&notes), &[], 4, 48000, &mut out, 1);
+        assert_eq!(out, [1.0, 0.0, 0.0, 0.0]);
+        assert!(!voices.is_sounding());
+    }
+
+    #[test]
+    fn test_unmapped_notes_are_silent() {
+        let sampler = sampler(&[36]);
+        let notes = [note(0, 4, 37)];
+        let mut voices = SamplerVoices::new();
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, Some(&notes), &[], 0, 48000, &mut out, 1);
+        assert_eq!(out, [0.0; 4]);
+    }
+
+    #[test]
+    fn test_pitched_notes_read_the_sample_faster() {
+        let ramp = (0..100).map(|i| i as f32).collect();
+        let mut sampler = Sampler::pitched(AudioArc::new(ramp, 48000, 1), 60);
+        sampler.envelope = instant();
+        let notes = [note(0, 100, 72)];
+        let mut voices = SamplerVoices::new();
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, Some(&notes), &[], 0, 48000, &mut out, 1);
+        assert_eq!(out, [0.0, 2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn test_envelope_attacks_decays_and_releases() {
+        let mut sampler = sampler(&[60]);
+        // At two frames a second, each stage takes two frames
+        sampler.envelope = Adsr {
+            attack: 1.0,
+            decay: 1.0,
+            sustain: 0.5,
+            release: 1.0,
+        };
+        let notes = [note(0, 6, 60)];
+        let mut voices = SamplerVoices::new();
+        let mut out = vec![0.0; 10];
+        voices.play(&sampler, Some(&notes), &[], 0, 2, &mut out, 1);
+        assert_eq!(out, [0.5, 1.0, 0.75, 0.5, 0.5, 0.5, 0.25, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_polyphony_cuts_off_the_oldest_note() {
+        let mut sampler = sampler(&[60, 62, 64]);
+        sampler.polyphony = 2;
+        let notes = [note(0, 1000, 60), note(0, 1000, 62), note(1, 1000, 64)];
+        let mut voices = SamplerVoices::new();
+        let mut out = vec![0.0; 200];
+        voices.play(&sampler, Some(&notes), &[], 0, 48000, &mut out, 1);
+        assert_eq!(out[0], 2.0);
+        // The first note fades out while the third comes in
+        assert!(out[1] > 2.0 && out[1] < 3.0);
+        assert_eq!(out[199], 2.0);
+    }
+
+    #[test]
+    fn test_live_notes_outlast_a_jump_in_the_arrangement() {
+        let sampler = sampler(&[60, 62]);
+        let notes = [note(0, 1000, 62)];
+        let live = [LiveNote::On {
+            note: 60,
+            velocity: 127,
+        }];
+        let mut voices = SamplerVoices::new();
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, Some(&notes), &live, 0, 48000, &mut out, 1);
+        assert_eq!(out, [2.0; 4]);
+
+        // Looping back releases the arrangement's note but not the live one
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, Some(&notes), &[], 500, 48000, &mut out, 1);
+        assert_eq!(out, [1.0; 4]);
+
+        let live = [LiveNote::Off { note: 60 }];
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, None, &live, 0, 48000, &mut out, 1);
+        assert_eq!(out, [0.0; 4]);
+        assert!(!voices.is_sounding());
+    }
+
+    #[test]
+    fn test_released_notes_are_passed_over() {
+        let sampler = sampler(&[60]);
+        let notes = [note(0, 2, 60), note(4, 6, 60), note(9, 100, 60)];
+        let mut voices = SamplerVoices::new();
+        for position in [0, 4, 8] {
+            let mut out = vec![0.0; 4];
+            voices.play(&sampler, Some(&notes), &[], position, 48000, &mut out, 1);
+        }
+        assert_eq!(voices.cursor, 2);
+
+        // Jumping back starts over from the first note
+        let mut out = vec![0.0; 4];
+        voices.play(&sampler, Some(&notes), &[], 0, 48000, &mut out, 1);
+        assert_eq!(out, [1.0, 1.0, 0.0, 0.0]);
+        assert_eq!(voices.cursor, 0);
+    }
+}
@@ -0,0 +1,197 @@
+//! Information about the material a clip's audio was imported from.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+const PITCH_CLASS_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// Tempos outside this range in a file name are more likely something else
+const BPM_RANGE: std::ops::RangeInclusive<f64> = 20.0..=400.0;
+
+/// A major or minor key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MusicalKey {
+    /// Pitch class of the tonic (0 = C, 11 = B)
+    pub tonic: u8,
+    pub minor: bool,
+}
+
+impl MusicalKey {
+    /// Parse a key name like `"C"`, `"F#m"`, `"Bbmin"` or `"Ebmajor"`.
+    ///
+    /// A bare note letter with no accidental or quality isn't accepted, since in a
+    /// file name it's rarely meant as a key.
+    pub fn parse(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        let letter = chars.next()?;
+        let natural = match letter {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return None,
+        };
+
+        let rest = chars.as_str();
+        if rest.is_empty() {
+            return None;
+        }
+        let (shift, quality) = if let Some(quality) = rest.strip_prefix('#') {
+            (1, quality)
+        } else if let Some(quality) = rest.strip_prefix('b') {
+            (11, quality)
+        } else {
+            (0, rest)
+        };
+
+        let minor = match quality.to_ascii_lowercase().as_str() {
+            "" | "maj" | "major" => false,
+            "m" | "min" | "minor" => true,
+            _ => return None,
+        };
+
+        Some(Self {
+            tonic: (natural + shift) % 12,
+            minor,
+        })
+    }
+}
+
+impl fmt::Display for MusicalKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = PITCH_CLASS_NAMES[self.tonic as usize % 12];
+        if self.minor {
+            write!(f, "{}m", name)
+        } else {
+            write!(f, "{}", name)
+        }
+    }
+}
+
+/// Where a clip's audio came from, recorded when it's imported.
+///
+/// Every field is optional: clips from older projects, recordings and generated
+/// material may know none of them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClipSource {
+    /// File the audio was imported from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Tempo of the source material in BPM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bpm: Option<f64>,
+    /// Key of the source material
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<MusicalKey>,
+    /// Sample rate of the file before any resampling
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<u32>,
+}
+
+impl ClipSource {
+    /// Source info for audio imported from `path`, with tempo and key detected
+    /// from the file name.
+    ///
+    /// Sample libraries usually tag loops in their names, so `bass_124bpm_F#m.wav`
+    /// gives a tempo of 124 and a key of F# minor.
+    pub fn from_file(path: &Path, sample_rate: u32) -> Self {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let tokens: Vec<&str> = stem
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '#')
+            .filter(|token| !token.is_empty())
+            .collect();
+
+        Self {
+            path: Some(path.to_path_buf()),
+            bpm: bpm_from_tokens(&tokens),
+            key: key_from_tokens(&tokens),
+            sample_rate: Some(sample_rate),
+        }
+    }
+
+    /// Whether nothing is known about the source
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A number followed by "bpm", either in the same token or the next one
+fn bpm_from_tokens(tokens: &[&str]) -> Option<f64> {
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        let lower = token.to_ascii_lowercase();
+        let number = match lower.strip_suffix("bpm") {
+            Some(number) if !number.is_empty() => number,
+            _ if tokens
+                .get(i + 1)
+                .is_some_and(|next| next.eq_ignore_ascii_case("bpm")) =>
+            {
+                lower.as_str()
+            }
+            _ => return None,
+        };
+        number
+            .parse::<f64>()
+            .ok()
+            .filter(|bpm| BPM_RANGE.contains(bpm))
+    })
+}
+
+/// A key name in one token, or a note letter followed by "major" or "minor"
+fn key_from_tokens(tokens: &[&str]) -> Option<MusicalKey> {
+    tokens.iter().enumerate().find_map(|(i, token)| {
+        MusicalKey::parse(token).or_else(|| {
+            let quality = tokens.get(i + 1)?;
+            if token.len() > 2
+                || !matches!(quality.to_ascii_lowercase().as_str(), "major" | "minor")
+            {
+                return None;
+            }
+            MusicalKey::parse(&format!("{}{}", token, quality))
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(tonic: u8, minor: bool) -> Option<MusicalKey> {
+        Some(MusicalKey { tonic, minor })
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(MusicalKey::parse("F#m"), key(6, true));
+        assert_eq!(MusicalKey::parse("Bbmin"), key(10, true));
+        assert_eq!(MusicalKey::parse("Ebmajor"), key(3, false));
+        assert_eq!(MusicalKey::parse("Cmaj"), key(0, false));
+        assert_eq!(MusicalKey::parse("C"), None);
+        assert_eq!(MusicalKey::parse("Cx"), None);
+        assert_eq!(key(1, true).unwrap().to_string(), "C#m");
+    }
+
+    #[test]
+    fn test_detect_from_file_name() {
+        let source = ClipSource::from_file(Path::new("loops/bass_124bpm_F#m.wav"), 44100);
+        assert_eq!(source.bpm, Some(124.0));
+        assert_eq!(source.key, key(6, true));
+        assert_eq!(source.sample_rate, Some(44100));
+
+        let source = ClipSource::from_file(Path::new("Pad - A minor - 90 BPM.wav"), 48000);
+        assert_eq!(source.bpm, Some(90.0));
+        assert_eq!(source.key, key(9, true));
+
+        // A one-shot names neither
+        let source = ClipSource::from_file(Path::new("cr78/Kick A.wav"), 44100);
+        assert_eq!((source.bpm, source.key), (None, None));
+    }
+}
@@ -0,0 +1,217 @@
+//! Converting between ticks and time when the tempo changes along the arrangement.
+//!
+//! Ticks are musical time, so where a tick lands in seconds depends on every tempo
+//! before it. Playback and renders both convert through a [`TempoCurve`], so a
+//! clip starts on the same sample whichever one is playing it.
+
+use crate::PPQN;
+
+/// Seconds one tick lasts at `bpm`
+fn seconds_per_tick(bpm: f64) -> f64 {
+    let seconds_per_beat = 60.0 / bpm;
+    seconds_per_beat / PPQN as f64
+}
+
+/// The tempo over the whole arrangement: a starting tempo and the changes after it.
+///
+/// A curve with no changes does the same arithmetic as a single tempo always has,
+/// so projects without tempo changes convert exactly as before.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoCurve {
+    bpm: f64,
+    changes: Vec<TempoSegment>,
+}
+
+/// A stretch of the arrangement at one tempo, starting at `tick`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TempoSegment {
+    tick: u64,
+    bpm: f64,
+    /// Time from the start of the arrangement to `tick`
+    seconds: f64,
+}
+
+impl TempoCurve {
+    /// A tempo that never changes
+    pub fn constant(bpm: f64) -> Self {
+        Self {
+            bpm,
+            changes: Vec::new(),
+        }
+    }
+
+    /// `bpm` from the start, then each `(tick, bpm)` change from its tick on.
+    ///
+    /// Changes can come in any order; ones that aren't a positive tempo are
+    /// skipped, and of two at the same tick the later one wins.
+    pub fn new(bpm: f64, changes: impl IntoIterator<Item = (u64, f64)>) -> Self {
+        let mut changes: Vec<(u64, f64)> = changes
+            .into_iter()
+            .filter(|(_, bpm)| bpm.is_finite() && *bpm > 0.0)
+            .collect();
+        changes.sort_by_key(|(tick, _)| *tick);
+
+        let mut curve = Self::constant(bpm);
+        for (tick, bpm) in changes {
+            let seconds = curve.ticks_to_seconds(tick as f64);
+            curve.changes.push(TempoSegment { tick, bpm, seconds });
+        }
+        curve
+    }
+
+    /// Whether the tempo stays the same throughout
+    pub fn is_constant(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Tempo in effect at `tick`
+    pub fn bpm_at(&self, tick: u64) -> f64 {
+        self.segment_at_tick(tick as f64).bpm
+    }
+
+    pub fn ticks_to_seconds(&self, ticks: f64) -> f64 {
+        let segment = self.segment_at_tick(ticks);
+        segment.seconds + (ticks - segment.tick as f64) * seconds_per_tick(segment.bpm)
+    }
+
+    pub fn seconds_to_ticks(&self, seconds: f64) -> f64 {
+        let segment = self
+            .changes
+            .iter()
+            .rev()
+            .find(|segment| segment.seconds <= seconds)
+            .copied()
+            .unwrap_or(self.first_segment());
+        segment.tick as f64 + (seconds - segment.seconds) / seconds_per_tick(segment.bpm)
+    }
+
+    pub fn ticks_to_samples(&self, ticks: f64, sample_rate: u32) -> f64 {
+        self.ticks_to_seconds(ticks) * sample_rate as f64
+    }
+
+    pub fn samples_to_ticks(&self, samples: f64, sample_rate: u32) -> f64 {
+        self.seconds_to_ticks(samples / sample_rate as f64)
+    }
+
+    /// Samples from `start` to `start + ticks`, for lengths like a clip's duration
+    /// or a fade.
+    ///
+    /// Inside one tempo this is the length at that tempo, the same wherever the span
+    /// starts; across a change it's the distance between the two positions.
+    pub fn span_to_samples(&self, start: u64, ticks: u64, sample_rate: u32) -> f64 {
+        let end = start.saturating_add(ticks);
+        let segment = self.segment_at_tick(start as f64);
+        if end <= self.next_change_after(start) {
+            ticks as f64 * seconds_per_tick(segment.bpm) * sample_rate as f64
+        } else {
+            self.ticks_to_samples(end as f64, sample_rate)
+                - self.ticks_to_samples(start as f64, sample_rate)
+        }
+    }
+
+    /// Ticks from `start` that last `samples`, the inverse of
+    /// [`span_to_samples`](Self::span_to_samples), for fitting a clip to its audio
+    pub fn samples_to_span(&self, start: u64, samples: f64, sample_rate: u32) -> f64 {
+        let segment = self.segment_at_tick(start as f64);
+        let ticks = samples / sample_rate as f64 / seconds_per_tick(segment.bpm);
+        if start as f64 + ticks <= self.next_change_after(start) as f64 {
+            ticks
+        } else {
+            let end = self.ticks_to_samples(start as f64, sample_rate) + samples;
+            self.samples_to_ticks(end, sample_rate) - start as f64
+        }
+    }
+
+    /// Tick of the first change after `tick`, or `u64::MAX` if there is none
+    fn next_change_after(&self, tick: u64) -> u64 {
+        self.changes
+            .iter()
+            .find(|change| change.tick > tick)
+            .map_or(u64::MAX, |change| change.tick)
+    }
+
+    fn first_segment(&self) -> TempoSegment {
+        TempoSegment {
+            tick: 0,
+            bpm: self.bpm,
+            seconds: 0.0,
+        }
+    }
+
+    fn segment_at_tick(&self, ticks: f64) -> TempoSegment {
+        self.changes
+            .iter()
+            .rev()
+            .find(|segment| segment.tick as f64 <= ticks)
+            .copied()
+            .unwrap_or(self.first_segment())
+    }
+}
+
+impl From<f64> for TempoCurve {
+    fn from(bpm: f64) -> Self {
+        Self::constant(bpm)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constant_tempo_matches_single_tempo_math() {
+        let curve = TempoCurve::constant(120.0);
+        // Half a second a beat
+        assert_eq!(curve.ticks_to_seconds(PPQN as f64), 0.5);
+        assert_eq!(curve.ticks_to_samples(PPQN as f64 * 4.0, 48000), 96000.0);
+        assert_eq!(curve.samples_to_ticks(96000.0, 48000), PPQN as f64 * 4.0);
+        assert_eq!(curve.span_to_samples(12345, PPQN, 48000), 24000.0);
+        assert_eq!(curve.samples_to_span(12345, 24000.0, 48000), PPQN as f64);
+    }
+
+    #[test]
+    fn test_tempo_changes_are_walked() {
+        // A bar at 120, then 60 from the second bar
+        let bar = PPQN * 4;
+        let curve = TempoCurve::new(120.0, [(bar, 60.0)]);
+        assert_eq!(curve.bpm_at(bar - 1), 120.0);
+        assert_eq!(curve.bpm_at(bar), 60.0);
+        assert_eq!(curve.ticks_to_seconds(bar as f64), 2.0);
+        assert_eq!(curve.ticks_to_seconds(bar as f64 * 2.0), 6.0);
+        assert_eq!(curve.seconds_to_ticks(6.0), bar as f64 * 2.0);
+        assert_eq!(curve.seconds_to_ticks(1.0), bar as f64 / 2.0);
+
+        // A span over the change takes the time it covers on each side
+        assert_eq!(curve.span_to_samples(bar - PPQN, PPQN * 2, 1000), 1500.0);
+        assert_eq!(curve.span_to_samples(bar, PPQN, 1000), 1000.0);
+        // Ending right on the change stays at the earlier tempo
+        assert_eq!(curve.span_to_samples(0, bar, 1000), 2000.0);
+
+        // Samples back to ticks take the same tempos
+        assert_eq!(
+            curve.samples_to_span(bar - PPQN, 1500.0, 1000),
+            PPQN as f64 * 2.0
+        );
+        assert_eq!(curve.samples_to_span(bar, 1000.0, 1000), PPQN as f64);
+        assert_eq!(curve.samples_to_span(0, 2000.0, 1000), bar as f64);
+    }
+
+    #[test]
+    fn test_changes_are_sorted_and_bad_tempos_skipped() {
+        let curve = TempoCurve::new(
+            120.0,
+            [
+                (PPQN * 8, 240.0),
+                (PPQN * 4, 60.0),
+                (PPQN * 2, 0.0),
+                (0, f64::NAN),
+            ],
+        );
+        assert_eq!(
+            curve,
+            TempoCurve::new(120.0, [(PPQN * 4, 60.0), (PPQN * 8, 240.0)])
+        );
+        assert_eq!(curve.ticks_to_seconds(PPQN as f64 * 9.0), 2.0 + 4.0 + 0.25);
+        assert!(TempoCurve::new(120.0, []).is_constant());
+    }
+}